@@ -5,12 +5,15 @@
 // set and halt the node if a mismatch is detected.
 
 use {
-    crossbeam_channel::{Receiver, Sender},
+    crossbeam_channel::{Receiver, Sender, TrySendError},
     solana_gossip::cluster_info::{ClusterInfo, MAX_SNAPSHOT_HASHES},
     solana_measure::{measure, measure::Measure},
     solana_runtime::{
-        accounts_hash::{CalcAccountsHashConfig, HashStats},
-        epoch_accounts_hash::EpochAccountsHash,
+        accounts::Accounts,
+        accounts_db::{SnapshotStorages, PUBKEY_BINS_FOR_CALCULATING_HASHES},
+        accounts_hash::{CalcAccountsHashConfig, CalcDeadline, DeadlineOverrunAction, HashStats},
+        epoch_accounts_hash::{self, EpochAccountsHash, KeyspaceShard, ShardAccountsHash},
+        rent_collector::RentCollector,
         snapshot_config::SnapshotConfig,
         snapshot_package::{
             self, retain_max_n_elements, AccountsPackage, AccountsPackageType,
@@ -19,7 +22,8 @@ use {
         sorted_storages::SortedStorages,
     },
     solana_sdk::{
-        clock::{Slot, SLOT_MS},
+        clock::{Epoch, Slot, SLOT_MS},
+        epoch_schedule::EpochSchedule,
         hash::Hash,
         pubkey::Pubkey,
     },
@@ -34,11 +38,34 @@ use {
     },
 };
 
+/// Called with the epoch and the epoch accounts hash once an EAH calculation has completed
+///
+/// The callback runs on its own thread, so it does not hold up the accounts hash verifier.
+pub type OnEpochAccountsHashComplete = Arc<dyn Fn(Epoch, &EpochAccountsHash) + Send + Sync>;
+
+/// Called with the slot and the accounts hash after each accounts package has been handled
+///
+/// The callback runs on the accounts hash verifier's thread, after the accounts hash has been
+/// calculated and no locks are held.  The next accounts package is not handled until the callback
+/// returns, so a slow callback slows down snapshots.
+pub type OnAccountsHashComplete = Box<dyn Fn(Slot, Hash) + Send + Sync>;
+
+/// Optional callbacks and behavior of the `AccountsHashVerifier`
+#[derive(Default)]
+pub struct AccountsHashVerifierConfig {
+    pub on_eah_complete: Option<OnEpochAccountsHashComplete>,
+    pub on_accounts_hash_complete: Option<OnAccountsHashComplete>,
+    /// if set, verify the epoch accounts hash incrementally within its window, in this many
+    /// shards, see `IncrementalEpochAccountsHashVerifier`
+    pub epoch_accounts_hash_verification_shards: Option<usize>,
+}
+
 pub struct AccountsHashVerifier {
     t_accounts_hash_verifier: JoinHandle<()>,
 }
 
 impl AccountsHashVerifier {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         accounts_package_sender: Sender<AccountsPackage>,
         accounts_package_receiver: Receiver<AccountsPackage>,
@@ -49,6 +76,7 @@ impl AccountsHashVerifier {
         halt_on_known_validators_accounts_hash_mismatch: bool,
         fault_injection_rate_slots: u64,
         snapshot_config: Option<SnapshotConfig>,
+        config: AccountsHashVerifierConfig,
     ) -> Self {
         // If there are no accounts packages to process, limit how often we re-check
         const LOOP_LIMITER: Duration = Duration::from_millis(SLOT_MS);
@@ -58,6 +86,13 @@ impl AccountsHashVerifier {
             .name("solAcctHashVer".to_string())
             .spawn(move || {
                 let mut hashes = vec![];
+                let AccountsHashVerifierConfig {
+                    on_eah_complete,
+                    on_accounts_hash_complete,
+                    epoch_accounts_hash_verification_shards,
+                } = config;
+                let incremental_eah_verifier = epoch_accounts_hash_verification_shards
+                    .map(IncrementalEpochAccountsHashVerifierService::new);
                 loop {
                     if exit.load(Ordering::Relaxed) {
                         break;
@@ -84,6 +119,9 @@ impl AccountsHashVerifier {
                             &exit,
                             fault_injection_rate_slots,
                             snapshot_config.as_ref(),
+                            on_eah_complete.as_ref(),
+                            on_accounts_hash_complete.as_ref(),
+                            incremental_eah_verifier.as_ref(),
                         ));
 
                         datapoint_info!(
@@ -105,6 +143,9 @@ impl AccountsHashVerifier {
                         std::thread::sleep(LOOP_LIMITER);
                     }
                 }
+                if let Some(incremental_eah_verifier) = incremental_eah_verifier {
+                    incremental_eah_verifier.join().unwrap();
+                }
             })
             .unwrap();
         Self {
@@ -185,10 +226,37 @@ impl AccountsHashVerifier {
         exit: &Arc<AtomicBool>,
         fault_injection_rate_slots: u64,
         snapshot_config: Option<&SnapshotConfig>,
+        on_eah_complete: Option<&OnEpochAccountsHashComplete>,
+        on_accounts_hash_complete: Option<&OnAccountsHashComplete>,
+        incremental_eah_verifier: Option<&IncrementalEpochAccountsHashVerifierService>,
     ) {
-        let accounts_hash = Self::calculate_and_verify_accounts_hash(&accounts_package);
+        let (accounts_hash, calculation_us) = {
+            // If configured, do not calculate the EAH while a snapshot archive is being created
+            let _epoch_accounts_hash_calculation_guard = snapshot_config
+                .and_then(|snapshot_config| {
+                    snapshot_config
+                        .epoch_accounts_hash_archive_coordinator
+                        .as_ref()
+                })
+                .filter(|_| accounts_package.package_type == AccountsPackageType::EpochAccountsHash)
+                .map(|coordinator| coordinator.begin_epoch_accounts_hash_calculation());
+            let (accounts_hash, measure_calculation) = measure!(
+                Self::calculate_and_verify_accounts_hash(&accounts_package),
+                "calculate_accounts_hash"
+            );
+            (accounts_hash, measure_calculation.as_us())
+        };
+
+        Self::save_epoch_accounts_hash(
+            &accounts_package,
+            accounts_hash,
+            calculation_us,
+            on_eah_complete,
+        );
 
-        Self::save_epoch_accounts_hash(&accounts_package, accounts_hash);
+        if let Some(incremental_eah_verifier) = incremental_eah_verifier {
+            incremental_eah_verifier.send(&accounts_package, accounts_hash);
+        }
 
         Self::push_accounts_hashes_to_cluster(
             &accounts_package,
@@ -201,12 +269,18 @@ impl AccountsHashVerifier {
             accounts_hash,
         );
 
+        let slot = accounts_package.slot;
         Self::submit_for_packaging(
             accounts_package,
             pending_snapshot_package,
             snapshot_config,
             accounts_hash,
+            exit,
         );
+
+        if let Some(on_accounts_hash_complete) = on_accounts_hash_complete {
+            on_accounts_hash_complete(slot, accounts_hash);
+        }
     }
 
     /// returns calculated accounts hash
@@ -234,6 +308,14 @@ impl AccountsHashVerifier {
                     rent_collector: &accounts_package.rent_collector,
                     store_detailed_debug_info_on_failure: false,
                     full_snapshot: None,
+                    // every node must calculate the EAH, so an overrun is only reported
+                    deadline: accounts_package
+                        .epoch_accounts_hash_deadline
+                        .map(|instant| CalcDeadline {
+                            instant,
+                            on_overrun: DeadlineOverrunAction::Report,
+                        }),
+                    ..CalcAccountsHashConfig::default()
                 },
                 &sorted_storages,
                 timings,
@@ -256,6 +338,7 @@ impl AccountsHashVerifier {
                         rent_collector: &accounts_package.rent_collector,
                         store_detailed_debug_info_on_failure: false,
                         full_snapshot: None,
+                        ..CalcAccountsHashConfig::default()
                     },
                 );
             info!(
@@ -275,6 +358,7 @@ impl AccountsHashVerifier {
                         // now that we've failed, store off the failing contents that produced a bad capitalization
                         store_detailed_debug_info_on_failure: true,
                         full_snapshot: None,
+                        ..CalcAccountsHashConfig::default()
                     },
                     &sorted_storages,
                     HashStats::default(),
@@ -296,6 +380,14 @@ impl AccountsHashVerifier {
                 sorted_storages.max_slot_inclusive(),
                 &accounts_package.epoch_schedule,
             );
+        if accounts_package.package_type
+            == AccountsPackageType::Snapshot(SnapshotType::FullSnapshot)
+        {
+            accounts_package
+                .accounts
+                .accounts_db
+                .set_last_full_snapshot_accounts_hash(accounts_package.slot, accounts_hash);
+        }
 
         measure_hash.stop();
         solana_runtime::serde_snapshot::reserialize_bank_with_new_accounts_hash(
@@ -312,7 +404,14 @@ impl AccountsHashVerifier {
         accounts_hash
     }
 
-    fn save_epoch_accounts_hash(accounts_package: &AccountsPackage, accounts_hash: Hash) {
+    /// `calculation_us` is how long calculating the EAH took, from when it began (after waiting
+    /// for any snapshot archive being created) until the EAH was calculated
+    fn save_epoch_accounts_hash(
+        accounts_package: &AccountsPackage,
+        accounts_hash: Hash,
+        calculation_us: u64,
+        on_eah_complete: Option<&OnEpochAccountsHashComplete>,
+    ) {
         if accounts_package.package_type == AccountsPackageType::EpochAccountsHash {
             info!(
                 "saving epoch accounts hash, slot: {}, hash: {}",
@@ -324,6 +423,24 @@ impl AccountsHashVerifier {
                 .accounts_db
                 .epoch_accounts_hash_manager
                 .set_valid(epoch_accounts_hash, accounts_package.slot);
+            datapoint_info!(
+                "epoch_accounts_hash",
+                ("slot", accounts_package.slot as i64, i64),
+                ("calculation_us", calculation_us as i64, i64),
+            );
+
+            if let Some(on_eah_complete) = on_eah_complete {
+                let on_eah_complete = Arc::clone(on_eah_complete);
+                let epoch = accounts_package
+                    .epoch_schedule
+                    .get_epoch(accounts_package.slot);
+                if let Err(err) = Builder::new()
+                    .name("solEahComplete".to_string())
+                    .spawn(move || on_eah_complete(epoch, &epoch_accounts_hash))
+                {
+                    warn!("failed to spawn the epoch accounts hash complete callback: {err}");
+                }
+            }
         }
     }
 
@@ -378,6 +495,7 @@ impl AccountsHashVerifier {
         pending_snapshot_package: Option<&PendingSnapshotPackage>,
         snapshot_config: Option<&SnapshotConfig>,
         accounts_hash: Hash,
+        exit: &AtomicBool,
     ) {
         if pending_snapshot_package.is_none()
             || !snapshot_config
@@ -391,24 +509,20 @@ impl AccountsHashVerifier {
             return;
         }
 
+        let slot = accounts_package.slot;
         let snapshot_package = SnapshotPackage::new(accounts_package, accounts_hash);
         let pending_snapshot_package = pending_snapshot_package.unwrap();
-
-        // If the snapshot package is an Incremental Snapshot, do not submit it if there's already
-        // a pending Full Snapshot.
-        let can_submit = match snapshot_package.snapshot_type {
-            SnapshotType::FullSnapshot => true,
-            SnapshotType::IncrementalSnapshot(_) => pending_snapshot_package
-                .lock()
-                .unwrap()
-                .as_ref()
-                .map_or(true, |snapshot_package| {
-                    snapshot_package.snapshot_type.is_incremental_snapshot()
-                }),
-        };
-
-        if can_submit {
-            *pending_snapshot_package.lock().unwrap() = Some(snapshot_package);
+        let policy = snapshot_config.unwrap().pending_package_policy;
+        if !snapshot_package::submit_snapshot_package(
+            pending_snapshot_package,
+            snapshot_package,
+            policy,
+            exit,
+        ) {
+            info!(
+                "Dropped snapshot package for slot {} due to pending package policy {:?}",
+                slot, policy
+            );
         }
     }
 
@@ -463,6 +577,241 @@ impl AccountsHashVerifier {
     }
 }
 
+/// Runs an `IncrementalEpochAccountsHashVerifier` on its own thread
+///
+/// Recalculating a shard scans the storages as of the EAH "start" slot, so it is kept off the
+/// accounts hash verifier's thread.  If a shard is still being recalculated when an accounts
+/// package is handled, that accounts package is skipped, and the next shard waits for a later one.
+struct IncrementalEpochAccountsHashVerifierService {
+    request_sender: Sender<IncrementalVerificationRequest>,
+    t_incremental_eah_verifier: JoinHandle<()>,
+}
+
+impl IncrementalEpochAccountsHashVerifierService {
+    fn new(num_shards: usize) -> Self {
+        let mut incremental_eah_verifier = IncrementalEpochAccountsHashVerifier::new(num_shards);
+        let (request_sender, request_receiver) = crossbeam_channel::bounded(1);
+        let t_incremental_eah_verifier = Builder::new()
+            .name("solEahShardVer".to_string())
+            .spawn(move || {
+                for request in request_receiver.iter() {
+                    incremental_eah_verifier.handle_request(request);
+                }
+            })
+            .unwrap();
+        Self {
+            request_sender,
+            t_incremental_eah_verifier,
+        }
+    }
+
+    /// Send the request for `accounts_package` to the verifier thread
+    ///
+    /// Only waits for the verifier thread to catch up when beginning to verify a new EAH.
+    fn send(&self, accounts_package: &AccountsPackage, accounts_hash: Hash) {
+        let request = IncrementalVerificationRequest::new(accounts_package, accounts_hash);
+        if matches!(request, IncrementalVerificationRequest::Begin(_)) {
+            self.request_sender
+                .send(request)
+                .expect("incremental epoch accounts hash verifier thread is alive");
+        } else if let Err(TrySendError::Full(_)) = self.request_sender.try_send(request) {
+            debug!(
+                "skipped incremental epoch accounts hash verification at slot {}, still \
+                 verifying the previous shard",
+                accounts_package.slot,
+            );
+        }
+    }
+
+    /// Wait for the verifier thread to finish its last request
+    fn join(self) -> thread::Result<()> {
+        drop(self.request_sender);
+        self.t_incremental_eah_verifier.join()
+    }
+}
+
+/// What the incremental EAH verifier needs from each accounts package
+enum IncrementalVerificationRequest {
+    /// Begin verifying the EAH, from the EAH accounts package
+    Begin(Box<InProgressEpochAccountsHashVerification>),
+    /// Verify the next shard(s), from any other accounts package
+    Continue { slot: Slot },
+}
+
+impl IncrementalVerificationRequest {
+    fn new(accounts_package: &AccountsPackage, accounts_hash: Hash) -> Self {
+        if accounts_package.package_type != AccountsPackageType::EpochAccountsHash {
+            return Self::Continue {
+                slot: accounts_package.slot,
+            };
+        }
+        Self::Begin(Box::new(InProgressEpochAccountsHashVerification {
+            slot: accounts_package.slot,
+            stop_slot: epoch_accounts_hash::calculation_info_for_slot(
+                &accounts_package.epoch_schedule,
+                accounts_package.slot,
+            )
+            .calculation_stop,
+            epoch_accounts_hash: EpochAccountsHash::new(accounts_hash),
+            accounts: Arc::clone(&accounts_package.accounts),
+            snapshot_storages: accounts_package.snapshot_storages.clone(),
+            epoch_schedule: accounts_package.epoch_schedule,
+            rent_collector: accounts_package.rent_collector.clone(),
+            shards: Vec::new(),
+        }))
+    }
+}
+
+/// Verifies the epoch accounts hash incrementally, one keyspace shard at a time
+///
+/// Once the EAH has been calculated, each accounts package handled for a slot within the EAH
+/// calculation window recalculates one more shard of the EAH, from the snapshot storages as of the
+/// "start" slot.  When all the shards are done, they are combined and compared to the EAH.  This
+/// spreads the verification over the window, so a divergence is detected before the "stop" slot,
+/// instead of all at once when the EAH is used.  If the "stop" slot is reached first, the
+/// remaining shards are verified all at once.
+///
+/// Like the EAH calculation itself, the verification holds the storages as of the "start" slot, so
+/// every shard is recalculated from the same storages even if shrink or clean replaces them.  They
+/// are released once all the shards are verified.
+struct IncrementalEpochAccountsHashVerifier {
+    num_shards: usize,
+    /// The verification in progress, from the EAH accounts package until all shards are verified
+    in_progress: Option<InProgressEpochAccountsHashVerification>,
+}
+
+/// The state kept for a single EAH while verifying it, see `IncrementalEpochAccountsHashVerifier`
+struct InProgressEpochAccountsHashVerification {
+    /// The slot of the EAH, i.e. the "start" slot
+    slot: Slot,
+    stop_slot: Slot,
+    epoch_accounts_hash: EpochAccountsHash,
+    accounts: Arc<Accounts>,
+    /// The storages as of the "start" slot, to recalculate the shards from
+    snapshot_storages: SnapshotStorages,
+    epoch_schedule: EpochSchedule,
+    rent_collector: RentCollector,
+    /// The shards verified so far, in order
+    shards: Vec<ShardAccountsHash>,
+}
+
+impl IncrementalEpochAccountsHashVerifier {
+    /// Panics if `num_shards` is not a power of two, or is larger than
+    /// `PUBKEY_BINS_FOR_CALCULATING_HASHES`, see `calculate_shard_from_storages()`
+    #[must_use]
+    fn new(num_shards: usize) -> Self {
+        assert!(
+            num_shards.is_power_of_two() && num_shards <= PUBKEY_BINS_FOR_CALCULATING_HASHES,
+            "the number of shards must be a power of two, at most \
+             {PUBKEY_BINS_FOR_CALCULATING_HASHES}: {num_shards}"
+        );
+        Self {
+            num_shards,
+            in_progress: None,
+        }
+    }
+
+    /// Verify the next shard(s) of the EAH, if the request is for a slot within the EAH
+    /// calculation window; or, if the request is for the EAH itself, begin verifying it
+    ///
+    /// Returns None while there are shards left to verify, otherwise whether the recalculated EAH
+    /// matches.  Failing to recalculate a shard also ends
+    /// the verification, as not matching.
+    fn handle_request(&mut self, request: IncrementalVerificationRequest) -> Option<bool> {
+        let slot = match request {
+            IncrementalVerificationRequest::Begin(in_progress) => {
+                if let Some(in_progress) = &self.in_progress {
+                    warn!(
+                        "incremental verification of the epoch accounts hash from slot {} did not \
+                         complete, verified {} of {} shards",
+                        in_progress.slot,
+                        in_progress.shards.len(),
+                        self.num_shards,
+                    );
+                }
+                self.in_progress = Some(*in_progress);
+                return None;
+            }
+            IncrementalVerificationRequest::Continue { slot } => slot,
+        };
+
+        let num_shards = self.num_shards;
+        let in_progress = self.in_progress.as_mut()?;
+        if slot <= in_progress.slot {
+            return None;
+        }
+        let num_shards_to_verify = if slot < in_progress.stop_slot {
+            1
+        } else {
+            num_shards - in_progress.shards.len()
+        };
+
+        let sorted_storages = SortedStorages::new(&in_progress.snapshot_storages);
+        let config = CalcAccountsHashConfig {
+            use_bg_thread_pool: true,
+            check_hash: false,
+            ancestors: None,
+            epoch_schedule: &in_progress.epoch_schedule,
+            rent_collector: &in_progress.rent_collector,
+            store_detailed_debug_info_on_failure: false,
+            full_snapshot: None,
+            ..CalcAccountsHashConfig::default()
+        };
+        let shards_to_verify = KeyspaceShard::all(num_shards)
+            .skip(in_progress.shards.len())
+            .take(num_shards_to_verify);
+        for shard in shards_to_verify {
+            match epoch_accounts_hash::calculate_shard_from_storages(
+                &in_progress.accounts.accounts_db,
+                &sorted_storages,
+                &config,
+                shard,
+            ) {
+                Ok(shard_accounts_hash) => in_progress.shards.push(shard_accounts_hash),
+                Err(err) => {
+                    error!(
+                        "failed to verify shard {} of the epoch accounts hash from slot {}: {err:?}",
+                        shard.index(),
+                        in_progress.slot,
+                    );
+                    self.in_progress = None;
+                    return Some(false);
+                }
+            }
+        }
+        datapoint_info!(
+            "epoch_accounts_hash_incremental_verification",
+            ("slot", slot as i64, i64),
+            ("num_shards_verified", in_progress.shards.len() as i64, i64),
+        );
+        if in_progress.shards.len() < num_shards {
+            return None;
+        }
+
+        let in_progress = self.in_progress.take().unwrap();
+        let calculated = epoch_accounts_hash::combine_shards(in_progress.shards)
+            .expect("all shards were verified, in order");
+        let is_match = calculated == in_progress.epoch_accounts_hash;
+        if is_match {
+            info!(
+                "verified the epoch accounts hash from slot {} incrementally",
+                in_progress.slot
+            );
+        } else {
+            error!(
+                "incremental verification of the epoch accounts hash from slot {} failed! \
+                 calculated: {:?}, expected: {:?}",
+                in_progress.slot, calculated, in_progress.epoch_accounts_hash,
+            );
+            datapoint_error!(
+                "epoch_accounts_hash_incremental_verification_mismatch",
+                ("slot", in_progress.slot as i64, i64),
+            );
+        }
+        Some(is_match)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use {
@@ -470,14 +819,14 @@ mod tests {
         rand::seq::SliceRandom,
         solana_gossip::{cluster_info::make_accounts_hashes_message, contact_info::ContactInfo},
         solana_runtime::{
-            rent_collector::RentCollector,
+            snapshot_package::SnapshotType,
             snapshot_utils::{ArchiveFormat, SnapshotVersion},
         },
         solana_sdk::{
+            account::AccountSharedData,
             genesis_config::ClusterType,
             hash::hash,
             signature::{Keypair, Signer},
-            sysvar::epoch_schedule::EpochSchedule,
         },
         solana_streamer::socket::SocketAddrSpace,
         std::{str::FromStr, time::Instant},
@@ -563,6 +912,7 @@ mod tests {
                 accounts: Arc::clone(&accounts),
                 epoch_schedule: EpochSchedule::default(),
                 rent_collector: RentCollector::default(),
+                epoch_accounts_hash_deadline: None,
                 enqueued: Instant::now(),
             };
 
@@ -576,6 +926,9 @@ mod tests {
                 &exit,
                 0,
                 Some(&snapshot_config),
+                None,
+                None,
+                None,
             );
 
             // sleep for 1ms to create a newer timestmap for gossip entry
@@ -602,6 +955,71 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_on_accounts_hash_complete() {
+        use {std::path::PathBuf, std::sync::Mutex, tempfile::TempDir};
+        let keypair = Keypair::new();
+        let contact_info = ContactInfo::new_localhost(&keypair.pubkey(), 0);
+        let cluster_info = new_test_cluster_info(contact_info);
+        let exit = Arc::new(AtomicBool::new(false));
+        let mut hashes = vec![];
+        let accounts = Arc::new(solana_runtime::accounts::Accounts::default_for_tests());
+
+        let observed = Arc::new(Mutex::new(Vec::new()));
+        let on_accounts_hash_complete: OnAccountsHashComplete = {
+            let observed = Arc::clone(&observed);
+            Box::new(move |slot, accounts_hash| {
+                observed.lock().unwrap().push((slot, accounts_hash))
+            })
+        };
+
+        let slots = [100, 200];
+        for slot in slots {
+            let accounts_package = AccountsPackage {
+                package_type: AccountsPackageType::AccountsHashVerifier,
+                slot,
+                block_height: slot,
+                slot_deltas: vec![],
+                snapshot_links: TempDir::new().unwrap(),
+                snapshot_storages: vec![],
+                archive_format: ArchiveFormat::TarBzip2,
+                snapshot_version: SnapshotVersion::default(),
+                full_snapshot_archives_dir: PathBuf::default(),
+                incremental_snapshot_archives_dir: PathBuf::default(),
+                expected_capitalization: 0,
+                accounts_hash_for_testing: None,
+                cluster_type: ClusterType::MainnetBeta,
+                accounts: Arc::clone(&accounts),
+                epoch_schedule: EpochSchedule::default(),
+                rent_collector: RentCollector::default(),
+                epoch_accounts_hash_deadline: None,
+                enqueued: Instant::now(),
+            };
+            AccountsHashVerifier::process_accounts_package(
+                accounts_package,
+                &cluster_info,
+                None,
+                false,
+                None,
+                &mut hashes,
+                &exit,
+                0,
+                None,
+                None,
+                Some(&on_accounts_hash_complete),
+                None,
+            );
+        }
+
+        let observed = observed.lock().unwrap();
+        assert_eq!(
+            observed.iter().map(|(slot, _)| *slot).collect::<Vec<_>>(),
+            slots,
+        );
+        // the callback sees the same accounts hashes that are pushed to the cluster
+        assert_eq!(*observed, hashes);
+    }
+
     /// Ensure that unhandled accounts packages are properly re-enqueued or dropped
     ///
     /// The accounts package handler should re-enqueue unhandled accounts packages, if those
@@ -747,4 +1165,62 @@ mod tests {
         )
         .is_none());
     }
+
+    #[test]
+    fn test_incremental_epoch_accounts_hash_verifier() {
+        use solana_runtime::{bank::Bank, genesis_utils::create_genesis_config};
+        let genesis_config_info = create_genesis_config(1_000_000);
+        let bank = Bank::new_for_tests(&genesis_config_info.genesis_config);
+        for lamports in 1..100 {
+            let account = AccountSharedData::new(lamports, 0, &Pubkey::default());
+            bank.store_account(&Pubkey::new_unique(), &account);
+        }
+        bank.freeze();
+        bank.squash();
+        bank.force_flush_accounts_cache();
+        let accounts_hash = bank.update_accounts_hash();
+        let stop_slot = epoch_accounts_hash::calculation_stop(&bank);
+
+        let new_eah = |epoch_accounts_hash| {
+            let accounts_package = AccountsPackage {
+                package_type: AccountsPackageType::EpochAccountsHash,
+                slot: bank.slot(),
+                block_height: bank.block_height(),
+                snapshot_storages: bank.get_snapshot_storages(None),
+                accounts: bank.accounts(),
+                epoch_schedule: *bank.epoch_schedule(),
+                rent_collector: bank.rent_collector().clone(),
+                ..AccountsPackage::default_for_tests()
+            };
+            IncrementalVerificationRequest::new(&accounts_package, epoch_accounts_hash)
+        };
+        let new_ahv = |slot| IncrementalVerificationRequest::Continue { slot };
+
+        // one shard is verified at each intermediate slot within the window
+        let num_shards = 4;
+        let mut verifier = IncrementalEpochAccountsHashVerifier::new(num_shards);
+        assert!(verifier.handle_request(new_eah(accounts_hash)).is_none());
+        for slot in 1..num_shards as Slot {
+            assert!(slot < stop_slot);
+            assert!(verifier.handle_request(new_ahv(slot)).is_none());
+            let in_progress = verifier.in_progress.as_ref().unwrap();
+            assert_eq!(in_progress.shards.len(), slot as usize);
+        }
+        assert_eq!(
+            verifier.handle_request(new_ahv(num_shards as Slot)),
+            Some(true),
+        );
+        assert!(verifier.in_progress.is_none());
+
+        // the remaining shards are verified all at once at the "stop" slot
+        assert!(verifier.handle_request(new_eah(accounts_hash)).is_none());
+        assert!(verifier.handle_request(new_ahv(1)).is_none());
+        assert_eq!(verifier.handle_request(new_ahv(stop_slot)), Some(true));
+
+        // a divergent EAH is detected
+        assert!(verifier
+            .handle_request(new_eah(Hash::new_unique()))
+            .is_none());
+        assert_eq!(verifier.handle_request(new_ahv(stop_slot)), Some(false));
+    }
 }
@@ -10,7 +10,10 @@ use {
             FullSnapshotHash, FullSnapshotHashes, IncrementalSnapshotHash,
             IncrementalSnapshotHashes, StartingSnapshotHashes,
         },
-        snapshot_package::{retain_max_n_elements, PendingSnapshotPackage, SnapshotType},
+        snapshot_package::{
+            retain_max_n_elements, take_snapshot_package_to_archive, PendingSnapshotPackage,
+            SnapshotType,
+        },
         snapshot_utils,
     },
     solana_sdk::{clock::Slot, hash::Hash},
@@ -48,6 +51,17 @@ impl SnapshotPackagerService {
             snapshot_config.maximum_incremental_snapshot_archives_to_retain,
         );
 
+        // Incremental snapshot packages are only archived if they are based on the last full
+        // snapshot archived, which at startup is the full snapshot loaded from, if any
+        let mut last_full_snapshot_slot = starting_snapshot_hashes
+            .as_ref()
+            .map(|starting_snapshot_hashes| starting_snapshot_hashes.full.hash.0)
+            .or_else(|| {
+                snapshot_utils::get_highest_full_snapshot_archive_slot(
+                    &snapshot_config.full_snapshot_archives_dir,
+                )
+            });
+
         let t_snapshot_packager = Builder::new()
             .name("solSnapshotPkgr".to_string())
             .spawn(move || {
@@ -72,12 +86,27 @@ impl SnapshotPackagerService {
                         break;
                     }
 
-                    let snapshot_package = pending_snapshot_package.lock().unwrap().take();
+                    let snapshot_package = take_snapshot_package_to_archive(
+                        &pending_snapshot_package,
+                        last_full_snapshot_slot,
+                    );
                     if snapshot_package.is_none() {
                         std::thread::sleep(Duration::from_millis(100));
                         continue;
                     }
                     let snapshot_package = snapshot_package.unwrap();
+                    check_snapshot_archive_age(
+                        snapshot_config.max_snapshot_age_slots,
+                        last_full_snapshot_slot,
+                        snapshot_package.slot(),
+                    );
+
+                    // If configured, wait for any epoch accounts hash calculation to complete,
+                    // and hold off new ones until archiving is done.
+                    let archiving_guard = snapshot_config
+                        .epoch_accounts_hash_archive_coordinator
+                        .as_ref()
+                        .map(|coordinator| coordinator.begin_archiving());
 
                     // Archiving the snapshot package is not allowed to fail.
                     // AccountsBackgroundService calls `clean_accounts()` with a value for
@@ -88,8 +117,15 @@ impl SnapshotPackagerService {
                         &snapshot_config.incremental_snapshot_archives_dir,
                         snapshot_config.maximum_full_snapshot_archives_to_retain,
                         snapshot_config.maximum_incremental_snapshot_archives_to_retain,
+                        snapshot_config.maximum_snapshots_total_bytes,
+                        snapshot_config.zstd_dictionary.as_deref(),
+                        None,
                     )
                     .expect("failed to archive snapshot package");
+                    drop(archiving_guard);
+                    if snapshot_package.snapshot_type.is_full_snapshot() {
+                        last_full_snapshot_slot = Some(snapshot_package.slot());
+                    }
 
                     if let Some(snapshot_gossip_manager) = snapshot_gossip_manager.as_mut() {
                         snapshot_gossip_manager.push_snapshot_hash(
@@ -111,6 +147,38 @@ impl SnapshotPackagerService {
     }
 }
 
+/// If `max_snapshot_age_slots` is set, check how far the last full snapshot archived is behind
+/// `slot`, the slot of the latest snapshot package.  If it is too far behind, archiving has likely
+/// stalled, so report a warning and return the age.  With no full snapshot archived yet, the age
+/// is measured from slot 0.
+///
+/// Note that the age is expected to reach the full snapshot interval during normal operation, so
+/// the threshold should be larger than that.
+fn check_snapshot_archive_age(
+    max_snapshot_age_slots: Option<Slot>,
+    last_full_snapshot_slot: Option<Slot>,
+    slot: Slot,
+) -> Option<Slot> {
+    let max_snapshot_age_slots = max_snapshot_age_slots?;
+    let last_full_snapshot_slot = last_full_snapshot_slot.unwrap_or_default();
+    let snapshot_age_slots = slot.saturating_sub(last_full_snapshot_slot);
+    (snapshot_age_slots > max_snapshot_age_slots).then(|| {
+        warn!(
+            "The last full snapshot archived, at slot {}, is {} slots behind slot {}. \
+             Is snapshot archiving stalled?",
+            last_full_snapshot_slot, snapshot_age_slots, slot,
+        );
+        datapoint_warn!(
+            "snapshot_archive_age_exceeded",
+            ("slot", slot, i64),
+            ("last_full_snapshot_slot", last_full_snapshot_slot, i64),
+            ("snapshot_age_slots", snapshot_age_slots, i64),
+            ("max_snapshot_age_slots", max_snapshot_age_slots, i64),
+        );
+        snapshot_age_slots
+    })
+}
+
 struct SnapshotGossipManager {
     cluster_info: Arc<ClusterInfo>,
     max_full_snapshot_hashes: usize,
@@ -221,6 +289,7 @@ mod tests {
     use {
         super::*,
         bincode::serialize_into,
+        solana_gossip::contact_info::ContactInfo,
         solana_runtime::{
             accounts_db::AccountStorageEntry,
             bank::BankSlotDelta,
@@ -230,11 +299,16 @@ mod tests {
                 self, ArchiveFormat, SnapshotVersion, SNAPSHOT_STATUS_CACHE_FILENAME,
             },
         },
-        solana_sdk::hash::Hash,
+        solana_sdk::{
+            hash::Hash,
+            signature::{Keypair, Signer},
+        },
+        solana_streamer::socket::SocketAddrSpace,
         std::{
             fs::{self, remove_dir_all, OpenOptions},
             io::Write,
             path::{Path, PathBuf},
+            time::Instant,
         },
         tempfile::TempDir,
     };
@@ -259,11 +333,68 @@ mod tests {
         remove_dir_all(temp_dir).expect("should remove tmp dir");
     }
 
+    #[test]
+    fn test_check_snapshot_archive_age() {
+        const MAX_SNAPSHOT_AGE_SLOTS: Option<Slot> = Some(100);
+
+        // advance slots without archiving; the warning only fires past the threshold
+        for slot in 0..=100 {
+            assert_eq!(
+                check_snapshot_archive_age(MAX_SNAPSHOT_AGE_SLOTS, None, slot),
+                None
+            );
+        }
+        assert_eq!(
+            check_snapshot_archive_age(MAX_SNAPSHOT_AGE_SLOTS, None, 101),
+            Some(101)
+        );
+
+        // once a full snapshot is archived, the age is measured from its slot
+        assert_eq!(
+            check_snapshot_archive_age(MAX_SNAPSHOT_AGE_SLOTS, Some(200), 300),
+            None
+        );
+        assert_eq!(
+            check_snapshot_archive_age(MAX_SNAPSHOT_AGE_SLOTS, Some(200), 301),
+            Some(101)
+        );
+
+        // without a threshold, never warn
+        assert_eq!(check_snapshot_archive_age(None, None, Slot::MAX), None);
+    }
+
     #[test]
     fn test_package_snapshots() {
         create_and_verify_snapshot(TempDir::new().unwrap().path())
     }
 
+    #[test]
+    fn test_push_full_snapshot_hash_to_gossip() {
+        let keypair = Arc::new(Keypair::new());
+        let cluster_info = Arc::new(ClusterInfo::new(
+            ContactInfo::new_localhost(&keypair.pubkey(), 0),
+            keypair,
+            SocketAddrSpace::Unspecified,
+        ));
+        let get_full_snapshot_hashes = || {
+            cluster_info.flush_push_queue();
+            cluster_info.get_snapshot_hash_for_node(&cluster_info.id(), |hashes| hashes.clone())
+        };
+        let mut snapshot_gossip_manager = SnapshotGossipManager {
+            cluster_info: cluster_info.clone(),
+            max_full_snapshot_hashes: MAX_SNAPSHOT_HASHES,
+            max_incremental_snapshot_hashes: MAX_INCREMENTAL_SNAPSHOT_HASHES,
+            full_snapshot_hashes: FullSnapshotHashes::default(),
+            incremental_snapshot_hashes: IncrementalSnapshotHashes::default(),
+        };
+        assert_eq!(get_full_snapshot_hashes(), None);
+
+        // once a full snapshot is archived, its slot and hash are advertised in gossip
+        let snapshot_hash = (42, Hash::new_unique());
+        snapshot_gossip_manager.push_snapshot_hash(SnapshotType::FullSnapshot, snapshot_hash);
+        assert_eq!(get_full_snapshot_hashes(), Some(vec![snapshot_hash]));
+    }
+
     fn create_and_verify_snapshot(temp_dir: &Path) {
         let accounts_dir = temp_dir.join("accounts");
         let snapshots_dir = temp_dir.join("snapshots");
@@ -330,6 +461,7 @@ mod tests {
             snapshot_storages: vec![storage_entries],
             snapshot_version: SnapshotVersion::default(),
             snapshot_type: SnapshotType::FullSnapshot,
+            enqueued: Instant::now(),
         };
 
         // Make tarball from packageable snapshot
@@ -339,6 +471,9 @@ mod tests {
             incremental_snapshot_archives_dir,
             snapshot_utils::DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
             snapshot_utils::DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+            None,
+            None,
         )
         .unwrap();
 
@@ -3,7 +3,7 @@
 pub use solana_perf::report_target_features;
 use {
     crate::{
-        accounts_hash_verifier::AccountsHashVerifier,
+        accounts_hash_verifier::{AccountsHashVerifier, AccountsHashVerifierConfig},
         broadcast_stage::BroadcastStageType,
         cache_block_meta_service::{CacheBlockMetaSender, CacheBlockMetaService},
         cluster_info_vote_listener::VoteTracker,
@@ -67,8 +67,8 @@ use {
     },
     solana_runtime::{
         accounts_background_service::{
-            AbsRequestHandlers, AbsRequestSender, AccountsBackgroundService, DroppedSlotsReceiver,
-            PrunedBanksRequestHandler, SnapshotRequestHandler,
+            AbsConfig, AbsRequestHandlers, AbsRequestSender, AccountsBackgroundService,
+            DroppedSlotsReceiver, PrunedBanksRequestHandler, SnapshotRequestHandler,
         },
         accounts_db::{AccountShrinkThreshold, AccountsDbConfig},
         accounts_index::AccountSecondaryIndexes,
@@ -142,6 +142,7 @@ pub struct ValidatorConfig {
     pub gossip_validators: Option<HashSet<Pubkey>>, // None = gossip with all
     pub halt_on_known_validators_accounts_hash_mismatch: bool,
     pub accounts_hash_fault_injection_slots: u64, // 0 = no fault injection
+    pub epoch_accounts_hash_verification_shards: Option<usize>, // None = no incremental verification
     pub no_rocksdb_compaction: bool,
     pub rocksdb_compaction_interval: Option<u64>,
     pub rocksdb_max_compaction_jitter: Option<u64>,
@@ -169,6 +170,7 @@ pub struct ValidatorConfig {
     pub warp_slot: Option<Slot>,
     pub accounts_db_test_hash_calculation: bool,
     pub accounts_db_skip_shrink: bool,
+    pub accounts_hash_checkpoint_interval_slots: Option<Slot>,
     pub tpu_coalesce_ms: u64,
     pub staked_nodes_overrides: Arc<RwLock<HashMap<Pubkey, u64>>>,
     pub validator_exit: Arc<RwLock<Exit>>,
@@ -207,6 +209,7 @@ impl Default for ValidatorConfig {
             gossip_validators: None,
             halt_on_known_validators_accounts_hash_mismatch: false,
             accounts_hash_fault_injection_slots: 0,
+            epoch_accounts_hash_verification_shards: None,
             no_rocksdb_compaction: false,
             rocksdb_compaction_interval: None,
             rocksdb_max_compaction_jitter: None,
@@ -233,6 +236,7 @@ impl Default for ValidatorConfig {
             warp_slot: None,
             accounts_db_test_hash_calculation: false,
             accounts_db_skip_shrink: false,
+            accounts_hash_checkpoint_interval_slots: None,
             tpu_coalesce_ms: DEFAULT_TPU_COALESCE_MS,
             staked_nodes_overrides: Arc::new(RwLock::new(HashMap::new())),
             validator_exit: Arc::new(RwLock::new(Exit::default())),
@@ -596,6 +600,12 @@ impl Validator {
                     .as_ref()
                     .map(|config| config.filler_accounts_config.count == 0)
                     .unwrap_or(true);
+                // the RPC service serves the snapshot archives this node generates
+                if enable_gossip_push
+                    && ContactInfo::is_valid_address(&node.info.rpc, &socket_addr_space)
+                {
+                    cluster_info.push_snapshot_service(node.info.rpc);
+                }
                 let pending_snapshot_package = PendingSnapshotPackage::default();
                 let snapshot_packager_service = SnapshotPackagerService::new(
                     pending_snapshot_package.clone(),
@@ -624,6 +634,11 @@ impl Validator {
             config.halt_on_known_validators_accounts_hash_mismatch,
             config.accounts_hash_fault_injection_slots,
             config.snapshot_config.clone(),
+            AccountsHashVerifierConfig {
+                epoch_accounts_hash_verification_shards: config
+                    .epoch_accounts_hash_verification_shards,
+                ..AccountsHashVerifierConfig::default()
+            },
         );
 
         let (snapshot_request_sender, snapshot_request_receiver) = unbounded();
@@ -635,9 +650,7 @@ impl Validator {
             snapshot_request_receiver,
             accounts_package_sender,
         };
-        let pruned_banks_request_handler = PrunedBanksRequestHandler {
-            pruned_banks_receiver,
-        };
+        let pruned_banks_request_handler = PrunedBanksRequestHandler::new(pruned_banks_receiver);
         let last_full_snapshot_slot = starting_snapshot_hashes.map(|x| x.full.hash.0);
         let accounts_background_service = AccountsBackgroundService::new(
             bank_forks.clone(),
@@ -649,6 +662,11 @@ impl Validator {
             config.accounts_db_caching_enabled,
             config.accounts_db_test_hash_calculation,
             last_full_snapshot_slot,
+            AbsConfig {
+                accounts_hash_checkpoint_interval_slots: config
+                    .accounts_hash_checkpoint_interval_slots,
+                ..AbsConfig::default()
+            },
         );
 
         let leader_schedule_cache = Arc::new(leader_schedule_cache);
@@ -1721,6 +1739,7 @@ fn maybe_warp_slot(
             snapshot_config.archive_format,
             snapshot_config.maximum_full_snapshot_archives_to_retain,
             snapshot_config.maximum_incremental_snapshot_archives_to_retain,
+            None,
         ) {
             Ok(archive_info) => archive_info,
             Err(e) => return Err(format!("Unable to create snapshot: {}", e)),
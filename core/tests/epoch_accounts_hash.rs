@@ -2,14 +2,16 @@
 use {
     log::*,
     solana_core::{
-        accounts_hash_verifier::AccountsHashVerifier,
+        accounts_hash_verifier::{
+            AccountsHashVerifier, AccountsHashVerifierConfig, OnEpochAccountsHashComplete,
+        },
         snapshot_packager_service::SnapshotPackagerService,
     },
     solana_gossip::{cluster_info::ClusterInfo, contact_info::ContactInfo},
     solana_runtime::{
         accounts_background_service::{
-            AbsRequestHandlers, AbsRequestSender, AccountsBackgroundService, DroppedSlotsReceiver,
-            PrunedBanksRequestHandler, SnapshotRequestHandler,
+            AbsConfig, AbsRequestHandlers, AbsRequestSender, AccountsBackgroundService,
+            DroppedSlotsReceiver, PrunedBanksRequestHandler, SnapshotRequestHandler,
         },
         accounts_db::AccountShrinkThreshold,
         accounts_hash::CalcAccountsHashConfig,
@@ -70,7 +72,12 @@ impl TestEnvironment {
 
     #[must_use]
     fn new() -> TestEnvironment {
-        Self::_new(SnapshotConfig::new_load_only())
+        Self::_new(SnapshotConfig::new_load_only(), None)
+    }
+
+    #[must_use]
+    fn new_with_on_eah_complete(on_eah_complete: OnEpochAccountsHashComplete) -> TestEnvironment {
+        Self::_new(SnapshotConfig::new_load_only(), Some(on_eah_complete))
     }
 
     #[must_use]
@@ -83,11 +90,14 @@ impl TestEnvironment {
             incremental_snapshot_archive_interval_slots,
             ..SnapshotConfig::default()
         };
-        Self::_new(snapshot_config)
+        Self::_new(snapshot_config, None)
     }
 
     #[must_use]
-    fn _new(snapshot_config: SnapshotConfig) -> TestEnvironment {
+    fn _new(
+        snapshot_config: SnapshotConfig,
+        on_eah_complete: Option<OnEpochAccountsHashComplete>,
+    ) -> TestEnvironment {
         let bank_snapshots_dir = TempDir::new().unwrap();
         let full_snapshot_archives_dir = TempDir::new().unwrap();
         let incremental_snapshot_archives_dir = TempDir::new().unwrap();
@@ -128,6 +138,7 @@ impl TestEnvironment {
             &snapshot_config,
             pruned_banks_receiver,
             Arc::clone(&bank_forks),
+            on_eah_complete,
         );
         let bank = bank_forks.read().unwrap().working_bank();
         bank.set_callback(Some(Box::new(
@@ -173,6 +184,7 @@ impl BackgroundServices {
         snapshot_config: &SnapshotConfig,
         pruned_banks_receiver: DroppedSlotsReceiver,
         bank_forks: Arc<RwLock<BankForks>>,
+        on_eah_complete: Option<OnEpochAccountsHashComplete>,
     ) -> Self {
         info!("Starting background services...");
 
@@ -197,6 +209,10 @@ impl BackgroundServices {
             false,
             0,
             Some(snapshot_config.clone()),
+            AccountsHashVerifierConfig {
+                on_eah_complete,
+                ..AccountsHashVerifierConfig::default()
+            },
         );
 
         let (snapshot_request_sender, snapshot_request_receiver) = crossbeam_channel::unbounded();
@@ -208,9 +224,7 @@ impl BackgroundServices {
             snapshot_request_receiver,
             accounts_package_sender,
         };
-        let pruned_banks_request_handler = PrunedBanksRequestHandler {
-            pruned_banks_receiver,
-        };
+        let pruned_banks_request_handler = PrunedBanksRequestHandler::new(pruned_banks_receiver);
         let accounts_background_service = AccountsBackgroundService::new(
             bank_forks,
             &exit,
@@ -221,6 +235,7 @@ impl BackgroundServices {
             false,
             false,
             None,
+            AbsConfig::default(),
         );
 
         info!("Starting background services... DONE");
@@ -320,6 +335,7 @@ fn test_epoch_accounts_hash_basic(test_environment: TestEnvironment) {
                         rent_collector: bank.rent_collector(),
                         store_detailed_debug_info_on_failure: false,
                         full_snapshot: None,
+                        ..CalcAccountsHashConfig::default()
                     },
                 )
                 .unwrap();
@@ -350,6 +366,53 @@ fn test_epoch_accounts_hash_basic(test_environment: TestEnvironment) {
     }
 }
 
+/// Ensure that the on-EAH-complete callback is called with the correct epoch and EAH
+#[test]
+fn test_on_epoch_accounts_hash_complete() {
+    solana_logger::setup();
+
+    let (eah_sender, eah_receiver) = crossbeam_channel::unbounded();
+    let on_eah_complete: OnEpochAccountsHashComplete =
+        Arc::new(move |epoch, epoch_accounts_hash: &EpochAccountsHash| {
+            _ = eah_sender.send((epoch, *epoch_accounts_hash));
+        });
+    let test_environment = TestEnvironment::new_with_on_eah_complete(on_eah_complete);
+    let bank_forks = &test_environment.bank_forks;
+
+    loop {
+        let bank = {
+            let parent = bank_forks.read().unwrap().working_bank();
+            let bank = bank_forks.write().unwrap().insert(Bank::new_from_parent(
+                &parent,
+                &Pubkey::default(),
+                parent.slot() + 1,
+            ));
+            bank.fill_bank_with_ticks_for_tests();
+            bank
+        };
+
+        // Root every bank, so the EAH calculation gets requested at the "start" slot
+        bank_forks.write().unwrap().set_root(
+            bank.slot(),
+            &test_environment
+                .background_services
+                .accounts_background_request_sender,
+            None,
+        );
+
+        if bank.slot() == epoch_accounts_hash::calculation_start(&bank) {
+            while bank.epoch_accounts_hash().is_none() {
+                std::thread::sleep(Duration::from_secs(1));
+            }
+            let (epoch, epoch_accounts_hash) =
+                eah_receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+            assert_eq!(epoch, bank.epoch());
+            assert_eq!(Some(epoch_accounts_hash), bank.epoch_accounts_hash());
+            break;
+        }
+    }
+}
+
 /// Ensure that snapshots always have the expected EAH
 ///
 /// Generate snapshots:
@@ -459,6 +522,7 @@ fn test_snapshots_have_expected_epoch_accounts_hash() {
                 None,
                 None,
                 &Arc::new(AtomicBool::new(false)),
+                snapshot_utils::SnapshotLoadOptions::default(),
             )
             .unwrap()
             .0;
@@ -7,13 +7,13 @@ use {
     itertools::Itertools,
     log::{info, trace},
     solana_core::{
-        accounts_hash_verifier::AccountsHashVerifier,
+        accounts_hash_verifier::{AccountsHashVerifier, AccountsHashVerifierConfig},
         snapshot_packager_service::SnapshotPackagerService,
     },
     solana_gossip::{cluster_info::ClusterInfo, contact_info::ContactInfo},
     solana_runtime::{
         accounts_background_service::{
-            AbsRequestHandlers, AbsRequestSender, AccountsBackgroundService,
+            AbsConfig, AbsRequestHandlers, AbsRequestSender, AccountsBackgroundService,
             PrunedBanksRequestHandler, SnapshotRequestHandler,
         },
         accounts_db::{self, ACCOUNTS_DB_CONFIG_FOR_TESTING},
@@ -178,6 +178,7 @@ fn restore_from_snapshot(
         Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
         None,
         &Arc::default(),
+        snapshot_utils::SnapshotLoadOptions::default(),
     )
     .unwrap();
 
@@ -229,6 +230,7 @@ fn run_bank_forks_snapshot_n<F>(
         false,
         0,
         Some(snapshot_test_config.snapshot_config.clone()),
+        AccountsHashVerifierConfig::default(),
     );
 
     let (snapshot_request_sender, snapshot_request_receiver) = unbounded();
@@ -289,6 +291,9 @@ fn run_bank_forks_snapshot_n<F>(
         &snapshot_config.incremental_snapshot_archives_dir,
         snapshot_config.maximum_full_snapshot_archives_to_retain,
         snapshot_config.maximum_incremental_snapshot_archives_to_retain,
+        snapshot_config.maximum_snapshots_total_bytes,
+        None,
+        None,
     )
     .unwrap();
 
@@ -727,6 +732,7 @@ fn test_bank_forks_incremental_snapshot(
         false,
         0,
         Some(snapshot_test_config.snapshot_config.clone()),
+        AccountsHashVerifierConfig::default(),
     );
 
     let (snapshot_request_sender, snapshot_request_receiver) = unbounded();
@@ -837,6 +843,7 @@ fn make_full_snapshot_archive(
         snapshot_config.snapshot_version,
         snapshot_config.maximum_full_snapshot_archives_to_retain,
         snapshot_config.maximum_incremental_snapshot_archives_to_retain,
+        None,
     )?;
 
     Ok(())
@@ -875,6 +882,7 @@ fn make_incremental_snapshot_archive(
         snapshot_config.snapshot_version,
         snapshot_config.maximum_full_snapshot_archives_to_retain,
         snapshot_config.maximum_incremental_snapshot_archives_to_retain,
+        None,
     )?;
 
     Ok(())
@@ -905,6 +913,9 @@ fn restore_from_snapshots_and_check_banks_are_equal(
         Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
         None,
         &Arc::default(),
+        None,
+        snapshot_utils::DuplicateIncrementalSnapshotArchivePolicy::default(),
+        snapshot_utils::SnapshotLoadOptions::default(),
     )?;
 
     assert_eq!(bank, &deserialized_bank);
@@ -986,9 +997,7 @@ fn test_snapshots_with_background_services(
         snapshot_request_receiver,
         accounts_package_sender: accounts_package_sender.clone(),
     };
-    let pruned_banks_request_handler = PrunedBanksRequestHandler {
-        pruned_banks_receiver,
-    };
+    let pruned_banks_request_handler = PrunedBanksRequestHandler::new(pruned_banks_receiver);
     let abs_request_handler = AbsRequestHandlers {
         snapshot_request_handler,
         pruned_banks_request_handler,
@@ -1014,6 +1023,7 @@ fn test_snapshots_with_background_services(
         false,
         0,
         Some(snapshot_test_config.snapshot_config.clone()),
+        AccountsHashVerifierConfig::default(),
     );
 
     let accounts_background_service = AccountsBackgroundService::new(
@@ -1023,6 +1033,7 @@ fn test_snapshots_with_background_services(
         false,
         true,
         None,
+        AbsConfig::default(),
     );
 
     let mint_keypair = &snapshot_test_config.genesis_config_info.mint_keypair;
@@ -1099,6 +1110,9 @@ fn test_snapshots_with_background_services(
         Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
         None,
         &Arc::default(),
+        None,
+        snapshot_utils::DuplicateIncrementalSnapshotArchivePolicy::default(),
+        snapshot_utils::SnapshotLoadOptions::default(),
     )
     .unwrap();
 
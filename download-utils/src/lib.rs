@@ -9,7 +9,7 @@ use {
     },
     solana_sdk::{clock::Slot, genesis_config::DEFAULT_GENESIS_ARCHIVE, hash::Hash},
     std::{
-        fs::{self, File},
+        fs::{self, File, OpenOptions},
         io::{self, Read},
         net::SocketAddr,
         path::{Path, PathBuf},
@@ -70,46 +70,211 @@ pub fn download_file<'a, 'b>(
     if destination_file.is_file() {
         return Err(format!("{:?} already exists", destination_file));
     }
-    let download_start = Instant::now();
+    let partial_file = partial_file_path(destination_file);
+    // Only snapshot archives are resumed, since they are verified once downloaded, see
+    // `download_snapshot_archive()`; anything else is downloaded again from the start.
+    let _ignored = fs::remove_file(&partial_file);
+    download_partial_file(
+        url,
+        &partial_file,
+        use_progress_bar,
+        progress_notify_callback,
+    )?;
+    std::fs::rename(partial_file, destination_file)
+        .map_err(|err| format!("Unable to rename: {:?}", err))?;
 
-    fs::create_dir_all(destination_file.parent().expect("parent"))
-        .map_err(|err| err.to_string())?;
+    Ok(())
+}
 
-    let mut temp_destination_file = destination_file.to_path_buf();
-    temp_destination_file.set_file_name(format!(
-        "tmp-{}",
+/// The `.partial` sibling of `destination_file` that a download is written to
+///
+/// The download is only renamed to `destination_file` once it is complete, so an interrupted
+/// download never looks complete, and can be resumed later.
+fn partial_file_path(destination_file: &Path) -> PathBuf {
+    let mut partial_file = destination_file.to_path_buf();
+    partial_file.set_file_name(format!(
+        "{}.partial",
         destination_file
             .file_name()
             .expect("file_name")
             .to_str()
             .expect("to_str")
     ));
+    partial_file
+}
+
+/// Where a `.partial` download came from, stored next to it
+///
+/// A download is only resumed from the same URL it was started from, and if the server sent an
+/// ETag, only if the file it serves still has that ETag.
+#[derive(Debug, PartialEq, Eq)]
+struct PartialFileSource {
+    url: String,
+    etag: Option<String>,
+}
+
+impl PartialFileSource {
+    fn path(partial_file: &Path) -> PathBuf {
+        let mut path = partial_file.as_os_str().to_os_string();
+        path.push(".source");
+        PathBuf::from(path)
+    }
+
+    /// The URL is on the first line, and the ETag, if any, on the second
+    fn read(partial_file: &Path) -> Option<Self> {
+        let source = fs::read_to_string(Self::path(partial_file)).ok()?;
+        let mut lines = source.lines();
+        Some(Self {
+            url: lines.next()?.to_string(),
+            etag: lines.next().map(str::to_string),
+        })
+    }
+
+    fn write(&self, partial_file: &Path) -> io::Result<()> {
+        let mut source = format!("{}\n", self.url);
+        if let Some(etag) = &self.etag {
+            source.push_str(etag);
+            source.push('\n');
+        }
+        fs::write(Self::path(partial_file), source)
+    }
+
+    fn remove(partial_file: &Path) {
+        let _ignored = fs::remove_file(Self::path(partial_file));
+    }
+}
+
+/// Parse the first byte position and the complete length from a `Content-Range` header
+///
+/// The header looks like `bytes 100-199/200`; the complete length may be `*` if it is unknown.
+fn parse_content_range(headers: &reqwest::header::HeaderMap) -> Option<(u64, Option<u64>)> {
+    let content_range = headers
+        .get(reqwest::header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .strip_prefix("bytes ")?;
+    let (range, complete_length) = content_range.split_once('/')?;
+    let (first_byte_pos, _last_byte_pos) = range.split_once('-')?;
+    Some((
+        first_byte_pos.trim().parse().ok()?,
+        complete_length.trim().parse().ok(),
+    ))
+}
+
+/// Download `url` into `partial_file`, resuming from where a previous download left off
+///
+/// If `partial_file` already has some bytes from the same `url`, only the rest of the file is
+/// requested with an HTTP range request, conditional on the ETag the server first sent (`If-Range`).
+/// If `partial_file` came from another URL, the server does not support range requests, cannot
+/// satisfy this one, or the file has changed since, the whole file is downloaded again instead.
+/// The length of `partial_file` is checked against the length the server reported once the
+/// download completes.
+///
+/// Where `partial_file` came from is kept next to it until the download completes, see
+/// `PartialFileSource`.
+///
+/// Returns whether the download was resumed.
+fn download_partial_file<'a, 'b>(
+    url: &str,
+    partial_file: &Path,
+    use_progress_bar: bool,
+    progress_notify_callback: &'a mut DownloadProgressCallbackOption<'b>,
+) -> Result<bool, String> {
+    let download_start = Instant::now();
+
+    fs::create_dir_all(partial_file.parent().expect("parent")).map_err(|err| err.to_string())?;
 
     let progress_bar = new_spinner_progress_bar();
     if use_progress_bar {
         progress_bar.set_message(format!("{}Downloading {}...", TRUCK, url));
     }
 
-    let response = reqwest::blocking::Client::new()
-        .get(url)
-        .send()
-        .and_then(|response| response.error_for_status())
+    let source = PartialFileSource::read(partial_file).filter(|source| source.url == url);
+    let if_range = source.as_ref().and_then(|source| source.etag.as_deref());
+    let client = reqwest::blocking::Client::new();
+    let send_request = |resume_from: u64| {
+        let mut request = client.get(url);
+        if resume_from > 0 {
+            request = request.header(reqwest::header::RANGE, format!("bytes={}-", resume_from));
+            if let Some(if_range) = if_range {
+                request = request.header(reqwest::header::IF_RANGE, if_range);
+            }
+        }
+        request
+            .send()
+            .and_then(|response| response.error_for_status())
+    };
+
+    let mut resume_from = if source.is_some() {
+        fs::metadata(partial_file)
+            .map(|metadata| metadata.len())
+            .unwrap_or(0)
+    } else {
+        0
+    };
+    let mut response = send_request(resume_from);
+    if resume_from > 0 {
+        let should_restart = match &response {
+            Ok(response) => {
+                response.status() == reqwest::StatusCode::PARTIAL_CONTENT
+                    && parse_content_range(response.headers()).map(|(start, _)| start)
+                        != Some(resume_from)
+            }
+            Err(err) => err.status() == Some(reqwest::StatusCode::RANGE_NOT_SATISFIABLE),
+        };
+        if should_restart {
+            info!(
+                "Unable to resume downloading {} from byte {}, downloading it again",
+                url, resume_from
+            );
+            resume_from = 0;
+            response = send_request(resume_from);
+        }
+    }
+    let response = response.map_err(|err| {
+        progress_bar.finish_and_clear();
+        err.to_string()
+    })?;
+
+    // A server that does not support range requests, or whose file no longer matches `If-Range`,
+    // responds with the whole file
+    let resumed = resume_from > 0 && response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+    if !resumed {
+        resume_from = 0;
+        PartialFileSource {
+            url: url.to_string(),
+            etag: response
+                .headers()
+                .get(reqwest::header::ETAG)
+                .and_then(|etag| etag.to_str().ok())
+                .map(str::to_string),
+        }
+        .write(partial_file)
         .map_err(|err| {
-            progress_bar.finish_and_clear();
-            err.to_string()
+            format!(
+                "Unable to write the source of {:?}: {:?}",
+                partial_file, err
+            )
         })?;
+    }
 
-    let download_size = {
-        response
-            .headers()
-            .get(reqwest::header::CONTENT_LENGTH)
-            .and_then(|content_length| content_length.to_str().ok())
-            .and_then(|content_length| content_length.parse().ok())
-            .unwrap_or(0)
+    let content_length: Option<u64> = response
+        .headers()
+        .get(reqwest::header::CONTENT_LENGTH)
+        .and_then(|content_length| content_length.to_str().ok())
+        .and_then(|content_length| content_length.parse().ok());
+    let expected_size = if resumed {
+        parse_content_range(response.headers())
+            .and_then(|(_, complete_length)| complete_length)
+            .or_else(|| content_length.map(|content_length| resume_from + content_length))
+    } else {
+        content_length
     };
+    let download_size = expected_size.unwrap_or(0);
 
     if use_progress_bar {
         progress_bar.set_length(download_size);
+        progress_bar.set_position(resume_from);
         progress_bar.set_style(
             ProgressStyle::default_bar()
                 .template(
@@ -119,6 +284,11 @@ pub fn download_file<'a, 'b>(
                 .progress_chars("=> "),
         );
         progress_bar.set_message(format!("{}Downloading~ {}", TRUCK, url));
+    } else if resumed {
+        info!(
+            "Resuming download of {} bytes from {} at byte {}",
+            download_size, url, resume_from
+        );
     } else {
         info!("Downloading {} bytes from {}", download_size, url);
     }
@@ -127,6 +297,7 @@ pub fn download_file<'a, 'b>(
         progress_bar: ProgressBar,
         response: R,
         last_print: Instant,
+        start_bytes: usize,
         current_bytes: usize,
         last_print_bytes: usize,
         download_size: f32,
@@ -154,7 +325,7 @@ pub fn download_file<'a, 'b>(
                 elapsed_time: self.start_time.elapsed(),
                 last_elapsed_time: self.last_print.elapsed(),
                 last_throughput,
-                total_throughput: self.current_bytes as f32
+                total_throughput: (self.current_bytes - self.start_bytes) as f32
                     / self.start_time.elapsed().as_secs_f32(),
                 total_bytes: self.download_size as usize,
                 current_bytes: self.current_bytes,
@@ -200,8 +371,9 @@ pub fn download_file<'a, 'b>(
         progress_bar,
         response,
         last_print: Instant::now(),
-        current_bytes: 0,
-        last_print_bytes: 0,
+        start_bytes: resume_from as usize,
+        current_bytes: resume_from as usize,
+        last_print_bytes: resume_from as usize,
         download_size: (download_size as f32).max(1f32),
         use_progress_bar,
         start_time: Instant::now(),
@@ -209,26 +381,41 @@ pub fn download_file<'a, 'b>(
         notification_count: 0,
     };
 
-    File::create(&temp_destination_file)
-        .and_then(|mut file| std::io::copy(&mut source, &mut file))
-        .map_err(|err| format!("Unable to write {:?}: {:?}", temp_destination_file, err))?;
+    let file = if resumed {
+        OpenOptions::new().append(true).open(partial_file)
+    } else {
+        File::create(partial_file)
+    };
+    file.and_then(|mut file| std::io::copy(&mut source, &mut file))
+        .map_err(|err| format!("Unable to write {:?}: {:?}", partial_file, err))?;
 
     source.progress_bar.finish_and_clear();
+
+    let downloaded_size = fs::metadata(partial_file)
+        .map_err(|err| format!("Unable to read {:?}: {:?}", partial_file, err))?
+        .len();
+    if let Some(expected_size) = expected_size {
+        if downloaded_size != expected_size {
+            return Err(format!(
+                "Downloaded {} bytes from {}, but expected {} bytes",
+                downloaded_size, url, expected_size
+            ));
+        }
+    }
+    PartialFileSource::remove(partial_file);
+
     info!(
         "  {}{}",
         SPARKLE,
         format!(
             "Downloaded {} ({} bytes) in {:?}",
             url,
-            download_size,
+            downloaded_size,
             Instant::now().duration_since(download_start),
         )
     );
 
-    std::fs::rename(temp_destination_file, destination_file)
-        .map_err(|err| format!("Unable to rename: {:?}", err))?;
-
-    Ok(())
+    Ok(resumed)
 }
 
 pub fn download_genesis_if_missing(
@@ -272,6 +459,7 @@ pub fn download_snapshot_archive<'a, 'b>(
         incremental_snapshot_archives_dir,
         maximum_full_snapshot_archives_to_retain,
         maximum_incremental_snapshot_archives_to_retain,
+        None,
     );
 
     let snapshot_archives_remote_dir =
@@ -310,16 +498,32 @@ pub fn download_snapshot_archive<'a, 'b>(
             return Ok(());
         }
 
-        match download_file(
+        let partial_path = partial_file_path(&destination_path);
+        match download_partial_file(
             &format!(
                 "http://{}/{}",
                 rpc_addr,
                 destination_path.file_name().unwrap().to_str().unwrap()
             ),
-            &destination_path,
+            &partial_path,
             use_progress_bar,
             progress_notify_callback,
-        ) {
+        )
+        .and_then(|resumed| {
+            if resumed {
+                verify_resumed_snapshot_archive(
+                    &partial_path,
+                    &destination_path,
+                    desired_snapshot_hash,
+                )
+            } else {
+                Ok(())
+            }
+        })
+        .and_then(|()| {
+            fs::rename(&partial_path, &destination_path)
+                .map_err(|err| format!("Unable to rename: {:?}", err))
+        }) {
             Ok(()) => return Ok(()),
             Err(err) => info!("{}", err),
         }
@@ -329,3 +533,251 @@ pub fn download_snapshot_archive<'a, 'b>(
         desired_snapshot_hash.0, rpc_addr
     ))
 }
+
+/// Verify a resumed snapshot archive download before it is renamed to `destination_path`
+///
+/// The node may have replaced the archive between the two parts of a resumed download, and not
+/// every node sends an ETag to catch that, so the archive is read through and its manifest checked
+/// against the desired slot and hash.  While being verified, the archive is moved into
+/// a temporary directory that is cleaned up by `remove_tmp_snapshot_archives()` if the validator
+/// halts.  If the verification fails, the download is discarded.
+fn verify_resumed_snapshot_archive(
+    partial_path: &Path,
+    destination_path: &Path,
+    desired_snapshot_hash: (Slot, Hash),
+) -> Result<(), String> {
+    let file_name = destination_path.file_name().expect("file_name");
+    let verify_dir = destination_path.with_file_name(format!(
+        "{}resumed-{}",
+        snapshot_utils::TMP_SNAPSHOT_ARCHIVE_PREFIX,
+        file_name.to_str().expect("to_str")
+    ));
+    let verify_path = verify_dir.join(file_name);
+    let _ignored = fs::remove_dir_all(&verify_dir);
+    fs::create_dir_all(&verify_dir).map_err(|err| err.to_string())?;
+    fs::rename(partial_path, &verify_path).map_err(|err| err.to_string())?;
+
+    let result = match snapshot_utils::verify_snapshot_archive_structure(&verify_path) {
+        Ok(verification) if (verification.slot, verification.hash) == desired_snapshot_hash => {
+            fs::rename(&verify_path, partial_path).map_err(|err| err.to_string())
+        }
+        Ok(verification) => Err(format!(
+            "Resumed download of {:?} is for slot {} and hash {}",
+            destination_path, verification.slot, verification.hash
+        )),
+        Err(err) => Err(format!(
+            "Resumed download of {:?} failed verification: {}",
+            destination_path, err
+        )),
+    };
+    let _ignored = fs::remove_dir_all(&verify_dir);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::{
+            io::{BufRead, BufReader, Write},
+            net::TcpListener,
+            sync::{Arc, Mutex},
+            thread,
+        },
+    };
+
+    const ETAG: &str = "\"test-etag\"";
+
+    /// How the test server answers range requests
+    #[derive(Clone, Copy)]
+    enum RangeSupport {
+        /// Responds with `206 Partial Content`, unless `If-Range` does not match
+        Supported,
+        /// Responds with the whole file, as if it does not support range requests
+        Ignored,
+        /// Responds with `416 Range Not Satisfiable`
+        NotSatisfiable,
+    }
+
+    /// The range related headers of a request the test server received
+    #[derive(Debug, PartialEq, Eq)]
+    struct RangeHeaders {
+        range: Option<String>,
+        if_range: Option<String>,
+    }
+
+    /// Serve `content` over HTTP, returning its URL and the requests received so far
+    fn serve(
+        content: Vec<u8>,
+        range_support: RangeSupport,
+    ) -> (String, Arc<Mutex<Vec<RangeHeaders>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/file", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = Arc::clone(&requests);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut headers = RangeHeaders {
+                    range: None,
+                    if_range: None,
+                };
+                for line in BufReader::new(&stream).lines() {
+                    let line = line.unwrap();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        let value = Some(value.trim().to_string());
+                        match name.to_ascii_lowercase().as_str() {
+                            "range" => headers.range = value,
+                            "if-range" => headers.if_range = value,
+                            _ => {}
+                        }
+                    }
+                }
+                let range_start = headers
+                    .range
+                    .as_deref()
+                    .and_then(|range| range.strip_prefix("bytes="))
+                    .and_then(|range| range.strip_suffix('-'))
+                    .map(|range_start| range_start.parse::<usize>().unwrap());
+                let if_range_matches = headers
+                    .if_range
+                    .as_deref()
+                    .map_or(true, |if_range| if_range == ETAG);
+                received.lock().unwrap().push(headers);
+
+                let (status, content_range, body) = match (range_start, range_support) {
+                    (Some(start), RangeSupport::Supported) if if_range_matches => (
+                        "206 Partial Content",
+                        Some(format!(
+                            "bytes {}-{}/{}",
+                            start,
+                            content.len() - 1,
+                            content.len()
+                        )),
+                        &content[start..],
+                    ),
+                    (Some(_), RangeSupport::NotSatisfiable) => (
+                        "416 Range Not Satisfiable",
+                        Some(format!("bytes */{}", content.len())),
+                        &content[..0],
+                    ),
+                    _ => ("200 OK", None, &content[..]),
+                };
+                let mut response = format!(
+                    "HTTP/1.1 {}\r\nETag: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+                    status,
+                    ETAG,
+                    body.len()
+                );
+                if let Some(content_range) = content_range {
+                    response.push_str(&format!("Content-Range: {}\r\n", content_range));
+                }
+                response.push_str("\r\n");
+                stream.write_all(response.as_bytes()).unwrap();
+                stream.write_all(body).unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    fn new_content() -> Vec<u8> {
+        (0..1_000).map(|i| i as u8).collect()
+    }
+
+    /// Start a download of `url` that was interrupted after `partial_content`
+    fn new_partial_file(url: &str, partial_content: &[u8]) -> (tempfile::TempDir, PathBuf) {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let partial_file = temp_dir.path().join("file.partial");
+        fs::write(&partial_file, partial_content).unwrap();
+        PartialFileSource {
+            url: url.to_string(),
+            etag: Some(ETAG.to_string()),
+        }
+        .write(&partial_file)
+        .unwrap();
+        (temp_dir, partial_file)
+    }
+
+    #[test]
+    fn test_download_partial_file_resumes_range() {
+        let content = new_content();
+        let (url, requests) = serve(content.clone(), RangeSupport::Supported);
+        let (_temp_dir, partial_file) = new_partial_file(&url, &content[..400]);
+
+        assert_eq!(
+            download_partial_file(&url, &partial_file, false, &mut None),
+            Ok(true)
+        );
+        assert_eq!(fs::read(&partial_file).unwrap(), content);
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![RangeHeaders {
+                range: Some("bytes=400-".to_string()),
+                if_range: Some(ETAG.to_string()),
+            }],
+        );
+        // the source is only kept while the download is incomplete
+        assert!(!PartialFileSource::path(&partial_file).exists());
+    }
+
+    #[test]
+    fn test_download_partial_file_range_ignored() {
+        let content = new_content();
+        let (url, requests) = serve(content.clone(), RangeSupport::Ignored);
+        let (_temp_dir, partial_file) = new_partial_file(&url, &[u8::MAX; 400]);
+
+        assert_eq!(
+            download_partial_file(&url, &partial_file, false, &mut None),
+            Ok(false)
+        );
+        assert_eq!(fs::read(&partial_file).unwrap(), content);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_download_partial_file_range_not_satisfiable() {
+        let content = new_content();
+        let (url, requests) = serve(content.clone(), RangeSupport::NotSatisfiable);
+        let (_temp_dir, partial_file) = new_partial_file(&url, &[u8::MAX; 400]);
+
+        assert_eq!(
+            download_partial_file(&url, &partial_file, false, &mut None),
+            Ok(false)
+        );
+        assert_eq!(fs::read(&partial_file).unwrap(), content);
+        // the whole file is requested again, without a range
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[1],
+            RangeHeaders {
+                range: None,
+                if_range: None,
+            },
+        );
+    }
+
+    #[test]
+    fn test_download_partial_file_other_source() {
+        let content = new_content();
+        let (url, requests) = serve(content.clone(), RangeSupport::Supported);
+        let (_temp_dir, partial_file) =
+            new_partial_file("http://127.0.0.1:1/other", &[u8::MAX; 400]);
+
+        assert_eq!(
+            download_partial_file(&url, &partial_file, false, &mut None),
+            Ok(false)
+        );
+        assert_eq!(fs::read(&partial_file).unwrap(), content);
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![RangeHeaders {
+                range: None,
+                if_range: None,
+            }],
+        );
+    }
+}
@@ -31,7 +31,8 @@ use {
         crds_gossip_pull::{CrdsFilter, ProcessPullStats, CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS},
         crds_value::{
             self, CrdsData, CrdsValue, CrdsValueLabel, EpochSlotsIndex, IncrementalSnapshotHashes,
-            LowestSlot, NodeInstance, SnapshotHashes, Version, Vote, MAX_WALLCLOCK,
+            LowestSlot, NodeInstance, SnapshotHashes, SnapshotService, Version, Vote,
+            MAX_WALLCLOCK,
         },
         epoch_slots::EpochSlots,
         gossip_error::GossipError,
@@ -382,7 +383,9 @@ fn retain_staked(values: &mut Vec<CrdsValue>, stakes: &HashMap<Pubkey, u64>) {
             // Unstaked nodes can still help repair.
             CrdsData::EpochSlots(_, _) => true,
             // Unstaked nodes can still serve snapshots.
-            CrdsData::SnapshotHashes(_) | CrdsData::IncrementalSnapshotHashes(_) => true,
+            CrdsData::SnapshotHashes(_)
+            | CrdsData::IncrementalSnapshotHashes(_)
+            | CrdsData::SnapshotService(_) => true,
             // Otherwise unstaked voting nodes will show up with no version in
             // the various dashboards.
             CrdsData::Version(_) => true,
@@ -1012,6 +1015,12 @@ impl ClusterInfo {
         Ok(())
     }
 
+    /// advertise the address this node serves snapshot archives from over HTTP
+    pub fn push_snapshot_service(&self, address: SocketAddr) {
+        let message = CrdsData::SnapshotService(SnapshotService::new(self.id(), address));
+        self.push_message(CrdsValue::new_signed(message, &self.keypair()));
+    }
+
     pub fn push_vote_at_index(&self, vote: Transaction, vote_index: u8) {
         assert!((vote_index as usize) < MAX_LOCKOUT_HISTORY);
         let self_pubkey = self.id();
@@ -1210,6 +1219,13 @@ impl ClusterInfo {
             .cloned()
     }
 
+    /// Returns the address the node serves snapshot archives from, if it advertises one.
+    pub fn get_snapshot_service_for_node(&self, pubkey: &Pubkey) -> Option<SocketAddr> {
+        let gossip_crds = self.gossip.crds.read().unwrap();
+        let snapshot_service = gossip_crds.get::<&SnapshotService>(*pubkey)?;
+        Some(snapshot_service.address)
+    }
+
     /// Returns epoch-slots inserted since the given cursor.
     /// Excludes entries from nodes with unkown or different shred version.
     pub fn get_epoch_slots(&self, cursor: &mut Cursor) -> Vec<EpochSlots> {
@@ -1251,6 +1267,31 @@ impl ClusterInfo {
             .collect()
     }
 
+    /// all nodes that advertise a valid snapshot service address regardless of `shred_version`,
+    /// to download snapshot archives from while bootstrapping.  Nodes that do not advertise one,
+    /// such as ones running older versions, are not included.  Use
+    /// `get_snapshot_service_for_node()` for the address itself.
+    pub fn lookup_snapshot_peers(&self) -> Vec<ContactInfo> {
+        let self_pubkey = self.id();
+        let gossip_crds = self.gossip.crds.read().unwrap();
+        gossip_crds
+            .get_nodes_contact_info()
+            .filter(|x| {
+                x.id != self_pubkey
+                    && gossip_crds
+                        .get::<&SnapshotService>(x.id)
+                        .map(|snapshot_service| {
+                            ContactInfo::is_valid_address(
+                                &snapshot_service.address,
+                                &self.socket_addr_space,
+                            )
+                        })
+                        .unwrap_or(false)
+            })
+            .cloned()
+            .collect()
+    }
+
     // All nodes in gossip (including spy nodes) and the last time we heard about them
     pub fn all_peers(&self) -> Vec<(ContactInfo, u64)> {
         let gossip_crds = self.gossip.crds.read().unwrap();
@@ -4282,6 +4323,69 @@ RPC Enabled Nodes: 1"#;
         assert!(pulls.into_iter().all(|(addr, _)| addr == other_node.gossip));
     }
 
+    #[test]
+    fn test_lookup_snapshot_peers() {
+        let node_keypair = Arc::new(Keypair::new());
+        let cluster_info = ClusterInfo::new(
+            ContactInfo::new_localhost(&node_keypair.pubkey(), timestamp()),
+            node_keypair,
+            SocketAddrSpace::Unspecified,
+        );
+        let insert_snapshot_service = |keypair: &Keypair, address| {
+            let value = CrdsValue::new_signed(
+                CrdsData::SnapshotService(SnapshotService::new(keypair.pubkey(), address)),
+                keypair,
+            );
+            let mut gossip_crds = cluster_info.gossip.crds.write().unwrap();
+            gossip_crds
+                .insert(value, timestamp(), GossipRoute::LocalMessage)
+                .unwrap();
+        };
+        let snapshot_service = socketaddr!([127, 0, 0, 1], 1241);
+        // the node itself is never a snapshot peer
+        cluster_info.push_snapshot_service(snapshot_service);
+        cluster_info.flush_push_queue();
+        assert_eq!(
+            cluster_info.get_snapshot_service_for_node(&cluster_info.id()),
+            Some(snapshot_service)
+        );
+        assert!(cluster_info.lookup_snapshot_peers().is_empty());
+
+        let snapshot_peer_keypair = Keypair::new();
+        let snapshot_peer =
+            ContactInfo::new_localhost(&snapshot_peer_keypair.pubkey(), timestamp());
+        cluster_info.insert_info(snapshot_peer.clone());
+        insert_snapshot_service(&snapshot_peer_keypair, snapshot_service);
+
+        // like nodes running older versions, this node does not advertise a snapshot service
+        let other_peer = ContactInfo::new_localhost(&Pubkey::new_unique(), timestamp());
+        cluster_info.insert_info(other_peer.clone());
+        assert_eq!(
+            cluster_info.get_snapshot_service_for_node(&other_peer.id),
+            None
+        );
+
+        let invalid_address_peer_keypair = Keypair::new();
+        let invalid_address_peer =
+            ContactInfo::new_localhost(&invalid_address_peer_keypair.pubkey(), timestamp());
+        cluster_info.insert_info(invalid_address_peer);
+        insert_snapshot_service(&invalid_address_peer_keypair, socketaddr_any!());
+
+        // shred version does not matter when looking for snapshots to bootstrap from
+        let other_shred_version_peer_keypair = Keypair::new();
+        let mut other_shred_version_peer =
+            ContactInfo::new_localhost(&other_shred_version_peer_keypair.pubkey(), timestamp());
+        other_shred_version_peer.shred_version = 1;
+        cluster_info.insert_info(other_shred_version_peer.clone());
+        insert_snapshot_service(&other_shred_version_peer_keypair, snapshot_service);
+
+        let mut snapshot_peers = cluster_info.lookup_snapshot_peers();
+        snapshot_peers.sort_by_key(|peer| peer.id);
+        let mut expected = vec![snapshot_peer, other_shred_version_peer];
+        expected.sort_by_key(|peer| peer.id);
+        assert_eq!(snapshot_peers, expected);
+    }
+
     #[test]
     fn test_repair_peers() {
         let node_keypair = Arc::new(Keypair::new());
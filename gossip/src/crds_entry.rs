@@ -4,7 +4,7 @@ use {
         crds::VersionedCrdsValue,
         crds_value::{
             CrdsData, CrdsValue, CrdsValueLabel, IncrementalSnapshotHashes, LegacyVersion,
-            LowestSlot, SnapshotHashes, Version,
+            LowestSlot, SnapshotHashes, SnapshotService, Version,
         },
     },
     indexmap::IndexMap,
@@ -62,6 +62,11 @@ impl_crds_entry!(
     CrdsData::IncrementalSnapshotHashes(incremental_snapshot_hashes),
     incremental_snapshot_hashes
 );
+impl_crds_entry!(
+    SnapshotService,
+    CrdsData::SnapshotService(snapshot_service),
+    snapshot_service
+);
 
 impl<'a, 'b> CrdsEntry<'a, 'b> for &'a SnapshotHashes {
     type Key = Pubkey;
@@ -130,6 +135,9 @@ mod tests {
                 CrdsData::IncrementalSnapshotHashes(hash) => {
                     assert_eq!(crds.get::<&IncrementalSnapshotHashes>(key), Some(hash))
                 }
+                CrdsData::SnapshotService(service) => {
+                    assert_eq!(crds.get::<&SnapshotService>(key), Some(service))
+                }
                 _ => (),
             }
         }
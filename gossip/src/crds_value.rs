@@ -24,6 +24,7 @@ use {
         cmp::Ordering,
         collections::{hash_map::Entry, BTreeSet, HashMap},
         fmt,
+        net::SocketAddr,
     },
 };
 
@@ -92,6 +93,7 @@ pub enum CrdsData {
     NodeInstance(NodeInstance),
     DuplicateShred(DuplicateShredIndex, DuplicateShred),
     IncrementalSnapshotHashes(IncrementalSnapshotHashes),
+    SnapshotService(SnapshotService),
 }
 
 impl Sanitize for CrdsData {
@@ -129,6 +131,7 @@ impl Sanitize for CrdsData {
                 }
             }
             CrdsData::IncrementalSnapshotHashes(val) => val.sanitize(),
+            CrdsData::SnapshotService(val) => val.sanitize(),
         }
     }
 }
@@ -233,6 +236,32 @@ impl Sanitize for IncrementalSnapshotHashes {
     }
 }
 
+/// Address a node serves its snapshot archives from over HTTP.  This is published separately from
+/// `ContactInfo` so that the wire format of `ContactInfo` does not change for older nodes.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, AbiExample)]
+pub struct SnapshotService {
+    pub from: Pubkey,
+    pub address: SocketAddr,
+    pub wallclock: u64,
+}
+
+impl Sanitize for SnapshotService {
+    fn sanitize(&self) -> Result<(), SanitizeError> {
+        sanitize_wallclock(self.wallclock)?;
+        self.from.sanitize()
+    }
+}
+
+impl SnapshotService {
+    pub fn new(from: Pubkey, address: SocketAddr) -> Self {
+        Self {
+            from,
+            address,
+            wallclock: timestamp(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, AbiExample)]
 pub struct LowestSlot {
     pub from: Pubkey,
@@ -491,6 +520,7 @@ pub enum CrdsValueLabel {
     NodeInstance(Pubkey),
     DuplicateShred(DuplicateShredIndex, Pubkey),
     IncrementalSnapshotHashes(Pubkey),
+    SnapshotService(Pubkey),
 }
 
 impl fmt::Display for CrdsValueLabel {
@@ -509,6 +539,7 @@ impl fmt::Display for CrdsValueLabel {
             CrdsValueLabel::IncrementalSnapshotHashes(_) => {
                 write!(f, "IncrementalSnapshotHashes({})", self.pubkey())
             }
+            CrdsValueLabel::SnapshotService(_) => write!(f, "SnapshotService({})", self.pubkey()),
         }
     }
 }
@@ -527,6 +558,7 @@ impl CrdsValueLabel {
             CrdsValueLabel::NodeInstance(p) => *p,
             CrdsValueLabel::DuplicateShred(_, p) => *p,
             CrdsValueLabel::IncrementalSnapshotHashes(p) => *p,
+            CrdsValueLabel::SnapshotService(p) => *p,
         }
     }
 }
@@ -576,6 +608,7 @@ impl CrdsValue {
             CrdsData::NodeInstance(node) => node.wallclock,
             CrdsData::DuplicateShred(_, shred) => shred.wallclock,
             CrdsData::IncrementalSnapshotHashes(hash) => hash.wallclock,
+            CrdsData::SnapshotService(service) => service.wallclock,
         }
     }
     pub fn pubkey(&self) -> Pubkey {
@@ -591,6 +624,7 @@ impl CrdsValue {
             CrdsData::NodeInstance(node) => node.from,
             CrdsData::DuplicateShred(_, shred) => shred.from,
             CrdsData::IncrementalSnapshotHashes(hash) => hash.from,
+            CrdsData::SnapshotService(service) => service.from,
         }
     }
     pub fn label(&self) -> CrdsValueLabel {
@@ -608,6 +642,7 @@ impl CrdsValue {
             CrdsData::IncrementalSnapshotHashes(_) => {
                 CrdsValueLabel::IncrementalSnapshotHashes(self.pubkey())
             }
+            CrdsData::SnapshotService(_) => CrdsValueLabel::SnapshotService(self.pubkey()),
         }
     }
     pub fn contact_info(&self) -> Option<&ContactInfo> {
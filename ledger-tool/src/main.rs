@@ -40,7 +40,7 @@ use {
     solana_measure::{measure, measure::Measure},
     solana_runtime::{
         accounts_background_service::{
-            AbsRequestHandlers, AbsRequestSender, AccountsBackgroundService,
+            AbsConfig, AbsRequestHandlers, AbsRequestSender, AccountsBackgroundService,
             PrunedBanksRequestHandler, SnapshotRequestHandler,
         },
         accounts_db::{AccountsDbConfig, FillerAccountsConfig},
@@ -1139,9 +1139,7 @@ fn load_bank_forks(
     };
     let pruned_banks_receiver =
         AccountsBackgroundService::setup_bank_drop_callback(bank_forks.clone());
-    let pruned_banks_request_handler = PrunedBanksRequestHandler {
-        pruned_banks_receiver,
-    };
+    let pruned_banks_request_handler = PrunedBanksRequestHandler::new(pruned_banks_receiver);
     let abs_request_handler = AbsRequestHandlers {
         snapshot_request_handler,
         pruned_banks_request_handler,
@@ -1154,6 +1152,7 @@ fn load_bank_forks(
         process_options.accounts_db_caching_enabled,
         process_options.accounts_db_test_hash_calculation,
         None,
+        AbsConfig::default(),
     );
 
     let result = blockstore_processor::process_blockstore_from_root(
@@ -3269,6 +3268,7 @@ fn main() {
                                     snapshot_archive_format,
                                     maximum_full_snapshot_archives_to_retain,
                                     maximum_incremental_snapshot_archives_to_retain,
+                                    None,
                                 )
                                 .unwrap_or_else(|err| {
                                     eprintln!("Unable to create incremental snapshot: {}", err);
@@ -3293,6 +3293,7 @@ fn main() {
                                     snapshot_archive_format,
                                     maximum_full_snapshot_archives_to_retain,
                                     maximum_incremental_snapshot_archives_to_retain,
+                                    None,
                                 )
                                 .unwrap_or_else(|err| {
                                     eprintln!("Unable to create snapshot: {}", err);
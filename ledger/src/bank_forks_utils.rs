@@ -221,6 +221,16 @@ fn bank_forks_from_snapshot(
             process_options.accounts_db_config.clone(),
             accounts_update_notifier,
             exit,
+            None,
+            snapshot_config.duplicate_incremental_snapshot_archive_policy,
+            snapshot_utils::SnapshotLoadOptions {
+                extraction_buffer_bytes: snapshot_config.extraction_buffer_bytes,
+                zstd_dictionary: snapshot_config.zstd_dictionary.as_deref(),
+                unarchive_thread_budget: snapshot_config.unarchive_thread_budget,
+                verification_thread_budget: snapshot_config.verification_thread_budget,
+                untar_across_account_paths: snapshot_config.untar_across_account_paths,
+                ..snapshot_utils::SnapshotLoadOptions::default()
+            },
         )
         .expect("Load from snapshot failed");
 
@@ -1635,6 +1635,7 @@ fn run_final_hash_calc(bank: &Bank, on_halt_store_hash_raw_data_for_debug: bool)
         require_rooted_bank: false,
         run_in_background: false,
         store_hash_raw_data_for_debug: on_halt_store_hash_raw_data_for_debug,
+        thread_pool: None,
     });
 }
 
@@ -2192,6 +2192,7 @@ fn create_snapshot_to_hard_fork(
         ArchiveFormat::TarZstd,
         1,
         1,
+        None,
     )
     .unwrap();
     info!(
@@ -10,6 +10,7 @@ use {
     solana_runtime::{
         accounts::{test_utils::create_test_accounts, AccountAddressFilter, Accounts},
         accounts_db::AccountShrinkThreshold,
+        accounts_hash::CalcAccountsHashConfig,
         accounts_index::{AccountSecondaryIndexes, ScanConfig},
         ancestors::Ancestors,
         bank::*,
@@ -109,6 +110,7 @@ fn test_accounts_hash_bank_hash(bencher: &mut Bencher) {
             false,
             false,
             false,
+            None,
         ))
     });
 }
@@ -133,6 +135,54 @@ fn test_update_accounts_hash(bencher: &mut Bencher) {
     });
 }
 
+fn bench_calculate_accounts_hash_from_index(bencher: &mut Bencher, thread_pool_size: usize) {
+    solana_logger::setup();
+    let accounts = Accounts::new_with_config_for_benches(
+        vec![PathBuf::from("calculate_accounts_hash_from_index")],
+        &ClusterType::Development,
+        AccountSecondaryIndexes::default(),
+        false,
+        AccountShrinkThreshold::default(),
+    );
+    let mut pubkeys: Vec<Pubkey> = vec![];
+    create_test_accounts(&accounts, &mut pubkeys, 200_000, 0);
+    let ancestors = Ancestors::from(vec![0]);
+    let epoch_schedule = EpochSchedule::default();
+    let rent_collector = RentCollector::default();
+    let config = CalcAccountsHashConfig {
+        use_bg_thread_pool: false,
+        check_hash: false,
+        ancestors: Some(&ancestors),
+        epoch_schedule: &epoch_schedule,
+        rent_collector: &rent_collector,
+        store_detailed_debug_info_on_failure: false,
+        full_snapshot: None,
+        thread_pool_size: Some(thread_pool_size),
+        ..CalcAccountsHashConfig::default()
+    };
+    bencher.iter(|| {
+        accounts
+            .accounts_db
+            .calculate_accounts_hash_from_index(0, &config)
+            .unwrap();
+    });
+}
+
+#[bench]
+fn bench_calculate_accounts_hash_from_index_1_thread(bencher: &mut Bencher) {
+    bench_calculate_accounts_hash_from_index(bencher, 1);
+}
+
+#[bench]
+fn bench_calculate_accounts_hash_from_index_4_threads(bencher: &mut Bencher) {
+    bench_calculate_accounts_hash_from_index(bencher, 4);
+}
+
+#[bench]
+fn bench_calculate_accounts_hash_from_index_physical_cores(bencher: &mut Bencher) {
+    bench_calculate_accounts_hash_from_index(bencher, num_cpus::get_physical());
+}
+
 #[bench]
 fn test_accounts_delta_hash(bencher: &mut Bencher) {
     solana_logger::setup();
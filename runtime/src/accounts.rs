@@ -821,6 +821,7 @@ impl Accounts {
         ignore_mismatch: bool,
         store_detailed_debug_info: bool,
         use_bg_thread_pool: bool,
+        thread_pool: Option<&rayon::ThreadPool>,
     ) -> bool {
         if let Err(err) = self.accounts_db.verify_bank_hash_and_lamports_new(
             slot,
@@ -832,6 +833,7 @@ impl Accounts {
             ignore_mismatch,
             store_detailed_debug_info,
             use_bg_thread_pool,
+            thread_pool,
         ) {
             warn!("verify_bank_hash failed: {:?}, slot: {}", err, slot);
             false
@@ -916,6 +918,31 @@ impl Accounts {
             .map(|_| collector)
     }
 
+    /// Like `load_by_program_with_filter()`, but for the accounts owned by any of `program_ids`,
+    /// in a single scan
+    pub fn load_by_program_ids_with_filter<F: Fn(&AccountSharedData) -> bool>(
+        &self,
+        ancestors: &Ancestors,
+        bank_id: BankId,
+        program_ids: &[Pubkey],
+        filter: F,
+        config: &ScanConfig,
+    ) -> ScanResult<Vec<TransactionAccount>> {
+        let mut collector = Vec::new();
+        self.accounts_db
+            .scan_accounts(
+                ancestors,
+                bank_id,
+                |some_account_tuple| {
+                    Self::load_while_filtering(&mut collector, some_account_tuple, |account| {
+                        program_ids.contains(account.owner()) && filter(account)
+                    })
+                },
+                config,
+            )
+            .map(|_| collector)
+    }
+
     fn calc_scan_result_size(account: &AccountSharedData) -> usize {
         account.data().len()
             + std::mem::size_of::<AccountSharedData>()
@@ -5,13 +5,14 @@
 mod stats;
 use {
     crate::{
-        accounts_db::CalcAccountsHashDataSource,
-        accounts_hash::CalcAccountsHashConfig,
+        accounts_db::{CalcAccountsHashDataSource, SnapshotStorages},
+        accounts_hash::{CalcAccountsHashConfig, HashStats},
         bank::{Bank, BankSlotDelta, DropCallback},
         bank_forks::BankForks,
-        snapshot_config::SnapshotConfig,
+        snapshot_config::{SnapshotConfig, SnapshotRequestKind},
         snapshot_package::{self, AccountsPackage, AccountsPackageType, SnapshotType},
         snapshot_utils::{self, SnapshotError},
+        sorted_storages::SortedStorages,
     },
     crossbeam_channel::{Receiver, SendError, Sender},
     log::*,
@@ -27,7 +28,7 @@ use {
         fmt::{Debug, Formatter},
         sync::{
             atomic::{AtomicBool, AtomicU64, Ordering},
-            Arc, RwLock,
+            Arc, Mutex, RwLock,
         },
         thread::{self, sleep, Builder, JoinHandle},
         time::{Duration, Instant},
@@ -351,6 +352,7 @@ impl SnapshotRequestHandler {
                         rent_collector: snapshot_root_bank.rent_collector(),
                         store_detailed_debug_info_on_failure: false,
                         full_snapshot: None,
+                        ..CalcAccountsHashConfig::default()
                     },
                 )
                 .unwrap();
@@ -432,6 +434,73 @@ impl SnapshotRequestHandler {
         Ok(snapshot_root_bank.block_height())
     }
 
+    /// Handle the next snapshot request like `handle_snapshot_requests()`, but only flush the
+    /// accounts cache and calculate the accounts hash of its bank, from the storages
+    ///
+    /// The accounts hash is calculated the same way as for a real request, so the flush is needed
+    /// for the storages to hold the bank's state.  Nothing is cleaned or shrunk, the accounts hash
+    /// is not stored, and no accounts package is sent.  Returns the calculated accounts hash, if
+    /// there was a request to handle.
+    pub fn handle_snapshot_requests_dry_run(
+        &self,
+        accounts_db_caching_enabled: bool,
+        last_full_snapshot_slot: Option<Slot>,
+    ) -> Option<Hash> {
+        let (snapshot_request, accounts_package_type, num_outstanding_requests, _) =
+            self.get_next_snapshot_request(last_full_snapshot_slot)?;
+        let snapshot_root_bank = snapshot_request.snapshot_root_bank;
+
+        let mut flush_accounts_cache_time = Measure::start("flush_accounts_cache_time");
+        if accounts_db_caching_enabled {
+            snapshot_root_bank.force_flush_accounts_cache();
+        }
+        flush_accounts_cache_time.stop();
+
+        let mut hash_time = Measure::start("hash_time");
+        let (accounts_hash, _capitalization) = snapshot_root_bank
+            .accounts()
+            .accounts_db
+            .calculate_accounts_hash(
+                CalcAccountsHashDataSource::Storages,
+                snapshot_root_bank.slot(),
+                &CalcAccountsHashConfig {
+                    use_bg_thread_pool: true,
+                    check_hash: false,
+                    ancestors: None,
+                    epoch_schedule: snapshot_root_bank.epoch_schedule(),
+                    rent_collector: snapshot_root_bank.rent_collector(),
+                    store_detailed_debug_info_on_failure: false,
+                    full_snapshot: None,
+                    ..CalcAccountsHashConfig::default()
+                },
+            )
+            .unwrap(); // unwrap here will never fail since check_hash = false
+        hash_time.stop();
+
+        info!(
+            "Dry run of snapshot request. accounts package type: {:?}, slot: {}, accounts hash: {}",
+            accounts_package_type,
+            snapshot_root_bank.slot(),
+            accounts_hash,
+        );
+        datapoint_info!(
+            "handle_snapshot_requests_dry_run",
+            ("slot", snapshot_root_bank.slot(), i64),
+            (
+                "num-outstanding-requests",
+                num_outstanding_requests as i64,
+                i64
+            ),
+            (
+                "flush_accounts_cache_time_us",
+                flush_accounts_cache_time.as_us(),
+                i64
+            ),
+            ("hash_time_us", hash_time.as_us(), i64),
+        );
+        Some(accounts_hash)
+    }
+
     /// Check if a SnapshotError should be treated as 'fatal' by SnapshotRequestHandler, and
     /// `handle_snapshot_requests()` in particular.  Fatal errors will cause the node to shutdown.
     /// Non-fatal errors are logged and then swallowed.
@@ -450,10 +519,19 @@ impl SnapshotRequestHandler {
             SnapshotError::PathToFileNameError(..) => true,
             SnapshotError::FileNameToStrError(..) => true,
             SnapshotError::ParseSnapshotArchiveFileNameError(..) => true,
-            SnapshotError::MismatchedBaseSlot(..) => true,
+            SnapshotError::MismatchedBaseSlot { .. } => true,
             SnapshotError::NoSnapshotArchives => true,
             SnapshotError::MismatchedSlotHash(..) => true,
             SnapshotError::VerifySlotDeltas(..) => true,
+            SnapshotError::ArchiveStructure(..) => true,
+            SnapshotError::PeerConsensusNotReached(..) => true,
+            SnapshotError::MismatchedGenesisConfig(..) => true,
+            SnapshotError::EpochAccountsHash(..) => true,
+            SnapshotError::MissingZstdDictionary(..) => true,
+            SnapshotError::MismatchedZstdDictionary { .. } => true,
+            SnapshotError::NoSnapshotArchivesAsOfSlot(..) => true,
+            SnapshotError::NoSnapshotArchiveAtSlot { .. } => true,
+            SnapshotError::DuplicateIncrementalSnapshotArchives { .. } => true,
         }
     }
 }
@@ -474,6 +552,17 @@ impl AbsRequestSender {
         self.snapshot_request_sender.is_some()
     }
 
+    /// Returns the number of snapshot requests sent but not yet received by the service
+    ///
+    /// If this keeps growing while banks are rooted quickly, the background service is not
+    /// keeping up.  Returns 0 if snapshot creation is not enabled.
+    pub fn queue_len(&self) -> usize {
+        self.snapshot_request_sender
+            .as_ref()
+            .map(SnapshotRequestSender::len)
+            .unwrap_or_default()
+    }
+
     pub fn send_snapshot_request(
         &self,
         snapshot_request: SnapshotRequest,
@@ -486,26 +575,128 @@ impl AbsRequestSender {
     }
 }
 
+/// What to do when dropping a pruned bank fails
+///
+/// `AccountsDb::purge_slot()` does not report failures yet, so outside of tests drops do not fail
+/// and this policy has no effect.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PrunedBankDropFailurePolicy {
+    /// Count the failure and move on, leaving the bank's storages behind
+    Ignore,
+    /// Count the failure and re-queue the drop, to retry it when the next requests are handled
+    Retry,
+}
+
+impl Default for PrunedBankDropFailurePolicy {
+    fn default() -> Self {
+        Self::Ignore
+    }
+}
+
 #[derive(Debug)]
 pub struct PrunedBanksRequestHandler {
     pub pruned_banks_receiver: DroppedSlotsReceiver,
+    drop_failure_policy: PrunedBankDropFailurePolicy,
+    /// Drops that failed and were re-queued by `PrunedBankDropFailurePolicy::Retry`
+    failed_drops: Mutex<Vec<(Slot, BankId)>>,
+    /// The number of drops that failed, including failed retries
+    num_drop_failures: AtomicU64,
+    /// Fail this many of the next drops, to exercise the failure handling
+    #[cfg(test)]
+    fault_injection_drops: std::sync::atomic::AtomicUsize,
 }
 
 impl PrunedBanksRequestHandler {
+    pub fn new(pruned_banks_receiver: DroppedSlotsReceiver) -> Self {
+        Self {
+            pruned_banks_receiver,
+            drop_failure_policy: PrunedBankDropFailurePolicy::default(),
+            failed_drops: Mutex::default(),
+            num_drop_failures: AtomicU64::default(),
+            #[cfg(test)]
+            fault_injection_drops: std::sync::atomic::AtomicUsize::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_drop_failure_policy(
+        self,
+        drop_failure_policy: PrunedBankDropFailurePolicy,
+    ) -> Self {
+        Self {
+            drop_failure_policy,
+            ..self
+        }
+    }
+
+    /// The number of pruned bank drops that have failed so far
+    ///
+    /// A growing count means storages of pruned banks are accumulating.
+    pub fn num_drop_failures(&self) -> u64 {
+        self.num_drop_failures.load(Ordering::Relaxed)
+    }
+
+    /// Fail the next `count` drops, to exercise the failure handling
+    #[cfg(test)]
+    fn inject_drop_failures(&self, count: usize) {
+        self.fault_injection_drops.store(count, Ordering::Relaxed);
+    }
+
     pub fn handle_request(&self, bank: &Bank, is_serialized_with_abs: bool) -> usize {
         let mut count = 0;
-        for (pruned_slot, pruned_bank_id) in self.pruned_banks_receiver.try_iter() {
+        let failed_drops = std::mem::take(&mut *self.failed_drops.lock().unwrap());
+        for (pruned_slot, pruned_bank_id) in failed_drops
+            .into_iter()
+            .chain(self.pruned_banks_receiver.try_iter())
+        {
             count += 1;
-            bank.rc.accounts.accounts_db.purge_slot(
-                pruned_slot,
-                pruned_bank_id,
-                is_serialized_with_abs,
-            );
+            if !self.drop_pruned_bank(bank, pruned_slot, pruned_bank_id, is_serialized_with_abs) {
+                let num_drop_failures = self.num_drop_failures.fetch_add(1, Ordering::Relaxed) + 1;
+                warn!(
+                    "Failed to drop pruned bank {} for slot {}, policy: {:?}, failures so far: {}",
+                    pruned_bank_id, pruned_slot, self.drop_failure_policy, num_drop_failures,
+                );
+                if self.drop_failure_policy == PrunedBankDropFailurePolicy::Retry {
+                    self.failed_drops
+                        .lock()
+                        .unwrap()
+                        .push((pruned_slot, pruned_bank_id));
+                }
+            }
         }
 
         count
     }
 
+    /// Purge the storages of a pruned bank, returning false if that failed
+    fn drop_pruned_bank(
+        &self,
+        bank: &Bank,
+        pruned_slot: Slot,
+        pruned_bank_id: BankId,
+        is_serialized_with_abs: bool,
+    ) -> bool {
+        #[cfg(test)]
+        {
+            if self
+                .fault_injection_drops
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                    count.checked_sub(1)
+                })
+                .is_ok()
+            {
+                return false;
+            }
+        }
+
+        bank.rc.accounts.accounts_db.purge_slot(
+            pruned_slot,
+            pruned_bank_id,
+            is_serialized_with_abs,
+        );
+        true
+    }
+
     fn remove_dead_slots(
         &self,
         bank: &Bank,
@@ -522,6 +713,7 @@ impl PrunedBanksRequestHandler {
                 "remove_slots_timing",
                 ("remove_slots_time", *total_remove_slots_time, i64),
                 ("removed_slots_count", *removed_slots_count, i64),
+                ("drop_failures_count", self.num_drop_failures(), i64),
             );
             *total_remove_slots_time = 0;
             *removed_slots_count = 0;
@@ -552,6 +744,20 @@ impl AbsRequestHandlers {
     }
 }
 
+/// Optional behavior of the `AccountsBackgroundService`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AbsConfig {
+    /// if set, store an accounts hash checkpoint every this many slots, see
+    /// `AccountsHashCheckpointer`
+    pub accounts_hash_checkpoint_interval_slots: Option<Slot>,
+    /// handle requests without cleaning or shrinking, to measure how long they take on real
+    /// state: accounts hashes are calculated from the storages and shrink candidates are
+    /// selected, but no accounts hashes are stored and no accounts packages are sent.  The
+    /// accounts cache is still flushed and pruned banks are still purged, so that memory stays
+    /// bounded.  Note that without accounts packages, epoch accounts hashes are never calculated.
+    pub dry_run: bool,
+}
+
 pub struct AccountsBackgroundService {
     t_background: JoinHandle<()>,
 }
@@ -564,11 +770,15 @@ impl AccountsBackgroundService {
         accounts_db_caching_enabled: bool,
         test_hash_calculation: bool,
         mut last_full_snapshot_slot: Option<Slot>,
+        config: AbsConfig,
     ) -> Self {
         info!("AccountsBackgroundService active");
         let exit = exit.clone();
         let mut consumed_budget = 0;
         let mut last_cleaned_block_height = 0;
+        let mut accounts_hash_checkpointer = config
+            .accounts_hash_checkpoint_interval_slots
+            .map(AccountsHashCheckpointer::new);
         let mut removed_slots_count = 0;
         let mut total_remove_slots_time = 0;
         let mut last_expiration_check_time = Instant::now();
@@ -582,10 +792,31 @@ impl AccountsBackgroundService {
                         break;
                     }
                     let start_time = Instant::now();
+                    stats.record_snapshot_request_queue_len(
+                        request_handlers
+                            .snapshot_request_handler
+                            .snapshot_request_receiver
+                            .len(),
+                    );
 
                     // Grab the current root bank
                     let bank = bank_forks.read().unwrap().root_bank().clone();
 
+                    if config.dry_run {
+                        Self::handle_requests_dry_run(
+                            &bank_forks,
+                            &bank,
+                            &request_handlers,
+                            accounts_db_caching_enabled,
+                            last_full_snapshot_slot,
+                            &mut removed_slots_count,
+                            &mut total_remove_slots_time,
+                        );
+                        stats.record_and_maybe_submit(start_time.elapsed());
+                        sleep(Duration::from_millis(INTERVAL_MS));
+                        continue;
+                    }
+
                     // Purge accounts of any dead slots
                     request_handlers
                         .pruned_banks_request_handler
@@ -646,6 +877,14 @@ impl AccountsBackgroundService {
                         // as any later snapshots that are taken are of
                         // slots >= bank.slot()
                         bank.flush_accounts_cache_if_needed();
+                        Self::evict_banks(&bank_forks, &bank);
+                    }
+
+                    // Like snapshot requests, checkpoints must wait for startup verification
+                    if let Some(accounts_hash_checkpointer) = accounts_hash_checkpointer.as_mut() {
+                        if bank.is_startup_verification_complete() {
+                            accounts_hash_checkpointer.maybe_request_checkpoint(&bank);
+                        }
                     }
 
                     if let Some(snapshot_block_height_result) = snapshot_block_height_option_result
@@ -689,11 +928,60 @@ impl AccountsBackgroundService {
                     stats.record_and_maybe_submit(start_time.elapsed());
                     sleep(Duration::from_millis(INTERVAL_MS));
                 }
+                if let Some(accounts_hash_checkpointer) = accounts_hash_checkpointer {
+                    accounts_hash_checkpointer.join().unwrap();
+                }
             })
             .unwrap();
         Self { t_background }
     }
 
+    /// Handle the pending requests without cleaning or shrinking, see `AbsConfig::dry_run`
+    #[allow(clippy::too_many_arguments)]
+    fn handle_requests_dry_run(
+        bank_forks: &RwLock<BankForks>,
+        bank: &Bank,
+        request_handlers: &AbsRequestHandlers,
+        accounts_db_caching_enabled: bool,
+        last_full_snapshot_slot: Option<Slot>,
+        removed_slots_count: &mut usize,
+        total_remove_slots_time: &mut u64,
+    ) {
+        // Pruned banks are never part of the measured state, and keeping their slots would grow
+        // the accounts cache without bound
+        request_handlers
+            .pruned_banks_request_handler
+            .remove_dead_slots(bank, removed_slots_count, total_remove_slots_time);
+
+        // Like snapshot requests, see `new()`, the accounts hash must not be calculated
+        // concurrently with startup verification
+        let handled_snapshot_request = bank.is_startup_verification_complete()
+            && request_handlers
+                .snapshot_request_handler
+                .handle_snapshot_requests_dry_run(
+                    accounts_db_caching_enabled,
+                    last_full_snapshot_slot,
+                )
+                .is_some();
+
+        if accounts_db_caching_enabled {
+            bank.flush_accounts_cache_if_needed();
+            Self::evict_banks(bank_forks, bank);
+        }
+        if !handled_snapshot_request {
+            let mut select_time = Measure::start("select_shrink_candidates_time");
+            let num_shrink_candidates = bank.rc.accounts.accounts_db.select_shrink_candidates();
+            select_time.stop();
+            if num_shrink_candidates > 0 {
+                datapoint_info!(
+                    "accounts_background_service_dry_run",
+                    ("num_shrink_candidates", num_shrink_candidates, i64),
+                    ("select_shrink_candidates_us", select_time.as_us(), i64),
+                );
+            }
+        }
+    }
+
     /// Should be called immediately after bank_fork_utils::load_bank_forks(), and as such, there
     /// should only be one bank, the root bank, in `bank_forks`
     /// All banks added to `bank_forks` will be descended from the root bank, and thus will inherit
@@ -728,6 +1016,138 @@ impl AccountsBackgroundService {
             *last_expiration_check_time = now;
         }
     }
+
+    /// Flush the cached accounts of the banks that `BankForks` wants evicted, see
+    /// `BankEvictionPolicy`
+    ///
+    /// The slots are collected under the `BankForks` read lock, but flushed after releasing it, so
+    /// that inserting and rooting banks does not wait on the writes to storage.  Returns the number
+    /// of banks evicted.
+    fn evict_banks(bank_forks: &RwLock<BankForks>, bank: &Bank) -> usize {
+        let slots_to_evict = bank_forks.read().unwrap().slots_to_evict();
+        let accounts_db = &bank.rc.accounts.accounts_db;
+        slots_to_evict
+            .into_iter()
+            .filter(|slot| accounts_db.flush_unrooted_slot_cache(*slot))
+            .count()
+    }
+}
+
+/// Calculates accounts hash checkpoints for `AccountsBackgroundService`
+///
+/// Checkpoints are independent of snapshots; they give frequent accounts hashes (retrievable
+/// with `AccountsDb::get_accounts_hash()`) to detect divergence without creating archives.  The
+/// accounts hashes are calculated on a dedicated thread, so a checkpoint does not delay cleaning,
+/// shrinking, or snapshot requests.
+struct AccountsHashCheckpointer {
+    checkpoint_interval_slots: Slot,
+    last_checkpoint_slot: Option<Slot>,
+    /// set from when a checkpoint is requested until its accounts hash has been stored
+    in_progress: Arc<AtomicBool>,
+    checkpoint_sender: Sender<(Arc<Bank>, SnapshotStorages)>,
+    t_checkpoint: JoinHandle<()>,
+}
+
+impl AccountsHashCheckpointer {
+    fn new(checkpoint_interval_slots: Slot) -> Self {
+        let in_progress = Arc::new(AtomicBool::new(false));
+        let (checkpoint_sender, checkpoint_receiver) =
+            crossbeam_channel::unbounded::<(Arc<Bank>, SnapshotStorages)>();
+        let t_checkpoint = {
+            let in_progress = in_progress.clone();
+            Builder::new()
+                .name("solAcctsHashChk".to_string())
+                .spawn(move || {
+                    for (bank, snapshot_storages) in checkpoint_receiver.iter() {
+                        Self::checkpoint_accounts_hash(&bank, &snapshot_storages);
+                        in_progress.store(false, Ordering::Release);
+                    }
+                })
+                .unwrap()
+        };
+        Self {
+            checkpoint_interval_slots,
+            last_checkpoint_slot: None,
+            in_progress,
+            checkpoint_sender,
+            t_checkpoint,
+        }
+    }
+
+    /// Request a checkpoint of `bank` if it has crossed a checkpoint interval
+    ///
+    /// Since roots may skip slots, a checkpoint is taken at the first root at or after each
+    /// multiple of `checkpoint_interval_slots`.  If the previous checkpoint is still being
+    /// calculated, the checkpoint is taken at a later root instead.
+    ///
+    /// Like for snapshot requests, the accounts cache is flushed on the calling thread, since
+    /// only the background service may flush it; then the storages are handed to the checkpoint
+    /// thread.  Returns true if a checkpoint was requested.
+    fn maybe_request_checkpoint(&mut self, bank: &Arc<Bank>) -> bool {
+        if self.checkpoint_interval_slots == 0 {
+            return false;
+        }
+        let last_checkpoint_interval =
+            self.last_checkpoint_slot.unwrap_or_default() / self.checkpoint_interval_slots;
+        if bank.slot() / self.checkpoint_interval_slots <= last_checkpoint_interval
+            || self.in_progress.load(Ordering::Acquire)
+        {
+            return false;
+        }
+
+        // The accounts hash is calculated from the storages, so all roots <= `bank.slot()` must
+        // be flushed first.
+        bank.force_flush_accounts_cache();
+        let snapshot_storages = bank.get_snapshot_storages(None);
+        self.in_progress.store(true, Ordering::Release);
+        self.checkpoint_sender
+            .send((bank.clone(), snapshot_storages))
+            .unwrap();
+        self.last_checkpoint_slot = Some(bank.slot());
+        true
+    }
+
+    /// Calculate the accounts hash of `bank` from `snapshot_storages` and store it
+    fn checkpoint_accounts_hash(bank: &Bank, snapshot_storages: &SnapshotStorages) -> Hash {
+        let mut measure = Measure::start("accounts_hash_checkpoint");
+        let accounts_db = &bank.rc.accounts.accounts_db;
+        let (accounts_hash, _capitalization) = accounts_db
+            .calculate_accounts_hash_from_storages(
+                &CalcAccountsHashConfig {
+                    use_bg_thread_pool: true,
+                    check_hash: false,
+                    ancestors: None,
+                    epoch_schedule: bank.epoch_schedule(),
+                    rent_collector: bank.rent_collector(),
+                    store_detailed_debug_info_on_failure: false,
+                    full_snapshot: None,
+                    ..CalcAccountsHashConfig::default()
+                },
+                &SortedStorages::new(snapshot_storages),
+                HashStats::default(),
+            )
+            .unwrap(); // unwrap here will never fail since check_hash = false
+        accounts_db.set_accounts_hash(bank.slot(), accounts_hash);
+        measure.stop();
+
+        info!(
+            "accounts hash checkpoint at slot {}: {}",
+            bank.slot(),
+            accounts_hash
+        );
+        datapoint_info!(
+            "accounts_hash_checkpoint",
+            ("slot", bank.slot(), i64),
+            ("time_us", measure.as_us(), i64),
+        );
+        accounts_hash
+    }
+
+    /// Wait for the requested checkpoints to be calculated, then stop the checkpoint thread
+    fn join(self) -> thread::Result<()> {
+        drop(self.checkpoint_sender);
+        self.t_checkpoint.join()
+    }
 }
 
 /// Get the AccountsPackageType from a given SnapshotRequest
@@ -737,27 +1157,24 @@ fn new_accounts_package_type(
     snapshot_config: &SnapshotConfig,
     last_full_snapshot_slot: Option<Slot>,
 ) -> AccountsPackageType {
-    let block_height = snapshot_request.snapshot_root_bank.block_height();
     match snapshot_request.request_type {
         SnapshotRequestType::EpochAccountsHash => AccountsPackageType::EpochAccountsHash,
-        _ => {
-            if snapshot_utils::should_take_full_snapshot(
-                block_height,
-                snapshot_config.full_snapshot_archive_interval_slots,
-            ) {
+        _ => match (
+            snapshot_request
+                .snapshot_root_bank
+                .is_snapshot_boundary(snapshot_config),
+            last_full_snapshot_slot,
+        ) {
+            (Some(SnapshotRequestKind::Full), _) => {
                 AccountsPackageType::Snapshot(SnapshotType::FullSnapshot)
-            } else if snapshot_utils::should_take_incremental_snapshot(
-                block_height,
-                snapshot_config.incremental_snapshot_archive_interval_slots,
-                last_full_snapshot_slot,
-            ) {
+            }
+            (Some(SnapshotRequestKind::Incremental), Some(last_full_snapshot_slot)) => {
                 AccountsPackageType::Snapshot(SnapshotType::IncrementalSnapshot(
-                    last_full_snapshot_slot.unwrap(),
+                    last_full_snapshot_slot,
                 ))
-            } else {
-                AccountsPackageType::AccountsHashVerifier
             }
-        }
+            _ => AccountsPackageType::AccountsHashVerifier,
+        },
     }
 }
 
@@ -793,6 +1210,7 @@ mod test {
         crate::{epoch_accounts_hash, genesis_utils::create_genesis_config},
         crossbeam_channel::unbounded,
         solana_sdk::{account::AccountSharedData, epoch_schedule::EpochSchedule, pubkey::Pubkey},
+        std::fs,
     };
 
     #[test]
@@ -800,9 +1218,7 @@ mod test {
         let genesis = create_genesis_config(10);
         let bank0 = Arc::new(Bank::new_for_tests(&genesis.genesis_config));
         let (pruned_banks_sender, pruned_banks_receiver) = unbounded();
-        let pruned_banks_request_handler = PrunedBanksRequestHandler {
-            pruned_banks_receiver,
-        };
+        let pruned_banks_request_handler = PrunedBanksRequestHandler::new(pruned_banks_receiver);
 
         // Store an account in slot 0
         let account_key = Pubkey::new_unique();
@@ -820,6 +1236,130 @@ mod test {
         assert!(bank0.rc.accounts.scan_slot(0, |_| Some(())).is_empty());
     }
 
+    #[test]
+    fn test_pruned_banks_request_handler_drop_failure_policy() {
+        let genesis = create_genesis_config(10);
+        let bank0 = Arc::new(Bank::new_for_tests(&genesis.genesis_config));
+        let account_key = Pubkey::new_unique();
+        // the banks are kept alive, because dropping them would purge their slots directly
+        let new_bank_with_account = |slot| {
+            let bank = Bank::new_from_parent(&bank0, &Pubkey::default(), slot);
+            bank.store_account(
+                &account_key,
+                &AccountSharedData::new(264, 0, &Pubkey::default()),
+            );
+            bank
+        };
+        let is_slot_purged = |slot| bank0.rc.accounts.scan_slot(slot, |_| Some(())).is_empty();
+
+        // with the retry policy, a failed drop is re-queued and succeeds the next time
+        let (pruned_banks_sender, pruned_banks_receiver) = unbounded();
+        let pruned_banks_request_handler = PrunedBanksRequestHandler::new(pruned_banks_receiver)
+            .with_drop_failure_policy(PrunedBankDropFailurePolicy::Retry);
+        let bank1 = new_bank_with_account(1);
+        pruned_banks_sender.send((1, bank1.bank_id())).unwrap();
+        pruned_banks_request_handler.inject_drop_failures(1);
+        assert_eq!(pruned_banks_request_handler.handle_request(&bank0, true), 1);
+        assert!(!is_slot_purged(1));
+        assert_eq!(pruned_banks_request_handler.num_drop_failures(), 1);
+        assert_eq!(pruned_banks_request_handler.handle_request(&bank0, true), 1);
+        assert!(is_slot_purged(1));
+        assert_eq!(pruned_banks_request_handler.num_drop_failures(), 1);
+        assert_eq!(pruned_banks_request_handler.handle_request(&bank0, true), 0);
+
+        // with the default policy, a failed drop is only counted
+        let (pruned_banks_sender, pruned_banks_receiver) = unbounded();
+        let pruned_banks_request_handler = PrunedBanksRequestHandler::new(pruned_banks_receiver);
+        let bank2 = new_bank_with_account(2);
+        pruned_banks_sender.send((2, bank2.bank_id())).unwrap();
+        pruned_banks_request_handler.inject_drop_failures(1);
+        assert_eq!(pruned_banks_request_handler.handle_request(&bank0, true), 1);
+        assert_eq!(pruned_banks_request_handler.handle_request(&bank0, true), 0);
+        assert!(!is_slot_purged(2));
+        assert_eq!(pruned_banks_request_handler.num_drop_failures(), 1);
+    }
+
+    #[test]
+    fn test_accounts_hash_checkpoints() {
+        solana_logger::setup();
+        const CHECKPOINT_INTERVAL_SLOTS: Slot = 5;
+        const NUM_SLOTS: Slot = 23;
+        let genesis = create_genesis_config(1_000_000);
+        let mut bank = Arc::new(Bank::new_for_tests(&genesis.genesis_config));
+        let mut accounts_hash_checkpointer =
+            AccountsHashCheckpointer::new(CHECKPOINT_INTERVAL_SLOTS);
+        let wait_for_checkpoint = |accounts_hash_checkpointer: &AccountsHashCheckpointer| {
+            while accounts_hash_checkpointer
+                .in_progress
+                .load(Ordering::Acquire)
+            {
+                sleep(Duration::from_millis(1));
+            }
+        };
+        let mut checkpoint_slots = Vec::new();
+        for _ in 0..NUM_SLOTS {
+            bank = Arc::new(Bank::new_from_parent(
+                &bank,
+                &Pubkey::default(),
+                bank.slot() + 1,
+            ));
+            bank.squash();
+            if accounts_hash_checkpointer.maybe_request_checkpoint(&bank) {
+                checkpoint_slots.push(bank.slot());
+                wait_for_checkpoint(&accounts_hash_checkpointer);
+            }
+        }
+
+        let expected_checkpoint_slots: Vec<_> = (CHECKPOINT_INTERVAL_SLOTS..=NUM_SLOTS)
+            .step_by(CHECKPOINT_INTERVAL_SLOTS as usize)
+            .collect();
+        assert_eq!(checkpoint_slots, expected_checkpoint_slots);
+        assert_eq!(
+            accounts_hash_checkpointer.last_checkpoint_slot,
+            expected_checkpoint_slots.last().copied()
+        );
+        for slot in checkpoint_slots {
+            assert_ne!(
+                bank.rc.accounts.accounts_db.get_accounts_hash(slot),
+                Hash::default()
+            );
+        }
+
+        // if roots skip past a checkpoint interval, the next root is checkpointed instead
+        let skipped_slot = bank.slot() + CHECKPOINT_INTERVAL_SLOTS + 1;
+        let bank = Arc::new(Bank::new_from_parent(
+            &bank,
+            &Pubkey::default(),
+            skipped_slot,
+        ));
+        bank.squash();
+        assert!(accounts_hash_checkpointer.maybe_request_checkpoint(&bank));
+        assert_eq!(
+            accounts_hash_checkpointer.last_checkpoint_slot,
+            Some(skipped_slot)
+        );
+        accounts_hash_checkpointer.join().unwrap();
+        let accounts_hash = bank.get_accounts_hash();
+        assert_eq!(
+            bank.update_accounts_hash_with_index_option(
+                CalcAccountsHashDataSource::Index,
+                false,
+                false
+            ),
+            accounts_hash
+        );
+
+        // while a checkpoint is being calculated, later roots are not checkpointed
+        let mut accounts_hash_checkpointer =
+            AccountsHashCheckpointer::new(CHECKPOINT_INTERVAL_SLOTS);
+        accounts_hash_checkpointer
+            .in_progress
+            .store(true, Ordering::Release);
+        assert!(!accounts_hash_checkpointer.maybe_request_checkpoint(&bank));
+        assert_eq!(accounts_hash_checkpointer.last_checkpoint_slot, None);
+        accounts_hash_checkpointer.join().unwrap();
+    }
+
     /// Ensure that unhandled snapshot requests are properly re-enqueued or dropped
     ///
     /// The snapshot request handler should be flexible and handle re-queueing unhandled snapshot
@@ -957,4 +1497,176 @@ mod test {
             .get_next_snapshot_request(Some(60))
             .is_none());
     }
+
+    /// Ensure that a dry run handles the requests without cleaning or shrinking, but still purges
+    /// pruned banks and flushes the accounts cache
+    #[test]
+    fn test_accounts_background_service_dry_run() {
+        solana_logger::setup();
+        let genesis = create_genesis_config(1_000_000);
+        let bank0 = Bank::new_for_tests(&genesis.genesis_config);
+        bank0.set_startup_verification_complete();
+        let bank_forks = Arc::new(RwLock::new(BankForks::new(bank0)));
+
+        // Root several banks that update and then zero out the same accounts, flushing the early
+        // ones to storages, so there is work left to flush and clean
+        let pubkeys: Vec<_> = (0..10).map(|_| Pubkey::new_unique()).collect();
+        for slot in 1..=6 {
+            let parent = bank_forks.read().unwrap().root_bank();
+            let bank = Bank::new_from_parent(&parent, &Pubkey::default(), slot);
+            let lamports = if slot > 3 { 0 } else { slot };
+            for pubkey in &pubkeys {
+                bank.store_account(
+                    pubkey,
+                    &AccountSharedData::new(lamports, 0, &Pubkey::default()),
+                );
+            }
+            bank.freeze();
+            let mut bank_forks = bank_forks.write().unwrap();
+            bank_forks.insert(bank);
+            bank_forks.set_root(slot, &AbsRequestSender::default(), None);
+            if slot <= 3 {
+                bank_forks.root_bank().force_flush_accounts_cache();
+            }
+        }
+        let root_bank = bank_forks.read().unwrap().root_bank();
+        // a pruned bank, which is kept alive so only the request handler would purge its slot
+        let pruned_bank = Bank::new_from_parent(&root_bank, &Pubkey::default(), 7);
+        pruned_bank.store_account(
+            &pubkeys[0],
+            &AccountSharedData::new(1, 0, &Pubkey::default()),
+        );
+        pruned_bank.freeze();
+
+        let accounts_db = &root_bank.rc.accounts.accounts_db;
+        let accounts_state = || {
+            let mut storages: Vec<_> = accounts_db
+                .storage
+                .map
+                .iter()
+                .flat_map(|slot_stores| {
+                    let slot = *slot_stores.key();
+                    slot_stores
+                        .value()
+                        .read()
+                        .unwrap()
+                        .values()
+                        .map(|store| {
+                            (
+                                slot,
+                                store.append_vec_id(),
+                                fs::read(store.get_path()).unwrap(),
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+            storages.sort_unstable_by_key(|(slot, append_vec_id, _)| (*slot, *append_vec_id));
+            let mut accounts = root_bank.get_all_accounts_with_modified_slots().unwrap();
+            accounts.sort_unstable_by_key(|(pubkey, _, slot)| (*pubkey, *slot));
+            (storages, accounts)
+        };
+        let (storages_before, accounts_before) = accounts_state();
+        let mut cached_slots_before = accounts_db.accounts_cache.cached_frozen_slots();
+        cached_slots_before.sort_unstable();
+        assert_eq!(cached_slots_before, vec![4, 5, 6, 7]);
+
+        let (accounts_package_sender, accounts_package_receiver) = unbounded();
+        let (snapshot_request_sender, snapshot_request_receiver) = unbounded();
+        let (pruned_banks_sender, pruned_banks_receiver) = unbounded();
+        snapshot_request_sender
+            .send(SnapshotRequest {
+                snapshot_root_bank: Arc::clone(&root_bank),
+                status_cache_slot_deltas: Vec::default(),
+                request_type: SnapshotRequestType::Snapshot,
+                enqueued: Instant::now(),
+            })
+            .unwrap();
+        pruned_banks_sender
+            .send((pruned_bank.slot(), pruned_bank.bank_id()))
+            .unwrap();
+        let request_handlers = AbsRequestHandlers {
+            snapshot_request_handler: SnapshotRequestHandler {
+                snapshot_config: SnapshotConfig::default(),
+                snapshot_request_sender: snapshot_request_sender.clone(),
+                snapshot_request_receiver,
+                accounts_package_sender,
+            },
+            pruned_banks_request_handler: PrunedBanksRequestHandler::new(pruned_banks_receiver),
+        };
+        let exit = Arc::new(AtomicBool::new(false));
+        let accounts_background_service = AccountsBackgroundService::new(
+            Arc::clone(&bank_forks),
+            &exit,
+            request_handlers,
+            true,
+            false,
+            None,
+            AbsConfig {
+                dry_run: true,
+                ..AbsConfig::default()
+            },
+        );
+
+        let start = Instant::now();
+        while !snapshot_request_sender.is_empty() || !pruned_banks_sender.is_empty() {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            sleep(Duration::from_millis(10));
+        }
+        // let a few more iterations run, with no requests pending
+        sleep(Duration::from_millis(INTERVAL_MS * 3));
+        exit.store(true, Ordering::Relaxed);
+        accounts_background_service.join().unwrap();
+
+        assert!(accounts_package_receiver.is_empty());
+        // the snapshot request flushed the rooted slots, and the pruned slot was purged
+        assert!(accounts_db.accounts_cache.cached_frozen_slots().is_empty());
+        assert!(accounts_db.storage.get_slot_stores(7).is_none());
+        // nothing was cleaned or shrunk: the storages from before are untouched, and the flush
+        // only added storages for the rooted slots that were cached
+        let (storages_after, accounts_after) = accounts_state();
+        assert_eq!(accounts_after, accounts_before);
+        assert_eq!(
+            storages_after
+                .iter()
+                .filter(|(slot, _, _)| *slot <= 3)
+                .collect::<Vec<_>>(),
+            storages_before.iter().collect::<Vec<_>>()
+        );
+        let flushed_slots: Vec<_> = storages_after
+            .iter()
+            .map(|(slot, _, _)| *slot)
+            .filter(|slot| *slot > 3)
+            .collect();
+        assert!(flushed_slots.contains(&6));
+        assert!(flushed_slots.iter().all(|slot| *slot <= 6));
+    }
+
+    #[test]
+    fn test_abs_request_sender_queue_len() {
+        assert_eq!(AbsRequestSender::default().queue_len(), 0);
+
+        let genesis_config_info = create_genesis_config(10);
+        let bank = Arc::new(Bank::new_for_tests(&genesis_config_info.genesis_config));
+        let (snapshot_request_sender, snapshot_request_receiver) = unbounded();
+        let abs_request_sender = AbsRequestSender::new(snapshot_request_sender);
+        assert_eq!(abs_request_sender.queue_len(), 0);
+
+        // nothing is receiving, so every request sent stays queued
+        for i in 1..=5 {
+            abs_request_sender
+                .send_snapshot_request(SnapshotRequest {
+                    snapshot_root_bank: Arc::clone(&bank),
+                    status_cache_slot_deltas: Vec::default(),
+                    request_type: SnapshotRequestType::Snapshot,
+                    enqueued: Instant::now(),
+                })
+                .unwrap();
+            assert_eq!(abs_request_sender.queue_len(), i);
+        }
+
+        // receiving a request takes it off the queue
+        snapshot_request_receiver.try_recv().unwrap();
+        assert_eq!(abs_request_sender.queue_len(), 4);
+    }
 }
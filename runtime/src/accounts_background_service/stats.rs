@@ -33,6 +33,11 @@ impl StatsManager {
         self.maybe_submit();
     }
 
+    /// Record how many snapshot requests are waiting to be handled at the start of this iteration
+    pub(super) fn record_snapshot_request_queue_len(&mut self, queue_len: usize) {
+        self.stats.record_snapshot_request_queue_len(queue_len);
+    }
+
     /// Maybe submit the datapoints based on how long it has been since the previous submission.
     fn maybe_submit(&mut self) {
         let duration_since_previous_submit = Instant::now() - self.previous_submit;
@@ -68,6 +73,16 @@ impl StatsManager {
                 self.stats.max_runtime.as_millis() as i64,
                 i64
             ),
+            (
+                "snapshot_request_queue_len",
+                self.stats.snapshot_request_queue_len as i64,
+                i64
+            ),
+            (
+                "max_snapshot_request_queue_len",
+                self.stats.max_snapshot_request_queue_len as i64,
+                i64
+            ),
         );
 
         // reset the stats back to default
@@ -88,6 +103,10 @@ struct Stats {
     min_runtime: Duration,
     /// Maximum runtime seen for one iteration
     max_runtime: Duration,
+    /// Number of snapshot requests waiting to be handled, as of the latest iteration
+    snapshot_request_queue_len: usize,
+    /// Maximum number of snapshot requests seen waiting to be handled
+    max_snapshot_request_queue_len: usize,
 }
 
 impl Stats {
@@ -99,6 +118,12 @@ impl Stats {
         self.max_runtime = self.max_runtime.max(runtime);
     }
 
+    /// Record how many snapshot requests are waiting to be handled
+    fn record_snapshot_request_queue_len(&mut self, queue_len: usize) {
+        self.snapshot_request_queue_len = queue_len;
+        self.max_snapshot_request_queue_len = self.max_snapshot_request_queue_len.max(queue_len);
+    }
+
     /// Calculate the mean runtime of all iterations
     ///
     /// Requires that the number of iterations recorded is in the range [0, u32::MAX].
@@ -117,6 +142,8 @@ impl Default for Stats {
             cumulative_runtime: Duration::ZERO,
             min_runtime: Duration::MAX,
             max_runtime: Duration::ZERO,
+            snapshot_request_queue_len: 0,
+            max_snapshot_request_queue_len: 0,
         }
     }
 }
@@ -285,6 +285,16 @@ impl AccountsCache {
         }
     }
 
+    /// The lowest slot in the cache, if any
+    pub fn min_slot(&self) -> Option<Slot> {
+        self.cache.iter().map(|item| *item.key()).min()
+    }
+
+    /// The number of accounts in the cache, across all slots
+    pub fn num_accounts(&self) -> usize {
+        self.cache.iter().map(|item| item.value().len()).sum()
+    }
+
     pub fn contains_any_slots(&self, max_slot_inclusive: Slot) -> bool {
         self.cache.iter().any(|e| e.key() <= &max_slot_inclusive)
     }
@@ -24,14 +24,14 @@ use {
         accounts_background_service::{DroppedSlotsSender, SendDroppedBankCallback},
         accounts_cache::{AccountsCache, CachedAccount, SlotCache},
         accounts_hash::{
-            AccountsHash, CalcAccountsHashConfig, CalculateHashIntermediate, HashStats,
-            PreviousPass,
+            AccountsHash, AccountsHashError, AccountsHashThroughput, CalcAccountsHashConfig,
+            CalculateHashIntermediate, HashStats, OwnerSubtotal, OwnerSubtotals, PreviousPass,
         },
         accounts_index::{
-            AccountIndexGetResult, AccountSecondaryIndexes, AccountsIndex, AccountsIndexConfig,
-            AccountsIndexRootsStats, AccountsIndexScanResult, IndexKey, IndexValue, IsCached,
-            RefCount, ScanConfig, ScanResult, SlotList, UpsertReclaim, ZeroLamport,
-            ACCOUNTS_INDEX_CONFIG_FOR_BENCHMARKS, ACCOUNTS_INDEX_CONFIG_FOR_TESTING,
+            AccountIndex, AccountIndexGetResult, AccountSecondaryIndexes, AccountsIndex,
+            AccountsIndexConfig, AccountsIndexRootsStats, AccountsIndexScanResult, IndexKey,
+            IndexValue, IsCached, RefCount, ScanConfig, ScanResult, SlotList, UpsertReclaim,
+            ZeroLamport, ACCOUNTS_INDEX_CONFIG_FOR_BENCHMARKS, ACCOUNTS_INDEX_CONFIG_FOR_TESTING,
         },
         accounts_index_storage::Startup,
         accounts_update_notifier_interface::AccountsUpdateNotifier,
@@ -49,6 +49,9 @@ use {
         read_only_accounts_cache::ReadOnlyAccountsCache,
         rent_collector::RentCollector,
         rent_paying_accounts_by_partition::RentPayingAccountsByPartition,
+        secondary_index::SecondaryIndexSizeStats,
+        snapshot_archive_info::{IncrementalSnapshotArchiveInfo, SnapshotArchiveInfoGetter},
+        snapshot_utils::{self, SnapshotError},
         sorted_storages::SortedStorages,
         storable_accounts::StorableAccounts,
         verify_accounts_hash_in_background::VerifyAccountsHashInBackground,
@@ -63,6 +66,7 @@ use {
     rand::{thread_rng, Rng},
     rayon::{prelude::*, ThreadPool},
     serde::{Deserialize, Serialize},
+    solana_bloom::bloom::{AtomicBloom, Bloom},
     solana_measure::{measure, measure::Measure},
     solana_rayon_threadlimit::get_thread_count,
     solana_sdk::{
@@ -79,7 +83,7 @@ use {
     std::{
         borrow::{Borrow, Cow},
         boxed::Box,
-        collections::{hash_map::Entry, BTreeSet, HashMap, HashSet},
+        collections::{hash_map::Entry, BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
         convert::TryFrom,
         hash::{Hash as StdHash, Hasher as StdHasher},
         io::{Error as IoError, Result as IoResult},
@@ -88,7 +92,7 @@ use {
         str::FromStr,
         sync::{
             atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
-            Arc, Condvar, Mutex, MutexGuard, RwLock,
+            Arc, Condvar, Mutex, MutexGuard, Once, RwLock,
         },
         thread::{sleep, Builder},
         time::{Duration, Instant},
@@ -113,6 +117,10 @@ pub const DEFAULT_NUM_DIRS: u32 = 4;
 // When calculating hashes, it is helpful to break the pubkeys found into bins based on the pubkey value.
 // More bins means smaller vectors to sort, copy, etc.
 pub const PUBKEY_BINS_FOR_CALCULATING_HASHES: usize = 65536;
+/// The false positive rate of the pubkey bloom filter built while calculating the accounts hash
+const PUBKEY_BLOOM_FALSE_POSITIVE_RATE: f64 = 0.01;
+/// The number of accounts hash calculations remembered for `AccountsDb::recent_hash_timings()`
+pub const MAX_RECENT_ACCOUNTS_HASH_TIMINGS: usize = 64;
 pub const NUM_SCAN_PASSES_DEFAULT: usize = 2;
 
 // Without chunks, we end up with 1 output vec for each outer snapshot storage.
@@ -337,6 +345,8 @@ pub const ACCOUNTS_DB_CONFIG_FOR_TESTING: AccountsDbConfig = AccountsDbConfig {
     ancient_append_vecs: false,
     skip_initial_hash_calc: false,
     exhaustively_verify_refcounts: false,
+    generate_index_threads: None,
+    startup_progress: None,
 };
 pub const ACCOUNTS_DB_CONFIG_FOR_BENCHMARKS: AccountsDbConfig = AccountsDbConfig {
     index: Some(ACCOUNTS_INDEX_CONFIG_FOR_BENCHMARKS),
@@ -348,6 +358,8 @@ pub const ACCOUNTS_DB_CONFIG_FOR_BENCHMARKS: AccountsDbConfig = AccountsDbConfig
     ancient_append_vecs: false,
     skip_initial_hash_calc: false,
     exhaustively_verify_refcounts: false,
+    generate_index_threads: None,
+    startup_progress: None,
 };
 
 pub type BinnedHashData = Vec<Vec<CalculateHashIntermediate>>;
@@ -375,6 +387,20 @@ pub struct AccountsAddRootTiming {
     pub store_us: u64,
 }
 
+/// Summary of applying the account deltas from an incremental snapshot archive
+/// with `AccountsDb::apply_incremental_delta()`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ApplyReport {
+    /// the incremental snapshot's base slot, which was the highest root before applying
+    pub base_slot: Slot,
+    /// the incremental snapshot's slot, which is the highest root after applying
+    pub slot: Slot,
+    /// the slots whose accounts were stored and rooted, in ascending order
+    pub slots: Vec<Slot>,
+    /// the number of accounts stored across all slots
+    pub num_accounts: usize,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct FillerAccountsConfig {
     /// Number of filler accounts
@@ -406,6 +432,25 @@ pub struct AccountsDbConfig {
     pub ancient_append_vecs: bool,
     pub skip_initial_hash_calc: bool,
     pub exhaustively_verify_refcounts: bool,
+    /// number of threads used to generate the accounts index at startup
+    /// if None, the number of threads depends on whether the disk index is enabled
+    pub generate_index_threads: Option<usize>,
+    /// if set, the startup work is counted here, so progress can be reported while loading
+    pub startup_progress: Option<Arc<StartupProgress>>,
+}
+
+/// Progress of the work done at startup, while loading a bank from a snapshot, see
+/// `AccountsDbConfig::startup_progress`
+///
+/// The counters only ever increase, so they can be polled from another thread.
+#[derive(Debug, Default)]
+pub struct StartupProgress {
+    /// number of accounts inserted into the accounts index by `generate_index()`
+    pub index_entries: AtomicU64,
+    /// number of passes of the startup accounts hash verification that have completed
+    pub hash_passes_completed: AtomicUsize,
+    /// total number of passes of the startup accounts hash verification; 0 until it starts
+    pub hash_passes_total: AtomicUsize,
 }
 
 pub struct FoundStoredAccount<'a> {
@@ -423,18 +468,24 @@ pub enum AccountShrinkThreshold {
     /// Measure the total space sparseness across all candidates
     /// And select the candidates by using the top sparse account storage entries to shrink.
     /// The value is the overall shrink threshold measured as ratio of the total live bytes
-    /// over the total bytes.
-    TotalSpace { shrink_ratio: f64 },
+    /// over the total bytes.  Storage entries that would reclaim fewer than `min_reclaim_bytes`
+    /// are not shrunk, to avoid churning on tiny stores.
+    TotalSpace {
+        shrink_ratio: f64,
+        min_reclaim_bytes: u64,
+    },
     /// Use the following option to shrink all stores whose alive ratio is below
     /// the specified threshold.
     IndividualStore { shrink_ratio: f64 },
 }
 pub const DEFAULT_ACCOUNTS_SHRINK_OPTIMIZE_TOTAL_SPACE: bool = true;
 pub const DEFAULT_ACCOUNTS_SHRINK_RATIO: f64 = 0.80;
+pub const DEFAULT_ACCOUNTS_SHRINK_MIN_RECLAIM_BYTES: u64 = 0;
 // The default extra account space in percentage from the ideal target
 const DEFAULT_ACCOUNTS_SHRINK_THRESHOLD_OPTION: AccountShrinkThreshold =
     AccountShrinkThreshold::TotalSpace {
         shrink_ratio: DEFAULT_ACCOUNTS_SHRINK_RATIO,
+        min_reclaim_bytes: DEFAULT_ACCOUNTS_SHRINK_MIN_RECLAIM_BYTES,
     };
 
 impl Default for AccountShrinkThreshold {
@@ -889,6 +940,13 @@ pub enum BankHashVerificationError {
     MismatchedBankHash,
     MissingBankHash,
     MismatchedTotalLamports(u64, u64),
+    AccountsHash(AccountsHashError),
+}
+
+impl From<AccountsHashError> for BankHashVerificationError {
+    fn from(error: AccountsHashError) -> Self {
+        Self::AccountsHash(error)
+    }
 }
 
 #[derive(Default)]
@@ -1313,6 +1371,10 @@ pub struct AccountsDb {
 
     pub thread_pool_clean: ThreadPool,
 
+    /// Thread pools for accounts hash calculations, by number of threads, see
+    /// `CalcAccountsHashConfig::thread_pool_size`
+    accounts_hash_thread_pools: Mutex<HashMap<usize, Arc<ThreadPool>>>,
+
     /// Number of append vecs to create to maximize parallelism when scanning
     /// the accounts
     min_num_stores: usize,
@@ -1395,12 +1457,50 @@ pub struct AccountsDb {
     /// debug feature to scan every append vec and verify refcounts are equal
     exhaustively_verify_refcounts: bool,
 
+    /// number of threads used to generate the accounts index at startup, see AccountsDbConfig
+    generate_index_threads: Option<usize>,
+
+    /// progress of the startup work, see AccountsDbConfig
+    startup_progress: Option<Arc<StartupProgress>>,
+
     /// the full accounts hash calculation as of a predetermined block height 'N'
     /// to be included in the bank hash at a predetermined block height 'M'
     /// The cadence is once per epoch, all nodes calculate a full accounts hash as of a known slot calculated using 'N'
     /// Some time later (to allow for slow calculation time), the bank hash at a slot calculated using 'M' includes the full accounts hash.
     /// Thus, the state of all accounts on a validator is known to be correct at least once per epoch.
     pub epoch_accounts_hash_manager: EpochAccountsHashManager,
+
+    /// running average throughput of the accounts hash calculations from storages
+    pub(crate) accounts_hash_throughput: AccountsHashThroughput,
+
+    /// the slot and accounts hash of the last full snapshot, see `last_full_snapshot_accounts_hash()`
+    last_full_snapshot_accounts_hash: Mutex<Option<(Slot, Hash)>>,
+
+    /// when the last accounts hash calculation finished, see `time_since_last_accounts_hash()`
+    last_accounts_hash_time: Mutex<Option<Instant>>,
+
+    /// the slots and durations of the latest accounts hash calculations, oldest first, see
+    /// `recent_hash_timings()`
+    recent_accounts_hash_timings: Mutex<VecDeque<(Slot, Duration)>>,
+
+    /// true while lazy secondary indexes are not built yet, so loading and storing accounts skips
+    /// updating them.  Stores hold the read lock, so none races with starting the build.
+    secondary_indexes_deferred: RwLock<bool>,
+
+    /// builds lazy secondary indexes on the first indexed scan
+    build_secondary_indexes: Once,
+}
+
+/// Where an account is stored within a slot's storages, see `AccountsDb::storage_layout()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountLocation {
+    pub pubkey: Pubkey,
+    /// the storage (append vec) the account is stored in
+    pub store_id: AppendVecId,
+    /// the offset of the account within the storage
+    pub offset: usize,
+    /// the number of bytes the account occupies in the storage, including alignment
+    pub stored_size: usize,
 }
 
 #[derive(Debug, Default)]
@@ -2108,6 +2208,15 @@ pub fn make_min_priority_thread_pool() -> ThreadPool {
         .unwrap()
 }
 
+/// A thread pool for accounts hash calculations, see `CalcAccountsHashConfig::thread_pool_size`
+fn make_accounts_hash_thread_pool(num_threads: usize) -> ThreadPool {
+    rayon::ThreadPoolBuilder::new()
+        .thread_name(|i| format!("solAcctsHash{:02}", i))
+        .num_threads(num_threads)
+        .build()
+        .unwrap()
+}
+
 #[cfg(all(test, RUSTC_WITH_SPECIALIZATION))]
 impl solana_frozen_abi::abi_example::AbiExample for AccountsDb {
     fn example() -> Self {
@@ -2171,6 +2280,15 @@ trait AppendVecScan: Send + Sync + Clone {
     fn set_accum(&mut self, accum: BinnedHashData);
 }
 
+/// The accounts hash calculated from storages, along with what was collected during the same scan,
+/// see `AccountsDb::calculate_accounts_hash_and_collect_from_storages()`
+struct AccountsHashAndCollected {
+    accounts_hash: Hash,
+    capitalization: u64,
+    pubkey_bloom: Option<Bloom<Pubkey>>,
+    owner_subtotals: Option<OwnerSubtotals>,
+}
+
 #[derive(Clone)]
 /// state to keep while scanning append vec accounts for hash calculation
 /// These would have been captured in a fn from within the scan function.
@@ -2185,15 +2303,47 @@ struct ScanState<'a> {
     bin_range: &'a Range<usize>,
     config: &'a CalcAccountsHashConfig<'a>,
     mismatch_found: Arc<AtomicU64>,
+    /// the first pubkey found live more than once, if `config.detect_duplicates`
+    duplicate_found: Arc<Mutex<Option<(Pubkey, Vec<Slot>)>>>,
+    /// pubkeys found so far in `current_slot`, only populated if `config.detect_duplicates`
+    pubkeys_in_current_slot: HashSet<Pubkey>,
+    /// used to find the live version of each pubkey, if `config.detect_duplicates`
+    accounts_index: &'a AccountInfoAccountsIndex,
+    /// the highest slot being scanned
+    max_slot: Slot,
     filler_account_suffix: Option<&'a Pubkey>,
     range: usize,
     sort_time: Arc<AtomicU64>,
     pubkey_to_bin_index: usize,
 }
 
+impl<'a> ScanState<'a> {
+    /// Returns the slots `pubkey` is live in, if the version in `current_slot` makes it live more
+    /// than once
+    ///
+    /// The hash uses the latest version found in the storages, so a version in a later slot than
+    /// the live version in the index is live too, and so is a second version in the same slot.
+    /// Versions in earlier slots than the live one are expected until they are cleaned.
+    fn duplicate_live_slots(&mut self, pubkey: &Pubkey) -> Option<Vec<Slot>> {
+        if !self.pubkeys_in_current_slot.insert(*pubkey) {
+            return Some(vec![self.current_slot; 2]);
+        }
+        let live_slot =
+            match self
+                .accounts_index
+                .get(pubkey, self.config.ancestors, Some(self.max_slot))
+            {
+                AccountIndexGetResult::Found(lock, index) => lock.slot_list()[index].0,
+                AccountIndexGetResult::NotFound => return None,
+            };
+        (live_slot < self.current_slot).then(|| vec![live_slot, self.current_slot])
+    }
+}
+
 impl<'a> AppendVecScan for ScanState<'a> {
     fn set_slot(&mut self, slot: Slot) {
         self.current_slot = slot;
+        self.pubkeys_in_current_slot.clear();
     }
     fn filter(&mut self, pubkey: &Pubkey) -> bool {
         self.pubkey_to_bin_index = self.bin_calculator.bin_from_pubkey(pubkey);
@@ -2211,6 +2361,21 @@ impl<'a> AppendVecScan for ScanState<'a> {
         // when we are scanning with bin ranges, we don't need to use exact bin numbers. Subtract to make first bin we care about at index 0.
         self.pubkey_to_bin_index -= self.bin_range.start;
 
+        if (self.config.exclude_executable && loaded_account.executable())
+            || matches!(self.config.owner_filter, Some(owner) if loaded_account.owner() != &owner)
+        {
+            // Whether an account is excluded depends on its latest version, which is only known
+            // after de-dup.  So keep a zero-lamport version in its place, which de-dup eliminates
+            // if it is the latest version, instead of falling back to an older version.
+            self.init_accum(self.range);
+            self.accum[self.pubkey_to_bin_index].push(CalculateHashIntermediate::new(
+                Hash::default(),
+                0,
+                *pubkey,
+            ));
+            return;
+        }
+
         let balance = loaded_account.lamports();
         let loaded_hash = loaded_account.loaded_hash();
         let source_item = CalculateHashIntermediate::new(loaded_hash, balance, *pubkey);
@@ -2232,6 +2397,14 @@ impl<'a> AppendVecScan for ScanState<'a> {
                 self.mismatch_found.fetch_add(1, Ordering::Relaxed);
             }
         }
+        if self.config.detect_duplicates {
+            if let Some(slots) = self.duplicate_live_slots(pubkey) {
+                let mut duplicate_found = self.duplicate_found.lock().unwrap();
+                if duplicate_found.is_none() {
+                    *duplicate_found = Some((*pubkey, slots));
+                }
+            }
+        }
         self.init_accum(self.range);
         self.accum[self.pubkey_to_bin_index].push(source_item);
     }
@@ -2332,6 +2505,7 @@ impl AccountsDb {
                 .build()
                 .unwrap(),
             thread_pool_clean: make_min_priority_thread_pool(),
+            accounts_hash_thread_pools: Mutex::default(),
             min_num_stores: num_threads,
             bank_hashes: RwLock::new(bank_hashes),
             external_purge_slots_stats: PurgeStats::default(),
@@ -2357,7 +2531,15 @@ impl AccountsDb {
             num_hash_scan_passes,
             log_dead_slots: AtomicBool::new(true),
             exhaustively_verify_refcounts: false,
+            generate_index_threads: None,
+            startup_progress: None,
             epoch_accounts_hash_manager: EpochAccountsHashManager::new_invalid(),
+            accounts_hash_throughput: AccountsHashThroughput::default(),
+            last_full_snapshot_accounts_hash: Mutex::default(),
+            last_accounts_hash_time: Mutex::default(),
+            recent_accounts_hash_timings: Mutex::default(),
+            secondary_indexes_deferred: RwLock::default(),
+            build_secondary_indexes: Once::new(),
         }
     }
 
@@ -2428,11 +2610,20 @@ impl AccountsDb {
             .map(|config| config.exhaustively_verify_refcounts)
             .unwrap_or_default();
 
+        let generate_index_threads = accounts_db_config
+            .as_ref()
+            .and_then(|config| config.generate_index_threads);
+
+        let startup_progress = accounts_db_config
+            .as_ref()
+            .and_then(|config| config.startup_progress.clone());
+
         let filler_account_suffix = if filler_accounts_config.count > 0 {
             Some(solana_sdk::pubkey::new_rand())
         } else {
             None
         };
+        let secondary_indexes_deferred = account_indexes.lazy && !account_indexes.is_empty();
         let paths_is_empty = paths.is_empty();
         let mut new = Self {
             paths,
@@ -2450,6 +2641,9 @@ impl AccountsDb {
                 .as_ref()
                 .and_then(|x| x.write_cache_limit_bytes),
             exhaustively_verify_refcounts,
+            generate_index_threads,
+            startup_progress,
+            secondary_indexes_deferred: RwLock::new(secondary_indexes_deferred),
             ..Self::default_with_accounts_index(
                 accounts_index,
                 accounts_hash_cache_path,
@@ -4150,6 +4344,7 @@ impl AccountsDb {
     fn select_candidates_by_total_usage(
         shrink_slots: &ShrinkCandidates,
         shrink_ratio: f64,
+        min_reclaim_bytes: u64,
     ) -> (ShrinkCandidates, ShrinkCandidates) {
         struct StoreUsageInfo {
             slot: Slot,
@@ -4189,6 +4384,13 @@ impl AccountsDb {
         let mut shrink_slots_next_batch: ShrinkCandidates = HashMap::new();
         for usage in &store_usage {
             let store = &usage.store;
+            let current_store_size = store.total_bytes();
+            let after_shrink_size = Self::page_align(store.alive_bytes() as u64);
+            let bytes_saved = current_store_size.saturating_sub(after_shrink_size);
+            if bytes_saved < min_reclaim_bytes {
+                // not worth shrinking, no matter how sparse it is
+                continue;
+            }
             let alive_ratio = (total_alive_bytes as f64) / (total_bytes as f64);
             debug!("alive_ratio: {:?} store_id: {:?}, store_ratio: {:?} requirment: {:?}, total_bytes: {:?} total_alive_bytes: {:?}",
                 alive_ratio, usage.store.append_vec_id(), usage.alive_ratio, shrink_ratio, total_bytes, total_alive_bytes);
@@ -4208,9 +4410,6 @@ impl AccountsDb {
                     break;
                 }
             } else {
-                let current_store_size = store.total_bytes();
-                let after_shrink_size = Self::page_align(store.alive_bytes() as u64);
-                let bytes_saved = current_store_size.saturating_sub(after_shrink_size);
                 total_bytes -= bytes_saved;
                 shrink_slots
                     .entry(usage.slot)
@@ -4572,6 +4771,69 @@ impl AccountsDb {
         uncleaned_pubkeys.extend(pubkeys);
     }
 
+    /// Returns the number of storages of each rooted slot, to spot slots fragmented across many
+    /// storages
+    ///
+    /// Only the storage map is read; the storages themselves are not scanned.
+    pub fn storage_count_by_slot(&self) -> BTreeMap<Slot, usize> {
+        self.storage
+            .map
+            .iter()
+            .filter(|entry| self.accounts_index.is_alive_root(*entry.key()))
+            .map(|entry| (*entry.key(), entry.value().read().unwrap().len()))
+            .collect()
+    }
+
+    /// Returns the size of each configured secondary index, see `AccountSecondaryIndexes`
+    ///
+    /// This is cheap to call, since the sizes are kept as running counters as the indexes are
+    /// updated.  This is meant for tracking down the memory used by the secondary indexes.
+    ///
+    /// With lazy secondary indexes (`AccountSecondaryIndexes::lazy`), the indexes are only built
+    /// by the first query that uses them, so until then every size is reported as zero.
+    pub fn secondary_index_stats(&self) -> Vec<(AccountIndex, SecondaryIndexSizeStats)> {
+        self.accounts_index
+            .secondary_index_stats(&self.account_indexes)
+    }
+
+    /// Returns the current shrink candidate slots, each with a fragmentation score, sorted from
+    /// the most to the least fragmented
+    ///
+    /// The score is the fraction of the slot's storages that is not alive (with alive bytes page
+    /// aligned, as when deciding whether to shrink), from 0.0 (not fragmented) to 1.0 (nothing
+    /// alive).  This is meant for tuning `AccountShrinkThreshold`; nothing is shrunk.
+    pub fn shrink_candidates_scored(&self) -> Vec<(Slot, f64)> {
+        let mut scores: Vec<_> = self
+            .shrink_candidate_slots
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(slot, slot_shrink_candidates)| {
+                let (alive_bytes, total_bytes) = slot_shrink_candidates.values().fold(
+                    (0, 0),
+                    |(alive_bytes, total_bytes), store| {
+                        (
+                            alive_bytes + Self::page_align(store.alive_bytes() as u64),
+                            total_bytes + store.total_bytes(),
+                        )
+                    },
+                );
+                let score = if total_bytes == 0 {
+                    0.0
+                } else {
+                    (1.0 - alive_bytes as f64 / total_bytes as f64).clamp(0.0, 1.0)
+                };
+                (*slot, score)
+            })
+            .collect();
+        scores.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(a.0.cmp(&b.0))
+        });
+        scores
+    }
+
     pub fn shrink_candidate_slots(&self) -> usize {
         if !self.shrink_candidate_slots.lock().unwrap().is_empty() {
             // this can affect 'shrink_candidate_slots', so don't 'take' it until after this completes
@@ -4582,9 +4844,17 @@ impl AccountsDb {
             std::mem::take(&mut *self.shrink_candidate_slots.lock().unwrap());
 
         let (shrink_slots, shrink_slots_next_batch) = {
-            if let AccountShrinkThreshold::TotalSpace { shrink_ratio } = self.shrink_ratio {
+            if let AccountShrinkThreshold::TotalSpace {
+                shrink_ratio,
+                min_reclaim_bytes,
+            } = self.shrink_ratio
+            {
                 let (shrink_slots, shrink_slots_next_batch) =
-                    Self::select_candidates_by_total_usage(&shrink_candidates_slots, shrink_ratio);
+                    Self::select_candidates_by_total_usage(
+                        &shrink_candidates_slots,
+                        shrink_ratio,
+                        min_reclaim_bytes,
+                    );
                 (shrink_slots, Some(shrink_slots_next_batch))
             } else {
                 (shrink_candidates_slots, None)
@@ -4635,6 +4905,28 @@ impl AccountsDb {
         num_candidates
     }
 
+    /// Select the stores that `shrink_candidate_slots()` would shrink next, without shrinking them
+    /// or removing them from the shrink candidates
+    ///
+    /// Returns the number of stores selected.
+    pub fn select_shrink_candidates(&self) -> usize {
+        let shrink_candidate_slots = self.shrink_candidate_slots.lock().unwrap();
+        if let AccountShrinkThreshold::TotalSpace {
+            shrink_ratio,
+            min_reclaim_bytes,
+        } = self.shrink_ratio
+        {
+            let (shrink_slots, _shrink_slots_next_batch) = Self::select_candidates_by_total_usage(
+                &shrink_candidate_slots,
+                shrink_ratio,
+                min_reclaim_bytes,
+            );
+            shrink_slots.values().map(HashMap::len).sum()
+        } else {
+            shrink_candidate_slots.values().map(HashMap::len).sum()
+        }
+    }
+
     pub fn shrink_all_slots(&self, is_startup: bool, last_full_snapshot_slot: Option<Slot>) {
         let _guard = self.active_stats.activate(ActiveStatItem::Shrink);
         const DIRTY_STORES_CLEANING_THRESHOLD: usize = 10_000;
@@ -4778,6 +5070,10 @@ impl AccountsDb {
             self.scan_accounts(ancestors, bank_id, scan_func, config)?;
             return Ok(used_index);
         }
+        if self.account_indexes.lazy {
+            self.build_secondary_indexes
+                .call_once(|| self.build_lazy_secondary_indexes());
+        }
 
         self.accounts_index.index_scan_accounts(
             ancestors,
@@ -4796,6 +5092,44 @@ impl AccountsDb {
         Ok(used_index)
     }
 
+    /// Build the lazy secondary indexes from every version of every account in the index
+    ///
+    /// Accounts stored from here on update the indexes themselves, as if the indexes were not lazy.
+    fn build_lazy_secondary_indexes(&self) {
+        let mut measure = Measure::start("build_lazy_secondary_indexes");
+        *self.secondary_indexes_deferred.write().unwrap() = false;
+        // one bin at a time, so only a single bin's entries are held at once per thread
+        let full_pubkey_range = Pubkey::new(&[0; 32])..=Pubkey::new(&[0xff; 32]);
+        let num_accounts = AtomicUsize::default();
+        self.thread_pool_clean.install(|| {
+            self.accounts_index.account_maps.par_iter().for_each(|map| {
+                let items = map.items(&full_pubkey_range);
+                num_accounts.fetch_add(items.len(), Ordering::Relaxed);
+                for (pubkey, entry) in items {
+                    let slot_list = entry.slot_list.read().unwrap().clone();
+                    for (slot, account_info) in slot_list {
+                        if let Some(loaded_account) = self
+                            .get_account_accessor(slot, &pubkey, &account_info.storage_location())
+                            .get_loaded_account()
+                        {
+                            self.accounts_index.update_secondary_indexes(
+                                &pubkey,
+                                &loaded_account,
+                                &self.account_indexes,
+                            );
+                        }
+                    }
+                }
+            })
+        });
+        measure.stop();
+        info!(
+            "Built lazy secondary indexes for {} accounts in {}",
+            num_accounts.load(Ordering::Relaxed),
+            measure
+        );
+    }
+
     /// Scan a specific slot through all the account storage in parallel
     pub fn scan_account_storage<R, B>(
         &self,
@@ -6031,7 +6365,7 @@ impl AccountsDb {
         )
     }
 
-    fn hash_account_data(
+    pub(crate) fn hash_account_data(
         slot: Slot,
         lamports: u64,
         owner: &Pubkey,
@@ -6189,6 +6523,64 @@ impl AccountsDb {
         self.flush_slot_cache(slot);
     }
 
+    /// The oldest slot still in the write cache, if any
+    ///
+    /// A growing gap between this slot and the root means flushing is falling behind, which will
+    /// delay the next consistent snapshot.
+    pub fn oldest_unflushed_slot(&self) -> Option<Slot> {
+        self.accounts_cache.min_slot()
+    }
+
+    /// List where each account is stored within `slot`'s storages, sorted by storage and offset
+    ///
+    /// This is a diagnostic for debugging storage corruption.  Every account in the storages is
+    /// read, so do not use it on hot paths.  Accounts that are no longer alive are listed too, since
+    /// they still occupy space in the storages.  Accounts still in the write cache are not listed.
+    pub fn storage_layout(&self, slot: Slot) -> Vec<AccountLocation> {
+        let mut layout = self
+            .storage
+            .get_slot_storage_entries(slot)
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|store| {
+                store
+                    .all_accounts()
+                    .into_iter()
+                    .map(|account| AccountLocation {
+                        pubkey: account.meta.pubkey,
+                        store_id: store.append_vec_id(),
+                        offset: account.offset,
+                        stored_size: account.stored_size,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        layout.sort_unstable_by_key(|location| (location.store_id, location.offset));
+        layout
+    }
+
+    /// Approximate number of accounts an accounts hash calculation would scan right now
+    ///
+    /// This is the number of alive accounts in all storages, plus the accounts in the write cache.
+    /// Accounts updated in multiple slots are counted once per slot, as the scan also sees them.
+    pub fn approx_num_accounts_to_hash(&self) -> usize {
+        let num_stored_accounts: usize = self
+            .storage
+            .map
+            .iter()
+            .map(|slot_stores| {
+                slot_stores
+                    .value()
+                    .read()
+                    .unwrap()
+                    .values()
+                    .map(|store| store.count())
+                    .sum::<usize>()
+            })
+            .sum();
+        num_stored_accounts + self.accounts_cache.num_accounts()
+    }
+
     /// true if write cache is too big
     fn should_aggressively_flush_cache(&self) -> bool {
         self.write_cache_limit_bytes
@@ -6501,6 +6893,21 @@ impl AccountsDb {
         }
     }
 
+    /// Flush the cached accounts of a frozen, unrooted slot to storage, to reduce memory usage
+    ///
+    /// The accounts remain readable through the accounts index, and the slot can still be rooted
+    /// or purged later.  Returns true if the slot was flushed, and false if it is not in the cache
+    /// (e.g. it was already flushed), is not frozen, or is known to be dead.
+    pub fn flush_unrooted_slot_cache(&self, slot: Slot) -> bool {
+        let is_frozen = self
+            .accounts_cache
+            .slot_cache(slot)
+            .map_or(false, |slot_cache| slot_cache.is_frozen());
+        is_frozen
+            && slot > self.accounts_cache.fetch_max_flush_root()
+            && self.flush_slot_cache(slot).is_some()
+    }
+
     /// flush all accounts in this slot
     fn flush_slot_cache(&self, slot: Slot) -> Option<FlushStats> {
         self.flush_slot_cache_with_clean(&[slot], None::<&mut fn(&_, &_) -> bool>, None)
@@ -6808,32 +7215,147 @@ impl AccountsDb {
         AccountsHash::checked_cast_for_capitalization(balances.map(|b| b as u128).sum::<u128>())
     }
 
+    /// Get the thread pool with `num_threads` threads for accounts hash calculations, building it
+    /// on first use, see `CalcAccountsHashConfig::thread_pool_size`
+    fn accounts_hash_thread_pool(&self, num_threads: usize) -> Arc<ThreadPool> {
+        let mut accounts_hash_thread_pools = self.accounts_hash_thread_pools.lock().unwrap();
+        let thread_pool = accounts_hash_thread_pools
+            .entry(num_threads)
+            .or_insert_with(|| Arc::new(make_accounts_hash_thread_pool(num_threads)));
+        Arc::clone(thread_pool)
+    }
+
     pub fn calculate_accounts_hash_from_index(
         &self,
         max_slot: Slot,
         config: &CalcAccountsHashConfig<'_>,
     ) -> Result<(Hash, u64), BankHashVerificationError> {
-        use BankHashVerificationError::*;
+        let start = Instant::now();
         let mut collect = Measure::start("collect");
-        let keys: Vec<_> = self
-            .accounts_index
+        let keys = self.sorted_index_keys(|_| true);
+        collect.stop();
+
+        let mut scan = Measure::start("scan");
+        let (hashes, total_lamports) =
+            self.scan_account_hashes_from_index(&keys, max_slot, config)?;
+        scan.stop();
+        let stats = HashStats::default();
+
+        let mut hash_time = Measure::start("hash");
+        let (accumulated_hash, hash_total) = AccountsHash::calculate_hash(hashes);
+        hash_time.stop();
+        datapoint_info!(
+            "calculate_accounts_hash_from_index",
+            ("accounts_scan", scan.as_us(), i64),
+            ("hash", hash_time.as_us(), i64),
+            ("hash_total", hash_total, i64),
+            ("collect", collect.as_us(), i64),
+            (
+                "rehashed_rewrites",
+                stats.rehash_required.load(Ordering::Relaxed),
+                i64
+            ),
+            (
+                "rehashed_rewrites_unnecessary",
+                stats.rehash_unnecessary.load(Ordering::Relaxed),
+                i64
+            ),
+        );
+        self.assert_safe_squashing_accounts_hash(max_slot, config.epoch_schedule);
+        self.record_accounts_hash_time(max_slot, start.elapsed());
+
+        Ok((accumulated_hash, total_lamports))
+    }
+
+    /// Calculate the account hashes, in pubkey order, for only the pubkeys where `include_pubkey`
+    /// returns true, along with the total lamports of those accounts
+    ///
+    /// This is the building block for calculating the accounts hash piecewise: concatenating the
+    /// results over a set of disjoint, contiguous pubkey ranges yields the same leaves as
+    /// `calculate_accounts_hash_from_index()` uses for the whole keyspace.
+    pub fn calculate_account_hashes_from_index_filtered(
+        &self,
+        max_slot: Slot,
+        config: &CalcAccountsHashConfig<'_>,
+        include_pubkey: impl Fn(&Pubkey) -> bool,
+    ) -> Result<(Vec<Hash>, u64), BankHashVerificationError> {
+        let keys = self.sorted_index_keys(include_pubkey);
+        let (hashes, total_lamports) =
+            self.scan_account_hashes_from_index(&keys, max_slot, config)?;
+        Ok((hashes.into_iter().flatten().collect(), total_lamports))
+    }
+
+    /// Calculate the incremental accounts hash: the hash over the accounts whose latest version, as
+    /// of `slot`, was stored after `base_slot`
+    ///
+    /// These are the accounts an incremental snapshot from `base_slot` to `slot` carries, so
+    /// tooling can check an incremental snapshot on its own.  Unlike the full accounts hash,
+    /// zero-lamport accounts are included, with the default hash, because they record accounts
+    /// removed since `base_slot`.
+    pub fn calculate_incremental_accounts_hash(
+        &self,
+        base_slot: Slot,
+        slot: Slot,
+        ancestors: &Ancestors,
+    ) -> Hash {
+        let keys = self.sorted_index_keys(|pubkey| !self.is_filler_account(pubkey));
+        let hashes: Vec<Hash> = self.thread_pool_clean.install(|| {
+            keys.par_iter()
+                .filter_map(|pubkey| {
+                    if let AccountIndexGetResult::Found(lock, index) =
+                        self.accounts_index.get(pubkey, Some(ancestors), Some(slot))
+                    {
+                        let (account_slot, account_info) = &lock.slot_list()[index];
+                        if *account_slot <= base_slot {
+                            None
+                        } else if account_info.is_zero_lamport() {
+                            Some(Hash::default())
+                        } else {
+                            self.get_account_accessor(
+                                *account_slot,
+                                pubkey,
+                                &account_info.storage_location(),
+                            )
+                            .get_loaded_account()
+                            .map(|loaded_account| loaded_account.loaded_hash())
+                        }
+                    } else {
+                        None
+                    }
+                })
+                .collect()
+        });
+        let (accounts_hash, _hash_total) = AccountsHash::calculate_hash(vec![hashes]);
+        accounts_hash
+    }
+
+    /// Get the pubkeys in the accounts index, sorted
+    fn sorted_index_keys(&self, include_pubkey: impl Fn(&Pubkey) -> bool) -> Vec<Pubkey> {
+        self.accounts_index
             .account_maps
             .iter()
             .flat_map(|map| {
                 let mut keys = map.keys();
+                keys.retain(|pubkey| include_pubkey(pubkey));
                 keys.sort_unstable(); // hashmap is not ordered, but bins are relative to each other
                 keys
             })
-            .collect();
-        collect.stop();
+            .collect()
+    }
 
-        let mut scan = Measure::start("scan");
+    /// Load the hashes of the accounts for `keys` at `max_slot`, and sum their lamports
+    fn scan_account_hashes_from_index(
+        &self,
+        keys: &[Pubkey],
+        max_slot: Slot,
+        config: &CalcAccountsHashConfig<'_>,
+    ) -> Result<(Vec<Vec<Hash>>, u64), BankHashVerificationError> {
+        use BankHashVerificationError::*;
         let mismatch_found = AtomicU64::new(0);
         // Pick a chunk size big enough to allow us to produce output vectors that are smaller than the overall size.
         // We'll also accumulate the lamports within each chunk and fewer chunks results in less contention to accumulate the sum.
         let chunks = crate::accounts_hash::MERKLE_FANOUT.pow(4);
         let total_lamports = Mutex::<u64>::new(0);
-        let stats = HashStats::default();
 
         let get_hashes = || {
             keys.par_chunks(chunks)
@@ -6867,6 +7389,12 @@ impl AccountsDb {
                                     .get_loaded_account()
                                     .and_then(
                                         |loaded_account| {
+                                            if config.exclude_executable && loaded_account.executable() {
+                                                return None;
+                                            }
+                                            if matches!(config.owner_filter, Some(owner) if loaded_account.owner() != &owner) {
+                                                return None;
+                                            }
                                             let loaded_hash = loaded_account.loaded_hash();
                                             let balance = loaded_account.lamports();
                                             if config.check_hash && !self.is_filler_account(pubkey) {  // this will not be supported anymore
@@ -6899,10 +7427,13 @@ impl AccountsDb {
                 }).collect()
         };
 
-        let hashes: Vec<Vec<Hash>> = if config.check_hash {
-            get_hashes()
-        } else {
-            self.thread_pool_clean.install(get_hashes)
+        let hashes: Vec<Vec<Hash>> = match (config.thread_pool, config.thread_pool_size) {
+            (Some(thread_pool), _) => thread_pool.install(get_hashes),
+            (None, Some(thread_pool_size)) => self
+                .accounts_hash_thread_pool(thread_pool_size)
+                .install(get_hashes),
+            (None, None) if config.check_hash => get_hashes(),
+            (None, None) => self.thread_pool_clean.install(get_hashes),
         };
         if mismatch_found.load(Ordering::Relaxed) > 0 {
             warn!(
@@ -6912,57 +7443,121 @@ impl AccountsDb {
             return Err(MismatchedAccountHash);
         }
 
-        scan.stop();
         let total_lamports = *total_lamports.lock().unwrap();
-
-        let mut hash_time = Measure::start("hash");
-        let (accumulated_hash, hash_total) = AccountsHash::calculate_hash(hashes);
-        hash_time.stop();
-        datapoint_info!(
-            "calculate_accounts_hash_from_index",
-            ("accounts_scan", scan.as_us(), i64),
-            ("hash", hash_time.as_us(), i64),
-            ("hash_total", hash_total, i64),
-            ("collect", collect.as_us(), i64),
-            (
-                "rehashed_rewrites",
-                stats.rehash_required.load(Ordering::Relaxed),
-                i64
-            ),
-            (
-                "rehashed_rewrites_unnecessary",
-                stats.rehash_unnecessary.load(Ordering::Relaxed),
-                i64
-            ),
-        );
-        self.assert_safe_squashing_accounts_hash(max_slot, config.epoch_schedule);
-
-        Ok((accumulated_hash, total_lamports))
+        Ok((hashes, total_lamports))
     }
 
-    pub fn get_accounts_hash(&self, slot: Slot) -> Hash {
-        let bank_hashes = self.bank_hashes.read().unwrap();
-        let bank_hash_info = bank_hashes.get(&slot).unwrap();
-        bank_hash_info.snapshot_hash
+    /// Returns the slot and accounts hash of the last full snapshot, if any
+    ///
+    /// This is set when a full snapshot's accounts hash is calculated, and when loading from
+    /// snapshot archives.  Incremental snapshots are relative to this full snapshot.
+    pub fn last_full_snapshot_accounts_hash(&self) -> Option<(Slot, Hash)> {
+        *self.last_full_snapshot_accounts_hash.lock().unwrap()
     }
 
-    pub fn update_accounts_hash_for_tests(
-        &self,
-        slot: Slot,
-        ancestors: &Ancestors,
-        debug_verify: bool,
-        is_startup: bool,
-    ) -> (Hash, u64) {
-        self.update_accounts_hash(
-            CalcAccountsHashDataSource::Index,
-            debug_verify,
-            slot,
-            ancestors,
-            None,
-            &EpochSchedule::default(),
-            &RentCollector::default(),
-            is_startup,
-        )
+    /// Record the slot and accounts hash of a full snapshot, see
+    /// `last_full_snapshot_accounts_hash()`
+    ///
+    /// Full snapshots may be handled out of order, so an older full snapshot is ignored.
+    pub fn set_last_full_snapshot_accounts_hash(&self, slot: Slot, accounts_hash: Hash) {
+        let mut last_full_snapshot_accounts_hash =
+            self.last_full_snapshot_accounts_hash.lock().unwrap();
+        if last_full_snapshot_accounts_hash.map_or(true, |(last_slot, _)| slot >= last_slot) {
+            *last_full_snapshot_accounts_hash = Some((slot, accounts_hash));
+        }
+    }
+
+    /// Returns the ids of the storages that would compose the next incremental snapshot
+    ///
+    /// These are the storages of the rooted slots after the last full snapshot, see
+    /// `last_full_snapshot_accounts_hash()`, ordered by slot.  Roots still in the write cache are
+    /// not included, since they have no storages yet.  Returns nothing if there is no last full
+    /// snapshot, as the next snapshot must then be a full snapshot.
+    pub fn storages_since_last_full_snapshot(&self) -> Vec<AppendVecId> {
+        let full_snapshot_slot = match self.last_full_snapshot_accounts_hash() {
+            Some((full_snapshot_slot, _accounts_hash)) => full_snapshot_slot,
+            None => return Vec::default(),
+        };
+        let (storages, slots) = self.get_snapshot_storages(
+            self.accounts_index.max_root_inclusive(),
+            Some(full_snapshot_slot),
+            None,
+        );
+        let mut storages = storages.into_iter().zip(slots).collect::<Vec<_>>();
+        storages.sort_unstable_by_key(|(_storages, slot)| *slot);
+        storages
+            .into_iter()
+            .flat_map(|(storages, _slot)| {
+                let mut append_vec_ids = storages
+                    .iter()
+                    .map(|storage| storage.append_vec_id())
+                    .collect::<Vec<_>>();
+                append_vec_ids.sort_unstable();
+                append_vec_ids
+            })
+            .collect()
+    }
+
+    /// Returns the time since the last accounts hash calculation finished, if there has been one
+    ///
+    /// Accounts hashes are routinely calculated every `accounts_hash_interval_slots`, so a duration
+    /// much longer than that interval indicates that hashing has stalled.
+    pub fn time_since_last_accounts_hash(&self) -> Option<Duration> {
+        self.last_accounts_hash_time
+            .lock()
+            .unwrap()
+            .map(|last_accounts_hash_time| last_accounts_hash_time.elapsed())
+    }
+
+    /// Returns the slots and durations of up to the `n` latest accounts hash calculations, oldest
+    /// first
+    ///
+    /// At most `MAX_RECENT_ACCOUNTS_HASH_TIMINGS` calculations are remembered.  Use these to track
+    /// hashing throughput trends, which `epoch_accounts_hash::estimate_calculation_duration()`
+    /// relies on.
+    pub fn recent_hash_timings(&self, n: usize) -> Vec<(Slot, Duration)> {
+        let recent_accounts_hash_timings = self.recent_accounts_hash_timings.lock().unwrap();
+        let skip = recent_accounts_hash_timings.len().saturating_sub(n);
+        recent_accounts_hash_timings
+            .iter()
+            .skip(skip)
+            .copied()
+            .collect()
+    }
+
+    /// Record that the accounts hash calculation for `slot` finished, and took `duration`
+    fn record_accounts_hash_time(&self, slot: Slot, duration: Duration) {
+        *self.last_accounts_hash_time.lock().unwrap() = Some(Instant::now());
+        let mut recent_accounts_hash_timings = self.recent_accounts_hash_timings.lock().unwrap();
+        if recent_accounts_hash_timings.len() == MAX_RECENT_ACCOUNTS_HASH_TIMINGS {
+            recent_accounts_hash_timings.pop_front();
+        }
+        recent_accounts_hash_timings.push_back((slot, duration));
+    }
+
+    pub fn get_accounts_hash(&self, slot: Slot) -> Hash {
+        let bank_hashes = self.bank_hashes.read().unwrap();
+        let bank_hash_info = bank_hashes.get(&slot).unwrap();
+        bank_hash_info.snapshot_hash
+    }
+
+    pub fn update_accounts_hash_for_tests(
+        &self,
+        slot: Slot,
+        ancestors: &Ancestors,
+        debug_verify: bool,
+        is_startup: bool,
+    ) -> (Hash, u64) {
+        self.update_accounts_hash(
+            CalcAccountsHashDataSource::Index,
+            debug_verify,
+            slot,
+            ancestors,
+            None,
+            &EpochSchedule::default(),
+            &RentCollector::default(),
+            is_startup,
+        )
     }
 
     fn scan_multiple_account_storages_one_slot<S>(
@@ -7145,6 +7740,10 @@ impl AccountsDb {
             ),
             snapshot_storages,
         );
+        // the chunks are scanned in parallel, so each gets its share of the memory limit
+        let max_accum_bytes = config.max_memory_bytes.map(|max_memory_bytes| {
+            (max_memory_bytes as usize / rayon::current_num_threads()).max(1)
+        });
 
         (0..splitter.chunk_count)
             .into_par_iter()
@@ -7190,9 +7789,27 @@ impl AccountsDb {
                         bin_range.end,
                         hash
                     );
-                    if load_from_cache {
+                    let file_name = if config.exclude_executable {
+                        // filtered data must not be shared with unfiltered calculations
+                        format!("{}.exclude_executable", file_name)
+                    } else {
+                        file_name
+                    };
+                    let file_name = match config.owner_filter {
+                        Some(owner) => format!("{}.owner_{}", file_name, owner),
+                        None => file_name,
+                    };
+                    let file_name = if max_accum_bytes.is_some() {
+                        // the data may be split across several files, which are only used once
+                        load_from_cache = false;
+                        format!("{}.bounded", file_name)
+                    } else {
+                        file_name
+                    };
+                    // the cached data cannot be checked for duplicates, so always rescan
+                    if load_from_cache && !config.detect_duplicates {
                         if let Ok(mapped_file) = cache_hash_data.load_map(&Path::new(&file_name)) {
-                            return Some(mapped_file);
+                            return Some(vec![mapped_file]);
                         }
                     }
 
@@ -7204,25 +7821,53 @@ impl AccountsDb {
                 let range = bin_range.end - bin_range.start;
                 scanner.init_accum(range);
 
+                assert!(!file_name.is_empty());
+                let mut mapped_files = Vec::new();
+                let mut save = |binned_hash_data: &BinnedHashData| {
+                    if binned_hash_data.iter().any(|bin| !bin.is_empty()) {
+                        let file_name = if max_accum_bytes.is_some() {
+                            format!("{}.{}", file_name, mapped_files.len())
+                        } else {
+                            file_name.clone()
+                        };
+                        // error if we can't write this
+                        let file_name = Path::new(&file_name);
+                        cache_hash_data.save(file_name, binned_hash_data).unwrap();
+                        mapped_files.push(cache_hash_data.load_map(&file_name).unwrap());
+                    }
+                };
                 for (slot, sub_storages) in snapshot_storages.iter_range(&range_this_chunk) {
                     scanner.set_slot(slot);
                     if let Some(sub_storages) = sub_storages {
                         Self::scan_multiple_account_storages_one_slot(sub_storages, &mut scanner);
                     }
+                    if let Some(max_accum_bytes) = max_accum_bytes {
+                        // flush what was scanned so far; the files stay in slot order, so the
+                        // newest version of each account still wins when de-duplicating
+                        let accum = scanner.get_accum();
+                        if Self::binned_hash_data_bytes(&accum) > max_accum_bytes {
+                            let (accum, _sort_time_us) = Self::sort_slot_storage_scan(accum);
+                            save(&accum);
+                            scanner.init_accum(range);
+                        } else {
+                            scanner.set_accum(accum);
+                        }
+                    }
                 }
-                let r = scanner.scanning_complete();
-                assert!(!file_name.is_empty());
-                (!r.is_empty() && r.iter().any(|b| !b.is_empty())).then(|| {
-                    // error if we can't write this
-                    let file_name = Path::new(&file_name);
-                    cache_hash_data.save(Path::new(&file_name), &r).unwrap();
-                    cache_hash_data.load_map(&file_name).unwrap()
-                })
+                save(&scanner.scanning_complete());
+                Some(mapped_files)
             })
             .filter_map(|x| x)
+            .flatten()
             .collect()
     }
 
+    /// The bytes taken up in memory by the hash calculation data in `binned_hash_data`
+    fn binned_hash_data_bytes(binned_hash_data: &BinnedHashData) -> usize {
+        binned_hash_data.iter().map(Vec::len).sum::<usize>()
+            * std::mem::size_of::<CalculateHashIntermediate>()
+    }
+
     /// storages are sorted by slot and have range info.
     /// add all stores older than slots_per_epoch to dirty_stores so clean visits these slots
     fn mark_old_slots_as_dirty(
@@ -7351,6 +7996,7 @@ impl AccountsDb {
                     rent_collector,
                     store_detailed_debug_info_on_failure: false,
                     full_snapshot: None,
+                    ..CalcAccountsHashConfig::default()
                 },
                 expected_capitalization,
             )
@@ -7383,6 +8029,7 @@ impl AccountsDb {
         stats.num_snapshot_storage = storage.storage_count();
         stats.num_slots = storage.slot_count();
         let mismatch_found = Arc::new(AtomicU64::new(0));
+        let duplicate_found = Arc::new(Mutex::new(None));
         let range = bin_range.end - bin_range.start;
         let sort_time = Arc::new(AtomicU64::new(0));
 
@@ -7392,6 +8039,10 @@ impl AccountsDb {
             bin_calculator: &bin_calculator,
             config,
             mismatch_found: mismatch_found.clone(),
+            duplicate_found: duplicate_found.clone(),
+            pubkeys_in_current_slot: HashSet::default(),
+            accounts_index: &self.accounts_index,
+            max_slot: storage.max_slot_inclusive(),
             filler_account_suffix,
             range,
             bin_range,
@@ -7418,6 +8069,14 @@ impl AccountsDb {
             return Err(BankHashVerificationError::MismatchedAccountHash);
         }
 
+        if let Some((pubkey, slots)) = duplicate_found.lock().unwrap().take() {
+            warn!(
+                "account {} found live more than once in slots {:?}",
+                pubkey, slots
+            );
+            return Err(AccountsHashError::DuplicateLiveAccount { pubkey, slots }.into());
+        }
+
         time.stop();
         stats.scan_time_total_us += time.as_us();
 
@@ -7482,18 +8141,189 @@ impl AccountsDb {
         &self,
         config: &CalcAccountsHashConfig<'_>,
         storages: &SortedStorages<'_>,
-        mut stats: HashStats,
+        stats: HashStats,
     ) -> Result<(Hash, u64), BankHashVerificationError> {
+        self.calculate_accounts_hash_and_collect_from_storages(config, storages, stats)
+            .map(|collected| (collected.accounts_hash, collected.capitalization))
+    }
+
+    /// Calculate the accounts hash leaves from storages, for only the pubkeys in `bin_range` of the
+    /// `PUBKEY_BINS_FOR_CALCULATING_HASHES` bins; also return the total lamports of those accounts
+    ///
+    /// Like `calculate_account_hashes_from_index_filtered()`, concatenating the results over
+    /// contiguous bin ranges yields the leaves of the whole accounts hash.  Unlike the index, the
+    /// storages still hold the accounts as of `storages.max_slot_inclusive()` after later slots
+    /// have been rooted and cleaned.
+    pub fn calculate_account_hashes_from_storages_in_bins(
+        &self,
+        config: &CalcAccountsHashConfig<'_>,
+        storages: &SortedStorages<'_>,
+        bin_range: &Range<usize>,
+    ) -> Result<(Vec<Hash>, u64), BankHashVerificationError> {
+        let mut stats = HashStats::default();
+        // keep these cache files apart, so they do not evict the full calculation's cache files
+        let cache_hash_data =
+            CacheHashData::new(&self.accounts_hash_cache_path.join("account_hashes_in_bins"));
+        let hash = AccountsHash {
+            filler_account_suffix: if self.filler_accounts_config.count > 0 {
+                self.filler_account_suffix
+            } else {
+                None
+            },
+        };
+
+        let result = self.scan_snapshot_stores_with_cache(
+            &cache_hash_data,
+            storages,
+            &mut stats,
+            PUBKEY_BINS_FOR_CALCULATING_HASHES,
+            bin_range,
+            config,
+            hash.filler_account_suffix.as_ref(),
+        )?;
+        let slices = result
+            .iter()
+            .map(|d| d.get_cache_hash_data())
+            .collect::<Vec<_>>();
+        let binned =
+            AccountsHash::get_binned_data(&slices, PUBKEY_BINS_FOR_CALCULATING_HASHES, bin_range);
+        let (hashes, lamports) =
+            hash.de_dup_and_eliminate_zeros(&binned, &mut stats, bin_range.len(), None);
+        let hashes = hashes.into_iter().flatten().copied().collect();
+        Ok((hashes, lamports))
+    }
+
+    /// Calculate the accounts hash from storages, and if `config.collect_pubkey_bloom` is set, also
+    /// return a bloom filter of the pubkeys seen while scanning
+    ///
+    /// The filter is built during the same scan, so tools get fast, probabilistic membership checks
+    /// without a second scan or an index.  Besides false positives, it may also contain the pubkeys
+    /// of zero-lamport accounts, which the hash excludes.
+    pub fn calculate_accounts_hash_and_pubkey_bloom_from_storages(
+        &self,
+        config: &CalcAccountsHashConfig<'_>,
+        storages: &SortedStorages<'_>,
+        stats: HashStats,
+    ) -> Result<(Hash, u64, Option<Bloom<Pubkey>>), BankHashVerificationError> {
+        self.calculate_accounts_hash_and_collect_from_storages(config, storages, stats)
+            .map(|collected| {
+                (
+                    collected.accounts_hash,
+                    collected.capitalization,
+                    collected.pubkey_bloom,
+                )
+            })
+    }
+
+    /// Calculate the accounts hash from storages, and if `config.collect_owner_subtotals` is set,
+    /// also return the lamports and number of accounts per owner
+    ///
+    /// The subtotals are collected as de-dup picks the accounts to hash, so they cover the same
+    /// accounts as the hash: the latest version of each account, after filtering and excluding
+    /// zero-lamport accounts.  So their lamports add up to the capitalization.  Only an entry per
+    /// owner is held in memory, but the owner of each account is loaded through the index, which
+    /// makes the calculation slower.
+    pub fn calculate_accounts_hash_and_owner_subtotals_from_storages(
+        &self,
+        config: &CalcAccountsHashConfig<'_>,
+        storages: &SortedStorages<'_>,
+        stats: HashStats,
+    ) -> Result<(Hash, u64, Option<OwnerSubtotals>), BankHashVerificationError> {
+        self.calculate_accounts_hash_and_collect_from_storages(config, storages, stats)
+            .map(|collected| {
+                (
+                    collected.accounts_hash,
+                    collected.capitalization,
+                    collected.owner_subtotals,
+                )
+            })
+    }
+
+    /// The owner of the latest version of `pubkey` as of `max_slot`, loaded through the index
+    fn load_owner_from_index(
+        &self,
+        pubkey: &Pubkey,
+        ancestors: Option<&Ancestors>,
+        max_slot: Slot,
+    ) -> Option<Pubkey> {
+        match self.accounts_index.get(pubkey, ancestors, Some(max_slot)) {
+            AccountIndexGetResult::Found(lock, index) => {
+                let (slot, account_info) = &lock.slot_list()[index];
+                self.get_account_accessor(*slot, pubkey, &account_info.storage_location())
+                    .get_loaded_account()
+                    .map(|loaded_account| *loaded_account.owner())
+            }
+            AccountIndexGetResult::NotFound => None,
+        }
+    }
+
+    /// Calculate the accounts hash from storages, and collect whatever else `config` asks for,
+    /// see `config.collect_pubkey_bloom` and `config.collect_owner_subtotals`
+    fn calculate_accounts_hash_and_collect_from_storages(
+        &self,
+        config: &CalcAccountsHashConfig<'_>,
+        storages: &SortedStorages<'_>,
+        mut stats: HashStats,
+    ) -> Result<AccountsHashAndCollected, BankHashVerificationError> {
         let _guard = self.active_stats.activate(ActiveStatItem::Hash);
         stats.oldest_root = storages.range().start;
 
+        if config.deadline.is_some() {
+            // without a previous calculation to go by, only an already passed deadline is caught here
+            let projected =
+                self.accounts_hash_throughput
+                    .get()
+                    .map_or(Duration::ZERO, |accounts_per_second| {
+                        let num_accounts = Self::num_accounts_in_storages(storages);
+                        Duration::from_secs_f64(num_accounts as f64 / accounts_per_second)
+                    });
+            config.check_deadline(projected)?;
+        }
+
         self.mark_old_slots_as_dirty(storages, config.epoch_schedule.slots_per_epoch, &mut stats);
 
         let (num_hash_scan_passes, bins_per_pass) = Self::bins_per_pass(self.num_hash_scan_passes);
         let use_bg_thread_pool = config.use_bg_thread_pool;
+        if let Some(startup_progress) = config.startup_progress {
+            startup_progress
+                .hash_passes_total
+                .store(num_hash_scan_passes, Ordering::Relaxed);
+        }
+        let start = Instant::now();
         let mut scan_and_hash = || {
             let mut previous_pass = PreviousPass::default();
             let mut final_result = (Hash::default(), 0);
+            let pubkey_bloom = config.collect_pubkey_bloom.then(|| {
+                AtomicBloom::from(Bloom::random(
+                    Self::num_accounts_in_storages(storages),
+                    PUBKEY_BLOOM_FALSE_POSITIVE_RATE,
+                    usize::MAX,
+                ))
+            });
+            let owner_subtotals = config
+                .collect_owner_subtotals
+                .then(DashMap::<Pubkey, OwnerSubtotal>::default);
+            let num_owners_not_found = AtomicU64::default();
+            let add_to_owner_subtotals = |account: &CalculateHashIntermediate| {
+                let owner_subtotals = owner_subtotals.as_ref().unwrap();
+                match self.load_owner_from_index(
+                    &account.pubkey,
+                    config.ancestors,
+                    storages.max_slot_inclusive(),
+                ) {
+                    Some(owner) => {
+                        let mut owner_subtotal = owner_subtotals.entry(owner).or_default();
+                        owner_subtotal.lamports += account.lamports;
+                        owner_subtotal.num_accounts += 1;
+                    }
+                    None => {
+                        num_owners_not_found.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            };
+            let included_account = owner_subtotals
+                .is_some()
+                .then_some(&add_to_owner_subtotals as &(dyn Fn(&CalculateHashIntermediate) + Sync));
 
             let cache_hash_data = self.get_cache_hash_data(config, storages.max_slot_inclusive());
 
@@ -7528,6 +8358,14 @@ impl AccountsDb {
                     .map(|d| d.get_cache_hash_data())
                     .collect::<Vec<_>>();
 
+                if let Some(pubkey_bloom) = pubkey_bloom.as_ref() {
+                    slices.par_iter().for_each(|slice| {
+                        slice.iter().for_each(|entry| {
+                            pubkey_bloom.add(&entry.pubkey);
+                        })
+                    });
+                }
+
                 // rework slices of data into bins for parallel processing and to match data shape expected by 'rest_of_hash_calculation'
                 let result = AccountsHash::get_binned_data(
                     &slices,
@@ -7536,15 +8374,28 @@ impl AccountsDb {
                 );
 
                 // turn raw data into merkle tree hashes and sum of lamports
-                let (hash, lamports, for_next_pass) = hash.rest_of_hash_calculation(
-                    result,
-                    &mut stats,
-                    pass == num_hash_scan_passes - 1,
-                    previous_pass,
-                    bins_per_pass,
-                );
+                let (hash, lamports, for_next_pass) = hash
+                    .rest_of_hash_calculation_with_included_accounts(
+                        result,
+                        &mut stats,
+                        pass == num_hash_scan_passes - 1,
+                        previous_pass,
+                        bins_per_pass,
+                        included_account,
+                    );
                 previous_pass = for_next_pass;
                 final_result = (hash, lamports);
+                if let Some(startup_progress) = config.startup_progress {
+                    startup_progress
+                        .hash_passes_completed
+                        .fetch_add(1, Ordering::Relaxed);
+                }
+
+                let passes_remaining = num_hash_scan_passes - pass - 1;
+                if passes_remaining > 0 {
+                    let projected = start.elapsed() / (pass + 1) as u32 * passes_remaining as u32;
+                    config.check_deadline(projected)?;
+                }
             }
 
             info!(
@@ -7552,14 +8403,39 @@ impl AccountsDb {
                 storages.max_slot_inclusive(),
                 final_result
             );
-            Ok(final_result)
+            let num_owners_not_found = num_owners_not_found.load(Ordering::Relaxed);
+            if num_owners_not_found > 0 {
+                warn!(
+                    "owners of {} account(s) not found in the index, so they are missing from the \
+                     owner subtotals",
+                    num_owners_not_found
+                );
+            }
+            let (accounts_hash, capitalization) = final_result;
+            Ok(AccountsHashAndCollected {
+                accounts_hash,
+                capitalization,
+                pubkey_bloom: pubkey_bloom.map(Bloom::from),
+                owner_subtotals: owner_subtotals
+                    .map(|owner_subtotals| owner_subtotals.into_iter().collect()),
+            })
         };
 
-        let result = if use_bg_thread_pool {
-            self.thread_pool_clean.install(scan_and_hash)
-        } else {
-            scan_and_hash()
+        let mut measure = Measure::start("scan_and_hash");
+        let result = match (config.thread_pool, config.thread_pool_size) {
+            (Some(thread_pool), _) => thread_pool.install(scan_and_hash),
+            (None, Some(thread_pool_size)) => self
+                .accounts_hash_thread_pool(thread_pool_size)
+                .install(scan_and_hash),
+            (None, None) if use_bg_thread_pool => self.thread_pool_clean.install(scan_and_hash),
+            (None, None) => scan_and_hash(),
         };
+        measure.stop();
+        if result.is_ok() {
+            self.accounts_hash_throughput
+                .update(stats.unreduced_entries, measure.as_duration());
+            self.record_accounts_hash_time(storages.max_slot_inclusive(), measure.as_duration());
+        }
         self.assert_safe_squashing_accounts_hash(
             storages.max_slot_inclusive(),
             config.epoch_schedule,
@@ -7568,6 +8444,16 @@ impl AccountsDb {
         result
     }
 
+    /// The number of alive accounts in `storages`
+    fn num_accounts_in_storages(storages: &SortedStorages<'_>) -> usize {
+        storages
+            .iter_range(&(..))
+            .filter_map(|(_slot, storage)| storage)
+            .flatten()
+            .map(|store| store.count())
+            .sum()
+    }
+
     /// return alive roots to retain, even though they are ancient
     fn calc_alive_ancient_historical_roots(&self, min_root: Slot) -> HashSet<Slot> {
         let mut ancient_alive_roots = HashSet::default();
@@ -7615,10 +8501,14 @@ impl AccountsDb {
             false,
             false,
             use_bg_thread_pool,
+            None,
         )
     }
 
     /// Only called from startup or test code.
+    ///
+    /// If `thread_pool` is set, the accounts hash is calculated on it, see
+    /// `CalcAccountsHashConfig::thread_pool`.
     #[allow(clippy::too_many_arguments)]
     pub fn verify_bank_hash_and_lamports_new(
         &self,
@@ -7631,6 +8521,7 @@ impl AccountsDb {
         ignore_mismatch: bool,
         store_hash_raw_data_for_debug: bool,
         use_bg_thread_pool: bool,
+        thread_pool: Option<&ThreadPool>,
     ) -> Result<(), BankHashVerificationError> {
         use BankHashVerificationError::*;
 
@@ -7647,6 +8538,9 @@ impl AccountsDb {
                 rent_collector,
                 store_detailed_debug_info_on_failure: store_hash_raw_data_for_debug,
                 full_snapshot: None,
+                thread_pool,
+                startup_progress: self.startup_progress.as_deref(),
+                ..CalcAccountsHashConfig::default()
             },
             None,
         )?;
@@ -7804,6 +8698,14 @@ impl AccountsDb {
         // so, instead we limit how many threads will be created to the same size as the bg thread pool
         let len = std::cmp::min(accounts.len(), infos.len());
         let threshold = 1;
+        // hold the lock until the index is updated, see `secondary_indexes_deferred`
+        let secondary_indexes_deferred = self.secondary_indexes_deferred.read().unwrap();
+        let no_account_indexes = AccountSecondaryIndexes::default();
+        let account_indexes = if *secondary_indexes_deferred {
+            &no_account_indexes
+        } else {
+            &self.account_indexes
+        };
         let update = |start, end| {
             let mut reclaims = Vec::with_capacity((end - start) / 2);
 
@@ -7817,7 +8719,7 @@ impl AccountsDb {
                     old_slot,
                     pubkey,
                     pubkey_account.1,
-                    &self.account_indexes,
+                    account_indexes,
                     info,
                     &mut reclaims,
                     reclaim,
@@ -7897,7 +8799,7 @@ impl AccountsDb {
             store.total_bytes()
         };
         match self.shrink_ratio {
-            AccountShrinkThreshold::TotalSpace { shrink_ratio: _ } => {
+            AccountShrinkThreshold::TotalSpace { .. } => {
                 Self::page_align(store.alive_bytes() as u64) < total_bytes
             }
             AccountShrinkThreshold::IndividualStore { shrink_ratio } => {
@@ -8560,6 +9462,119 @@ impl AccountsDb {
         }
     }
 
+    /// Apply the account deltas from an incremental snapshot archive onto this accounts db.
+    ///
+    /// This accounts db must be rooted at the incremental snapshot's base slot.  Each slot in the
+    /// incremental snapshot is then stored and rooted in ascending order, which moves this
+    /// accounts db forward to the incremental snapshot's slot without a full reload.
+    pub fn apply_incremental_delta(
+        &self,
+        incremental_snapshot_archive_info: &IncrementalSnapshotArchiveInfo,
+    ) -> snapshot_utils::Result<ApplyReport> {
+        let base_slot = incremental_snapshot_archive_info.base_slot();
+        let max_root = self.accounts_index.max_root_inclusive();
+        if max_root != base_slot {
+            return Err(SnapshotError::MismatchedBaseSlot {
+                expected: max_root,
+                found: base_slot,
+            });
+        }
+
+        let mut measure = Measure::start("apply_incremental_delta");
+        let unpack_dir = tempfile::Builder::new()
+            .prefix("apply-incremental-delta-")
+            .tempdir()?;
+        let storage = snapshot_utils::unarchive_incremental_snapshot_storage(
+            incremental_snapshot_archive_info,
+            unpack_dir.path(),
+        )?;
+
+        let mut slots = storage
+            .iter()
+            .map(|entry| *entry.key())
+            .filter(|slot| *slot > base_slot)
+            .collect::<Vec<_>>();
+        slots.sort_unstable();
+
+        let mut num_accounts = 0;
+        for slot in slots.iter().copied() {
+            // If there are multiple storages for this slot, only the latest write version of
+            // each account is live.
+            let mut accounts =
+                HashMap::<Pubkey, (StoredMetaWriteVersion, AccountSharedData, Hash)>::new();
+            let slot_stores = storage.get(&slot).unwrap().value().clone();
+            for store in slot_stores.read().unwrap().values() {
+                store.accounts.account_iter().for_each(|stored_account| {
+                    let write_version = stored_account.meta.write_version;
+                    match accounts.entry(stored_account.meta.pubkey) {
+                        Entry::Occupied(mut occupied) => {
+                            if occupied.get().0 < write_version {
+                                occupied.insert((
+                                    write_version,
+                                    stored_account.clone_account(),
+                                    *stored_account.hash,
+                                ));
+                            }
+                        }
+                        Entry::Vacant(vacant) => {
+                            vacant.insert((
+                                write_version,
+                                stored_account.clone_account(),
+                                *stored_account.hash,
+                            ));
+                        }
+                    }
+                });
+            }
+
+            let (accounts, hashes): (Vec<_>, Vec<_>) = accounts
+                .iter()
+                .map(|(pubkey, (_write_version, account, hash))| ((pubkey, account), *hash))
+                .unzip();
+            num_accounts += accounts.len();
+
+            self.bank_hashes
+                .write()
+                .unwrap()
+                .entry(slot)
+                .or_insert_with(BankHashInfo::default);
+            // the hashes were already computed when the accounts were originally stored
+            self.store_accounts_frozen(
+                (
+                    slot,
+                    &accounts[..],
+                    INCLUDE_SLOT_IN_HASH_IRRELEVANT_APPEND_VEC_OPERATION,
+                ),
+                Some(&hashes[..]),
+                None,
+                None,
+                StoreReclaims::Default,
+            );
+            self.add_root(slot);
+        }
+
+        let slot = incremental_snapshot_archive_info.slot();
+        if slots.last() != Some(&slot) {
+            self.add_root(slot);
+        }
+        measure.stop();
+        datapoint_info!(
+            "accounts_db-apply_incremental_delta",
+            ("base_slot", base_slot, i64),
+            ("slot", slot, i64),
+            ("num_slots", slots.len(), i64),
+            ("num_accounts", num_accounts, i64),
+            ("total_us", measure.as_us(), i64),
+        );
+
+        Ok(ApplyReport {
+            base_slot,
+            slot,
+            slots,
+            num_accounts,
+        })
+    }
+
     pub fn get_snapshot_storages(
         &self,
         snapshot_slot: Slot,
@@ -8710,7 +9725,8 @@ impl AccountsDb {
             return SlotIndexGenerationInfo::default();
         }
 
-        let secondary = !self.account_indexes.is_empty();
+        let secondary =
+            !self.account_indexes.is_empty() && !*self.secondary_indexes_deferred.read().unwrap();
 
         let mut rent_paying_accounts_by_partition = Vec::default();
         let mut accounts_data_len = 0;
@@ -8986,6 +10002,13 @@ impl AccountsDb {
         // pass == 1 only runs if verify == true.
         // verify checks that all the expected items are in the accounts index and measures how long it takes to look them all up
         let passes = if verify { 2 } else { 1 };
+        let thread_pool = self.generate_index_threads.map(|num_threads| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .thread_name(|i| format!("solGenIndex{:02}", i))
+                .build()
+                .unwrap()
+        });
         for pass in 0..passes {
             if pass == 0 {
                 self.accounts_index
@@ -8994,7 +10017,9 @@ impl AccountsDb {
             let storage_info = StorageSizeAndCountMap::default();
             let total_processed_slots_across_all_threads = AtomicU64::new(0);
             let outer_slots_len = slots.len();
-            let threads = if self.accounts_index.is_disk_index_enabled() {
+            let threads = if let Some(thread_pool) = &thread_pool {
+                thread_pool.current_num_threads()
+            } else if self.accounts_index.is_disk_index_enabled() {
                 // these write directly to disk, so the more threads, the better
                 num_cpus::get()
             } else {
@@ -9008,92 +10033,108 @@ impl AccountsDb {
             let amount_to_top_off_rent = AtomicU64::new(0);
             let total_duplicates = AtomicU64::new(0);
             let storage_info_timings = Mutex::new(GenerateIndexTimings::default());
-            let scan_time: u64 = slots
-                .par_chunks(chunk_size)
-                .map(|slots| {
-                    let mut log_status = MultiThreadProgress::new(
-                        &total_processed_slots_across_all_threads,
-                        2,
-                        outer_slots_len as u64,
-                    );
-                    let mut scan_time_sum = 0;
-                    for (index, slot) in slots.iter().enumerate() {
-                        let mut scan_time = Measure::start("scan");
-                        log_status.report(index as u64);
-                        let storage_maps: Vec<Arc<AccountStorageEntry>> = self
-                            .storage
-                            .get_slot_storage_entries(*slot)
-                            .unwrap_or_default();
-                        let accounts_map = self.process_storage_slot(&storage_maps);
-                        scan_time.stop();
-                        scan_time_sum += scan_time.as_us();
-                        Self::update_storage_info(
-                            &storage_info,
-                            &accounts_map,
-                            &storage_info_timings,
+            let scan_slots = || {
+                slots
+                    .par_chunks(chunk_size)
+                    .map(|slots| {
+                        let mut log_status = MultiThreadProgress::new(
+                            &total_processed_slots_across_all_threads,
+                            2,
+                            outer_slots_len as u64,
                         );
+                        let mut scan_time_sum = 0;
+                        for (index, slot) in slots.iter().enumerate() {
+                            let mut scan_time = Measure::start("scan");
+                            log_status.report(index as u64);
+                            let storage_maps: Vec<Arc<AccountStorageEntry>> = self
+                                .storage
+                                .get_slot_storage_entries(*slot)
+                                .unwrap_or_default();
+                            let accounts_map = self.process_storage_slot(&storage_maps);
+                            scan_time.stop();
+                            scan_time_sum += scan_time.as_us();
+                            Self::update_storage_info(
+                                &storage_info,
+                                &accounts_map,
+                                &storage_info_timings,
+                            );
 
-                        let insert_us = if pass == 0 {
-                            // generate index
-                            self.maybe_throttle_index_generation();
-                            let SlotIndexGenerationInfo {
-                                insert_time_us: insert_us,
-                                num_accounts: total_this_slot,
-                                num_accounts_rent_paying: rent_paying_this_slot,
-                                accounts_data_len: accounts_data_len_this_slot,
-                                amount_to_top_off_rent: amount_to_top_off_rent_this_slot,
-                                rent_paying_accounts_by_partition:
-                                    rent_paying_accounts_by_partition_this_slot,
-                            } = self.generate_index_for_slot(accounts_map, slot, &rent_collector);
-                            rent_paying.fetch_add(rent_paying_this_slot, Ordering::Relaxed);
-                            amount_to_top_off_rent
-                                .fetch_add(amount_to_top_off_rent_this_slot, Ordering::Relaxed);
-                            total_duplicates.fetch_add(total_this_slot, Ordering::Relaxed);
-                            accounts_data_len
-                                .fetch_add(accounts_data_len_this_slot, Ordering::Relaxed);
-                            let mut rent_paying_accounts_by_partition =
-                                rent_paying_accounts_by_partition.lock().unwrap();
-                            rent_paying_accounts_by_partition_this_slot
-                                .iter()
-                                .for_each(|k| {
-                                    rent_paying_accounts_by_partition.add_account(k);
-                                });
-
-                            insert_us
-                        } else {
-                            // verify index matches expected and measure the time to get all items
-                            assert!(verify);
-                            let mut lookup_time = Measure::start("lookup_time");
-                            for account in accounts_map.into_iter() {
-                                let (key, account_info) = account;
-                                let lock = self.accounts_index.get_bin(&key);
-                                let x = lock.get(&key).unwrap();
-                                let sl = x.slot_list.read().unwrap();
-                                let mut count = 0;
-                                for (slot2, account_info2) in sl.iter() {
-                                    if slot2 == slot {
-                                        count += 1;
-                                        let ai = AccountInfo::new(
-                                            StorageLocation::AppendVec(
-                                                account_info.store_id,
-                                                account_info.stored_account.offset,
-                                            ), // will never be cached
-                                            account_info.stored_account.stored_size as StoredSize, // stored_size should never exceed StoredSize::MAX because of max data len const
-                                            account_info.stored_account.account_meta.lamports,
-                                        );
-                                        assert_eq!(&ai, account_info2);
+                            let insert_us = if pass == 0 {
+                                // generate index
+                                self.maybe_throttle_index_generation();
+                                let SlotIndexGenerationInfo {
+                                    insert_time_us: insert_us,
+                                    num_accounts: total_this_slot,
+                                    num_accounts_rent_paying: rent_paying_this_slot,
+                                    accounts_data_len: accounts_data_len_this_slot,
+                                    amount_to_top_off_rent: amount_to_top_off_rent_this_slot,
+                                    rent_paying_accounts_by_partition:
+                                        rent_paying_accounts_by_partition_this_slot,
+                                } = self.generate_index_for_slot(
+                                    accounts_map,
+                                    slot,
+                                    &rent_collector,
+                                );
+                                rent_paying.fetch_add(rent_paying_this_slot, Ordering::Relaxed);
+                                amount_to_top_off_rent
+                                    .fetch_add(amount_to_top_off_rent_this_slot, Ordering::Relaxed);
+                                total_duplicates.fetch_add(total_this_slot, Ordering::Relaxed);
+                                if let Some(startup_progress) = &self.startup_progress {
+                                    startup_progress
+                                        .index_entries
+                                        .fetch_add(total_this_slot, Ordering::Relaxed);
+                                }
+                                accounts_data_len
+                                    .fetch_add(accounts_data_len_this_slot, Ordering::Relaxed);
+                                let mut rent_paying_accounts_by_partition =
+                                    rent_paying_accounts_by_partition.lock().unwrap();
+                                rent_paying_accounts_by_partition_this_slot
+                                    .iter()
+                                    .for_each(|k| {
+                                        rent_paying_accounts_by_partition.add_account(k);
+                                    });
+
+                                insert_us
+                            } else {
+                                // verify index matches expected and measure the time to get all items
+                                assert!(verify);
+                                let mut lookup_time = Measure::start("lookup_time");
+                                for account in accounts_map.into_iter() {
+                                    let (key, account_info) = account;
+                                    let lock = self.accounts_index.get_bin(&key);
+                                    let x = lock.get(&key).unwrap();
+                                    let sl = x.slot_list.read().unwrap();
+                                    let mut count = 0;
+                                    for (slot2, account_info2) in sl.iter() {
+                                        if slot2 == slot {
+                                            count += 1;
+                                            let ai = AccountInfo::new(
+                                                StorageLocation::AppendVec(
+                                                    account_info.store_id,
+                                                    account_info.stored_account.offset,
+                                                ), // will never be cached
+                                                account_info.stored_account.stored_size
+                                                    as StoredSize, // stored_size should never exceed StoredSize::MAX because of max data len const
+                                                account_info.stored_account.account_meta.lamports,
+                                            );
+                                            assert_eq!(&ai, account_info2);
+                                        }
                                     }
+                                    assert_eq!(1, count);
                                 }
-                                assert_eq!(1, count);
-                            }
-                            lookup_time.stop();
-                            lookup_time.as_us()
-                        };
-                        insertion_time_us.fetch_add(insert_us, Ordering::Relaxed);
-                    }
-                    scan_time_sum
-                })
-                .sum();
+                                lookup_time.stop();
+                                lookup_time.as_us()
+                            };
+                            insertion_time_us.fetch_add(insert_us, Ordering::Relaxed);
+                        }
+                        scan_time_sum
+                    })
+                    .sum::<u64>()
+            };
+            let scan_time = match &thread_pool {
+                Some(thread_pool) => thread_pool.install(scan_slots),
+                None => scan_slots(),
+            };
             index_time.stop();
 
             info!("rent_collector: {:?}", rent_collector);
@@ -9645,7 +10686,7 @@ pub mod tests {
     use {
         super::*,
         crate::{
-            accounts_hash::MERKLE_FANOUT,
+            accounts_hash::{CalcDeadline, DeadlineOverrunAction, MERKLE_FANOUT},
             accounts_index::{
                 tests::*, AccountSecondaryIndexesIncludeExclude, ReadAccountMapEntry, RefCount,
             },
@@ -10207,42 +11248,819 @@ pub mod tests {
     }
 
     #[test]
-    fn test_accountsdb_calculate_accounts_hash_from_storages_simple() {
+    fn test_accountsdb_calculate_accounts_hash_from_storages_simple() {
+        solana_logger::setup();
+
+        let (storages, _size, _slot_expected) = sample_storage();
+        let db = AccountsDb::new(Vec::new(), &ClusterType::Development);
+        let result = db
+            .calculate_accounts_hash_from_storages(
+                &CalcAccountsHashConfig::default(),
+                &get_storage_refs(&storages),
+                HashStats::default(),
+            )
+            .unwrap();
+        let expected_hash = Hash::from_str("GKot5hBsd81kMupNCXHaqbhv3huEbxAFMLnpcX2hniwn").unwrap();
+        assert_eq!(result, (expected_hash, 0));
+    }
+
+    #[test]
+    fn test_accountsdb_calculate_accounts_hash_from_storages() {
+        solana_logger::setup();
+
+        let (storages, raw_expected) = sample_storages_and_accounts();
+        let expected_hash =
+            AccountsHash::compute_merkle_root_loop(raw_expected.clone(), MERKLE_FANOUT, |item| {
+                item.hash
+            });
+        let sum = raw_expected.iter().map(|item| item.lamports).sum();
+        let db = AccountsDb::new(Vec::new(), &ClusterType::Development);
+        let result = db
+            .calculate_accounts_hash_from_storages(
+                &CalcAccountsHashConfig::default(),
+                &get_storage_refs(&storages),
+                HashStats::default(),
+            )
+            .unwrap();
+
+        assert_eq!(result, (expected_hash, sum));
+    }
+
+    #[test]
+    fn test_calculate_accounts_hash_and_pubkey_bloom_from_storages() {
+        solana_logger::setup();
+
+        let (storages, raw_expected) = sample_storages_and_accounts();
+        let db = AccountsDb::new(Vec::new(), &ClusterType::Development);
+        let calculate_accounts_hash = |collect_pubkey_bloom| {
+            db.calculate_accounts_hash_and_pubkey_bloom_from_storages(
+                &CalcAccountsHashConfig {
+                    collect_pubkey_bloom,
+                    ..CalcAccountsHashConfig::default()
+                },
+                &get_storage_refs(&storages),
+                HashStats::default(),
+            )
+            .unwrap()
+        };
+
+        let (accounts_hash, capitalization, pubkey_bloom) = calculate_accounts_hash(false);
+        assert!(pubkey_bloom.is_none());
+
+        // collecting the bloom filter does not change the hash
+        let (accounts_hash_with_bloom, capitalization_with_bloom, pubkey_bloom) =
+            calculate_accounts_hash(true);
+        assert_eq!(
+            (accounts_hash_with_bloom, capitalization_with_bloom),
+            (accounts_hash, capitalization)
+        );
+        let pubkey_bloom = pubkey_bloom.unwrap();
+        assert!(raw_expected
+            .iter()
+            .all(|item| pubkey_bloom.contains(&item.pubkey)));
+        assert!(!(0..100).all(|_| pubkey_bloom.contains(&solana_sdk::pubkey::new_rand())));
+    }
+
+    #[test]
+    fn test_calculate_accounts_hash_and_owner_subtotals_from_storages() {
+        solana_logger::setup();
+
+        let db = AccountsDb::new(Vec::new(), &ClusterType::Development);
+        let owners = [(); 3].map(|_| Pubkey::new_unique());
+        let pubkeys = [(); 10].map(|_| Pubkey::new_unique());
+        let mut latest_accounts = HashMap::new();
+        let mut store = |slot, pubkey: &Pubkey, lamports, owner| {
+            let account = AccountSharedData::new(lamports, 0, owner);
+            db.store_uncached(slot, &[(pubkey, &account)]);
+            latest_accounts.insert(*pubkey, account);
+        };
+        for (i, pubkey) in pubkeys.iter().enumerate() {
+            store(0, pubkey, i as u64 + 1, &owners[i % owners.len()]);
+        }
+        // update an account, move an account to another owner, and close an account
+        store(1, &pubkeys[0], 100, &owners[0]);
+        store(1, &pubkeys[1], 200, &owners[2]);
+        store(2, &pubkeys[2], 0, &owners[2]);
+        let max_slot = 2;
+        for slot in 0..=max_slot {
+            db.add_root(slot);
+        }
+
+        let mut expected_owner_subtotals = OwnerSubtotals::default();
+        for account in latest_accounts.values() {
+            if account.lamports() != 0 {
+                let owner_subtotal = expected_owner_subtotals
+                    .entry(*account.owner())
+                    .or_default();
+                owner_subtotal.lamports += account.lamports();
+                owner_subtotal.num_accounts += 1;
+            }
+        }
+
+        let (storages, slots) = db.get_snapshot_storages(max_slot, None, None);
+        let storages = SortedStorages::new_with_slots(
+            storages.iter().zip(slots.into_iter()),
+            None,
+            Some(max_slot),
+        );
+        let calculate_accounts_hash = |collect_owner_subtotals| {
+            db.calculate_accounts_hash_and_owner_subtotals_from_storages(
+                &CalcAccountsHashConfig {
+                    collect_owner_subtotals,
+                    ..CalcAccountsHashConfig::default()
+                },
+                &storages,
+                HashStats::default(),
+            )
+            .unwrap()
+        };
+
+        let (accounts_hash, capitalization, owner_subtotals) = calculate_accounts_hash(false);
+        assert!(owner_subtotals.is_none());
+
+        // collecting the subtotals does not change the hash, and they add up to the capitalization
+        let (accounts_hash_with_subtotals, capitalization_with_subtotals, owner_subtotals) =
+            calculate_accounts_hash(true);
+        assert_eq!(accounts_hash_with_subtotals, accounts_hash);
+        assert_eq!(capitalization_with_subtotals, capitalization);
+        let owner_subtotals = owner_subtotals.unwrap();
+        assert_eq!(owner_subtotals, expected_owner_subtotals);
+        assert_eq!(
+            owner_subtotals
+                .values()
+                .map(|owner_subtotal| owner_subtotal.lamports)
+                .sum::<u64>(),
+            capitalization
+        );
+        assert_eq!(
+            owner_subtotals
+                .values()
+                .map(|owner_subtotal| owner_subtotal.num_accounts)
+                .sum::<u64>(),
+            pubkeys.len() as u64 - 1
+        );
+
+        // with an owner filter, the account moved away from the owner is not counted for it
+        let (_accounts_hash, capitalization, owner_subtotals) = db
+            .calculate_accounts_hash_and_owner_subtotals_from_storages(
+                &CalcAccountsHashConfig {
+                    collect_owner_subtotals: true,
+                    owner_filter: Some(owners[1]),
+                    ..CalcAccountsHashConfig::default()
+                },
+                &storages,
+                HashStats::default(),
+            )
+            .unwrap();
+        let owner_subtotals = owner_subtotals.unwrap();
+        assert_eq!(
+            owner_subtotals,
+            OwnerSubtotals::from([(owners[1], expected_owner_subtotals[&owners[1]])])
+        );
+        assert_eq!(owner_subtotals[&owners[1]].lamports, capitalization);
+    }
+
+    #[test]
+    fn test_storages_since_last_full_snapshot() {
+        solana_logger::setup();
+
+        let db = AccountsDb::new(Vec::new(), &ClusterType::Development);
+        let store_and_root = |slot: Slot| {
+            let account = AccountSharedData::new(slot + 1, 0, &Pubkey::default());
+            db.store_uncached(slot, &[(&Pubkey::new_unique(), &account)]);
+            db.add_root(slot);
+        };
+        let storage_ids = |slot: Slot| {
+            db.get_storages_for_slot(slot)
+                .unwrap()
+                .iter()
+                .map(|storage| storage.append_vec_id())
+                .collect::<Vec<_>>()
+        };
+
+        for slot in 0..3 {
+            store_and_root(slot);
+        }
+        // without a full snapshot, there is no incremental snapshot to compose
+        assert!(db.storages_since_last_full_snapshot().is_empty());
+
+        db.set_last_full_snapshot_accounts_hash(2, Hash::new_unique());
+        assert!(db.storages_since_last_full_snapshot().is_empty());
+
+        // only the writes after the full snapshot are reported
+        for slot in 3..5 {
+            store_and_root(slot);
+        }
+        // not rooted yet, so it would not be in the next incremental snapshot
+        let account = AccountSharedData::new(1, 0, &Pubkey::default());
+        db.store_uncached(5, &[(&Pubkey::new_unique(), &account)]);
+
+        let expected = [storage_ids(3), storage_ids(4)].concat();
+        assert_eq!(expected.len(), 2);
+        assert_eq!(db.storages_since_last_full_snapshot(), expected);
+    }
+
+    #[test]
+    fn test_time_since_last_accounts_hash() {
+        solana_logger::setup();
+
+        let (storages, _size, _slot_expected) = sample_storage();
+        let db = AccountsDb::new(Vec::new(), &ClusterType::Development);
+        assert_eq!(db.time_since_last_accounts_hash(), None);
+
+        db.calculate_accounts_hash_from_storages(
+            &CalcAccountsHashConfig::default(),
+            &get_storage_refs(&storages),
+            HashStats::default(),
+        )
+        .unwrap();
+        let time_since_last_accounts_hash = db.time_since_last_accounts_hash().unwrap();
+        assert!(time_since_last_accounts_hash < Duration::from_secs(60));
+
+        sleep(Duration::from_millis(10));
+        assert!(db.time_since_last_accounts_hash().unwrap() > time_since_last_accounts_hash);
+    }
+
+    #[test]
+    fn test_recent_hash_timings() {
+        solana_logger::setup();
+
+        let db = AccountsDb::new(Vec::new(), &ClusterType::Development);
+        assert!(db.recent_hash_timings(10).is_empty());
+
+        let pubkey = Pubkey::new_unique();
+        let num_slots = MAX_RECENT_ACCOUNTS_HASH_TIMINGS as Slot + 2;
+        for slot in 0..num_slots {
+            let account = AccountSharedData::new(slot + 1, 0, &Pubkey::default());
+            db.store_uncached(slot, &[(&pubkey, &account)]);
+            db.add_root(slot);
+            let data_source = if slot % 2 == 0 {
+                CalcAccountsHashDataSource::Storages
+            } else {
+                CalcAccountsHashDataSource::Index
+            };
+            let ancestors = vec![(slot, 0)].into_iter().collect();
+            db.calculate_accounts_hash(
+                data_source,
+                slot,
+                &CalcAccountsHashConfig {
+                    ancestors: Some(&ancestors),
+                    ..CalcAccountsHashConfig::default()
+                },
+            )
+            .unwrap();
+
+            // the latest calculations are reported oldest first
+            let slots = |n| {
+                db.recent_hash_timings(n)
+                    .into_iter()
+                    .map(|(slot, _duration)| slot)
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(
+                slots(3),
+                (slot.saturating_sub(2)..=slot).collect::<Vec<_>>()
+            );
+        }
+
+        // only the latest calculations are remembered
+        let recent_hash_timings = db.recent_hash_timings(usize::MAX);
+        assert_eq!(recent_hash_timings.len(), MAX_RECENT_ACCOUNTS_HASH_TIMINGS);
+        assert_eq!(recent_hash_timings[0].0, 2);
+        assert_eq!(recent_hash_timings.last().unwrap().0, num_slots - 1);
+        assert!(recent_hash_timings
+            .iter()
+            .all(|(_slot, duration)| *duration < Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_accountsdb_calculate_accounts_hash_from_storages_detect_duplicates() {
+        solana_logger::setup();
+
+        let slot = 1;
+        let pubkey = solana_sdk::pubkey::new_rand();
+        let tf = crate::append_vec::test_utils::get_append_vec_path(
+            "test_accountsdb_calculate_accounts_hash_from_storages_detect_duplicates",
+        );
+        let storages = sample_storage_with_entries(&tf, 0, slot, &pubkey);
+        // inject the same pubkey a second time into the slot
+        append_sample_data_to_storage(&storages, &pubkey, 1);
+
+        let db = AccountsDb::new(Vec::new(), &ClusterType::Development);
+        let calculate_accounts_hash = |detect_duplicates| {
+            db.calculate_accounts_hash_from_storages(
+                &CalcAccountsHashConfig {
+                    detect_duplicates,
+                    ..CalcAccountsHashConfig::default()
+                },
+                &get_storage_refs(&storages),
+                HashStats::default(),
+            )
+        };
+
+        // without detection, the duplicate is silently resolved
+        assert!(calculate_accounts_hash(false).is_ok());
+
+        assert_matches!(
+            calculate_accounts_hash(true),
+            Err(BankHashVerificationError::AccountsHash(
+                AccountsHashError::DuplicateLiveAccount { pubkey: duplicate_pubkey, slots }
+            )) if duplicate_pubkey == pubkey && slots == vec![slot, slot]
+        );
+    }
+
+    #[test]
+    fn test_accountsdb_calculate_accounts_hash_from_storages_detect_duplicates_across_slots() {
+        solana_logger::setup();
+
+        // the live version of the pubkey, per the index, is in slot 1
+        let pubkey = solana_sdk::pubkey::new_rand();
+        let db = AccountsDb::new_single_for_tests();
+        let account = AccountSharedData::new(1, 0, &Pubkey::default());
+        db.store_uncached(1, &[(&pubkey, &account)]);
+        db.add_root(1);
+        let mut storages = db.get_snapshot_storages(1, None, None).0;
+
+        // inject a version of the pubkey into a storage for slot 2, without updating the index
+        let tf = crate::append_vec::test_utils::get_append_vec_path(
+            "test_accountsdb_calculate_accounts_hash_from_storages_detect_duplicates_across_slots",
+        );
+        storages.extend(sample_storage_with_entries(&tf, 1, 2, &pubkey));
+
+        let calculate_accounts_hash = |detect_duplicates| {
+            db.calculate_accounts_hash_from_storages(
+                &CalcAccountsHashConfig {
+                    detect_duplicates,
+                    ..CalcAccountsHashConfig::default()
+                },
+                &get_storage_refs(&storages),
+                HashStats::default(),
+            )
+        };
+
+        // without detection, the version in slot 2 is silently hashed
+        assert!(calculate_accounts_hash(false).is_ok());
+
+        assert_matches!(
+            calculate_accounts_hash(true),
+            Err(BankHashVerificationError::AccountsHash(
+                AccountsHashError::DuplicateLiveAccount { pubkey: duplicate_pubkey, slots }
+            )) if duplicate_pubkey == pubkey && slots == vec![1, 2]
+        );
+
+        // once the version in slot 2 is live, the one in slot 1 is just not cleaned yet
+        db.store_uncached(2, &[(&pubkey, &account)]);
+        db.add_root(2);
+        assert!(calculate_accounts_hash(true).is_ok());
+    }
+
+    #[test]
+    fn test_calculate_accounts_hash_thread_pool_size() {
+        solana_logger::setup();
+
+        let slot = 0;
+        let ancestors = vec![(slot, 0)].into_iter().collect();
+        let db = AccountsDb::new(Vec::new(), &ClusterType::Development);
+        for i in 0..100 {
+            let account = AccountSharedData::new(i + 1, 0, &Pubkey::default());
+            db.store_uncached(slot, &[(&solana_sdk::pubkey::new_rand(), &account)]);
+        }
+        db.add_root(slot);
+        let calculate_accounts_hash = |data_source, use_bg_thread_pool, thread_pool_size| {
+            db.calculate_accounts_hash(
+                data_source,
+                slot,
+                &CalcAccountsHashConfig {
+                    use_bg_thread_pool,
+                    ancestors: Some(&ancestors),
+                    thread_pool_size,
+                    ..CalcAccountsHashConfig::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let expected = calculate_accounts_hash(CalcAccountsHashDataSource::Storages, false, None);
+        assert_eq!(expected.1, (1..=100).sum::<u64>());
+        for data_source in [
+            CalcAccountsHashDataSource::Storages,
+            CalcAccountsHashDataSource::Index,
+        ] {
+            for use_bg_thread_pool in [false, true] {
+                for thread_pool_size in [None, Some(1), Some(4)] {
+                    assert_eq!(
+                        calculate_accounts_hash(data_source, use_bg_thread_pool, thread_pool_size),
+                        expected
+                    );
+                }
+            }
+        }
+
+        // the thread pool of each size is built once, and reused by later calculations
+        assert_eq!(db.accounts_hash_thread_pools.lock().unwrap().len(), 2);
+        assert!(Arc::ptr_eq(
+            &db.accounts_hash_thread_pool(4),
+            &db.accounts_hash_thread_pool(4),
+        ));
+        assert_eq!(db.accounts_hash_thread_pool(4).current_num_threads(), 4);
+    }
+
+    #[test]
+    fn test_calculate_accounts_hash_thread_pool() {
+        solana_logger::setup();
+
+        let slot = 0;
+        let ancestors = vec![(slot, 0)].into_iter().collect();
+        let db = AccountsDb::new(Vec::new(), &ClusterType::Development);
+        for i in 0..100 {
+            let account = AccountSharedData::new(i + 1, 0, &Pubkey::default());
+            db.store_uncached(slot, &[(&solana_sdk::pubkey::new_rand(), &account)]);
+        }
+        db.add_root(slot);
+        let thread_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .build()
+            .unwrap();
+        let calculate_accounts_hash = |data_source, thread_pool| {
+            db.calculate_accounts_hash(
+                data_source,
+                slot,
+                &CalcAccountsHashConfig {
+                    ancestors: Some(&ancestors),
+                    thread_pool,
+                    ..CalcAccountsHashConfig::default()
+                },
+            )
+            .unwrap()
+        };
+
+        // without a thread pool, or the background thread pool, the calculation runs on this thread
+        let expected = calculate_accounts_hash(CalcAccountsHashDataSource::Storages, None);
+        for data_source in [
+            CalcAccountsHashDataSource::Storages,
+            CalcAccountsHashDataSource::Index,
+        ] {
+            assert_eq!(
+                calculate_accounts_hash(data_source, Some(&thread_pool)),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_calculate_accounts_hash_deadline() {
+        solana_logger::setup();
+
+        let slot = 0;
+        let ancestors = vec![(slot, 0)].into_iter().collect();
+        let db = AccountsDb::new(Vec::new(), &ClusterType::Development);
+        for i in 0..100 {
+            let account = AccountSharedData::new(i + 1, 0, &Pubkey::default());
+            db.store_uncached(slot, &[(&Pubkey::new_unique(), &account)]);
+        }
+        db.add_root(slot);
+        let calculate_accounts_hash_with_action = |deadline: Option<Instant>, on_overrun| {
+            db.calculate_accounts_hash(
+                CalcAccountsHashDataSource::Storages,
+                slot,
+                &CalcAccountsHashConfig {
+                    ancestors: Some(&ancestors),
+                    deadline: deadline.map(|instant| CalcDeadline {
+                        instant,
+                        on_overrun,
+                    }),
+                    ..CalcAccountsHashConfig::default()
+                },
+            )
+        };
+        let calculate_accounts_hash =
+            |deadline| calculate_accounts_hash_with_action(deadline, DeadlineOverrunAction::Abort);
+
+        // a deadline that has already passed can never be met, even without a throughput estimate
+        assert_matches!(
+            calculate_accounts_hash(Some(Instant::now())),
+            Err(BankHashVerificationError::AccountsHash(
+                AccountsHashError::ProjectedDeadlineOverrun { remaining, .. }
+            )) if remaining.is_zero()
+        );
+
+        // at one account per second, 100 accounts cannot be hashed within a minute
+        db.accounts_hash_throughput
+            .update(1, Duration::from_secs(1));
+        assert_matches!(
+            calculate_accounts_hash(Some(Instant::now() + Duration::from_secs(60))),
+            Err(BankHashVerificationError::AccountsHash(
+                AccountsHashError::ProjectedDeadlineOverrun { projected, .. }
+            )) if projected == Duration::from_secs(100)
+        );
+
+        let expected = calculate_accounts_hash(None).unwrap();
+        assert_eq!(
+            calculate_accounts_hash(Some(Instant::now() + Duration::from_secs(3600))).unwrap(),
+            expected
+        );
+
+        // an overrun that is only reported does not affect the result
+        assert_eq!(
+            calculate_accounts_hash_with_action(
+                Some(Instant::now()),
+                DeadlineOverrunAction::Report
+            )
+            .unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_calculate_accounts_hash_exclude_executable() {
+        solana_logger::setup();
+
+        let slot = 0;
+        let ancestors = vec![(slot, 0)].into_iter().collect();
+        let accounts = (0..10)
+            .map(|i| {
+                let mut account = AccountSharedData::new(i + 1, 0, &Pubkey::default());
+                account.set_executable(i % 3 == 0);
+                (Pubkey::new_unique(), account)
+            })
+            .collect::<Vec<_>>();
+        let data_accounts = accounts
+            .iter()
+            .filter(|(_, account)| !account.executable())
+            .cloned()
+            .collect::<Vec<_>>();
+        let data_lamports: u64 = data_accounts
+            .iter()
+            .map(|(_, account)| account.lamports())
+            .sum();
+
+        let store_accounts = |accounts: &[(Pubkey, AccountSharedData)]| {
+            let db = AccountsDb::new(Vec::new(), &ClusterType::Development);
+            for (pubkey, account) in accounts {
+                db.store_uncached(slot, &[(pubkey, account)]);
+            }
+            db.add_root(slot);
+            db
+        };
+        let calculate_accounts_hash = |db: &AccountsDb, data_source, exclude_executable| {
+            db.calculate_accounts_hash(
+                data_source,
+                slot,
+                &CalcAccountsHashConfig {
+                    ancestors: Some(&ancestors),
+                    exclude_executable,
+                    ..CalcAccountsHashConfig::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let db = store_accounts(&accounts);
+        let data_db = store_accounts(&data_accounts);
+        for data_source in [
+            CalcAccountsHashDataSource::Storages,
+            CalcAccountsHashDataSource::Index,
+        ] {
+            // filtering out exactly the executable accounts matches hashing only the data accounts
+            let filtered = calculate_accounts_hash(&db, data_source, true);
+            assert_eq!(filtered.1, data_lamports);
+            assert_eq!(
+                filtered,
+                calculate_accounts_hash(&data_db, data_source, false)
+            );
+            // the filtered hash is reproducible
+            assert_eq!(filtered, calculate_accounts_hash(&db, data_source, true));
+            // with no executable accounts, the option has no effect
+            assert_eq!(
+                filtered,
+                calculate_accounts_hash(&data_db, data_source, true)
+            );
+            assert_ne!(filtered, calculate_accounts_hash(&db, data_source, false));
+        }
+    }
+
+    #[test]
+    fn test_calculate_accounts_hash_exclude_executable_latest_version() {
+        solana_logger::setup();
+
+        let ancestors = vec![(0, 0), (1, 0)].into_iter().collect();
+        // the latest version of `pubkey_executable` is executable, but an older one is not
+        let pubkey_executable = Pubkey::new_unique();
+        // the latest version of `pubkey_data` is not executable, but an older one is
+        let pubkey_data = Pubkey::new_unique();
+        let new_account = |lamports, executable| {
+            let mut account = AccountSharedData::new(lamports, 0, &Pubkey::default());
+            account.set_executable(executable);
+            account
+        };
+
+        let db = AccountsDb::new(Vec::new(), &ClusterType::Development);
+        db.store_uncached(0, &[(&pubkey_executable, &new_account(1, false))]);
+        db.store_uncached(0, &[(&pubkey_data, &new_account(2, true))]);
+        db.add_root(0);
+        db.store_uncached(1, &[(&pubkey_executable, &new_account(3, true))]);
+        db.store_uncached(1, &[(&pubkey_data, &new_account(4, false))]);
+        db.add_root(1);
+
+        let calculate_accounts_hash = |data_source| {
+            db.calculate_accounts_hash(
+                data_source,
+                1,
+                &CalcAccountsHashConfig {
+                    ancestors: Some(&ancestors),
+                    exclude_executable: true,
+                    ..CalcAccountsHashConfig::default()
+                },
+            )
+            .unwrap()
+        };
+        // only the latest version of each account counts, so only `pubkey_data` is hashed
+        let from_storages = calculate_accounts_hash(CalcAccountsHashDataSource::Storages);
+        assert_eq!(from_storages.1, 4);
+        assert_eq!(
+            from_storages,
+            calculate_accounts_hash(CalcAccountsHashDataSource::Index)
+        );
+    }
+
+    #[test]
+    fn test_calculate_accounts_hash_owner_filter() {
+        solana_logger::setup();
+
+        let slot = 0;
+        let ancestors = vec![(slot, 0)].into_iter().collect();
+        let owner = Pubkey::new_unique();
+        let other_owner = Pubkey::new_unique();
+        let accounts = (0..10)
+            .map(|i| {
+                let account_owner = if i % 2 == 0 { &owner } else { &other_owner };
+                let account = AccountSharedData::new(i + 1, 0, account_owner);
+                (Pubkey::new_unique(), account)
+            })
+            .collect::<Vec<_>>();
+        let owned_lamports: u64 = accounts
+            .iter()
+            .filter(|(_, account)| account.owner() == &owner)
+            .map(|(_, account)| account.lamports())
+            .sum();
+
+        let store_accounts = |accounts: &[(Pubkey, AccountSharedData)]| {
+            let db = AccountsDb::new(Vec::new(), &ClusterType::Development);
+            for (pubkey, account) in accounts {
+                db.store_uncached(slot, &[(pubkey, account)]);
+            }
+            db.add_root(slot);
+            db
+        };
+        let calculate_accounts_hash = |db: &AccountsDb, data_source| {
+            db.calculate_accounts_hash(
+                data_source,
+                slot,
+                &CalcAccountsHashConfig {
+                    ancestors: Some(&ancestors),
+                    owner_filter: Some(owner),
+                    ..CalcAccountsHashConfig::default()
+                },
+            )
+            .unwrap()
+        };
+        let with_lamports = |index: usize, lamports| {
+            let mut accounts = accounts.clone();
+            accounts[index].1.set_lamports(lamports);
+            accounts
+        };
+
+        let db = store_accounts(&accounts);
+        // changing the lamports of an account owned by `owner` changes the filtered hash
+        let owned_changed_db = store_accounts(&with_lamports(0, 100));
+        // changing the lamports of any other account does not
+        let other_changed_db = store_accounts(&with_lamports(1, 100));
+        for data_source in [
+            CalcAccountsHashDataSource::Storages,
+            CalcAccountsHashDataSource::Index,
+        ] {
+            let filtered = calculate_accounts_hash(&db, data_source);
+            assert_eq!(filtered.1, owned_lamports);
+            assert_ne!(
+                filtered,
+                calculate_accounts_hash(&owned_changed_db, data_source)
+            );
+            assert_eq!(
+                filtered,
+                calculate_accounts_hash(&other_changed_db, data_source)
+            );
+        }
+    }
+
+    #[test]
+    fn test_calculate_accounts_hash_owner_filter_latest_version() {
         solana_logger::setup();
 
-        let (storages, _size, _slot_expected) = sample_storage();
+        let ancestors = vec![(0, 0), (1, 0)].into_iter().collect();
+        let owner = Pubkey::new_unique();
+        let other_owner = Pubkey::new_unique();
+        // `pubkey_reassigned` is owned by `owner`, then reassigned to `other_owner`
+        let pubkey_reassigned = Pubkey::new_unique();
+        // `pubkey_assigned` is owned by `other_owner`, then assigned to `owner`
+        let pubkey_assigned = Pubkey::new_unique();
+
         let db = AccountsDb::new(Vec::new(), &ClusterType::Development);
-        let result = db
-            .calculate_accounts_hash_from_storages(
-                &CalcAccountsHashConfig::default(),
-                &get_storage_refs(&storages),
-                HashStats::default(),
+        db.store_uncached(
+            0,
+            &[(&pubkey_reassigned, &AccountSharedData::new(1, 0, &owner))],
+        );
+        db.store_uncached(
+            0,
+            &[(
+                &pubkey_assigned,
+                &AccountSharedData::new(2, 0, &other_owner),
+            )],
+        );
+        db.add_root(0);
+        db.store_uncached(
+            1,
+            &[(
+                &pubkey_reassigned,
+                &AccountSharedData::new(3, 0, &other_owner),
+            )],
+        );
+        db.store_uncached(
+            1,
+            &[(&pubkey_assigned, &AccountSharedData::new(4, 0, &owner))],
+        );
+        db.add_root(1);
+
+        let calculate_accounts_hash = |data_source| {
+            db.calculate_accounts_hash(
+                data_source,
+                1,
+                &CalcAccountsHashConfig {
+                    ancestors: Some(&ancestors),
+                    owner_filter: Some(owner),
+                    ..CalcAccountsHashConfig::default()
+                },
             )
-            .unwrap();
-        let expected_hash = Hash::from_str("GKot5hBsd81kMupNCXHaqbhv3huEbxAFMLnpcX2hniwn").unwrap();
-        assert_eq!(result, (expected_hash, 0));
+            .unwrap()
+        };
+        // only the latest version of each account counts, so only `pubkey_assigned` is hashed
+        let from_storages = calculate_accounts_hash(CalcAccountsHashDataSource::Storages);
+        assert_eq!(from_storages.1, 4);
+        assert_eq!(
+            from_storages,
+            calculate_accounts_hash(CalcAccountsHashDataSource::Index)
+        );
     }
 
     #[test]
-    fn test_accountsdb_calculate_accounts_hash_from_storages() {
+    fn test_calculate_accounts_hash_max_memory_bytes() {
         solana_logger::setup();
 
-        let (storages, raw_expected) = sample_storages_and_accounts();
-        let expected_hash =
-            AccountsHash::compute_merkle_root_loop(raw_expected.clone(), MERKLE_FANOUT, |item| {
-                item.hash
-            });
-        let sum = raw_expected.iter().map(|item| item.lamports).sum();
         let db = AccountsDb::new(Vec::new(), &ClusterType::Development);
-        let result = db
-            .calculate_accounts_hash_from_storages(
-                &CalcAccountsHashConfig::default(),
-                &get_storage_refs(&storages),
-                HashStats::default(),
+        let pubkeys = (0..20).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+        let max_slot = 5;
+        for slot in 0..=max_slot {
+            // later slots update some of the accounts stored in earlier slots, and remove some
+            for (i, pubkey) in pubkeys.iter().enumerate() {
+                if (i + slot as usize) % 3 == 0 {
+                    let lamports = if i % 7 == 0 { 0 } else { slot + i as u64 + 1 };
+                    let account = AccountSharedData::new(lamports, 0, &Pubkey::default());
+                    db.store_uncached(slot, &[(pubkey, &account)]);
+                }
+            }
+            db.add_root(slot);
+        }
+
+        let ancestors = (0..=max_slot).map(|slot| (slot, 0)).collect();
+        let calculate_accounts_hash = |max_memory_bytes| {
+            db.calculate_accounts_hash(
+                CalcAccountsHashDataSource::Storages,
+                max_slot,
+                &CalcAccountsHashConfig {
+                    ancestors: Some(&ancestors),
+                    max_memory_bytes,
+                    ..CalcAccountsHashConfig::default()
+                },
             )
-            .unwrap();
+            .unwrap()
+        };
 
-        assert_eq!(result, (expected_hash, sum));
+        let expected = calculate_accounts_hash(None);
+        // a tiny limit flushes the scanned accounts after every slot
+        assert_eq!(calculate_accounts_hash(Some(1)), expected);
+        // a limit never reached flushes once per chunk, like the unbounded calculation
+        assert_eq!(calculate_accounts_hash(Some(u64::MAX)), expected);
+        assert_eq!(
+            expected,
+            db.calculate_accounts_hash(
+                CalcAccountsHashDataSource::Index,
+                max_slot,
+                &CalcAccountsHashConfig {
+                    ancestors: Some(&ancestors),
+                    ..CalcAccountsHashConfig::default()
+                },
+            )
+            .unwrap()
+        );
     }
 
     fn sample_storage() -> (SnapshotStorages, usize, Slot) {
@@ -11600,6 +13418,102 @@ pub mod tests {
         assert_eq!(found_accounts, vec![pubkey2]);
     }
 
+    #[test]
+    fn test_lazy_secondary_indexes() {
+        solana_logger::setup();
+        let mint_key = Pubkey::new_unique();
+        let mut account_data_with_mint = vec![0; inline_spl_token::Account::get_packed_len()];
+        account_data_with_mint[..PUBKEY_BYTES].clone_from_slice(&(mint_key.to_bytes()));
+        let mut token_account = AccountSharedData::new(1, 0, &inline_spl_token::id());
+        token_account.set_data(account_data_with_mint);
+
+        let db = AccountsDb::new_single_for_tests();
+        let mut pubkeys = HashSet::new();
+        for slot in 0..3 {
+            let pubkey = Pubkey::new_unique();
+            db.store_uncached(slot, &[(&pubkey, &token_account)]);
+            pubkeys.insert(pubkey);
+            let other_account = AccountSharedData::new(1, 0, &Pubkey::default());
+            db.store_uncached(slot, &[(&Pubkey::new_unique(), &other_account)]);
+            db.add_root(slot);
+        }
+
+        let load = |lazy| {
+            let new_db = AccountsDb::new_with_config_for_tests(
+                Vec::new(),
+                &ClusterType::Development,
+                AccountSecondaryIndexes {
+                    lazy,
+                    ..spl_token_mint_index_enabled()
+                },
+                false,
+                AccountShrinkThreshold::default(),
+            );
+            for entry in db.storage.map.iter() {
+                new_db
+                    .storage
+                    .map
+                    .insert(*entry.key(), entry.value().clone());
+            }
+            new_db.generate_index(None, false, &GenesisConfig::default());
+            for slot in 0..3 {
+                new_db.add_root(slot);
+            }
+            new_db
+        };
+        let index_key = IndexKey::SplTokenMint(mint_key);
+        // the pubkeys in the secondary index, without building it
+        let indexed_pubkeys = |db: &AccountsDb| {
+            let mut found = HashSet::new();
+            db.accounts_index
+                .index_scan_accounts(
+                    &Ancestors::default(),
+                    0,
+                    index_key,
+                    |pubkey, _| {
+                        found.insert(*pubkey);
+                    },
+                    &ScanConfig::default(),
+                )
+                .unwrap();
+            found
+        };
+        let index_scan = |db: &AccountsDb| {
+            let mut found = HashSet::new();
+            let used_index = db
+                .index_scan_accounts(
+                    &Ancestors::default(),
+                    0,
+                    index_key,
+                    |account| {
+                        found.insert(*account.unwrap().0);
+                    },
+                    &ScanConfig::default(),
+                )
+                .unwrap();
+            assert!(used_index);
+            found
+        };
+
+        let eager_db = load(false);
+        assert_eq!(indexed_pubkeys(&eager_db), pubkeys);
+        assert_eq!(index_scan(&eager_db), pubkeys);
+
+        // loading leaves the work of building the index to the first indexed scan
+        let lazy_db = load(true);
+        assert!(indexed_pubkeys(&lazy_db).is_empty());
+        assert_eq!(index_scan(&lazy_db), pubkeys);
+        assert_eq!(indexed_pubkeys(&lazy_db), pubkeys);
+
+        // once built, the index is kept up to date by stores
+        let pubkey = Pubkey::new_unique();
+        lazy_db.store_uncached(3, &[(&pubkey, &token_account)]);
+        lazy_db.add_root(3);
+        pubkeys.insert(pubkey);
+        assert_eq!(indexed_pubkeys(&lazy_db), pubkeys);
+        assert_eq!(index_scan(&lazy_db), pubkeys);
+    }
+
     #[test]
     fn test_clean_max_slot_zero_lamport_account() {
         solana_logger::setup();
@@ -12408,26 +14322,6 @@ pub mod tests {
         }
     }
 
-    // something we can get a ref to
-    lazy_static! {
-        pub static ref EPOCH_SCHEDULE: EpochSchedule = EpochSchedule::default();
-        pub static ref RENT_COLLECTOR: RentCollector = RentCollector::default();
-    }
-
-    impl<'a> CalcAccountsHashConfig<'a> {
-        fn default() -> Self {
-            Self {
-                use_bg_thread_pool: false,
-                check_hash: false,
-                ancestors: None,
-                epoch_schedule: &EPOCH_SCHEDULE,
-                rent_collector: &RENT_COLLECTOR,
-                store_detailed_debug_info_on_failure: false,
-                full_snapshot: None,
-            }
-        }
-    }
-
     // this test tests check_hash=true, which is unsupported behavior at the moment. It cannot be enabled by anything but these tests.
     #[ignore]
     #[test]
@@ -13358,6 +15252,61 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_storage_count_by_slot() {
+        let db = AccountsDb::new_single_for_tests();
+        assert!(db.storage_count_by_slot().is_empty());
+
+        for (slot, num_storages) in [(1, 1), (2, 3), (4, 2)] {
+            for _ in 0..num_storages {
+                db.create_and_insert_store(slot, 1000, "test_storage_count_by_slot");
+            }
+            db.add_root(slot);
+        }
+        // unrooted slots are not reported
+        db.create_and_insert_store(5, 1000, "test_storage_count_by_slot");
+
+        assert_eq!(
+            db.storage_count_by_slot(),
+            BTreeMap::from([(1, 1), (2, 3), (4, 2)]),
+        );
+    }
+
+    #[test]
+    fn test_shrink_candidates_scored() {
+        let db = AccountsDb::new_single_for_tests();
+        assert!(db.shrink_candidates_scored().is_empty());
+
+        // one store per slot, with 0, 1, and 2 of its 4 pages alive
+        let store_file_size = 4 * PAGE_SIZE;
+        for (slot, alive_bytes) in [(3, 2 * PAGE_SIZE), (5, 0), (7, PAGE_SIZE)] {
+            let store = Arc::new(AccountStorageEntry::new(
+                Path::new(""),
+                slot,
+                slot as AppendVecId,
+                store_file_size,
+            ));
+            store
+                .alive_bytes
+                .store(alive_bytes as usize, Ordering::Release);
+            db.shrink_candidate_slots
+                .lock()
+                .unwrap()
+                .entry(slot)
+                .or_default()
+                .insert(store.append_vec_id(), store);
+        }
+
+        // the more fragmented, the higher the score
+        assert_eq!(
+            db.shrink_candidates_scored(),
+            vec![(5, 1.0), (7, 0.75), (3, 0.5)],
+        );
+
+        // nothing was shrunk
+        assert_eq!(db.shrink_candidate_slots.lock().unwrap().len(), 3);
+    }
+
     #[test]
     fn test_select_candidates_by_total_usage_no_candidates() {
         // no input candidates -- none should be selected
@@ -13367,6 +15316,7 @@ pub mod tests {
         let (selected_candidates, next_candidates) = AccountsDb::select_candidates_by_total_usage(
             &candidates,
             DEFAULT_ACCOUNTS_SHRINK_RATIO,
+            DEFAULT_ACCOUNTS_SHRINK_MIN_RECLAIM_BYTES,
         );
 
         assert_eq!(0, selected_candidates.len());
@@ -13440,7 +15390,7 @@ pub mod tests {
         // to the candidates list for next round.
         let target_alive_ratio = 0.6;
         let (selected_candidates, next_candidates) =
-            AccountsDb::select_candidates_by_total_usage(&candidates, target_alive_ratio);
+            AccountsDb::select_candidates_by_total_usage(&candidates, target_alive_ratio, 0);
         assert_eq!(1, selected_candidates.len());
         assert_eq!(1, selected_candidates[&common_slot_id].len());
         assert!(selected_candidates[&common_slot_id].contains(&store1.append_vec_id()));
@@ -13512,7 +15462,7 @@ pub mod tests {
         // Set the target ratio to default (0.8), both store1 and store2 must be selected and store3 is ignored.
         let target_alive_ratio = DEFAULT_ACCOUNTS_SHRINK_RATIO;
         let (selected_candidates, next_candidates) =
-            AccountsDb::select_candidates_by_total_usage(&candidates, target_alive_ratio);
+            AccountsDb::select_candidates_by_total_usage(&candidates, target_alive_ratio, 0);
         assert_eq!(1, selected_candidates.len());
         assert_eq!(2, selected_candidates[&common_slot_id].len());
         assert!(selected_candidates[&common_slot_id].contains(&store1.append_vec_id()));
@@ -13520,6 +15470,47 @@ pub mod tests {
         assert_eq!(0, next_candidates.len());
     }
 
+    #[test]
+    fn test_select_candidates_by_total_usage_min_reclaim_bytes() {
+        // a store that is sparse enough to shrink, but would not reclaim enough bytes, is skipped
+        solana_logger::setup();
+        let mut candidates: ShrinkCandidates = HashMap::new();
+
+        let common_store_path = Path::new("");
+        let common_slot_id = 12;
+        let store_file_size = 2 * PAGE_SIZE;
+
+        let store1_id = 22;
+        let store1 = Arc::new(AccountStorageEntry::new(
+            common_store_path,
+            common_slot_id,
+            store1_id,
+            store_file_size,
+        ));
+        // The store1's alive_ratio is 0.5: shrinking it reclaims 1 page.
+        store1
+            .alive_bytes
+            .store(PAGE_SIZE as usize, Ordering::Release);
+        candidates
+            .entry(common_slot_id)
+            .or_default()
+            .insert(store1.append_vec_id(), store1.clone());
+
+        let target_alive_ratio = 0.6;
+        let (selected_candidates, next_candidates) =
+            AccountsDb::select_candidates_by_total_usage(&candidates, target_alive_ratio, 0);
+        assert!(selected_candidates[&common_slot_id].contains(&store1.append_vec_id()));
+        assert!(next_candidates.is_empty());
+
+        let (selected_candidates, next_candidates) = AccountsDb::select_candidates_by_total_usage(
+            &candidates,
+            target_alive_ratio,
+            PAGE_SIZE + 1,
+        );
+        assert!(selected_candidates.is_empty());
+        assert!(next_candidates.is_empty());
+    }
+
     #[test]
     fn test_select_candidates_by_total_usage_all_clean() {
         // 2 candidates, they must be selected to achieve the target alive ratio
@@ -13572,7 +15563,7 @@ pub mod tests {
         // Set the target ratio to default (0.8), both stores from the two different slots must be selected.
         let target_alive_ratio = DEFAULT_ACCOUNTS_SHRINK_RATIO;
         let (selected_candidates, next_candidates) =
-            AccountsDb::select_candidates_by_total_usage(&candidates, target_alive_ratio);
+            AccountsDb::select_candidates_by_total_usage(&candidates, target_alive_ratio, 0);
         assert_eq!(2, selected_candidates.len());
         assert_eq!(1, selected_candidates[&slot1].len());
         assert_eq!(1, selected_candidates[&slot2].len());
@@ -14121,6 +16112,127 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_generate_index_threads() {
+        let db = AccountsDb::new_single_for_tests();
+        let pubkeys: Vec<_> = (0..20).map(|_| Pubkey::new_unique()).collect();
+        for slot in 0..10 {
+            // each pubkey is stored in several slots
+            for (i, pubkey) in pubkeys.iter().enumerate() {
+                if (i + slot as usize) % 3 == 0 {
+                    let account = AccountSharedData::new(slot + 1, i, &Pubkey::default());
+                    db.store_uncached(slot, &[(pubkey, &account)]);
+                }
+            }
+            db.add_root(slot);
+        }
+
+        let generate_index = |generate_index_threads| {
+            let new_db = AccountsDb::new_with_config(
+                Vec::new(),
+                &ClusterType::Development,
+                AccountSecondaryIndexes::default(),
+                false,
+                AccountShrinkThreshold::default(),
+                Some(AccountsDbConfig {
+                    generate_index_threads,
+                    ..ACCOUNTS_DB_CONFIG_FOR_TESTING
+                }),
+                None,
+                &Arc::default(),
+            );
+            for entry in db.storage.map.iter() {
+                new_db
+                    .storage
+                    .map
+                    .insert(*entry.key(), entry.value().clone());
+            }
+            new_db.generate_index(None, false, &GenesisConfig::default());
+            pubkeys
+                .iter()
+                .map(|pubkey| {
+                    let entry = new_db
+                        .accounts_index
+                        .get_account_read_entry(pubkey)
+                        .unwrap();
+                    let mut slot_list = entry.slot_list().clone();
+                    slot_list.sort_unstable_by_key(|(slot, _account_info)| *slot);
+                    (slot_list, entry.ref_count())
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let index_contents = generate_index(Some(1));
+        assert_eq!(index_contents.len(), pubkeys.len());
+        assert!(index_contents
+            .iter()
+            .all(|(slot_list, ref_count)| !slot_list.is_empty()
+                && slot_list.len() as RefCount == *ref_count));
+        assert_eq!(index_contents, generate_index(Some(4)));
+        assert_eq!(index_contents, generate_index(None));
+    }
+
+    #[test]
+    fn test_storage_layout() {
+        let db = AccountsDb::new_single_for_tests();
+        let slot = 1;
+        assert!(db.storage_layout(slot).is_empty());
+
+        let accounts = (0..10)
+            .map(|i| {
+                (
+                    Pubkey::new_unique(),
+                    AccountSharedData::new(1, i * 13, &Pubkey::default()),
+                )
+            })
+            .collect::<Vec<_>>();
+        for (pubkey, account) in &accounts {
+            db.store_uncached(slot, &[(pubkey, account)]);
+        }
+
+        let layout = db.storage_layout(slot);
+        assert_eq!(layout.len(), accounts.len());
+        for (pubkey, account) in &accounts {
+            let location = layout
+                .iter()
+                .find(|location| location.pubkey == *pubkey)
+                .unwrap();
+            assert!(location.stored_size >= account.data().len());
+        }
+        // within each storage, accounts must not overlap
+        for locations in layout.windows(2) {
+            if locations[0].store_id == locations[1].store_id {
+                assert!(locations[0].offset + locations[0].stored_size <= locations[1].offset);
+            }
+        }
+    }
+
+    #[test]
+    fn test_oldest_unflushed_slot() {
+        let mut db = AccountsDb::new(Vec::new(), &ClusterType::Development);
+        db.caching_enabled = true;
+        let account0 = AccountSharedData::new(1, 0, &Pubkey::default());
+        assert_eq!(db.oldest_unflushed_slot(), None);
+
+        // write out of order, so the oldest slot is not the first one written
+        for slot in [7, 3, 5, 9] {
+            db.store_cached((slot, &[(&Pubkey::new_unique(), &account0)][..]), None);
+            db.mark_slot_frozen(slot);
+        }
+        assert_eq!(db.oldest_unflushed_slot(), Some(3));
+
+        // flushing the oldest roots advances the oldest unflushed slot
+        db.add_root(3);
+        db.add_root(5);
+        db.flush_accounts_cache(true, Some(5));
+        assert_eq!(db.oldest_unflushed_slot(), Some(7));
+
+        db.add_root(7);
+        db.add_root(9);
+        db.flush_accounts_cache(true, Some(9));
+        assert_eq!(db.oldest_unflushed_slot(), None);
+    }
+
     fn max_cache_slots() -> usize {
         // this used to be the limiting factor - used here to facilitate tests.
         200
@@ -15829,7 +17941,7 @@ pub mod tests {
             store_file_size,
         ));
         match accounts.shrink_ratio {
-            AccountShrinkThreshold::TotalSpace { shrink_ratio } => {
+            AccountShrinkThreshold::TotalSpace { shrink_ratio, .. } => {
                 assert_eq!(
                     (DEFAULT_ACCOUNTS_SHRINK_RATIO * 100.) as u64,
                     (shrink_ratio * 100.) as u64
@@ -15843,7 +17955,10 @@ pub mod tests {
         assert!(accounts.is_candidate_for_shrink(&entry, false));
         entry.alive_bytes.store(5000, Ordering::Release);
         assert!(!accounts.is_candidate_for_shrink(&entry, false));
-        accounts.shrink_ratio = AccountShrinkThreshold::TotalSpace { shrink_ratio: 0.3 };
+        accounts.shrink_ratio = AccountShrinkThreshold::TotalSpace {
+            shrink_ratio: 0.3,
+            min_reclaim_bytes: 0,
+        };
         entry.alive_bytes.store(3000, Ordering::Release);
         assert!(accounts.is_candidate_for_shrink(&entry, false));
         accounts.shrink_ratio = AccountShrinkThreshold::IndividualStore { shrink_ratio: 0.3 };
@@ -1,8 +1,12 @@
 use {
-    crate::{accounts_db::SnapshotStorages, ancestors::Ancestors, rent_collector::RentCollector},
+    crate::{
+        accounts_db::{SnapshotStorages, StartupProgress},
+        ancestors::Ancestors,
+        rent_collector::RentCollector,
+    },
     core::ops::Range,
     log::*,
-    rayon::prelude::*,
+    rayon::{prelude::*, ThreadPool},
     solana_measure::measure::Measure,
     solana_sdk::{
         hash::{Hash, Hasher},
@@ -12,15 +16,49 @@ use {
     },
     std::{
         borrow::Borrow,
+        collections::HashMap,
         convert::TryInto,
         sync::{
             atomic::{AtomicU64, AtomicUsize, Ordering},
             Mutex,
         },
+        time::{Duration, Instant},
     },
+    thiserror::Error,
 };
 pub const MERKLE_FANOUT: usize = 16;
 
+/// Running average of the accounts hash calculation throughput, in accounts per second
+///
+/// Used to estimate how long an upcoming calculation will take (e.g. the epoch accounts hash).
+#[derive(Debug, Default)]
+pub struct AccountsHashThroughput {
+    accounts_per_second: Mutex<Option<f64>>,
+}
+
+impl AccountsHashThroughput {
+    /// Weight of the most recent calculation in the running average
+    const NEW_SAMPLE_WEIGHT: f64 = 0.25;
+
+    /// Add a calculation that scanned `num_accounts` in `duration` to the running average
+    pub fn update(&self, num_accounts: usize, duration: Duration) {
+        if num_accounts == 0 || duration.is_zero() {
+            return;
+        }
+        let sample = num_accounts as f64 / duration.as_secs_f64();
+        let mut accounts_per_second = self.accounts_per_second.lock().unwrap();
+        *accounts_per_second = Some(match *accounts_per_second {
+            Some(average) => average + Self::NEW_SAMPLE_WEIGHT * (sample - average),
+            None => sample,
+        });
+    }
+
+    /// Get the running average, or None if no calculations have been added yet
+    pub fn get(&self) -> Option<f64> {
+        *self.accounts_per_second.lock().unwrap()
+    }
+}
+
 /// the data passed through the processing functions
 pub type SortedDataByPubkey<'a> = Vec<&'a [CalculateHashIntermediate]>;
 
@@ -58,6 +96,120 @@ pub struct CalcAccountsHashConfig<'a> {
     pub store_detailed_debug_info_on_failure: bool,
     /// `Some` if this is an incremental snapshot which only hashes slots since the base full snapshot
     pub full_snapshot: Option<FullSnapshotAccountsHashInfo>,
+    /// return an error if a pubkey is found live in more than one storage: either twice in a slot,
+    /// or in a later slot than its live version in the accounts index, which the hash would
+    /// otherwise silently use instead.  Only used when calculating from storages.
+    /// this looks up every scanned account in the index, so it is for tracking down corruption
+    pub detect_duplicates: bool,
+    /// skip executable accounts so the resulting hash only covers non-executable (data) accounts
+    /// only the latest version of each account decides whether it is skipped
+    /// the result is not a valid bank hash, it is intended for comparing data accounts across nodes
+    pub exclude_executable: bool,
+    /// if set, check whether the calculation is projected to finish by the deadline, based on the
+    /// throughput of previous calculations and the progress so far, and act on an overrun as the
+    /// deadline says.  Only used when calculating from storages.
+    pub deadline: Option<CalcDeadline>,
+    /// also collect a bloom filter of the pubkeys seen while scanning storages, see
+    /// `AccountsDb::calculate_accounts_hash_and_pubkey_bloom_from_storages()`
+    pub collect_pubkey_bloom: bool,
+    /// if set, run the calculation on a thread pool with this many threads, instead of the thread
+    /// pool it would otherwise run on (see `use_bg_thread_pool`).  The thread pool is built the
+    /// first time a size is used and kept by the AccountsDb, so later calculations reuse it.
+    pub thread_pool_size: Option<usize>,
+    /// if set, run the calculation on this thread pool, instead of the thread pool it would
+    /// otherwise run on, to control its parallelism; takes precedence over `thread_pool_size`
+    pub thread_pool: Option<&'a ThreadPool>,
+    /// if set, only hash accounts owned by this program
+    /// only the latest version of each account decides whether it is owned by the program
+    /// the result is not a valid bank hash and must not be used for consensus, it is intended for
+    /// comparing the state of a single program across forks or nodes
+    pub owner_filter: Option<Pubkey>,
+    /// if set, bound the scanned account data buffered in memory while calculating from storages
+    /// to about this many bytes, by flushing it to temporary cache files more often.  This trades
+    /// speed for a bounded footprint; the resulting hash is the same.
+    pub max_memory_bytes: Option<u64>,
+    /// also collect the lamports and number of accounts per owner while calculating from
+    /// storages, see `AccountsDb::calculate_accounts_hash_and_owner_subtotals_from_storages()`
+    pub collect_owner_subtotals: bool,
+    /// if set, the passes of this calculation are counted here; only for the accounts hash
+    /// verification at startup, see `StartupProgress`
+    pub startup_progress: Option<&'a StartupProgress>,
+}
+
+lazy_static! {
+    static ref DEFAULT_EPOCH_SCHEDULE: EpochSchedule = EpochSchedule::default();
+    static ref DEFAULT_RENT_COLLECTOR: RentCollector = RentCollector::default();
+}
+
+impl Default for CalcAccountsHashConfig<'_> {
+    /// The default epoch schedule and rent collector are only meant for tests,
+    /// callers hashing a bank's accounts must set the bank's own
+    fn default() -> Self {
+        Self {
+            use_bg_thread_pool: false,
+            check_hash: false,
+            ancestors: None,
+            epoch_schedule: &DEFAULT_EPOCH_SCHEDULE,
+            rent_collector: &DEFAULT_RENT_COLLECTOR,
+            store_detailed_debug_info_on_failure: false,
+            full_snapshot: None,
+            detect_duplicates: false,
+            exclude_executable: false,
+            deadline: None,
+            collect_pubkey_bloom: false,
+            thread_pool_size: None,
+            thread_pool: None,
+            owner_filter: None,
+            max_memory_bytes: None,
+            collect_owner_subtotals: false,
+            startup_progress: None,
+        }
+    }
+}
+
+/// When an accounts hash calculation should finish, and what to do if it is projected not to,
+/// see `CalcAccountsHashConfig::deadline`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CalcDeadline {
+    pub instant: Instant,
+    pub on_overrun: DeadlineOverrunAction,
+}
+
+/// What to do when an accounts hash calculation is projected to overrun its deadline
+///
+/// The projection depends on each node's own hardware and load, so aborting is not
+/// deterministic across nodes.  Calculations whose result every node must produce, like the epoch
+/// accounts hash, must only `Report`: an aborted EAH calculation would leave the EAH in flight,
+/// and the bank at the "stop" slot would wait for it forever.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DeadlineOverrunAction {
+    /// abort with `AccountsHashError::ProjectedDeadlineOverrun`
+    Abort,
+    /// log and report a metric, then keep calculating
+    Report,
+}
+
+/// The lamports and number of the accounts owned by one program
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OwnerSubtotal {
+    pub lamports: u64,
+    pub num_accounts: u64,
+}
+
+/// The subtotals of the accounts included in an accounts hash, per owner
+pub type OwnerSubtotals = HashMap<Pubkey, OwnerSubtotal>;
+
+/// Errors that can occur while calculating the accounts hash
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AccountsHashError {
+    #[error("account {pubkey} is live more than once, found in slots {slots:?}")]
+    DuplicateLiveAccount { pubkey: Pubkey, slots: Vec<Slot> },
+
+    #[error("calculation is projected to take another {projected:?}, but the deadline is in {remaining:?}")]
+    ProjectedDeadlineOverrun {
+        projected: Duration,
+        remaining: Duration,
+    },
 }
 
 impl<'a> CalcAccountsHashConfig<'a> {
@@ -67,6 +219,38 @@ impl<'a> CalcAccountsHashConfig<'a> {
         // skipping rewrites is not enabled in this branch. It requires a cli argument.
         true
     }
+
+    /// Act on the deadline if the rest of the calculation, projected to take `projected`, would
+    /// not finish by it; only returns an error if the deadline says to abort
+    pub fn check_deadline(&self, projected: Duration) -> Result<(), AccountsHashError> {
+        let deadline = match self.deadline {
+            Some(deadline) => deadline,
+            None => return Ok(()),
+        };
+        let remaining = deadline.instant.saturating_duration_since(Instant::now());
+        if !remaining.is_zero() && projected <= remaining {
+            return Ok(());
+        }
+        match deadline.on_overrun {
+            DeadlineOverrunAction::Abort => Err(AccountsHashError::ProjectedDeadlineOverrun {
+                projected,
+                remaining,
+            }),
+            DeadlineOverrunAction::Report => {
+                warn!(
+                    "accounts hash calculation is projected to take another {:?}, but the \
+                     deadline is in {:?}",
+                    projected, remaining
+                );
+                datapoint_warn!(
+                    "accounts_hash_projected_deadline_overrun",
+                    ("projected_us", projected.as_micros() as i64, i64),
+                    ("remaining_us", remaining.as_micros() as i64, i64),
+                );
+                Ok(())
+            }
+        }
+    }
 }
 
 // smallest, 3 quartiles, largest, average
@@ -741,11 +925,14 @@ impl AccountsHash {
             .collect::<Vec<_>>()
     }
 
-    fn de_dup_and_eliminate_zeros<'a>(
+    /// if `included_account` is set, it is called with the latest version of each account that is
+    /// included in the hash
+    pub(crate) fn de_dup_and_eliminate_zeros<'a>(
         &self,
         sorted_data_by_pubkey: &'a [SortedDataByPubkey<'a>],
         stats: &mut HashStats,
         max_bin: usize,
+        included_account: Option<&(dyn Fn(&CalculateHashIntermediate) + Sync)>,
     ) -> (Vec<Vec<&'a Hash>>, u64) {
         // 1. eliminate zero lamport accounts
         // 2. pick the highest slot or (slot = and highest version) of each pubkey
@@ -759,7 +946,7 @@ impl AccountsHash {
             .into_par_iter()
             .map(|bin| {
                 let (hashes, lamports_bin, unreduced_entries_count) =
-                    self.de_dup_accounts_in_parallel(sorted_data_by_pubkey, bin);
+                    self.de_dup_accounts_in_parallel(sorted_data_by_pubkey, bin, included_account);
                 {
                     let mut lock = min_max_sum_entries_hashes.lock().unwrap();
                     let (mut min, mut max, mut lamports_sum, mut entries, mut hash_total) = *lock;
@@ -836,6 +1023,7 @@ impl AccountsHash {
         &self,
         pubkey_division: &'a [SortedDataByPubkey<'a>],
         pubkey_bin: usize,
+        included_account: Option<&(dyn Fn(&CalculateHashIntermediate) + Sync)>,
     ) -> (Vec<&'a Hash>, u64, usize) {
         let len = pubkey_division.len();
         let mut item_len = 0;
@@ -909,6 +1097,9 @@ impl AccountsHash {
                     item.lamports as u128 + overall_sum as u128,
                 );
                 hashes.push(&item.hash);
+                if let Some(included_account) = included_account {
+                    included_account(item);
+                }
             }
             if !duplicate_pubkey_indexes.is_empty() {
                 // skip past duplicate keys in earlier slots
@@ -942,15 +1133,40 @@ impl AccountsHash {
     //   vec: [0..bins] - where bins are pubkey ranges (these are ordered by Pubkey range)
     //     vec: [..] - items which fit in the containing bin. Sorted by: Pubkey, higher Slot, higher Write version (if pubkey =)
     pub fn rest_of_hash_calculation(
+        &self,
+        data_sections_by_pubkey: Vec<SortedDataByPubkey<'_>>,
+        stats: &mut HashStats,
+        is_last_pass: bool,
+        previous_state: PreviousPass,
+        max_bin: usize,
+    ) -> (Hash, u64, PreviousPass) {
+        self.rest_of_hash_calculation_with_included_accounts(
+            data_sections_by_pubkey,
+            stats,
+            is_last_pass,
+            previous_state,
+            max_bin,
+            None,
+        )
+    }
+
+    /// Like `rest_of_hash_calculation()`, and if `included_account` is set, it is called with the
+    /// latest version of each account that is included in the hash, as de-dup finds it
+    pub fn rest_of_hash_calculation_with_included_accounts(
         &self,
         data_sections_by_pubkey: Vec<SortedDataByPubkey<'_>>,
         mut stats: &mut HashStats,
         is_last_pass: bool,
         mut previous_state: PreviousPass,
         max_bin: usize,
+        included_account: Option<&(dyn Fn(&CalculateHashIntermediate) + Sync)>,
     ) -> (Hash, u64, PreviousPass) {
-        let (mut hashes, mut total_lamports) =
-            self.de_dup_and_eliminate_zeros(&data_sections_by_pubkey, stats, max_bin);
+        let (mut hashes, mut total_lamports) = self.de_dup_and_eliminate_zeros(
+            &data_sections_by_pubkey,
+            stats,
+            max_bin,
+            included_account,
+        );
 
         total_lamports += previous_state.lamports;
 
@@ -1061,6 +1277,24 @@ impl AccountsHash {
 pub mod tests {
     use {super::*, std::str::FromStr};
 
+    #[test]
+    fn test_accounts_hash_throughput() {
+        let throughput = AccountsHashThroughput::default();
+        assert_eq!(throughput.get(), None);
+
+        // empty calculations are ignored
+        throughput.update(0, Duration::from_secs(1));
+        throughput.update(1_000, Duration::ZERO);
+        assert_eq!(throughput.get(), None);
+
+        throughput.update(1_000, Duration::from_secs(1));
+        assert_eq!(throughput.get(), Some(1_000.0));
+
+        // later calculations move the average towards their throughput
+        throughput.update(5_000, Duration::from_secs(1));
+        assert_eq!(throughput.get(), Some(2_000.0));
+    }
+
     #[test]
     fn test_accountsdb_div_ceil() {
         assert_eq!(AccountsHash::div_ceil(10, 3), 4);
@@ -1459,7 +1693,8 @@ pub mod tests {
         }]]];
         let temp_vec = vec.to_vec();
         let slice = convert_to_slice2(&temp_vec);
-        let (hashes, lamports, _) = AccountsHash::default().de_dup_accounts_in_parallel(&slice, 0);
+        let (hashes, lamports, _) =
+            AccountsHash::default().de_dup_accounts_in_parallel(&slice, 0, None);
         assert_eq!(vec![&Hash::default()], hashes);
         assert_eq!(lamports, 1);
     }
@@ -1470,25 +1705,33 @@ pub mod tests {
         let accounts_hash = AccountsHash::default();
 
         let vec = vec![vec![], vec![]];
-        let (hashes, lamports) =
-            accounts_hash.de_dup_and_eliminate_zeros(&vec, &mut HashStats::default(), one_range());
+        let (hashes, lamports) = accounts_hash.de_dup_and_eliminate_zeros(
+            &vec,
+            &mut HashStats::default(),
+            one_range(),
+            None,
+        );
         assert_eq!(
             vec![&Hash::default(); 0],
             hashes.into_iter().flatten().collect::<Vec<_>>()
         );
         assert_eq!(lamports, 0);
         let vec = vec![];
-        let (hashes, lamports) =
-            accounts_hash.de_dup_and_eliminate_zeros(&vec, &mut HashStats::default(), zero_range());
+        let (hashes, lamports) = accounts_hash.de_dup_and_eliminate_zeros(
+            &vec,
+            &mut HashStats::default(),
+            zero_range(),
+            None,
+        );
         let empty: Vec<Vec<&Hash>> = Vec::default();
         assert_eq!(empty, hashes);
         assert_eq!(lamports, 0);
 
-        let (hashes, lamports, _) = accounts_hash.de_dup_accounts_in_parallel(&[], 1);
+        let (hashes, lamports, _) = accounts_hash.de_dup_accounts_in_parallel(&[], 1, None);
         assert_eq!(vec![&Hash::default(); 0], hashes);
         assert_eq!(lamports, 0);
 
-        let (hashes, lamports, _) = accounts_hash.de_dup_accounts_in_parallel(&[], 2);
+        let (hashes, lamports, _) = accounts_hash.de_dup_accounts_in_parallel(&[], 2, None);
         assert_eq!(vec![&Hash::default(); 0], hashes);
         assert_eq!(lamports, 0);
     }
@@ -1571,15 +1814,18 @@ pub mod tests {
                     let slice2 = vec![vec![slice.to_vec()]];
                     let slice = &slice2[..];
                     let slice_temp = convert_to_slice2(&slice2);
-                    let (hashes2, lamports2, _) = hash.de_dup_accounts_in_parallel(&slice_temp, 0);
+                    let (hashes2, lamports2, _) =
+                        hash.de_dup_accounts_in_parallel(&slice_temp, 0, None);
                     let slice3 = convert_to_slice2(&slice2);
-                    let (hashes3, lamports3, _) = hash.de_dup_accounts_in_parallel(&slice3, 0);
+                    let (hashes3, lamports3, _) =
+                        hash.de_dup_accounts_in_parallel(&slice3, 0, None);
                     let vec = slice.to_vec();
                     let slice4 = convert_to_slice2(&vec);
                     let (hashes4, lamports4) = hash.de_dup_and_eliminate_zeros(
                         &slice4,
                         &mut HashStats::default(),
                         end - start,
+                        None,
                     );
                     let vec = slice.to_vec();
                     let slice5 = convert_to_slice2(&vec);
@@ -1587,6 +1833,7 @@ pub mod tests {
                         &slice5,
                         &mut HashStats::default(),
                         end - start,
+                        None,
                     );
                     let vec = slice.to_vec();
                     let slice5 = convert_to_slice2(&vec);
@@ -1594,6 +1841,7 @@ pub mod tests {
                         &slice5,
                         &mut HashStats::default(),
                         end - start,
+                        None,
                     );
 
                     assert_eq!(hashes2, hashes3);
@@ -1703,7 +1951,7 @@ pub mod tests {
     fn test_de_dup_accounts_in_parallel<'a>(
         account_maps: &'a [SortedDataByPubkey<'a>],
     ) -> (Vec<&'a Hash>, u64, usize) {
-        AccountsHash::default().de_dup_accounts_in_parallel(account_maps, 0)
+        AccountsHash::default().de_dup_accounts_in_parallel(account_maps, 0, None)
     }
 
     #[test]
@@ -2097,7 +2345,7 @@ pub mod tests {
             ),
             CalculateHashIntermediate::new(Hash::new(&[2u8; 32]), offset + 1, Pubkey::new_unique()),
         ];
-        AccountsHash::default().de_dup_accounts_in_parallel(&[convert_to_slice(&[input])], 0);
+        AccountsHash::default().de_dup_accounts_in_parallel(&[convert_to_slice(&[input])], 0, None);
     }
 
     fn convert_to_slice(
@@ -2137,6 +2385,7 @@ pub mod tests {
             &[convert_to_slice(&input)],
             &mut HashStats::default(),
             2, // accounts above are in 2 groups
+            None,
         );
     }
 
@@ -204,6 +204,9 @@ pub struct AccountsIndexConfig {
 pub struct AccountSecondaryIndexes {
     pub keys: Option<AccountSecondaryIndexesIncludeExclude>,
     pub indexes: HashSet<AccountIndex>,
+    /// if set, do not build the indexes while loading and storing accounts, but only on the first
+    /// indexed scan, see `AccountsDb::index_scan_accounts()`
+    pub lazy: bool,
 }
 
 impl AccountSecondaryIndexes {
@@ -1472,6 +1475,27 @@ impl<T: IndexValue> AccountsIndex<T> {
         }
     }
 
+    /// Returns the size of each of the secondary indexes in `account_indexes`
+    pub(crate) fn secondary_index_stats(
+        &self,
+        account_indexes: &AccountSecondaryIndexes,
+    ) -> Vec<(AccountIndex, SecondaryIndexSizeStats)> {
+        [
+            (AccountIndex::ProgramId, self.program_id_index.size_stats()),
+            (
+                AccountIndex::SplTokenOwner,
+                self.spl_token_owner_index.size_stats(),
+            ),
+            (
+                AccountIndex::SplTokenMint,
+                self.spl_token_mint_index.size_stats(),
+            ),
+        ]
+        .into_iter()
+        .filter(|(account_index, _)| account_indexes.contains(account_index))
+        .collect()
+    }
+
     /// log any secondary index counts, if non-zero
     pub(crate) fn log_secondary_indexes(&self) {
         if !self.program_id_index.index.is_empty() {
@@ -2024,6 +2048,7 @@ pub mod tests {
         AccountSecondaryIndexes {
             indexes: account_indexes,
             keys: None,
+            lazy: false,
         }
     }
 
@@ -2033,6 +2058,7 @@ pub mod tests {
         AccountSecondaryIndexes {
             indexes: account_indexes,
             keys: None,
+            lazy: false,
         }
     }
 
@@ -3857,6 +3883,63 @@ pub mod tests {
         assert!(secondary_index.reverse_index.is_empty());
     }
 
+    #[test]
+    fn test_secondary_index_stats() {
+        let index = AccountsIndex::<bool>::default_for_tests();
+        let mut secondary_indexes = AccountSecondaryIndexes::default();
+        secondary_indexes.indexes.insert(AccountIndex::ProgramId);
+        let slot = 1;
+        let owner1 = Pubkey::new_unique();
+        let owner2 = Pubkey::new_unique();
+        let owner1_account_keys: Vec<_> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let owner2_account_key = Pubkey::new_unique();
+        for (account_key, owner) in owner1_account_keys
+            .iter()
+            .map(|account_key| (account_key, &owner1))
+            .chain(std::iter::once((&owner2_account_key, &owner2)))
+        {
+            index.upsert(
+                slot,
+                slot,
+                account_key,
+                &AccountSharedData::new(1, 0, owner),
+                &secondary_indexes,
+                true,
+                &mut vec![],
+                UPSERT_POPULATE_RECLAIMS,
+            );
+        }
+
+        // only the configured indexes are reported
+        assert_eq!(
+            index.secondary_index_stats(&secondary_indexes),
+            vec![(
+                AccountIndex::ProgramId,
+                SecondaryIndexSizeStats {
+                    num_keys: 2,
+                    num_inner_keys: 4,
+                    largest_entry_len: 3,
+                }
+            )],
+        );
+
+        // removing an account lowers the counts, but not the largest entry's high-water mark
+        let account_key = &owner1_account_keys[0];
+        index.purge_exact(account_key, &slot, &mut vec![]);
+        let _ = index.handle_dead_keys(&[account_key], &secondary_indexes);
+        assert_eq!(
+            index.secondary_index_stats(&secondary_indexes),
+            vec![(
+                AccountIndex::ProgramId,
+                SecondaryIndexSizeStats {
+                    num_keys: 2,
+                    num_inner_keys: 3,
+                    largest_entry_len: 3,
+                }
+            )],
+        );
+    }
+
     #[test]
     fn test_dashmap_secondary_index_same_slot_and_forks() {
         let (key_start, key_end, account_index) = create_dashmap_secondary_index_state();
@@ -48,18 +48,26 @@ use {
             IncludeSlotInHash, SnapshotStorages, ACCOUNTS_DB_CONFIG_FOR_BENCHMARKS,
             ACCOUNTS_DB_CONFIG_FOR_TESTING,
         },
-        accounts_index::{AccountSecondaryIndexes, IndexKey, ScanConfig, ScanResult, ZeroLamport},
+        accounts_hash::{CalcAccountsHashConfig, HashStats},
+        accounts_index::{
+            AccountIndex, AccountSecondaryIndexes, IndexKey, ScanConfig, ScanResult, ZeroLamport,
+        },
         accounts_update_notifier_interface::AccountsUpdateNotifier,
         ancestors::{Ancestors, AncestorsForSerialization},
         blockhash_queue::BlockhashQueue,
         builtins::{self, BuiltinAction, BuiltinFeatureTransition, Builtins},
         cost_tracker::CostTracker,
-        epoch_accounts_hash::{self, EpochAccountsHash},
+        epoch_accounts_hash::{
+            self, EpochAccountsHash, EpochAccountsHashError, EpochAccountsHashStatus,
+        },
         epoch_stakes::{EpochStakes, NodeVoteAccounts},
         inline_spl_associated_token_account, inline_spl_token,
         message_processor::MessageProcessor,
         rent_collector::{CollectedInfo, RentCollector},
         runtime_config::RuntimeConfig,
+        snapshot_config::{SnapshotConfig, SnapshotRequestKind},
+        snapshot_utils,
+        sorted_storages::SortedStorages,
         stake_account::{self, StakeAccount},
         stake_weighted_timestamp::{
             calculate_stake_weighted_timestamp, MaxAllowableDrift,
@@ -103,6 +111,7 @@ use {
             AccountSharedData, InheritableAccountFields, ReadableAccount, WritableAccount,
         },
         account_utils::StateMut,
+        bpf_loader, bpf_loader_deprecated,
         bpf_loader_upgradeable::{self, UpgradeableLoaderState},
         clock::{
             BankId, Epoch, Slot, SlotCount, SlotIndex, UnixTimestamp, DEFAULT_TICKS_PER_SECOND,
@@ -157,7 +166,7 @@ use {
     std::{
         borrow::Cow,
         cell::RefCell,
-        collections::{HashMap, HashSet},
+        collections::{BTreeMap, BTreeSet, HashMap, HashSet},
         convert::{TryFrom, TryInto},
         fmt, mem,
         ops::{Deref, RangeInclusive},
@@ -182,6 +191,9 @@ pub struct VerifyBankHash {
     pub require_rooted_bank: bool,
     pub run_in_background: bool,
     pub store_hash_raw_data_for_debug: bool,
+    /// if set, the accounts hash is calculated on this thread pool, instead of the background or
+    /// foreground one, to cap the threads it uses
+    pub thread_pool: Option<Arc<ThreadPool>>,
 }
 
 #[derive(Debug, Default)]
@@ -257,6 +269,17 @@ pub struct BankIncrementalSnapshotPersistence {
     pub incremental_capitalization: u64,
 }
 
+/// A serializable record of a bank's feature set, from `Bank::feature_set_snapshot()`
+///
+/// The features are sorted by id, so records from different banks or nodes can be diffed offline.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FeatureSetSnapshot {
+    /// the active features, with the slots they were activated in
+    pub active: BTreeMap<Pubkey, Slot>,
+    /// the inactive features
+    pub inactive: BTreeSet<Pubkey>,
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct RentDebits(HashMap<Pubkey, RentDebit>);
 impl RentDebits {
@@ -856,10 +879,12 @@ impl PartialEq for Bank {
             feature_set: _,
             drop_callback: _,
             freeze_started: _,
+            frozen_at: _,
             vote_only_bank: _,
             cost_tracker: _,
             rewrites_skipped_this_slot: _,
             sysvar_cache: _,
+            rent_collected_prior_this_epoch: _,
             accounts_data_size_initial: _,
             accounts_data_size_delta_on_chain: _,
             accounts_data_size_delta_off_chain: _,
@@ -1041,6 +1066,9 @@ pub struct Bank {
     /// Rent that has been collected
     collected_rent: AtomicU64,
 
+    /// Rent collected by this bank's ancestors in the same epoch, see `rent_collected_this_epoch()`
+    rent_collected_prior_this_epoch: u64,
+
     /// latest rent collector, knows the epoch
     rent_collector: RentCollector,
 
@@ -1100,6 +1128,9 @@ pub struct Bank {
 
     pub freeze_started: AtomicBool,
 
+    /// When `freeze()` froze this bank, see `frozen_at()`
+    frozen_at: RwLock<Option<Instant>>,
+
     vote_only_bank: bool,
 
     cost_tracker: RwLock<CostTracker>,
@@ -1285,6 +1316,7 @@ impl Bank {
             fee_calculator: FeeCalculator::default(),
             fee_rate_governor: FeeRateGovernor::default(),
             collected_rent: AtomicU64::default(),
+            rent_collected_prior_this_epoch: 0,
             rent_collector: RentCollector::default(),
             epoch_schedule: EpochSchedule::default(),
             inflation: Arc::<RwLock<Inflation>>::default(),
@@ -1306,6 +1338,7 @@ impl Bank {
             feature_set: Arc::<FeatureSet>::default(),
             drop_callback: RwLock::new(OptionalDropCallback(None)),
             freeze_started: AtomicBool::default(),
+            frozen_at: RwLock::default(),
             vote_only_bank: false,
             cost_tracker: RwLock::<CostTracker>::default(),
             sysvar_cache: RwLock::<SysvarCache>::default(),
@@ -1574,6 +1607,11 @@ impl Bank {
             slots_per_year: parent.slots_per_year,
             epoch_schedule,
             collected_rent: AtomicU64::new(0),
+            rent_collected_prior_this_epoch: if epoch == parent.epoch() {
+                parent.rent_collected_this_epoch()
+            } else {
+                0
+            },
             rent_collector: Self::get_rent_collector_from(&parent.rent_collector, epoch),
             max_tick_height: (slot + 1) * parent.ticks_per_slot,
             block_height: parent.block_height + 1,
@@ -1621,6 +1659,7 @@ impl Bank {
                     .map(|drop_callback| drop_callback.clone_box()),
             )),
             freeze_started: AtomicBool::new(false),
+            frozen_at: RwLock::default(),
             cost_tracker: RwLock::new(CostTracker::new_with_account_data_size_limit(
                 feature_set
                     .is_active(&feature_set::cap_accounts_data_len::id())
@@ -1951,6 +1990,7 @@ impl Bank {
             fee_calculator: fields.fee_calculator,
             fee_rate_governor: fields.fee_rate_governor,
             collected_rent: AtomicU64::new(fields.collected_rent),
+            rent_collected_prior_this_epoch: 0,
             // clone()-ing is needed to consider a gated behavior in rent_collector
             rent_collector: Self::get_rent_collector_from(&fields.rent_collector, fields.epoch),
             epoch_schedule: fields.epoch_schedule,
@@ -1972,6 +2012,7 @@ impl Bank {
             feature_set: Arc::clone(&feature_set),
             drop_callback: RwLock::new(OptionalDropCallback(None)),
             freeze_started: AtomicBool::new(fields.hash != Hash::default()),
+            frozen_at: RwLock::default(),
             vote_only_bank: false,
             cost_tracker: RwLock::new(CostTracker::default()),
             sysvar_cache: RwLock::new(SysvarCache::default()),
@@ -2122,6 +2163,14 @@ impl Bank {
         *self.hash.read().unwrap() != Hash::default()
     }
 
+    /// Returns when `freeze()` froze this bank, or None if it has not been frozen yet
+    ///
+    /// This is for measuring latencies from freezing a bank, e.g. until its accounts hash or
+    /// snapshot is done.  Banks rebuilt from a snapshot are already frozen, and return None.
+    pub fn frozen_at(&self) -> Option<Instant> {
+        *self.frozen_at.read().unwrap()
+    }
+
     pub fn freeze_started(&self) -> bool {
         self.freeze_started.load(Relaxed)
     }
@@ -3188,10 +3237,37 @@ impl Bank {
             // freeze is a one-way trip, idempotent
             self.freeze_started.store(true, Relaxed);
             *hash = self.hash_internal_state();
+            *self.frozen_at.write().unwrap() = Some(Instant::now());
             self.rc.accounts.accounts_db.mark_slot_frozen(self.slot());
+
+            if self.runtime_config.verify_hash_on_freeze {
+                // verifying reads the hash, so release the lock first
+                drop(hash);
+                self.verify_hash_on_freeze();
+            }
         }
     }
 
+    /// Calculate the accounts hash and verify the bank hash, right after freezing
+    ///
+    /// See `RuntimeConfig::verify_hash_on_freeze`.  The accounts hash is calculated from the
+    /// index, so it is available from `get_accounts_hash()` as soon as `freeze()` returns.
+    fn verify_hash_on_freeze(&self) {
+        let mut measure = Measure::start("verify_hash_on_freeze");
+        self.update_accounts_hash();
+        assert!(
+            self.verify_hash(),
+            "bank hash verification failed on freeze, slot: {}",
+            self.slot()
+        );
+        measure.stop();
+        debug!(
+            "verified hash on freeze, slot: {}, took {}us",
+            self.slot(),
+            measure.as_us()
+        );
+    }
+
     // dangerous; don't use this; this is only needed for ledger-tool's special command
     pub fn unfreeze_for_ledger_tool(&self) {
         self.freeze_started.store(false, Relaxed);
@@ -3450,6 +3526,10 @@ impl Bank {
         blockhash_queue.is_hash_valid(hash)
     }
 
+    /// The minimum balance for an account with `data_len` bytes of data to be rent exempt, using
+    /// this bank's rent settings
+    ///
+    /// The result is at least one lamport, so the account is not treated as a zero-lamport account.
     pub fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> u64 {
         self.rent_collector.rent.minimum_balance(data_len).max(1)
     }
@@ -3518,6 +3598,26 @@ impl Bank {
             .verification_complete()
     }
 
+    /// Flag that startup verification failed, but was not fatal (e.g. for forensic tooling).
+    /// Startup verification will then never be complete, so neither this bank nor its
+    /// descendants can be used for consensus (i.e. voting or producing blocks).
+    pub fn set_startup_verification_failed(&self) {
+        self.rc
+            .accounts
+            .accounts_db
+            .verify_accounts_hash_in_bg
+            .verification_failed()
+    }
+
+    /// Did startup verification fail?  See `set_startup_verification_failed()`.
+    pub fn is_startup_verification_failed(&self) -> bool {
+        self.rc
+            .accounts
+            .accounts_db
+            .verify_accounts_hash_in_bg
+            .is_failed()
+    }
+
     pub fn get_fee_for_message_with_lamports_per_signature(
         &self,
         message: &SanitizedMessage,
@@ -6474,6 +6574,23 @@ impl Bank {
         self.load_slow(&self.ancestors, pubkey)
     }
 
+    /// Returns the hash of `pubkey`'s account, as it contributes to the accounts hash
+    ///
+    /// The account is hashed the same way it was when stored: at the slot it was last modified,
+    /// and including the slot only if `account_hash_ignore_slot` is not active.  Returns None if
+    /// the account does not exist.
+    pub fn account_hash(&self, pubkey: &Pubkey) -> Option<Hash> {
+        self.get_account_modified_slot(pubkey)
+            .map(|(account, slot)| {
+                crate::accounts_db::AccountsDb::hash_account(
+                    slot,
+                    &account,
+                    pubkey,
+                    self.include_slot_in_hash(),
+                )
+            })
+    }
+
     fn load_slow(
         &self,
         ancestors: &Ancestors,
@@ -6539,10 +6656,145 @@ impl Bank {
         self.rc.accounts.account_indexes_include_key(key)
     }
 
+    /// Returns the executable program accounts in this bank, sorted by pubkey
+    ///
+    /// Programs are the executable accounts owned by the native loader (i.e. builtins) or by one
+    /// of the BPF loaders.  The program id secondary index is used for the loaders it covers; the
+    /// accounts of any other loaders are found with a single scan of all accounts.  If this bank is
+    /// removed while scanning, the accounts that could not be loaded are left out.
+    pub fn program_accounts(&self) -> Vec<TransactionAccount> {
+        let loaders = [
+            native_loader::id(),
+            bpf_loader_deprecated::id(),
+            bpf_loader::id(),
+            bpf_loader_upgradeable::id(),
+        ];
+        let account_indexes = &self.rc.accounts.accounts_db.account_indexes;
+        let (indexed_loaders, scanned_loaders): (Vec<_>, Vec<_>) =
+            loaders.into_iter().partition(|loader| {
+                account_indexes.contains(&AccountIndex::ProgramId)
+                    && account_indexes.include_key(loader)
+            });
+
+        let mut program_accounts = Vec::new();
+        for loader in indexed_loaders {
+            // Like the RPC's `getProgramAccounts`, check the owner again, since a wiped and
+            // reinitialized account may remain in the index of its previous owner
+            let result = self.get_filtered_indexed_accounts(
+                &IndexKey::ProgramId(loader),
+                |account| account.owner() == &loader && account.executable(),
+                &ScanConfig::default(),
+                None,
+            );
+            match result {
+                Ok(accounts) => program_accounts.extend(accounts),
+                Err(err) => warn!("Failed to load the programs owned by {}: {}", loader, err),
+            }
+        }
+        if !scanned_loaders.is_empty() {
+            let result = self.rc.accounts.load_by_program_ids_with_filter(
+                &self.ancestors,
+                self.bank_id,
+                &scanned_loaders,
+                |account| account.executable(),
+                &ScanConfig::default(),
+            );
+            match result {
+                Ok(accounts) => program_accounts.extend(accounts),
+                Err(err) => warn!("Failed to scan for programs: {}", err),
+            }
+        }
+        program_accounts.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        program_accounts
+    }
+
     pub fn get_all_accounts_with_modified_slots(&self) -> ScanResult<Vec<PubkeyAccountSlot>> {
         self.rc.accounts.load_all(&self.ancestors, self.bank_id)
     }
 
+    /// Returns the accounts whose stored rent epoch is below `epoch`, sorted by pubkey
+    ///
+    /// These are the accounts that rent collection has not touched recently, which is useful when
+    /// auditing rent collection coverage.  This scans all accounts, so do not use it on hot paths.
+    pub fn accounts_with_rent_epoch_below(&self, epoch: Epoch) -> ScanResult<Vec<Pubkey>> {
+        let mut pubkeys: Vec<_> = self
+            .get_all_accounts_with_modified_slots()?
+            .into_iter()
+            .filter(|(_pubkey, account, _slot)| account.rent_epoch() < epoch)
+            .map(|(pubkey, _account, _slot)| pubkey)
+            .collect();
+        pubkeys.sort_unstable();
+        Ok(pubkeys)
+    }
+
+    /// Returns the accounts created in this bank's epoch, sorted by pubkey
+    ///
+    /// Only the slots of this epoch on this bank's fork are scanned, so the cost is bounded by the
+    /// epoch length rather than all of history.  An account counts as created if it was stored in
+    /// one of those slots, and the accounts index has no older version of it with lamports.  Clean
+    /// purges older versions once they are superseded by a newer root, so after clean an updated
+    /// account may be reported too.  This is meant for auditing state growth, not for consensus.
+    pub fn accounts_created_this_epoch(&self) -> Vec<Pubkey> {
+        let first_slot_in_epoch = self.epoch_schedule().get_first_slot_in_epoch(self.epoch());
+        let accounts_index = &self.rc.accounts.accounts_db.accounts_index;
+        let stored_this_epoch: HashSet<_> = (first_slot_in_epoch..=self.slot())
+            .filter(|slot| self.ancestors.contains_key(slot) || accounts_index.is_alive_root(*slot))
+            .flat_map(|slot| {
+                self.rc
+                    .accounts
+                    .scan_slot(slot, |loaded_account| Some(*loaded_account.pubkey()))
+            })
+            .collect();
+        let mut pubkeys: Vec<_> = stored_this_epoch
+            .into_iter()
+            .filter(|pubkey| {
+                accounts_index
+                    .get_account_read_entry(pubkey)
+                    .map(|entry| {
+                        !entry.slot_list().iter().any(|(slot, account_info)| {
+                            *slot < first_slot_in_epoch && !account_info.is_zero_lamport()
+                        })
+                    })
+                    .unwrap_or(true)
+            })
+            .collect();
+        pubkeys.sort_unstable();
+        pubkeys
+    }
+
+    /// Returns all live accounts sorted by pubkey, so tests can compute golden values that do not
+    /// depend on the order the accounts happen to be stored in.
+    pub fn accounts_sorted_for_tests(&self) -> Vec<(Pubkey, AccountSharedData)> {
+        let mut accounts: Vec<_> = self
+            .get_all_accounts_with_modified_slots()
+            .unwrap()
+            .into_iter()
+            .map(|(pubkey, account, _slot)| (pubkey, account))
+            .collect();
+        accounts.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        accounts
+    }
+
+    /// Returns true if both banks have the same live pubkeys, and `cmp` holds for each pair of
+    /// accounts with the same pubkey
+    ///
+    /// Tests use this to compare a bank against its deserialized copy while ignoring account fields
+    /// that may legitimately differ between the two, such as the rent epoch.
+    pub fn accounts_equal_with(
+        &self,
+        other: &Bank,
+        cmp: impl Fn(&AccountSharedData, &AccountSharedData) -> bool,
+    ) -> bool {
+        let accounts = self.accounts_sorted_for_tests();
+        let other_accounts = other.accounts_sorted_for_tests();
+        accounts.len() == other_accounts.len()
+            && accounts.iter().zip(other_accounts.iter()).all(
+                |((pubkey, account), (other_pubkey, other_account))| {
+                    pubkey == other_pubkey && cmp(account, other_account)
+                },
+            )
+    }
+
     pub fn get_program_accounts_modified_since_parent(
         &self,
         program_id: &Pubkey,
@@ -6595,6 +6847,29 @@ impl Bank {
         )
     }
 
+    /// Returns the accounts whose data is at least `min_data_len` bytes, with their data lengths,
+    /// sorted by data length from largest to smallest
+    ///
+    /// This is for finding the outliers that dominate snapshot size and accounts hash time.  It
+    /// scans all accounts, so do not use it on hot paths.
+    pub fn large_accounts(&self, min_data_len: usize) -> ScanResult<Vec<(Pubkey, usize)>> {
+        let mut large_accounts = Vec::new();
+        self.rc.accounts.accounts_db.scan_accounts(
+            &self.ancestors,
+            self.bank_id,
+            |option| {
+                if let Some((pubkey, account, _slot)) = option {
+                    if account.lamports() != 0 && account.data().len() >= min_data_len {
+                        large_accounts.push((*pubkey, account.data().len()));
+                    }
+                }
+            },
+            &ScanConfig::default(),
+        )?;
+        large_accounts.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        Ok(large_accounts)
+    }
+
     pub fn transaction_count(&self) -> u64 {
         self.transaction_count.load(Relaxed)
     }
@@ -6818,6 +7093,7 @@ impl Bank {
                             config.store_hash_raw_data_for_debug,
                             // true to run using bg thread pool
                             true,
+                            config.thread_pool.as_deref(),
                         );
                         accounts_
                             .accounts_db
@@ -6840,6 +7116,7 @@ impl Bank {
                 config.store_hash_raw_data_for_debug,
                 // fg is waiting for this to run, so we can use the fg thread pool
                 false,
+                config.thread_pool.as_deref(),
             );
             self.set_initial_accounts_hash_verification_completed();
             result
@@ -6877,7 +7154,7 @@ impl Bank {
     }
 
     #[must_use]
-    fn verify_hash(&self) -> bool {
+    pub(crate) fn verify_hash(&self) -> bool {
         assert!(self.is_frozen());
         let calculated_hash = self.hash_internal_state();
         let expected_hash = self.hash();
@@ -6895,6 +7172,101 @@ impl Bank {
         }
     }
 
+    /// Recalculate the epoch accounts hash from the state at this epoch's calculation start slot,
+    /// and compare it against the stored EAH, for self-auditing
+    ///
+    /// The EAH is only hashed into banks from this epoch's stop slot onward, so earlier banks
+    /// return `NotIncluded`.  The state is read from the storages retained for slots at or below
+    /// the start slot, not from the accounts index, since clean drops index entries for versions
+    /// that have been superseded since.  Shrinking those slots after the start slot still removes
+    /// the superseded versions from the storages themselves, so a node that has shrunk them will
+    /// report a mismatch.
+    pub fn reverify_epoch_accounts_hash(&self) -> Result<(), EpochAccountsHashError> {
+        let stop_slot = epoch_accounts_hash::calculation_stop(self);
+        if self.slot() < stop_slot {
+            return Err(EpochAccountsHashError::NotIncluded(self.slot(), stop_slot));
+        }
+        let expected = *self
+            .epoch_accounts_hash()
+            .ok_or(EpochAccountsHashError::Missing)?
+            .as_ref();
+
+        let start_slot = epoch_accounts_hash::calculation_start(self);
+        let storages: SnapshotStorages = self
+            .get_snapshot_storages(None)
+            .into_iter()
+            .filter(|storage| {
+                storage
+                    .first()
+                    .map(|entry| entry.slot() <= start_slot)
+                    .unwrap_or_default()
+            })
+            .collect();
+        let (calculated, _capitalization) = self
+            .rc
+            .accounts
+            .accounts_db
+            .calculate_accounts_hash_from_storages(
+                &CalcAccountsHashConfig {
+                    use_bg_thread_pool: false,
+                    check_hash: false,
+                    ancestors: None,
+                    epoch_schedule: &self.epoch_schedule,
+                    rent_collector: &self.rent_collector,
+                    store_detailed_debug_info_on_failure: false,
+                    full_snapshot: None,
+                    ..CalcAccountsHashConfig::default()
+                },
+                &SortedStorages::new(&storages),
+                HashStats::default(),
+            )
+            .map_err(|err| EpochAccountsHashError::Calculation(start_slot, err))?;
+
+        if calculated != expected {
+            return Err(EpochAccountsHashError::Mismatch {
+                calculated,
+                expected,
+            });
+        }
+        Ok(())
+    }
+
+    /// **NOT CONSENSUS**: Calculate this bank's hash as if `feature_set` were active
+    ///
+    /// This is for investigating how feature activations affect hashing.  The bank's own feature
+    /// set and hash are left untouched.  Returns `None` if the hash would include the epoch
+    /// accounts hash, but it is not available.
+    pub fn calculate_hash_with_feature_set(
+        &mut self,
+        feature_set: Arc<FeatureSet>,
+    ) -> Option<Hash> {
+        let original_feature_set = std::mem::replace(&mut self.feature_set, feature_set);
+        let hash = (!self.should_include_epoch_accounts_hash()
+            || self.epoch_accounts_hash().is_some())
+        .then(|| self.hash_internal_state());
+        self.feature_set = original_feature_set;
+        hash
+    }
+
+    /// Verify the epoch accounts hash, if it is hashed into this bank's hash
+    ///
+    /// The EAH is only included in one bank per epoch (see `should_include_epoch_accounts_hash()`),
+    /// so there is nothing to verify for all other banks.  Unlike `hash_internal_state()`, this
+    /// does not block waiting for the EAH.
+    pub fn verify_epoch_accounts_hash(&self) -> bool {
+        if !self.should_include_epoch_accounts_hash() {
+            return true;
+        }
+        if self.epoch_accounts_hash().is_none() {
+            warn!(
+                "verify epoch accounts hash failed: slot: {}, epoch accounts hash is missing",
+                self.slot(),
+            );
+            return false;
+        }
+        self.verify_hash()
+    }
+
     pub fn verify_transaction(
         &self,
         tx: VersionedTransaction,
@@ -6972,6 +7344,19 @@ impl Bank {
         self.rc.accounts.accounts_db.get_accounts_hash(self.slot)
     }
 
+    /// Calculate a hash over only the accounts modified after `slot` in this bank's ancestry
+    ///
+    /// This is the incremental accounts hash from `slot` to this bank, see
+    /// `AccountsDb::calculate_incremental_accounts_hash()`, so accounts removed since `slot` are
+    /// included with the default hash.  It commits to the changes between two points of a fork,
+    /// and is not a bank hash.
+    pub fn incremental_hash_since(&self, slot: Slot) -> Hash {
+        self.rc
+            .accounts
+            .accounts_db
+            .calculate_incremental_accounts_hash(slot, self.slot(), &self.ancestors)
+    }
+
     pub fn get_thread_pool(&self) -> &ThreadPool {
         &self.rc.accounts.accounts_db.thread_pool_clean
     }
@@ -7039,11 +7424,14 @@ impl Bank {
 
     /// A snapshot bank should be purged of 0 lamport accounts which are not part of the hash
     /// calculation and could shield other real accounts.
+    ///
+    /// If `thread_pool` is set, the accounts hash is verified on it, see `VerifyBankHash`.
     pub fn verify_snapshot_bank(
         &self,
         test_hash_calculation: bool,
         accounts_db_skip_shrink: bool,
         last_full_snapshot_slot: Slot,
+        thread_pool: Option<Arc<ThreadPool>>,
     ) -> bool {
         let mut clean_time = Measure::start("clean");
         if !accounts_db_skip_shrink && self.slot() > 0 {
@@ -7074,6 +7462,7 @@ impl Bank {
                 require_rooted_bank: false,
                 run_in_background: true,
                 store_hash_raw_data_for_debug: false,
+                thread_pool,
             });
             verify_time.stop();
             (verify, verify_time.as_us())
@@ -7137,6 +7526,15 @@ impl Bank {
         self.capitalization.load(Relaxed)
     }
 
+    /// Return the rent collected so far in this bank's epoch, by this bank and its ancestors
+    ///
+    /// Rent collected before this bank's fork was loaded from a snapshot is not included; only
+    /// the snapshot bank's own collected rent is known.
+    pub fn rent_collected_this_epoch(&self) -> u64 {
+        self.rent_collected_prior_this_epoch
+            .saturating_add(self.collected_rent.load(Relaxed))
+    }
+
     /// Return this bank's max_tick_height
     pub fn max_tick_height(&self) -> u64 {
         self.max_tick_height
@@ -7154,6 +7552,10 @@ impl Bank {
 
     /// returns the epoch for which this bank's leader_schedule_slot_offset and slot would
     ///  need to cache leader_schedule
+    ///
+    /// This is the same mapping the runtime uses when caching the epoch stakes that leader
+    /// schedules are generated from, including the warmup epochs (where the offset behaves as if
+    /// it were one full epoch).
     pub fn get_leader_schedule_epoch(&self, slot: Slot) -> Epoch {
         self.epoch_schedule().get_leader_schedule_epoch(slot)
     }
@@ -7240,6 +7642,20 @@ impl Bank {
             .get(node_id)
     }
 
+    /// Get the identity pubkeys of the validators with stake in the current epoch, sorted
+    pub fn current_epoch_validators(&self) -> Vec<Pubkey> {
+        let mut validators: Vec<_> = self
+            .epoch_stakes
+            .get(&self.epoch)
+            .expect("Epoch stakes for bank's own epoch must exist")
+            .node_id_to_vote_accounts()
+            .keys()
+            .copied()
+            .collect();
+        validators.sort_unstable();
+        validators
+    }
+
     /// Get the fixed total stake of all vote accounts for current epoch
     pub fn total_epoch_stake(&self) -> u64 {
         self.epoch_stakes
@@ -7442,6 +7858,20 @@ impl Bank {
         self.feature_set = Arc::new(feature_set);
     }
 
+    /// Returns a serializable record of the state of every feature, and the activation slots of
+    /// the active ones
+    pub fn feature_set_snapshot(&self) -> FeatureSetSnapshot {
+        FeatureSetSnapshot {
+            active: self
+                .feature_set
+                .active
+                .iter()
+                .map(|(id, slot)| (*id, *slot))
+                .collect(),
+            inactive: self.feature_set.inactive.iter().copied().collect(),
+        }
+    }
+
     pub fn fill_bank_with_ticks_for_tests(&self) {
         if self.tick_height.load(Relaxed) < self.max_tick_height {
             let last_blockhash = self.last_blockhash();
@@ -7720,6 +8150,60 @@ impl Bank {
             .try_get_epoch_accounts_hash()
     }
 
+    /// Convenience fn to get the status of the Epoch Accounts Hash
+    ///
+    /// Unlike `epoch_accounts_hash()`, this distinguishes a calculation that has not been
+    /// requested from one that is still in-flight.
+    pub fn epoch_accounts_hash_status(&self) -> EpochAccountsHashStatus {
+        self.rc
+            .accounts
+            .accounts_db
+            .epoch_accounts_hash_manager
+            .status()
+    }
+
+    /// Returns the kind of snapshot this bank's slot warrants under `snapshot_config`, if any
+    ///
+    /// Snapshots are taken based on block height, so skipped slots do not shift the intervals.  A
+    /// full snapshot takes precedence over an incremental snapshot at the same block height.
+    /// Whether an incremental snapshot can actually be taken also depends on there being a full
+    /// snapshot to base it on, which the caller must check.
+    #[must_use]
+    pub fn is_snapshot_boundary(
+        &self,
+        snapshot_config: &SnapshotConfig,
+    ) -> Option<SnapshotRequestKind> {
+        let block_height = self.block_height();
+        if snapshot_utils::should_take_full_snapshot(
+            block_height,
+            snapshot_config.full_snapshot_archive_interval_slots,
+        ) {
+            Some(SnapshotRequestKind::Full)
+        } else if block_height % snapshot_config.incremental_snapshot_archive_interval_slots == 0 {
+            Some(SnapshotRequestKind::Incremental)
+        } else {
+            None
+        }
+    }
+
+    /// A single commitment to this bank's state, to compare banks across nodes with one value
+    ///
+    /// The state root is `hashv(&[bank hash, accounts hash, epoch accounts hash])`, in that order.
+    /// If there is no epoch accounts hash, `Hash::default()` is used in its place.  The accounts
+    /// hash is the one last calculated for this bank's slot (see `update_accounts_hash()`), so the
+    /// banks being compared must both have calculated it.
+    pub fn state_root(&self) -> Hash {
+        let epoch_accounts_hash = self
+            .epoch_accounts_hash()
+            .map(|epoch_accounts_hash| *epoch_accounts_hash.as_ref())
+            .unwrap_or_default();
+        hashv(&[
+            self.hash().as_ref(),
+            self.get_accounts_hash().as_ref(),
+            epoch_accounts_hash.as_ref(),
+        ])
+    }
+
     /// Checks a batch of sanitized transactions again bank for age and status
     pub fn check_transactions_with_forwarding_delay(
         &self,
@@ -7941,7 +8425,8 @@ pub(crate) mod tests {
         super::*,
         crate::{
             accounts_background_service::{PrunedBanksRequestHandler, SendDroppedBankCallback},
-            accounts_db::DEFAULT_ACCOUNTS_SHRINK_RATIO,
+            accounts_db::{LoadHint, DEFAULT_ACCOUNTS_SHRINK_RATIO},
+            accounts_hash::AccountsHash,
             accounts_index::{AccountIndex, AccountSecondaryIndexes, ScanError, ITER_BATCH_SIZE},
             ancestors::Ancestors,
             bank_client::BankClient,
@@ -9847,6 +10332,55 @@ pub(crate) mod tests {
         }
     }
 
+    #[test]
+    fn test_rent_collected_this_epoch() {
+        solana_logger::setup();
+
+        let (mut genesis_config, _mint_keypair) = create_genesis_config(1_000_000);
+        activate_all_features(&mut genesis_config);
+        let bank0 = Arc::new(Bank::new_for_tests(&genesis_config));
+        let little_lamports = 1234;
+        let store_rent_due_account = |bank: &Bank| {
+            bank.store_account(
+                &solana_sdk::pubkey::new_rand(),
+                &AccountSharedData::new(little_lamports, 0, &Pubkey::default()),
+            );
+        };
+
+        // the first slot of the next epoch
+        store_rent_due_account(&bank0);
+        let slot = MINIMUM_SLOTS_PER_EPOCH;
+        let bank1 = Arc::new(Bank::new_from_parent(&bank0, &Pubkey::default(), slot));
+        assert_eq!(bank1.epoch(), bank0.epoch() + 1);
+        assert_eq!(bank1.rent_collected_this_epoch(), 0);
+        bank1.collect_rent_in_partition((0, 0, 1), false, &RentMetrics::default()); // all range
+        let rent_collected_bank1 = bank1.rent_collected_this_epoch();
+        assert!(rent_collected_bank1 > 0);
+        assert_eq!(rent_collected_bank1, bank1.collected_rent.load(Relaxed));
+
+        // a child in the same epoch includes the parent's collected rent
+        let bank2 = Arc::new(Bank::new_from_parent(&bank1, &Pubkey::default(), slot + 1));
+        assert_eq!(bank2.epoch(), bank1.epoch());
+        let rent_collected_bank1 = bank1.rent_collected_this_epoch(); // bank1 is frozen now
+        assert_eq!(bank2.rent_collected_this_epoch(), rent_collected_bank1);
+        store_rent_due_account(&bank2);
+        bank2.collect_rent_in_partition((0, 0, 1), false, &RentMetrics::default()); // all range
+        let rent_collected_bank2 = bank2.collected_rent.load(Relaxed);
+        assert!(rent_collected_bank2 > 0);
+        assert_eq!(
+            bank2.rent_collected_this_epoch(),
+            rent_collected_bank1 + rent_collected_bank2,
+        );
+
+        // a new epoch starts over
+        let next_epoch_slot = bank2
+            .epoch_schedule()
+            .get_first_slot_in_epoch(bank2.epoch() + 1);
+        let bank3 = Bank::new_from_parent(&bank2, &Pubkey::default(), next_epoch_slot);
+        assert_eq!(bank3.epoch(), bank2.epoch() + 1);
+        assert_eq!(bank3.rent_collected_this_epoch(), 0);
+    }
+
     #[test]
     fn test_rent_eager_collect_rent_in_partition() {
         solana_logger::setup();
@@ -10324,6 +10858,7 @@ pub(crate) mod tests {
                 require_rooted_bank: false,
                 run_in_background: false,
                 store_hash_raw_data_for_debug: false,
+                thread_pool: None,
             }
         }
     }
@@ -11554,6 +12089,78 @@ pub(crate) mod tests {
         assert!(bank3.verify_bank_hash(VerifyBankHash::default_for_test()));
     }
 
+    #[test]
+    fn test_frozen_at() {
+        let bank = create_simple_test_bank(2_000);
+        assert_eq!(bank.frozen_at(), None);
+
+        let before_freeze = Instant::now();
+        bank.freeze();
+        let frozen_at = bank.frozen_at().unwrap();
+        assert!(frozen_at >= before_freeze);
+
+        // freezing again is a no-op
+        bank.freeze();
+        assert_eq!(bank.frozen_at(), Some(frozen_at));
+    }
+
+    #[test]
+    fn test_verify_hash_on_freeze() {
+        let (genesis_config, _mint_keypair) = create_genesis_config(sol_to_lamports(1.));
+        let bank = Bank::new_for_tests(&genesis_config);
+        let verifying_bank = Bank::new_with_runtime_config_for_tests(
+            &genesis_config,
+            Arc::new(RuntimeConfig {
+                verify_hash_on_freeze: true,
+                ..RuntimeConfig::default()
+            }),
+        );
+        for bank in [&bank, &verifying_bank] {
+            let account = AccountSharedData::new(1_000, 0, &Pubkey::default());
+            bank.store_account(&Pubkey::new_from_array([1; 32]), &account);
+        }
+        bank.freeze();
+        verifying_bank.freeze();
+
+        // the accounts hash is available right away, and hashing synchronously changes nothing
+        assert_ne!(verifying_bank.get_accounts_hash(), Hash::default());
+        assert_eq!(verifying_bank.hash(), bank.hash());
+        assert_eq!(
+            verifying_bank.get_accounts_hash(),
+            bank.update_accounts_hash()
+        );
+
+        // the option is inherited by child banks
+        let child = Bank::new_from_parent(&Arc::new(verifying_bank), &Pubkey::default(), 1);
+        child.freeze();
+        assert_ne!(child.get_accounts_hash(), Hash::default());
+    }
+
+    #[test]
+    fn test_compute_budget_override_bank_hash() {
+        let (genesis_config, mint_keypair) = create_genesis_config(sol_to_lamports(1.));
+        let bank = Bank::new_for_tests(&genesis_config);
+        let overridden_bank = Bank::new_with_runtime_config_for_tests(
+            &genesis_config,
+            Arc::new(RuntimeConfig {
+                compute_budget: Some(ComputeBudget::new(
+                    compute_budget::MAX_COMPUTE_UNIT_LIMIT as u64,
+                )),
+                ..RuntimeConfig::default()
+            }),
+        );
+
+        // the override itself is not hashed, so the bank hash is the same as long as the
+        // transactions have the same outcome under the override
+        let recipient = Pubkey::new_unique();
+        for bank in [&bank, &overridden_bank] {
+            bank.transfer(1_000, &mint_keypair, &recipient).unwrap();
+            bank.freeze();
+        }
+        assert_eq!(overridden_bank.get_balance(&recipient), 1_000);
+        assert_eq!(overridden_bank.hash(), bank.hash());
+    }
+
     #[test]
     #[should_panic(expected = "assertion failed: self.is_frozen()")]
     fn test_verify_hash_unfrozen() {
@@ -11575,11 +12182,11 @@ pub(crate) mod tests {
         .unwrap();
         bank.freeze();
         bank.update_accounts_hash();
-        assert!(bank.verify_snapshot_bank(true, false, bank.slot()));
+        assert!(bank.verify_snapshot_bank(true, false, bank.slot(), None));
 
         // tamper the bank after freeze!
         bank.increment_signature_count(1);
-        assert!(!bank.verify_snapshot_bank(true, false, bank.slot()));
+        assert!(!bank.verify_snapshot_bank(true, false, bank.slot(), None));
     }
 
     // Test that two bank forks with the same accounts should not hash to the same value.
@@ -11985,6 +12592,58 @@ pub(crate) mod tests {
         );
     }
 
+    #[test]
+    fn test_current_epoch_validators() {
+        let leader_pubkey = solana_sdk::pubkey::new_rand();
+        let genesis_config = create_genesis_config_with_leader(5, &leader_pubkey, 3).genesis_config;
+        let bank0 = Arc::new(Bank::new_for_tests(&genesis_config));
+        assert_eq!(bank0.current_epoch_validators(), vec![leader_pubkey]);
+
+        let bank1 =
+            Bank::new_from_parent(&bank0, &Pubkey::default(), bank0.get_slots_in_epoch(0) + 1);
+        assert_eq!(bank1.epoch(), 1);
+        assert_eq!(bank1.current_epoch_validators(), vec![leader_pubkey]);
+    }
+
+    #[test]
+    fn test_incremental_hash_since() {
+        let (genesis_config, _mint_keypair) = create_genesis_config(sol_to_lamports(1.));
+        let bank0 = Arc::new(Bank::new_for_tests(&genesis_config));
+        let bank1 = Arc::new(Bank::new_from_parent(&bank0, &Pubkey::default(), 1));
+        let pubkey1 = Pubkey::new_unique();
+        bank1.store_account(&pubkey1, &AccountSharedData::new(1, 0, &Pubkey::default()));
+        let bank2 = Bank::new_from_parent(&bank1, &Pubkey::default(), 2);
+        let pubkey2 = Pubkey::new_unique();
+        bank2.store_account(&pubkey2, &AccountSharedData::new(2, 0, &Pubkey::default()));
+
+        // only the accounts stored in bank2 are after bank1
+        let mut modified = bank2.get_all_accounts_modified_since_parent();
+        modified.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        assert!(modified.iter().any(|(pubkey, _)| pubkey == &pubkey2));
+        assert!(!modified.iter().any(|(pubkey, _)| pubkey == &pubkey1));
+        let hashes = modified
+            .iter()
+            .map(|(pubkey, _)| {
+                bank2
+                    .rc
+                    .accounts
+                    .accounts_db
+                    .load_account_hash(&bank2.ancestors, pubkey, None, LoadHint::Unspecified)
+                    .unwrap()
+            })
+            .collect();
+        let (expected, _hash_total) = AccountsHash::calculate_hash(vec![hashes]);
+        assert_eq!(bank2.incremental_hash_since(bank1.slot()), expected);
+
+        // going back further also includes the changes in bank1
+        assert_ne!(bank2.incremental_hash_since(bank0.slot()), expected);
+        // nothing is modified after bank2 itself
+        assert_eq!(
+            bank2.incremental_hash_since(bank2.slot()),
+            AccountsHash::calculate_hash(vec![vec![]]).0
+        );
+    }
+
     #[test]
     fn test_bank_epoch_vote_accounts() {
         let leader_pubkey = solana_sdk::pubkey::new_rand();
@@ -12126,6 +12785,209 @@ pub(crate) mod tests {
         );
     }
 
+    #[test]
+    fn test_bank_get_leader_schedule_epoch() {
+        let (mut genesis_config, _) = create_genesis_config(500);
+        const SLOTS_PER_EPOCH: u64 = MINIMUM_SLOTS_PER_EPOCH as u64;
+        const LEADER_SCHEDULE_SLOT_OFFSET: u64 = SLOTS_PER_EPOCH / 2;
+        genesis_config.epoch_schedule =
+            EpochSchedule::custom(SLOTS_PER_EPOCH, LEADER_SCHEDULE_SLOT_OFFSET, false);
+        let bank = Bank::new_for_tests(&genesis_config);
+
+        // the leader schedule epoch advances the offset's number of slots before each epoch
+        let epoch_boundary = SLOTS_PER_EPOCH;
+        let offset_boundary = epoch_boundary - LEADER_SCHEDULE_SLOT_OFFSET;
+        assert_eq!(bank.get_leader_schedule_epoch(0), 0);
+        assert_eq!(bank.get_leader_schedule_epoch(offset_boundary - 1), 0);
+        assert_eq!(bank.get_leader_schedule_epoch(offset_boundary), 1);
+        assert_eq!(bank.get_leader_schedule_epoch(epoch_boundary - 1), 1);
+        assert_eq!(bank.get_leader_schedule_epoch(epoch_boundary), 1);
+        assert_eq!(
+            bank.get_leader_schedule_epoch(epoch_boundary + offset_boundary),
+            2
+        );
+
+        // during warmup the offset behaves as one full epoch
+        let (genesis_config, _) = create_genesis_config(500);
+        let bank = Bank::new_for_tests(&genesis_config);
+        assert!(genesis_config.epoch_schedule.warmup);
+        assert_eq!(bank.get_leader_schedule_epoch(0), 1);
+        assert_eq!(
+            bank.get_leader_schedule_epoch(MINIMUM_SLOTS_PER_EPOCH as u64),
+            2
+        );
+    }
+
+    #[test]
+    fn test_bank_get_minimum_balance_for_rent_exemption() {
+        let (mut genesis_config, _) = create_genesis_config(500);
+        genesis_config.rent = Rent {
+            lamports_per_byte_year: 7,
+            exemption_threshold: 3.0,
+            burn_percent: 10,
+        };
+        let bank = Bank::new_for_tests(&genesis_config);
+        for data_len in [0, 1, 100, 10 * 1024, MAX_PERMITTED_DATA_LENGTH as usize] {
+            assert_eq!(
+                bank.get_minimum_balance_for_rent_exemption(data_len),
+                genesis_config.rent.minimum_balance(data_len),
+            );
+        }
+
+        // zero rent still requires a nonzero balance
+        genesis_config.rent = Rent::free();
+        let bank = Bank::new_for_tests(&genesis_config);
+        assert_eq!(genesis_config.rent.minimum_balance(0), 0);
+        assert_eq!(bank.get_minimum_balance_for_rent_exemption(0), 1);
+    }
+
+    #[test]
+    fn test_reverify_epoch_accounts_hash() {
+        let (genesis_config, _mint_keypair) = create_genesis_config(sol_to_lamports(100.));
+        let bank0 = Arc::new(Bank::new_for_tests(&genesis_config));
+        let start_slot = epoch_accounts_hash::calculation_start(&bank0);
+        let stop_slot = epoch_accounts_hash::calculation_stop(&bank0);
+
+        let start_bank = Arc::new(Bank::new_from_parent(
+            &bank0,
+            &Pubkey::default(),
+            start_slot,
+        ));
+        let pubkey = Pubkey::new_unique();
+        start_bank.deposit(&pubkey, sol_to_lamports(1.)).unwrap();
+        start_bank
+            .deposit(&Pubkey::new_unique(), sol_to_lamports(1.))
+            .unwrap();
+        start_bank.squash();
+        start_bank.force_flush_accounts_cache();
+        assert!(matches!(
+            start_bank.reverify_epoch_accounts_hash(),
+            Err(EpochAccountsHashError::NotIncluded(slot, stop))
+                if slot == start_slot && stop == stop_slot
+        ));
+
+        let accounts_hash = start_bank.update_accounts_hash();
+        let epoch_accounts_hash_manager =
+            &bank0.rc.accounts.accounts_db.epoch_accounts_hash_manager;
+        epoch_accounts_hash_manager.set_valid(EpochAccountsHash::new(accounts_hash), start_slot);
+
+        // changes after the start slot are not part of the EAH, even once clean has purged the
+        // superseded versions from the accounts index
+        let stop_bank = Bank::new_from_parent(&start_bank, &Pubkey::default(), stop_slot);
+        stop_bank.deposit(&pubkey, 1).unwrap();
+        stop_bank.deposit(&Pubkey::new_unique(), 1).unwrap();
+        stop_bank.squash();
+        stop_bank.force_flush_accounts_cache();
+        stop_bank.clean_accounts_for_tests();
+        assert!(stop_bank.reverify_epoch_accounts_hash().is_ok());
+
+        // tamper with the EAH
+        let tampered_hash = Hash::new_unique();
+        epoch_accounts_hash_manager.set_in_flight(stop_slot);
+        epoch_accounts_hash_manager.set_valid(EpochAccountsHash::new(tampered_hash), stop_slot);
+        assert!(matches!(
+            stop_bank.reverify_epoch_accounts_hash(),
+            Err(EpochAccountsHashError::Mismatch { calculated, expected })
+                if calculated == accounts_hash && expected == tampered_hash
+        ));
+    }
+
+    #[test]
+    fn test_epoch_accounts_hash_status() {
+        let (genesis_config, _mint_keypair) = create_genesis_config(sol_to_lamports(100.));
+        let bank0 = Arc::new(Bank::new_for_tests(&genesis_config));
+        let start_slot = epoch_accounts_hash::calculation_start(&bank0);
+        let stop_slot = epoch_accounts_hash::calculation_stop(&bank0);
+        let epoch_accounts_hash_manager =
+            &bank0.rc.accounts.accounts_db.epoch_accounts_hash_manager;
+
+        // before the "start" slot
+        assert_eq!(
+            bank0.epoch_accounts_hash_status(),
+            EpochAccountsHashStatus::Invalid,
+        );
+
+        // between the "start" and "stop" slots, while the calculation is in-flight
+        let start_bank = Bank::new_from_parent(&bank0, &Pubkey::default(), start_slot);
+        epoch_accounts_hash_manager.set_in_flight(start_slot);
+        assert_eq!(
+            start_bank.epoch_accounts_hash_status(),
+            EpochAccountsHashStatus::InFlight,
+        );
+        assert!(start_bank.epoch_accounts_hash().is_none());
+
+        // after the "stop" slot
+        let epoch_accounts_hash = EpochAccountsHash::new(Hash::new_unique());
+        epoch_accounts_hash_manager.set_valid(epoch_accounts_hash, start_slot);
+        let stop_bank = Bank::new_from_parent(&bank0, &Pubkey::default(), stop_slot);
+        assert_eq!(
+            stop_bank.epoch_accounts_hash_status(),
+            EpochAccountsHashStatus::Valid(epoch_accounts_hash),
+        );
+    }
+
+    #[test]
+    fn test_is_snapshot_boundary() {
+        let (genesis_config, _mint_keypair) = create_genesis_config(sol_to_lamports(100.));
+        let snapshot_config = SnapshotConfig {
+            full_snapshot_archive_interval_slots: 20,
+            incremental_snapshot_archive_interval_slots: 5,
+            ..SnapshotConfig::default()
+        };
+        let mut bank = Arc::new(Bank::new_for_tests(&genesis_config));
+        for slot in 1..=45 {
+            bank = Arc::new(Bank::new_from_parent(&bank, &Pubkey::default(), slot));
+            let expected = match slot {
+                20 | 40 => Some(SnapshotRequestKind::Full),
+                5 | 10 | 15 | 25 | 30 | 35 | 45 => Some(SnapshotRequestKind::Incremental),
+                _ => None,
+            };
+            assert_eq!(
+                bank.is_snapshot_boundary(&snapshot_config),
+                expected,
+                "slot {slot}"
+            );
+        }
+
+        // skipped slots do not shift the boundaries, since they are based on block height
+        let bank = Bank::new_from_parent(&bank, &Pubkey::default(), 100);
+        assert_eq!(bank.block_height(), 46);
+        assert_eq!(bank.is_snapshot_boundary(&snapshot_config), None);
+    }
+
+    #[test]
+    fn test_bank_state_root() {
+        let (genesis_config, _) = create_genesis_config(500);
+        let new_bank = |deposit: Option<Pubkey>| {
+            let bank = Bank::new_for_tests(&genesis_config);
+            if let Some(pubkey) = deposit {
+                bank.deposit(&pubkey, 42).unwrap();
+            }
+            bank.freeze();
+            bank.update_accounts_hash();
+            bank
+        };
+
+        // identical banks produce identical state roots
+        let bank0 = new_bank(None);
+        let bank1 = new_bank(None);
+        assert_eq!(bank0.state_root(), bank1.state_root());
+        assert_eq!(bank0.state_root(), bank0.state_root());
+
+        // divergent banks do not
+        let bank2 = new_bank(Some(Pubkey::new_unique()));
+        assert_ne!(bank0.state_root(), bank2.state_root());
+
+        // the epoch accounts hash is part of the state root
+        bank1
+            .rc
+            .accounts
+            .accounts_db
+            .epoch_accounts_hash_manager
+            .set_valid(EpochAccountsHash::new(Hash::new_unique()), 0);
+        assert_ne!(bank0.state_root(), bank1.state_root());
+    }
+
     #[test]
     fn test_is_delta_true() {
         let (genesis_config, mint_keypair) = create_genesis_config(sol_to_lamports(1.0));
@@ -12474,6 +13336,180 @@ pub(crate) mod tests {
         );
     }
 
+    #[test]
+    fn test_accounts_sorted_for_tests() {
+        let (genesis_config, mint_keypair) = create_genesis_config(sol_to_lamports(1.));
+        let bank0 = Arc::new(Bank::new_for_tests(&genesis_config));
+        let bank1 = Arc::new(new_from_parent(&bank0));
+        for _ in 0..10 {
+            bank1
+                .transfer(LAMPORTS_PER_SOL / 100, &mint_keypair, &Pubkey::new_unique())
+                .unwrap();
+        }
+
+        let accounts = bank1.accounts_sorted_for_tests();
+        assert_eq!(
+            accounts.len(),
+            bank1.get_all_accounts_with_modified_slots().unwrap().len()
+        );
+        assert!(accounts.windows(2).all(|window| window[0].0 < window[1].0));
+        assert_eq!(accounts, bank1.accounts_sorted_for_tests());
+    }
+
+    #[test]
+    fn test_feature_set_snapshot() {
+        let (genesis_config, _mint_keypair) = create_genesis_config(sol_to_lamports(1.));
+        let mut bank = Bank::new_for_tests(&genesis_config);
+        bank.deactivate_feature(&feature_set::epoch_accounts_hash::id());
+        let feature_set_snapshot = bank.feature_set_snapshot();
+        assert!(feature_set_snapshot
+            .inactive
+            .contains(&feature_set::epoch_accounts_hash::id()));
+        assert!(!feature_set_snapshot
+            .active
+            .contains_key(&feature_set::epoch_accounts_hash::id()));
+
+        bank.activate_feature(&feature_set::epoch_accounts_hash::id());
+        let feature_set_snapshot = bank.feature_set_snapshot();
+        assert_eq!(
+            feature_set_snapshot
+                .active
+                .get(&feature_set::epoch_accounts_hash::id()),
+            Some(&0),
+        );
+        assert!(!feature_set_snapshot
+            .inactive
+            .contains(&feature_set::epoch_accounts_hash::id()));
+        assert_eq!(
+            feature_set_snapshot.active.len() + feature_set_snapshot.inactive.len(),
+            bank.feature_set.active.len() + bank.feature_set.inactive.len(),
+        );
+
+        let serialized = bincode::serialize(&feature_set_snapshot).unwrap();
+        let deserialized: FeatureSetSnapshot = bincode::deserialize(&serialized).unwrap();
+        assert_eq!(deserialized, feature_set_snapshot);
+    }
+
+    #[test]
+    fn test_accounts_equal_with() {
+        let (genesis_config, _mint_keypair) = create_genesis_config(sol_to_lamports(1.));
+        let bank_a = Bank::new_for_tests(&genesis_config);
+        let bank_b = Bank::new_for_tests(&genesis_config);
+        let pubkey = solana_sdk::pubkey::new_rand();
+        let mut account = AccountSharedData::new(LAMPORTS_PER_SOL, 0, &Pubkey::default());
+        account.set_rent_epoch(1);
+        bank_a.store_account(&pubkey, &account);
+        account.set_rent_epoch(2);
+        bank_b.store_account(&pubkey, &account);
+
+        let ignoring_rent_epoch = |a: &AccountSharedData, b: &AccountSharedData| {
+            let mut b = b.clone();
+            b.set_rent_epoch(a.rent_epoch());
+            a == &b
+        };
+        assert!(!bank_a.accounts_equal_with(&bank_b, |a, b| a == b));
+        assert!(bank_a.accounts_equal_with(&bank_b, ignoring_rent_epoch));
+
+        // a pubkey present in only one bank is never equal, whatever the comparator
+        bank_b.store_account(&solana_sdk::pubkey::new_rand(), &account);
+        assert!(!bank_a.accounts_equal_with(&bank_b, ignoring_rent_epoch));
+    }
+
+    #[test]
+    fn test_account_hash() {
+        let (genesis_config, _mint_keypair) = create_genesis_config(sol_to_lamports(1.));
+        let bank_a = Bank::new_for_tests(&genesis_config);
+        let bank_b = Bank::new_for_tests(&genesis_config);
+        let pubkey = Pubkey::new_unique();
+        assert_eq!(bank_a.account_hash(&pubkey), None);
+
+        let account = AccountSharedData::new(LAMPORTS_PER_SOL, 42, &Pubkey::new_unique());
+        bank_a.store_account(&pubkey, &account);
+        bank_b.store_account(&pubkey, &account);
+        let account_hash = bank_a.account_hash(&pubkey);
+        assert!(account_hash.is_some());
+        assert_eq!(account_hash, bank_b.account_hash(&pubkey));
+
+        // the hash covers the account's data, owner, and lamports
+        let mut different_account = account.clone();
+        different_account.data_as_mut_slice()[0] = 1;
+        bank_b.store_account(&pubkey, &different_account);
+        assert_ne!(account_hash, bank_b.account_hash(&pubkey));
+
+        let mut different_account = account.clone();
+        different_account.set_owner(Pubkey::new_unique());
+        bank_b.store_account(&pubkey, &different_account);
+        assert_ne!(account_hash, bank_b.account_hash(&pubkey));
+
+        let mut different_account = account.clone();
+        different_account.set_lamports(LAMPORTS_PER_SOL + 1);
+        bank_b.store_account(&pubkey, &different_account);
+        assert_ne!(account_hash, bank_b.account_hash(&pubkey));
+
+        bank_b.store_account(&pubkey, &account);
+        assert_eq!(account_hash, bank_b.account_hash(&pubkey));
+    }
+
+    #[test]
+    fn test_accounts_with_rent_epoch_below() {
+        let (genesis_config, _mint_keypair) = create_genesis_config(500);
+        let bank = Bank::new_for_tests(&genesis_config);
+
+        let pubkeys = [1, 3, 5].map(|rent_epoch| {
+            let pubkey = solana_sdk::pubkey::new_rand();
+            let mut account = AccountSharedData::new(1, 0, &Pubkey::default());
+            account.set_rent_epoch(rent_epoch);
+            bank.store_account(&pubkey, &account);
+            pubkey
+        });
+
+        let lagging = bank.accounts_with_rent_epoch_below(4).unwrap();
+        assert!(lagging.contains(&pubkeys[0]));
+        assert!(lagging.contains(&pubkeys[1]));
+        assert!(!lagging.contains(&pubkeys[2]));
+        assert!(lagging.windows(2).all(|pubkeys| pubkeys[0] < pubkeys[1]));
+
+        let lagging = bank.accounts_with_rent_epoch_below(1).unwrap();
+        assert!(!pubkeys.iter().any(|pubkey| lagging.contains(pubkey)));
+    }
+
+    #[test]
+    fn test_accounts_created_this_epoch() {
+        const SLOTS_PER_EPOCH: Slot = 32;
+        let (mut genesis_config, _mint_keypair) = create_genesis_config(500);
+        genesis_config.epoch_schedule =
+            EpochSchedule::custom(SLOTS_PER_EPOCH, SLOTS_PER_EPOCH, false);
+        let account = AccountSharedData::new(1, 0, &Pubkey::default());
+
+        // created in epoch 0
+        let bank0 = Arc::new(Bank::new_for_tests(&genesis_config));
+        let pubkey0 = solana_sdk::pubkey::new_rand();
+        bank0.store_account(&pubkey0, &account);
+        let bank1 = Arc::new(Bank::new_from_parent(
+            &bank0,
+            &Pubkey::default(),
+            SLOTS_PER_EPOCH - 1,
+        ));
+        let pubkey1 = solana_sdk::pubkey::new_rand();
+        bank1.store_account(&pubkey1, &account);
+        let created = bank1.accounts_created_this_epoch();
+        assert!(created.contains(&pubkey0));
+        assert!(created.contains(&pubkey1));
+
+        // created in epoch 1, while the epoch 0 accounts are only updated
+        let bank2 = Bank::new_from_parent(&bank1, &Pubkey::default(), SLOTS_PER_EPOCH);
+        assert_eq!(bank2.epoch(), 1);
+        let pubkey2 = solana_sdk::pubkey::new_rand();
+        bank2.store_account(&pubkey2, &account);
+        let updated_account = AccountSharedData::new(2, 0, &Pubkey::default());
+        bank2.store_account(&pubkey0, &updated_account);
+        let created = bank2.accounts_created_this_epoch();
+        assert!(created.contains(&pubkey2));
+        assert!(!created.contains(&pubkey0));
+        assert!(!created.contains(&pubkey1));
+        assert!(created.windows(2).all(|pubkeys| pubkeys[0] < pubkeys[1]));
+    }
+
     #[test]
     fn test_get_filtered_indexed_accounts_limit_exceeded() {
         let (genesis_config, _mint_keypair) = create_genesis_config(500);
@@ -12502,6 +13538,40 @@ pub(crate) mod tests {
             .is_err());
     }
 
+    #[test]
+    fn test_program_accounts() {
+        let (mut genesis_config, _mint_keypair) = create_genesis_config(500);
+        let program_id = Pubkey::new_unique();
+        let mut program_account = AccountSharedData::new(1, 42, &bpf_loader::id());
+        program_account.set_executable(true);
+        genesis_config.add_account(program_id, program_account);
+        let data_address = Pubkey::new_unique();
+        genesis_config.add_account(
+            data_address,
+            AccountSharedData::new(1, 42, &bpf_loader::id()),
+        );
+
+        let mut account_indexes = AccountSecondaryIndexes::default();
+        account_indexes.indexes.insert(AccountIndex::ProgramId);
+        for account_indexes in [AccountSecondaryIndexes::default(), account_indexes] {
+            let bank = Bank::new_with_config_for_tests(
+                &genesis_config,
+                account_indexes,
+                false,
+                AccountShrinkThreshold::default(),
+            );
+            let program_accounts = bank.program_accounts();
+            let pubkeys: Vec<_> = program_accounts.iter().map(|(pubkey, _)| *pubkey).collect();
+            assert!(pubkeys.contains(&program_id));
+            assert!(pubkeys.contains(&system_program::id()));
+            assert!(!pubkeys.contains(&data_address));
+            assert!(program_accounts
+                .iter()
+                .all(|(_, account)| account.executable()));
+            assert!(pubkeys.windows(2).all(|pubkeys| pubkeys[0] < pubkeys[1]));
+        }
+    }
+
     #[test]
     fn test_get_filtered_indexed_accounts() {
         let (genesis_config, _mint_keypair) = create_genesis_config(500);
@@ -16582,9 +17652,8 @@ pub(crate) mod tests {
     fn test_store_scan_consistency_unrooted() {
         for accounts_db_caching_enabled in &[false, true] {
             let (pruned_banks_sender, pruned_banks_receiver) = unbounded();
-            let pruned_banks_request_handler = PrunedBanksRequestHandler {
-                pruned_banks_receiver,
-            };
+            let pruned_banks_request_handler =
+                PrunedBanksRequestHandler::new(pruned_banks_receiver);
             test_store_scan_consistency(
                 *accounts_db_caching_enabled,
                 move |bank0,
@@ -17645,6 +18714,38 @@ pub(crate) mod tests {
         );
     }
 
+    #[test]
+    fn test_large_accounts() {
+        let (genesis_config, _mint_keypair) = create_genesis_config(sol_to_lamports(100.));
+        let bank = Bank::new_for_tests(&genesis_config);
+        // larger than any sysvar, so only the accounts stored here are reported
+        let min_data_len = 128 * 1024;
+        let bigger_data_len = 2 * min_data_len;
+
+        let store = |data_len| {
+            let pubkey = Pubkey::new_unique();
+            let account = AccountSharedData::new(1, data_len, &Pubkey::default());
+            bank.store_account(&pubkey, &account);
+            pubkey
+        };
+        let small = store(min_data_len - 1);
+        let large = store(min_data_len);
+        let larger = store(bigger_data_len);
+        let large_zero_lamport = Pubkey::new_unique();
+        bank.store_account(
+            &large_zero_lamport,
+            &AccountSharedData::new(0, bigger_data_len, &Pubkey::default()),
+        );
+
+        let large_accounts = bank.large_accounts(min_data_len).unwrap();
+        assert_eq!(
+            large_accounts,
+            vec![(larger, bigger_data_len), (large, min_data_len)]
+        );
+        assert!(!large_accounts.iter().any(|(pubkey, _)| *pubkey == small));
+        assert!(bank.large_accounts(bigger_data_len + 1).unwrap().is_empty());
+    }
+
     #[test]
     fn test_transfer_sysvar() {
         solana_logger::setup();
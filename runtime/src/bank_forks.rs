@@ -4,7 +4,9 @@ use {
     crate::{
         accounts_background_service::{AbsRequestSender, SnapshotRequest, SnapshotRequestType},
         bank::Bank,
-        epoch_accounts_hash,
+        epoch_accounts_hash::{
+            self, EpochAccountsHashInFlightPolicy, EpochAccountsHashInFlightRequestOutcome,
+        },
         snapshot_config::SnapshotConfig,
     },
     log::*,
@@ -19,10 +21,24 @@ use {
         },
         time::Instant,
     },
+    thiserror::Error,
 };
 
 pub const MAX_ROOT_DISTANCE_FOR_VOTE_ONLY: Slot = 400;
 pub type AtomicSlot = AtomicU64;
+
+/// Error from `BankForks::set_accounts_hash_interval_fraction()`
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AccountsHashIntervalError {
+    #[error(
+        "snapshot interval of {snapshot_interval_slots} slots is not a multiple of the accounts \
+         hash interval of {accounts_hash_interval_slots} slots"
+    )]
+    SnapshotIntervalNotAMultiple {
+        snapshot_interval_slots: Slot,
+        accounts_hash_interval_slots: Slot,
+    },
+}
 pub struct ReadOnlyAtomicSlot {
     slot: Arc<AtomicSlot>,
 }
@@ -56,6 +72,42 @@ struct SetRootTimings {
     prune_remove_ms: i64,
 }
 
+/// How `BankForks` evicts the cached accounts of frozen banks that are not rooted yet
+///
+/// Until a bank is rooted, the accounts it stored are held in memory, in the accounts cache.  On
+/// memory-constrained nodes, the accounts of older banks can be flushed to storage instead, by
+/// `AccountsBackgroundService`.  The banks themselves are kept, and their accounts are read back
+/// from storage transparently.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BankEvictionPolicy {
+    /// Keep the accounts of all banks cached until they are rooted or pruned
+    KeepAll,
+    /// Keep the accounts of only the newest `n` frozen, unrooted banks cached
+    KeepNewest(usize),
+}
+
+impl Default for BankEvictionPolicy {
+    fn default() -> Self {
+        Self::KeepAll
+    }
+}
+
+/// The result of setting a new root, see `BankForks::set_root_with_result()`
+#[derive(Debug)]
+pub struct SetRootResult {
+    /// the slots of the banks that were pruned, in ascending order
+    pub pruned_banks: Vec<Slot>,
+    /// the pruned banks themselves, as returned by `BankForks::set_root()`
+    ///
+    /// Dropping the last reference to a pruned bank purges its slot, so drop these after
+    /// releasing the `BankForks` lock.
+    pub removed_banks: Vec<Arc<Bank>>,
+    pub new_root: Slot,
+}
+
+/// Called with the new root slot around each root change, see `BankForks::set_root_hooks()`
+pub type SetRootHook = Arc<dyn Fn(Slot) + Send + Sync>;
+
 pub struct BankForks {
     banks: HashMap<Slot, Arc<Bank>>,
     descendants: HashMap<Slot, HashSet<Slot>>,
@@ -66,6 +118,13 @@ pub struct BankForks {
     pub accounts_hash_interval_slots: Slot,
     last_accounts_hash_slot: Slot,
     in_vote_only_mode: Arc<AtomicBool>,
+    epoch_accounts_hash_in_flight_policy: EpochAccountsHashInFlightPolicy,
+    /// The bank whose epoch accounts hash request is queued behind an in-flight calculation, see
+    /// `EpochAccountsHashInFlightPolicy::Queue`
+    queued_epoch_accounts_hash_bank: Option<Arc<Bank>>,
+    bank_eviction_policy: BankEvictionPolicy,
+    before_set_root_hook: Option<SetRootHook>,
+    after_set_root_hook: Option<SetRootHook>,
 }
 
 impl Index<u64> for BankForks {
@@ -182,6 +241,11 @@ impl BankForks {
             accounts_hash_interval_slots: std::u64::MAX,
             last_accounts_hash_slot: root,
             in_vote_only_mode: Arc::new(AtomicBool::new(false)),
+            epoch_accounts_hash_in_flight_policy: EpochAccountsHashInFlightPolicy::default(),
+            queued_epoch_accounts_hash_bank: None,
+            bank_eviction_policy: BankEvictionPolicy::default(),
+            before_set_root_hook: None,
+            after_set_root_hook: None,
         }
     }
 
@@ -197,6 +261,27 @@ impl BankForks {
         bank
     }
 
+    /// The slots of the frozen, unrooted banks whose cached accounts should be evicted, according
+    /// to the bank eviction policy, newest first
+    ///
+    /// Evicting flushes the accounts to storage, so `AccountsBackgroundService` does it after
+    /// releasing the `BankForks` lock, see `AccountsDb::flush_unrooted_slot_cache()`.
+    pub fn slots_to_evict(&self) -> Vec<Slot> {
+        let num_banks_to_keep = match self.bank_eviction_policy {
+            BankEvictionPolicy::KeepAll => return Vec::new(),
+            BankEvictionPolicy::KeepNewest(num_banks_to_keep) => num_banks_to_keep,
+        };
+        let root = self.root();
+        let mut frozen_slots: Vec<_> = self
+            .banks
+            .values()
+            .filter(|bank| bank.slot() > root && bank.is_frozen())
+            .map(|bank| bank.slot())
+            .collect();
+        frozen_slots.sort_unstable_by(|a, b| b.cmp(a));
+        frozen_slots.into_iter().skip(num_banks_to_keep).collect()
+    }
+
     pub fn remove(&mut self, slot: Slot) -> Option<Arc<Bank>> {
         let bank = self.banks.remove(&slot)?;
         for parent in bank.proper_ancestors() {
@@ -227,6 +312,14 @@ impl BankForks {
         self[self.highest_slot()].clone()
     }
 
+    /// Returns how many slots the working bank is ahead of the root
+    ///
+    /// Both are read from `self`, so the distance is consistent when called under a single lock
+    /// of the `BankForks`.
+    pub fn root_distance(&self) -> Slot {
+        self.highest_slot() - self.root()
+    }
+
     fn do_set_root_return_metrics(
         &mut self,
         root: Slot,
@@ -291,6 +384,28 @@ impl BankForks {
             eah_banks.len(),
             eah_banks.iter().map(|bank| bank.slot()).collect::<Vec<_>>(),
         );
+        // A request queued behind an in-flight calculation is sent once that calculation has
+        // completed, before handling a new request
+        if let Some(queued_eah_bank) = &self.queued_epoch_accounts_hash_bank {
+            if let Some(queued_slot) = queued_eah_bank
+                .rc
+                .accounts
+                .accounts_db
+                .epoch_accounts_hash_manager
+                .try_set_queued_in_flight()
+            {
+                assert_eq!(queued_slot, queued_eah_bank.slot());
+                let queued_eah_bank = self.queued_epoch_accounts_hash_bank.take().unwrap();
+                debug!(
+                    "sending queued epoch accounts hash request, slot: {}",
+                    queued_slot
+                );
+                Self::send_epoch_accounts_hash_request(
+                    accounts_background_request_sender,
+                    queued_eah_bank,
+                );
+            }
+        }
         if let Some(eah_bank) = eah_banks.first() {
             debug!(
                 "sending epoch accounts hash request, slot: {}",
@@ -306,20 +421,27 @@ impl BankForks {
             total_squash_cache_ms += squash_timing.squash_cache_ms as i64;
             is_root_bank_squashed = eah_bank.slot() == root;
 
-            eah_bank
+            match eah_bank
                 .rc
                 .accounts
                 .accounts_db
                 .epoch_accounts_hash_manager
-                .set_in_flight(eah_bank.slot());
-            accounts_background_request_sender
-                .send_snapshot_request(SnapshotRequest {
-                    snapshot_root_bank: Arc::clone(eah_bank),
-                    status_cache_slot_deltas: Vec::default(),
-                    request_type: SnapshotRequestType::EpochAccountsHash,
-                    enqueued: Instant::now(),
-                })
-                .expect("send epoch accounts hash request");
+                .try_set_in_flight(
+                    eah_bank.slot(),
+                    self.epoch_accounts_hash_in_flight_policy,
+                    eah_bank.epoch_schedule(),
+                ) {
+                EpochAccountsHashInFlightRequestOutcome::Accepted => {
+                    Self::send_epoch_accounts_hash_request(
+                        accounts_background_request_sender,
+                        Arc::clone(eah_bank),
+                    );
+                }
+                EpochAccountsHashInFlightRequestOutcome::Queued => {
+                    self.queued_epoch_accounts_hash_bank = Some(Arc::clone(eah_bank));
+                }
+                EpochAccountsHashInFlightRequestOutcome::Rejected => (),
+            }
         }
         drop(eah_banks);
 
@@ -422,6 +544,9 @@ impl BankForks {
         accounts_background_request_sender: &AbsRequestSender,
         highest_confirmed_root: Option<Slot>,
     ) -> Vec<Arc<Bank>> {
+        if let Some(before_set_root_hook) = &self.before_set_root_hook {
+            before_set_root_hook(root);
+        }
         let set_root_start = Instant::now();
         let (removed_banks, set_root_metrics) = self.do_set_root_return_metrics(
             root,
@@ -496,9 +621,37 @@ impl BankForks {
             ("dropped_banks_len", set_root_metrics.dropped_banks_len, i64),
             ("accounts_data_len", set_root_metrics.accounts_data_len, i64),
         );
+        if let Some(after_set_root_hook) = &self.after_set_root_hook {
+            after_set_root_hook(root);
+        }
         removed_banks
     }
 
+    /// Same as `set_root()`, but also returns the slots of the pruned banks
+    ///
+    /// This lets callers reconcile their own per-slot state on the same thread.  As with
+    /// `set_root()`, the pruned banks are returned to the caller, so they are not dropped, and
+    /// sent on the dropped-slots channel, while the `BankForks` lock is held.
+    pub fn set_root_with_result(
+        &mut self,
+        root: Slot,
+        accounts_background_request_sender: &AbsRequestSender,
+        highest_confirmed_root: Option<Slot>,
+    ) -> SetRootResult {
+        let removed_banks = self.set_root(
+            root,
+            accounts_background_request_sender,
+            highest_confirmed_root,
+        );
+        let mut pruned_banks: Vec<_> = removed_banks.iter().map(|bank| bank.slot()).collect();
+        pruned_banks.sort_unstable();
+        SetRootResult {
+            pruned_banks,
+            removed_banks,
+            new_root: root,
+        }
+    }
+
     pub fn root(&self) -> Slot {
         self.root.load(Ordering::Relaxed)
     }
@@ -608,6 +761,107 @@ impl BankForks {
         self.accounts_hash_interval_slots = accounts_interval_slots;
     }
 
+    /// Set what to do if an epoch accounts hash calculation is requested while another is still
+    /// in flight, see `EpochAccountsHashInFlightPolicy`
+    pub fn set_epoch_accounts_hash_in_flight_policy(
+        &mut self,
+        policy: EpochAccountsHashInFlightPolicy,
+    ) {
+        self.epoch_accounts_hash_in_flight_policy = policy;
+    }
+
+    /// Set the hooks called with the new root slot, right before and right after each root change
+    ///
+    /// Both hooks run on the thread calling `set_root()`, while it borrows `self` mutably, which
+    /// usually means while it holds the BankForks write lock.  So the hooks must not lock
+    /// BankForks themselves, or they will deadlock, and they should return quickly, as replay and
+    /// RPC are blocked on the lock until they do.  The "after" hook runs once the root has been
+    /// updated and the pruned banks removed, but before the pruned banks are dropped.
+    pub fn set_root_hooks(&mut self, before: Option<SetRootHook>, after: Option<SetRootHook>) {
+        self.before_set_root_hook = before;
+        self.after_set_root_hook = after;
+    }
+
+    /// Set how the cached accounts of frozen, unrooted banks are evicted, see `BankEvictionPolicy`
+    pub fn set_bank_eviction_policy(&mut self, policy: BankEvictionPolicy) {
+        self.bank_eviction_policy = policy;
+    }
+
+    /// Set the accounts hash interval as a fraction of the (post-warmup) epoch length
+    ///
+    /// This scales the interval with clusters that have different epoch lengths.  The interval is
+    /// rounded to the nearest slot, and is at least one slot.  Since the epoch length is fixed in
+    /// the genesis config, the resulting interval is the same on every node that uses the same
+    /// fraction.
+    ///
+    /// Snapshots are only taken at accounts hash intervals, so if snapshots are generated, their
+    /// intervals must be multiples of the resulting interval.  Otherwise, the interval is left
+    /// unchanged and an error is returned.
+    pub fn set_accounts_hash_interval_fraction(
+        &mut self,
+        fraction: f64,
+    ) -> Result<(), AccountsHashIntervalError> {
+        assert!(
+            fraction.is_finite() && fraction > 0.0,
+            "accounts hash interval fraction must be positive: {}",
+            fraction
+        );
+        let slots_per_epoch = self.root_bank().epoch_schedule().slots_per_epoch;
+        let accounts_hash_interval_slots =
+            ((slots_per_epoch as f64 * fraction).round() as Slot).max(1);
+
+        if let Some(snapshot_config) = self
+            .snapshot_config
+            .as_ref()
+            .filter(|snapshot_config| snapshot_config.should_generate_snapshots())
+        {
+            for snapshot_interval_slots in [
+                snapshot_config.full_snapshot_archive_interval_slots,
+                snapshot_config.incremental_snapshot_archive_interval_slots,
+            ] {
+                // an interval of Slot::MAX disables that kind of snapshot
+                if snapshot_interval_slots != Slot::MAX
+                    && snapshot_interval_slots % accounts_hash_interval_slots != 0
+                {
+                    return Err(AccountsHashIntervalError::SnapshotIntervalNotAMultiple {
+                        snapshot_interval_slots,
+                        accounts_hash_interval_slots,
+                    });
+                }
+            }
+        }
+
+        self.set_accounts_hash_interval_slots(accounts_hash_interval_slots);
+        Ok(())
+    }
+
+    /// Returns the next slot after the working bank at which a full snapshot will be taken
+    ///
+    /// Full snapshots are taken based on block height, so this assumes there are no skipped slots
+    /// between the working bank and the returned slot.  Returns `None` if full snapshots are not
+    /// being generated.
+    #[must_use]
+    pub fn next_full_snapshot_slot(&self) -> Option<Slot> {
+        let snapshot_config = self.snapshot_config.as_ref()?;
+        let full_snapshot_archive_interval_slots =
+            snapshot_config.full_snapshot_archive_interval_slots;
+        if !snapshot_config.should_generate_snapshots()
+            || full_snapshot_archive_interval_slots == 0
+            || full_snapshot_archive_interval_slots == Slot::MAX
+        {
+            return None;
+        }
+
+        let working_bank = self.working_bank();
+        let block_height = working_bank.block_height();
+        let num_full_snapshot_intervals = block_height / full_snapshot_archive_interval_slots + 1;
+        let next_full_snapshot_block_height =
+            num_full_snapshot_intervals.checked_mul(full_snapshot_archive_interval_slots)?;
+        working_bank
+            .slot()
+            .checked_add(next_full_snapshot_block_height - block_height)
+    }
+
     /// Determine if this bank should request an epoch accounts hash
     #[must_use]
     fn should_request_epoch_accounts_hash(&self, bank: &Bank) -> bool {
@@ -623,6 +877,21 @@ impl BankForks {
             && bank.parent_slot() < start_slot
             && bank.slot() >= start_slot
     }
+
+    /// Send the request to calculate the epoch accounts hash of `eah_bank`, which must be in flight
+    fn send_epoch_accounts_hash_request(
+        accounts_background_request_sender: &AbsRequestSender,
+        eah_bank: Arc<Bank>,
+    ) {
+        accounts_background_request_sender
+            .send_snapshot_request(SnapshotRequest {
+                snapshot_root_bank: eah_bank,
+                status_cache_slot_deltas: Vec::default(),
+                request_type: SnapshotRequestType::EpochAccountsHash,
+                enqueued: Instant::now(),
+            })
+            .expect("send epoch accounts hash request");
+    }
 }
 
 #[cfg(test)]
@@ -643,7 +912,10 @@ mod tests {
             signature::{Keypair, Signer},
         },
         solana_vote_program::vote_state::BlockTimestamp,
-        std::{sync::atomic::Ordering::Relaxed, time::Duration},
+        std::{
+            sync::{atomic::Ordering::Relaxed, Mutex},
+            time::Duration,
+        },
     };
 
     #[test]
@@ -658,6 +930,157 @@ mod tests {
         assert_eq!(bank_forks.working_bank().tick_height(), 1);
     }
 
+    #[test]
+    fn test_root_distance() {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let bank = Bank::new_for_tests(&genesis_config);
+        let mut bank_forks = BankForks::new(bank);
+        assert_eq!(bank_forks.root_distance(), 0);
+
+        for slot in 1..=5 {
+            let parent = bank_forks[slot - 1].clone();
+            bank_forks.insert(Bank::new_from_parent(&parent, &Pubkey::default(), slot));
+        }
+        assert_eq!(bank_forks.root_distance(), 5);
+
+        // a skipped slot counts towards the distance
+        let parent = bank_forks[5].clone();
+        bank_forks.insert(Bank::new_from_parent(&parent, &Pubkey::default(), 8));
+        assert_eq!(bank_forks.root_distance(), 8);
+
+        bank_forks.set_root(3, &AbsRequestSender::default(), None);
+        assert_eq!(bank_forks.root_distance(), 5);
+        bank_forks.set_root(8, &AbsRequestSender::default(), None);
+        assert_eq!(bank_forks.root_distance(), 0);
+    }
+
+    #[test]
+    fn test_next_full_snapshot_slot() {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let bank = Bank::new_for_tests(&genesis_config);
+        let mut bank_forks = BankForks::new(bank);
+        assert_eq!(bank_forks.next_full_snapshot_slot(), None);
+
+        let full_snapshot_archive_interval_slots = 10;
+        bank_forks.set_snapshot_config(Some(SnapshotConfig {
+            full_snapshot_archive_interval_slots,
+            ..SnapshotConfig::default()
+        }));
+        assert_eq!(
+            bank_forks.next_full_snapshot_slot(),
+            Some(full_snapshot_archive_interval_slots)
+        );
+
+        for slot in 1..=25 {
+            let parent = bank_forks[slot - 1].clone();
+            bank_forks.insert(Bank::new_from_parent(&parent, &Pubkey::default(), slot));
+            let expected_slot = (slot / full_snapshot_archive_interval_slots + 1)
+                * full_snapshot_archive_interval_slots;
+            assert_eq!(bank_forks.next_full_snapshot_slot(), Some(expected_slot));
+        }
+
+        bank_forks.set_snapshot_config(Some(SnapshotConfig::new_load_only()));
+        assert_eq!(bank_forks.next_full_snapshot_slot(), None);
+    }
+
+    #[test]
+    fn test_set_accounts_hash_interval_fraction() {
+        for (slots_per_epoch, expected_accounts_hash_interval_slots) in
+            [(8_192, 2_048), (432_000, 108_000)]
+        {
+            let GenesisConfigInfo {
+                mut genesis_config, ..
+            } = create_genesis_config(10_000);
+            genesis_config.epoch_schedule =
+                EpochSchedule::custom(slots_per_epoch, slots_per_epoch, false);
+            let bank = Bank::new_for_tests(&genesis_config);
+            let mut bank_forks = BankForks::new(bank);
+
+            bank_forks
+                .set_accounts_hash_interval_fraction(0.25)
+                .unwrap();
+            assert_eq!(
+                bank_forks.accounts_hash_interval_slots,
+                expected_accounts_hash_interval_slots
+            );
+        }
+
+        // the interval is never less than one slot
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let mut bank_forks = BankForks::new(Bank::new_for_tests(&genesis_config));
+        bank_forks
+            .set_accounts_hash_interval_fraction(f64::MIN_POSITIVE)
+            .unwrap();
+        assert_eq!(bank_forks.accounts_hash_interval_slots, 1);
+
+        // the snapshot intervals must be multiples of the interval
+        bank_forks.set_snapshot_config(Some(SnapshotConfig {
+            full_snapshot_archive_interval_slots: 100,
+            incremental_snapshot_archive_interval_slots: 20,
+            ..SnapshotConfig::default()
+        }));
+        let slots_per_epoch = bank_forks.root_bank().epoch_schedule().slots_per_epoch as f64;
+        bank_forks
+            .set_accounts_hash_interval_fraction(10.0 / slots_per_epoch)
+            .unwrap();
+        assert_eq!(bank_forks.accounts_hash_interval_slots, 10);
+        assert_eq!(
+            bank_forks.set_accounts_hash_interval_fraction(40.0 / slots_per_epoch),
+            Err(AccountsHashIntervalError::SnapshotIntervalNotAMultiple {
+                snapshot_interval_slots: 20,
+                accounts_hash_interval_slots: 40,
+            })
+        );
+        assert_eq!(bank_forks.accounts_hash_interval_slots, 10);
+
+        // unless that kind of snapshot is disabled
+        bank_forks.set_snapshot_config(Some(SnapshotConfig {
+            full_snapshot_archive_interval_slots: 100,
+            incremental_snapshot_archive_interval_slots: Slot::MAX,
+            ..SnapshotConfig::default()
+        }));
+        bank_forks
+            .set_accounts_hash_interval_fraction(50.0 / slots_per_epoch)
+            .unwrap();
+        assert_eq!(bank_forks.accounts_hash_interval_slots, 50);
+    }
+
+    #[test]
+    fn test_bank_eviction_policy() {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let bank = Bank::new_for_tests(&genesis_config);
+        let mut bank_forks = BankForks::new(bank);
+        bank_forks.set_bank_eviction_policy(BankEvictionPolicy::KeepNewest(1));
+        let root_bank = bank_forks.root_bank();
+        let accounts_db = &root_bank.rc.accounts.accounts_db;
+
+        let mut pubkeys = vec![];
+        for slot in 1..=4 {
+            // inserting a bank freezes its parent, which can then be evicted
+            let parent = bank_forks[slot - 1].clone();
+            let bank = bank_forks.insert(Bank::new_from_parent(&parent, &Pubkey::default(), slot));
+            let pubkey = Pubkey::new_unique();
+            bank.deposit(&pubkey, slot).unwrap();
+            pubkeys.push(pubkey);
+        }
+
+        // banks 1 and 2 are evicted, bank 3 is the newest frozen bank, bank 4 is not frozen
+        assert_eq!(bank_forks.slots_to_evict(), vec![2, 1]);
+        for slot in bank_forks.slots_to_evict() {
+            assert!(accounts_db.flush_unrooted_slot_cache(slot));
+        }
+        assert!(accounts_db.accounts_cache.slot_cache(1).is_none());
+        assert!(accounts_db.accounts_cache.slot_cache(2).is_none());
+        assert!(accounts_db.accounts_cache.slot_cache(3).is_some());
+        assert!(accounts_db.accounts_cache.slot_cache(4).is_some());
+
+        // evicted banks' accounts are still readable
+        for (slot, pubkey) in (1..=4).zip(&pubkeys) {
+            assert_eq!(bank_forks[4].get_balance(pubkey), slot);
+            assert_eq!(bank_forks[slot].get_balance(pubkey), slot);
+        }
+    }
+
     #[test]
     fn test_bank_forks_new_from_banks() {
         let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
@@ -888,6 +1311,75 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_bank_forks_set_root_with_result() {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        // keep the banks alive, so pruning them does not purge their slots
+        let make_bank_forks = || {
+            let mut banks = vec![Arc::new(Bank::new_for_tests(&genesis_config))];
+            let mut bank_forks = BankForks::new_from_banks(&banks, 0);
+            for (parent, slot) in [(0, 1), (1, 2), (0, 3), (3, 4)] {
+                let bank = Bank::new_from_parent(&banks[parent], &Pubkey::default(), slot);
+                banks.push(bank_forks.insert(bank));
+            }
+            (bank_forks, banks)
+        };
+
+        let (mut bank_forks, _banks) = make_bank_forks();
+        let set_root_result =
+            bank_forks.set_root_with_result(2, &AbsRequestSender::default(), None);
+        assert_eq!(set_root_result.pruned_banks, vec![0, 1, 3, 4]);
+        assert_eq!(set_root_result.new_root, 2);
+        let mut removed_slots: Vec<_> = set_root_result
+            .removed_banks
+            .iter()
+            .map(|bank| bank.slot())
+            .collect();
+        removed_slots.sort_unstable();
+        assert_eq!(removed_slots, set_root_result.pruned_banks);
+        assert_eq!(bank_forks.root(), 2);
+
+        // banks at or above the highest confirmed root, on the path to the new root, are kept
+        let (mut bank_forks, _banks) = make_bank_forks();
+        let set_root_result =
+            bank_forks.set_root_with_result(2, &AbsRequestSender::default(), Some(1));
+        assert_eq!(set_root_result.pruned_banks, vec![0, 3, 4]);
+        assert_eq!(set_root_result.new_root, 2);
+    }
+
+    #[test]
+    fn test_bank_forks_set_root_hooks() {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let bank0 = Bank::new_for_tests(&genesis_config);
+        let mut bank_forks = BankForks::new(bank0);
+        let bank1 = Bank::new_from_parent(&bank_forks[0], &Pubkey::default(), 1);
+        bank_forks.insert(bank1);
+
+        // record the root as seen from within each hook
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let new_hook = |name: &'static str| -> SetRootHook {
+            let calls = Arc::clone(&calls);
+            let root = bank_forks.get_atomic_root();
+            Arc::new(move |slot| calls.lock().unwrap().push((name, slot, root.get())))
+        };
+        let before = new_hook("before");
+        let after = new_hook("after");
+        bank_forks.set_root_hooks(Some(before), Some(after));
+
+        bank_forks.set_root(1, &AbsRequestSender::default(), None);
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![("before", 1, 0), ("after", 1, 1)],
+        );
+
+        // clearing the hooks stops calling them
+        bank_forks.set_root_hooks(None, None);
+        let bank2 = Bank::new_from_parent(&bank_forks[1], &Pubkey::default(), 2);
+        bank_forks.insert(bank2);
+        bank_forks.set_root(2, &AbsRequestSender::default(), None);
+        assert_eq!(calls.lock().unwrap().len(), 2);
+    }
+
     #[test]
     fn test_bank_forks_with_highest_confirmed_root() {
         let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
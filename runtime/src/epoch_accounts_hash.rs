@@ -7,13 +7,37 @@
 //!
 //! This results in all nodes effectively voting on the accounts state (at least) once per epoch.
 
-use solana_sdk::hash::Hash;
+use {
+    crate::accounts_db::BankHashVerificationError,
+    solana_sdk::{
+        clock::Slot,
+        hash::{Hash, ParseHashError, HASH_BYTES},
+    },
+    std::{fmt, str::FromStr},
+    thiserror::Error,
+};
 
 mod utils;
 pub use utils::*;
 
 mod manager;
-pub use manager::Manager as EpochAccountsHashManager;
+pub use manager::{
+    InFlightPolicy as EpochAccountsHashInFlightPolicy,
+    InFlightRequestOutcome as EpochAccountsHashInFlightRequestOutcome,
+    Manager as EpochAccountsHashManager, Status as EpochAccountsHashStatus,
+};
+
+mod archive_coordinator;
+pub use archive_coordinator::{
+    ArchiveCoordinator as EpochAccountsHashArchiveCoordinator, ArchivingGuard,
+    EpochAccountsHashCalculationGuard,
+};
+
+mod shard;
+pub use shard::{
+    calculate_shard, calculate_shard_from_storages, combine_shards, CombineShardsError,
+    KeyspaceShard, ShardAccountsHash,
+};
 
 /// The EpochAccountsHash holds the result after calculating the accounts hash once per epoch
 #[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, Clone, Copy)]
@@ -31,4 +55,83 @@ impl EpochAccountsHash {
     pub const fn new(accounts_hash: Hash) -> Self {
         Self(accounts_hash)
     }
+
+    /// The raw bytes of this EpochAccountsHash, for comparing it out-of-band
+    #[must_use]
+    pub fn to_bytes(self) -> [u8; HASH_BYTES] {
+        self.0.to_bytes()
+    }
+
+    /// Make an EpochAccountsHash from raw bytes, see `to_bytes()`
+    #[must_use]
+    pub const fn from_bytes(bytes: [u8; HASH_BYTES]) -> Self {
+        Self(Hash::new_from_array(bytes))
+    }
+}
+
+/// Displays the hash as base58, the same as `Hash`
+impl fmt::Display for EpochAccountsHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Parses the base58 text form, see `Display`
+impl FromStr for EpochAccountsHash {
+    type Err = ParseHashError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Hash::from_str(s).map(Self)
+    }
+}
+
+/// Errors that can occur when re-verifying an epoch accounts hash, see
+/// `Bank::reverify_epoch_accounts_hash()`
+#[derive(Error, Debug)]
+pub enum EpochAccountsHashError {
+    #[error("bank {0} is before the epoch accounts hash stop slot {1}")]
+    NotIncluded(Slot, Slot),
+
+    #[error("no epoch accounts hash is available")]
+    Missing,
+
+    #[error("failed to calculate the accounts hash at slot {0}: {1:?}")]
+    Calculation(Slot, BankHashVerificationError),
+
+    #[error("epoch accounts hash mismatch: calculated {calculated}, expected {expected}")]
+    Mismatch { calculated: Hash, expected: Hash },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let epoch_accounts_hash = EpochAccountsHash::new(Hash::new_unique());
+        let bytes = epoch_accounts_hash.to_bytes();
+        assert_eq!(bytes, epoch_accounts_hash.as_ref().to_bytes());
+        assert_eq!(EpochAccountsHash::from_bytes(bytes), epoch_accounts_hash);
+    }
+
+    #[test]
+    fn test_base58_round_trip() {
+        let hash = Hash::new_unique();
+        let epoch_accounts_hash = EpochAccountsHash::new(hash);
+        let text = epoch_accounts_hash.to_string();
+        assert_eq!(text, hash.to_string());
+        assert_eq!(
+            text.parse::<EpochAccountsHash>().unwrap(),
+            epoch_accounts_hash
+        );
+
+        assert_eq!(
+            "not base58!".parse::<EpochAccountsHash>(),
+            Err(ParseHashError::Invalid)
+        );
+        assert_eq!(
+            "".parse::<EpochAccountsHash>(),
+            Err(ParseHashError::WrongSize)
+        );
+    }
 }
@@ -0,0 +1,155 @@
+use std::sync::{Condvar, Mutex};
+
+/// Serialize epoch accounts hash calculations and snapshot archive creation
+///
+/// On constrained nodes, calculating the EAH while also creating a snapshot archive thrashes I/O.
+/// When shared between AccountsHashVerifier and SnapshotPackagerService (via `SnapshotConfig`),
+/// only one of the two runs at a time.  Since the EAH is consensus-critical, it takes precedence:
+/// an archive will not start while an EAH calculation is running or waiting to run.
+#[derive(Debug, Default)]
+pub struct ArchiveCoordinator {
+    /// Current state of the EAH calculations and archive creation
+    state: Mutex<State>,
+    /// This condition variable is used to wait for the other side to finish
+    cvar: Condvar,
+}
+
+impl ArchiveCoordinator {
+    /// Create a new coordinator, with nothing running
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An epoch accounts hash calculation is about to start
+    ///
+    /// If an archive is being created, this call will block until it completes.  The calculation
+    /// is considered running until the returned guard is dropped.
+    #[must_use]
+    pub fn begin_epoch_accounts_hash_calculation(&self) -> EpochAccountsHashCalculationGuard {
+        let mut state = self.state.lock().unwrap();
+        // mark the calculation as pending first, so no new archives start while we wait
+        state.num_epoch_accounts_hash_calculations += 1;
+        while state.is_archiving {
+            state = self.cvar.wait(state).unwrap();
+        }
+        EpochAccountsHashCalculationGuard { coordinator: self }
+    }
+
+    /// A snapshot archive is about to be created
+    ///
+    /// If an epoch accounts hash calculation is running or pending, this call will block until
+    /// it completes.  The archive is considered being created until the returned guard is dropped.
+    #[must_use]
+    pub fn begin_archiving(&self) -> ArchivingGuard {
+        let mut state = self.state.lock().unwrap();
+        while state.is_archiving || state.num_epoch_accounts_hash_calculations > 0 {
+            state = self.cvar.wait(state).unwrap();
+        }
+        state.is_archiving = true;
+        ArchivingGuard { coordinator: self }
+    }
+
+    /// Is an epoch accounts hash calculation running or pending?
+    #[must_use]
+    pub fn is_epoch_accounts_hash_calculation_in_progress(&self) -> bool {
+        self.state
+            .lock()
+            .unwrap()
+            .num_epoch_accounts_hash_calculations
+            > 0
+    }
+
+    /// Is a snapshot archive being created?
+    #[must_use]
+    pub fn is_archiving(&self) -> bool {
+        self.state.lock().unwrap().is_archiving
+    }
+}
+
+/// Running or pending epoch accounts hash calculations and archive creation
+#[derive(Debug, Default)]
+struct State {
+    num_epoch_accounts_hash_calculations: usize,
+    is_archiving: bool,
+}
+
+/// Marks an epoch accounts hash calculation as running until dropped
+#[derive(Debug)]
+pub struct EpochAccountsHashCalculationGuard<'a> {
+    coordinator: &'a ArchiveCoordinator,
+}
+
+impl Drop for EpochAccountsHashCalculationGuard<'_> {
+    fn drop(&mut self) {
+        let mut state = self.coordinator.state.lock().unwrap();
+        state.num_epoch_accounts_hash_calculations -= 1;
+        self.coordinator.cvar.notify_all();
+    }
+}
+
+/// Marks a snapshot archive as being created until dropped
+#[derive(Debug)]
+pub struct ArchivingGuard<'a> {
+    coordinator: &'a ArchiveCoordinator,
+}
+
+impl Drop for ArchivingGuard<'_> {
+    fn drop(&mut self) {
+        let mut state = self.coordinator.state.lock().unwrap();
+        state.is_archiving = false;
+        self.coordinator.cvar.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::{
+            sync::atomic::{AtomicBool, Ordering},
+            time::Duration,
+        },
+    };
+
+    #[test]
+    fn test_archiving_waits_for_epoch_accounts_hash_calculation() {
+        let coordinator = ArchiveCoordinator::new();
+        let is_archiving = AtomicBool::new(false);
+
+        let eah_guard = coordinator.begin_epoch_accounts_hash_calculation();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let _archiving_guard = coordinator.begin_archiving();
+                is_archiving.store(true, Ordering::Relaxed);
+            });
+            std::thread::sleep(Duration::from_millis(500));
+            assert!(!is_archiving.load(Ordering::Relaxed));
+            assert!(coordinator.is_epoch_accounts_hash_calculation_in_progress());
+            drop(eah_guard);
+        });
+        assert!(is_archiving.load(Ordering::Relaxed));
+        assert!(!coordinator.is_archiving());
+        assert!(!coordinator.is_epoch_accounts_hash_calculation_in_progress());
+    }
+
+    #[test]
+    fn test_epoch_accounts_hash_calculation_waits_for_archiving() {
+        let coordinator = ArchiveCoordinator::new();
+        let is_calculating = AtomicBool::new(false);
+
+        let archiving_guard = coordinator.begin_archiving();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let _eah_guard = coordinator.begin_epoch_accounts_hash_calculation();
+                is_calculating.store(true, Ordering::Relaxed);
+            });
+            std::thread::sleep(Duration::from_millis(500));
+            assert!(!is_calculating.load(Ordering::Relaxed));
+            // the pending calculation takes precedence over any new archives
+            assert!(coordinator.is_epoch_accounts_hash_calculation_in_progress());
+            drop(archiving_guard);
+        });
+        assert!(is_calculating.load(Ordering::Relaxed));
+    }
+}
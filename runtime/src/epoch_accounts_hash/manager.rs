@@ -1,6 +1,7 @@
 use {
     super::EpochAccountsHash,
-    solana_sdk::{clock::Slot, hash::Hash},
+    log::*,
+    solana_sdk::{clock::Slot, epoch_schedule::EpochSchedule, hash::Hash},
     std::sync::{Condvar, Mutex},
 };
 
@@ -14,6 +15,8 @@ pub struct Manager {
     state: Mutex<State>,
     /// This condition variable is used to wait for an in-flight EAH calculation to complete
     cvar: Condvar,
+    /// Requests made while a calculation was in flight; always locked after `state`
+    conflicting_requests: Mutex<ConflictingRequests>,
 }
 
 impl Manager {
@@ -22,6 +25,7 @@ impl Manager {
         Self {
             state: Mutex::new(state),
             cvar: Condvar::new(),
+            conflicting_requests: Mutex::default(),
         }
     }
 
@@ -46,9 +50,93 @@ impl Manager {
         *state = State::InFlight(slot);
     }
 
+    /// An epoch accounts hash calculation has been requested; update our state
+    ///
+    /// If another calculation is already in flight, `policy` decides the outcome of this request.
+    #[must_use]
+    pub fn try_set_in_flight(
+        &self,
+        slot: Slot,
+        policy: InFlightPolicy,
+        epoch_schedule: &EpochSchedule,
+    ) -> InFlightRequestOutcome {
+        let mut state = self.state.lock().unwrap();
+        let in_flight_slot = match *state {
+            State::InFlight(in_flight_slot) => in_flight_slot,
+            State::Invalid | State::Valid(..) => {
+                *state = State::InFlight(slot);
+                return InFlightRequestOutcome::Accepted;
+            }
+        };
+
+        let mut conflicting_requests = self.conflicting_requests.lock().unwrap();
+        let outcome = match policy {
+            InFlightPolicy::Reject => InFlightRequestOutcome::Rejected,
+            InFlightPolicy::Queue => {
+                if conflicting_requests.queued_slot.is_none() {
+                    conflicting_requests.queued_slot = Some(slot);
+                    InFlightRequestOutcome::Queued
+                } else {
+                    InFlightRequestOutcome::Rejected
+                }
+            }
+            InFlightPolicy::ReplaceIfNewerEpoch => {
+                if epoch_schedule.get_epoch(slot) > epoch_schedule.get_epoch(in_flight_slot) {
+                    conflicting_requests.replaced_slot = Some(in_flight_slot);
+                    *state = State::InFlight(slot);
+                    InFlightRequestOutcome::Accepted
+                } else {
+                    InFlightRequestOutcome::Rejected
+                }
+            }
+        };
+        warn!(
+            "An epoch accounts hash calculation was requested for slot {} while one is already \
+             in-flight from slot {}. policy: {:?}, outcome: {:?}",
+            slot, in_flight_slot, policy, outcome,
+        );
+        outcome
+    }
+
+    /// Put the queued epoch accounts hash calculation in flight, if the calculation it was queued
+    /// behind has completed (see `InFlightPolicy::Queue`)
+    ///
+    /// Returns the slot of the queued request if it is now in flight, and the calculation should
+    /// be performed.
+    #[must_use]
+    pub fn try_set_queued_in_flight(&self) -> Option<Slot> {
+        let mut state = self.state.lock().unwrap();
+        if let State::InFlight(_slot) = *state {
+            return None;
+        }
+        let queued_slot = self
+            .conflicting_requests
+            .lock()
+            .unwrap()
+            .queued_slot
+            .take()?;
+        *state = State::InFlight(queued_slot);
+        Some(queued_slot)
+    }
+
     /// An epoch accounts hash calculation has completed; update our state
+    ///
+    /// If the calculation was replaced (see `InFlightPolicy::ReplaceIfNewerEpoch`), its result is
+    /// ignored.
     pub fn set_valid(&self, epoch_accounts_hash: EpochAccountsHash, slot: Slot) {
         let mut state = self.state.lock().unwrap();
+        {
+            let mut conflicting_requests = self.conflicting_requests.lock().unwrap();
+            if conflicting_requests.replaced_slot == Some(slot) {
+                conflicting_requests.replaced_slot = None;
+                info!(
+                    "Ignoring the epoch accounts hash calculated for slot {}, since it was \
+                     replaced by a newer epoch's calculation",
+                    slot,
+                );
+                return;
+            }
+        }
         if let State::Valid(old_epoch_accounts_hash, old_slot) = &*state {
             panic!(
                 "The epoch accounts hash is already valid! \
@@ -85,6 +173,18 @@ impl Manager {
         }
     }
 
+    /// Get the status of the epoch accounts hash
+    ///
+    /// This fn does not block, even if an EAH calculation is in-flight
+    pub fn status(&self) -> Status {
+        let state = self.state.lock().unwrap();
+        match &*state {
+            State::Invalid => Status::Invalid,
+            State::InFlight(_slot) => Status::InFlight,
+            State::Valid(epoch_accounts_hash, _slot) => Status::Valid(*epoch_accounts_hash),
+        }
+    }
+
     /// **FOR TESTS ONLY**
     /// Set the state to Invalid
     /// This is needed by tests that do not fully startup all the accounts background services.
@@ -94,6 +194,67 @@ impl Manager {
     }
 }
 
+/// The status of the epoch accounts hash, as seen from outside the manager
+///
+/// Relative to the epoch, the status is `InFlight` from the "start" slot until the calculation
+/// completes, which is no later than the "stop" slot.  Afterwards, the status is `Valid` until the
+/// next epoch's "start" slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// No EAH calculation has been requested yet; see `State::Invalid`
+    Invalid,
+    /// An EAH calculation has been requested and has not completed yet
+    InFlight,
+    /// The EAH calculation has completed
+    Valid(EpochAccountsHash),
+}
+
+/// What to do when an epoch accounts hash calculation is requested while another is in flight
+///
+/// Only one calculation is requested per epoch, and the "stop" Bank waits for it to complete, so
+/// this should not happen.  If it does anyway (e.g. due to a bug), this defines the outcome.  No
+/// policy hands a Bank waiting for the in-flight calculation the result of another one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InFlightPolicy {
+    /// Reject the new request; the in-flight calculation continues
+    Reject,
+    /// Queue the new request; it is put in flight once the in-flight calculation has completed and
+    /// its result is valid, see `Manager::try_set_queued_in_flight()`.  At most one request is
+    /// queued, later ones are rejected.
+    Queue,
+    /// Accept the new request only if it is for a newer epoch than the in-flight calculation.  The
+    /// new calculation is in flight immediately, and the replaced calculation's result is ignored.
+    /// A Bank of the newer epoch is only rooted after the "stop" Bank of the replaced calculation's
+    /// epoch, so no Bank still waits for the replaced calculation.
+    ReplaceIfNewerEpoch,
+}
+
+impl Default for InFlightPolicy {
+    fn default() -> Self {
+        Self::Reject
+    }
+}
+
+/// The outcome of requesting an epoch accounts hash calculation, see `Manager::try_set_in_flight()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InFlightRequestOutcome {
+    /// The calculation is in flight, and should be performed
+    Accepted,
+    /// The request is queued behind the in-flight calculation, see `InFlightPolicy::Queue`
+    Queued,
+    /// The request is rejected, and the calculation should not be performed
+    Rejected,
+}
+
+/// Requests made while a calculation was in flight, see `InFlightPolicy`
+#[derive(Debug, Default)]
+struct ConflictingRequests {
+    /// The slot of the request waiting for the in-flight calculation to complete
+    queued_slot: Option<Slot>,
+    /// The slot of the in-flight calculation that was replaced by a newer request
+    replaced_slot: Option<Slot>,
+}
+
 /// The EpochAccountsHash is calculated in the background via AccountsBackgroundService.  This enum
 /// is used to track the state of that calculation, and queried when saving the EAH into a Bank.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -144,6 +305,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_status() {
+        let epoch_accounts_hash = EpochAccountsHash::new(Hash::new_unique());
+        let manager = Manager::new_invalid();
+        assert_eq!(manager.status(), Status::Invalid);
+        manager.set_in_flight(123);
+        assert_eq!(manager.status(), Status::InFlight);
+        manager.set_valid(epoch_accounts_hash, 123);
+        assert_eq!(manager.status(), Status::Valid(epoch_accounts_hash));
+    }
+
     #[test]
     fn test_try_get_epoch_accounts_hash() {
         let epoch_accounts_hash = EpochAccountsHash::new(Hash::new_unique());
@@ -161,6 +333,91 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_try_set_in_flight() {
+        let epoch_schedule = EpochSchedule::custom(100, 100, false);
+        let epoch_accounts_hash = EpochAccountsHash::new(Hash::new_unique());
+        let conflicting_epoch_accounts_hash = EpochAccountsHash::new(Hash::new_unique());
+        let new_in_flight_manager = || {
+            let manager = Manager::new_invalid();
+            assert_eq!(
+                manager.try_set_in_flight(25, InFlightPolicy::default(), &epoch_schedule),
+                InFlightRequestOutcome::Accepted,
+            );
+            manager
+        };
+
+        // Reject: the in-flight calculation is unaffected
+        {
+            let manager = new_in_flight_manager();
+            assert_eq!(
+                manager.try_set_in_flight(125, InFlightPolicy::Reject, &epoch_schedule),
+                InFlightRequestOutcome::Rejected,
+            );
+            assert_eq!(*manager.state.lock().unwrap(), State::InFlight(25));
+            manager.set_valid(epoch_accounts_hash, 25);
+            assert_eq!(
+                manager.try_get_epoch_accounts_hash(),
+                Some(epoch_accounts_hash),
+            );
+            assert!(manager.try_set_queued_in_flight().is_none());
+        }
+
+        // Queue: the queued request is put in flight only after the in-flight calculation has
+        // completed, and the completed calculation's result is valid in the meantime
+        {
+            let manager = new_in_flight_manager();
+            assert_eq!(
+                manager.try_set_in_flight(125, InFlightPolicy::Queue, &epoch_schedule),
+                InFlightRequestOutcome::Queued,
+            );
+            assert_eq!(
+                manager.try_set_in_flight(225, InFlightPolicy::Queue, &epoch_schedule),
+                InFlightRequestOutcome::Rejected,
+            );
+            assert_eq!(*manager.state.lock().unwrap(), State::InFlight(25));
+            assert!(manager.try_set_queued_in_flight().is_none());
+            manager.set_valid(epoch_accounts_hash, 25);
+            assert_eq!(manager.wait_get_epoch_accounts_hash(), epoch_accounts_hash,);
+            assert_eq!(manager.try_set_queued_in_flight(), Some(125));
+            assert_eq!(*manager.state.lock().unwrap(), State::InFlight(125));
+            assert!(manager.try_set_queued_in_flight().is_none());
+            manager.set_valid(conflicting_epoch_accounts_hash, 125);
+            assert_eq!(
+                manager.try_get_epoch_accounts_hash(),
+                Some(conflicting_epoch_accounts_hash),
+            );
+        }
+
+        // ReplaceIfNewerEpoch: only a newer epoch replaces the in-flight calculation, and the
+        // replaced calculation's result is ignored
+        {
+            let manager = new_in_flight_manager();
+            assert_eq!(
+                manager.try_set_in_flight(75, InFlightPolicy::ReplaceIfNewerEpoch, &epoch_schedule),
+                InFlightRequestOutcome::Rejected,
+            );
+            assert_eq!(*manager.state.lock().unwrap(), State::InFlight(25));
+            assert_eq!(
+                manager.try_set_in_flight(
+                    125,
+                    InFlightPolicy::ReplaceIfNewerEpoch,
+                    &epoch_schedule
+                ),
+                InFlightRequestOutcome::Accepted,
+            );
+            assert_eq!(*manager.state.lock().unwrap(), State::InFlight(125));
+            manager.set_valid(epoch_accounts_hash, 25);
+            assert_eq!(*manager.state.lock().unwrap(), State::InFlight(125));
+            assert!(manager.try_get_epoch_accounts_hash().is_none());
+            manager.set_valid(conflicting_epoch_accounts_hash, 125);
+            assert_eq!(
+                manager.try_get_epoch_accounts_hash(),
+                Some(conflicting_epoch_accounts_hash),
+            );
+        }
+    }
+
     #[test]
     fn test_wait_epoch_accounts_hash() {
         // Test: State is Invalid, no need to wait
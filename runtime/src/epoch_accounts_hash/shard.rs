@@ -0,0 +1,310 @@
+//! Calculate the Epoch Accounts Hash piecewise, over shards of the pubkey keyspace
+//!
+//! The accounts hash is a merkle tree over the hashes of *all* accounts, sorted by pubkey.  The
+//! tree's interior nodes straddle arbitrary account boundaries, so the root of one shard cannot be
+//! combined with the roots of the other shards.  Instead, each shard produces its (much smaller
+//! than the accounts themselves) ordered account hashes, and combining the shards builds the
+//! merkle tree once over the concatenation.  Loading and hashing the accounts, which is the
+//! expensive part, is what gets distributed.
+
+use {
+    super::EpochAccountsHash,
+    crate::{
+        accounts_db::{AccountsDb, BankHashVerificationError, PUBKEY_BINS_FOR_CALCULATING_HASHES},
+        accounts_hash::{AccountsHash, CalcAccountsHashConfig},
+        bank::Bank,
+        sorted_storages::SortedStorages,
+    },
+    solana_sdk::{hash::Hash, pubkey::Pubkey},
+    thiserror::Error,
+};
+
+/// The number of leading bits of a pubkey used to assign it to a shard
+const SHARD_PREFIX_BITS: u32 = 24;
+
+/// One of `num_shards` equally sized, contiguous slices of the pubkey keyspace
+///
+/// Shards are ordered by `index`: every pubkey in shard `i` sorts before every pubkey in shard
+/// `i + 1`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct KeyspaceShard {
+    index: usize,
+    num_shards: usize,
+}
+
+impl KeyspaceShard {
+    /// Make shard `index` of `num_shards`
+    ///
+    /// Panics if `num_shards` is zero or larger than 2^24, or if `index` is out of bounds.
+    #[must_use]
+    pub fn new(index: usize, num_shards: usize) -> Self {
+        assert!(num_shards > 0 && num_shards <= 1 << SHARD_PREFIX_BITS);
+        assert!(index < num_shards);
+        Self { index, num_shards }
+    }
+
+    /// Make all `num_shards` shards, in order
+    pub fn all(num_shards: usize) -> impl Iterator<Item = Self> {
+        (0..num_shards).map(move |index| Self::new(index, num_shards))
+    }
+
+    #[must_use]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    #[must_use]
+    pub fn num_shards(&self) -> usize {
+        self.num_shards
+    }
+
+    /// Is `pubkey` within this shard?
+    #[must_use]
+    pub fn contains(&self, pubkey: &Pubkey) -> bool {
+        Self::shard_index(pubkey, self.num_shards) == self.index
+    }
+
+    fn shard_index(pubkey: &Pubkey, num_shards: usize) -> usize {
+        let bytes = pubkey.as_ref();
+        let prefix = (bytes[0] as u64) << 16 | (bytes[1] as u64) << 8 | bytes[2] as u64;
+        ((prefix * num_shards as u64) >> SHARD_PREFIX_BITS) as usize
+    }
+}
+
+/// The result of calculating the accounts hash over a single shard
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShardAccountsHash {
+    pub shard: KeyspaceShard,
+    /// The hashes of the accounts in this shard, sorted by pubkey
+    pub account_hashes: Vec<Hash>,
+    /// The total lamports of the accounts in this shard
+    pub lamports: u64,
+}
+
+/// Errors that can occur when combining shards, see `combine_shards()`
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum CombineShardsError {
+    #[error("no shards to combine")]
+    Empty,
+
+    #[error("shards disagree on the number of shards: {0} vs {1}")]
+    MismatchedNumShards(usize, usize),
+
+    #[error("shard {0} is missing")]
+    MissingShard(usize),
+
+    #[error("shard {0} is duplicated")]
+    DuplicateShard(usize),
+}
+
+/// Calculate the accounts hash leaves of `bank` for only the accounts within `shard`
+///
+/// `bank` should be the bank at the EAH "start" slot, so that combining all the shards yields the
+/// epoch accounts hash.
+pub fn calculate_shard(
+    bank: &Bank,
+    shard: KeyspaceShard,
+) -> Result<ShardAccountsHash, BankHashVerificationError> {
+    let (account_hashes, lamports) = bank
+        .rc
+        .accounts
+        .accounts_db
+        .calculate_account_hashes_from_index_filtered(
+            bank.slot(),
+            &CalcAccountsHashConfig {
+                use_bg_thread_pool: false,
+                check_hash: false,
+                ancestors: Some(&bank.ancestors),
+                epoch_schedule: bank.epoch_schedule(),
+                rent_collector: bank.rent_collector(),
+                store_detailed_debug_info_on_failure: false,
+                full_snapshot: None,
+                ..CalcAccountsHashConfig::default()
+            },
+            |pubkey| shard.contains(pubkey),
+        )?;
+    Ok(ShardAccountsHash {
+        shard,
+        account_hashes,
+        lamports,
+    })
+}
+
+/// Calculate the accounts hash leaves from `storages` for only the accounts within `shard`
+///
+/// This is the counterpart to `calculate_shard()` for once the bank at the EAH "start" slot is gone
+/// and later slots have been rooted; `storages` should be the snapshot storages as of the "start"
+/// slot.  The shards must line up with the accounts hash calculation's pubkey bins, so
+/// `shard.num_shards()` must be a power of two, no larger than `PUBKEY_BINS_FOR_CALCULATING_HASHES`.
+pub fn calculate_shard_from_storages(
+    accounts_db: &AccountsDb,
+    storages: &SortedStorages<'_>,
+    config: &CalcAccountsHashConfig<'_>,
+    shard: KeyspaceShard,
+) -> Result<ShardAccountsHash, BankHashVerificationError> {
+    let num_shards = shard.num_shards();
+    assert!(num_shards.is_power_of_two() && num_shards <= PUBKEY_BINS_FOR_CALCULATING_HASHES);
+    let bins_per_shard = PUBKEY_BINS_FOR_CALCULATING_HASHES / num_shards;
+    let bin_range = shard.index() * bins_per_shard..(shard.index() + 1) * bins_per_shard;
+    let (account_hashes, lamports) =
+        accounts_db.calculate_account_hashes_from_storages_in_bins(config, storages, &bin_range)?;
+    Ok(ShardAccountsHash {
+        shard,
+        account_hashes,
+        lamports,
+    })
+}
+
+/// Combine the results of every shard of the keyspace into the epoch accounts hash
+///
+/// The shards may be given in any order, but all of them must be present exactly once.
+pub fn combine_shards(
+    mut shards: Vec<ShardAccountsHash>,
+) -> Result<EpochAccountsHash, CombineShardsError> {
+    let num_shards = shards
+        .first()
+        .ok_or(CombineShardsError::Empty)?
+        .shard
+        .num_shards();
+    if let Some(shard) = shards
+        .iter()
+        .find(|shard| shard.shard.num_shards() != num_shards)
+    {
+        return Err(CombineShardsError::MismatchedNumShards(
+            num_shards,
+            shard.shard.num_shards(),
+        ));
+    }
+
+    shards.sort_unstable_by_key(|shard| shard.shard.index());
+    if let Some(window) = shards
+        .windows(2)
+        .find(|window| window[0].shard == window[1].shard)
+    {
+        return Err(CombineShardsError::DuplicateShard(window[0].shard.index()));
+    }
+    if let Some(index) = (0..num_shards).find(|index| {
+        shards
+            .get(*index)
+            .map_or(true, |shard| shard.shard.index() != *index)
+    }) {
+        return Err(CombineShardsError::MissingShard(index));
+    }
+
+    let hashes = shards
+        .into_iter()
+        .map(|shard| shard.account_hashes)
+        .collect();
+    let (accounts_hash, _hash_total) = AccountsHash::calculate_hash(hashes);
+    Ok(EpochAccountsHash::new(accounts_hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_sdk::{account::AccountSharedData, genesis_config::create_genesis_config},
+    };
+
+    #[test]
+    fn test_keyspace_shard_contains() {
+        let pubkeys = [
+            Pubkey::new_from_array([0x00; 32]),
+            Pubkey::new_from_array([0x7f; 32]),
+            Pubkey::new_from_array([0x80; 32]),
+            Pubkey::new_from_array([0xff; 32]),
+        ];
+        for num_shards in [1, 2, 3, 16] {
+            for pubkey in &pubkeys {
+                let containing = KeyspaceShard::all(num_shards)
+                    .filter(|shard| shard.contains(pubkey))
+                    .count();
+                assert_eq!(containing, 1);
+            }
+        }
+
+        let first = KeyspaceShard::new(0, 2);
+        let second = KeyspaceShard::new(1, 2);
+        assert!(first.contains(&pubkeys[0]) && first.contains(&pubkeys[1]));
+        assert!(second.contains(&pubkeys[2]) && second.contains(&pubkeys[3]));
+    }
+
+    #[test]
+    fn test_calculate_shard_combine() {
+        let (genesis_config, _mint_keypair) = create_genesis_config(1_000_000);
+        let bank = Bank::new_for_tests(&genesis_config);
+        for lamports in 1..100 {
+            let account = AccountSharedData::new(lamports, 0, &Pubkey::default());
+            bank.store_account(&solana_sdk::pubkey::new_rand(), &account);
+        }
+        bank.freeze();
+        let expected = bank.update_accounts_hash();
+
+        let shards: Vec<_> = KeyspaceShard::all(2)
+            .map(|shard| calculate_shard(&bank, shard).unwrap())
+            .collect();
+        assert!(shards.iter().all(|shard| !shard.account_hashes.is_empty()));
+        assert_eq!(
+            shards.iter().map(|shard| shard.lamports).sum::<u64>(),
+            bank.capitalization(),
+        );
+
+        // the order of the shards does not matter, but all of them must be there, once
+        let reversed: Vec<_> = shards.iter().rev().cloned().collect();
+        assert_eq!(
+            combine_shards(reversed).unwrap(),
+            EpochAccountsHash::new(expected)
+        );
+        assert_eq!(
+            combine_shards(vec![shards[0].clone()]),
+            Err(CombineShardsError::MissingShard(1)),
+        );
+        assert_eq!(
+            combine_shards(vec![shards[1].clone(), shards[1].clone()]),
+            Err(CombineShardsError::DuplicateShard(1)),
+        );
+        assert_eq!(combine_shards(vec![]), Err(CombineShardsError::Empty));
+    }
+
+    #[test]
+    fn test_calculate_shard_from_storages() {
+        let (genesis_config, _mint_keypair) = create_genesis_config(1_000_000);
+        let bank = Bank::new_for_tests(&genesis_config);
+        for lamports in 1..100 {
+            let account = AccountSharedData::new(lamports, 0, &Pubkey::default());
+            bank.store_account(&solana_sdk::pubkey::new_rand(), &account);
+        }
+        bank.freeze();
+        bank.squash();
+        bank.force_flush_accounts_cache();
+        let expected = bank.update_accounts_hash();
+
+        let snapshot_storages = bank.get_snapshot_storages(None);
+        let storages = SortedStorages::new(&snapshot_storages);
+        let config = CalcAccountsHashConfig {
+            use_bg_thread_pool: false,
+            check_hash: false,
+            ancestors: None,
+            epoch_schedule: bank.epoch_schedule(),
+            rent_collector: bank.rent_collector(),
+            store_detailed_debug_info_on_failure: false,
+            full_snapshot: None,
+            ..CalcAccountsHashConfig::default()
+        };
+        let accounts_db = &bank.rc.accounts.accounts_db;
+        let shards: Vec<_> = KeyspaceShard::all(4)
+            .map(|shard| {
+                calculate_shard_from_storages(accounts_db, &storages, &config, shard).unwrap()
+            })
+            .collect();
+
+        // the same leaves as calculating the shards from the index
+        for shard in &shards {
+            assert_eq!(*shard, calculate_shard(&bank, shard.shard).unwrap());
+        }
+        assert_eq!(
+            combine_shards(shards).unwrap(),
+            EpochAccountsHash::new(expected)
+        );
+    }
+}
@@ -2,9 +2,17 @@
 
 use {
     crate::bank::Bank,
-    solana_sdk::clock::{Epoch, Slot},
+    solana_sdk::{
+        clock::{Epoch, Slot},
+        epoch_schedule::EpochSchedule,
+    },
+    std::time::{Duration, Instant},
 };
 
+/// The accounts hash calculation throughput (in accounts per second) assumed until a calculation
+/// has completed and the actual throughput is known
+const DEFAULT_CALCULATION_ACCOUNTS_PER_SECOND: f64 = 1_000_000.0;
+
 /// Calculation of the EAH occurs once per epoch.  All nodes in the cluster must agree on which
 /// slot the EAH is based on.  This slot will be at an offset into the epoch, and referred to as
 /// the "start" slot for the EAH calculation.
@@ -38,10 +46,46 @@ pub fn calculation_stop(bank: &Bank) -> Slot {
     calculation_info(bank).calculation_stop
 }
 
+/// Estimate how long the EAH calculation will take, if it were to start now
+///
+/// This is based on the number of accounts the calculation will scan, and the running average
+/// throughput of recent accounts hash calculations.  Compare it to the time until the "stop" slot
+/// to judge whether the calculation will complete in time.
+#[must_use]
+pub fn estimate_calculation_duration(bank: &Bank) -> Duration {
+    let accounts_db = &bank.rc.accounts.accounts_db;
+    let num_accounts = accounts_db.approx_num_accounts_to_hash();
+    let accounts_per_second = accounts_db
+        .accounts_hash_throughput
+        .get()
+        .unwrap_or(DEFAULT_CALCULATION_ACCOUNTS_PER_SECOND);
+    Duration::from_secs_f64(num_accounts as f64 / accounts_per_second)
+}
+
+/// Get the time by which the EAH calculation must complete: when the "stop" slot is expected to
+/// be reached, assuming `bank` is the current bank and slots take their nominal duration
+///
+/// The accounts hash verifier reports EAH calculations that are projected to not complete by this
+/// deadline, see `DeadlineOverrunAction`; they are not aborted, since every node must calculate the
+/// EAH.  Returns None if the deadline is too far in the future to be represented.
+#[must_use]
+pub fn calculation_deadline(bank: &Bank) -> Option<Instant> {
+    let remaining_slots = calculation_stop(bank).saturating_sub(bank.slot());
+    let remaining_nanos = bank.ns_per_slot.saturating_mul(remaining_slots as u128);
+    let remaining = Duration::from_nanos(u64::try_from(remaining_nanos).ok()?);
+    Instant::now().checked_add(remaining)
+}
+
 /// For the epoch that `bank` is in, get all the EAH calculation information
 pub fn calculation_info(bank: &Bank) -> CalculationInfo {
-    let epoch = bank.epoch();
-    let epoch_schedule = bank.epoch_schedule();
+    calculation_info_for_slot(bank.epoch_schedule(), bank.slot())
+}
+
+/// For the epoch that `slot` is in, get all the EAH calculation information
+///
+/// Use this where there is no bank, only the epoch schedule, e.g. when handling accounts packages.
+pub fn calculation_info_for_slot(epoch_schedule: &EpochSchedule, slot: Slot) -> CalculationInfo {
+    let epoch = epoch_schedule.get_epoch(slot);
 
     let slots_per_epoch = epoch_schedule.get_slots_in_epoch(epoch);
     let calculation_offset_start = slots_per_epoch / 4;
@@ -100,7 +144,10 @@ pub struct CalculationInfo {
 mod tests {
     use {
         super::*,
-        solana_sdk::{epoch_schedule::EpochSchedule, genesis_config::GenesisConfig},
+        solana_sdk::{
+            account::AccountSharedData, epoch_schedule::EpochSchedule,
+            genesis_config::GenesisConfig, pubkey::Pubkey,
+        },
     };
 
     #[test]
@@ -119,6 +166,45 @@ mod tests {
         assert!(start < stop);
     }
 
+    #[test]
+    fn test_estimate_calculation_duration() {
+        let bank = Bank::default_for_tests();
+        bank.rc
+            .accounts
+            .accounts_db
+            .accounts_hash_throughput
+            .update(10_000, Duration::from_secs(1));
+        let store_accounts = |num_accounts| {
+            for _ in 0..num_accounts {
+                let account = AccountSharedData::new(1, 0, &Pubkey::default());
+                bank.store_account(&Pubkey::new_unique(), &account);
+            }
+        };
+
+        store_accounts(1_000);
+        let estimate = estimate_calculation_duration(&bank);
+        assert!(estimate > Duration::ZERO);
+
+        // twice the new accounts, roughly twice as long (the genesis accounts are counted too)
+        store_accounts(1_000);
+        let estimate2 = estimate_calculation_duration(&bank);
+        assert!(estimate2 > estimate.mul_f64(1.5));
+        assert!(estimate2 < estimate.mul_f64(2.0));
+    }
+
+    #[test]
+    fn test_calculation_deadline() {
+        let bank = Bank::new_for_tests(&GenesisConfig::default());
+        let before = Instant::now();
+        let deadline = calculation_deadline(&bank).unwrap();
+        let slots_until_stop = calculation_stop(&bank) - bank.slot();
+        let time_until_stop =
+            Duration::from_nanos((bank.ns_per_slot * slots_until_stop as u128) as u64);
+        assert!(deadline > before);
+        assert!(deadline >= before + time_until_stop);
+        assert!(deadline <= Instant::now() + time_until_stop);
+    }
+
     #[test]
     fn test_calculation_info() {
         for slots_per_epoch in [32, 100, 65_536, 432_000, 123_456_789] {
@@ -1,7 +1,6 @@
 use {
     bzip2::bufread::BzDecoder,
     log::*,
-    rand::{thread_rng, Rng},
     solana_sdk::genesis_config::{GenesisConfig, DEFAULT_GENESIS_ARCHIVE, DEFAULT_GENESIS_FILE},
     std::{
         collections::HashMap,
@@ -377,8 +376,10 @@ where
                     None => {}
                 };
                 if let ["accounts", file] = parts {
-                    // Randomly distribute the accounts files about the available `account_paths`,
-                    let path_index = thread_rng().gen_range(0, account_paths.len());
+                    // Round-robin the accounts files across the available `account_paths`.  This
+                    // goes by the entry index, so if the number of parallel divisions is a
+                    // multiple of the number of paths, each division only writes to one path.
+                    let path_index = (i - 1) % account_paths.len();
                     match account_paths
                         .get(path_index)
                         .map(|path_buf| path_buf.as_path())
@@ -809,6 +810,55 @@ mod tests {
         assert_matches!(result, Ok(()));
     }
 
+    #[test]
+    fn test_archive_unpack_snapshot_round_robin_account_paths() {
+        let mut archive = Builder::new(Vec::new());
+        for file in ["1.0", "1.1", "1.2", "1.3"] {
+            let mut header = Header::new_gnu();
+            header.set_path(Path::new("accounts").join(file)).unwrap();
+            header.set_size(4);
+            header.set_cksum();
+            let data: &[u8] = &[1, 2, 3, 4];
+            archive.append(&header, data).unwrap();
+        }
+        let data = archive.into_inner().unwrap();
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let account_paths = vec![temp_dir.path().join("a"), temp_dir.path().join("b")];
+        let unpack = |parallel_selector| {
+            let mut archive = Archive::new(BufReader::new(&data[..]));
+            unpack_snapshot(
+                &mut archive,
+                temp_dir.path(),
+                &account_paths,
+                parallel_selector,
+            )
+            .unwrap()
+        };
+
+        // the files alternate between the account paths
+        let unpacked_append_vec_map = unpack(None);
+        for (file, path_index) in [("1.0", 0), ("1.1", 1), ("1.2", 0), ("1.3", 1)] {
+            assert_eq!(
+                unpacked_append_vec_map[file],
+                account_paths[path_index].join("accounts").join(file)
+            );
+            assert!(unpacked_append_vec_map[file].is_file());
+        }
+
+        // with as many parallel divisions as account paths, each division writes to one path
+        for index in 0..account_paths.len() {
+            let unpacked_append_vec_map = unpack(Some(ParallelSelector {
+                index,
+                divisions: account_paths.len(),
+            }));
+            assert_eq!(unpacked_append_vec_map.len(), 2);
+            assert!(unpacked_append_vec_map
+                .values()
+                .all(|path| path.starts_with(&account_paths[index])));
+        }
+    }
+
     #[test]
     fn test_archive_unpack_genesis_ok() {
         let mut header = Header::new_gnu();
@@ -4,7 +4,17 @@ use solana_program_runtime::compute_budget::ComputeBudget;
 #[derive(AbiExample, Debug, Default, Clone)]
 pub struct RuntimeConfig {
     pub bpf_jit: bool,
+    /// If set, used as every transaction's compute budget instead of the one derived from the
+    /// transaction's compute budget instructions, e.g. to replay blocks under a hypothetical
+    /// budget.  The override itself is excluded from consensus-relevant hashing: like the rest
+    /// of `RuntimeConfig`, it is neither hashed by `Bank::hash_internal_state()` nor serialized
+    /// into snapshots.  It can still change the outcome of executing a transaction, and so the
+    /// accounts it writes and the bank hash; it must not be set on a validator that votes or
+    /// produces blocks.
     pub compute_budget: Option<ComputeBudget>,
     pub log_messages_bytes_limit: Option<usize>,
     pub transaction_account_lock_limit: Option<usize>,
+    /// Calculate the accounts hash and verify the bank hash as part of `Bank::freeze()`, instead
+    /// of leaving the accounts hash to be calculated later in the background
+    pub verify_hash_on_freeze: bool,
 }
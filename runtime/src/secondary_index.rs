@@ -19,7 +19,9 @@ use {
 pub type SecondaryReverseIndexEntry = RwLock<Vec<Pubkey>>;
 
 pub trait SecondaryIndexEntry: Debug {
-    fn insert_if_not_exists(&self, key: &Pubkey, inner_keys_count: &AtomicU64);
+    // Inserts a value into the set, if it is not already present. Returns whether the value was
+    // inserted.
+    fn insert_if_not_exists(&self, key: &Pubkey, inner_keys_count: &AtomicU64) -> bool;
     // Removes a value from the set. Returns whether the value was present in the set.
     fn remove_inner_key(&self, key: &Pubkey) -> bool;
     fn is_empty(&self) -> bool;
@@ -31,6 +33,19 @@ pub trait SecondaryIndexEntry: Debug {
 pub struct SecondaryIndexStats {
     last_report: AtomicInterval,
     num_inner_keys: AtomicU64,
+    largest_entry_len: AtomicU64,
+}
+
+/// The size of a secondary index, see `SecondaryIndex::size_stats()`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SecondaryIndexSizeStats {
+    /// The number of index keys (e.g. program ids), each with its own entry of inner keys
+    pub num_keys: usize,
+    /// The number of inner keys (i.e. account pubkeys) across all entries
+    pub num_inner_keys: u64,
+    /// The most inner keys that any one entry has held.  This is a high-water mark, so it is not
+    /// lowered when inner keys are removed.
+    pub largest_entry_len: u64,
 }
 
 #[derive(Debug, Default)]
@@ -39,12 +54,15 @@ pub struct DashMapSecondaryIndexEntry {
 }
 
 impl SecondaryIndexEntry for DashMapSecondaryIndexEntry {
-    fn insert_if_not_exists(&self, key: &Pubkey, inner_keys_count: &AtomicU64) {
+    fn insert_if_not_exists(&self, key: &Pubkey, inner_keys_count: &AtomicU64) -> bool {
+        let mut inserted = false;
         if self.account_keys.get(key).is_none() {
             self.account_keys.entry(*key).or_insert_with(|| {
                 inner_keys_count.fetch_add(1, Ordering::Relaxed);
+                inserted = true;
             });
         }
+        inserted
     }
 
     fn remove_inner_key(&self, key: &Pubkey) -> bool {
@@ -73,13 +91,14 @@ pub struct RwLockSecondaryIndexEntry {
 }
 
 impl SecondaryIndexEntry for RwLockSecondaryIndexEntry {
-    fn insert_if_not_exists(&self, key: &Pubkey, inner_keys_count: &AtomicU64) {
+    fn insert_if_not_exists(&self, key: &Pubkey, inner_keys_count: &AtomicU64) -> bool {
         let exists = self.account_keys.read().unwrap().contains(key);
         if !exists {
             let mut w_account_keys = self.account_keys.write().unwrap();
             w_account_keys.insert(*key);
             inner_keys_count.fetch_add(1, Ordering::Relaxed);
         };
+        !exists
     }
 
     fn remove_inner_key(&self, key: &Pubkey) -> bool {
@@ -125,7 +144,11 @@ impl<SecondaryIndexEntryType: SecondaryIndexEntry + Default + Sync + Send>
                 .get(key)
                 .unwrap_or_else(|| self.index.entry(*key).or_default().downgrade());
 
-            pubkeys_map.insert_if_not_exists(inner_key, &self.stats.num_inner_keys);
+            if pubkeys_map.insert_if_not_exists(inner_key, &self.stats.num_inner_keys) {
+                self.stats
+                    .largest_entry_len
+                    .fetch_max(pubkeys_map.len() as u64, Ordering::Relaxed);
+            }
         }
 
         {
@@ -159,6 +182,11 @@ impl<SecondaryIndexEntryType: SecondaryIndexEntry + Default + Sync + Send>
                     self.reverse_index.len() as i64,
                     i64
                 ),
+                (
+                    "largest_entry_len",
+                    self.stats.largest_entry_len.load(Ordering::Relaxed) as i64,
+                    i64
+                ),
             );
         }
     }
@@ -223,6 +251,15 @@ impl<SecondaryIndexEntryType: SecondaryIndexEntry + Default + Sync + Send>
         }
     }
 
+    /// Returns the size of this index from running counters, without iterating over its entries
+    pub fn size_stats(&self) -> SecondaryIndexSizeStats {
+        SecondaryIndexSizeStats {
+            num_keys: self.index.len(),
+            num_inner_keys: self.stats.num_inner_keys.load(Ordering::Relaxed),
+            largest_entry_len: self.stats.largest_entry_len.load(Ordering::Relaxed),
+        }
+    }
+
     /// log top 20 (owner, # accounts) in descending order of # accounts
     pub fn log_contents(&self) {
         let mut entries = self
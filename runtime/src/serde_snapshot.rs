@@ -53,7 +53,7 @@ mod storage;
 mod tests;
 mod utils;
 
-pub(crate) use storage::SerializedAppendVecId;
+pub(crate) use storage::{SerializableAccountStorageEntry, SerializedAppendVecId};
 // a number of test cases in accounts_db use this
 #[cfg(test)]
 pub(crate) use tests::reconstruct_accounts_db_via_serialization;
@@ -139,6 +139,18 @@ impl<T> SnapshotAccountsDbFields<T> {
     }
 }
 
+impl<T> AccountsDbFields<T> {
+    /// The number of account storages referenced by the snapshot
+    pub(crate) fn storage_count(&self) -> usize {
+        self.0.values().map(Vec::len).sum()
+    }
+
+    /// The bank hash info for the snapshot's slot
+    pub(crate) fn bank_hash_info(&self) -> &BankHashInfo {
+        &self.3
+    }
+}
+
 trait TypeContext<'a>: PartialEq {
     type SerializableAccountStorageEntry: Serialize
         + DeserializeOwned
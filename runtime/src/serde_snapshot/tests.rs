@@ -594,6 +594,7 @@ fn test_extra_fields_full_snapshot_archive() {
         ArchiveFormat::TarBzip2,
         1,
         0,
+        None,
     )
     .unwrap();
 
@@ -617,6 +618,7 @@ fn test_extra_fields_full_snapshot_archive() {
         Some(crate::accounts_db::ACCOUNTS_DB_CONFIG_FOR_TESTING),
         None,
         &Arc::default(),
+        snapshot_utils::SnapshotLoadOptions::default(),
     )
     .unwrap();
 
@@ -55,6 +55,24 @@ impl SharedBuffer {
     pub fn new<T: 'static + Read + std::marker::Send>(reader: T) -> Self {
         Self::new_with_sizes(TOTAL_BUFFER_BUDGET_DEFAULT, CHUNK_SIZE_DEFAULT, reader)
     }
+    /// Like `new()`, but read ahead at most `total_buffer_budget` bytes
+    ///
+    /// The budget is split into the same number of chunks as the default, so a smaller budget
+    /// reads ahead in smaller chunks instead of in fewer of them.
+    pub fn new_with_budget<T: 'static + Read + std::marker::Send>(
+        total_buffer_budget: usize,
+        reader: T,
+    ) -> Self {
+        Self::new_with_sizes(
+            total_buffer_budget,
+            Self::chunk_size_for_budget(total_buffer_budget),
+            reader,
+        )
+    }
+    fn chunk_size_for_budget(total_buffer_budget: usize) -> usize {
+        (total_buffer_budget / (TOTAL_BUFFER_BUDGET_DEFAULT / CHUNK_SIZE_DEFAULT))
+            .clamp(1, CHUNK_SIZE_DEFAULT)
+    }
     fn new_with_sizes<T: 'static + Read + std::marker::Send>(
         total_buffer_budget: usize,
         chunk_size: usize,
@@ -771,6 +789,29 @@ pub mod tests {
         assert_eq!(sent, data);
     }
 
+    #[test]
+    fn test_shared_buffer_new_with_budget() {
+        assert_eq!(
+            SharedBuffer::chunk_size_for_budget(TOTAL_BUFFER_BUDGET_DEFAULT),
+            CHUNK_SIZE_DEFAULT
+        );
+        for budget_sz in [1, 19, 20, 21, 1_000, TOTAL_BUFFER_BUDGET_DEFAULT * 2] {
+            let chunk_sz = SharedBuffer::chunk_size_for_budget(budget_sz);
+            assert!(adjusted_buffer_size(budget_sz, chunk_sz) <= budget_sz);
+        }
+
+        let done_signal = vec![];
+        let (sender, receiver) = unbounded();
+        let file = SimpleReader::new(receiver);
+        let shared_buffer = SharedBuffer::new_with_budget(100, file);
+        let mut reader = SharedBufferReader::new(&shared_buffer);
+        let size = 10_000;
+        let sent = (0..size).map(|i| (i % 256) as u8).collect::<Vec<_>>();
+        let _ = sender.send((sent.clone(), None));
+        let _ = sender.send((done_signal, None));
+        assert_eq!(sent, test_read_all(&mut reader, None));
+    }
+
     fn adjusted_buffer_size(total_buffer_budget: usize, chunk_size: usize) -> usize {
         let num_buffers = SharedBufferBgReader::num_buffers(total_buffer_budget, chunk_size);
         num_buffers * chunk_size
@@ -52,6 +52,19 @@ pub struct SnapshotArchiveInfo {
     pub archive_format: ArchiveFormat,
 }
 
+impl PartialOrd for SnapshotArchiveInfo {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Order `SnapshotArchiveInfo` by slot (ascending), then by path so that the order is total
+impl Ord for SnapshotArchiveInfo {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.slot, &self.path).cmp(&(other.slot, &other.path))
+    }
+}
+
 /// Information about a full snapshot archive: its path, slot, hash, and archive format
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub struct FullSnapshotArchiveInfo(SnapshotArchiveInfo);
@@ -1,7 +1,13 @@
 use {
-    crate::snapshot_utils::{self, ArchiveFormat, SnapshotVersion},
+    crate::{
+        epoch_accounts_hash::EpochAccountsHashArchiveCoordinator,
+        snapshot_package::PendingPackagePolicy,
+        snapshot_utils::{
+            self, ArchiveFormat, DuplicateIncrementalSnapshotArchivePolicy, SnapshotVersion,
+        },
+    },
     solana_sdk::clock::Slot,
-    std::path::PathBuf,
+    std::{path::PathBuf, sync::Arc},
 };
 
 /// Snapshot configuration and runtime information
@@ -43,6 +49,51 @@ pub struct SnapshotConfig {
 
     // Thread niceness adjustment for snapshot packager service
     pub packager_thread_niceness_adj: i8,
+
+    /// If set, epoch accounts hash calculations and snapshot archive creation are serialized
+    /// (with the EAH taking precedence) instead of running concurrently.  The coordinator is
+    /// shared by all clones of this config.
+    pub epoch_accounts_hash_archive_coordinator: Option<Arc<EpochAccountsHashArchiveCoordinator>>,
+
+    /// What to do with a new snapshot package when the previous one has not been archived yet
+    pub pending_package_policy: PendingPackagePolicy,
+
+    /// If set, the snapshot packager warns when the last full snapshot archived falls more than
+    /// this many slots behind the latest snapshot package, which indicates archiving has stalled
+    pub max_snapshot_age_slots: Option<Slot>,
+
+    /// If set, bound the decompressed data buffered in memory while extracting a snapshot archive
+    /// at startup to this many bytes (instead of about 2 GB).  Smaller buffers reduce peak memory
+    /// on small machines, at the cost of extraction throughput.  Must be non-zero.
+    pub extraction_buffer_bytes: Option<usize>,
+
+    /// If set, purge the oldest snapshot archives until the full and incremental snapshot
+    /// archives together take up no more than this many bytes.  The retention counts above still
+    /// apply, and the highest full snapshot archive is always kept.
+    pub maximum_snapshots_total_bytes: Option<u64>,
+
+    /// The zstd dictionary used to create and unpack `ArchiveFormat::TarZstdWithDict` archives.
+    /// Archives in that format cannot be created or loaded without it.
+    pub zstd_dictionary: Option<Arc<[u8]>>,
+
+    /// If set, cap the threads used to unarchive the full and incremental snapshot archives at
+    /// startup to this many, so loading does not take over every core on a shared host.  It is at
+    /// least `snapshot_utils::MIN_UNARCHIVE_THREAD_BUDGET`.
+    pub unarchive_thread_budget: Option<usize>,
+
+    /// If set, verify the accounts hashes at startup on a single thread pool of this many threads.
+    /// The accounts of the full and incremental snapshots are verified together, along with the
+    /// epoch accounts hash if enabled, so this caps the threads used across all of them.
+    pub verification_thread_budget: Option<usize>,
+
+    /// Untar the snapshot archives at startup with at least one thread per account path, each
+    /// writing to one path, after verifying the archives' manifests and status caches.  Ignored
+    /// if `unarchive_thread_budget` is too small for a thread per account path.
+    pub untar_across_account_paths: bool,
+
+    /// What to do at startup when there are several incremental snapshot archives with the same
+    /// base slot and slot
+    pub duplicate_incremental_snapshot_archive_policy: DuplicateIncrementalSnapshotArchivePolicy,
 }
 
 impl Default for SnapshotConfig {
@@ -64,6 +115,17 @@ impl Default for SnapshotConfig {
                 snapshot_utils::DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
             accounts_hash_debug_verify: false,
             packager_thread_niceness_adj: 0,
+            epoch_accounts_hash_archive_coordinator: None,
+            pending_package_policy: PendingPackagePolicy::default(),
+            max_snapshot_age_slots: None,
+            extraction_buffer_bytes: None,
+            maximum_snapshots_total_bytes: None,
+            zstd_dictionary: None,
+            unarchive_thread_budget: None,
+            verification_thread_budget: None,
+            untar_across_account_paths: false,
+            duplicate_incremental_snapshot_archive_policy:
+                DuplicateIncrementalSnapshotArchivePolicy::default(),
         }
     }
 }
@@ -78,6 +140,15 @@ impl SnapshotConfig {
         }
     }
 
+    /// Serialize epoch accounts hash calculations and snapshot archive creation
+    #[must_use]
+    pub fn with_serialized_epoch_accounts_hash_and_archiving(self) -> Self {
+        Self {
+            epoch_accounts_hash_archive_coordinator: Some(Arc::default()),
+            ..self
+        }
+    }
+
     /// Should snapshots be generated?
     #[must_use]
     pub fn should_generate_snapshots(&self) -> bool {
@@ -94,3 +165,12 @@ pub enum SnapshotUsage {
     /// generate).  This enables taking snapshots.
     LoadAndGenerate,
 }
+
+/// The kind of snapshot that a bank's slot warrants, see `Bank::is_snapshot_boundary()`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SnapshotRequestKind {
+    /// A full snapshot is due
+    Full,
+    /// An incremental snapshot is due, if there is a full snapshot for it to be based on
+    Incremental,
+}
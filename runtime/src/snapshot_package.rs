@@ -3,6 +3,7 @@ use {
         accounts::Accounts,
         accounts_db::SnapshotStorages,
         bank::{Bank, BankSlotDelta},
+        epoch_accounts_hash,
         rent_collector::RentCollector,
         snapshot_archive_info::{SnapshotArchiveInfo, SnapshotArchiveInfoGetter},
         snapshot_utils::{
@@ -17,8 +18,12 @@ use {
     std::{
         fs,
         path::{Path, PathBuf},
-        sync::{Arc, Mutex},
-        time::Instant,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        thread,
+        time::{Duration, Instant},
     },
     tempfile::TempDir,
 };
@@ -30,6 +35,127 @@ pub use compare::*;
 /// SnapshotPackagerService for archiving
 pub type PendingSnapshotPackage = Arc<Mutex<Option<SnapshotPackage>>>;
 
+/// How often to re-check the pending snapshot package when blocked on it
+const PENDING_PACKAGE_BLOCK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// What to do when submitting a snapshot package while another one is still pending
+///
+/// A full snapshot package is never dropped by any policy: by the time it is submitted,
+/// AccountsBackgroundService has already made its slot the last full snapshot slot, which
+/// incremental snapshots and cleaning are based on.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PendingPackagePolicy {
+    /// The new package overwrites the pending one, so the newest package wins.
+    /// An incremental snapshot package never overwrites a pending full snapshot package though.
+    Overwrite,
+    /// A new incremental snapshot package is dropped, so the pending package wins.
+    /// A new full snapshot package overwrites the pending package though.
+    DropNew,
+    /// Wait until the pending package is taken to be archived, then submit the new package, so
+    /// no package is dropped.
+    /// AccountsHashVerifier submits packages, so it does not handle any other accounts package,
+    /// including epoch accounts hash ones, while it waits.  The wait is at most about as long as
+    /// archiving the previous package takes.
+    Block,
+}
+
+impl Default for PendingPackagePolicy {
+    fn default() -> Self {
+        Self::Overwrite
+    }
+}
+
+/// Submit `snapshot_package` to be archived, handling a still-pending package per `policy`
+///
+/// Returns true if the package was submitted.  With `PendingPackagePolicy::Block`, returns false
+/// if `exit` is set while waiting.
+pub fn submit_snapshot_package(
+    pending_snapshot_package: &PendingSnapshotPackage,
+    snapshot_package: SnapshotPackage,
+    policy: PendingPackagePolicy,
+    exit: &AtomicBool,
+) -> bool {
+    let mut pending_snapshot_package_lock = pending_snapshot_package.lock().unwrap();
+    let is_incremental_snapshot = snapshot_package.snapshot_type.is_incremental_snapshot();
+    let drop_new = match policy {
+        PendingPackagePolicy::Overwrite => {
+            is_incremental_snapshot
+                && pending_snapshot_package_lock.as_ref().map_or(
+                    false,
+                    |pending_snapshot_package| {
+                        pending_snapshot_package.snapshot_type.is_full_snapshot()
+                    },
+                )
+        }
+        PendingPackagePolicy::DropNew => {
+            is_incremental_snapshot && pending_snapshot_package_lock.is_some()
+        }
+        PendingPackagePolicy::Block => {
+            while pending_snapshot_package_lock.is_some() {
+                if exit.load(Ordering::Relaxed) {
+                    return false;
+                }
+                drop(pending_snapshot_package_lock);
+                thread::sleep(PENDING_PACKAGE_BLOCK_INTERVAL);
+                pending_snapshot_package_lock = pending_snapshot_package.lock().unwrap();
+            }
+            false
+        }
+    };
+    if drop_new {
+        return false;
+    }
+
+    if let Some(dropped_snapshot_package) = pending_snapshot_package_lock.replace(snapshot_package)
+    {
+        info!(
+            "Overwrote pending snapshot package for slot {}",
+            dropped_snapshot_package.slot()
+        );
+    }
+    true
+}
+
+/// Get when the pending snapshot package, if any, was enqueued
+///
+/// Use this to tell whether SnapshotPackagerService has fallen behind: a package that has been
+/// pending for long means the next one will likely overwrite it, or be dropped, without ever
+/// being archived.
+pub fn pending_since(pending_snapshot_package: &PendingSnapshotPackage) -> Option<Instant> {
+    pending_snapshot_package
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|snapshot_package| snapshot_package.enqueued)
+}
+
+/// Take the pending snapshot package, if any, to archive it
+///
+/// An incremental snapshot package is dropped, instead of returned, if its base is not the last
+/// full snapshot archived (`last_full_snapshot_slot`).  Its base full snapshot was superseded or
+/// never archived, and publishing it would advertise an incremental snapshot nobody can load.
+pub fn take_snapshot_package_to_archive(
+    pending_snapshot_package: &PendingSnapshotPackage,
+    last_full_snapshot_slot: Option<Slot>,
+) -> Option<SnapshotPackage> {
+    let snapshot_package = pending_snapshot_package.lock().unwrap().take()?;
+    match snapshot_package.snapshot_type {
+        SnapshotType::IncrementalSnapshot(base_slot)
+            if Some(base_slot) != last_full_snapshot_slot =>
+        {
+            info!(
+                "Dropped incremental snapshot package for slot {}, its base slot {} is not the last \
+                 full snapshot archived ({:?})",
+                snapshot_package.slot(),
+                base_slot,
+                last_full_snapshot_slot,
+            );
+            None
+        }
+        _ => Some(snapshot_package),
+    }
+}
+
 pub struct AccountsPackage {
     pub package_type: AccountsPackageType,
     pub slot: Slot,
@@ -47,6 +173,9 @@ pub struct AccountsPackage {
     pub accounts: Arc<Accounts>,
     pub epoch_schedule: EpochSchedule,
     pub rent_collector: RentCollector,
+    /// For EAH accounts packages, when the EAH calculation must complete, see
+    /// `epoch_accounts_hash::calculation_deadline()`
+    pub epoch_accounts_hash_deadline: Option<Instant>,
 
     /// The instant this accounts package was send to the queue.
     /// Used to track how long accounts packages wait before processing.
@@ -121,6 +250,9 @@ impl AccountsPackage {
             accounts: bank.accounts(),
             epoch_schedule: *bank.epoch_schedule(),
             rent_collector: bank.rent_collector().clone(),
+            epoch_accounts_hash_deadline: (package_type == AccountsPackageType::EpochAccountsHash)
+                .then(|| epoch_accounts_hash::calculation_deadline(bank))
+                .flatten(),
             enqueued: Instant::now(),
         })
     }
@@ -145,6 +277,7 @@ impl AccountsPackage {
             accounts: Arc::new(Accounts::default_for_tests()),
             epoch_schedule: EpochSchedule::default(),
             rent_collector: RentCollector::default(),
+            epoch_accounts_hash_deadline: None,
             enqueued: Instant::now(),
         }
     }
@@ -178,6 +311,10 @@ pub struct SnapshotPackage {
     pub snapshot_storages: SnapshotStorages,
     pub snapshot_version: SnapshotVersion,
     pub snapshot_type: SnapshotType,
+
+    /// The instant this snapshot package was created, to be submitted for archiving.
+    /// Used to track how long snapshot packages wait before being archived.
+    pub enqueued: Instant,
 }
 
 impl SnapshotPackage {
@@ -237,6 +374,7 @@ impl SnapshotPackage {
             snapshot_storages,
             snapshot_version: accounts_package.snapshot_version,
             snapshot_type,
+            enqueued: Instant::now(),
         }
     }
 }
@@ -274,3 +412,228 @@ pub fn retain_max_n_elements<T>(v: &mut Vec<T>, n: usize) {
         v.truncate(n);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_snapshot_package(slot: Slot, snapshot_type: SnapshotType) -> SnapshotPackage {
+        let mut accounts_package = AccountsPackage::default_for_tests();
+        accounts_package.package_type = AccountsPackageType::Snapshot(snapshot_type);
+        accounts_package.slot = slot;
+        SnapshotPackage::new(accounts_package, Hash::new_unique())
+    }
+
+    fn pending_slot(pending_snapshot_package: &PendingSnapshotPackage) -> Option<Slot> {
+        pending_snapshot_package
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|snapshot_package| snapshot_package.slot())
+    }
+
+    #[test]
+    fn test_submit_snapshot_package() {
+        let exit = AtomicBool::new(false);
+        // a full snapshot package is never dropped
+        for policy in [
+            PendingPackagePolicy::Overwrite,
+            PendingPackagePolicy::DropNew,
+        ] {
+            let pending_snapshot_package = PendingSnapshotPackage::default();
+            assert!(submit_snapshot_package(
+                &pending_snapshot_package,
+                new_snapshot_package(100, SnapshotType::FullSnapshot),
+                policy,
+                &exit,
+            ));
+            assert!(submit_snapshot_package(
+                &pending_snapshot_package,
+                new_snapshot_package(200, SnapshotType::FullSnapshot),
+                policy,
+                &exit,
+            ));
+            assert_eq!(
+                pending_slot(&pending_snapshot_package),
+                Some(200),
+                "policy: {:?}",
+                policy,
+            );
+        }
+
+        // an incremental snapshot package only overwrites a pending incremental snapshot package
+        // with the overwrite policy
+        for (policy, expected_submitted, expected_pending_slot) in [
+            (PendingPackagePolicy::Overwrite, true, 200),
+            (PendingPackagePolicy::DropNew, false, 150),
+        ] {
+            let pending_snapshot_package = PendingSnapshotPackage::default();
+            assert!(submit_snapshot_package(
+                &pending_snapshot_package,
+                new_snapshot_package(150, SnapshotType::IncrementalSnapshot(100)),
+                policy,
+                &exit,
+            ));
+            assert_eq!(
+                submit_snapshot_package(
+                    &pending_snapshot_package,
+                    new_snapshot_package(200, SnapshotType::IncrementalSnapshot(100)),
+                    policy,
+                    &exit,
+                ),
+                expected_submitted,
+            );
+            assert_eq!(
+                pending_slot(&pending_snapshot_package),
+                Some(expected_pending_slot),
+                "policy: {:?}",
+                policy,
+            );
+        }
+
+        // an incremental snapshot package never overwrites a pending full snapshot package
+        let pending_snapshot_package = PendingSnapshotPackage::default();
+        assert!(submit_snapshot_package(
+            &pending_snapshot_package,
+            new_snapshot_package(100, SnapshotType::FullSnapshot),
+            PendingPackagePolicy::Overwrite,
+            &exit,
+        ));
+        assert!(!submit_snapshot_package(
+            &pending_snapshot_package,
+            new_snapshot_package(200, SnapshotType::IncrementalSnapshot(100)),
+            PendingPackagePolicy::Overwrite,
+            &exit,
+        ));
+        assert_eq!(pending_slot(&pending_snapshot_package), Some(100));
+    }
+
+    #[test]
+    fn test_take_snapshot_package_to_archive() {
+        let exit = AtomicBool::new(false);
+        let pending_snapshot_package = PendingSnapshotPackage::default();
+        let last_full_snapshot_slot = Some(100);
+        assert!(take_snapshot_package_to_archive(&pending_snapshot_package, None).is_none());
+
+        // an incremental snapshot package based on the last full snapshot archived is archived
+        assert!(submit_snapshot_package(
+            &pending_snapshot_package,
+            new_snapshot_package(150, SnapshotType::IncrementalSnapshot(100)),
+            PendingPackagePolicy::DropNew,
+            &exit,
+        ));
+        assert_eq!(
+            take_snapshot_package_to_archive(&pending_snapshot_package, last_full_snapshot_slot)
+                .unwrap()
+                .slot(),
+            150,
+        );
+
+        // a newer full snapshot package wins over a pending incremental snapshot package...
+        assert!(submit_snapshot_package(
+            &pending_snapshot_package,
+            new_snapshot_package(250, SnapshotType::IncrementalSnapshot(200)),
+            PendingPackagePolicy::DropNew,
+            &exit,
+        ));
+        assert!(submit_snapshot_package(
+            &pending_snapshot_package,
+            new_snapshot_package(300, SnapshotType::FullSnapshot),
+            PendingPackagePolicy::DropNew,
+            &exit,
+        ));
+        assert!(!submit_snapshot_package(
+            &pending_snapshot_package,
+            new_snapshot_package(350, SnapshotType::IncrementalSnapshot(300)),
+            PendingPackagePolicy::DropNew,
+            &exit,
+        ));
+        assert_eq!(
+            take_snapshot_package_to_archive(&pending_snapshot_package, last_full_snapshot_slot)
+                .unwrap()
+                .slot(),
+            300,
+        );
+
+        // ...and an incremental snapshot package based on an older full snapshot is dropped
+        assert!(submit_snapshot_package(
+            &pending_snapshot_package,
+            new_snapshot_package(280, SnapshotType::IncrementalSnapshot(200)),
+            PendingPackagePolicy::Overwrite,
+            &exit,
+        ));
+        assert!(take_snapshot_package_to_archive(&pending_snapshot_package, Some(300)).is_none());
+        assert!(pending_slot(&pending_snapshot_package).is_none());
+
+        // as is one that was submitted before any full snapshot was archived
+        assert!(submit_snapshot_package(
+            &pending_snapshot_package,
+            new_snapshot_package(150, SnapshotType::IncrementalSnapshot(100)),
+            PendingPackagePolicy::Overwrite,
+            &exit,
+        ));
+        assert!(take_snapshot_package_to_archive(&pending_snapshot_package, None).is_none());
+    }
+
+    #[test]
+    fn test_pending_since() {
+        let exit = AtomicBool::new(false);
+        let pending_snapshot_package = PendingSnapshotPackage::default();
+        assert!(pending_since(&pending_snapshot_package).is_none());
+
+        let snapshot_package = new_snapshot_package(100, SnapshotType::FullSnapshot);
+        let enqueued = snapshot_package.enqueued;
+        assert!(submit_snapshot_package(
+            &pending_snapshot_package,
+            snapshot_package,
+            PendingPackagePolicy::Overwrite,
+            &exit,
+        ));
+        assert_eq!(pending_since(&pending_snapshot_package), Some(enqueued));
+
+        // once the pending package is consumed, nothing is pending
+        pending_snapshot_package.lock().unwrap().take().unwrap();
+        assert!(pending_since(&pending_snapshot_package).is_none());
+    }
+
+    #[test]
+    fn test_submit_snapshot_package_block() {
+        let exit = AtomicBool::new(false);
+        let pending_snapshot_package = PendingSnapshotPackage::default();
+        assert!(submit_snapshot_package(
+            &pending_snapshot_package,
+            new_snapshot_package(100, SnapshotType::FullSnapshot),
+            PendingPackagePolicy::Block,
+            &exit,
+        ));
+
+        thread::scope(|s| {
+            let submitter = s.spawn(|| {
+                submit_snapshot_package(
+                    &pending_snapshot_package,
+                    new_snapshot_package(150, SnapshotType::IncrementalSnapshot(100)),
+                    PendingPackagePolicy::Block,
+                    &exit,
+                )
+            });
+
+            // the new package waits until the pending package is taken, instead of being dropped
+            thread::sleep(Duration::from_millis(200));
+            assert_eq!(pending_slot(&pending_snapshot_package), Some(100));
+            let taken_snapshot_package = pending_snapshot_package.lock().unwrap().take();
+            assert_eq!(taken_snapshot_package.unwrap().slot(), 100);
+            assert!(submitter.join().unwrap());
+        });
+        assert_eq!(pending_slot(&pending_snapshot_package), Some(150));
+
+        // exiting stops waiting
+        exit.store(true, Ordering::Relaxed);
+        assert!(!submit_snapshot_package(
+            &pending_snapshot_package,
+            new_snapshot_package(200, SnapshotType::FullSnapshot),
+            PendingPackagePolicy::Block,
+            &exit,
+        ));
+        assert_eq!(pending_slot(&pending_snapshot_package), Some(150));
+    }
+}
@@ -2,19 +2,26 @@ use {
     crate::{
         accounts_db::{
             AccountShrinkThreshold, AccountsDbConfig, SnapshotStorage, SnapshotStorages,
+            StartupProgress, PUBKEY_BINS_FOR_CALCULATING_HASHES,
         },
         accounts_index::AccountSecondaryIndexes,
         accounts_update_notifier_interface::AccountsUpdateNotifier,
-        bank::{Bank, BankFieldsToDeserialize, BankSlotDelta},
+        append_vec::AppendVec,
+        bank::{Bank, BankFieldsToDeserialize, BankSlotDelta, VerifyBankHash},
         builtins::Builtins,
+        epoch_accounts_hash::EpochAccountsHashError,
         hardened_unpack::{unpack_snapshot, ParallelSelector, UnpackError, UnpackedAppendVecMap},
+        pubkey_bins::PubkeyBinCalculator24,
         runtime_config::RuntimeConfig,
         serde_snapshot::{
-            bank_from_streams, bank_to_stream, fields_from_streams, SerdeStyle, SnapshotStreams,
+            bank_from_streams, bank_to_stream, fields_from_stream, fields_from_streams,
+            snapshot_storage_lengths_from_fields, AccountsDbFields, SerdeStyle,
+            SerializableAccountStorageEntry, SerializedAppendVecId, SnapshotStreams,
         },
         shared_buffer_reader::{SharedBuffer, SharedBufferReader},
         snapshot_archive_info::{
-            FullSnapshotArchiveInfo, IncrementalSnapshotArchiveInfo, SnapshotArchiveInfoGetter,
+            FullSnapshotArchiveInfo, IncrementalSnapshotArchiveInfo, SnapshotArchiveInfo,
+            SnapshotArchiveInfoGetter,
         },
         snapshot_package::{AccountsPackage, AccountsPackageType, SnapshotPackage, SnapshotType},
         snapshot_utils::snapshot_storage_rebuilder::SnapshotStorageRebuilder,
@@ -25,11 +32,14 @@ use {
     flate2::read::GzDecoder,
     lazy_static::lazy_static,
     log::*,
-    rayon::prelude::*,
+    rayon::{prelude::*, ThreadPool, ThreadPoolBuilder},
     regex::Regex,
+    serde::{Deserialize, Serialize},
     solana_measure::{measure, measure::Measure},
     solana_sdk::{
-        clock::Slot,
+        account::AccountSharedData,
+        clock::{Epoch, Slot},
+        feature_set::FeatureSet,
         genesis_config::GenesisConfig,
         hash::Hash,
         pubkey::Pubkey,
@@ -37,17 +47,23 @@ use {
     },
     std::{
         cmp::Ordering,
-        collections::{HashMap, HashSet},
+        collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet},
         fmt,
         fs::{self, File},
-        io::{BufReader, BufWriter, Error as IoError, ErrorKind, Read, Seek, Write},
+        io::{
+            BufReader, BufWriter, Error as IoError, ErrorKind, Read, Result as IoResult, Seek,
+            Write,
+        },
+        ops::Range,
         path::{Path, PathBuf},
         process::ExitStatus,
         str::FromStr,
         sync::{
-            atomic::{AtomicBool, AtomicU32},
+            atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering as AtomicOrdering},
             Arc,
         },
+        thread,
+        time::Duration,
     },
     tar::{self, Archive},
     tempfile::TempDir,
@@ -59,11 +75,12 @@ mod snapshot_storage_rebuilder;
 pub use archive_format::*;
 use {
     crate::{
-        accounts_db::{AccountStorageMap, AtomicAppendVecId},
+        accounts_db::{AccountStorageMap, AccountsDb, AtomicAppendVecId, IncludeSlotInHash},
+        append_vec::StoredMetaWriteVersion,
         hardened_unpack::streaming_unpack_snapshot,
         snapshot_utils::snapshot_storage_rebuilder::RebuiltSnapshotStorage,
     },
-    crossbeam_channel::Sender,
+    crossbeam_channel::{Receiver, RecvTimeoutError, Sender},
     std::thread::{Builder, JoinHandle},
 };
 
@@ -80,8 +97,8 @@ pub const BANK_SNAPSHOT_PRE_FILENAME_EXTENSION: &str = "pre";
 pub const MAX_BANK_SNAPSHOTS_TO_RETAIN: usize = 8; // Save some bank snapshots but not too many
 pub const DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN: usize = 2;
 pub const DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN: usize = 4;
-pub const FULL_SNAPSHOT_ARCHIVE_FILENAME_REGEX: &str = r"^snapshot-(?P<slot>[[:digit:]]+)-(?P<hash>[[:alnum:]]+)\.(?P<ext>tar|tar\.bz2|tar\.zst|tar\.gz|tar\.lz4)$";
-pub const INCREMENTAL_SNAPSHOT_ARCHIVE_FILENAME_REGEX: &str = r"^incremental-snapshot-(?P<base>[[:digit:]]+)-(?P<slot>[[:digit:]]+)-(?P<hash>[[:alnum:]]+)\.(?P<ext>tar|tar\.bz2|tar\.zst|tar\.gz|tar\.lz4)$";
+pub const FULL_SNAPSHOT_ARCHIVE_FILENAME_REGEX: &str = r"^snapshot-(?P<slot>[[:digit:]]+)-(?P<hash>[[:alnum:]]+)\.(?P<ext>tar|tar\.bz2|tar\.zst|tar\.dict-[[:xdigit:]]{8}\.zst|tar\.gz|tar\.lz4)$";
+pub const INCREMENTAL_SNAPSHOT_ARCHIVE_FILENAME_REGEX: &str = r"^incremental-snapshot-(?P<base>[[:digit:]]+)-(?P<slot>[[:digit:]]+)-(?P<hash>[[:alnum:]]+)\.(?P<ext>tar|tar\.bz2|tar\.zst|tar\.dict-[[:xdigit:]]{8}\.zst|tar\.gz|tar\.lz4)$";
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum SnapshotVersion {
@@ -183,6 +200,36 @@ struct SnapshotRootPaths {
     incremental_snapshot_root_file_path: Option<PathBuf>,
 }
 
+/// Creates the temporary directories that snapshot archives are extracted into
+///
+/// By default, `bank_from_snapshot_archives()` extracts into temporary directories within the
+/// bank snapshots dir, see `SnapshotLoadOptions::temp_dir_factory`.  A factory lets callers pick another location (e.g. a large scratch
+/// volume), and observe or clean up the directories it creates.
+pub trait TempDirFactory {
+    /// Create a new temporary directory, whose name starts with `prefix`
+    fn create_temp_dir(&self, prefix: &str) -> IoResult<TempDir>;
+}
+
+/// A `TempDirFactory` that creates temporary directories within `dir`
+#[derive(Debug, Clone)]
+pub struct TempDirInFactory {
+    dir: PathBuf,
+}
+
+impl TempDirInFactory {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl TempDirFactory for TempDirInFactory {
+    fn create_temp_dir(&self, prefix: &str) -> IoResult<TempDir> {
+        tempfile::Builder::new()
+            .prefix(prefix)
+            .tempdir_in(&self.dir)
+    }
+}
+
 /// Helper type to bundle up the results from `unarchive_snapshot()`
 #[derive(Debug)]
 struct UnarchivedSnapshot {
@@ -237,8 +284,8 @@ pub enum SnapshotError {
     #[error("could not parse snapshot archive's file name: {0}")]
     ParseSnapshotArchiveFileNameError(String),
 
-    #[error("snapshots are incompatible: full snapshot slot ({0}) and incremental snapshot base slot ({1}) do not match")]
-    MismatchedBaseSlot(Slot, Slot),
+    #[error("snapshots are incompatible: the incremental snapshot is based on slot {found}, but it must be based on slot {expected}")]
+    MismatchedBaseSlot { expected: Slot, found: Slot },
 
     #[error("no snapshot archives to load from")]
     NoSnapshotArchives,
@@ -248,6 +295,48 @@ pub enum SnapshotError {
 
     #[error("snapshot slot deltas are invalid: {0}")]
     VerifySlotDeltas(#[from] VerifySlotDeltasError),
+
+    #[error("snapshot archive structure is invalid: {0}")]
+    ArchiveStructure(#[from] ArchiveStructureError),
+
+    #[error("snapshot bank hash {0} matches {1} peer hashes, but {2} are required")]
+    PeerConsensusNotReached(Hash, usize, usize),
+
+    #[error("snapshot {0} does not match the genesis config")]
+    MismatchedGenesisConfig(&'static str),
+
+    #[error("snapshot epoch accounts hash is invalid: {0}")]
+    EpochAccountsHash(#[from] EpochAccountsHashError),
+
+    #[error("snapshot archive {} needs a zstd dictionary, but none was given", .0.display())]
+    MissingZstdDictionary(PathBuf),
+
+    #[error(
+        "snapshot archive {} needs zstd dictionary {:08x}, but dictionary {:08x} was given",
+        .path.display(),
+        .expected,
+        .actual
+    )]
+    MismatchedZstdDictionary {
+        path: PathBuf,
+        expected: ZstdDictionaryId,
+        actual: ZstdDictionaryId,
+    },
+
+    #[error("no snapshot archives to load from at or before slot {0}")]
+    NoSnapshotArchivesAsOfSlot(Slot),
+
+    #[error("no snapshot archive at slot {requested}, the highest before it is at slot {highest}")]
+    NoSnapshotArchiveAtSlot { requested: Slot, highest: Slot },
+
+    #[error(
+        "found {count} incremental snapshot archives with base slot {base_slot} and slot {slot}"
+    )]
+    DuplicateIncrementalSnapshotArchives {
+        base_slot: Slot,
+        slot: Slot,
+        count: usize,
+    },
 }
 pub type Result<T> = std::result::Result<T, SnapshotError>;
 
@@ -276,6 +365,19 @@ pub enum VerifySlotDeltasError {
     BadSlotHistory,
 }
 
+/// Errors that can happen in `validate_archive_structure()`
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ArchiveStructureError {
+    #[error("missing entry: {}", .0.display())]
+    MissingEntry(PathBuf),
+
+    #[error("entry is out of order: {}", .0.display())]
+    EntryOutOfOrder(PathBuf),
+
+    #[error("unexpected entry: {}", .0.display())]
+    UnexpectedEntry(PathBuf),
+}
+
 /// If the validator halts in the middle of `archive_snapshot_package()`, the temporary staging
 /// directory won't be cleaned up.  Call this function to clean them up.
 pub fn remove_tmp_snapshot_archives(snapshot_archives_dir: impl AsRef<Path>) {
@@ -299,19 +401,94 @@ pub fn remove_tmp_snapshot_archives(snapshot_archives_dir: impl AsRef<Path>) {
     }
 }
 
+/// Progress of creating a snapshot archive, see `archive_snapshot_package()`
+///
+/// The variants are in the order they are reported.  Each one carries the number of bytes
+/// processed so far in that phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveProgress {
+    /// The bank and its status cache have been serialized, into `bytes`
+    SerializingBank { bytes: u64 },
+    /// The account storages are being staged for the archive, `bytes` of them so far
+    CopyingStorages { bytes: u64 },
+    /// The staged files are being archived and compressed, `bytes` of them (uncompressed) so far
+    Compressing { bytes: u64 },
+    /// The archive, of `bytes`, has been moved into place
+    Finalizing { bytes: u64 },
+}
+
+/// Called with the `ArchiveProgress` of creating a snapshot archive
+///
+/// It is called on the thread creating the archive, which it blocks, so it should return quickly.
+pub type ArchiveProgressCallback = Box<dyn Fn(ArchiveProgress) + Send>;
+
+/// How many bytes to archive between reports of `ArchiveProgress::Compressing`
+const ARCHIVE_PROGRESS_INTERVAL_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Report `archive_progress` to `progress`, if any
+fn report_archive_progress(
+    progress: Option<&ArchiveProgressCallback>,
+    archive_progress: ArchiveProgress,
+) {
+    if let Some(progress) = progress {
+        progress(archive_progress);
+    }
+}
+
+/// Counts the bytes written through it, and reports them as `ArchiveProgress::Compressing`
+struct ArchiveProgressWriter<'a> {
+    inner: &'a mut dyn Write,
+    progress: &'a ArchiveProgressCallback,
+    bytes: u64,
+    last_reported_bytes: u64,
+}
+
+impl Write for ArchiveProgressWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        let num_bytes = self.inner.write(buf)?;
+        self.bytes += num_bytes as u64;
+        if self.bytes - self.last_reported_bytes >= ARCHIVE_PROGRESS_INTERVAL_BYTES {
+            (self.progress)(ArchiveProgress::Compressing { bytes: self.bytes });
+            self.last_reported_bytes = self.bytes;
+        }
+        Ok(num_bytes)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
+
 /// Make a snapshot archive out of the snapshot package
+///
+/// `zstd_dictionary` is only used, and required, for `ArchiveFormat::TarZstdWithDict`, and must
+/// be the dictionary named by the archive format.
+///
+/// If `progress` is set, it is called with the `ArchiveProgress` of each phase, on the calling
+/// thread.  Without it, no progress is tracked at all.
+#[allow(clippy::too_many_arguments)]
 pub fn archive_snapshot_package(
     snapshot_package: &SnapshotPackage,
     full_snapshot_archives_dir: impl AsRef<Path>,
     incremental_snapshot_archives_dir: impl AsRef<Path>,
     maximum_full_snapshot_archives_to_retain: usize,
     maximum_incremental_snapshot_archives_to_retain: usize,
+    maximum_snapshots_total_bytes: Option<u64>,
+    zstd_dictionary: Option<&[u8]>,
+    progress: Option<ArchiveProgressCallback>,
 ) -> Result<()> {
+    let progress = progress.as_ref();
     info!(
         "Generating snapshot archive for slot {}",
         snapshot_package.slot()
     );
 
+    check_zstd_dictionary(
+        snapshot_package.archive_format(),
+        zstd_dictionary,
+        snapshot_package.path(),
+    )?;
+
     serialize_status_cache(
         snapshot_package.slot(),
         &snapshot_package.slot_deltas,
@@ -320,6 +497,14 @@ pub fn archive_snapshot_package(
             .path()
             .join(SNAPSHOT_STATUS_CACHE_FILENAME),
     )?;
+    if progress.is_some() {
+        report_archive_progress(
+            progress,
+            ArchiveProgress::SerializingBank {
+                bytes: dir_size(snapshot_package.snapshot_links.path()),
+            },
+        );
+    }
 
     let mut timer = Measure::start("snapshot_package-package_snapshots");
     let tar_dir = snapshot_package
@@ -355,6 +540,7 @@ pub fn archive_snapshot_package(
     .map_err(|e| SnapshotError::IoWithSource(e, "create staging symlinks"))?;
 
     // Add the AppendVecs into the compressible list
+    let mut storages_bytes = 0;
     for storage in snapshot_package.snapshot_storages.iter().flatten() {
         storage.flush()?;
         let storage_path = storage.get_path();
@@ -372,6 +558,15 @@ pub fn archive_snapshot_package(
         if !output_path.is_file() {
             return Err(SnapshotError::StoragePathSymlinkInvalid);
         }
+        if progress.is_some() {
+            storages_bytes += storage.total_bytes();
+            report_archive_progress(
+                progress,
+                ArchiveProgress::CopyingStorages {
+                    bytes: storages_bytes,
+                },
+            );
+        }
     }
 
     // Write version file
@@ -393,7 +588,7 @@ pub fn archive_snapshot_package(
     {
         let mut archive_file = fs::File::create(&archive_path)?;
 
-        let do_archive_files = |encoder: &mut dyn Write| -> Result<()> {
+        let archive_files = |encoder: &mut dyn Write| -> Result<()> {
             let mut archive = tar::Builder::new(encoder);
             // Serialize the version and snapshots files before accounts so we can quickly determine the version
             // and other bank fields. This is necessary if we want to interleave unpacking with reconstruction
@@ -404,6 +599,24 @@ pub fn archive_snapshot_package(
             archive.into_inner()?;
             Ok(())
         };
+        let do_archive_files = |encoder: &mut dyn Write| -> Result<()> {
+            match progress {
+                None => archive_files(encoder),
+                Some(progress) => {
+                    let mut writer = ArchiveProgressWriter {
+                        inner: encoder,
+                        progress,
+                        bytes: 0,
+                        last_reported_bytes: 0,
+                    };
+                    archive_files(&mut writer)?;
+                    progress(ArchiveProgress::Compressing {
+                        bytes: writer.bytes,
+                    });
+                    Ok(())
+                }
+            }
+        };
 
         match snapshot_package.archive_format() {
             ArchiveFormat::TarBzip2 => {
@@ -423,6 +636,15 @@ pub fn archive_snapshot_package(
                 do_archive_files(&mut encoder)?;
                 encoder.finish()?;
             }
+            ArchiveFormat::TarZstdWithDict(_) => {
+                let mut encoder = zstd::stream::Encoder::with_dictionary(
+                    archive_file,
+                    0,
+                    zstd_dictionary.unwrap(), // checked above
+                )?;
+                do_archive_files(&mut encoder)?;
+                encoder.finish()?;
+            }
             ArchiveFormat::TarLz4 => {
                 let mut encoder = lz4::EncoderBuilder::new().level(1).build(archive_file)?;
                 do_archive_files(&mut encoder)?;
@@ -440,12 +662,19 @@ pub fn archive_snapshot_package(
         .map_err(|e| SnapshotError::IoWithSource(e, "archive path stat"))?;
     fs::rename(&archive_path, snapshot_package.path())
         .map_err(|e| SnapshotError::IoWithSource(e, "archive path rename"))?;
+    report_archive_progress(
+        progress,
+        ArchiveProgress::Finalizing {
+            bytes: metadata.len(),
+        },
+    );
 
     purge_old_snapshot_archives(
         full_snapshot_archives_dir,
         incremental_snapshot_archives_dir,
         maximum_full_snapshot_archives_to_retain,
         maximum_incremental_snapshot_archives_to_retain,
+        maximum_snapshots_total_bytes,
     );
 
     timer.stop();
@@ -839,56 +1068,364 @@ where
     Ok(())
 }
 
+/// Progress of loading a bank from snapshot archives, see
+/// `SnapshotLoadOptions::progress_sender`
+///
+/// The variants are in the order they are sent, and the counts within a variant only ever
+/// increase, so progress only ever increases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LoadProgress {
+    /// The snapshot archives are being unpacked, and their storages rebuilt
+    UnpackingArchives,
+    /// `untar_percent` of the snapshot archives' bytes have been unpacked, and
+    /// `num_storages_rebuilt` storages rebuilt from the unpacked files
+    Unpacking {
+        untar_percent: u8,
+        num_storages_rebuilt: usize,
+    },
+    /// The snapshot archives have been unpacked, and `num_storages` storages rebuilt
+    ArchivesUnpacked { num_storages: usize },
+    /// `num_index_entries` accounts have been inserted into the accounts index
+    GeneratingIndex { num_index_entries: u64 },
+    /// The bank has been rebuilt, including generating the accounts index
+    BankRebuilt { slot: Slot },
+    /// The bank is being verified, including calculating the accounts hash
+    VerifyingBank,
+    /// `percent` of the accounts hash verification is done
+    HashingAccounts { percent: u8 },
+    /// The bank has been loaded and verified
+    ///
+    /// The accounts hash verification may continue in the background after
+    /// `bank_from_snapshot_archives()` returns, in which case this is sent once it completes.
+    Complete,
+}
+
+/// How often the progress of loading a bank from snapshot archives is polled
+const LOAD_PROGRESS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Counters of the work done while loading a bank from snapshot archives, polled to send
+/// `LoadProgress`
+#[derive(Debug, Default)]
+struct LoadProgressCounters {
+    /// number of bytes read from the snapshot archives
+    untar_bytes_read: Arc<AtomicU64>,
+    /// total size of the snapshot archives
+    untar_bytes_total: u64,
+    /// number of storages rebuilt from the unpacked files
+    num_storages_rebuilt: Arc<AtomicUsize>,
+    /// the work done by the accounts db, see `AccountsDbConfig::startup_progress`
+    accounts_db: Arc<StartupProgress>,
+}
+
+impl LoadProgressCounters {
+    fn unpacking(&self) -> LoadProgress {
+        let untar_bytes_read = self.untar_bytes_read.load(AtomicOrdering::Relaxed);
+        LoadProgress::Unpacking {
+            untar_percent: percent(untar_bytes_read, self.untar_bytes_total),
+            num_storages_rebuilt: self.num_storages_rebuilt.load(AtomicOrdering::Relaxed),
+        }
+    }
+
+    fn generating_index(&self) -> LoadProgress {
+        LoadProgress::GeneratingIndex {
+            num_index_entries: self.accounts_db.index_entries.load(AtomicOrdering::Relaxed),
+        }
+    }
+
+    fn hashing_accounts(&self) -> LoadProgress {
+        let passes_completed = self
+            .accounts_db
+            .hash_passes_completed
+            .load(AtomicOrdering::Relaxed);
+        let passes_total = self
+            .accounts_db
+            .hash_passes_total
+            .load(AtomicOrdering::Relaxed);
+        LoadProgress::HashingAccounts {
+            percent: percent(passes_completed as u64, passes_total as u64),
+        }
+    }
+}
+
+/// `part` as a percentage of `total`; 0 if `total` is 0
+fn percent(part: u64, total: u64) -> u8 {
+    if total == 0 {
+        0
+    } else {
+        (part.min(total) * 100 / total) as u8
+    }
+}
+
+/// Send `progress` to `progress_sender`, if any, without blocking
+///
+/// Progress is informational, so it is dropped if the channel is full or disconnected.
+fn send_load_progress(progress_sender: Option<&Sender<LoadProgress>>, progress: LoadProgress) {
+    if let Some(progress_sender) = progress_sender {
+        let _ = progress_sender.try_send(progress);
+    }
+}
+
+/// Send the progress from `poll_progress` to `progress_sender` until `wait_is_done` returns true
+///
+/// `wait_is_done` is expected to wait for about `LOAD_PROGRESS_POLL_INTERVAL`.  Progress is only
+/// sent when it changes, and is polled one last time once done, so the final counts are sent.
+fn send_polled_load_progress(
+    progress_sender: &Sender<LoadProgress>,
+    poll_progress: impl Fn() -> LoadProgress,
+    mut wait_is_done: impl FnMut() -> bool,
+) {
+    let mut last_progress = None;
+    loop {
+        let is_done = wait_is_done();
+        let progress = poll_progress();
+        if last_progress != Some(progress) {
+            send_load_progress(Some(progress_sender), progress);
+            last_progress = Some(progress);
+        }
+        if is_done {
+            break;
+        }
+    }
+}
+
+/// Run `work`, and meanwhile poll the progress from `poll_progress` and send it to the progress
+/// sender of `progress`, if any
+fn with_polled_load_progress<T>(
+    progress: Option<(&Sender<LoadProgress>, &LoadProgressCounters)>,
+    poll_progress: fn(&LoadProgressCounters) -> LoadProgress,
+    work: impl FnOnce() -> T,
+) -> T {
+    match progress {
+        None => work(),
+        Some((progress_sender, progress_counters)) => thread::scope(|scope| {
+            let (done_sender, done_receiver) = crossbeam_channel::bounded::<()>(0);
+            scope.spawn(move || {
+                send_polled_load_progress(
+                    progress_sender,
+                    || poll_progress(progress_counters),
+                    || {
+                        matches!(
+                            done_receiver.recv_timeout(LOAD_PROGRESS_POLL_INTERVAL),
+                            Err(RecvTimeoutError::Disconnected),
+                        )
+                    },
+                )
+            });
+            let result = work();
+            drop(done_sender);
+            result
+        }),
+    }
+}
+
+/// Counts the bytes read from `reader`, so the progress of unpacking an archive can be reported
+struct ProgressReader<R> {
+    reader: R,
+    bytes_read: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let num_bytes = self.reader.read(buf)?;
+        self.bytes_read
+            .fetch_add(num_bytes as u64, AtomicOrdering::Relaxed);
+        Ok(num_bytes)
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct BankFromArchiveTimings {
     pub rebuild_bank_from_snapshots_us: u64,
     pub full_snapshot_untar_us: u64,
     pub incremental_snapshot_untar_us: u64,
+    pub verify_epoch_accounts_hash_us: u64,
     pub verify_snapshot_bank_us: u64,
 }
 
 // From testing, 4 seems to be a sweet spot for ranges of 60M-360M accounts and 16-64 cores. This may need to be tuned later.
 const PARALLEL_UNTAR_READERS_DEFAULT: usize = 4;
 
+/// What to do when loading finds several incremental snapshot archives with the same base slot
+/// and slot (e.g. with different hashes)
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DuplicateIncrementalSnapshotArchivePolicy {
+    /// Pick the archive with the lowest path, so every run picks the same one
+    PickFirstPath,
+    /// Fail with `SnapshotError::DuplicateIncrementalSnapshotArchives`
+    Fail,
+}
+
+impl Default for DuplicateIncrementalSnapshotArchivePolicy {
+    fn default() -> Self {
+        Self::PickFirstPath
+    }
+}
+
+/// The smallest `SnapshotConfig::unarchive_thread_budget`: one thread to read the archive, one to
+/// untar it, and one to rebuild the account storages
+pub const MIN_UNARCHIVE_THREAD_BUDGET: usize = 3;
+
+/// Returns the number of threads to untar a snapshot archive with, and the number of threads to
+/// rebuild its account storages with
+///
+/// One more thread reads and decompresses the archive.  If `thread_budget` is set, the total
+/// number of threads, including that one, is within the budget (or `MIN_UNARCHIVE_THREAD_BUDGET`,
+/// if the budget is smaller).
+fn unarchive_thread_counts(thread_budget: Option<usize>) -> (usize, usize) {
+    match thread_budget {
+        None => {
+            let parallel_divisions = std::cmp::min(
+                PARALLEL_UNTAR_READERS_DEFAULT,
+                std::cmp::max(1, num_cpus::get() / 4),
+            );
+            let num_rebuilder_threads = num_cpus::get_physical()
+                .saturating_sub(parallel_divisions)
+                .max(1);
+            (parallel_divisions, num_rebuilder_threads)
+        }
+        Some(thread_budget) => {
+            let num_threads = thread_budget.max(MIN_UNARCHIVE_THREAD_BUDGET) - 1;
+            let parallel_divisions = std::cmp::min(
+                PARALLEL_UNTAR_READERS_DEFAULT,
+                std::cmp::max(1, num_threads / 4),
+            );
+            (parallel_divisions, num_threads - parallel_divisions)
+        }
+    }
+}
+
+/// The thread pool the accounts hashes are verified on at startup, if
+/// `SnapshotConfig::verification_thread_budget` is set
+fn new_verification_thread_pool(thread_budget: usize) -> ThreadPool {
+    ThreadPoolBuilder::new()
+        .num_threads(thread_budget.max(1))
+        .thread_name(|i| format!("solSnapVerify{i:02}"))
+        .build()
+        .unwrap()
+}
+
+/// Like `unarchive_thread_counts()`, but so that each untar thread writes to one of
+/// `num_account_paths` account paths
+///
+/// The untar threads are rounded down to a multiple of `num_account_paths`, but at least
+/// `num_account_paths`.  If `thread_budget` is set, any extra untar threads are taken from the
+/// rebuilder threads, so the total stays within the budget.  Returns None if the budget is too
+/// small for one untar thread per account path and at least one rebuilder thread.
+fn unarchive_thread_counts_across_account_paths(
+    thread_budget: Option<usize>,
+    num_account_paths: usize,
+) -> Option<(usize, usize)> {
+    let (parallel_divisions, num_rebuilder_threads) = unarchive_thread_counts(thread_budget);
+    let num_account_paths = num_account_paths.max(1);
+    let num_untar_threads = (parallel_divisions / num_account_paths).max(1) * num_account_paths;
+    if thread_budget.is_none() {
+        return Some((num_untar_threads, num_rebuilder_threads));
+    }
+    let num_threads = parallel_divisions + num_rebuilder_threads;
+    (num_untar_threads < num_threads).then(|| (num_untar_threads, num_threads - num_untar_threads))
+}
+
+#[allow(clippy::too_many_arguments)]
 fn verify_and_unarchive_snapshots(
     bank_snapshots_dir: impl AsRef<Path>,
     full_snapshot_archive_info: &FullSnapshotArchiveInfo,
     incremental_snapshot_archive_info: Option<&IncrementalSnapshotArchiveInfo>,
     account_paths: &[PathBuf],
+    temp_dir_factory: Option<&dyn TempDirFactory>,
+    extraction_buffer_bytes: Option<usize>,
+    zstd_dictionary: Option<&[u8]>,
+    unarchive_thread_budget: Option<usize>,
+    untar_across_account_paths: bool,
+    progress_counters: Option<&LoadProgressCounters>,
 ) -> Result<(UnarchivedSnapshot, Option<UnarchivedSnapshot>, AtomicU32)> {
     check_are_snapshots_compatible(
         full_snapshot_archive_info,
         incremental_snapshot_archive_info,
     )?;
 
-    let parallel_divisions = std::cmp::min(
-        PARALLEL_UNTAR_READERS_DEFAULT,
-        std::cmp::max(1, num_cpus::get() / 4),
-    );
+    // The full and incremental snapshots are unarchived one after the other, so they both get the
+    // whole thread budget
+    let (mut parallel_divisions, mut num_rebuilder_threads) =
+        unarchive_thread_counts(unarchive_thread_budget);
+
+    // Account storage files are round-robined across the account paths by their index in the
+    // archive, so with a multiple of the number of account paths as untar threads, each thread
+    // writes to one path
+    let untar_across_account_paths = untar_across_account_paths
+        && match unarchive_thread_counts_across_account_paths(
+            unarchive_thread_budget,
+            account_paths.len(),
+        ) {
+            Some(thread_counts) => {
+                (parallel_divisions, num_rebuilder_threads) = thread_counts;
+                true
+            }
+            None => {
+                warn!(
+                    "The unarchive thread budget of {:?} is too small to untar across {} account \
+                     paths, so the snapshot archives are untarred as usual",
+                    unarchive_thread_budget,
+                    account_paths.len(),
+                );
+                false
+            }
+        };
+
+    if untar_across_account_paths {
+        verify_archived_manifest_and_status_cache(
+            full_snapshot_archive_info,
+            extraction_buffer_bytes,
+            zstd_dictionary,
+        )?;
+        if let Some(incremental_snapshot_archive_info) = incremental_snapshot_archive_info {
+            verify_archived_manifest_and_status_cache(
+                incremental_snapshot_archive_info,
+                extraction_buffer_bytes,
+                zstd_dictionary,
+            )?;
+        }
+    }
+
+    let default_temp_dir_factory;
+    let temp_dir_factory = match temp_dir_factory {
+        Some(temp_dir_factory) => temp_dir_factory,
+        None => {
+            default_temp_dir_factory = TempDirInFactory::new(bank_snapshots_dir.as_ref());
+            &default_temp_dir_factory
+        }
+    };
 
     let next_append_vec_id = Arc::new(AtomicU32::new(0));
     let unarchived_full_snapshot = unarchive_snapshot(
-        &bank_snapshots_dir,
+        temp_dir_factory,
         TMP_SNAPSHOT_ARCHIVE_PREFIX,
         full_snapshot_archive_info.path(),
         "snapshot untar",
         account_paths,
         full_snapshot_archive_info.archive_format(),
         parallel_divisions,
+        num_rebuilder_threads,
         next_append_vec_id.clone(),
+        extraction_buffer_bytes,
+        zstd_dictionary,
+        progress_counters,
     )?;
 
     let unarchived_incremental_snapshot =
         if let Some(incremental_snapshot_archive_info) = incremental_snapshot_archive_info {
             let unarchived_incremental_snapshot = unarchive_snapshot(
-                &bank_snapshots_dir,
+                temp_dir_factory,
                 TMP_SNAPSHOT_ARCHIVE_PREFIX,
                 incremental_snapshot_archive_info.path(),
                 "incremental snapshot untar",
                 account_paths,
                 incremental_snapshot_archive_info.archive_format(),
                 parallel_divisions,
+                num_rebuilder_threads,
                 next_append_vec_id.clone(),
+                extraction_buffer_bytes,
+                zstd_dictionary,
+                progress_counters,
             )?;
             Some(unarchived_incremental_snapshot)
         } else {
@@ -930,6 +1467,12 @@ pub fn bank_fields_from_snapshot_archives(
             &full_snapshot_archive_info,
             incremental_snapshot_archive_info.as_ref(),
             &account_paths,
+            None,
+            None,
+            None,
+            None,
+            false,
+            None,
         )?;
 
     bank_fields_from_snapshots(
@@ -942,846 +1485,1484 @@ pub fn bank_fields_from_snapshot_archives(
     )
 }
 
-/// Rebuild bank from snapshot archives.  Handles either just a full snapshot, or both a full
-/// snapshot and an incremental snapshot.
-#[allow(clippy::too_many_arguments)]
-pub fn bank_from_snapshot_archives(
-    account_paths: &[PathBuf],
-    bank_snapshots_dir: impl AsRef<Path>,
-    full_snapshot_archive_info: &FullSnapshotArchiveInfo,
-    incremental_snapshot_archive_info: Option<&IncrementalSnapshotArchiveInfo>,
-    genesis_config: &GenesisConfig,
-    runtime_config: &RuntimeConfig,
-    debug_keys: Option<Arc<HashSet<Pubkey>>>,
-    additional_builtins: Option<&Builtins>,
-    account_secondary_indexes: AccountSecondaryIndexes,
-    accounts_db_caching_enabled: bool,
-    limit_load_slot_count_from_snapshot: Option<usize>,
-    shrink_ratio: AccountShrinkThreshold,
-    test_hash_calculation: bool,
-    accounts_db_skip_shrink: bool,
-    verify_index: bool,
-    accounts_db_config: Option<AccountsDbConfig>,
-    accounts_update_notifier: Option<AccountsUpdateNotifier>,
-    exit: &Arc<AtomicBool>,
-) -> Result<(Bank, BankFromArchiveTimings)> {
-    let (unarchived_full_snapshot, mut unarchived_incremental_snapshot, next_append_vec_id) =
-        verify_and_unarchive_snapshots(
-            bank_snapshots_dir,
-            full_snapshot_archive_info,
-            incremental_snapshot_archive_info,
-            account_paths,
-        )?;
-
-    let mut storage = unarchived_full_snapshot.storage;
-    if let Some(ref mut unarchive_preparation_result) = unarchived_incremental_snapshot {
-        let incremental_snapshot_storages =
-            std::mem::take(&mut unarchive_preparation_result.storage);
-        storage.extend(incremental_snapshot_storages.into_iter());
+/// Parse the slot, base slot (for incremental snapshot archives), and archive format from the
+/// file name of either a full or an incremental snapshot archive
+fn parse_snapshot_archive_path(archive: &Path) -> Result<(Slot, Option<Slot>, ArchiveFormat)> {
+    let archive_filename = path_to_file_name_str(archive)?;
+    match parse_full_snapshot_archive_filename(archive_filename) {
+        Ok((slot, _hash, archive_format)) => Ok((slot, None, archive_format)),
+        Err(_) => {
+            let (base_slot, slot, _hash, archive_format) =
+                parse_incremental_snapshot_archive_filename(archive_filename)?;
+            Ok((slot, Some(base_slot), archive_format))
+        }
     }
+}
 
-    let storage_and_next_append_vec_id = StorageAndNextAppendVecId {
-        storage,
-        next_append_vec_id,
-    };
+/// The path of the bank snapshot file (the "manifest") within a snapshot archive
+fn get_archived_manifest_path(slot: Slot) -> PathBuf {
+    Path::new("snapshots")
+        .join(slot.to_string())
+        .join(get_snapshot_file_name(slot))
+}
 
-    let mut measure_rebuild = Measure::start("rebuild bank from snapshots");
-    let bank = rebuild_bank_from_snapshots(
-        &unarchived_full_snapshot.unpacked_snapshots_dir_and_version,
-        unarchived_incremental_snapshot
-            .as_ref()
-            .map(|unarchive_preparation_result| {
-                &unarchive_preparation_result.unpacked_snapshots_dir_and_version
-            }),
-        account_paths,
-        storage_and_next_append_vec_id,
-        genesis_config,
-        runtime_config,
-        debug_keys,
-        additional_builtins,
-        account_secondary_indexes,
-        accounts_db_caching_enabled,
-        limit_load_slot_count_from_snapshot,
-        shrink_ratio,
-        verify_index,
-        accounts_db_config,
-        accounts_update_notifier,
-        exit,
-    )?;
-    measure_rebuild.stop();
-    info!("{}", measure_rebuild);
+/// Summary of a snapshot archive's structure, from `validate_archive_structure()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveStructureReport {
+    pub slot: Slot,
+    /// the full snapshot slot this archive is based on, if this is an incremental snapshot archive
+    pub base_slot: Option<Slot>,
+    pub archive_format: ArchiveFormat,
+    /// the number of entries in the archive, including directories
+    pub num_entries: usize,
+    /// the number of account storage files in the archive
+    pub num_storages: usize,
+}
 
-    let mut measure_verify = Measure::start("verify");
-    if !bank.verify_snapshot_bank(
-        test_hash_calculation,
-        accounts_db_skip_shrink || !full_snapshot_archive_info.is_remote(),
-        full_snapshot_archive_info.slot(),
-    ) && limit_load_slot_count_from_snapshot.is_none()
-    {
-        panic!("Snapshot bank for slot {} failed to verify", bank.slot());
+/// Validate the structure of a snapshot archive by only walking its tar headers
+///
+/// The archive must contain the version file first, followed by the snapshots dir (holding the
+/// manifest and status cache), followed by the accounts dir.  Entry payloads are not read, so
+/// this is much cheaper than unpacking or verifying the archive.
+pub fn validate_archive_structure(archive: impl AsRef<Path>) -> Result<ArchiveStructureReport> {
+    let archive = archive.as_ref();
+    let (slot, base_slot, archive_format) = parse_snapshot_archive_path(archive)?;
+    let manifest_path = get_archived_manifest_path(slot);
+    let status_cache_path = Path::new("snapshots").join(SNAPSHOT_STATUS_CACHE_FILENAME);
+
+    let shared_buffer =
+        untar_snapshot_create_shared_buffer(archive, archive_format, None, None, None)?;
+    let mut tar = Archive::new(SharedBufferReader::new(&shared_buffer));
+    let mut num_entries = 0;
+    let mut num_storages = 0;
+    let mut has_manifest = false;
+    let mut has_status_cache = false;
+    let mut has_accounts_dir = false;
+    for entry in tar.entries()? {
+        let entry = entry?;
+        let path = entry.path()?.into_owned();
+        let is_first_entry = num_entries == 0;
+        num_entries += 1;
+
+        if path == Path::new("version") {
+            if !is_first_entry {
+                return Err(ArchiveStructureError::EntryOutOfOrder(path).into());
+            }
+        } else if is_first_entry {
+            return Err(ArchiveStructureError::MissingEntry(PathBuf::from("version")).into());
+        } else if path.starts_with("snapshots") {
+            if has_accounts_dir {
+                return Err(ArchiveStructureError::EntryOutOfOrder(path).into());
+            }
+            has_manifest |= path == manifest_path;
+            has_status_cache |= path == status_cache_path;
+        } else if path.starts_with("accounts") {
+            has_accounts_dir = true;
+            if entry.header().entry_type().is_file() {
+                num_storages += 1;
+            }
+        } else {
+            return Err(ArchiveStructureError::UnexpectedEntry(path).into());
+        }
     }
-    measure_verify.stop();
 
-    let timings = BankFromArchiveTimings {
-        rebuild_bank_from_snapshots_us: measure_rebuild.as_us(),
-        full_snapshot_untar_us: unarchived_full_snapshot.measure_untar.as_us(),
-        incremental_snapshot_untar_us: unarchived_incremental_snapshot
-            .map_or(0, |unarchive_preparation_result| {
-                unarchive_preparation_result.measure_untar.as_us()
-            }),
-        verify_snapshot_bank_us: measure_verify.as_us(),
-    };
-    Ok((bank, timings))
-}
+    if num_entries == 0 {
+        return Err(ArchiveStructureError::MissingEntry(PathBuf::from("version")).into());
+    }
+    for (is_present, path) in [
+        (has_manifest, manifest_path),
+        (has_status_cache, status_cache_path),
+        (has_accounts_dir, PathBuf::from("accounts")),
+    ] {
+        if !is_present {
+            return Err(ArchiveStructureError::MissingEntry(path).into());
+        }
+    }
 
-/// Rebuild bank from snapshot archives.  This function searches `full_snapshot_archives_dir` and `incremental_snapshot_archives_dir` for the
-/// highest full snapshot and highest corresponding incremental snapshot, then rebuilds the bank.
-#[allow(clippy::too_many_arguments)]
-pub fn bank_from_latest_snapshot_archives(
-    bank_snapshots_dir: impl AsRef<Path>,
-    full_snapshot_archives_dir: impl AsRef<Path>,
-    incremental_snapshot_archives_dir: impl AsRef<Path>,
-    account_paths: &[PathBuf],
-    genesis_config: &GenesisConfig,
-    runtime_config: &RuntimeConfig,
-    debug_keys: Option<Arc<HashSet<Pubkey>>>,
-    additional_builtins: Option<&Builtins>,
-    account_secondary_indexes: AccountSecondaryIndexes,
-    accounts_db_caching_enabled: bool,
-    limit_load_slot_count_from_snapshot: Option<usize>,
-    shrink_ratio: AccountShrinkThreshold,
-    test_hash_calculation: bool,
-    accounts_db_skip_shrink: bool,
-    verify_index: bool,
-    accounts_db_config: Option<AccountsDbConfig>,
-    accounts_update_notifier: Option<AccountsUpdateNotifier>,
-    exit: &Arc<AtomicBool>,
-) -> Result<(
-    Bank,
-    FullSnapshotArchiveInfo,
-    Option<IncrementalSnapshotArchiveInfo>,
-)> {
-    let full_snapshot_archive_info =
-        get_highest_full_snapshot_archive_info(&full_snapshot_archives_dir)
-            .ok_or(SnapshotError::NoSnapshotArchives)?;
+    Ok(ArchiveStructureReport {
+        slot,
+        base_slot,
+        archive_format,
+        num_entries,
+        num_storages,
+    })
+}
 
-    let incremental_snapshot_archive_info = get_highest_incremental_snapshot_archive_info(
-        &incremental_snapshot_archives_dir,
-        full_snapshot_archive_info.slot(),
-    );
+/// A difference between the accounts in two snapshot archives, from `diff_archives()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccountDiff {
+    /// The account is only in the first archive
+    OnlyInA { pubkey: Pubkey, hash: Hash },
+    /// The account is only in the second archive
+    OnlyInB { pubkey: Pubkey, hash: Hash },
+    /// The account is in both archives, but its lamports, owner, data, etc. differ
+    Mismatch {
+        pubkey: Pubkey,
+        hash_a: Hash,
+        hash_b: Hash,
+    },
+}
 
-    info!(
-        "Loading bank from full snapshot: {}, and incremental snapshot: {:?}",
-        full_snapshot_archive_info.path().display(),
-        incremental_snapshot_archive_info
-            .as_ref()
-            .map(
-                |incremental_snapshot_archive_info| incremental_snapshot_archive_info
-                    .path()
-                    .display()
-            )
-    );
+impl AccountDiff {
+    pub fn pubkey(&self) -> &Pubkey {
+        match self {
+            AccountDiff::OnlyInA { pubkey, .. }
+            | AccountDiff::OnlyInB { pubkey, .. }
+            | AccountDiff::Mismatch { pubkey, .. } => pubkey,
+        }
+    }
+}
 
-    let (bank, timings) = bank_from_snapshot_archives(
-        account_paths,
-        bank_snapshots_dir.as_ref(),
-        &full_snapshot_archive_info,
-        incremental_snapshot_archive_info.as_ref(),
-        genesis_config,
-        runtime_config,
-        debug_keys,
-        additional_builtins,
-        account_secondary_indexes,
-        accounts_db_caching_enabled,
-        limit_load_slot_count_from_snapshot,
-        shrink_ratio,
-        test_hash_calculation,
-        accounts_db_skip_shrink,
-        verify_index,
-        accounts_db_config,
-        accounts_update_notifier,
-        exit,
-    )?;
+/// The most accounts that the archive helpers, e.g. `diff_archives()`, keep in memory at once;
+/// the accounts of larger archives are processed in several passes over ranges of pubkeys
+const MAX_ARCHIVE_ACCOUNTS_PER_PASS: usize = 4_000_000;
 
-    datapoint_info!(
-        "bank_from_snapshot_archives",
-        (
-            "full_snapshot_untar_us",
-            timings.full_snapshot_untar_us,
-            i64
-        ),
-        (
-            "incremental_snapshot_untar_us",
-            timings.incremental_snapshot_untar_us,
-            i64
-        ),
-        (
-            "rebuild_bank_from_snapshots_us",
-            timings.rebuild_bank_from_snapshots_us,
-            i64
-        ),
-        (
-            "verify_snapshot_bank_us",
-            timings.verify_snapshot_bank_us,
-            i64
-        ),
-    );
+/// Find the accounts that differ between two snapshot archives
+///
+/// Only the latest version of each account in an archive is compared, and zero-lamport accounts
+/// are treated as absent.  Accounts are compared by their hash (without the slot), so two
+/// archives of different slots can be diffed.  The diffs are sorted by pubkey.
+///
+/// The archives are unpacked into temporary directories from `temp_dir_factory`.  The accounts
+/// are compared in passes over ranges of pubkeys, and only the pubkeys and hashes of one range
+/// are kept at a time, so memory usage depends on neither the number of accounts nor the size of
+/// their data.  Note that an incremental snapshot archive only contains the accounts changed since
+/// its base slot.
+pub fn diff_archives(
+    a: impl AsRef<Path>,
+    b: impl AsRef<Path>,
+    temp_dir_factory: &dyn TempDirFactory,
+) -> Result<Vec<AccountDiff>> {
+    diff_archives_in_passes(
+        a.as_ref(),
+        b.as_ref(),
+        temp_dir_factory,
+        MAX_ARCHIVE_ACCOUNTS_PER_PASS,
+    )
+}
 
-    verify_bank_against_expected_slot_hash(
-        &bank,
-        incremental_snapshot_archive_info.as_ref().map_or(
-            full_snapshot_archive_info.slot(),
-            |incremental_snapshot_archive_info| incremental_snapshot_archive_info.slot(),
-        ),
-        incremental_snapshot_archive_info.as_ref().map_or(
-            *full_snapshot_archive_info.hash(),
-            |incremental_snapshot_archive_info| *incremental_snapshot_archive_info.hash(),
-        ),
-    )?;
+fn diff_archives_in_passes(
+    a: &Path,
+    b: &Path,
+    temp_dir_factory: &dyn TempDirFactory,
+    max_accounts_per_pass: usize,
+) -> Result<Vec<AccountDiff>> {
+    let (_unpack_dir_a, storage_a) =
+        unarchive_snapshot_storage_in_temp_dir(a, "snapshot diff untar", temp_dir_factory)?;
+    let (_unpack_dir_b, storage_b) =
+        unarchive_snapshot_storage_in_temp_dir(b, "snapshot diff untar", temp_dir_factory)?;
+
+    let mut diffs = Vec::new();
+    for bin_range in archive_pass_bin_ranges(&[&storage_a, &storage_b], max_accounts_per_pass) {
+        let accounts_a = get_sorted_account_hashes(&storage_a, &bin_range);
+        let accounts_b = get_sorted_account_hashes(&storage_b, &bin_range);
+        let mut iter_a = accounts_a.into_iter().peekable();
+        let mut iter_b = accounts_b.into_iter().peekable();
+        loop {
+            let diff = match (iter_a.peek(), iter_b.peek()) {
+                (None, None) => break,
+                (Some(_), None) => {
+                    let (pubkey, hash) = iter_a.next().unwrap();
+                    AccountDiff::OnlyInA { pubkey, hash }
+                }
+                (None, Some(_)) => {
+                    let (pubkey, hash) = iter_b.next().unwrap();
+                    AccountDiff::OnlyInB { pubkey, hash }
+                }
+                (Some((pubkey_a, _)), Some((pubkey_b, _))) => match pubkey_a.cmp(pubkey_b) {
+                    Ordering::Less => {
+                        let (pubkey, hash) = iter_a.next().unwrap();
+                        AccountDiff::OnlyInA { pubkey, hash }
+                    }
+                    Ordering::Greater => {
+                        let (pubkey, hash) = iter_b.next().unwrap();
+                        AccountDiff::OnlyInB { pubkey, hash }
+                    }
+                    Ordering::Equal => {
+                        let (pubkey, hash_a) = iter_a.next().unwrap();
+                        let (_, hash_b) = iter_b.next().unwrap();
+                        if hash_a == hash_b {
+                            continue;
+                        }
+                        AccountDiff::Mismatch {
+                            pubkey,
+                            hash_a,
+                            hash_b,
+                        }
+                    }
+                },
+            };
+            diffs.push(diff);
+        }
+    }
+    Ok(diffs)
+}
 
-    Ok((
-        bank,
-        full_snapshot_archive_info,
-        incremental_snapshot_archive_info,
-    ))
+/// Find the accounts that changed from `older_archive` to `newer_archive`, i.e. reconstruct the
+/// incremental delta between two full snapshot archives
+///
+/// The result has the latest version of every account that was created or modified, sorted by
+/// pubkey.  Accounts that were removed are returned as zero-lamport accounts, just like an
+/// incremental snapshot would have them.  Accounts are compared by their hash (without the
+/// slot).
+///
+/// The archives are unpacked into temporary directories from `temp_dir_factory`.  Like
+/// `diff_archives()`, the accounts are compared in passes over ranges of pubkeys, and only the
+/// changed accounts are loaded, so memory usage is bounded by the size of the delta, not by the
+/// size of the archives.
+pub fn accounts_changed_between(
+    older_archive: impl AsRef<Path>,
+    newer_archive: impl AsRef<Path>,
+    temp_dir_factory: &dyn TempDirFactory,
+) -> Result<Vec<(Pubkey, AccountSharedData)>> {
+    accounts_changed_between_in_passes(
+        older_archive.as_ref(),
+        newer_archive.as_ref(),
+        temp_dir_factory,
+        MAX_ARCHIVE_ACCOUNTS_PER_PASS,
+    )
 }
 
-/// Check to make sure the deserialized bank's slot and hash matches the snapshot archive's slot
-/// and hash
-fn verify_bank_against_expected_slot_hash(
-    bank: &Bank,
-    expected_slot: Slot,
-    expected_hash: Hash,
-) -> Result<()> {
-    let bank_slot = bank.slot();
-    let bank_hash = bank.get_accounts_hash();
+fn accounts_changed_between_in_passes(
+    older_archive: &Path,
+    newer_archive: &Path,
+    temp_dir_factory: &dyn TempDirFactory,
+    max_accounts_per_pass: usize,
+) -> Result<Vec<(Pubkey, AccountSharedData)>> {
+    let (_older_unpack_dir, older_storage) = unarchive_snapshot_storage_in_temp_dir(
+        older_archive,
+        "snapshot delta untar",
+        temp_dir_factory,
+    )?;
+    let (_newer_unpack_dir, newer_storage) = unarchive_snapshot_storage_in_temp_dir(
+        newer_archive,
+        "snapshot delta untar",
+        temp_dir_factory,
+    )?;
 
-    if bank_slot != expected_slot || bank_hash != expected_hash {
-        return Err(SnapshotError::MismatchedSlotHash(
-            (bank_slot, bank_hash),
-            (expected_slot, expected_hash),
-        ));
-    }
+    let bin_calculator = PubkeyBinCalculator24::new(PUBKEY_BINS_FOR_CALCULATING_HASHES);
+    let mut changed_accounts = Vec::new();
+    for bin_range in
+        archive_pass_bin_ranges(&[&older_storage, &newer_storage], max_accounts_per_pass)
+    {
+        let mut older_accounts: HashMap<_, _> =
+            get_sorted_account_hashes(&older_storage, &bin_range)
+                .into_iter()
+                .collect();
+        let newer_accounts = get_latest_account_hashes(&newer_storage, &bin_range);
+
+        let mut pass_changed_accounts = Vec::new();
+        for entry in newer_storage.iter() {
+            let slot = *entry.key();
+            for store in entry.value().read().unwrap().values() {
+                store.accounts.account_iter().for_each(|stored_account| {
+                    let pubkey = &stored_account.meta.pubkey;
+                    if !bin_range.contains(&bin_calculator.bin_from_pubkey(pubkey)) {
+                        return;
+                    }
+                    let (latest_slot, latest_write_version, hash) = &newer_accounts[pubkey];
+                    if (*latest_slot, *latest_write_version)
+                        != (slot, stored_account.meta.write_version)
+                    {
+                        return;
+                    }
+                    // zero-lamport accounts hash to the default hash, same as absent accounts
+                    let older_hash = older_accounts.remove(pubkey).unwrap_or_default();
+                    if *hash != older_hash {
+                        pass_changed_accounts.push((*pubkey, stored_account.clone_account()));
+                    }
+                });
+            }
+        }
+        // what remains was in the older archive, but is not in the newer one at all
+        pass_changed_accounts.extend(
+            older_accounts
+                .into_keys()
+                .map(|pubkey| (pubkey, AccountSharedData::default())),
+        );
 
-    Ok(())
+        // the bin ranges are in pubkey order, so sorting each pass sorts the whole result
+        pass_changed_accounts.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        changed_accounts.append(&mut pass_changed_accounts);
+    }
+    Ok(changed_accounts)
 }
 
-/// Spawns a thread for unpacking a snapshot
-fn spawn_unpack_snapshot_thread(
-    file_sender: Sender<PathBuf>,
-    account_paths: Arc<Vec<PathBuf>>,
-    ledger_dir: Arc<PathBuf>,
-    mut archive: Archive<SharedBufferReader>,
-    parallel_selector: Option<ParallelSelector>,
-    thread_index: usize,
-) -> JoinHandle<()> {
-    Builder::new()
-        .name(format!("solUnpkSnpsht{thread_index:02}"))
-        .spawn(move || {
-            streaming_unpack_snapshot(
-                &mut archive,
-                ledger_dir.as_path(),
-                &account_paths,
-                parallel_selector,
-                &file_sender,
-            )
-            .unwrap();
-        })
-        .unwrap()
+/// Count the accounts in a snapshot archive per owner program
+///
+/// Only the latest version of each account is counted, and zero-lamport accounts are skipped.
+/// The account storages are unpacked into a temporary directory from `temp_dir_factory` and read
+/// from there.  Like `diff_archives()`, the accounts are counted in passes over ranges of pubkeys,
+/// and only the pubkey, version, and owner of the accounts in one range are kept at a time.  Note
+/// that an incremental snapshot archive only contains the accounts changed since its base slot.
+pub fn owner_histogram(
+    archive: impl AsRef<Path>,
+    temp_dir_factory: &dyn TempDirFactory,
+) -> Result<BTreeMap<Pubkey, u64>> {
+    owner_histogram_in_passes(
+        archive.as_ref(),
+        temp_dir_factory,
+        MAX_ARCHIVE_ACCOUNTS_PER_PASS,
+    )
 }
 
-/// Streams unpacked files across channel
-fn streaming_unarchive_snapshot(
-    file_sender: Sender<PathBuf>,
-    account_paths: Vec<PathBuf>,
-    ledger_dir: PathBuf,
-    snapshot_archive_path: PathBuf,
-    archive_format: ArchiveFormat,
-    num_threads: usize,
-) -> Vec<JoinHandle<()>> {
-    let account_paths = Arc::new(account_paths);
-    let ledger_dir = Arc::new(ledger_dir);
-    let shared_buffer = untar_snapshot_create_shared_buffer(&snapshot_archive_path, archive_format);
+fn owner_histogram_in_passes(
+    archive: &Path,
+    temp_dir_factory: &dyn TempDirFactory,
+    max_accounts_per_pass: usize,
+) -> Result<BTreeMap<Pubkey, u64>> {
+    let (_unpack_dir, storage) = unarchive_snapshot_storage_in_temp_dir(
+        archive,
+        "snapshot owner histogram untar",
+        temp_dir_factory,
+    )?;
 
-    // All shared buffer readers need to be created before the threads are spawned
-    #[allow(clippy::needless_collect)]
-    let archives: Vec<_> = (0..num_threads)
-        .map(|_| {
-            let reader = SharedBufferReader::new(&shared_buffer);
-            Archive::new(reader)
-        })
-        .collect();
+    let bin_calculator = PubkeyBinCalculator24::new(PUBKEY_BINS_FOR_CALCULATING_HASHES);
+    let mut histogram = BTreeMap::new();
+    for bin_range in archive_pass_bin_ranges(&[&storage], max_accounts_per_pass) {
+        // For each account, the latest version is in the highest slot, with the highest write
+        // version.  The owner is None if the latest version has zero lamports.
+        let mut accounts = HashMap::<Pubkey, (Slot, StoredMetaWriteVersion, Option<Pubkey>)>::new();
+        for entry in storage.iter() {
+            let slot = *entry.key();
+            for store in entry.value().read().unwrap().values() {
+                store.accounts.account_iter().for_each(|stored_account| {
+                    if !bin_range
+                        .contains(&bin_calculator.bin_from_pubkey(&stored_account.meta.pubkey))
+                    {
+                        return;
+                    }
+                    let version = (slot, stored_account.meta.write_version);
+                    let is_latest = accounts
+                        .get(&stored_account.meta.pubkey)
+                        .map_or(true, |(slot, write_version, _)| {
+                            (*slot, *write_version) < version
+                        });
+                    if is_latest {
+                        let owner = (stored_account.account_meta.lamports != 0)
+                            .then(|| stored_account.account_meta.owner);
+                        accounts.insert(stored_account.meta.pubkey, (version.0, version.1, owner));
+                    }
+                });
+            }
+        }
 
-    archives
-        .into_iter()
-        .enumerate()
-        .map(|(thread_index, archive)| {
-            let parallel_selector = Some(ParallelSelector {
-                index: thread_index,
-                divisions: num_threads,
-            });
+        for owner in accounts
+            .into_values()
+            .filter_map(|(_slot, _write_version, owner)| owner)
+        {
+            *histogram.entry(owner).or_default() += 1;
+        }
+    }
+    Ok(histogram)
+}
 
-            spawn_unpack_snapshot_thread(
-                file_sender.clone(),
-                account_paths.clone(),
-                ledger_dir.clone(),
-                archive,
-                parallel_selector,
-                thread_index,
-            )
+/// Split the `PUBKEY_BINS_FOR_CALCULATING_HASHES` pubkey bins into ranges, in pubkey order, so
+/// that each range holds about `max_accounts_per_pass` of the accounts stored in `storages`
+fn archive_pass_bin_ranges(
+    storages: &[&AccountStorageMap],
+    max_accounts_per_pass: usize,
+) -> Vec<Range<usize>> {
+    let num_accounts: usize = storages
+        .iter()
+        .flat_map(|storage| storage.iter())
+        .map(|entry| {
+            entry
+                .value()
+                .read()
+                .unwrap()
+                .values()
+                .map(|store| store.approx_stored_count())
+                .sum::<usize>()
+        })
+        .sum();
+    let max_accounts_per_pass = max_accounts_per_pass.max(1);
+    let num_passes = ((num_accounts + max_accounts_per_pass - 1) / max_accounts_per_pass)
+        .clamp(1, PUBKEY_BINS_FOR_CALCULATING_HASHES);
+    (0..num_passes)
+        .map(|pass| {
+            pass * PUBKEY_BINS_FOR_CALCULATING_HASHES / num_passes
+                ..(pass + 1) * PUBKEY_BINS_FOR_CALCULATING_HASHES / num_passes
         })
         .collect()
 }
 
-/// Perform the common tasks when unarchiving a snapshot.  Handles creating the temporary
-/// directories, untaring, reading the version file, and then returning those fields plus the
-/// rebuilt storage
-fn unarchive_snapshot<P, Q>(
-    bank_snapshots_dir: P,
-    unpacked_snapshots_dir_prefix: &'static str,
-    snapshot_archive_path: Q,
-    measure_name: &'static str,
-    account_paths: &[PathBuf],
-    archive_format: ArchiveFormat,
-    parallel_divisions: usize,
-    next_append_vec_id: Arc<AtomicU32>,
-) -> Result<UnarchivedSnapshot>
-where
-    P: AsRef<Path>,
-    Q: AsRef<Path>,
-{
-    let unpack_dir = tempfile::Builder::new()
-        .prefix(unpacked_snapshots_dir_prefix)
-        .tempdir_in(bank_snapshots_dir)?;
-    let unpacked_snapshots_dir = unpack_dir.path().join("snapshots");
-
-    let (file_sender, file_receiver) = crossbeam_channel::unbounded();
-    streaming_unarchive_snapshot(
-        file_sender,
-        account_paths.to_vec(),
-        unpack_dir.path().to_path_buf(),
-        snapshot_archive_path.as_ref().to_path_buf(),
-        archive_format,
-        parallel_divisions,
-    );
+/// Get the pubkey and hash of the latest version of every (non-zero-lamport) account in `storage`
+/// whose pubkey is in `bin_range`, sorted by pubkey
+fn get_sorted_account_hashes(
+    storage: &AccountStorageMap,
+    bin_range: &Range<usize>,
+) -> Vec<(Pubkey, Hash)> {
+    let accounts = get_latest_account_hashes(storage, bin_range);
 
-    let num_rebuilder_threads = num_cpus::get_physical()
-        .saturating_sub(parallel_divisions)
-        .max(1);
-    let (version_and_storages, measure_untar) = measure!(
-        SnapshotStorageRebuilder::rebuild_storage(
-            file_receiver,
-            num_rebuilder_threads,
-            next_append_vec_id
-        )?,
-        measure_name
-    );
-    info!("{}", measure_untar);
+    // zero-lamport accounts hash to the default hash
+    let mut accounts = accounts
+        .into_iter()
+        .filter(|(_pubkey, (_slot, _write_version, hash))| *hash != Hash::default())
+        .map(|(pubkey, (_slot, _write_version, hash))| (pubkey, hash))
+        .collect::<Vec<_>>();
+    accounts.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    accounts
+}
 
-    let RebuiltSnapshotStorage {
-        snapshot_version,
-        storage,
-    } = version_and_storages;
-    Ok(UnarchivedSnapshot {
-        unpack_dir,
-        storage,
-        unpacked_snapshots_dir_and_version: UnpackedSnapshotsDirAndVersion {
-            unpacked_snapshots_dir,
-            snapshot_version,
-        },
-        measure_untar,
-    })
-}
-
-/// Reads the `snapshot_version` from a file. Before opening the file, its size
-/// is compared to `MAX_SNAPSHOT_VERSION_FILE_SIZE`. If the size exceeds this
-/// threshold, it is not opened and an error is returned.
-fn snapshot_version_from_file(path: impl AsRef<Path>) -> Result<String> {
-    // Check file size.
-    let file_size = fs::metadata(&path)?.len();
-    if file_size > MAX_SNAPSHOT_VERSION_FILE_SIZE {
-        let error_message = format!(
-            "snapshot version file too large: {} has {} bytes (max size is {} bytes)",
-            path.as_ref().display(),
-            file_size,
-            MAX_SNAPSHOT_VERSION_FILE_SIZE,
-        );
-        return Err(get_io_error(&error_message));
+/// Get the slot, write version, and hash of the latest version of every account in `storage`
+/// whose pubkey is in `bin_range`
+fn get_latest_account_hashes(
+    storage: &AccountStorageMap,
+    bin_range: &Range<usize>,
+) -> HashMap<Pubkey, (Slot, StoredMetaWriteVersion, Hash)> {
+    let bin_calculator = PubkeyBinCalculator24::new(PUBKEY_BINS_FOR_CALCULATING_HASHES);
+    // For each account, the latest version is in the highest slot, with the highest write version
+    let mut accounts = HashMap::<Pubkey, (Slot, StoredMetaWriteVersion, Hash)>::new();
+    for entry in storage.iter() {
+        let slot = *entry.key();
+        for store in entry.value().read().unwrap().values() {
+            store.accounts.account_iter().for_each(|stored_account| {
+                if !bin_range.contains(&bin_calculator.bin_from_pubkey(&stored_account.meta.pubkey))
+                {
+                    return;
+                }
+                let version = (slot, stored_account.meta.write_version);
+                let is_latest = accounts
+                    .get(&stored_account.meta.pubkey)
+                    .map_or(true, |(slot, write_version, _)| {
+                        (*slot, *write_version) < version
+                    });
+                if is_latest {
+                    let hash = AccountsDb::hash_account_data(
+                        slot,
+                        stored_account.account_meta.lamports,
+                        &stored_account.account_meta.owner,
+                        stored_account.account_meta.executable,
+                        stored_account.account_meta.rent_epoch,
+                        stored_account.data,
+                        &stored_account.meta.pubkey,
+                        IncludeSlotInHash::RemoveSlot,
+                    );
+                    accounts.insert(stored_account.meta.pubkey, (version.0, version.1, hash));
+                }
+            });
+        }
     }
+    accounts
+}
 
-    // Read snapshot_version from file.
-    let mut snapshot_version = String::new();
-    File::open(path).and_then(|mut f| f.read_to_string(&mut snapshot_version))?;
-    Ok(snapshot_version.trim().to_string())
+/// The maximum number of accounts in a batch passed to `ColumnarAccountsWriter::write_batch()`
+pub const EXPORT_ACCOUNTS_BATCH_SIZE: usize = 8192;
+
+/// A batch of accounts exported from a snapshot archive, stored column by column
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AccountsColumns {
+    pub pubkeys: Vec<Pubkey>,
+    pub lamports: Vec<u64>,
+    pub owners: Vec<Pubkey>,
+    pub data_lens: Vec<u64>,
+    /// the slot of the storage the account was stored in
+    pub slots: Vec<Slot>,
 }
 
-/// Check if an incremental snapshot is compatible with a full snapshot.  This is done by checking
-/// if the incremental snapshot's base slot is the same as the full snapshot's slot.
-fn check_are_snapshots_compatible(
-    full_snapshot_archive_info: &FullSnapshotArchiveInfo,
-    incremental_snapshot_archive_info: Option<&IncrementalSnapshotArchiveInfo>,
-) -> Result<()> {
-    if incremental_snapshot_archive_info.is_none() {
-        return Ok(());
+impl AccountsColumns {
+    pub fn len(&self) -> usize {
+        self.pubkeys.len()
     }
 
-    let incremental_snapshot_archive_info = incremental_snapshot_archive_info.unwrap();
+    pub fn is_empty(&self) -> bool {
+        self.pubkeys.is_empty()
+    }
 
-    (full_snapshot_archive_info.slot() == incremental_snapshot_archive_info.base_slot())
-        .then_some(())
-        .ok_or_else(|| {
-            SnapshotError::MismatchedBaseSlot(
-                full_snapshot_archive_info.slot(),
-                incremental_snapshot_archive_info.base_slot(),
-            )
-        })
+    fn clear(&mut self) {
+        self.pubkeys.clear();
+        self.lamports.clear();
+        self.owners.clear();
+        self.data_lens.clear();
+        self.slots.clear();
+    }
 }
 
-/// Get the `&str` from a `&Path`
-pub fn path_to_file_name_str(path: &Path) -> Result<&str> {
-    path.file_name()
-        .ok_or_else(|| SnapshotError::PathToFileNameError(path.to_path_buf()))?
-        .to_str()
-        .ok_or_else(|| SnapshotError::FileNameToStrError(path.to_path_buf()))
+/// Writes batches of exported accounts to a columnar format, e.g. a Parquet file for analytics
+/// pipelines.  See `export_accounts_columnar()`.
+pub trait ColumnarAccountsWriter {
+    fn write_batch(&mut self, columns: &AccountsColumns) -> IoResult<()>;
 }
 
-pub fn build_snapshot_archives_remote_dir(snapshot_archives_dir: impl AsRef<Path>) -> PathBuf {
-    snapshot_archives_dir
-        .as_ref()
-        .join(SNAPSHOT_ARCHIVE_DOWNLOAD_DIR)
+/// Export the accounts in a snapshot archive to `writer`, and return the number of accounts
+///
+/// Every stored version of every account is exported, along with the slot it was stored in, so
+/// the latest version of an account is the one in the highest slot.  The account storages are
+/// unpacked into a temporary directory from `temp_dir_factory` and read from there, and accounts are passed to `writer` in
+/// batches of at most `EXPORT_ACCOUNTS_BATCH_SIZE`, so memory usage does not depend on the size
+/// of the archive.
+pub fn export_accounts_columnar(
+    archive: impl AsRef<Path>,
+    writer: &mut impl ColumnarAccountsWriter,
+    temp_dir_factory: &dyn TempDirFactory,
+) -> Result<usize> {
+    let (_unpack_dir, storage) = unarchive_snapshot_storage_in_temp_dir(
+        archive.as_ref(),
+        "snapshot export untar",
+        temp_dir_factory,
+    )?;
+
+    let mut num_accounts = 0;
+    let mut columns = AccountsColumns::default();
+    for entry in storage.iter() {
+        let slot = *entry.key();
+        for store in entry.value().read().unwrap().values() {
+            for stored_account in store.accounts.account_iter() {
+                columns.pubkeys.push(stored_account.meta.pubkey);
+                columns.lamports.push(stored_account.account_meta.lamports);
+                columns.owners.push(stored_account.account_meta.owner);
+                columns.data_lens.push(stored_account.meta.data_len);
+                columns.slots.push(slot);
+                if columns.len() == EXPORT_ACCOUNTS_BATCH_SIZE {
+                    writer.write_batch(&columns)?;
+                    num_accounts += columns.len();
+                    columns.clear();
+                }
+            }
+        }
+    }
+    if !columns.is_empty() {
+        writer.write_batch(&columns)?;
+        num_accounts += columns.len();
+    }
+    Ok(num_accounts)
 }
 
-/// Build the full snapshot archive path from its components: the snapshot archives directory, the
-/// snapshot slot, the accounts hash, and the archive format.
-pub fn build_full_snapshot_archive_path(
-    full_snapshot_archives_dir: impl AsRef<Path>,
-    slot: Slot,
-    hash: &Hash,
-    archive_format: ArchiveFormat,
-) -> PathBuf {
-    full_snapshot_archives_dir.as_ref().join(format!(
-        "snapshot-{}-{}.{}",
-        slot,
-        hash,
-        archive_format.extension(),
-    ))
+/// Snapshot archive metadata, as exported by `export_archive_metadata_json()`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotArchiveMetadata {
+    pub slot: Slot,
+    pub epoch: Epoch,
+    /// the full snapshot slot this archive is based on, if this is an incremental snapshot archive
+    pub base_slot: Option<Slot>,
+    pub bank_hash: String,
+    pub accounts_hash: String,
+    pub epoch_accounts_hash: Option<String>,
+    /// the number of accounts stored in the archive's account storages; an account stored in
+    /// several slots is counted once per slot
+    pub account_count: u64,
+    pub storage_count: usize,
 }
 
-/// Build the incremental snapshot archive path from its components: the snapshot archives
-/// directory, the snapshot base slot, the snapshot slot, the accounts hash, and the archive
-/// format.
-pub fn build_incremental_snapshot_archive_path(
-    incremental_snapshot_archives_dir: impl AsRef<Path>,
-    base_slot: Slot,
-    slot: Slot,
-    hash: &Hash,
-    archive_format: ArchiveFormat,
-) -> PathBuf {
-    incremental_snapshot_archives_dir.as_ref().join(format!(
-        "incremental-snapshot-{}-{}-{}.{}",
+/// Write the metadata of a snapshot archive to `out` as JSON.
+///
+/// The manifest does not record the number of accounts, so the account storages are counted too.
+/// They are unpacked one at a time into a temporary directory next to the archive, which is
+/// removed afterwards, so at most one storage is on disk at once.
+pub fn export_archive_metadata_json(archive: impl AsRef<Path>, out: &mut impl Write) -> Result<()> {
+    let archive = archive.as_ref();
+    let (slot, base_slot, archive_format) = parse_snapshot_archive_path(archive)?;
+    let (bank_fields, accounts_db_fields) = read_archived_manifest(archive, slot, archive_format)?;
+    let account_count = count_archived_accounts(
+        archive,
+        archive_format,
+        &snapshot_storage_lengths_from_fields(&accounts_db_fields),
+    )?;
+
+    let bank_hash_info = accounts_db_fields.bank_hash_info();
+    let metadata = SnapshotArchiveMetadata {
+        slot: bank_fields.slot,
+        epoch: bank_fields.epoch,
         base_slot,
-        slot,
-        hash,
-        archive_format.extension(),
-    ))
+        bank_hash: bank_fields.hash.to_string(),
+        accounts_hash: bank_hash_info.snapshot_hash.to_string(),
+        epoch_accounts_hash: bank_fields
+            .epoch_accounts_hash
+            .map(|epoch_accounts_hash| epoch_accounts_hash.to_string()),
+        account_count,
+        storage_count: accounts_db_fields.storage_count(),
+    };
+    serde_json::to_writer(out, &metadata).map_err(IoError::from)?;
+    Ok(())
 }
 
-/// Parse a full snapshot archive filename into its Slot, Hash, and Archive Format
-pub(crate) fn parse_full_snapshot_archive_filename(
-    archive_filename: &str,
-) -> Result<(Slot, Hash, ArchiveFormat)> {
-    lazy_static! {
-        static ref RE: Regex = Regex::new(FULL_SNAPSHOT_ARCHIVE_FILENAME_REGEX).unwrap();
+/// Count the accounts stored in a snapshot archive's account storages
+///
+/// `storage_lengths` are the storages' lengths from the manifest, by slot and id.  Each storage is
+/// unpacked into a temporary directory next to the archive, counted, and removed before the next
+/// one is unpacked.
+fn count_archived_accounts(
+    archive: &Path,
+    archive_format: ArchiveFormat,
+    storage_lengths: &HashMap<Slot, HashMap<SerializedAppendVecId, usize>>,
+) -> Result<u64> {
+    let archive_dir = archive
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let unpack_dir = tempfile::Builder::new()
+        .prefix(&format!("{}count-", TMP_SNAPSHOT_ARCHIVE_PREFIX))
+        .tempdir_in(archive_dir)?;
+
+    let shared_buffer =
+        untar_snapshot_create_shared_buffer(archive, archive_format, None, None, None)?;
+    let mut tar = Archive::new(SharedBufferReader::new(&shared_buffer));
+    let mut account_count = 0;
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        if !path.starts_with("accounts") || !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let current_len = path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .and_then(|file_name| file_name.split_once('.'))
+            .and_then(|(slot, id)| Some((slot.parse().ok()?, id.parse().ok()?)))
+            .and_then(|(slot, id): (Slot, SerializedAppendVecId)| {
+                storage_lengths.get(&slot)?.get(&id).copied()
+            })
+            .ok_or_else(|| {
+                get_io_error(&format!(
+                    "account storage not in the snapshot manifest: {}",
+                    path.display()
+                ))
+            })?;
+        let storage_path = unpack_dir.path().join(path.file_name().unwrap());
+        entry.unpack(&storage_path)?;
+        // the append vec removes its file when dropped
+        let (_append_vec, num_accounts) = AppendVec::new_from_file(&storage_path, current_len)?;
+        account_count += num_accounts as u64;
     }
+    Ok(account_count)
+}
 
-    let do_parse = || {
-        RE.captures(archive_filename).and_then(|captures| {
-            let slot = captures
-                .name("slot")
-                .map(|x| x.as_str().parse::<Slot>())?
-                .ok()?;
-            let hash = captures
-                .name("hash")
-                .map(|x| x.as_str().parse::<Hash>())?
-                .ok()?;
-            let archive_format = captures
-                .name("ext")
-                .map(|x| x.as_str().parse::<ArchiveFormat>())?
-                .ok()?;
+/// Verify that the bank hash of a snapshot archive matches at least `threshold` of the bank
+/// hashes reported by peers
+///
+/// This generalizes trusting a single known hash to trusting a quorum of peers, e.g. when booting
+/// from a snapshot downloaded from an untrusted source.  Only the bank snapshot file (the
+/// "manifest") is read from the archive.  Each entry in `peer_hashes` is one peer's report,
+/// so a peer should not be listed more than once.
+pub fn verify_against_peer_consensus(
+    archive: impl AsRef<Path>,
+    peer_hashes: &[Hash],
+    threshold: usize,
+) -> Result<()> {
+    let archive = archive.as_ref();
+    let (slot, _base_slot, archive_format) = parse_snapshot_archive_path(archive)?;
+    let (bank_fields, _accounts_db_fields) = read_archived_manifest(archive, slot, archive_format)?;
 
-            Some((slot, hash, archive_format))
-        })
+    let num_matches = peer_hashes
+        .iter()
+        .filter(|peer_hash| **peer_hash == bank_fields.hash)
+        .count();
+    if num_matches < threshold {
+        return Err(SnapshotError::PeerConsensusNotReached(
+            bank_fields.hash,
+            num_matches,
+            threshold,
+        ));
+    }
+    Ok(())
+}
+
+/// Verify that a snapshot archive belongs to the cluster of `genesis_config`
+///
+/// Snapshot archives do not record the genesis hash, so this performs the same sanity checks
+/// between the bank snapshot and the genesis config that loading the bank does, without having to
+/// unpack the account storages.  Only the manifest is read from the archive.
+pub fn verify_archive_genesis_config(
+    archive: impl AsRef<Path>,
+    genesis_config: &GenesisConfig,
+) -> Result<()> {
+    let archive = archive.as_ref();
+    let (slot, _base_slot, archive_format) = parse_snapshot_archive_path(archive)?;
+    let (bank_fields, _accounts_db_fields) = read_archived_manifest(archive, slot, archive_format)?;
+
+    let poh_config = &genesis_config.poh_config;
+    let mismatch = if bank_fields.genesis_creation_time != genesis_config.creation_time {
+        Some("genesis creation time")
+    } else if bank_fields.hashes_per_tick != poh_config.hashes_per_tick {
+        Some("hashes per tick")
+    } else if bank_fields.ticks_per_slot != genesis_config.ticks_per_slot {
+        Some("ticks per slot")
+    } else if bank_fields.ns_per_slot
+        != poh_config.target_tick_duration.as_nanos() * genesis_config.ticks_per_slot as u128
+    {
+        Some("ns per slot")
+    } else if bank_fields.epoch_schedule != genesis_config.epoch_schedule {
+        Some("epoch schedule")
+    } else {
+        None
     };
+    match mismatch {
+        Some(field) => Err(SnapshotError::MismatchedGenesisConfig(field)),
+        None => Ok(()),
+    }
+}
 
-    do_parse().ok_or_else(|| {
-        SnapshotError::ParseSnapshotArchiveFileNameError(archive_filename.to_string())
-    })
+/// The result of `verify_snapshot_archive_structure()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotArchiveVerification {
+    pub slot: Slot,
+    /// the accounts hash, which the archive's file name and manifest agree on
+    pub hash: Hash,
+    pub snapshot_type: SnapshotType,
+    pub archive_format: ArchiveFormat,
 }
 
-/// Parse an incremental snapshot archive filename into its base Slot, actual Slot, Hash, and Archive Format
-pub(crate) fn parse_incremental_snapshot_archive_filename(
-    archive_filename: &str,
-) -> Result<(Slot, Slot, Hash, ArchiveFormat)> {
-    lazy_static! {
-        static ref RE: Regex = Regex::new(INCREMENTAL_SNAPSHOT_ARCHIVE_FILENAME_REGEX).unwrap();
-    }
+/// Verify the structure of a snapshot archive, without unpacking it or deserializing a bank from it
+///
+/// Every entry in the archive is read through, so a truncated or corrupt archive is caught, but
+/// nothing is written to disk.  The version and status cache files must be present, and the
+/// manifest must agree with the archive's file name on the slot and the accounts hash.  The
+/// account storages are not hashed, so this does not catch accounts that do not match the accounts
+/// hash; only rebuilding a bank from the archive does.
+pub fn verify_snapshot_archive_structure(
+    archive: impl AsRef<Path>,
+) -> Result<SnapshotArchiveVerification> {
+    let archive = archive.as_ref();
+    let archive_filename = path_to_file_name_str(archive)?;
+    let (slot, hash, archive_format, snapshot_type) =
+        match parse_full_snapshot_archive_filename(archive_filename) {
+            Ok((slot, hash, archive_format)) => {
+                (slot, hash, archive_format, SnapshotType::FullSnapshot)
+            }
+            Err(_) => {
+                let (base_slot, slot, hash, archive_format) =
+                    parse_incremental_snapshot_archive_filename(archive_filename)?;
+                (
+                    slot,
+                    hash,
+                    archive_format,
+                    SnapshotType::IncrementalSnapshot(base_slot),
+                )
+            }
+        };
 
-    let do_parse = || {
-        RE.captures(archive_filename).and_then(|captures| {
-            let base_slot = captures
-                .name("base")
-                .map(|x| x.as_str().parse::<Slot>())?
-                .ok()?;
-            let slot = captures
-                .name("slot")
-                .map(|x| x.as_str().parse::<Slot>())?
-                .ok()?;
-            let hash = captures
-                .name("hash")
-                .map(|x| x.as_str().parse::<Hash>())?
-                .ok()?;
-            let archive_format = captures
-                .name("ext")
-                .map(|x| x.as_str().parse::<ArchiveFormat>())?
-                .ok()?;
+    let version_path = Path::new("version");
+    let status_cache_path = Path::new("snapshots").join(SNAPSHOT_STATUS_CACHE_FILENAME);
+    let manifest_path = get_archived_manifest_path(slot);
+    let shared_buffer =
+        untar_snapshot_create_shared_buffer(archive, archive_format, None, None, None)?;
+    let mut tar = Archive::new(SharedBufferReader::new(&shared_buffer));
+    let mut has_version = false;
+    let mut has_status_cache = false;
+    let mut manifest = None;
+    // Advancing to the next entry reads through whatever is left of the current one, e.g. the
+    // account storages, without writing it anywhere.
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.into_owned();
+        if path == version_path {
+            has_version = true;
+        } else if path == status_cache_path {
+            has_status_cache = true;
+        } else if path == manifest_path {
+            if entry.header().size()? > MAX_SNAPSHOT_DATA_FILE_SIZE {
+                return Err(get_io_error(&format!(
+                    "snapshot manifest too large: {} (max size is {} bytes)",
+                    manifest_path.display(),
+                    MAX_SNAPSHOT_DATA_FILE_SIZE,
+                )));
+            }
+            manifest = Some(fields_from_stream(
+                SerdeStyle::Newer,
+                &mut BufReader::new(&mut entry),
+            )?);
+        }
+    }
 
-            Some((base_slot, slot, hash, archive_format))
-        })
-    };
+    for (path, is_present) in [
+        (version_path, has_version),
+        (status_cache_path.as_path(), has_status_cache),
+    ] {
+        if !is_present {
+            return Err(ArchiveStructureError::MissingEntry(path.to_path_buf()).into());
+        }
+    }
+    let (bank_fields, accounts_db_fields) =
+        manifest.ok_or_else(|| ArchiveStructureError::MissingEntry(manifest_path))?;
+    let manifest_hash = accounts_db_fields.bank_hash_info().snapshot_hash;
+    if bank_fields.slot != slot || manifest_hash != hash {
+        return Err(SnapshotError::MismatchedSlotHash(
+            (bank_fields.slot, manifest_hash),
+            (slot, hash),
+        ));
+    }
 
-    do_parse().ok_or_else(|| {
-        SnapshotError::ParseSnapshotArchiveFileNameError(archive_filename.to_string())
+    Ok(SnapshotArchiveVerification {
+        slot,
+        hash,
+        snapshot_type,
+        archive_format,
     })
 }
 
-/// Walk down the snapshot archive to collect snapshot archive file info
-fn get_snapshot_archives<T, F>(snapshot_archives_dir: &Path, cb: F) -> Vec<T>
-where
-    F: Fn(PathBuf) -> Result<T>,
-{
-    let walk_dir = |dir: &Path| -> Vec<T> {
-        let entry_iter = fs::read_dir(dir);
-        match entry_iter {
-            Err(err) => {
+/// Check whether a snapshot archive can be loaded by a binary that uses `target` snapshots
+///
+/// This combines the two checks loading the archive would fail on: the archive's snapshot version
+/// must be `target`, and its bank snapshot file (the "manifest") must deserialize in the format
+/// `target` uses, so a manifest written with fields or features unknown to `target` is caught.
+/// Only the manifest is read from the archive.
+///
+/// Returns `Ok(false)` if the archive is incompatible, and an error if it cannot be read.
+pub fn is_compatible_with_version(
+    archive: impl AsRef<Path>,
+    target: SnapshotVersion,
+) -> Result<bool> {
+    let archive = archive.as_ref();
+    let (slot, _base_slot, archive_format) = parse_snapshot_archive_path(archive)?;
+    let manifest_path = get_archived_manifest_path(slot);
+
+    // The version file is archived first, and the manifest before the accounts, so neither
+    // requires reading through the account storages.
+    let shared_buffer =
+        untar_snapshot_create_shared_buffer(archive, archive_format, None, None, None)?;
+    let mut tar = Archive::new(SharedBufferReader::new(&shared_buffer));
+    let mut snapshot_version = None;
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        if path == Path::new("version") {
+            let file_size = entry.header().size()?;
+            if file_size > MAX_SNAPSHOT_VERSION_FILE_SIZE {
+                return Err(get_io_error(&format!(
+                    "snapshot version file too large: {} bytes (max size is {} bytes)",
+                    file_size, MAX_SNAPSHOT_VERSION_FILE_SIZE,
+                )));
+            }
+            let mut version = String::new();
+            entry.read_to_string(&mut version)?;
+            snapshot_version = Some(version.trim().to_string());
+        } else if path == manifest_path {
+            let snapshot_version = snapshot_version.ok_or_else(|| {
+                get_io_error(&format!(
+                    "snapshot version file not found in archive: {}",
+                    archive.display()
+                ))
+            })?;
+            if snapshot_version.parse::<SnapshotVersion>() != Ok(target) {
                 info!(
-                    "Unable to read snapshot archives directory: err: {}, path: {}",
-                    err,
-                    dir.display()
+                    "snapshot archive {} has version {}, which is not compatible with version {}",
+                    archive.display(),
+                    snapshot_version,
+                    target,
                 );
-                vec![]
+                return Ok(false);
             }
-            Ok(entries) => entries
-                .filter_map(|entry| entry.map_or(None, |entry| cb(entry.path()).ok()))
-                .collect(),
-        }
-    };
 
-    let mut ret = walk_dir(snapshot_archives_dir);
-    let remote_dir = build_snapshot_archives_remote_dir(snapshot_archives_dir);
-    if remote_dir.exists() {
-        ret.append(&mut walk_dir(remote_dir.as_ref()));
+            if entry.header().size()? > MAX_SNAPSHOT_DATA_FILE_SIZE {
+                return Err(get_io_error(&format!(
+                    "snapshot manifest too large: {} (max size is {} bytes)",
+                    manifest_path.display(),
+                    MAX_SNAPSHOT_DATA_FILE_SIZE,
+                )));
+            }
+            let serde_style = match target {
+                SnapshotVersion::V1_2_0 => SerdeStyle::Newer,
+            };
+            return match fields_from_stream(serde_style, &mut BufReader::new(entry)) {
+                Ok(_) => Ok(true),
+                Err(err) => {
+                    info!(
+                        "snapshot archive {} manifest is not compatible with version {}: {}",
+                        archive.display(),
+                        target,
+                        err,
+                    );
+                    Ok(false)
+                }
+            };
+        }
     }
-    ret
+    Err(get_io_error(&format!(
+        "snapshot manifest not found in archive: {}",
+        archive.display()
+    )))
 }
 
-/// Get a list of the full snapshot archives from a directory
-pub fn get_full_snapshot_archives(
-    full_snapshot_archives_dir: impl AsRef<Path>,
-) -> Vec<FullSnapshotArchiveInfo> {
-    get_snapshot_archives(
-        full_snapshot_archives_dir.as_ref(),
-        FullSnapshotArchiveInfo::new_from_path,
-    )
+/// Read the bank snapshot file (the "manifest") from a snapshot archive
+fn read_archived_manifest(
+    archive: &Path,
+    slot: Slot,
+    archive_format: ArchiveFormat,
+) -> Result<(
+    BankFieldsToDeserialize,
+    AccountsDbFields<SerializableAccountStorageEntry>,
+)> {
+    // The snapshots dir is archived before the accounts, so the manifest is found without
+    // having to read through the account storages.
+    let manifest_path = get_archived_manifest_path(slot);
+    let shared_buffer =
+        untar_snapshot_create_shared_buffer(archive, archive_format, None, None, None)?;
+    let mut tar = Archive::new(SharedBufferReader::new(&shared_buffer));
+    let mut manifest = None;
+    for entry in tar.entries()? {
+        let entry = entry?;
+        if entry.path()? != manifest_path {
+            continue;
+        }
+        if entry.header().size()? > MAX_SNAPSHOT_DATA_FILE_SIZE {
+            return Err(get_io_error(&format!(
+                "snapshot manifest too large: {} (max size is {} bytes)",
+                manifest_path.display(),
+                MAX_SNAPSHOT_DATA_FILE_SIZE,
+            )));
+        }
+        manifest = Some(fields_from_stream(
+            SerdeStyle::Newer,
+            &mut BufReader::new(entry),
+        )?);
+        break;
+    }
+    manifest.ok_or_else(|| {
+        get_io_error(&format!(
+            "snapshot manifest not found in archive: {}",
+            archive.display()
+        ))
+    })
 }
 
-/// Get a list of the incremental snapshot archives from a directory
-pub fn get_incremental_snapshot_archives(
-    incremental_snapshot_archives_dir: impl AsRef<Path>,
-) -> Vec<IncrementalSnapshotArchiveInfo> {
-    get_snapshot_archives(
-        incremental_snapshot_archives_dir.as_ref(),
-        IncrementalSnapshotArchiveInfo::new_from_path,
-    )
-}
-
-/// Get the highest slot of the full snapshot archives in a directory
-pub fn get_highest_full_snapshot_archive_slot(
-    full_snapshot_archives_dir: impl AsRef<Path>,
-) -> Option<Slot> {
-    get_highest_full_snapshot_archive_info(full_snapshot_archives_dir)
-        .map(|full_snapshot_archive_info| full_snapshot_archive_info.slot())
-}
+/// Read the bank snapshot file (the "manifest") and the status cache from a snapshot archive, and
+/// verify them, without untarring the account storages
+///
+/// The manifest must be for the archive's slot, and the status cache must pass the same
+/// structural checks as when the bank is rebuilt.  Both are archived before the accounts, so this
+/// does not read through the account storages.
+fn verify_archived_manifest_and_status_cache(
+    snapshot_archive_info: &impl SnapshotArchiveInfoGetter,
+    extraction_buffer_bytes: Option<usize>,
+    zstd_dictionary: Option<&[u8]>,
+) -> Result<()> {
+    let archive = snapshot_archive_info.path();
+    let slot = snapshot_archive_info.slot();
+    let manifest_path = get_archived_manifest_path(slot);
+    let status_cache_path = Path::new("snapshots").join(SNAPSHOT_STATUS_CACHE_FILENAME);
+
+    let shared_buffer = untar_snapshot_create_shared_buffer(
+        archive,
+        snapshot_archive_info.archive_format(),
+        extraction_buffer_bytes,
+        zstd_dictionary,
+        None,
+    )?;
+    let mut tar = Archive::new(SharedBufferReader::new(&shared_buffer));
+    let mut manifest = None;
+    let mut slot_deltas = None;
+    for entry in tar.entries()? {
+        let entry = entry?;
+        let path = entry.path()?.into_owned();
+        if path != manifest_path && path != status_cache_path {
+            continue;
+        }
+        if entry.header().size()? > MAX_SNAPSHOT_DATA_FILE_SIZE {
+            return Err(get_io_error(&format!(
+                "snapshot data file too large: {} (max size is {} bytes)",
+                path.display(),
+                MAX_SNAPSHOT_DATA_FILE_SIZE,
+            )));
+        }
+        let mut stream = BufReader::new(entry);
+        if path == manifest_path {
+            manifest = Some(fields_from_stream(SerdeStyle::Newer, &mut stream)?);
+        } else {
+            let status_cache: Vec<BankSlotDelta> = bincode::options()
+                .with_limit(MAX_SNAPSHOT_DATA_FILE_SIZE)
+                .with_fixint_encoding()
+                .allow_trailing_bytes()
+                .deserialize_from(&mut stream)?;
+            slot_deltas = Some(status_cache);
+        }
+        if manifest.is_some() && slot_deltas.is_some() {
+            break;
+        }
+    }
 
-/// Get the highest slot of the incremental snapshot archives in a directory, for a given full
-/// snapshot slot
-pub fn get_highest_incremental_snapshot_archive_slot(
-    incremental_snapshot_archives_dir: impl AsRef<Path>,
-    full_snapshot_slot: Slot,
-) -> Option<Slot> {
-    get_highest_incremental_snapshot_archive_info(
-        incremental_snapshot_archives_dir,
-        full_snapshot_slot,
-    )
-    .map(|incremental_snapshot_archive_info| incremental_snapshot_archive_info.slot())
+    let (bank_fields, accounts_db_fields) =
+        manifest.ok_or(ArchiveStructureError::MissingEntry(manifest_path))?;
+    let slot_deltas = slot_deltas.ok_or(ArchiveStructureError::MissingEntry(status_cache_path))?;
+    if bank_fields.slot != slot {
+        return Err(SnapshotError::MismatchedSlotHash(
+            (
+                bank_fields.slot,
+                accounts_db_fields.bank_hash_info().snapshot_hash,
+            ),
+            (slot, *snapshot_archive_info.hash()),
+        ));
+    }
+    verify_slot_deltas_structural(&slot_deltas, bank_fields.slot)?;
+    Ok(())
 }
 
-/// Get the path (and metadata) for the full snapshot archive with the highest slot in a directory
-pub fn get_highest_full_snapshot_archive_info(
-    full_snapshot_archives_dir: impl AsRef<Path>,
-) -> Option<FullSnapshotArchiveInfo> {
-    let mut full_snapshot_archives = get_full_snapshot_archives(full_snapshot_archives_dir);
-    full_snapshot_archives.sort_unstable();
-    full_snapshot_archives.into_iter().rev().next()
+/// Options for `bank_from_snapshot_archives()`
+///
+/// The defaults load the archives the way a validator does: unpacked within the bank snapshots
+/// dir, without progress reports, and with a mismatched epoch accounts hash being fatal.
+pub struct SnapshotLoadOptions<'a> {
+    /// Creates the temporary directories the archives are extracted into; if None, they are
+    /// created within the bank snapshots dir
+    pub temp_dir_factory: Option<&'a dyn TempDirFactory>,
+    /// If the epoch accounts hash does not match, and this is false, the bank is still returned,
+    /// but flagged with `Bank::set_startup_verification_failed()` so it cannot be used for
+    /// consensus.  This lets forensic tooling inspect divergent archives.
+    pub epoch_accounts_hash_mismatch_is_fatal: bool,
+    /// Loading a large snapshot takes a long time, so if set, the loading progress is sent here,
+    /// to let a UI or log show that it is still progressing.  Progress is sent without blocking,
+    /// and dropped if the channel is full.  The counts in the progress are polled, so they are
+    /// sent at most every `LOAD_PROGRESS_POLL_INTERVAL`.
+    pub progress_sender: Option<&'a Sender<LoadProgress>>,
+    /// **NOT CONSENSUS**: if set, the bank hash is recalculated as if this feature set were
+    /// active, instead of being verified.  This is for investigating how feature activations
+    /// affect hashing.  The recalculated hash is logged, and the bank is flagged with
+    /// `Bank::set_startup_verification_failed()` so it cannot be used for consensus.
+    pub non_consensus_feature_set: Option<Arc<FeatureSet>>,
+    /// If set, at most this many bytes of decompressed archive data are buffered in memory at
+    /// once, see `SnapshotConfig::extraction_buffer_bytes`
+    pub extraction_buffer_bytes: Option<usize>,
+    /// If set, and the snapshot slot is at or after the epoch accounts hash stop slot, the EAH is
+    /// recalculated from the accounts state at the EAH start slot, and
+    /// `SnapshotError::EpochAccountsHash` is returned if it does not match the one in the
+    /// snapshot.  This is for snapshots from untrusted sources.  Snapshots before the stop slot
+    /// are not checked.  Only the archived storages at or below the start slot are hashed, see
+    /// `Bank::reverify_epoch_accounts_hash()`, so cleaning past the start slot is fine, but the
+    /// check reports a mismatch for snapshots whose start slot storages were shrunk since.
+    pub verify_epoch_accounts_hash: bool,
+    /// Needed to unpack `ArchiveFormat::TarZstdWithDict` archives;
+    /// `SnapshotError::MissingZstdDictionary` is returned if such an archive is given without it
+    pub zstd_dictionary: Option<&'a [u8]>,
+    /// If set, caps the threads used to unarchive the full and incremental snapshot archives, see
+    /// `SnapshotConfig::unarchive_thread_budget`
+    pub unarchive_thread_budget: Option<usize>,
+    /// If set, caps the threads used to verify the accounts hashes, see
+    /// `SnapshotConfig::verification_thread_budget`
+    pub verification_thread_budget: Option<usize>,
+    /// If set, the archives are untarred with at least one thread per account path, and each
+    /// thread writes its account storage files to one path.  Before any account storages are
+    /// untarred, each archive's manifest and status cache are read and verified.  If
+    /// `unarchive_thread_budget` is too small for a thread per account path, this is ignored.
+    pub untar_across_account_paths: bool,
 }
 
-/// Get the path for the incremental snapshot archive with the highest slot, for a given full
-/// snapshot slot, in a directory
-pub fn get_highest_incremental_snapshot_archive_info(
-    incremental_snapshot_archives_dir: impl AsRef<Path>,
-    full_snapshot_slot: Slot,
-) -> Option<IncrementalSnapshotArchiveInfo> {
-    // Since we want to filter down to only the incremental snapshot archives that have the same
-    // full snapshot slot as the value passed in, perform the filtering before sorting to avoid
-    // doing unnecessary work.
-    let mut incremental_snapshot_archives =
-        get_incremental_snapshot_archives(incremental_snapshot_archives_dir)
-            .into_iter()
-            .filter(|incremental_snapshot_archive_info| {
-                incremental_snapshot_archive_info.base_slot() == full_snapshot_slot
-            })
-            .collect::<Vec<_>>();
-    incremental_snapshot_archives.sort_unstable();
-    incremental_snapshot_archives.into_iter().rev().next()
+impl Default for SnapshotLoadOptions<'_> {
+    fn default() -> Self {
+        Self {
+            temp_dir_factory: None,
+            epoch_accounts_hash_mismatch_is_fatal: true,
+            progress_sender: None,
+            non_consensus_feature_set: None,
+            extraction_buffer_bytes: None,
+            verify_epoch_accounts_hash: false,
+            zstd_dictionary: None,
+            unarchive_thread_budget: None,
+            verification_thread_budget: None,
+            untar_across_account_paths: false,
+        }
+    }
 }
 
-pub fn purge_old_snapshot_archives(
-    full_snapshot_archives_dir: impl AsRef<Path>,
-    incremental_snapshot_archives_dir: impl AsRef<Path>,
-    maximum_full_snapshot_archives_to_retain: usize,
-    maximum_incremental_snapshot_archives_to_retain: usize,
-) {
-    info!(
-        "Purging old full snapshot archives in {}, retaining up to {} full snapshots",
-        full_snapshot_archives_dir.as_ref().display(),
-        maximum_full_snapshot_archives_to_retain
-    );
+/// Rebuild bank from snapshot archives.  Handles either just a full snapshot, or both a full
+/// snapshot and an incremental snapshot.
+///
+/// See `SnapshotLoadOptions` for how `load_options` change the way the archives are loaded.
+#[allow(clippy::too_many_arguments)]
+pub fn bank_from_snapshot_archives(
+    account_paths: &[PathBuf],
+    bank_snapshots_dir: impl AsRef<Path>,
+    full_snapshot_archive_info: &FullSnapshotArchiveInfo,
+    incremental_snapshot_archive_info: Option<&IncrementalSnapshotArchiveInfo>,
+    genesis_config: &GenesisConfig,
+    runtime_config: &RuntimeConfig,
+    debug_keys: Option<Arc<HashSet<Pubkey>>>,
+    additional_builtins: Option<&Builtins>,
+    account_secondary_indexes: AccountSecondaryIndexes,
+    accounts_db_caching_enabled: bool,
+    limit_load_slot_count_from_snapshot: Option<usize>,
+    shrink_ratio: AccountShrinkThreshold,
+    test_hash_calculation: bool,
+    accounts_db_skip_shrink: bool,
+    verify_index: bool,
+    accounts_db_config: Option<AccountsDbConfig>,
+    accounts_update_notifier: Option<AccountsUpdateNotifier>,
+    exit: &Arc<AtomicBool>,
+    load_options: SnapshotLoadOptions,
+) -> Result<(Bank, BankFromArchiveTimings)> {
+    let SnapshotLoadOptions {
+        temp_dir_factory,
+        epoch_accounts_hash_mismatch_is_fatal,
+        progress_sender,
+        non_consensus_feature_set,
+        extraction_buffer_bytes,
+        verify_epoch_accounts_hash,
+        zstd_dictionary,
+        unarchive_thread_budget,
+        verification_thread_budget,
+        untar_across_account_paths,
+    } = load_options;
+    let progress_counters = progress_sender.map(|_| {
+        let untar_bytes_total = std::iter::once(full_snapshot_archive_info.path())
+            .chain(incremental_snapshot_archive_info.map(|info| info.path()))
+            .map(|path| fs::metadata(path).map_or(0, |metadata| metadata.len()))
+            .sum();
+        Arc::new(LoadProgressCounters {
+            untar_bytes_total,
+            ..LoadProgressCounters::default()
+        })
+    });
+    let progress = progress_sender.zip(progress_counters.as_deref());
+    let accounts_db_config = match &progress_counters {
+        Some(progress_counters) => Some(AccountsDbConfig {
+            startup_progress: Some(progress_counters.accounts_db.clone()),
+            ..accounts_db_config.unwrap_or_default()
+        }),
+        None => accounts_db_config,
+    };
 
-    let mut full_snapshot_archives = get_full_snapshot_archives(&full_snapshot_archives_dir);
-    full_snapshot_archives.sort_unstable();
-    full_snapshot_archives.reverse();
+    send_load_progress(progress_sender, LoadProgress::UnpackingArchives);
+    let (unarchived_full_snapshot, mut unarchived_incremental_snapshot, next_append_vec_id) =
+        with_polled_load_progress(progress, LoadProgressCounters::unpacking, || {
+            verify_and_unarchive_snapshots(
+                bank_snapshots_dir,
+                full_snapshot_archive_info,
+                incremental_snapshot_archive_info,
+                account_paths,
+                temp_dir_factory,
+                extraction_buffer_bytes,
+                zstd_dictionary,
+                unarchive_thread_budget,
+                untar_across_account_paths,
+                progress_counters.as_deref(),
+            )
+        })?;
 
-    let num_to_retain = full_snapshot_archives.len().min(
-        maximum_full_snapshot_archives_to_retain
-            .max(1 /* Always keep at least one full snapshot */),
-    );
-    trace!(
-        "There are {} full snapshot archives, retaining {}",
-        full_snapshot_archives.len(),
-        num_to_retain,
+    let mut storage = unarchived_full_snapshot.storage;
+    if let Some(ref mut unarchive_preparation_result) = unarchived_incremental_snapshot {
+        let incremental_snapshot_storages =
+            std::mem::take(&mut unarchive_preparation_result.storage);
+        storage.extend(incremental_snapshot_storages.into_iter());
+    }
+    let num_storages = storage
+        .iter()
+        .map(|slot_stores| slot_stores.value().read().unwrap().len())
+        .sum();
+    send_load_progress(
+        progress_sender,
+        LoadProgress::ArchivesUnpacked { num_storages },
     );
 
-    let (full_snapshot_archives_to_retain, full_snapshot_archives_to_remove) =
-        if full_snapshot_archives.is_empty() {
-            None
-        } else {
-            Some(full_snapshot_archives.split_at(num_to_retain))
-        }
-        .unwrap_or_default();
-
-    let retained_full_snapshot_slots = full_snapshot_archives_to_retain
-        .iter()
-        .map(|ai| ai.slot())
-        .collect::<HashSet<_>>();
+    let storage_and_next_append_vec_id = StorageAndNextAppendVecId {
+        storage,
+        next_append_vec_id,
+    };
 
-    fn remove_archives<T: SnapshotArchiveInfoGetter>(archives: &[T]) {
-        for path in archives.iter().map(|a| a.path()) {
-            trace!("Removing snapshot archive: {}", path.display());
-            fs::remove_file(path)
-                .unwrap_or_else(|err| info!("Failed to remove {}: {}", path.display(), err));
-        }
+    let mut measure_rebuild = Measure::start("rebuild bank from snapshots");
+    let bank = with_polled_load_progress(progress, LoadProgressCounters::generating_index, || {
+        rebuild_bank_from_snapshots(
+            &unarchived_full_snapshot.unpacked_snapshots_dir_and_version,
+            unarchived_incremental_snapshot
+                .as_ref()
+                .map(|unarchive_preparation_result| {
+                    &unarchive_preparation_result.unpacked_snapshots_dir_and_version
+                }),
+            account_paths,
+            storage_and_next_append_vec_id,
+            genesis_config,
+            runtime_config,
+            debug_keys,
+            additional_builtins,
+            account_secondary_indexes,
+            accounts_db_caching_enabled,
+            limit_load_slot_count_from_snapshot,
+            shrink_ratio,
+            verify_index,
+            accounts_db_config,
+            accounts_update_notifier,
+            exit,
+        )
+    })?;
+    measure_rebuild.stop();
+    info!("{}", measure_rebuild);
+    let accounts_db = &bank.rc.accounts.accounts_db;
+    if unarchived_incremental_snapshot.is_none() {
+        accounts_db.set_last_full_snapshot_accounts_hash(bank.slot(), bank.get_accounts_hash());
+    } else if let Some(incremental_snapshot_persistence) = &bank.incremental_snapshot_persistence {
+        accounts_db.set_last_full_snapshot_accounts_hash(
+            incremental_snapshot_persistence.full_slot,
+            incremental_snapshot_persistence.full_hash,
+        );
     }
-    remove_archives(full_snapshot_archives_to_remove);
-
-    info!(
-        "Purging old incremental snapshot archives in {}, retaining up to {} incremental snapshots",
-        incremental_snapshot_archives_dir.as_ref().display(),
-        maximum_incremental_snapshot_archives_to_retain
+    send_load_progress(
+        progress_sender,
+        LoadProgress::BankRebuilt { slot: bank.slot() },
     );
-    let mut incremental_snapshot_archives_by_base_slot = HashMap::<Slot, Vec<_>>::new();
-    for incremental_snapshot_archive in
-        get_incremental_snapshot_archives(&incremental_snapshot_archives_dir)
-    {
-        incremental_snapshot_archives_by_base_slot
-            .entry(incremental_snapshot_archive.base_slot())
-            .or_default()
-            .push(incremental_snapshot_archive)
+
+    send_load_progress(progress_sender, LoadProgress::VerifyingBank);
+    // Sent once the bank has been verified; dropped without sending if verification fails
+    let (verified_sender, verified_receiver) = crossbeam_channel::bounded(1);
+    if let Some((progress_sender, progress_counters)) = progress_sender.zip(progress_counters) {
+        // The accounts hash verification may continue in the background, so report its progress
+        // from another thread, until it completes
+        let progress_sender = progress_sender.clone();
+        let accounts_db = bank.rc.accounts.accounts_db.clone();
+        thread::Builder::new()
+            .name("solLoadProgress".to_string())
+            .spawn(move || {
+                let mut is_verified = false;
+                let mut is_failed = false;
+                send_polled_load_progress(
+                    &progress_sender,
+                    || progress_counters.hashing_accounts(),
+                    || {
+                        if is_verified {
+                            thread::sleep(LOAD_PROGRESS_POLL_INTERVAL);
+                        } else {
+                            match verified_receiver.recv_timeout(LOAD_PROGRESS_POLL_INTERVAL) {
+                                Ok(()) => is_verified = true,
+                                Err(RecvTimeoutError::Timeout) => {}
+                                Err(RecvTimeoutError::Disconnected) => is_failed = true,
+                            }
+                        }
+                        is_failed
+                            || (is_verified && !accounts_db.verify_accounts_hash_in_bg.is_running())
+                    },
+                );
+                if !is_failed {
+                    send_load_progress(Some(&progress_sender), LoadProgress::Complete);
+                }
+            })
+            .unwrap();
     }
 
-    let highest_full_snapshot_slot = retained_full_snapshot_slots.iter().max().copied();
-    for (base_slot, mut incremental_snapshot_archives) in incremental_snapshot_archives_by_base_slot
+    // The EAH and the accounts of the full and incremental snapshots are verified one after the
+    // other, so they can all share this thread pool
+    let verification_thread_pool =
+        verification_thread_budget.map(|budget| Arc::new(new_verification_thread_pool(budget)));
+
+    // This must run before verifying the bank, which shrinks away old account versions
+    let mut measure_verify_epoch_accounts_hash = Measure::start("verify epoch accounts hash");
+    if verify_epoch_accounts_hash
+        && bank
+            .feature_set
+            .is_active(&solana_sdk::feature_set::epoch_accounts_hash::id())
     {
-        incremental_snapshot_archives.sort_unstable();
-        let num_to_retain = if Some(base_slot) == highest_full_snapshot_slot {
-            maximum_incremental_snapshot_archives_to_retain
-        } else {
-            usize::from(retained_full_snapshot_slots.contains(&base_slot))
+        // `reverify_epoch_accounts_hash()` runs on whichever thread pool it is called from
+        let reverify_epoch_accounts_hash = || bank.reverify_epoch_accounts_hash();
+        let result = match &verification_thread_pool {
+            Some(thread_pool) => thread_pool.install(reverify_epoch_accounts_hash),
+            None => reverify_epoch_accounts_hash(),
         };
-        trace!(
-            "There are {} incremental snapshot archives for base slot {}, removing {} of them",
-            incremental_snapshot_archives.len(),
-            base_slot,
-            incremental_snapshot_archives
-                .len()
-                .saturating_sub(num_to_retain),
+        match result {
+            // the EAH is not part of the bank state before the stop slot
+            Err(EpochAccountsHashError::NotIncluded(..)) => {}
+            result => result?,
+        }
+    }
+    measure_verify_epoch_accounts_hash.stop();
+    let mut measure_verify = Measure::start("verify");
+    if let Some(feature_set) = non_consensus_feature_set {
+        let calculated_hash = bank.calculate_hash_with_feature_set(feature_set);
+        warn!(
+            "Snapshot bank for slot {} was recalculated with an overridden feature set: \
+             {:?} (calculated) vs {} (snapshot). \
+             The bank is flagged as unverified, and cannot be used for consensus.",
+            bank.slot(),
+            calculated_hash,
+            bank.hash(),
         );
-
-        incremental_snapshot_archives.truncate(
-            incremental_snapshot_archives
-                .len()
-                .saturating_sub(num_to_retain),
+        bank.set_startup_verification_failed();
+    } else if !bank.verify_epoch_accounts_hash() {
+        // The EAH is verified first, since verifying the bank hash blocks if the EAH is missing
+        if epoch_accounts_hash_mismatch_is_fatal {
+            panic!(
+                "Snapshot bank for slot {} failed to verify the epoch accounts hash",
+                bank.slot()
+            );
+        }
+        // The bank hash cannot verify either, so skip the remaining verification
+        warn!(
+            "Snapshot bank for slot {} failed to verify the epoch accounts hash. \
+             The bank is flagged as unverified, and cannot be used for consensus.",
+            bank.slot()
         );
-        remove_archives(&incremental_snapshot_archives);
+        bank.set_startup_verification_failed();
+    } else if !bank.verify_snapshot_bank(
+        test_hash_calculation,
+        accounts_db_skip_shrink || !full_snapshot_archive_info.is_remote(),
+        full_snapshot_archive_info.slot(),
+        verification_thread_pool,
+    ) && limit_load_slot_count_from_snapshot.is_none()
+    {
+        panic!("Snapshot bank for slot {} failed to verify", bank.slot());
     }
-}
-
-fn unpack_snapshot_local(
-    shared_buffer: SharedBuffer,
-    ledger_dir: &Path,
-    account_paths: &[PathBuf],
-    parallel_divisions: usize,
-) -> Result<UnpackedAppendVecMap> {
-    assert!(parallel_divisions > 0);
+    measure_verify.stop();
+    let _ = verified_sender.send(());
 
-    // allocate all readers before any readers start reading
-    let readers = (0..parallel_divisions)
-        .into_iter()
-        .map(|_| SharedBufferReader::new(&shared_buffer))
-        .collect::<Vec<_>>();
+    let timings = BankFromArchiveTimings {
+        rebuild_bank_from_snapshots_us: measure_rebuild.as_us(),
+        full_snapshot_untar_us: unarchived_full_snapshot.measure_untar.as_us(),
+        incremental_snapshot_untar_us: unarchived_incremental_snapshot
+            .map_or(0, |unarchive_preparation_result| {
+                unarchive_preparation_result.measure_untar.as_us()
+            }),
+        verify_epoch_accounts_hash_us: measure_verify_epoch_accounts_hash.as_us(),
+        verify_snapshot_bank_us: measure_verify.as_us(),
+    };
+    Ok((bank, timings))
+}
 
-    // create 'parallel_divisions' # of parallel workers, each responsible for 1/parallel_divisions of all the files to extract.
-    let all_unpacked_append_vec_map = readers
-        .into_par_iter()
-        .enumerate()
-        .map(|(index, reader)| {
-            let parallel_selector = Some(ParallelSelector {
-                index,
-                divisions: parallel_divisions,
-            });
-            let mut archive = Archive::new(reader);
-            unpack_snapshot(&mut archive, ledger_dir, account_paths, parallel_selector)
-        })
-        .collect::<Vec<_>>();
+/// Summary of validating a snapshot archive, from `load_for_validation()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub slot: Slot,
+    pub bank_hash: Hash,
+    pub capitalization: u64,
+    /// did the epoch accounts hash verify (or is it not included in this bank)
+    pub epoch_accounts_hash_verified: bool,
+    /// did the accounts hash and capitalization verify
+    pub accounts_hash_verified: bool,
+    /// did the bank hash verify
+    pub bank_hash_verified: bool,
+}
 
-    let mut unpacked_append_vec_map = UnpackedAppendVecMap::new();
-    for h in all_unpacked_append_vec_map {
-        unpacked_append_vec_map.extend(h?);
+impl ValidationReport {
+    /// Did every verification pass?
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        self.epoch_accounts_hash_verified && self.accounts_hash_verified && self.bank_hash_verified
     }
+}
 
-    Ok(unpacked_append_vec_map)
+/// Fully load the bank from a full snapshot archive and verify it, then drop the bank
+///
+/// Unlike `bank_from_snapshot_archives()`, verification failures do not panic, and all
+/// verification runs in the foreground.  Only the report is returned, and the bank and its
+/// account storages are dropped before returning, so validating an archive (e.g. in CI) does not
+/// keep the accounts in memory.  Everything is unpacked into a temporary directory from
+/// `temp_dir_factory`.
+pub fn load_for_validation(
+    archive: impl AsRef<Path>,
+    genesis_config: &GenesisConfig,
+    temp_dir_factory: &dyn TempDirFactory,
+) -> Result<ValidationReport> {
+    let work_dir = temp_dir_factory.create_temp_dir(TMP_SNAPSHOT_ARCHIVE_PREFIX)?;
+    load_for_validation_in(archive.as_ref(), genesis_config, work_dir.path())
 }
 
-fn untar_snapshot_create_shared_buffer(
-    snapshot_tar: &Path,
-    archive_format: ArchiveFormat,
-) -> SharedBuffer {
-    let open_file = || File::open(snapshot_tar).unwrap();
-    match archive_format {
-        ArchiveFormat::TarBzip2 => SharedBuffer::new(BzDecoder::new(BufReader::new(open_file()))),
-        ArchiveFormat::TarGzip => SharedBuffer::new(GzDecoder::new(BufReader::new(open_file()))),
-        ArchiveFormat::TarZstd => SharedBuffer::new(
-            zstd::stream::read::Decoder::new(BufReader::new(open_file())).unwrap(),
-        ),
-        ArchiveFormat::TarLz4 => {
-            SharedBuffer::new(lz4::Decoder::new(BufReader::new(open_file())).unwrap())
-        }
-        ArchiveFormat::Tar => SharedBuffer::new(BufReader::new(open_file())),
-    }
+fn load_for_validation_in(
+    archive: &Path,
+    genesis_config: &GenesisConfig,
+    work_dir: &Path,
+) -> Result<ValidationReport> {
+    let bank = rebuild_bank_for_validation(archive, genesis_config, work_dir)?;
+
+    // The EAH is verified first, since verifying the bank hash blocks if the EAH is missing
+    let epoch_accounts_hash_verified = bank.verify_epoch_accounts_hash();
+    let accounts_hash_verified = bank.verify_bank_hash(VerifyBankHash {
+        test_hash_calculation: false,
+        ignore_mismatch: false,
+        require_rooted_bank: false,
+        run_in_background: false,
+        store_hash_raw_data_for_debug: false,
+        thread_pool: None,
+    });
+    let bank_hash_verified =
+        epoch_accounts_hash_verified && accounts_hash_verified && bank.verify_hash();
+
+    Ok(ValidationReport {
+        slot: bank.slot(),
+        bank_hash: bank.hash(),
+        capitalization: bank.capitalization(),
+        epoch_accounts_hash_verified,
+        accounts_hash_verified,
+        bank_hash_verified,
+    })
 }
 
-fn untar_snapshot_in<P: AsRef<Path>>(
-    snapshot_tar: P,
-    unpack_dir: &Path,
-    account_paths: &[PathBuf],
-    archive_format: ArchiveFormat,
-    parallel_divisions: usize,
-) -> Result<UnpackedAppendVecMap> {
-    let shared_buffer = untar_snapshot_create_shared_buffer(snapshot_tar.as_ref(), archive_format);
-    unpack_snapshot_local(shared_buffer, unpack_dir, account_paths, parallel_divisions)
-}
-
-fn verify_unpacked_snapshots_dir_and_version(
-    unpacked_snapshots_dir_and_version: &UnpackedSnapshotsDirAndVersion,
-) -> Result<(SnapshotVersion, BankSnapshotInfo)> {
-    info!(
-        "snapshot version: {}",
-        &unpacked_snapshots_dir_and_version.snapshot_version
-    );
-
-    let snapshot_version = unpacked_snapshots_dir_and_version.snapshot_version;
-    let mut bank_snapshots =
-        get_bank_snapshots_post(&unpacked_snapshots_dir_and_version.unpacked_snapshots_dir);
-    if bank_snapshots.len() > 1 {
-        return Err(get_io_error("invalid snapshot format"));
-    }
-    let root_paths = bank_snapshots
-        .pop()
-        .ok_or_else(|| get_io_error("No snapshots found in snapshots directory"))?;
-    Ok((snapshot_version, root_paths))
+/// Load the bank from a full snapshot archive, and check that its epoch accounts hash matches
+/// the one recalculated from the archive's own accounts, as of the EAH calculation start slot
+///
+/// See `Bank::reverify_epoch_accounts_hash()`; archives of banks before the EAH stop slot do not
+/// include an EAH, and fail with `EpochAccountsHashError::NotIncluded`.  The recalculation only
+/// reads the archived storages at or below the start slot, so it fails with a mismatch if the
+/// archiving node had shrunk those slots.  Everything is unpacked into a temporary directory from
+/// `temp_dir_factory`.
+pub fn self_verify_eah(
+    archive: impl AsRef<Path>,
+    genesis_config: &GenesisConfig,
+    temp_dir_factory: &dyn TempDirFactory,
+) -> Result<()> {
+    let work_dir = temp_dir_factory.create_temp_dir(TMP_SNAPSHOT_ARCHIVE_PREFIX)?;
+    let bank = rebuild_bank_for_validation(archive.as_ref(), genesis_config, work_dir.path())?;
+    bank.reverify_epoch_accounts_hash()?;
+    Ok(())
 }
 
-fn bank_fields_from_snapshots(
-    full_snapshot_unpacked_snapshots_dir_and_version: &UnpackedSnapshotsDirAndVersion,
-    incremental_snapshot_unpacked_snapshots_dir_and_version: Option<
-        &UnpackedSnapshotsDirAndVersion,
-    >,
-) -> Result<BankFieldsToDeserialize> {
-    let (full_snapshot_version, full_snapshot_root_paths) =
-        verify_unpacked_snapshots_dir_and_version(
-            full_snapshot_unpacked_snapshots_dir_and_version,
-        )?;
-    let (incremental_snapshot_version, incremental_snapshot_root_paths) =
-        if let Some(snapshot_unpacked_snapshots_dir_and_version) =
-            incremental_snapshot_unpacked_snapshots_dir_and_version
-        {
-            let (snapshot_version, bank_snapshot_info) = verify_unpacked_snapshots_dir_and_version(
-                snapshot_unpacked_snapshots_dir_and_version,
-            )?;
-            (Some(snapshot_version), Some(bank_snapshot_info))
-        } else {
-            (None, None)
-        };
-    info!(
-        "Loading bank from full snapshot {} and incremental snapshot {:?}",
-        full_snapshot_root_paths.snapshot_path.display(),
-        incremental_snapshot_root_paths
-            .as_ref()
-            .map(|paths| paths.snapshot_path.display()),
-    );
-
-    let snapshot_root_paths = SnapshotRootPaths {
-        full_snapshot_root_file_path: full_snapshot_root_paths.snapshot_path,
-        incremental_snapshot_root_file_path: incremental_snapshot_root_paths
-            .map(|root_paths| root_paths.snapshot_path),
-    };
+/// Unpack a full snapshot archive into `work_dir` and rebuild its bank, without verifying it
+fn rebuild_bank_for_validation(
+    archive: &Path,
+    genesis_config: &GenesisConfig,
+    work_dir: &Path,
+) -> Result<Bank> {
+    let full_snapshot_archive_info = FullSnapshotArchiveInfo::new_from_path(archive.to_path_buf())?;
+    let bank_snapshots_dir = work_dir.join("snapshots");
+    let account_paths = vec![work_dir.join("accounts")];
+    fs::create_dir_all(&bank_snapshots_dir)?;
+    fs::create_dir_all(&account_paths[0])?;
 
-    deserialize_snapshot_data_files(&snapshot_root_paths, |snapshot_streams| {
-        Ok(
-            match incremental_snapshot_version.unwrap_or(full_snapshot_version) {
-                SnapshotVersion::V1_2_0 => fields_from_streams(SerdeStyle::Newer, snapshot_streams)
-                    .map(|(bank_fields, _accountsdb_fields)| bank_fields),
-            }?,
-        )
-    })
+    let (unarchived_full_snapshot, _, next_append_vec_id) = verify_and_unarchive_snapshots(
+        &bank_snapshots_dir,
+        &full_snapshot_archive_info,
+        None,
+        &account_paths,
+        None,
+        None,
+        None,
+        None,
+        false,
+        None,
+    )?;
+    let UnarchivedSnapshot {
+        unpack_dir: _unpack_dir,
+        storage,
+        unpacked_snapshots_dir_and_version,
+        ..
+    } = unarchived_full_snapshot;
+    rebuild_bank_from_snapshots(
+        &unpacked_snapshots_dir_and_version,
+        None,
+        &account_paths,
+        StorageAndNextAppendVecId {
+            storage,
+            next_append_vec_id,
+        },
+        genesis_config,
+        &RuntimeConfig::default(),
+        None,
+        None,
+        AccountSecondaryIndexes::default(),
+        false,
+        None,
+        AccountShrinkThreshold::default(),
+        false,
+        None,
+        None,
+        &Arc::new(AtomicBool::new(false)),
+    )
 }
 
+/// Rebuild bank from snapshot archives.  This function searches `full_snapshot_archives_dir` and `incremental_snapshot_archives_dir` for the
+/// highest full snapshot and highest corresponding incremental snapshot, then rebuilds the bank.
+///
+/// If `as_of_slot` is set, the bank is rebuilt from the full snapshot, or the full and
+/// incremental snapshots, whose slot is exactly `as_of_slot`.  This is for reproducing historical
+/// state.  `SnapshotError::NoSnapshotArchivesAsOfSlot` is returned if there is no full snapshot
+/// archive at or before `as_of_slot`, and `SnapshotError::NoSnapshotArchiveAtSlot` if the
+/// archives at or before `as_of_slot` would only rebuild an earlier slot.
+///
+/// Duplicate incremental snapshot archives (same base slot and slot) are resolved per
+/// `duplicate_incremental_snapshot_archive_policy`.  The archives are loaded per `load_options`,
+/// see `bank_from_snapshot_archives()`.
 #[allow(clippy::too_many_arguments)]
-fn rebuild_bank_from_snapshots(
-    full_snapshot_unpacked_snapshots_dir_and_version: &UnpackedSnapshotsDirAndVersion,
-    incremental_snapshot_unpacked_snapshots_dir_and_version: Option<
-        &UnpackedSnapshotsDirAndVersion,
-    >,
+pub fn bank_from_latest_snapshot_archives(
+    bank_snapshots_dir: impl AsRef<Path>,
+    full_snapshot_archives_dir: impl AsRef<Path>,
+    incremental_snapshot_archives_dir: impl AsRef<Path>,
     account_paths: &[PathBuf],
-    storage_and_next_append_vec_id: StorageAndNextAppendVecId,
     genesis_config: &GenesisConfig,
     runtime_config: &RuntimeConfig,
     debug_keys: Option<Arc<HashSet<Pubkey>>>,
@@ -1790,1524 +2971,5175 @@ fn rebuild_bank_from_snapshots(
     accounts_db_caching_enabled: bool,
     limit_load_slot_count_from_snapshot: Option<usize>,
     shrink_ratio: AccountShrinkThreshold,
+    test_hash_calculation: bool,
+    accounts_db_skip_shrink: bool,
     verify_index: bool,
     accounts_db_config: Option<AccountsDbConfig>,
     accounts_update_notifier: Option<AccountsUpdateNotifier>,
     exit: &Arc<AtomicBool>,
-) -> Result<Bank> {
-    let (full_snapshot_version, full_snapshot_root_paths) =
-        verify_unpacked_snapshots_dir_and_version(
-            full_snapshot_unpacked_snapshots_dir_and_version,
-        )?;
-    let (incremental_snapshot_version, incremental_snapshot_root_paths) =
-        if let Some(snapshot_unpacked_snapshots_dir_and_version) =
-            incremental_snapshot_unpacked_snapshots_dir_and_version
-        {
-            let (snapshot_version, bank_snapshot_info) = verify_unpacked_snapshots_dir_and_version(
-                snapshot_unpacked_snapshots_dir_and_version,
+    as_of_slot: Option<Slot>,
+    duplicate_incremental_snapshot_archive_policy: DuplicateIncrementalSnapshotArchivePolicy,
+    load_options: SnapshotLoadOptions,
+) -> Result<(
+    Bank,
+    FullSnapshotArchiveInfo,
+    Option<IncrementalSnapshotArchiveInfo>,
+)> {
+    let (full_snapshot_archive_info, incremental_snapshot_archive_info) = match as_of_slot {
+        None => {
+            let full_snapshot_archive_info =
+                get_highest_full_snapshot_archive_info(&full_snapshot_archives_dir)
+                    .ok_or(SnapshotError::NoSnapshotArchives)?;
+            let incremental_snapshot_archive_info =
+                get_highest_incremental_snapshot_archive_info_with_policy(
+                    &incremental_snapshot_archives_dir,
+                    full_snapshot_archive_info.slot(),
+                    duplicate_incremental_snapshot_archive_policy,
+                )?;
+            (
+                full_snapshot_archive_info,
+                incremental_snapshot_archive_info,
+            )
+        }
+        Some(as_of_slot) => {
+            let full_snapshot_archive_info =
+                get_full_snapshot_archives(&full_snapshot_archives_dir)
+                    .into_iter()
+                    .filter(|full_snapshot_archive_info| {
+                        full_snapshot_archive_info.slot() <= as_of_slot
+                    })
+                    .max()
+                    .ok_or(SnapshotError::NoSnapshotArchivesAsOfSlot(as_of_slot))?;
+            let incremental_snapshot_archive_info = pick_highest_incremental_snapshot_archive(
+                get_incremental_snapshot_archives(&incremental_snapshot_archives_dir)
+                    .into_iter()
+                    .filter(|incremental_snapshot_archive_info| {
+                        incremental_snapshot_archive_info.base_slot()
+                            == full_snapshot_archive_info.slot()
+                            && incremental_snapshot_archive_info.slot() <= as_of_slot
+                    }),
+                duplicate_incremental_snapshot_archive_policy,
             )?;
-            (Some(snapshot_version), Some(bank_snapshot_info))
-        } else {
-            (None, None)
-        };
+            let highest = incremental_snapshot_archive_info
+                .as_ref()
+                .map(|incremental_snapshot_archive_info| incremental_snapshot_archive_info.slot())
+                .unwrap_or_else(|| full_snapshot_archive_info.slot());
+            if highest != as_of_slot {
+                return Err(SnapshotError::NoSnapshotArchiveAtSlot {
+                    requested: as_of_slot,
+                    highest,
+                });
+            }
+            (
+                full_snapshot_archive_info,
+                incremental_snapshot_archive_info,
+            )
+        }
+    };
+
     info!(
-        "Loading bank from full snapshot {} and incremental snapshot {:?}",
-        full_snapshot_root_paths.snapshot_path.display(),
-        incremental_snapshot_root_paths
+        "Loading bank from full snapshot: {}, and incremental snapshot: {:?}",
+        full_snapshot_archive_info.path().display(),
+        incremental_snapshot_archive_info
             .as_ref()
-            .map(|paths| paths.snapshot_path.display()),
+            .map(
+                |incremental_snapshot_archive_info| incremental_snapshot_archive_info
+                    .path()
+                    .display()
+            )
     );
 
-    let snapshot_root_paths = SnapshotRootPaths {
-        full_snapshot_root_file_path: full_snapshot_root_paths.snapshot_path,
-        incremental_snapshot_root_file_path: incremental_snapshot_root_paths
-            .map(|root_paths| root_paths.snapshot_path),
-    };
+    let (bank, timings) = bank_from_snapshot_archives(
+        account_paths,
+        bank_snapshots_dir.as_ref(),
+        &full_snapshot_archive_info,
+        incremental_snapshot_archive_info.as_ref(),
+        genesis_config,
+        runtime_config,
+        debug_keys,
+        additional_builtins,
+        account_secondary_indexes,
+        accounts_db_caching_enabled,
+        limit_load_slot_count_from_snapshot,
+        shrink_ratio,
+        test_hash_calculation,
+        accounts_db_skip_shrink,
+        verify_index,
+        accounts_db_config,
+        accounts_update_notifier,
+        exit,
+        load_options,
+    )?;
 
-    let bank = deserialize_snapshot_data_files(&snapshot_root_paths, |snapshot_streams| {
-        Ok(
-            match incremental_snapshot_version.unwrap_or(full_snapshot_version) {
-                SnapshotVersion::V1_2_0 => bank_from_streams(
-                    SerdeStyle::Newer,
-                    snapshot_streams,
-                    account_paths,
-                    storage_and_next_append_vec_id,
-                    genesis_config,
-                    runtime_config,
-                    debug_keys,
-                    additional_builtins,
-                    account_secondary_indexes,
-                    accounts_db_caching_enabled,
-                    limit_load_slot_count_from_snapshot,
-                    shrink_ratio,
-                    verify_index,
-                    accounts_db_config,
-                    accounts_update_notifier,
-                    exit,
-                ),
-            }?,
-        )
-    })?;
+    datapoint_info!(
+        "bank_from_snapshot_archives",
+        (
+            "full_snapshot_untar_us",
+            timings.full_snapshot_untar_us,
+            i64
+        ),
+        (
+            "incremental_snapshot_untar_us",
+            timings.incremental_snapshot_untar_us,
+            i64
+        ),
+        (
+            "rebuild_bank_from_snapshots_us",
+            timings.rebuild_bank_from_snapshots_us,
+            i64
+        ),
+        (
+            "verify_epoch_accounts_hash_us",
+            timings.verify_epoch_accounts_hash_us,
+            i64
+        ),
+        (
+            "verify_snapshot_bank_us",
+            timings.verify_snapshot_bank_us,
+            i64
+        ),
+    );
 
-    // The status cache is rebuilt from the latest snapshot.  So, if there's an incremental
-    // snapshot, use that.  Otherwise use the full snapshot.
-    let status_cache_path = incremental_snapshot_unpacked_snapshots_dir_and_version
-        .map_or_else(
-            || {
-                full_snapshot_unpacked_snapshots_dir_and_version
-                    .unpacked_snapshots_dir
-                    .as_path()
-            },
-            |unpacked_snapshots_dir_and_version| {
-                unpacked_snapshots_dir_and_version
-                    .unpacked_snapshots_dir
-                    .as_path()
-            },
-        )
-        .join(SNAPSHOT_STATUS_CACHE_FILENAME);
-    let slot_deltas = deserialize_snapshot_data_file(&status_cache_path, |stream| {
-        info!(
-            "Rebuilding status cache from {}",
-            status_cache_path.display()
-        );
-        let slot_deltas: Vec<BankSlotDelta> = bincode::options()
-            .with_limit(MAX_SNAPSHOT_DATA_FILE_SIZE)
-            .with_fixint_encoding()
-            .allow_trailing_bytes()
-            .deserialize_from(stream)?;
-        Ok(slot_deltas)
-    })?;
-
-    verify_slot_deltas(slot_deltas.as_slice(), &bank)?;
-
-    bank.status_cache.write().unwrap().append(&slot_deltas);
-
-    bank.prepare_rewrites_for_hash();
+    verify_bank_against_expected_slot_hash(
+        &bank,
+        incremental_snapshot_archive_info.as_ref().map_or(
+            full_snapshot_archive_info.slot(),
+            |incremental_snapshot_archive_info| incremental_snapshot_archive_info.slot(),
+        ),
+        incremental_snapshot_archive_info.as_ref().map_or(
+            *full_snapshot_archive_info.hash(),
+            |incremental_snapshot_archive_info| *incremental_snapshot_archive_info.hash(),
+        ),
+    )?;
 
-    info!("Loaded bank for slot: {}", bank.slot());
-    Ok(bank)
+    Ok((
+        bank,
+        full_snapshot_archive_info,
+        incremental_snapshot_archive_info,
+    ))
 }
 
-/// Verify that the snapshot's slot deltas are not corrupt/invalid
-fn verify_slot_deltas(
-    slot_deltas: &[BankSlotDelta],
+/// Check to make sure the deserialized bank's slot and hash matches the snapshot archive's slot
+/// and hash
+fn verify_bank_against_expected_slot_hash(
     bank: &Bank,
-) -> std::result::Result<(), VerifySlotDeltasError> {
-    let info = verify_slot_deltas_structural(slot_deltas, bank.slot())?;
-    verify_slot_deltas_with_history(&info.slots, &bank.get_slot_history(), bank.slot())
-}
+    expected_slot: Slot,
+    expected_hash: Hash,
+) -> Result<()> {
+    let bank_slot = bank.slot();
+    let bank_hash = bank.get_accounts_hash();
 
-/// Verify that the snapshot's slot deltas are not corrupt/invalid
-/// These checks are simple/structural
-fn verify_slot_deltas_structural(
-    slot_deltas: &[BankSlotDelta],
-    bank_slot: Slot,
-) -> std::result::Result<VerifySlotDeltasStructuralInfo, VerifySlotDeltasError> {
-    // there should not be more entries than that status cache's max
-    let num_entries = slot_deltas.len();
-    if num_entries > status_cache::MAX_CACHE_ENTRIES {
-        return Err(VerifySlotDeltasError::TooManyEntries(
-            num_entries,
-            status_cache::MAX_CACHE_ENTRIES,
+    if bank_slot != expected_slot || bank_hash != expected_hash {
+        return Err(SnapshotError::MismatchedSlotHash(
+            (bank_slot, bank_hash),
+            (expected_slot, expected_hash),
         ));
     }
 
-    let mut slots_seen_so_far = HashSet::new();
-    for &(slot, is_root, ..) in slot_deltas {
-        // all entries should be roots
-        if !is_root {
-            return Err(VerifySlotDeltasError::SlotIsNotRoot(slot));
-        }
+    Ok(())
+}
 
-        // all entries should be for slots less than or equal to the bank's slot
-        if slot > bank_slot {
-            return Err(VerifySlotDeltasError::SlotGreaterThanMaxRoot(
-                slot, bank_slot,
-            ));
-        }
+/// Spawns a thread for unpacking a snapshot
+fn spawn_unpack_snapshot_thread(
+    file_sender: Sender<PathBuf>,
+    account_paths: Arc<Vec<PathBuf>>,
+    ledger_dir: Arc<PathBuf>,
+    mut archive: Archive<SharedBufferReader>,
+    parallel_selector: Option<ParallelSelector>,
+    thread_index: usize,
+) -> JoinHandle<()> {
+    Builder::new()
+        .name(format!("solUnpkSnpsht{thread_index:02}"))
+        .spawn(move || {
+            streaming_unpack_snapshot(
+                &mut archive,
+                ledger_dir.as_path(),
+                &account_paths,
+                parallel_selector,
+                &file_sender,
+            )
+            .unwrap();
+        })
+        .unwrap()
+}
 
-        // there should only be one entry per slot
-        let is_duplicate = !slots_seen_so_far.insert(slot);
-        if is_duplicate {
-            return Err(VerifySlotDeltasError::SlotHasMultipleEntries(slot));
-        }
-    }
+/// Streams unpacked files across channel
+#[allow(clippy::too_many_arguments)]
+fn streaming_unarchive_snapshot(
+    file_sender: Sender<PathBuf>,
+    account_paths: Vec<PathBuf>,
+    ledger_dir: PathBuf,
+    snapshot_archive_path: PathBuf,
+    archive_format: ArchiveFormat,
+    num_threads: usize,
+    extraction_buffer_bytes: Option<usize>,
+    zstd_dictionary: Option<&[u8]>,
+    untar_bytes_read: Option<Arc<AtomicU64>>,
+) -> Result<Vec<JoinHandle<()>>> {
+    let account_paths = Arc::new(account_paths);
+    let ledger_dir = Arc::new(ledger_dir);
+    let shared_buffer = untar_snapshot_create_shared_buffer(
+        &snapshot_archive_path,
+        archive_format,
+        extraction_buffer_bytes,
+        zstd_dictionary,
+        untar_bytes_read,
+    )?;
 
-    // detect serious logic error for future careless changes. :)
-    assert_eq!(slots_seen_so_far.len(), slot_deltas.len());
+    // All shared buffer readers need to be created before the threads are spawned
+    #[allow(clippy::needless_collect)]
+    let archives: Vec<_> = (0..num_threads)
+        .map(|_| {
+            let reader = SharedBufferReader::new(&shared_buffer);
+            Archive::new(reader)
+        })
+        .collect();
 
-    Ok(VerifySlotDeltasStructuralInfo {
-        slots: slots_seen_so_far,
-    })
-}
+    Ok(archives
+        .into_iter()
+        .enumerate()
+        .map(|(thread_index, archive)| {
+            let parallel_selector = Some(ParallelSelector {
+                index: thread_index,
+                divisions: num_threads,
+            });
 
-/// Computed information from `verify_slot_deltas_structural()`, that may be reused/useful later.
-#[derive(Debug, PartialEq, Eq)]
-struct VerifySlotDeltasStructuralInfo {
-    /// All the slots in the slot deltas
-    slots: HashSet<Slot>,
+            spawn_unpack_snapshot_thread(
+                file_sender.clone(),
+                account_paths.clone(),
+                ledger_dir.clone(),
+                archive,
+                parallel_selector,
+                thread_index,
+            )
+        })
+        .collect())
 }
 
-/// Verify that the snapshot's slot deltas are not corrupt/invalid
-/// These checks use the slot history for verification
-fn verify_slot_deltas_with_history(
-    slots_from_slot_deltas: &HashSet<Slot>,
-    slot_history: &SlotHistory,
-    bank_slot: Slot,
-) -> std::result::Result<(), VerifySlotDeltasError> {
-    // ensure the slot history is valid (as much as possible), since we're using it to verify the
-    // slot deltas
-    if slot_history.newest() != bank_slot {
-        return Err(VerifySlotDeltasError::BadSlotHistory);
-    }
+/// Perform the common tasks when unarchiving a snapshot.  Handles creating the temporary
+/// directories, untaring, reading the version file, and then returning those fields plus the
+/// rebuilt storage
+#[allow(clippy::too_many_arguments)]
+fn unarchive_snapshot<Q>(
+    temp_dir_factory: &dyn TempDirFactory,
+    unpacked_snapshots_dir_prefix: &'static str,
+    snapshot_archive_path: Q,
+    measure_name: &'static str,
+    account_paths: &[PathBuf],
+    archive_format: ArchiveFormat,
+    parallel_divisions: usize,
+    num_rebuilder_threads: usize,
+    next_append_vec_id: Arc<AtomicU32>,
+    extraction_buffer_bytes: Option<usize>,
+    zstd_dictionary: Option<&[u8]>,
+    progress_counters: Option<&LoadProgressCounters>,
+) -> Result<UnarchivedSnapshot>
+where
+    Q: AsRef<Path>,
+{
+    let unpack_dir = temp_dir_factory.create_temp_dir(unpacked_snapshots_dir_prefix)?;
+    let unpacked_snapshots_dir = unpack_dir.path().join("snapshots");
 
-    // all slots in the slot deltas should be in the bank's slot history
-    let slot_missing_from_history = slots_from_slot_deltas
-        .iter()
-        .find(|slot| slot_history.check(**slot) != Check::Found);
-    if let Some(slot) = slot_missing_from_history {
-        return Err(VerifySlotDeltasError::SlotNotFoundInHistory(*slot));
-    }
+    let (file_sender, file_receiver) = crossbeam_channel::unbounded();
+    streaming_unarchive_snapshot(
+        file_sender,
+        account_paths.to_vec(),
+        unpack_dir.path().to_path_buf(),
+        snapshot_archive_path.as_ref().to_path_buf(),
+        archive_format,
+        parallel_divisions,
+        extraction_buffer_bytes,
+        zstd_dictionary,
+        progress_counters.map(|counters| counters.untar_bytes_read.clone()),
+    )?;
 
-    // all slots in the history should be in the slot deltas (up to MAX_CACHE_ENTRIES)
-    // this ensures nothing was removed from the status cache
-    //
-    // go through the slot history and make sure there's an entry for each slot
-    // note: it's important to go highest-to-lowest since the status cache removes
-    // older entries first
-    // note: we already checked above that `bank_slot == slot_history.newest()`
-    let slot_missing_from_deltas = (slot_history.oldest()..=slot_history.newest())
-        .rev()
-        .filter(|slot| slot_history.check(*slot) == Check::Found)
-        .take(status_cache::MAX_CACHE_ENTRIES)
-        .find(|slot| !slots_from_slot_deltas.contains(slot));
-    if let Some(slot) = slot_missing_from_deltas {
-        return Err(VerifySlotDeltasError::SlotNotFoundInDeltas(slot));
-    }
+    let (version_and_storages, measure_untar) = measure!(
+        SnapshotStorageRebuilder::rebuild_storage(
+            file_receiver,
+            num_rebuilder_threads,
+            next_append_vec_id,
+            progress_counters.map(|counters| counters.num_storages_rebuilt.clone()),
+        )?,
+        measure_name
+    );
+    info!("{}", measure_untar);
 
-    Ok(())
+    let RebuiltSnapshotStorage {
+        snapshot_version,
+        storage,
+    } = version_and_storages;
+    Ok(UnarchivedSnapshot {
+        unpack_dir,
+        storage,
+        unpacked_snapshots_dir_and_version: UnpackedSnapshotsDirAndVersion {
+            unpacked_snapshots_dir,
+            snapshot_version,
+        },
+        measure_untar,
+    })
 }
 
-pub(crate) fn get_snapshot_file_name(slot: Slot) -> String {
-    slot.to_string()
+/// Unarchive only the account storages from an incremental snapshot archive.  The storages are
+/// rebuilt from files unpacked within `unpack_dir`, so it must outlive the returned storages.
+pub(crate) fn unarchive_incremental_snapshot_storage(
+    incremental_snapshot_archive_info: &IncrementalSnapshotArchiveInfo,
+    unpack_dir: impl AsRef<Path>,
+) -> Result<AccountStorageMap> {
+    unarchive_snapshot_storage(
+        incremental_snapshot_archive_info.path(),
+        incremental_snapshot_archive_info.archive_format(),
+        "incremental snapshot delta untar",
+        unpack_dir,
+    )
 }
 
-pub(crate) fn get_bank_snapshots_dir<P: AsRef<Path>>(path: P, slot: Slot) -> PathBuf {
-    path.as_ref().join(slot.to_string())
+/// Unarchive only the account storages from a snapshot archive, into a temporary directory from
+/// `temp_dir_factory`.  The storages are rebuilt from files unpacked within the returned
+/// directory, so it must outlive the returned storages.
+fn unarchive_snapshot_storage_in_temp_dir(
+    snapshot_archive_path: &Path,
+    measure_name: &'static str,
+    temp_dir_factory: &dyn TempDirFactory,
+) -> Result<(TempDir, AccountStorageMap)> {
+    let (_slot, _base_slot, archive_format) = parse_snapshot_archive_path(snapshot_archive_path)?;
+    let unpack_dir = temp_dir_factory.create_temp_dir(TMP_SNAPSHOT_ARCHIVE_PREFIX)?;
+    let storage = unarchive_snapshot_storage(
+        snapshot_archive_path,
+        archive_format,
+        measure_name,
+        &unpack_dir,
+    )?;
+    Ok((unpack_dir, storage))
 }
 
-fn get_io_error(error: &str) -> SnapshotError {
-    warn!("Snapshot Error: {:?}", error);
-    SnapshotError::Io(IoError::new(ErrorKind::Other, error))
+/// Unarchive only the account storages from a snapshot archive.  The storages are rebuilt from
+/// files unpacked within `unpack_dir`, so it must outlive the returned storages.
+fn unarchive_snapshot_storage(
+    snapshot_archive_path: impl AsRef<Path>,
+    archive_format: ArchiveFormat,
+    measure_name: &'static str,
+    unpack_dir: impl AsRef<Path>,
+) -> Result<AccountStorageMap> {
+    let account_paths = vec![unpack_dir.as_ref().join("accounts")];
+    fs::create_dir_all(&account_paths[0])?;
+
+    let (parallel_divisions, num_rebuilder_threads) = unarchive_thread_counts(None);
+    let unarchived_snapshot = unarchive_snapshot(
+        &TempDirInFactory::new(unpack_dir.as_ref()),
+        TMP_SNAPSHOT_ARCHIVE_PREFIX,
+        snapshot_archive_path,
+        measure_name,
+        &account_paths,
+        archive_format,
+        parallel_divisions,
+        num_rebuilder_threads,
+        Arc::new(AtomicU32::new(0)),
+        None,
+        None,
+        None,
+    )?;
+    Ok(unarchived_snapshot.storage)
 }
 
-#[derive(Debug, Copy, Clone)]
-/// allow tests to specify what happened to the serialized format
-pub enum VerifyBank {
-    /// the bank's serialized format is expected to be identical to what we are comparing against
-    Deterministic,
-    /// the serialized bank was 'reserialized' into a non-deterministic format at the specified slot
-    /// so, deserialize both files and compare deserialized results
-    NonDeterministic(Slot),
-}
-
-pub fn verify_snapshot_archive<P, Q, R>(
-    snapshot_archive: P,
-    snapshots_to_verify: Q,
-    storages_to_verify: R,
-    archive_format: ArchiveFormat,
-    verify_bank: VerifyBank,
-) where
-    P: AsRef<Path>,
-    Q: AsRef<Path>,
-    R: AsRef<Path>,
-{
-    let temp_dir = tempfile::TempDir::new().unwrap();
-    let unpack_dir = temp_dir.path();
-    untar_snapshot_in(
-        snapshot_archive,
-        unpack_dir,
-        &[unpack_dir.to_path_buf()],
-        archive_format,
-        1,
-    )
-    .unwrap();
+/// Reads the `snapshot_version` from a file. Before opening the file, its size
+/// is compared to `MAX_SNAPSHOT_VERSION_FILE_SIZE`. If the size exceeds this
+/// threshold, it is not opened and an error is returned.
+fn snapshot_version_from_file(path: impl AsRef<Path>) -> Result<String> {
+    // Check file size.
+    let file_size = fs::metadata(&path)?.len();
+    if file_size > MAX_SNAPSHOT_VERSION_FILE_SIZE {
+        let error_message = format!(
+            "snapshot version file too large: {} has {} bytes (max size is {} bytes)",
+            path.as_ref().display(),
+            file_size,
+            MAX_SNAPSHOT_VERSION_FILE_SIZE,
+        );
+        return Err(get_io_error(&error_message));
+    }
 
-    // Check snapshots are the same
-    let unpacked_snapshots = unpack_dir.join("snapshots");
-    if let VerifyBank::NonDeterministic(slot) = verify_bank {
-        // file contents may be different, but deserialized structs should be equal
-        let slot = slot.to_string();
-        let p1 = snapshots_to_verify.as_ref().join(&slot).join(&slot);
-        let p2 = unpacked_snapshots.join(&slot).join(&slot);
+    // Read snapshot_version from file.
+    let mut snapshot_version = String::new();
+    File::open(path).and_then(|mut f| f.read_to_string(&mut snapshot_version))?;
+    Ok(snapshot_version.trim().to_string())
+}
 
-        assert!(crate::serde_snapshot::compare_two_serialized_banks(&p1, &p2).unwrap());
-        std::fs::remove_file(p1).unwrap();
-        std::fs::remove_file(p2).unwrap();
+/// Check if an incremental snapshot is compatible with a full snapshot.  This is done by checking
+/// if the incremental snapshot's base slot is the same as the full snapshot's slot.
+///
+/// This must be checked before unarchiving, since merging mismatched snapshots only fails later,
+/// with a confusing deserialization error.
+fn check_are_snapshots_compatible(
+    full_snapshot_archive_info: &FullSnapshotArchiveInfo,
+    incremental_snapshot_archive_info: Option<&IncrementalSnapshotArchiveInfo>,
+) -> Result<()> {
+    if incremental_snapshot_archive_info.is_none() {
+        return Ok(());
     }
 
-    assert!(!dir_diff::is_different(&snapshots_to_verify, unpacked_snapshots).unwrap());
+    let incremental_snapshot_archive_info = incremental_snapshot_archive_info.unwrap();
 
-    // Check the account entries are the same
-    let unpacked_accounts = unpack_dir.join("accounts");
-    assert!(!dir_diff::is_different(&storages_to_verify, unpacked_accounts).unwrap());
+    (full_snapshot_archive_info.slot() == incremental_snapshot_archive_info.base_slot())
+        .then_some(())
+        .ok_or_else(|| SnapshotError::MismatchedBaseSlot {
+            expected: full_snapshot_archive_info.slot(),
+            found: incremental_snapshot_archive_info.base_slot(),
+        })
 }
 
-/// Remove outdated bank snapshots
-pub fn purge_old_bank_snapshots(bank_snapshots_dir: impl AsRef<Path>) {
-    let do_purge = |mut bank_snapshots: Vec<BankSnapshotInfo>| {
-        bank_snapshots.sort_unstable();
-        bank_snapshots
-            .into_iter()
-            .rev()
-            .skip(MAX_BANK_SNAPSHOTS_TO_RETAIN)
-            .for_each(|bank_snapshot| {
-                let r = remove_bank_snapshot(bank_snapshot.slot, &bank_snapshots_dir);
-                if r.is_err() {
-                    warn!(
-                        "Couldn't remove bank snapshot at: {}",
-                        bank_snapshot.snapshot_path.display()
-                    );
-                }
-            })
-    };
+/// Get the `&str` from a `&Path`
+pub fn path_to_file_name_str(path: &Path) -> Result<&str> {
+    path.file_name()
+        .ok_or_else(|| SnapshotError::PathToFileNameError(path.to_path_buf()))?
+        .to_str()
+        .ok_or_else(|| SnapshotError::FileNameToStrError(path.to_path_buf()))
+}
 
-    do_purge(get_bank_snapshots_pre(&bank_snapshots_dir));
-    do_purge(get_bank_snapshots_post(&bank_snapshots_dir));
+pub fn build_snapshot_archives_remote_dir(snapshot_archives_dir: impl AsRef<Path>) -> PathBuf {
+    snapshot_archives_dir
+        .as_ref()
+        .join(SNAPSHOT_ARCHIVE_DOWNLOAD_DIR)
 }
 
-/// Gather the necessary elements for a snapshot of the given `root_bank`.
-///
-/// **DEVELOPER NOTE** Any error that is returned from this function may bring down the node!  This
-/// function is called from AccountsBackgroundService to handle snapshot requests.  Since taking a
-/// snapshot is not permitted to fail, any errors returned here will trigger the node to shutdown.
-/// So, be careful whenever adding new code that may return errors.
-#[allow(clippy::too_many_arguments)]
-pub fn snapshot_bank(
-    root_bank: &Bank,
-    status_cache_slot_deltas: Vec<BankSlotDelta>,
-    accounts_package_sender: &Sender<AccountsPackage>,
-    bank_snapshots_dir: impl AsRef<Path>,
+/// Build the full snapshot archive path from its components: the snapshot archives directory, the
+/// snapshot slot, the accounts hash, and the archive format.
+pub fn build_full_snapshot_archive_path(
     full_snapshot_archives_dir: impl AsRef<Path>,
+    slot: Slot,
+    hash: &Hash,
+    archive_format: ArchiveFormat,
+) -> PathBuf {
+    full_snapshot_archives_dir.as_ref().join(format!(
+        "snapshot-{}-{}.{}",
+        slot,
+        hash,
+        archive_format.extension(),
+    ))
+}
+
+/// Build the incremental snapshot archive path from its components: the snapshot archives
+/// directory, the snapshot base slot, the snapshot slot, the accounts hash, and the archive
+/// format.
+pub fn build_incremental_snapshot_archive_path(
     incremental_snapshot_archives_dir: impl AsRef<Path>,
-    snapshot_version: SnapshotVersion,
+    base_slot: Slot,
+    slot: Slot,
+    hash: &Hash,
     archive_format: ArchiveFormat,
-    hash_for_testing: Option<Hash>,
-    accounts_package_type: AccountsPackageType,
-) -> Result<()> {
-    let snapshot_storages = get_snapshot_storages(root_bank);
+) -> PathBuf {
+    incremental_snapshot_archives_dir.as_ref().join(format!(
+        "incremental-snapshot-{}-{}-{}.{}",
+        base_slot,
+        slot,
+        hash,
+        archive_format.extension(),
+    ))
+}
 
-    let mut add_snapshot_time = Measure::start("add-snapshot-ms");
-    let bank_snapshot_info = add_bank_snapshot(
-        &bank_snapshots_dir,
-        root_bank,
-        &snapshot_storages,
-        snapshot_version,
-    )?;
-    add_snapshot_time.stop();
-    inc_new_counter_info!("add-snapshot-ms", add_snapshot_time.as_ms() as usize);
+/// Parse a full snapshot archive filename into its Slot, Hash, and Archive Format
+pub(crate) fn parse_full_snapshot_archive_filename(
+    archive_filename: &str,
+) -> Result<(Slot, Hash, ArchiveFormat)> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(FULL_SNAPSHOT_ARCHIVE_FILENAME_REGEX).unwrap();
+    }
 
-    let accounts_package = AccountsPackage::new(
-        accounts_package_type,
-        root_bank,
-        &bank_snapshot_info,
-        bank_snapshots_dir,
-        status_cache_slot_deltas,
-        full_snapshot_archives_dir,
-        incremental_snapshot_archives_dir,
-        snapshot_storages,
-        archive_format,
-        snapshot_version,
-        hash_for_testing,
-    )
-    .expect("failed to hard link bank snapshot into a tmpdir");
+    let do_parse = || {
+        RE.captures(archive_filename).and_then(|captures| {
+            let slot = captures
+                .name("slot")
+                .map(|x| x.as_str().parse::<Slot>())?
+                .ok()?;
+            let hash = captures
+                .name("hash")
+                .map(|x| x.as_str().parse::<Hash>())?
+                .ok()?;
+            let archive_format = captures
+                .name("ext")
+                .map(|x| x.as_str().parse::<ArchiveFormat>())?
+                .ok()?;
 
-    // Submit the accounts package
-    accounts_package_sender
-        .send(accounts_package)
-        .expect("send accounts package");
+            Some((slot, hash, archive_format))
+        })
+    };
 
-    Ok(())
+    do_parse().ok_or_else(|| {
+        SnapshotError::ParseSnapshotArchiveFileNameError(archive_filename.to_string())
+    })
 }
 
-/// Get the snapshot storages for this bank
-fn get_snapshot_storages(bank: &Bank) -> SnapshotStorages {
-    let mut measure_snapshot_storages = Measure::start("snapshot-storages");
-    let snapshot_storages = bank.get_snapshot_storages(None);
-    measure_snapshot_storages.stop();
-    let snapshot_storages_count = snapshot_storages.iter().map(Vec::len).sum::<usize>();
-    datapoint_info!(
-        "get_snapshot_storages",
-        ("snapshot-storages-count", snapshot_storages_count, i64),
-        (
-            "snapshot-storages-time-ms",
-            measure_snapshot_storages.as_ms(),
-            i64
-        ),
-    );
+/// Parse an incremental snapshot archive filename into its base Slot, actual Slot, Hash, and Archive Format
+pub(crate) fn parse_incremental_snapshot_archive_filename(
+    archive_filename: &str,
+) -> Result<(Slot, Slot, Hash, ArchiveFormat)> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(INCREMENTAL_SNAPSHOT_ARCHIVE_FILENAME_REGEX).unwrap();
+    }
 
-    snapshot_storages
+    let do_parse = || {
+        RE.captures(archive_filename).and_then(|captures| {
+            let base_slot = captures
+                .name("base")
+                .map(|x| x.as_str().parse::<Slot>())?
+                .ok()?;
+            let slot = captures
+                .name("slot")
+                .map(|x| x.as_str().parse::<Slot>())?
+                .ok()?;
+            let hash = captures
+                .name("hash")
+                .map(|x| x.as_str().parse::<Hash>())?
+                .ok()?;
+            let archive_format = captures
+                .name("ext")
+                .map(|x| x.as_str().parse::<ArchiveFormat>())?
+                .ok()?;
+
+            Some((base_slot, slot, hash, archive_format))
+        })
+    };
+
+    do_parse().ok_or_else(|| {
+        SnapshotError::ParseSnapshotArchiveFileNameError(archive_filename.to_string())
+    })
 }
 
-/// Convenience function to create a full snapshot archive out of any Bank, regardless of state.
-/// The Bank will be frozen during the process.
-/// This is only called from ledger-tool or tests. Warping is a special case as well.
-///
-/// Requires:
-///     - `bank` is complete
-pub fn bank_to_full_snapshot_archive(
-    bank_snapshots_dir: impl AsRef<Path>,
-    bank: &Bank,
-    snapshot_version: Option<SnapshotVersion>,
+/// Walk down the snapshot archive to collect snapshot archive file info
+fn get_snapshot_archives<T, F>(snapshot_archives_dir: &Path, cb: F) -> Vec<T>
+where
+    F: Fn(PathBuf) -> Result<T>,
+{
+    let walk_dir = |dir: &Path| -> Vec<T> {
+        let entry_iter = fs::read_dir(dir);
+        match entry_iter {
+            Err(err) => {
+                info!(
+                    "Unable to read snapshot archives directory: err: {}, path: {}",
+                    err,
+                    dir.display()
+                );
+                vec![]
+            }
+            Ok(entries) => entries
+                .filter_map(|entry| entry.map_or(None, |entry| cb(entry.path()).ok()))
+                .collect(),
+        }
+    };
+
+    let mut ret = walk_dir(snapshot_archives_dir);
+    let remote_dir = build_snapshot_archives_remote_dir(snapshot_archives_dir);
+    if remote_dir.exists() {
+        ret.append(&mut walk_dir(remote_dir.as_ref()));
+    }
+    ret
+}
+
+/// Get a list of the full snapshot archives from a directory
+pub fn get_full_snapshot_archives(
     full_snapshot_archives_dir: impl AsRef<Path>,
+) -> Vec<FullSnapshotArchiveInfo> {
+    get_snapshot_archives(
+        full_snapshot_archives_dir.as_ref(),
+        FullSnapshotArchiveInfo::new_from_path,
+    )
+}
+
+/// Get a list of the incremental snapshot archives from a directory
+pub fn get_incremental_snapshot_archives(
     incremental_snapshot_archives_dir: impl AsRef<Path>,
-    archive_format: ArchiveFormat,
-    maximum_full_snapshot_archives_to_retain: usize,
-    maximum_incremental_snapshot_archives_to_retain: usize,
-) -> Result<FullSnapshotArchiveInfo> {
-    let snapshot_version = snapshot_version.unwrap_or_default();
+) -> Vec<IncrementalSnapshotArchiveInfo> {
+    get_snapshot_archives(
+        incremental_snapshot_archives_dir.as_ref(),
+        IncrementalSnapshotArchiveInfo::new_from_path,
+    )
+}
 
-    assert!(bank.is_complete());
-    bank.squash(); // Bank may not be a root
-    bank.force_flush_accounts_cache();
-    bank.clean_accounts(Some(bank.slot()));
-    bank.update_accounts_hash();
-    bank.rehash(); // Bank accounts may have been manually modified by the caller
+/// How many snapshot archives `iter_snapshot_archives()` reads per pass over the directory
+const ITER_SNAPSHOT_ARCHIVES_BATCH_SIZE: usize = 1024;
 
-    let temp_dir = tempfile::tempdir_in(bank_snapshots_dir)?;
-    let snapshot_storages = bank.get_snapshot_storages(None);
-    let bank_snapshot_info =
-        add_bank_snapshot(&temp_dir, bank, &snapshot_storages, snapshot_version)?;
+/// Iterate over the snapshot archives in a directory, full and incremental alike, by slot
+///
+/// Files whose names do not parse as snapshot archives are skipped.  To yield by slot without
+/// keeping every archive info in memory, the directory is read once per batch of
+/// `ITER_SNAPSHOT_ARCHIVES_BATCH_SIZE` archives, keeping only the lowest archives after the
+/// previous batch.
+pub fn iter_snapshot_archives(
+    snapshot_archives_dir: impl AsRef<Path>,
+) -> impl Iterator<Item = SnapshotArchiveInfo> {
+    iter_snapshot_archives_in_batches(snapshot_archives_dir, ITER_SNAPSHOT_ARCHIVES_BATCH_SIZE)
+}
 
-    package_and_archive_full_snapshot(
-        bank,
-        &bank_snapshot_info,
-        &temp_dir,
-        full_snapshot_archives_dir,
-        incremental_snapshot_archives_dir,
-        snapshot_storages,
+fn iter_snapshot_archives_in_batches(
+    snapshot_archives_dir: impl AsRef<Path>,
+    batch_size: usize,
+) -> impl Iterator<Item = SnapshotArchiveInfo> {
+    let snapshot_archives_dir = snapshot_archives_dir.as_ref().to_path_buf();
+    let remote_dir = build_snapshot_archives_remote_dir(&snapshot_archives_dir);
+    let dirs = [snapshot_archives_dir, remote_dir];
+    let mut last_in_batch = None;
+    let mut batch = Vec::new().into_iter();
+    std::iter::from_fn(move || {
+        if batch.as_slice().is_empty() {
+            let next_batch =
+                read_snapshot_archives_batch(&dirs, last_in_batch.as_ref(), batch_size);
+            last_in_batch = next_batch.last().cloned().or(last_in_batch.take());
+            batch = next_batch.into_iter();
+        }
+        batch.next()
+    })
+}
+
+/// Read the lowest `batch_size` snapshot archives in `dirs` that come after `after`, in order
+fn read_snapshot_archives_batch(
+    dirs: &[PathBuf],
+    after: Option<&SnapshotArchiveInfo>,
+    batch_size: usize,
+) -> Vec<SnapshotArchiveInfo> {
+    let mut batch = BinaryHeap::with_capacity(batch_size + 1);
+    dirs.iter()
+        .filter_map(|dir| match fs::read_dir(dir) {
+            Ok(entries) => Some(entries),
+            Err(err) => {
+                debug!(
+                    "Unable to read snapshot archives directory: err: {}, path: {}",
+                    err,
+                    dir.display()
+                );
+                None
+            }
+        })
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            match parse_snapshot_archive_info(path.clone()) {
+                Ok(snapshot_archive_info) => Some(snapshot_archive_info),
+                Err(err) => {
+                    debug!("Skipping {}: {}", path.display(), err);
+                    None
+                }
+            }
+        })
+        .filter(|snapshot_archive_info| after.map_or(true, |after| snapshot_archive_info > after))
+        .for_each(|snapshot_archive_info| {
+            // the heap is a max-heap, so popping drops the highest archive beyond the batch size
+            batch.push(snapshot_archive_info);
+            if batch.len() > batch_size {
+                batch.pop();
+            }
+        });
+    batch.into_sorted_vec()
+}
+
+/// Parse the path to either a full or an incremental snapshot archive
+fn parse_snapshot_archive_info(path: PathBuf) -> Result<SnapshotArchiveInfo> {
+    let archive_filename = path_to_file_name_str(&path)?;
+    let (slot, hash, archive_format) = match parse_full_snapshot_archive_filename(archive_filename)
+    {
+        Ok(parsed) => parsed,
+        Err(_) => {
+            let (_base_slot, slot, hash, archive_format) =
+                parse_incremental_snapshot_archive_filename(archive_filename)?;
+            (slot, hash, archive_format)
+        }
+    };
+    Ok(SnapshotArchiveInfo {
+        path,
+        slot,
+        hash,
         archive_format,
-        snapshot_version,
-        maximum_full_snapshot_archives_to_retain,
-        maximum_incremental_snapshot_archives_to_retain,
-    )
+    })
 }
 
-/// Convenience function to create an incremental snapshot archive out of any Bank, regardless of
-/// state.  The Bank will be frozen during the process.
-/// This is only called from ledger-tool or tests. Warping is a special case as well.
+/// A problem with the name of a snapshot archive, see `audit_archive_names()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArchiveNameIssue {
+    /// the name looks like a snapshot archive's, but the extension is not a known archive format
+    InvalidExtension(PathBuf),
+    /// the name looks like a snapshot archive's, but the slots or the hash do not parse
+    Malformed(PathBuf),
+    /// more than one archive has the same slot (and base slot, for incremental snapshot archives)
+    DuplicateSlot {
+        base_slot: Option<Slot>,
+        slot: Slot,
+        paths: Vec<PathBuf>,
+    },
+}
+
+/// Report the snapshot archives in a directory whose names are malformed or inconsistent
 ///
-/// Requires:
-///     - `bank` is complete
-///     - `bank`'s slot is greater than `full_snapshot_slot`
-pub fn bank_to_incremental_snapshot_archive(
-    bank_snapshots_dir: impl AsRef<Path>,
-    bank: &Bank,
-    full_snapshot_slot: Slot,
-    snapshot_version: Option<SnapshotVersion>,
-    full_snapshot_archives_dir: impl AsRef<Path>,
-    incremental_snapshot_archives_dir: impl AsRef<Path>,
-    archive_format: ArchiveFormat,
-    maximum_full_snapshot_archives_to_retain: usize,
-    maximum_incremental_snapshot_archives_to_retain: usize,
-) -> Result<IncrementalSnapshotArchiveInfo> {
-    let snapshot_version = snapshot_version.unwrap_or_default();
+/// Only filenames are read.  Files whose names do not start like a full or an incremental snapshot
+/// archive's are not considered, so other files in the directory are not reported.  This is for
+/// cleaning up after manual file operations.
+pub fn audit_archive_names(dir: impl AsRef<Path>) -> Vec<ArchiveNameIssue> {
+    let mut archive_names: Vec<_> = match fs::read_dir(dir.as_ref()) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect(),
+        Err(err) => {
+            info!(
+                "Unable to read snapshot archives directory: err: {}, path: {}",
+                err,
+                dir.as_ref().display()
+            );
+            return Vec::new();
+        }
+    };
+    archive_names.sort_unstable();
+
+    let mut issues = Vec::new();
+    let mut paths_by_slots: BTreeMap<(Option<Slot>, Slot), Vec<PathBuf>> = BTreeMap::new();
+    for archive_name in archive_names {
+        let path = dir.as_ref().join(&archive_name);
+        let parsed = if archive_name.starts_with("incremental-snapshot-") {
+            parse_incremental_snapshot_archive_filename(&archive_name)
+                .map(|(base_slot, slot, ..)| (Some(base_slot), slot))
+        } else if archive_name.starts_with("snapshot-") {
+            parse_full_snapshot_archive_filename(&archive_name).map(|(slot, ..)| (None, slot))
+        } else {
+            continue;
+        };
+        match parsed {
+            Ok(slots) => paths_by_slots.entry(slots).or_default().push(path),
+            Err(_) => {
+                let has_valid_extension = archive_name
+                    .split_once('.')
+                    .map_or(false, |(_, extension)| {
+                        extension.parse::<ArchiveFormat>().is_ok()
+                    });
+                issues.push(if has_valid_extension {
+                    ArchiveNameIssue::Malformed(path)
+                } else {
+                    ArchiveNameIssue::InvalidExtension(path)
+                });
+            }
+        }
+    }
 
-    assert!(bank.is_complete());
-    assert!(bank.slot() > full_snapshot_slot);
-    bank.squash(); // Bank may not be a root
-    bank.force_flush_accounts_cache();
-    bank.clean_accounts(Some(full_snapshot_slot));
-    bank.update_accounts_hash();
-    bank.rehash(); // Bank accounts may have been manually modified by the caller
+    issues.extend(
+        paths_by_slots
+            .into_iter()
+            .filter(|(_, paths)| paths.len() > 1)
+            .map(
+                |((base_slot, slot), paths)| ArchiveNameIssue::DuplicateSlot {
+                    base_slot,
+                    slot,
+                    paths,
+                },
+            ),
+    );
+    issues
+}
 
-    let temp_dir = tempfile::tempdir_in(bank_snapshots_dir)?;
-    let snapshot_storages = bank.get_snapshot_storages(Some(full_snapshot_slot));
-    let bank_snapshot_info =
-        add_bank_snapshot(&temp_dir, bank, &snapshot_storages, snapshot_version)?;
+/// Get the epochs of the full snapshot archives in a directory
+///
+/// Only the archives' filenames are read; their slots are mapped to epochs with the epoch
+/// schedule from `genesis_config`.
+pub fn epochs_with_snapshots(
+    full_snapshot_archives_dir: impl AsRef<Path>,
+    genesis_config: &GenesisConfig,
+) -> BTreeSet<Epoch> {
+    get_full_snapshot_archives(full_snapshot_archives_dir)
+        .iter()
+        .map(|full_snapshot_archive_info| {
+            genesis_config
+                .epoch_schedule
+                .get_epoch(full_snapshot_archive_info.slot())
+        })
+        .collect()
+}
 
-    package_and_archive_incremental_snapshot(
-        bank,
-        full_snapshot_slot,
-        &bank_snapshot_info,
-        &temp_dir,
-        full_snapshot_archives_dir,
+/// Get the highest slot of the full snapshot archives in a directory
+pub fn get_highest_full_snapshot_archive_slot(
+    full_snapshot_archives_dir: impl AsRef<Path>,
+) -> Option<Slot> {
+    get_highest_full_snapshot_archive_info(full_snapshot_archives_dir)
+        .map(|full_snapshot_archive_info| full_snapshot_archive_info.slot())
+}
+
+/// Get the highest slot of the incremental snapshot archives in a directory, for a given full
+/// snapshot slot
+pub fn get_highest_incremental_snapshot_archive_slot(
+    incremental_snapshot_archives_dir: impl AsRef<Path>,
+    full_snapshot_slot: Slot,
+) -> Option<Slot> {
+    get_highest_incremental_snapshot_archive_info(
         incremental_snapshot_archives_dir,
-        snapshot_storages,
-        archive_format,
-        snapshot_version,
-        maximum_full_snapshot_archives_to_retain,
-        maximum_incremental_snapshot_archives_to_retain,
+        full_snapshot_slot,
     )
+    .map(|incremental_snapshot_archive_info| incremental_snapshot_archive_info.slot())
 }
 
-/// Helper function to hold shared code to package, process, and archive full snapshots
-#[allow(clippy::too_many_arguments)]
-pub fn package_and_archive_full_snapshot(
-    bank: &Bank,
-    bank_snapshot_info: &BankSnapshotInfo,
-    bank_snapshots_dir: impl AsRef<Path>,
+/// Get the path (and metadata) for the full snapshot archive with the highest slot in a directory
+pub fn get_highest_full_snapshot_archive_info(
     full_snapshot_archives_dir: impl AsRef<Path>,
-    incremental_snapshot_archives_dir: impl AsRef<Path>,
-    snapshot_storages: SnapshotStorages,
-    archive_format: ArchiveFormat,
-    snapshot_version: SnapshotVersion,
-    maximum_full_snapshot_archives_to_retain: usize,
-    maximum_incremental_snapshot_archives_to_retain: usize,
-) -> Result<FullSnapshotArchiveInfo> {
-    let slot_deltas = bank.status_cache.read().unwrap().root_slot_deltas();
-    let accounts_package = AccountsPackage::new(
-        AccountsPackageType::Snapshot(SnapshotType::FullSnapshot),
-        bank,
-        bank_snapshot_info,
-        bank_snapshots_dir,
-        slot_deltas,
-        &full_snapshot_archives_dir,
-        &incremental_snapshot_archives_dir,
-        snapshot_storages,
-        archive_format,
-        snapshot_version,
-        None,
-    )?;
-
-    crate::serde_snapshot::reserialize_bank_with_new_accounts_hash(
-        accounts_package.snapshot_links.path(),
-        accounts_package.slot,
-        &bank.get_accounts_hash(),
-        None,
-        None, // todo: this needs to be passed through
-    );
+) -> Option<FullSnapshotArchiveInfo> {
+    let mut full_snapshot_archives = get_full_snapshot_archives(full_snapshot_archives_dir);
+    full_snapshot_archives.sort_unstable();
+    full_snapshot_archives.into_iter().rev().next()
+}
 
-    let snapshot_package = SnapshotPackage::new(accounts_package, bank.get_accounts_hash());
-    archive_snapshot_package(
-        &snapshot_package,
-        full_snapshot_archives_dir,
+/// Get the path for the incremental snapshot archive with the highest slot, for a given full
+/// snapshot slot, in a directory
+///
+/// If there are multiple archives with the highest slot (i.e. with different hashes), the one
+/// with the lowest path is picked.
+pub fn get_highest_incremental_snapshot_archive_info(
+    incremental_snapshot_archives_dir: impl AsRef<Path>,
+    full_snapshot_slot: Slot,
+) -> Option<IncrementalSnapshotArchiveInfo> {
+    get_highest_incremental_snapshot_archive_info_with_policy(
         incremental_snapshot_archives_dir,
-        maximum_full_snapshot_archives_to_retain,
-        maximum_incremental_snapshot_archives_to_retain,
-    )?;
-
-    Ok(FullSnapshotArchiveInfo::new(
-        snapshot_package.snapshot_archive_info,
-    ))
+        full_snapshot_slot,
+        DuplicateIncrementalSnapshotArchivePolicy::PickFirstPath,
+    )
+    .expect("picking the first path never fails")
 }
 
-/// Helper function to hold shared code to package, process, and archive incremental snapshots
-#[allow(clippy::too_many_arguments)]
-pub fn package_and_archive_incremental_snapshot(
-    bank: &Bank,
-    incremental_snapshot_base_slot: Slot,
-    bank_snapshot_info: &BankSnapshotInfo,
-    bank_snapshots_dir: impl AsRef<Path>,
+/// Get the path for the incremental snapshot archive with the highest slot, for a given full
+/// snapshot slot, in a directory, resolving duplicate archives per `policy`
+pub fn get_highest_incremental_snapshot_archive_info_with_policy(
+    incremental_snapshot_archives_dir: impl AsRef<Path>,
+    full_snapshot_slot: Slot,
+    policy: DuplicateIncrementalSnapshotArchivePolicy,
+) -> Result<Option<IncrementalSnapshotArchiveInfo>> {
+    pick_highest_incremental_snapshot_archive(
+        get_incremental_snapshot_archives(incremental_snapshot_archives_dir)
+            .into_iter()
+            .filter(|incremental_snapshot_archive_info| {
+                incremental_snapshot_archive_info.base_slot() == full_snapshot_slot
+            }),
+        policy,
+    )
+}
+
+/// Pick the incremental snapshot archive with the highest slot
+///
+/// Archives with the same base slot and slot compare equal, so which of them gets picked is
+/// decided by `policy` instead of by the order they were found in.
+fn pick_highest_incremental_snapshot_archive(
+    incremental_snapshot_archives: impl IntoIterator<Item = IncrementalSnapshotArchiveInfo>,
+    policy: DuplicateIncrementalSnapshotArchivePolicy,
+) -> Result<Option<IncrementalSnapshotArchiveInfo>> {
+    let mut highest_incremental_snapshot_archives: Vec<IncrementalSnapshotArchiveInfo> = Vec::new();
+    for incremental_snapshot_archive_info in incremental_snapshot_archives {
+        match highest_incremental_snapshot_archives
+            .first()
+            .map(|highest| incremental_snapshot_archive_info.cmp(highest))
+        {
+            None | Some(Ordering::Greater) => {
+                highest_incremental_snapshot_archives = vec![incremental_snapshot_archive_info]
+            }
+            Some(Ordering::Equal) => {
+                highest_incremental_snapshot_archives.push(incremental_snapshot_archive_info)
+            }
+            Some(Ordering::Less) => (),
+        }
+    }
+
+    highest_incremental_snapshot_archives.sort_unstable_by(|a, b| a.path().cmp(b.path()));
+    if highest_incremental_snapshot_archives.len() > 1 {
+        let duplicate = &highest_incremental_snapshot_archives[0];
+        let (base_slot, slot) = (duplicate.base_slot(), duplicate.slot());
+        match policy {
+            DuplicateIncrementalSnapshotArchivePolicy::PickFirstPath => warn!(
+                "Found {} incremental snapshot archives with base slot {} and slot {}, picking {}",
+                highest_incremental_snapshot_archives.len(),
+                base_slot,
+                slot,
+                duplicate.path().display(),
+            ),
+            DuplicateIncrementalSnapshotArchivePolicy::Fail => {
+                return Err(SnapshotError::DuplicateIncrementalSnapshotArchives {
+                    base_slot,
+                    slot,
+                    count: highest_incremental_snapshot_archives.len(),
+                })
+            }
+        }
+    }
+    Ok(highest_incremental_snapshot_archives.into_iter().next())
+}
+
+pub fn purge_old_snapshot_archives(
     full_snapshot_archives_dir: impl AsRef<Path>,
     incremental_snapshot_archives_dir: impl AsRef<Path>,
-    snapshot_storages: SnapshotStorages,
-    archive_format: ArchiveFormat,
-    snapshot_version: SnapshotVersion,
     maximum_full_snapshot_archives_to_retain: usize,
     maximum_incremental_snapshot_archives_to_retain: usize,
-) -> Result<IncrementalSnapshotArchiveInfo> {
-    let slot_deltas = bank.status_cache.read().unwrap().root_slot_deltas();
-    let accounts_package = AccountsPackage::new(
-        AccountsPackageType::Snapshot(SnapshotType::IncrementalSnapshot(
-            incremental_snapshot_base_slot,
-        )),
-        bank,
-        bank_snapshot_info,
-        bank_snapshots_dir,
-        slot_deltas,
-        &full_snapshot_archives_dir,
-        &incremental_snapshot_archives_dir,
-        snapshot_storages,
-        archive_format,
-        snapshot_version,
-        None,
-    )?;
+    maximum_snapshots_total_bytes: Option<u64>,
+) {
+    info!(
+        "Purging old full snapshot archives in {}, retaining up to {} full snapshots",
+        full_snapshot_archives_dir.as_ref().display(),
+        maximum_full_snapshot_archives_to_retain
+    );
 
-    crate::serde_snapshot::reserialize_bank_with_new_accounts_hash(
-        accounts_package.snapshot_links.path(),
-        accounts_package.slot,
-        &bank.get_accounts_hash(),
-        None,
-        None, // todo: this needs to be passed through
+    let mut full_snapshot_archives = get_full_snapshot_archives(&full_snapshot_archives_dir);
+    full_snapshot_archives.sort_unstable();
+    full_snapshot_archives.reverse();
+
+    let num_to_retain = full_snapshot_archives.len().min(
+        maximum_full_snapshot_archives_to_retain
+            .max(1 /* Always keep at least one full snapshot */),
+    );
+    trace!(
+        "There are {} full snapshot archives, retaining {}",
+        full_snapshot_archives.len(),
+        num_to_retain,
     );
 
-    let snapshot_package = SnapshotPackage::new(accounts_package, bank.get_accounts_hash());
-    archive_snapshot_package(
-        &snapshot_package,
-        full_snapshot_archives_dir,
-        incremental_snapshot_archives_dir,
-        maximum_full_snapshot_archives_to_retain,
-        maximum_incremental_snapshot_archives_to_retain,
-    )?;
+    let (full_snapshot_archives_to_retain, full_snapshot_archives_to_remove) =
+        if full_snapshot_archives.is_empty() {
+            None
+        } else {
+            Some(full_snapshot_archives.split_at(num_to_retain))
+        }
+        .unwrap_or_default();
 
-    Ok(IncrementalSnapshotArchiveInfo::new(
-        incremental_snapshot_base_slot,
-        snapshot_package.snapshot_archive_info,
-    ))
-}
+    let retained_full_snapshot_slots = full_snapshot_archives_to_retain
+        .iter()
+        .map(|ai| ai.slot())
+        .collect::<HashSet<_>>();
 
-pub fn should_take_full_snapshot(
-    block_height: Slot,
-    full_snapshot_archive_interval_slots: Slot,
-) -> bool {
-    block_height % full_snapshot_archive_interval_slots == 0
-}
+    fn remove_archives<T: SnapshotArchiveInfoGetter>(archives: &[T]) {
+        for path in archives.iter().map(|a| a.path()) {
+            trace!("Removing snapshot archive: {}", path.display());
+            fs::remove_file(path)
+                .unwrap_or_else(|err| info!("Failed to remove {}: {}", path.display(), err));
+        }
+    }
+    remove_archives(full_snapshot_archives_to_remove);
 
-pub fn should_take_incremental_snapshot(
-    block_height: Slot,
-    incremental_snapshot_archive_interval_slots: Slot,
-    last_full_snapshot_slot: Option<Slot>,
-) -> bool {
-    block_height % incremental_snapshot_archive_interval_slots == 0
-        && last_full_snapshot_slot.is_some()
-}
+    info!(
+        "Purging old incremental snapshot archives in {}, retaining up to {} incremental snapshots",
+        incremental_snapshot_archives_dir.as_ref().display(),
+        maximum_incremental_snapshot_archives_to_retain
+    );
+    let mut incremental_snapshot_archives_by_base_slot = HashMap::<Slot, Vec<_>>::new();
+    for incremental_snapshot_archive in
+        get_incremental_snapshot_archives(&incremental_snapshot_archives_dir)
+    {
+        incremental_snapshot_archives_by_base_slot
+            .entry(incremental_snapshot_archive.base_slot())
+            .or_default()
+            .push(incremental_snapshot_archive)
+    }
 
-#[cfg(test)]
-mod tests {
-    use {
-        super::*,
-        crate::{accounts_db::ACCOUNTS_DB_CONFIG_FOR_TESTING, status_cache::Status},
-        assert_matches::assert_matches,
-        bincode::{deserialize_from, serialize_into},
-        solana_sdk::{
-            genesis_config::create_genesis_config,
-            native_token::sol_to_lamports,
-            signature::{Keypair, Signer},
-            slot_history::SlotHistory,
-            system_transaction,
-            transaction::SanitizedTransaction,
-        },
-        std::{convert::TryFrom, mem::size_of},
-        tempfile::NamedTempFile,
-    };
+    let highest_full_snapshot_slot = retained_full_snapshot_slots.iter().max().copied();
+    for (base_slot, mut incremental_snapshot_archives) in incremental_snapshot_archives_by_base_slot
+    {
+        incremental_snapshot_archives.sort_unstable();
+        let num_to_retain = if Some(base_slot) == highest_full_snapshot_slot {
+            maximum_incremental_snapshot_archives_to_retain
+        } else {
+            usize::from(retained_full_snapshot_slots.contains(&base_slot))
+        };
+        trace!(
+            "There are {} incremental snapshot archives for base slot {}, removing {} of them",
+            incremental_snapshot_archives.len(),
+            base_slot,
+            incremental_snapshot_archives
+                .len()
+                .saturating_sub(num_to_retain),
+        );
 
-    #[test]
-    fn test_serialize_snapshot_data_file_under_limit() {
-        let temp_dir = tempfile::TempDir::new().unwrap();
-        let expected_consumed_size = size_of::<u32>() as u64;
-        let consumed_size = serialize_snapshot_data_file_capped(
-            &temp_dir.path().join("data-file"),
-            expected_consumed_size,
-            |stream| {
-                serialize_into(stream, &2323_u32)?;
-                Ok(())
-            },
-        )
-        .unwrap();
-        assert_eq!(consumed_size, expected_consumed_size);
+        incremental_snapshot_archives.truncate(
+            incremental_snapshot_archives
+                .len()
+                .saturating_sub(num_to_retain),
+        );
+        remove_archives(&incremental_snapshot_archives);
     }
 
-    #[test]
-    fn test_serialize_snapshot_data_file_over_limit() {
-        let temp_dir = tempfile::TempDir::new().unwrap();
-        let expected_consumed_size = size_of::<u32>() as u64;
-        let result = serialize_snapshot_data_file_capped(
-            &temp_dir.path().join("data-file"),
-            expected_consumed_size - 1,
-            |stream| {
-                serialize_into(stream, &2323_u32)?;
-                Ok(())
-            },
+    if let Some(maximum_snapshots_total_bytes) = maximum_snapshots_total_bytes {
+        purge_snapshot_archives_over_total_bytes(
+            full_snapshot_archives_dir.as_ref(),
+            incremental_snapshot_archives_dir.as_ref(),
+            maximum_snapshots_total_bytes,
         );
-        assert_matches!(result, Err(SnapshotError::Io(ref message)) if message.to_string().starts_with("too large snapshot data file to serialize"));
     }
+}
 
-    #[test]
-    fn test_deserialize_snapshot_data_file_under_limit() {
-        let expected_data = 2323_u32;
-        let expected_consumed_size = size_of::<u32>() as u64;
+/// Remove snapshot archives, oldest first, until the full and incremental snapshot archives
+/// together take up no more than `maximum_snapshots_total_bytes`
+///
+/// The highest full snapshot archive is always kept, even if it alone exceeds the limit.
+fn purge_snapshot_archives_over_total_bytes(
+    full_snapshot_archives_dir: &Path,
+    incremental_snapshot_archives_dir: &Path,
+    maximum_snapshots_total_bytes: u64,
+) {
+    let archive_size = |path: &Path| fs::metadata(path).map_or(0, |metadata| metadata.len());
 
-        let temp_dir = tempfile::TempDir::new().unwrap();
-        serialize_snapshot_data_file_capped(
-            &temp_dir.path().join("data-file"),
-            expected_consumed_size,
-            |stream| {
-                serialize_into(stream, &expected_data)?;
-                Ok(())
-            },
+    let mut full_snapshot_archives = get_full_snapshot_archives(full_snapshot_archives_dir);
+    full_snapshot_archives.sort_unstable();
+    let highest_full_snapshot_archive = full_snapshot_archives.pop();
+    let mut total_bytes = highest_full_snapshot_archive
+        .as_ref()
+        .map_or(0, |archive| archive_size(archive.path()));
+
+    let mut snapshot_archives: Vec<_> = full_snapshot_archives
+        .iter()
+        .map(|archive| archive.snapshot_archive_info().clone())
+        .chain(
+            get_incremental_snapshot_archives(incremental_snapshot_archives_dir)
+                .iter()
+                .map(|archive| archive.snapshot_archive_info().clone()),
         )
-        .unwrap();
+        .map(|archive| {
+            let size = archive_size(&archive.path);
+            total_bytes += size;
+            (archive, size)
+        })
+        .collect();
+    snapshot_archives.sort_unstable();
 
-        let snapshot_root_paths = SnapshotRootPaths {
-            full_snapshot_root_file_path: temp_dir.path().join("data-file"),
-            incremental_snapshot_root_file_path: None,
-        };
+    for (archive, size) in snapshot_archives {
+        if total_bytes <= maximum_snapshots_total_bytes {
+            break;
+        }
+        trace!(
+            "Removing snapshot archive to stay under {} bytes: {}",
+            maximum_snapshots_total_bytes,
+            archive.path.display()
+        );
+        match fs::remove_file(&archive.path) {
+            Ok(()) => total_bytes -= size,
+            Err(err) => info!("Failed to remove {}: {}", archive.path.display(), err),
+        }
+    }
 
-        let actual_data = deserialize_snapshot_data_files_capped(
-            &snapshot_root_paths,
-            expected_consumed_size,
-            |stream| {
-                Ok(deserialize_from::<_, u32>(
-                    &mut stream.full_snapshot_stream,
-                )?)
-            },
-        )
-        .unwrap();
-        assert_eq!(actual_data, expected_data);
+    if total_bytes > maximum_snapshots_total_bytes {
+        warn!(
+            "Snapshot archives take up {} bytes, more than the maximum of {} bytes, but the \
+             highest full snapshot archive is always kept: {:?}",
+            total_bytes,
+            maximum_snapshots_total_bytes,
+            highest_full_snapshot_archive
+                .as_ref()
+                .map(|archive| archive.path()),
+        );
     }
+}
 
-    #[test]
-    fn test_deserialize_snapshot_data_file_over_limit() {
-        let expected_data = 2323_u32;
-        let expected_consumed_size = size_of::<u32>() as u64;
+/// The bytes on disk taken up by snapshots, by category, see `snapshot_disk_usage()`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DiskUsageReport {
+    pub full_snapshot_archives_bytes: u64,
+    pub incremental_snapshot_archives_bytes: u64,
+    pub bank_snapshots_bytes: u64,
+}
 
-        let temp_dir = tempfile::TempDir::new().unwrap();
-        serialize_snapshot_data_file_capped(
-            &temp_dir.path().join("data-file"),
-            expected_consumed_size,
-            |stream| {
-                serialize_into(stream, &expected_data)?;
-                Ok(())
+impl DiskUsageReport {
+    #[must_use]
+    pub fn total_bytes(&self) -> u64 {
+        self.full_snapshot_archives_bytes
+            + self.incremental_snapshot_archives_bytes
+            + self.bank_snapshots_bytes
+    }
+}
+
+/// Report how many bytes the snapshot archives and bank snapshots take up on disk
+///
+/// Only file sizes are read, not file contents.  Files in the archives directories whose names do
+/// not parse as snapshot archives are not counted, so the directories may be shared.  For bank
+/// snapshots, every file in each bank snapshot's directory is counted.
+pub fn snapshot_disk_usage(
+    full_snapshot_archives_dir: impl AsRef<Path>,
+    incremental_snapshot_archives_dir: impl AsRef<Path>,
+    bank_snapshots_dir: impl AsRef<Path>,
+) -> DiskUsageReport {
+    let archive_size = |path: &Path| fs::metadata(path).map_or(0, |metadata| metadata.len());
+    let bank_snapshot_slots: BTreeSet<_> = get_bank_snapshots(&bank_snapshots_dir)
+        .into_iter()
+        .map(|bank_snapshot| bank_snapshot.slot)
+        .collect();
+    DiskUsageReport {
+        full_snapshot_archives_bytes: get_full_snapshot_archives(full_snapshot_archives_dir)
+            .iter()
+            .map(|archive| archive_size(archive.path()))
+            .sum(),
+        incremental_snapshot_archives_bytes: get_incremental_snapshot_archives(
+            incremental_snapshot_archives_dir,
+        )
+        .iter()
+        .map(|archive| archive_size(archive.path()))
+        .sum(),
+        bank_snapshots_bytes: bank_snapshot_slots
+            .into_iter()
+            .map(|slot| dir_size(&get_bank_snapshots_dir(&bank_snapshots_dir, slot)))
+            .sum(),
+    }
+}
+
+/// The total size of the files in a directory, and in all its subdirectories
+fn dir_size(dir: &Path) -> u64 {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            info!("Unable to read directory {}: {}", dir.display(), err);
+            return 0;
+        }
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => dir_size(&entry.path()),
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+fn unpack_snapshot_local(
+    shared_buffer: SharedBuffer,
+    ledger_dir: &Path,
+    account_paths: &[PathBuf],
+    parallel_divisions: usize,
+) -> Result<UnpackedAppendVecMap> {
+    assert!(parallel_divisions > 0);
+
+    // allocate all readers before any readers start reading
+    let readers = (0..parallel_divisions)
+        .into_iter()
+        .map(|_| SharedBufferReader::new(&shared_buffer))
+        .collect::<Vec<_>>();
+
+    // create 'parallel_divisions' # of parallel workers, each responsible for 1/parallel_divisions of all the files to extract.
+    let all_unpacked_append_vec_map = readers
+        .into_par_iter()
+        .enumerate()
+        .map(|(index, reader)| {
+            let parallel_selector = Some(ParallelSelector {
+                index,
+                divisions: parallel_divisions,
+            });
+            let mut archive = Archive::new(reader);
+            unpack_snapshot(&mut archive, ledger_dir, account_paths, parallel_selector)
+        })
+        .collect::<Vec<_>>();
+
+    let mut unpacked_append_vec_map = UnpackedAppendVecMap::new();
+    for h in all_unpacked_append_vec_map {
+        unpacked_append_vec_map.extend(h?);
+    }
+
+    Ok(unpacked_append_vec_map)
+}
+
+/// Check that `zstd_dictionary` is the one that the `ArchiveFormat::TarZstdWithDict` archive at
+/// `path` is compressed with; other archive formats need no dictionary
+fn check_zstd_dictionary(
+    archive_format: ArchiveFormat,
+    zstd_dictionary: Option<&[u8]>,
+    path: &Path,
+) -> Result<()> {
+    if let ArchiveFormat::TarZstdWithDict(expected) = archive_format {
+        let zstd_dictionary = zstd_dictionary
+            .ok_or_else(|| SnapshotError::MissingZstdDictionary(path.to_path_buf()))?;
+        let actual = zstd_dictionary_id(zstd_dictionary);
+        if actual != expected {
+            return Err(SnapshotError::MismatchedZstdDictionary {
+                path: path.to_path_buf(),
+                expected,
+                actual,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn untar_snapshot_create_shared_buffer(
+    snapshot_tar: &Path,
+    archive_format: ArchiveFormat,
+    extraction_buffer_bytes: Option<usize>,
+    zstd_dictionary: Option<&[u8]>,
+    bytes_read: Option<Arc<AtomicU64>>,
+) -> Result<SharedBuffer> {
+    fn new_shared_buffer<T: 'static + Read + Send>(
+        reader: T,
+        extraction_buffer_bytes: Option<usize>,
+    ) -> SharedBuffer {
+        match extraction_buffer_bytes {
+            Some(total_buffer_budget) => SharedBuffer::new_with_budget(total_buffer_budget, reader),
+            None => SharedBuffer::new(reader),
+        }
+    }
+
+    let bytes_read = bytes_read.unwrap_or_default();
+    let open_file = || ProgressReader {
+        reader: File::open(snapshot_tar).unwrap(),
+        bytes_read: bytes_read.clone(),
+    };
+    let shared_buffer = match archive_format {
+        ArchiveFormat::TarBzip2 => new_shared_buffer(
+            BzDecoder::new(BufReader::new(open_file())),
+            extraction_buffer_bytes,
+        ),
+        ArchiveFormat::TarGzip => new_shared_buffer(
+            GzDecoder::new(BufReader::new(open_file())),
+            extraction_buffer_bytes,
+        ),
+        ArchiveFormat::TarZstd => new_shared_buffer(
+            zstd::stream::read::Decoder::new(BufReader::new(open_file())).unwrap(),
+            extraction_buffer_bytes,
+        ),
+        ArchiveFormat::TarZstdWithDict(_) => {
+            check_zstd_dictionary(archive_format, zstd_dictionary, snapshot_tar)?;
+            new_shared_buffer(
+                zstd::stream::read::Decoder::with_dictionary(
+                    BufReader::new(open_file()),
+                    zstd_dictionary.unwrap(), // checked above
+                )?,
+                extraction_buffer_bytes,
+            )
+        }
+        ArchiveFormat::TarLz4 => new_shared_buffer(
+            lz4::Decoder::new(BufReader::new(open_file())).unwrap(),
+            extraction_buffer_bytes,
+        ),
+        ArchiveFormat::Tar => {
+            new_shared_buffer(BufReader::new(open_file()), extraction_buffer_bytes)
+        }
+    };
+    Ok(shared_buffer)
+}
+
+fn untar_snapshot_in<P: AsRef<Path>>(
+    snapshot_tar: P,
+    unpack_dir: &Path,
+    account_paths: &[PathBuf],
+    archive_format: ArchiveFormat,
+    parallel_divisions: usize,
+) -> Result<UnpackedAppendVecMap> {
+    let shared_buffer = untar_snapshot_create_shared_buffer(
+        snapshot_tar.as_ref(),
+        archive_format,
+        None,
+        None,
+        None,
+    )?;
+    unpack_snapshot_local(shared_buffer, unpack_dir, account_paths, parallel_divisions)
+}
+
+fn verify_unpacked_snapshots_dir_and_version(
+    unpacked_snapshots_dir_and_version: &UnpackedSnapshotsDirAndVersion,
+) -> Result<(SnapshotVersion, BankSnapshotInfo)> {
+    info!(
+        "snapshot version: {}",
+        &unpacked_snapshots_dir_and_version.snapshot_version
+    );
+
+    let snapshot_version = unpacked_snapshots_dir_and_version.snapshot_version;
+    let mut bank_snapshots =
+        get_bank_snapshots_post(&unpacked_snapshots_dir_and_version.unpacked_snapshots_dir);
+    if bank_snapshots.len() > 1 {
+        return Err(get_io_error("invalid snapshot format"));
+    }
+    let root_paths = bank_snapshots
+        .pop()
+        .ok_or_else(|| get_io_error("No snapshots found in snapshots directory"))?;
+    Ok((snapshot_version, root_paths))
+}
+
+fn bank_fields_from_snapshots(
+    full_snapshot_unpacked_snapshots_dir_and_version: &UnpackedSnapshotsDirAndVersion,
+    incremental_snapshot_unpacked_snapshots_dir_and_version: Option<
+        &UnpackedSnapshotsDirAndVersion,
+    >,
+) -> Result<BankFieldsToDeserialize> {
+    let (full_snapshot_version, full_snapshot_root_paths) =
+        verify_unpacked_snapshots_dir_and_version(
+            full_snapshot_unpacked_snapshots_dir_and_version,
+        )?;
+    let (incremental_snapshot_version, incremental_snapshot_root_paths) =
+        if let Some(snapshot_unpacked_snapshots_dir_and_version) =
+            incremental_snapshot_unpacked_snapshots_dir_and_version
+        {
+            let (snapshot_version, bank_snapshot_info) = verify_unpacked_snapshots_dir_and_version(
+                snapshot_unpacked_snapshots_dir_and_version,
+            )?;
+            (Some(snapshot_version), Some(bank_snapshot_info))
+        } else {
+            (None, None)
+        };
+    info!(
+        "Loading bank from full snapshot {} and incremental snapshot {:?}",
+        full_snapshot_root_paths.snapshot_path.display(),
+        incremental_snapshot_root_paths
+            .as_ref()
+            .map(|paths| paths.snapshot_path.display()),
+    );
+
+    let snapshot_root_paths = SnapshotRootPaths {
+        full_snapshot_root_file_path: full_snapshot_root_paths.snapshot_path,
+        incremental_snapshot_root_file_path: incremental_snapshot_root_paths
+            .map(|root_paths| root_paths.snapshot_path),
+    };
+
+    deserialize_snapshot_data_files(&snapshot_root_paths, |snapshot_streams| {
+        Ok(
+            match incremental_snapshot_version.unwrap_or(full_snapshot_version) {
+                SnapshotVersion::V1_2_0 => fields_from_streams(SerdeStyle::Newer, snapshot_streams)
+                    .map(|(bank_fields, _accountsdb_fields)| bank_fields),
+            }?,
+        )
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn rebuild_bank_from_snapshots(
+    full_snapshot_unpacked_snapshots_dir_and_version: &UnpackedSnapshotsDirAndVersion,
+    incremental_snapshot_unpacked_snapshots_dir_and_version: Option<
+        &UnpackedSnapshotsDirAndVersion,
+    >,
+    account_paths: &[PathBuf],
+    storage_and_next_append_vec_id: StorageAndNextAppendVecId,
+    genesis_config: &GenesisConfig,
+    runtime_config: &RuntimeConfig,
+    debug_keys: Option<Arc<HashSet<Pubkey>>>,
+    additional_builtins: Option<&Builtins>,
+    account_secondary_indexes: AccountSecondaryIndexes,
+    accounts_db_caching_enabled: bool,
+    limit_load_slot_count_from_snapshot: Option<usize>,
+    shrink_ratio: AccountShrinkThreshold,
+    verify_index: bool,
+    accounts_db_config: Option<AccountsDbConfig>,
+    accounts_update_notifier: Option<AccountsUpdateNotifier>,
+    exit: &Arc<AtomicBool>,
+) -> Result<Bank> {
+    let (full_snapshot_version, full_snapshot_root_paths) =
+        verify_unpacked_snapshots_dir_and_version(
+            full_snapshot_unpacked_snapshots_dir_and_version,
+        )?;
+    let (incremental_snapshot_version, incremental_snapshot_root_paths) =
+        if let Some(snapshot_unpacked_snapshots_dir_and_version) =
+            incremental_snapshot_unpacked_snapshots_dir_and_version
+        {
+            let (snapshot_version, bank_snapshot_info) = verify_unpacked_snapshots_dir_and_version(
+                snapshot_unpacked_snapshots_dir_and_version,
+            )?;
+            (Some(snapshot_version), Some(bank_snapshot_info))
+        } else {
+            (None, None)
+        };
+    info!(
+        "Loading bank from full snapshot {} and incremental snapshot {:?}",
+        full_snapshot_root_paths.snapshot_path.display(),
+        incremental_snapshot_root_paths
+            .as_ref()
+            .map(|paths| paths.snapshot_path.display()),
+    );
+
+    let snapshot_root_paths = SnapshotRootPaths {
+        full_snapshot_root_file_path: full_snapshot_root_paths.snapshot_path,
+        incremental_snapshot_root_file_path: incremental_snapshot_root_paths
+            .map(|root_paths| root_paths.snapshot_path),
+    };
+
+    let bank = deserialize_snapshot_data_files(&snapshot_root_paths, |snapshot_streams| {
+        Ok(
+            match incremental_snapshot_version.unwrap_or(full_snapshot_version) {
+                SnapshotVersion::V1_2_0 => bank_from_streams(
+                    SerdeStyle::Newer,
+                    snapshot_streams,
+                    account_paths,
+                    storage_and_next_append_vec_id,
+                    genesis_config,
+                    runtime_config,
+                    debug_keys,
+                    additional_builtins,
+                    account_secondary_indexes,
+                    accounts_db_caching_enabled,
+                    limit_load_slot_count_from_snapshot,
+                    shrink_ratio,
+                    verify_index,
+                    accounts_db_config,
+                    accounts_update_notifier,
+                    exit,
+                ),
+            }?,
+        )
+    })?;
+
+    // The status cache is rebuilt from the latest snapshot.  So, if there's an incremental
+    // snapshot, use that.  Otherwise use the full snapshot.
+    let status_cache_path = incremental_snapshot_unpacked_snapshots_dir_and_version
+        .map_or_else(
+            || {
+                full_snapshot_unpacked_snapshots_dir_and_version
+                    .unpacked_snapshots_dir
+                    .as_path()
+            },
+            |unpacked_snapshots_dir_and_version| {
+                unpacked_snapshots_dir_and_version
+                    .unpacked_snapshots_dir
+                    .as_path()
             },
         )
+        .join(SNAPSHOT_STATUS_CACHE_FILENAME);
+    let slot_deltas = deserialize_snapshot_data_file(&status_cache_path, |stream| {
+        info!(
+            "Rebuilding status cache from {}",
+            status_cache_path.display()
+        );
+        let slot_deltas: Vec<BankSlotDelta> = bincode::options()
+            .with_limit(MAX_SNAPSHOT_DATA_FILE_SIZE)
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .deserialize_from(stream)?;
+        Ok(slot_deltas)
+    })?;
+
+    verify_slot_deltas(slot_deltas.as_slice(), &bank)?;
+
+    bank.status_cache.write().unwrap().append(&slot_deltas);
+
+    bank.prepare_rewrites_for_hash();
+
+    info!("Loaded bank for slot: {}", bank.slot());
+    Ok(bank)
+}
+
+/// Verify that the snapshot's slot deltas are not corrupt/invalid
+fn verify_slot_deltas(
+    slot_deltas: &[BankSlotDelta],
+    bank: &Bank,
+) -> std::result::Result<(), VerifySlotDeltasError> {
+    let info = verify_slot_deltas_structural(slot_deltas, bank.slot())?;
+    verify_slot_deltas_with_history(&info.slots, &bank.get_slot_history(), bank.slot())
+}
+
+/// Verify that the snapshot's slot deltas are not corrupt/invalid
+/// These checks are simple/structural
+fn verify_slot_deltas_structural(
+    slot_deltas: &[BankSlotDelta],
+    bank_slot: Slot,
+) -> std::result::Result<VerifySlotDeltasStructuralInfo, VerifySlotDeltasError> {
+    // there should not be more entries than that status cache's max
+    let num_entries = slot_deltas.len();
+    if num_entries > status_cache::MAX_CACHE_ENTRIES {
+        return Err(VerifySlotDeltasError::TooManyEntries(
+            num_entries,
+            status_cache::MAX_CACHE_ENTRIES,
+        ));
+    }
+
+    let mut slots_seen_so_far = HashSet::new();
+    for &(slot, is_root, ..) in slot_deltas {
+        // all entries should be roots
+        if !is_root {
+            return Err(VerifySlotDeltasError::SlotIsNotRoot(slot));
+        }
+
+        // all entries should be for slots less than or equal to the bank's slot
+        if slot > bank_slot {
+            return Err(VerifySlotDeltasError::SlotGreaterThanMaxRoot(
+                slot, bank_slot,
+            ));
+        }
+
+        // there should only be one entry per slot
+        let is_duplicate = !slots_seen_so_far.insert(slot);
+        if is_duplicate {
+            return Err(VerifySlotDeltasError::SlotHasMultipleEntries(slot));
+        }
+    }
+
+    // detect serious logic error for future careless changes. :)
+    assert_eq!(slots_seen_so_far.len(), slot_deltas.len());
+
+    Ok(VerifySlotDeltasStructuralInfo {
+        slots: slots_seen_so_far,
+    })
+}
+
+/// Computed information from `verify_slot_deltas_structural()`, that may be reused/useful later.
+#[derive(Debug, PartialEq, Eq)]
+struct VerifySlotDeltasStructuralInfo {
+    /// All the slots in the slot deltas
+    slots: HashSet<Slot>,
+}
+
+/// Verify that the snapshot's slot deltas are not corrupt/invalid
+/// These checks use the slot history for verification
+fn verify_slot_deltas_with_history(
+    slots_from_slot_deltas: &HashSet<Slot>,
+    slot_history: &SlotHistory,
+    bank_slot: Slot,
+) -> std::result::Result<(), VerifySlotDeltasError> {
+    // ensure the slot history is valid (as much as possible), since we're using it to verify the
+    // slot deltas
+    if slot_history.newest() != bank_slot {
+        return Err(VerifySlotDeltasError::BadSlotHistory);
+    }
+
+    // all slots in the slot deltas should be in the bank's slot history
+    let slot_missing_from_history = slots_from_slot_deltas
+        .iter()
+        .find(|slot| slot_history.check(**slot) != Check::Found);
+    if let Some(slot) = slot_missing_from_history {
+        return Err(VerifySlotDeltasError::SlotNotFoundInHistory(*slot));
+    }
+
+    // all slots in the history should be in the slot deltas (up to MAX_CACHE_ENTRIES)
+    // this ensures nothing was removed from the status cache
+    //
+    // go through the slot history and make sure there's an entry for each slot
+    // note: it's important to go highest-to-lowest since the status cache removes
+    // older entries first
+    // note: we already checked above that `bank_slot == slot_history.newest()`
+    let slot_missing_from_deltas = (slot_history.oldest()..=slot_history.newest())
+        .rev()
+        .filter(|slot| slot_history.check(*slot) == Check::Found)
+        .take(status_cache::MAX_CACHE_ENTRIES)
+        .find(|slot| !slots_from_slot_deltas.contains(slot));
+    if let Some(slot) = slot_missing_from_deltas {
+        return Err(VerifySlotDeltasError::SlotNotFoundInDeltas(slot));
+    }
+
+    Ok(())
+}
+
+pub(crate) fn get_snapshot_file_name(slot: Slot) -> String {
+    slot.to_string()
+}
+
+pub(crate) fn get_bank_snapshots_dir<P: AsRef<Path>>(path: P, slot: Slot) -> PathBuf {
+    path.as_ref().join(slot.to_string())
+}
+
+fn get_io_error(error: &str) -> SnapshotError {
+    warn!("Snapshot Error: {:?}", error);
+    SnapshotError::Io(IoError::new(ErrorKind::Other, error))
+}
+
+#[derive(Debug, Copy, Clone)]
+/// allow tests to specify what happened to the serialized format
+pub enum VerifyBank {
+    /// the bank's serialized format is expected to be identical to what we are comparing against
+    Deterministic,
+    /// the serialized bank was 'reserialized' into a non-deterministic format at the specified slot
+    /// so, deserialize both files and compare deserialized results
+    NonDeterministic(Slot),
+}
+
+pub fn verify_snapshot_archive<P, Q, R>(
+    snapshot_archive: P,
+    snapshots_to_verify: Q,
+    storages_to_verify: R,
+    archive_format: ArchiveFormat,
+    verify_bank: VerifyBank,
+) where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    R: AsRef<Path>,
+{
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let unpack_dir = temp_dir.path();
+    untar_snapshot_in(
+        snapshot_archive,
+        unpack_dir,
+        &[unpack_dir.to_path_buf()],
+        archive_format,
+        1,
+    )
+    .unwrap();
+
+    // Check snapshots are the same
+    let unpacked_snapshots = unpack_dir.join("snapshots");
+    if let VerifyBank::NonDeterministic(slot) = verify_bank {
+        // file contents may be different, but deserialized structs should be equal
+        let slot = slot.to_string();
+        let p1 = snapshots_to_verify.as_ref().join(&slot).join(&slot);
+        let p2 = unpacked_snapshots.join(&slot).join(&slot);
+
+        assert!(crate::serde_snapshot::compare_two_serialized_banks(&p1, &p2).unwrap());
+        std::fs::remove_file(p1).unwrap();
+        std::fs::remove_file(p2).unwrap();
+    }
+
+    assert!(!dir_diff::is_different(&snapshots_to_verify, unpacked_snapshots).unwrap());
+
+    // Check the account entries are the same
+    let unpacked_accounts = unpack_dir.join("accounts");
+    assert!(!dir_diff::is_different(&storages_to_verify, unpacked_accounts).unwrap());
+}
+
+/// Remove outdated bank snapshots
+pub fn purge_old_bank_snapshots(bank_snapshots_dir: impl AsRef<Path>) {
+    let do_purge = |mut bank_snapshots: Vec<BankSnapshotInfo>| {
+        bank_snapshots.sort_unstable();
+        bank_snapshots
+            .into_iter()
+            .rev()
+            .skip(MAX_BANK_SNAPSHOTS_TO_RETAIN)
+            .for_each(|bank_snapshot| {
+                let r = remove_bank_snapshot(bank_snapshot.slot, &bank_snapshots_dir);
+                if r.is_err() {
+                    warn!(
+                        "Couldn't remove bank snapshot at: {}",
+                        bank_snapshot.snapshot_path.display()
+                    );
+                }
+            })
+    };
+
+    do_purge(get_bank_snapshots_pre(&bank_snapshots_dir));
+    do_purge(get_bank_snapshots_post(&bank_snapshots_dir));
+}
+
+/// Gather the necessary elements for a snapshot of the given `root_bank`.
+///
+/// **DEVELOPER NOTE** Any error that is returned from this function may bring down the node!  This
+/// function is called from AccountsBackgroundService to handle snapshot requests.  Since taking a
+/// snapshot is not permitted to fail, any errors returned here will trigger the node to shutdown.
+/// So, be careful whenever adding new code that may return errors.
+#[allow(clippy::too_many_arguments)]
+pub fn snapshot_bank(
+    root_bank: &Bank,
+    status_cache_slot_deltas: Vec<BankSlotDelta>,
+    accounts_package_sender: &Sender<AccountsPackage>,
+    bank_snapshots_dir: impl AsRef<Path>,
+    full_snapshot_archives_dir: impl AsRef<Path>,
+    incremental_snapshot_archives_dir: impl AsRef<Path>,
+    snapshot_version: SnapshotVersion,
+    archive_format: ArchiveFormat,
+    hash_for_testing: Option<Hash>,
+    accounts_package_type: AccountsPackageType,
+) -> Result<()> {
+    let snapshot_storages = get_snapshot_storages(root_bank);
+
+    let mut add_snapshot_time = Measure::start("add-snapshot-ms");
+    let bank_snapshot_info = add_bank_snapshot(
+        &bank_snapshots_dir,
+        root_bank,
+        &snapshot_storages,
+        snapshot_version,
+    )?;
+    add_snapshot_time.stop();
+    inc_new_counter_info!("add-snapshot-ms", add_snapshot_time.as_ms() as usize);
+
+    let accounts_package = AccountsPackage::new(
+        accounts_package_type,
+        root_bank,
+        &bank_snapshot_info,
+        bank_snapshots_dir,
+        status_cache_slot_deltas,
+        full_snapshot_archives_dir,
+        incremental_snapshot_archives_dir,
+        snapshot_storages,
+        archive_format,
+        snapshot_version,
+        hash_for_testing,
+    )
+    .expect("failed to hard link bank snapshot into a tmpdir");
+
+    // Submit the accounts package
+    accounts_package_sender
+        .send(accounts_package)
+        .expect("send accounts package");
+
+    Ok(())
+}
+
+/// Get the snapshot storages for this bank
+fn get_snapshot_storages(bank: &Bank) -> SnapshotStorages {
+    let mut measure_snapshot_storages = Measure::start("snapshot-storages");
+    let snapshot_storages = bank.get_snapshot_storages(None);
+    measure_snapshot_storages.stop();
+    let snapshot_storages_count = snapshot_storages.iter().map(Vec::len).sum::<usize>();
+    datapoint_info!(
+        "get_snapshot_storages",
+        ("snapshot-storages-count", snapshot_storages_count, i64),
+        (
+            "snapshot-storages-time-ms",
+            measure_snapshot_storages.as_ms(),
+            i64
+        ),
+    );
+
+    snapshot_storages
+}
+
+/// Convenience function to create a full snapshot archive out of any Bank, regardless of state.
+/// The Bank will be frozen during the process.
+/// This is only called from ledger-tool or tests. Warping is a special case as well.
+///
+/// If `progress` is set, it is called with the progress of creating the archive, see
+/// `archive_snapshot_package()`.
+///
+/// Requires:
+///     - `bank` is complete
+#[allow(clippy::too_many_arguments)]
+pub fn bank_to_full_snapshot_archive(
+    bank_snapshots_dir: impl AsRef<Path>,
+    bank: &Bank,
+    snapshot_version: Option<SnapshotVersion>,
+    full_snapshot_archives_dir: impl AsRef<Path>,
+    incremental_snapshot_archives_dir: impl AsRef<Path>,
+    archive_format: ArchiveFormat,
+    maximum_full_snapshot_archives_to_retain: usize,
+    maximum_incremental_snapshot_archives_to_retain: usize,
+    progress: Option<ArchiveProgressCallback>,
+) -> Result<FullSnapshotArchiveInfo> {
+    let snapshot_version = snapshot_version.unwrap_or_default();
+
+    assert!(bank.is_complete());
+    bank.squash(); // Bank may not be a root
+    bank.force_flush_accounts_cache();
+    bank.clean_accounts(Some(bank.slot()));
+    bank.update_accounts_hash();
+    bank.rehash(); // Bank accounts may have been manually modified by the caller
+
+    let temp_dir = tempfile::tempdir_in(bank_snapshots_dir)?;
+    let snapshot_storages = bank.get_snapshot_storages(None);
+    let bank_snapshot_info =
+        add_bank_snapshot(&temp_dir, bank, &snapshot_storages, snapshot_version)?;
+
+    package_and_archive_full_snapshot(
+        bank,
+        &bank_snapshot_info,
+        &temp_dir,
+        full_snapshot_archives_dir,
+        incremental_snapshot_archives_dir,
+        snapshot_storages,
+        archive_format,
+        snapshot_version,
+        maximum_full_snapshot_archives_to_retain,
+        maximum_incremental_snapshot_archives_to_retain,
+        progress,
+    )
+}
+
+/// Convenience function to create an incremental snapshot archive out of any Bank, regardless of
+/// state.  The Bank will be frozen during the process.
+/// This is only called from ledger-tool or tests. Warping is a special case as well.
+///
+/// If `progress` is set, it is called with the progress of creating the archive, see
+/// `archive_snapshot_package()`.
+///
+/// Requires:
+///     - `bank` is complete
+///     - `bank`'s slot is greater than `full_snapshot_slot`
+#[allow(clippy::too_many_arguments)]
+pub fn bank_to_incremental_snapshot_archive(
+    bank_snapshots_dir: impl AsRef<Path>,
+    bank: &Bank,
+    full_snapshot_slot: Slot,
+    snapshot_version: Option<SnapshotVersion>,
+    full_snapshot_archives_dir: impl AsRef<Path>,
+    incremental_snapshot_archives_dir: impl AsRef<Path>,
+    archive_format: ArchiveFormat,
+    maximum_full_snapshot_archives_to_retain: usize,
+    maximum_incremental_snapshot_archives_to_retain: usize,
+    progress: Option<ArchiveProgressCallback>,
+) -> Result<IncrementalSnapshotArchiveInfo> {
+    let snapshot_version = snapshot_version.unwrap_or_default();
+
+    assert!(bank.is_complete());
+    assert!(bank.slot() > full_snapshot_slot);
+    bank.squash(); // Bank may not be a root
+    bank.force_flush_accounts_cache();
+    bank.clean_accounts(Some(full_snapshot_slot));
+    bank.update_accounts_hash();
+    bank.rehash(); // Bank accounts may have been manually modified by the caller
+
+    let temp_dir = tempfile::tempdir_in(bank_snapshots_dir)?;
+    let snapshot_storages = bank.get_snapshot_storages(Some(full_snapshot_slot));
+    let bank_snapshot_info =
+        add_bank_snapshot(&temp_dir, bank, &snapshot_storages, snapshot_version)?;
+
+    package_and_archive_incremental_snapshot(
+        bank,
+        full_snapshot_slot,
+        &bank_snapshot_info,
+        &temp_dir,
+        full_snapshot_archives_dir,
+        incremental_snapshot_archives_dir,
+        snapshot_storages,
+        archive_format,
+        snapshot_version,
+        maximum_full_snapshot_archives_to_retain,
+        maximum_incremental_snapshot_archives_to_retain,
+        progress,
+    )
+}
+
+/// Helper function to hold shared code to package, process, and archive full snapshots
+#[allow(clippy::too_many_arguments)]
+pub fn package_and_archive_full_snapshot(
+    bank: &Bank,
+    bank_snapshot_info: &BankSnapshotInfo,
+    bank_snapshots_dir: impl AsRef<Path>,
+    full_snapshot_archives_dir: impl AsRef<Path>,
+    incremental_snapshot_archives_dir: impl AsRef<Path>,
+    snapshot_storages: SnapshotStorages,
+    archive_format: ArchiveFormat,
+    snapshot_version: SnapshotVersion,
+    maximum_full_snapshot_archives_to_retain: usize,
+    maximum_incremental_snapshot_archives_to_retain: usize,
+    progress: Option<ArchiveProgressCallback>,
+) -> Result<FullSnapshotArchiveInfo> {
+    let slot_deltas = bank.status_cache.read().unwrap().root_slot_deltas();
+    let accounts_package = AccountsPackage::new(
+        AccountsPackageType::Snapshot(SnapshotType::FullSnapshot),
+        bank,
+        bank_snapshot_info,
+        bank_snapshots_dir,
+        slot_deltas,
+        &full_snapshot_archives_dir,
+        &incremental_snapshot_archives_dir,
+        snapshot_storages,
+        archive_format,
+        snapshot_version,
+        None,
+    )?;
+
+    crate::serde_snapshot::reserialize_bank_with_new_accounts_hash(
+        accounts_package.snapshot_links.path(),
+        accounts_package.slot,
+        &bank.get_accounts_hash(),
+        None,
+        None, // todo: this needs to be passed through
+    );
+
+    let snapshot_package = SnapshotPackage::new(accounts_package, bank.get_accounts_hash());
+    archive_snapshot_package(
+        &snapshot_package,
+        full_snapshot_archives_dir,
+        incremental_snapshot_archives_dir,
+        maximum_full_snapshot_archives_to_retain,
+        maximum_incremental_snapshot_archives_to_retain,
+        None,
+        None,
+        progress,
+    )?;
+    bank.rc
+        .accounts
+        .accounts_db
+        .set_last_full_snapshot_accounts_hash(bank.slot(), bank.get_accounts_hash());
+
+    Ok(FullSnapshotArchiveInfo::new(
+        snapshot_package.snapshot_archive_info,
+    ))
+}
+
+/// Helper function to hold shared code to package, process, and archive incremental snapshots
+#[allow(clippy::too_many_arguments)]
+pub fn package_and_archive_incremental_snapshot(
+    bank: &Bank,
+    incremental_snapshot_base_slot: Slot,
+    bank_snapshot_info: &BankSnapshotInfo,
+    bank_snapshots_dir: impl AsRef<Path>,
+    full_snapshot_archives_dir: impl AsRef<Path>,
+    incremental_snapshot_archives_dir: impl AsRef<Path>,
+    snapshot_storages: SnapshotStorages,
+    archive_format: ArchiveFormat,
+    snapshot_version: SnapshotVersion,
+    maximum_full_snapshot_archives_to_retain: usize,
+    maximum_incremental_snapshot_archives_to_retain: usize,
+    progress: Option<ArchiveProgressCallback>,
+) -> Result<IncrementalSnapshotArchiveInfo> {
+    let slot_deltas = bank.status_cache.read().unwrap().root_slot_deltas();
+    let accounts_package = AccountsPackage::new(
+        AccountsPackageType::Snapshot(SnapshotType::IncrementalSnapshot(
+            incremental_snapshot_base_slot,
+        )),
+        bank,
+        bank_snapshot_info,
+        bank_snapshots_dir,
+        slot_deltas,
+        &full_snapshot_archives_dir,
+        &incremental_snapshot_archives_dir,
+        snapshot_storages,
+        archive_format,
+        snapshot_version,
+        None,
+    )?;
+
+    crate::serde_snapshot::reserialize_bank_with_new_accounts_hash(
+        accounts_package.snapshot_links.path(),
+        accounts_package.slot,
+        &bank.get_accounts_hash(),
+        None,
+        None, // todo: this needs to be passed through
+    );
+
+    let snapshot_package = SnapshotPackage::new(accounts_package, bank.get_accounts_hash());
+    archive_snapshot_package(
+        &snapshot_package,
+        full_snapshot_archives_dir,
+        incremental_snapshot_archives_dir,
+        maximum_full_snapshot_archives_to_retain,
+        maximum_incremental_snapshot_archives_to_retain,
+        None,
+        None,
+        progress,
+    )?;
+
+    Ok(IncrementalSnapshotArchiveInfo::new(
+        incremental_snapshot_base_slot,
+        snapshot_package.snapshot_archive_info,
+    ))
+}
+
+pub fn should_take_full_snapshot(
+    block_height: Slot,
+    full_snapshot_archive_interval_slots: Slot,
+) -> bool {
+    block_height % full_snapshot_archive_interval_slots == 0
+}
+
+pub fn should_take_incremental_snapshot(
+    block_height: Slot,
+    incremental_snapshot_archive_interval_slots: Slot,
+    last_full_snapshot_slot: Option<Slot>,
+) -> bool {
+    block_height % incremental_snapshot_archive_interval_slots == 0
+        && last_full_snapshot_slot.is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            accounts_db::ACCOUNTS_DB_CONFIG_FOR_TESTING,
+            accounts_hash::AccountsHash,
+            ancestors::Ancestors,
+            epoch_accounts_hash::{self, EpochAccountsHash},
+            status_cache::Status,
+        },
+        assert_matches::assert_matches,
+        bincode::{deserialize_from, serialize_into},
+        solana_sdk::{
+            account::{AccountSharedData, ReadableAccount},
+            epoch_schedule::EpochSchedule,
+            genesis_config::create_genesis_config,
+            native_token::sol_to_lamports,
+            signature::{Keypair, Signer},
+            slot_history::SlotHistory,
+            system_program, system_transaction,
+            transaction::SanitizedTransaction,
+        },
+        std::{convert::TryFrom, mem::size_of, sync::Mutex},
+        tempfile::NamedTempFile,
+    };
+
+    #[test]
+    fn test_serialize_snapshot_data_file_under_limit() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let expected_consumed_size = size_of::<u32>() as u64;
+        let consumed_size = serialize_snapshot_data_file_capped(
+            &temp_dir.path().join("data-file"),
+            expected_consumed_size,
+            |stream| {
+                serialize_into(stream, &2323_u32)?;
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(consumed_size, expected_consumed_size);
+    }
+
+    #[test]
+    fn test_serialize_snapshot_data_file_over_limit() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let expected_consumed_size = size_of::<u32>() as u64;
+        let result = serialize_snapshot_data_file_capped(
+            &temp_dir.path().join("data-file"),
+            expected_consumed_size - 1,
+            |stream| {
+                serialize_into(stream, &2323_u32)?;
+                Ok(())
+            },
+        );
+        assert_matches!(result, Err(SnapshotError::Io(ref message)) if message.to_string().starts_with("too large snapshot data file to serialize"));
+    }
+
+    #[test]
+    fn test_deserialize_snapshot_data_file_under_limit() {
+        let expected_data = 2323_u32;
+        let expected_consumed_size = size_of::<u32>() as u64;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        serialize_snapshot_data_file_capped(
+            &temp_dir.path().join("data-file"),
+            expected_consumed_size,
+            |stream| {
+                serialize_into(stream, &expected_data)?;
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        let snapshot_root_paths = SnapshotRootPaths {
+            full_snapshot_root_file_path: temp_dir.path().join("data-file"),
+            incremental_snapshot_root_file_path: None,
+        };
+
+        let actual_data = deserialize_snapshot_data_files_capped(
+            &snapshot_root_paths,
+            expected_consumed_size,
+            |stream| {
+                Ok(deserialize_from::<_, u32>(
+                    &mut stream.full_snapshot_stream,
+                )?)
+            },
+        )
+        .unwrap();
+        assert_eq!(actual_data, expected_data);
+    }
+
+    #[test]
+    fn test_deserialize_snapshot_data_file_over_limit() {
+        let expected_data = 2323_u32;
+        let expected_consumed_size = size_of::<u32>() as u64;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        serialize_snapshot_data_file_capped(
+            &temp_dir.path().join("data-file"),
+            expected_consumed_size,
+            |stream| {
+                serialize_into(stream, &expected_data)?;
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        let snapshot_root_paths = SnapshotRootPaths {
+            full_snapshot_root_file_path: temp_dir.path().join("data-file"),
+            incremental_snapshot_root_file_path: None,
+        };
+
+        let result = deserialize_snapshot_data_files_capped(
+            &snapshot_root_paths,
+            expected_consumed_size - 1,
+            |stream| {
+                Ok(deserialize_from::<_, u32>(
+                    &mut stream.full_snapshot_stream,
+                )?)
+            },
+        );
+        assert_matches!(result, Err(SnapshotError::Io(ref message)) if message.to_string().starts_with("too large snapshot data file to deserialize"));
+    }
+
+    #[test]
+    fn test_deserialize_snapshot_data_file_extra_data() {
+        let expected_data = 2323_u32;
+        let expected_consumed_size = size_of::<u32>() as u64;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        serialize_snapshot_data_file_capped(
+            &temp_dir.path().join("data-file"),
+            expected_consumed_size * 2,
+            |stream| {
+                serialize_into(stream.by_ref(), &expected_data)?;
+                serialize_into(stream.by_ref(), &expected_data)?;
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        let snapshot_root_paths = SnapshotRootPaths {
+            full_snapshot_root_file_path: temp_dir.path().join("data-file"),
+            incremental_snapshot_root_file_path: None,
+        };
+
+        let result = deserialize_snapshot_data_files_capped(
+            &snapshot_root_paths,
+            expected_consumed_size * 2,
+            |stream| {
+                Ok(deserialize_from::<_, u32>(
+                    &mut stream.full_snapshot_stream,
+                )?)
+            },
+        );
+        assert_matches!(result, Err(SnapshotError::Io(ref message)) if message.to_string().starts_with("invalid snapshot data file"));
+    }
+
+    #[test]
+    fn test_snapshot_version_from_file_under_limit() {
+        let file_content = SnapshotVersion::default().as_str();
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(file_content.as_bytes()).unwrap();
+        let version_from_file = snapshot_version_from_file(file.path()).unwrap();
+        assert_eq!(version_from_file, file_content);
+    }
+
+    #[test]
+    fn test_snapshot_version_from_file_over_limit() {
+        let over_limit_size = usize::try_from(MAX_SNAPSHOT_VERSION_FILE_SIZE + 1).unwrap();
+        let file_content = vec![7u8; over_limit_size];
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(&file_content).unwrap();
+        assert_matches!(
+            snapshot_version_from_file(file.path()),
+            Err(SnapshotError::Io(ref message)) if message.to_string().starts_with("snapshot version file too large")
+        );
+    }
+
+    #[test]
+    fn test_parse_full_snapshot_archive_filename() {
+        assert_eq!(
+            parse_full_snapshot_archive_filename(&format!(
+                "snapshot-42-{}.tar.bz2",
+                Hash::default()
+            ))
+            .unwrap(),
+            (42, Hash::default(), ArchiveFormat::TarBzip2)
+        );
+        assert_eq!(
+            parse_full_snapshot_archive_filename(&format!(
+                "snapshot-43-{}.tar.zst",
+                Hash::default()
+            ))
+            .unwrap(),
+            (43, Hash::default(), ArchiveFormat::TarZstd)
+        );
+        assert_eq!(
+            parse_full_snapshot_archive_filename(&format!("snapshot-44-{}.tar", Hash::default()))
+                .unwrap(),
+            (44, Hash::default(), ArchiveFormat::Tar)
+        );
+        assert_eq!(
+            parse_full_snapshot_archive_filename(&format!(
+                "snapshot-45-{}.tar.lz4",
+                Hash::default()
+            ))
+            .unwrap(),
+            (45, Hash::default(), ArchiveFormat::TarLz4)
+        );
+
+        assert!(parse_full_snapshot_archive_filename("invalid").is_err());
+        assert!(
+            parse_full_snapshot_archive_filename("snapshot-bad!slot-bad!hash.bad!ext").is_err()
+        );
+
+        assert!(
+            parse_full_snapshot_archive_filename("snapshot-12345678-bad!hash.bad!ext").is_err()
+        );
+        assert!(parse_full_snapshot_archive_filename(&format!(
+            "snapshot-12345678-{}.bad!ext",
+            Hash::new_unique()
+        ))
+        .is_err());
+        assert!(parse_full_snapshot_archive_filename("snapshot-12345678-bad!hash.tar").is_err());
+
+        assert!(parse_full_snapshot_archive_filename(&format!(
+            "snapshot-bad!slot-{}.bad!ext",
+            Hash::new_unique()
+        ))
+        .is_err());
+        assert!(parse_full_snapshot_archive_filename(&format!(
+            "snapshot-12345678-{}.bad!ext",
+            Hash::new_unique()
+        ))
+        .is_err());
+        assert!(parse_full_snapshot_archive_filename(&format!(
+            "snapshot-bad!slot-{}.tar",
+            Hash::new_unique()
+        ))
+        .is_err());
+
+        assert!(parse_full_snapshot_archive_filename("snapshot-bad!slot-bad!hash.tar").is_err());
+        assert!(parse_full_snapshot_archive_filename("snapshot-12345678-bad!hash.tar").is_err());
+        assert!(parse_full_snapshot_archive_filename(&format!(
+            "snapshot-bad!slot-{}.tar",
+            Hash::new_unique()
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_incremental_snapshot_archive_filename() {
+        solana_logger::setup();
+        assert_eq!(
+            parse_incremental_snapshot_archive_filename(&format!(
+                "incremental-snapshot-42-123-{}.tar.bz2",
+                Hash::default()
+            ))
+            .unwrap(),
+            (42, 123, Hash::default(), ArchiveFormat::TarBzip2)
+        );
+        assert_eq!(
+            parse_incremental_snapshot_archive_filename(&format!(
+                "incremental-snapshot-43-234-{}.tar.zst",
+                Hash::default()
+            ))
+            .unwrap(),
+            (43, 234, Hash::default(), ArchiveFormat::TarZstd)
+        );
+        assert_eq!(
+            parse_incremental_snapshot_archive_filename(&format!(
+                "incremental-snapshot-44-345-{}.tar",
+                Hash::default()
+            ))
+            .unwrap(),
+            (44, 345, Hash::default(), ArchiveFormat::Tar)
+        );
+        assert_eq!(
+            parse_incremental_snapshot_archive_filename(&format!(
+                "incremental-snapshot-45-456-{}.tar.lz4",
+                Hash::default()
+            ))
+            .unwrap(),
+            (45, 456, Hash::default(), ArchiveFormat::TarLz4)
+        );
+
+        assert!(parse_incremental_snapshot_archive_filename("invalid").is_err());
+        assert!(parse_incremental_snapshot_archive_filename(&format!(
+            "snapshot-42-{}.tar",
+            Hash::new_unique()
+        ))
+        .is_err());
+        assert!(parse_incremental_snapshot_archive_filename(
+            "incremental-snapshot-bad!slot-bad!slot-bad!hash.bad!ext"
+        )
+        .is_err());
+
+        assert!(parse_incremental_snapshot_archive_filename(&format!(
+            "incremental-snapshot-bad!slot-56785678-{}.tar",
+            Hash::new_unique()
+        ))
+        .is_err());
+
+        assert!(parse_incremental_snapshot_archive_filename(&format!(
+            "incremental-snapshot-12345678-bad!slot-{}.tar",
+            Hash::new_unique()
+        ))
+        .is_err());
+
+        assert!(parse_incremental_snapshot_archive_filename(
+            "incremental-snapshot-12341234-56785678-bad!HASH.tar"
+        )
+        .is_err());
+
+        assert!(parse_incremental_snapshot_archive_filename(&format!(
+            "incremental-snapshot-12341234-56785678-{}.bad!ext",
+            Hash::new_unique()
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn test_check_are_snapshots_compatible() {
+        solana_logger::setup();
+        let slot1: Slot = 1234;
+        let slot2: Slot = 5678;
+        let slot3: Slot = 999_999;
+
+        let full_snapshot_archive_info = FullSnapshotArchiveInfo::new_from_path(PathBuf::from(
+            format!("/dir/snapshot-{}-{}.tar", slot1, Hash::new_unique()),
+        ))
+        .unwrap();
+
+        assert!(check_are_snapshots_compatible(&full_snapshot_archive_info, None,).is_ok());
+
+        let incremental_snapshot_archive_info =
+            IncrementalSnapshotArchiveInfo::new_from_path(PathBuf::from(format!(
+                "/dir/incremental-snapshot-{}-{}-{}.tar",
+                slot1,
+                slot2,
+                Hash::new_unique()
+            )))
+            .unwrap();
+
+        assert!(check_are_snapshots_compatible(
+            &full_snapshot_archive_info,
+            Some(&incremental_snapshot_archive_info)
+        )
+        .is_ok());
+
+        let incremental_snapshot_archive_info =
+            IncrementalSnapshotArchiveInfo::new_from_path(PathBuf::from(format!(
+                "/dir/incremental-snapshot-{}-{}-{}.tar",
+                slot2,
+                slot3,
+                Hash::new_unique()
+            )))
+            .unwrap();
+
+        assert!(check_are_snapshots_compatible(
+            &full_snapshot_archive_info,
+            Some(&incremental_snapshot_archive_info)
+        )
+        .is_err());
+    }
+
+    /// A test heler function that creates bank snapshot files
+    fn common_create_bank_snapshot_files(
+        bank_snapshots_dir: &Path,
+        min_slot: Slot,
+        max_slot: Slot,
+    ) {
+        for slot in min_slot..max_slot {
+            let snapshot_dir = get_bank_snapshots_dir(bank_snapshots_dir, slot);
+            fs::create_dir_all(&snapshot_dir).unwrap();
+
+            let snapshot_filename = get_snapshot_file_name(slot);
+            let snapshot_path = snapshot_dir.join(snapshot_filename);
+            File::create(snapshot_path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_get_bank_snapshots() {
+        solana_logger::setup();
+        let temp_snapshots_dir = tempfile::TempDir::new().unwrap();
+        let min_slot = 10;
+        let max_slot = 20;
+        common_create_bank_snapshot_files(temp_snapshots_dir.path(), min_slot, max_slot);
+
+        let bank_snapshots = get_bank_snapshots(temp_snapshots_dir.path());
+        assert_eq!(bank_snapshots.len() as Slot, max_slot - min_slot);
+    }
+
+    #[test]
+    fn test_get_highest_bank_snapshot_post() {
+        solana_logger::setup();
+        let temp_snapshots_dir = tempfile::TempDir::new().unwrap();
+        let min_slot = 99;
+        let max_slot = 123;
+        common_create_bank_snapshot_files(temp_snapshots_dir.path(), min_slot, max_slot);
+
+        let highest_bank_snapshot = get_highest_bank_snapshot_post(temp_snapshots_dir.path());
+        assert!(highest_bank_snapshot.is_some());
+        assert_eq!(highest_bank_snapshot.unwrap().slot, max_slot - 1);
+    }
+
+    /// A test helper function that creates full and incremental snapshot archive files.  Creates
+    /// full snapshot files in the range (`min_full_snapshot_slot`, `max_full_snapshot_slot`], and
+    /// incremental snapshot files in the range (`min_incremental_snapshot_slot`,
+    /// `max_incremental_snapshot_slot`].  Additionally, "bad" files are created for both full and
+    /// incremental snapshots to ensure the tests properly filter them out.
+    fn common_create_snapshot_archive_files(
+        full_snapshot_archives_dir: &Path,
+        incremental_snapshot_archives_dir: &Path,
+        min_full_snapshot_slot: Slot,
+        max_full_snapshot_slot: Slot,
+        min_incremental_snapshot_slot: Slot,
+        max_incremental_snapshot_slot: Slot,
+    ) {
+        fs::create_dir_all(full_snapshot_archives_dir).unwrap();
+        fs::create_dir_all(incremental_snapshot_archives_dir).unwrap();
+        for full_snapshot_slot in min_full_snapshot_slot..max_full_snapshot_slot {
+            for incremental_snapshot_slot in
+                min_incremental_snapshot_slot..max_incremental_snapshot_slot
+            {
+                let snapshot_filename = format!(
+                    "incremental-snapshot-{}-{}-{}.tar",
+                    full_snapshot_slot,
+                    incremental_snapshot_slot,
+                    Hash::default()
+                );
+                let snapshot_filepath = incremental_snapshot_archives_dir.join(snapshot_filename);
+                File::create(snapshot_filepath).unwrap();
+            }
+
+            let snapshot_filename =
+                format!("snapshot-{}-{}.tar", full_snapshot_slot, Hash::default());
+            let snapshot_filepath = full_snapshot_archives_dir.join(snapshot_filename);
+            File::create(snapshot_filepath).unwrap();
+
+            // Add in an incremental snapshot with a bad filename and high slot to ensure filename are filtered and sorted correctly
+            let bad_filename = format!(
+                "incremental-snapshot-{}-{}-bad!hash.tar",
+                full_snapshot_slot,
+                max_incremental_snapshot_slot + 1,
+            );
+            let bad_filepath = incremental_snapshot_archives_dir.join(bad_filename);
+            File::create(bad_filepath).unwrap();
+        }
+
+        // Add in a snapshot with a bad filename and high slot to ensure filename are filtered and
+        // sorted correctly
+        let bad_filename = format!("snapshot-{}-bad!hash.tar", max_full_snapshot_slot + 1);
+        let bad_filepath = full_snapshot_archives_dir.join(bad_filename);
+        File::create(bad_filepath).unwrap();
+    }
+
+    #[test]
+    fn test_get_full_snapshot_archives() {
+        solana_logger::setup();
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let min_slot = 123;
+        let max_slot = 456;
+        common_create_snapshot_archive_files(
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            min_slot,
+            max_slot,
+            0,
+            0,
+        );
+
+        let snapshot_archives = get_full_snapshot_archives(full_snapshot_archives_dir);
+        assert_eq!(snapshot_archives.len() as Slot, max_slot - min_slot);
+    }
+
+    #[test]
+    fn test_epochs_with_snapshots() {
+        solana_logger::setup();
+        let genesis_config = GenesisConfig {
+            epoch_schedule: EpochSchedule::custom(100, 100, false),
+            ..GenesisConfig::default()
+        };
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        assert!(epochs_with_snapshots(&full_snapshot_archives_dir, &genesis_config).is_empty());
+
+        // slots 150 through 249 span epochs 1 and 2
+        common_create_snapshot_archive_files(
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            150,
+            250,
+            0,
+            0,
+        );
+        assert_eq!(
+            epochs_with_snapshots(&full_snapshot_archives_dir, &genesis_config),
+            BTreeSet::from([1, 2]),
+        );
+    }
+
+    #[test]
+    fn test_get_full_snapshot_archives_remote() {
+        solana_logger::setup();
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let min_slot = 123;
+        let max_slot = 456;
+        common_create_snapshot_archive_files(
+            &full_snapshot_archives_dir.path().join("remote"),
+            &incremental_snapshot_archives_dir.path().join("remote"),
+            min_slot,
+            max_slot,
+            0,
+            0,
+        );
+
+        let snapshot_archives = get_full_snapshot_archives(full_snapshot_archives_dir);
+        assert_eq!(snapshot_archives.len() as Slot, max_slot - min_slot);
+        assert!(snapshot_archives.iter().all(|info| info.is_remote()));
+    }
+
+    #[test]
+    fn test_get_incremental_snapshot_archives() {
+        solana_logger::setup();
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let min_full_snapshot_slot = 12;
+        let max_full_snapshot_slot = 23;
+        let min_incremental_snapshot_slot = 34;
+        let max_incremental_snapshot_slot = 45;
+        common_create_snapshot_archive_files(
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            min_full_snapshot_slot,
+            max_full_snapshot_slot,
+            min_incremental_snapshot_slot,
+            max_incremental_snapshot_slot,
+        );
+
+        let incremental_snapshot_archives =
+            get_incremental_snapshot_archives(incremental_snapshot_archives_dir);
+        assert_eq!(
+            incremental_snapshot_archives.len() as Slot,
+            (max_full_snapshot_slot - min_full_snapshot_slot)
+                * (max_incremental_snapshot_slot - min_incremental_snapshot_slot)
+        );
+    }
+
+    #[test]
+    fn test_iter_snapshot_archives() {
+        solana_logger::setup();
+        let snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let min_full_snapshot_slot = 12;
+        let max_full_snapshot_slot = 23;
+        let min_incremental_snapshot_slot = 34;
+        let max_incremental_snapshot_slot = 45;
+        common_create_snapshot_archive_files(
+            snapshot_archives_dir.path(),
+            &snapshot_archives_dir.path().join("remote"),
+            min_full_snapshot_slot,
+            max_full_snapshot_slot,
+            min_incremental_snapshot_slot,
+            max_incremental_snapshot_slot,
+        );
+
+        let snapshot_archives: Vec<_> = iter_snapshot_archives(&snapshot_archives_dir).collect();
+        let num_full_snapshot_archives = max_full_snapshot_slot - min_full_snapshot_slot;
+        assert_eq!(
+            snapshot_archives.len() as Slot,
+            num_full_snapshot_archives
+                + num_full_snapshot_archives
+                    * (max_incremental_snapshot_slot - min_incremental_snapshot_slot)
+        );
+        assert!(snapshot_archives
+            .windows(2)
+            .all(|window| window[0].slot <= window[1].slot));
+        assert_eq!(snapshot_archives[0].slot, min_full_snapshot_slot);
+        assert!(snapshot_archives
+            .last()
+            .unwrap()
+            .path
+            .parent()
+            .unwrap()
+            .ends_with(SNAPSHOT_ARCHIVE_DOWNLOAD_DIR));
+
+        // reading the directory in small batches yields the same archives, in the same order
+        for batch_size in [1, 7] {
+            assert_eq!(
+                iter_snapshot_archives_in_batches(&snapshot_archives_dir, batch_size)
+                    .collect::<Vec<_>>(),
+                snapshot_archives
+            );
+        }
+    }
+
+    #[test]
+    fn test_audit_archive_names() {
+        solana_logger::setup();
+        let snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let hash = Hash::new_unique();
+        let valid_names = [
+            format!("snapshot-100-{}.tar.zst", hash),
+            format!("incremental-snapshot-100-110-{}.tar.zst", hash),
+            // same slot as above, but a different base slot
+            format!("incremental-snapshot-90-110-{}.tar", hash),
+            "README".to_string(),
+        ];
+        let invalid_extension = format!("snapshot-120-{}.zip", hash);
+        let malformed = format!("incremental-snapshot-100-abc-{}.tar.zst", hash);
+        let duplicates = [
+            format!("snapshot-200-{}.tar.gz", hash),
+            format!("snapshot-200-{}.tar.zst", Hash::new_unique()),
+        ];
+        for name in valid_names
+            .iter()
+            .chain([&invalid_extension, &malformed])
+            .chain(&duplicates)
+        {
+            File::create(snapshot_archives_dir.path().join(name)).unwrap();
+        }
+        fs::create_dir(
+            snapshot_archives_dir
+                .path()
+                .join(SNAPSHOT_ARCHIVE_DOWNLOAD_DIR),
+        )
+        .unwrap();
+
+        let path = |name: &String| snapshot_archives_dir.path().join(name);
+        let mut expected_duplicate_paths: Vec<_> = duplicates.iter().map(path).collect();
+        expected_duplicate_paths.sort();
+        assert_eq!(
+            audit_archive_names(&snapshot_archives_dir),
+            vec![
+                ArchiveNameIssue::Malformed(path(&malformed)),
+                ArchiveNameIssue::InvalidExtension(path(&invalid_extension)),
+                ArchiveNameIssue::DuplicateSlot {
+                    base_slot: None,
+                    slot: 200,
+                    paths: expected_duplicate_paths,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_incremental_snapshot_archives_remote() {
+        solana_logger::setup();
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let min_full_snapshot_slot = 12;
+        let max_full_snapshot_slot = 23;
+        let min_incremental_snapshot_slot = 34;
+        let max_incremental_snapshot_slot = 45;
+        common_create_snapshot_archive_files(
+            &full_snapshot_archives_dir.path().join("remote"),
+            &incremental_snapshot_archives_dir.path().join("remote"),
+            min_full_snapshot_slot,
+            max_full_snapshot_slot,
+            min_incremental_snapshot_slot,
+            max_incremental_snapshot_slot,
+        );
+
+        let incremental_snapshot_archives =
+            get_incremental_snapshot_archives(incremental_snapshot_archives_dir);
+        assert_eq!(
+            incremental_snapshot_archives.len() as Slot,
+            (max_full_snapshot_slot - min_full_snapshot_slot)
+                * (max_incremental_snapshot_slot - min_incremental_snapshot_slot)
+        );
+        assert!(incremental_snapshot_archives
+            .iter()
+            .all(|info| info.is_remote()));
+    }
+
+    #[test]
+    fn test_get_highest_full_snapshot_archive_slot() {
+        solana_logger::setup();
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let min_slot = 123;
+        let max_slot = 456;
+        common_create_snapshot_archive_files(
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            min_slot,
+            max_slot,
+            0,
+            0,
+        );
+
+        assert_eq!(
+            get_highest_full_snapshot_archive_slot(full_snapshot_archives_dir.path()),
+            Some(max_slot - 1)
+        );
+    }
+
+    #[test]
+    fn test_get_highest_incremental_snapshot_slot() {
+        solana_logger::setup();
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let min_full_snapshot_slot = 12;
+        let max_full_snapshot_slot = 23;
+        let min_incremental_snapshot_slot = 34;
+        let max_incremental_snapshot_slot = 45;
+        common_create_snapshot_archive_files(
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            min_full_snapshot_slot,
+            max_full_snapshot_slot,
+            min_incremental_snapshot_slot,
+            max_incremental_snapshot_slot,
+        );
+
+        for full_snapshot_slot in min_full_snapshot_slot..max_full_snapshot_slot {
+            assert_eq!(
+                get_highest_incremental_snapshot_archive_slot(
+                    incremental_snapshot_archives_dir.path(),
+                    full_snapshot_slot
+                ),
+                Some(max_incremental_snapshot_slot - 1)
+            );
+        }
+
+        assert_eq!(
+            get_highest_incremental_snapshot_archive_slot(
+                incremental_snapshot_archives_dir.path(),
+                max_full_snapshot_slot
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_get_highest_incremental_snapshot_archive_info_with_policy() {
+        solana_logger::setup();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let base_slot = 100;
+        let slot = 150;
+        let mut duplicate_paths = [Hash::new_unique(), Hash::new_unique()].map(|hash| {
+            incremental_snapshot_archives_dir.path().join(format!(
+                "incremental-snapshot-{}-{}-{}.tar",
+                base_slot, slot, hash,
+            ))
+        });
+        duplicate_paths.sort();
+        // A lower incremental snapshot archive, which must not be picked
+        let lower_path = incremental_snapshot_archives_dir.path().join(format!(
+            "incremental-snapshot-{}-{}-{}.tar",
+            base_slot,
+            slot - 1,
+            Hash::new_unique(),
+        ));
+        for path in duplicate_paths.iter().chain(std::iter::once(&lower_path)) {
+            File::create(path).unwrap();
+        }
+
+        let picked = get_highest_incremental_snapshot_archive_info_with_policy(
+            incremental_snapshot_archives_dir.path(),
+            base_slot,
+            DuplicateIncrementalSnapshotArchivePolicy::PickFirstPath,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(picked.path(), &duplicate_paths[0]);
+        assert_eq!(
+            get_highest_incremental_snapshot_archive_info(
+                incremental_snapshot_archives_dir.path(),
+                base_slot,
+            )
+            .unwrap()
+            .path(),
+            &duplicate_paths[0],
+        );
+
+        assert!(matches!(
+            get_highest_incremental_snapshot_archive_info_with_policy(
+                incremental_snapshot_archives_dir.path(),
+                base_slot,
+                DuplicateIncrementalSnapshotArchivePolicy::Fail,
+            ),
+            Err(SnapshotError::DuplicateIncrementalSnapshotArchives {
+                base_slot: 100,
+                slot: 150,
+                count: 2,
+            })
+        ));
+
+        // Without duplicates, the policy does not matter
+        fs::remove_file(&duplicate_paths[1]).unwrap();
+        assert_eq!(
+            get_highest_incremental_snapshot_archive_info_with_policy(
+                incremental_snapshot_archives_dir.path(),
+                base_slot,
+                DuplicateIncrementalSnapshotArchivePolicy::Fail,
+            )
+            .unwrap()
+            .unwrap()
+            .path(),
+            &duplicate_paths[0],
+        );
+    }
+
+    fn common_test_purge_old_snapshot_archives(
+        snapshot_names: &[&String],
+        maximum_full_snapshot_archives_to_retain: usize,
+        maximum_incremental_snapshot_archives_to_retain: usize,
+        expected_snapshots: &[&String],
+    ) {
+        let temp_snap_dir = tempfile::TempDir::new().unwrap();
+
+        for snap_name in snapshot_names {
+            let snap_path = temp_snap_dir.path().join(snap_name);
+            let mut _snap_file = File::create(snap_path);
+        }
+        purge_old_snapshot_archives(
+            temp_snap_dir.path(),
+            temp_snap_dir.path(),
+            maximum_full_snapshot_archives_to_retain,
+            maximum_incremental_snapshot_archives_to_retain,
+            None,
+        );
+
+        let mut retained_snaps = HashSet::new();
+        for entry in fs::read_dir(temp_snap_dir.path()).unwrap() {
+            let entry_path_buf = entry.unwrap().path();
+            let entry_path = entry_path_buf.as_path();
+            let snapshot_name = entry_path
+                .file_name()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_string();
+            retained_snaps.insert(snapshot_name);
+        }
+
+        for snap_name in expected_snapshots {
+            assert!(
+                retained_snaps.contains(snap_name.as_str()),
+                "{} not found",
+                snap_name
+            );
+        }
+        assert_eq!(retained_snaps.len(), expected_snapshots.len());
+    }
+
+    #[test]
+    fn test_purge_old_snapshot_archives_over_total_bytes() {
+        let snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let create_archive = |name: String, size: u64| {
+            File::create(snapshot_archives_dir.path().join(name))
+                .unwrap()
+                .set_len(size)
+                .unwrap();
+        };
+        for slot in [1, 2, 3] {
+            create_archive(format!("snapshot-{}-{}.tar", slot, Hash::default()), 100);
+        }
+        for slot in [4, 5] {
+            create_archive(
+                format!("incremental-snapshot-3-{}-{}.tar", slot, Hash::default()),
+                10,
+            );
+        }
+        let remaining_slots = || {
+            let mut slots: Vec<_> = iter_snapshot_archives(snapshot_archives_dir.path())
+                .map(|archive| archive.slot)
+                .collect();
+            slots.sort_unstable();
+            slots
+        };
+        let purge = |maximum_snapshots_total_bytes| {
+            purge_old_snapshot_archives(
+                snapshot_archives_dir.path(),
+                snapshot_archives_dir.path(),
+                usize::MAX,
+                usize::MAX,
+                Some(maximum_snapshots_total_bytes),
+            )
+        };
+
+        // under the limit, nothing is removed
+        purge(320);
+        assert_eq!(remaining_slots(), vec![1, 2, 3, 4, 5]);
+
+        // the oldest archives are removed first
+        purge(230);
+        assert_eq!(remaining_slots(), vec![2, 3, 4, 5]);
+
+        // the highest full snapshot archive is kept, even though it alone exceeds the limit
+        purge(50);
+        assert_eq!(remaining_slots(), vec![3]);
+    }
+
+    #[test]
+    fn test_snapshot_disk_usage() {
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
+        let create_file = |path: PathBuf, size: u64| {
+            File::create(path).unwrap().set_len(size).unwrap();
+        };
+
+        for slot in [1, 2] {
+            create_file(
+                full_snapshot_archives_dir.path().join(format!(
+                    "snapshot-{}-{}.tar",
+                    slot,
+                    Hash::default()
+                )),
+                100,
+            );
+        }
+        // other files in the archives directories are not counted
+        create_file(full_snapshot_archives_dir.path().join("notes.txt"), 1_000);
+        create_file(
+            incremental_snapshot_archives_dir.path().join(format!(
+                "incremental-snapshot-2-3-{}.tar.zst",
+                Hash::default()
+            )),
+            10,
+        );
+        // a bank snapshot with both a "pre" and a "post" file is counted once
+        for slot in [3, 4] {
+            let bank_snapshot_dir = get_bank_snapshots_dir(&bank_snapshots_dir, slot);
+            fs::create_dir_all(&bank_snapshot_dir).unwrap();
+            let snapshot_path = bank_snapshot_dir.join(get_snapshot_file_name(slot));
+            create_file(
+                snapshot_path.with_extension(BANK_SNAPSHOT_PRE_FILENAME_EXTENSION),
+                5,
+            );
+            create_file(snapshot_path, 5);
+            create_file(bank_snapshot_dir.join(SNAPSHOT_STATUS_CACHE_FILENAME), 2);
+        }
+
+        let report = snapshot_disk_usage(
+            &full_snapshot_archives_dir,
+            &incremental_snapshot_archives_dir,
+            &bank_snapshots_dir,
+        );
+        assert_eq!(
+            report,
+            DiskUsageReport {
+                full_snapshot_archives_bytes: 200,
+                incremental_snapshot_archives_bytes: 10,
+                bank_snapshots_bytes: 24,
+            }
+        );
+        assert_eq!(report.total_bytes(), 234);
+
+        // missing directories take up no space
+        assert_eq!(
+            snapshot_disk_usage(
+                bank_snapshots_dir.path().join("missing"),
+                bank_snapshots_dir.path().join("missing"),
+                bank_snapshots_dir.path().join("missing"),
+            ),
+            DiskUsageReport::default(),
+        );
+    }
+
+    #[test]
+    fn test_purge_old_full_snapshot_archives() {
+        let snap1_name = format!("snapshot-1-{}.tar.zst", Hash::default());
+        let snap2_name = format!("snapshot-3-{}.tar.zst", Hash::default());
+        let snap3_name = format!("snapshot-50-{}.tar.zst", Hash::default());
+        let snapshot_names = vec![&snap1_name, &snap2_name, &snap3_name];
+
+        // expecting only the newest to be retained
+        let expected_snapshots = vec![&snap3_name];
+        common_test_purge_old_snapshot_archives(
+            &snapshot_names,
+            1,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            &expected_snapshots,
+        );
+
+        // retaining 0, but minimum to retain is 1
+        common_test_purge_old_snapshot_archives(
+            &snapshot_names,
+            0,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            &expected_snapshots,
+        );
+
+        // retaining 2, expecting the 2 newest to be retained
+        let expected_snapshots = vec![&snap2_name, &snap3_name];
+        common_test_purge_old_snapshot_archives(
+            &snapshot_names,
+            2,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            &expected_snapshots,
+        );
+
+        // retaining 3, all three should be retained
+        let expected_snapshots = vec![&snap1_name, &snap2_name, &snap3_name];
+        common_test_purge_old_snapshot_archives(
+            &snapshot_names,
+            3,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            &expected_snapshots,
+        );
+    }
+
+    /// Mimic a running node's behavior w.r.t. purging old snapshot archives.  Take snapshots in a
+    /// loop, and periodically purge old snapshot archives.  After purging, check to make sure the
+    /// snapshot archives on disk are correct.
+    #[test]
+    fn test_purge_old_full_snapshot_archives_in_the_loop() {
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let maximum_snapshots_to_retain = 5;
+        let starting_slot: Slot = 42;
+
+        for slot in (starting_slot..).take(100) {
+            let full_snapshot_archive_file_name =
+                format!("snapshot-{}-{}.tar", slot, Hash::default());
+            let full_snapshot_archive_path = full_snapshot_archives_dir
+                .as_ref()
+                .join(full_snapshot_archive_file_name);
+            File::create(full_snapshot_archive_path).unwrap();
+
+            // don't purge-and-check until enough snapshot archives have been created
+            if slot < starting_slot + maximum_snapshots_to_retain as Slot {
+                continue;
+            }
+
+            // purge infrequently, so there will always be snapshot archives to purge
+            if slot % (maximum_snapshots_to_retain as Slot * 2) != 0 {
+                continue;
+            }
+
+            purge_old_snapshot_archives(
+                &full_snapshot_archives_dir,
+                &incremental_snapshot_archives_dir,
+                maximum_snapshots_to_retain,
+                usize::MAX,
+                None,
+            );
+            let mut full_snapshot_archives =
+                get_full_snapshot_archives(&full_snapshot_archives_dir);
+            full_snapshot_archives.sort_unstable();
+            assert_eq!(full_snapshot_archives.len(), maximum_snapshots_to_retain);
+            assert_eq!(full_snapshot_archives.last().unwrap().slot(), slot);
+            for (i, full_snapshot_archive) in full_snapshot_archives.iter().rev().enumerate() {
+                assert_eq!(full_snapshot_archive.slot(), slot - i as Slot);
+            }
+        }
+    }
+
+    #[test]
+    fn test_purge_old_incremental_snapshot_archives() {
+        solana_logger::setup();
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let starting_slot = 100_000;
+
+        let maximum_incremental_snapshot_archives_to_retain =
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN;
+        let maximum_full_snapshot_archives_to_retain = DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN;
+
+        let incremental_snapshot_interval = 100;
+        let num_incremental_snapshots_per_full_snapshot =
+            maximum_incremental_snapshot_archives_to_retain * 2;
+        let full_snapshot_interval =
+            incremental_snapshot_interval * num_incremental_snapshots_per_full_snapshot;
+
+        let mut snapshot_filenames = vec![];
+        (starting_slot..)
+            .step_by(full_snapshot_interval)
+            .take(maximum_full_snapshot_archives_to_retain * 2)
+            .for_each(|full_snapshot_slot| {
+                let snapshot_filename =
+                    format!("snapshot-{}-{}.tar", full_snapshot_slot, Hash::default());
+                let snapshot_path = full_snapshot_archives_dir.path().join(&snapshot_filename);
+                File::create(snapshot_path).unwrap();
+                snapshot_filenames.push(snapshot_filename);
+
+                (full_snapshot_slot..)
+                    .step_by(incremental_snapshot_interval)
+                    .take(num_incremental_snapshots_per_full_snapshot)
+                    .skip(1)
+                    .for_each(|incremental_snapshot_slot| {
+                        let snapshot_filename = format!(
+                            "incremental-snapshot-{}-{}-{}.tar",
+                            full_snapshot_slot,
+                            incremental_snapshot_slot,
+                            Hash::default()
+                        );
+                        let snapshot_path = incremental_snapshot_archives_dir
+                            .path()
+                            .join(&snapshot_filename);
+                        File::create(snapshot_path).unwrap();
+                        snapshot_filenames.push(snapshot_filename);
+                    });
+            });
+
+        purge_old_snapshot_archives(
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            maximum_full_snapshot_archives_to_retain,
+            maximum_incremental_snapshot_archives_to_retain,
+            None,
+        );
+
+        // Ensure correct number of full snapshot archives are purged/retained
+        // NOTE: One extra full snapshot is always kept (the oldest), hence the `+1`
+        let mut remaining_full_snapshot_archives =
+            get_full_snapshot_archives(full_snapshot_archives_dir.path());
+        assert_eq!(
+            remaining_full_snapshot_archives.len(),
+            maximum_full_snapshot_archives_to_retain,
+        );
+        remaining_full_snapshot_archives.sort_unstable();
+        let latest_full_snapshot_archive_slot =
+            remaining_full_snapshot_archives.last().unwrap().slot();
+
+        // Ensure correct number of incremental snapshot archives are purged/retained
+        let mut remaining_incremental_snapshot_archives =
+            get_incremental_snapshot_archives(incremental_snapshot_archives_dir.path());
+        assert_eq!(
+            remaining_incremental_snapshot_archives.len(),
+            maximum_incremental_snapshot_archives_to_retain
+                + maximum_full_snapshot_archives_to_retain.saturating_sub(1)
+        );
+        remaining_incremental_snapshot_archives.sort_unstable();
+        remaining_incremental_snapshot_archives.reverse();
+
+        // Ensure there exists one incremental snapshot all but the latest full snapshot
+        for i in (1..maximum_full_snapshot_archives_to_retain).rev() {
+            let incremental_snapshot_archive =
+                remaining_incremental_snapshot_archives.pop().unwrap();
+
+            let expected_base_slot =
+                latest_full_snapshot_archive_slot - (i * full_snapshot_interval) as u64;
+            assert_eq!(incremental_snapshot_archive.base_slot(), expected_base_slot);
+            let expected_slot = expected_base_slot
+                + (full_snapshot_interval - incremental_snapshot_interval) as u64;
+            assert_eq!(incremental_snapshot_archive.slot(), expected_slot);
+        }
+
+        // Ensure all remaining incremental snapshots are only for the latest full snapshot
+        for incremental_snapshot_archive in &remaining_incremental_snapshot_archives {
+            assert_eq!(
+                incremental_snapshot_archive.base_slot(),
+                latest_full_snapshot_archive_slot
+            );
+        }
+
+        // Ensure the remaining incremental snapshots are at the right slot
+        let expected_remaing_incremental_snapshot_archive_slots =
+            (latest_full_snapshot_archive_slot..)
+                .step_by(incremental_snapshot_interval)
+                .take(num_incremental_snapshots_per_full_snapshot)
+                .skip(
+                    num_incremental_snapshots_per_full_snapshot
+                        - maximum_incremental_snapshot_archives_to_retain,
+                )
+                .collect::<HashSet<_>>();
+
+        let actual_remaining_incremental_snapshot_archive_slots =
+            remaining_incremental_snapshot_archives
+                .iter()
+                .map(|snapshot| snapshot.slot())
+                .collect::<HashSet<_>>();
+        assert_eq!(
+            actual_remaining_incremental_snapshot_archive_slots,
+            expected_remaing_incremental_snapshot_archive_slots
+        );
+    }
+
+    #[test]
+    fn test_purge_all_incremental_snapshot_archives_when_no_full_snapshot_archives() {
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+
+        for snapshot_filenames in [
+            format!("incremental-snapshot-100-120-{}.tar", Hash::default()),
+            format!("incremental-snapshot-100-140-{}.tar", Hash::default()),
+            format!("incremental-snapshot-100-160-{}.tar", Hash::default()),
+            format!("incremental-snapshot-100-180-{}.tar", Hash::default()),
+            format!("incremental-snapshot-200-220-{}.tar", Hash::default()),
+            format!("incremental-snapshot-200-240-{}.tar", Hash::default()),
+            format!("incremental-snapshot-200-260-{}.tar", Hash::default()),
+            format!("incremental-snapshot-200-280-{}.tar", Hash::default()),
+        ] {
+            let snapshot_path = incremental_snapshot_archives_dir
+                .path()
+                .join(&snapshot_filenames);
+            File::create(snapshot_path).unwrap();
+        }
+
+        purge_old_snapshot_archives(
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            usize::MAX,
+            usize::MAX,
+            None,
+        );
+
+        let remaining_incremental_snapshot_archives =
+            get_incremental_snapshot_archives(incremental_snapshot_archives_dir.path());
+        assert!(remaining_incremental_snapshot_archives.is_empty());
+    }
+
+    /// Test roundtrip of bank to a full snapshot archive compressed with a zstd dictionary, then
+    /// back again, and that the archive can neither be created nor loaded without the dictionary
+    #[test]
+    fn test_roundtrip_bank_to_and_from_full_snapshot_zstd_dictionary() {
+        solana_logger::setup();
+        let genesis_config = GenesisConfig::default();
+        let original_bank = Bank::new_for_tests(&genesis_config);
+
+        while !original_bank.is_complete() {
+            original_bank.register_tick(&Hash::new_unique());
+        }
+        original_bank.squash();
+        original_bank.force_flush_accounts_cache();
+        original_bank.update_accounts_hash();
+
+        let accounts_dir = tempfile::TempDir::new().unwrap();
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let zstd_dictionary = b"version snapshots accounts status_cache".repeat(16);
+        let other_zstd_dictionary = b"status_cache accounts snapshots version".repeat(16);
+        let archive_format = ArchiveFormat::TarZstdWithDict(zstd_dictionary_id(&zstd_dictionary));
+
+        let snapshot_storages = original_bank.get_snapshot_storages(None);
+        let bank_snapshot_info = add_bank_snapshot(
+            &bank_snapshots_dir,
+            &original_bank,
+            &snapshot_storages,
+            SnapshotVersion::default(),
+        )
+        .unwrap();
+        let accounts_package = AccountsPackage::new(
+            AccountsPackageType::Snapshot(SnapshotType::FullSnapshot),
+            &original_bank,
+            &bank_snapshot_info,
+            &bank_snapshots_dir,
+            original_bank
+                .status_cache
+                .read()
+                .unwrap()
+                .root_slot_deltas(),
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            snapshot_storages,
+            archive_format,
+            SnapshotVersion::default(),
+            None,
+        )
         .unwrap();
-
-        let snapshot_root_paths = SnapshotRootPaths {
-            full_snapshot_root_file_path: temp_dir.path().join("data-file"),
-            incremental_snapshot_root_file_path: None,
+        crate::serde_snapshot::reserialize_bank_with_new_accounts_hash(
+            accounts_package.snapshot_links.path(),
+            accounts_package.slot,
+            &original_bank.get_accounts_hash(),
+            None,
+            None,
+        );
+        let snapshot_package =
+            SnapshotPackage::new(accounts_package, original_bank.get_accounts_hash());
+        assert!(snapshot_package
+            .path()
+            .to_str()
+            .unwrap()
+            .ends_with(&format!(".{}", archive_format.extension())));
+
+        let archive = |zstd_dictionary: Option<&[u8]>| {
+            archive_snapshot_package(
+                &snapshot_package,
+                full_snapshot_archives_dir.path(),
+                incremental_snapshot_archives_dir.path(),
+                usize::MAX,
+                usize::MAX,
+                None,
+                zstd_dictionary,
+                None,
+            )
         };
-
-        let result = deserialize_snapshot_data_files_capped(
-            &snapshot_root_paths,
-            expected_consumed_size - 1,
-            |stream| {
-                Ok(deserialize_from::<_, u32>(
-                    &mut stream.full_snapshot_stream,
-                )?)
-            },
+        assert_matches!(archive(None), Err(SnapshotError::MissingZstdDictionary(_)));
+        assert_matches!(
+            archive(Some(other_zstd_dictionary.as_slice())),
+            Err(SnapshotError::MismatchedZstdDictionary { .. })
         );
-        assert_matches!(result, Err(SnapshotError::Io(ref message)) if message.to_string().starts_with("too large snapshot data file to deserialize"));
+        archive(Some(zstd_dictionary.as_slice())).unwrap();
+        let snapshot_archive_info =
+            FullSnapshotArchiveInfo::new_from_path(snapshot_package.path().clone()).unwrap();
+        assert_eq!(snapshot_archive_info.archive_format(), archive_format);
+
+        let load = |zstd_dictionary: Option<&[u8]>| {
+            bank_from_snapshot_archives(
+                &[PathBuf::from(accounts_dir.path())],
+                bank_snapshots_dir.path(),
+                &snapshot_archive_info,
+                None,
+                &genesis_config,
+                &RuntimeConfig::default(),
+                None,
+                None,
+                AccountSecondaryIndexes::default(),
+                false,
+                None,
+                AccountShrinkThreshold::default(),
+                false,
+                false,
+                false,
+                Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
+                None,
+                &Arc::default(),
+                SnapshotLoadOptions {
+                    zstd_dictionary,
+                    ..SnapshotLoadOptions::default()
+                },
+            )
+        };
+        assert_matches!(load(None), Err(SnapshotError::MissingZstdDictionary(_)));
+        assert_matches!(
+            load(Some(other_zstd_dictionary.as_slice())),
+            Err(SnapshotError::MismatchedZstdDictionary { expected, actual, .. })
+                if expected == zstd_dictionary_id(&zstd_dictionary)
+                    && actual == zstd_dictionary_id(&other_zstd_dictionary)
+        );
+        let (roundtrip_bank, _) = load(Some(zstd_dictionary.as_slice())).unwrap();
+        assert_eq!(original_bank, roundtrip_bank);
     }
 
+    /// Test roundtrip of bank to a full snapshot, then back again.  This test creates the simplest
+    /// bank possible, so the contents of the snapshot archive will be quite minimal.
     #[test]
-    fn test_deserialize_snapshot_data_file_extra_data() {
-        let expected_data = 2323_u32;
-        let expected_consumed_size = size_of::<u32>() as u64;
+    fn test_roundtrip_bank_to_and_from_full_snapshot_simple() {
+        solana_logger::setup();
+        let genesis_config = GenesisConfig::default();
+        let original_bank = Bank::new_for_tests(&genesis_config);
 
-        let temp_dir = tempfile::TempDir::new().unwrap();
-        serialize_snapshot_data_file_capped(
-            &temp_dir.path().join("data-file"),
-            expected_consumed_size * 2,
-            |stream| {
-                serialize_into(stream.by_ref(), &expected_data)?;
-                serialize_into(stream.by_ref(), &expected_data)?;
-                Ok(())
-            },
+        while !original_bank.is_complete() {
+            original_bank.register_tick(&Hash::new_unique());
+        }
+
+        let accounts_dir = tempfile::TempDir::new().unwrap();
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let snapshot_archive_format = ArchiveFormat::Tar;
+
+        let snapshot_archive_info = bank_to_full_snapshot_archive(
+            &bank_snapshots_dir,
+            &original_bank,
+            None,
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            snapshot_archive_format,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
         )
         .unwrap();
 
-        let snapshot_root_paths = SnapshotRootPaths {
-            full_snapshot_root_file_path: temp_dir.path().join("data-file"),
-            incremental_snapshot_root_file_path: None,
-        };
+        let (roundtrip_bank, _) = bank_from_snapshot_archives(
+            &[PathBuf::from(accounts_dir.path())],
+            bank_snapshots_dir.path(),
+            &snapshot_archive_info,
+            None,
+            &genesis_config,
+            &RuntimeConfig::default(),
+            None,
+            None,
+            AccountSecondaryIndexes::default(),
+            false,
+            None,
+            AccountShrinkThreshold::default(),
+            false,
+            false,
+            false,
+            Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
+            None,
+            &Arc::default(),
+            SnapshotLoadOptions::default(),
+        )
+        .unwrap();
 
-        let result = deserialize_snapshot_data_files_capped(
-            &snapshot_root_paths,
-            expected_consumed_size * 2,
-            |stream| {
-                Ok(deserialize_from::<_, u32>(
-                    &mut stream.full_snapshot_stream,
-                )?)
-            },
-        );
-        assert_matches!(result, Err(SnapshotError::Io(ref message)) if message.to_string().starts_with("invalid snapshot data file"));
+        assert_eq!(original_bank, roundtrip_bank);
     }
 
     #[test]
-    fn test_snapshot_version_from_file_under_limit() {
-        let file_content = SnapshotVersion::default().as_str();
-        let mut file = NamedTempFile::new().unwrap();
-        file.write_all(file_content.as_bytes()).unwrap();
-        let version_from_file = snapshot_version_from_file(file.path()).unwrap();
-        assert_eq!(version_from_file, file_content);
+    fn test_unarchive_thread_counts() {
+        for thread_budget in 0..64 {
+            let (parallel_divisions, num_rebuilder_threads) =
+                unarchive_thread_counts(Some(thread_budget));
+            assert!(parallel_divisions >= 1 && num_rebuilder_threads >= 1);
+            // the full and incremental snapshots are unarchived one after the other, with these
+            // threads, plus one to read the archive
+            assert!(
+                1 + parallel_divisions + num_rebuilder_threads
+                    <= thread_budget.max(MIN_UNARCHIVE_THREAD_BUDGET)
+            );
+        }
     }
 
+    /// Test that the accounts hashes of a full and an incremental snapshot are verified within the
+    /// verification thread budget
     #[test]
-    fn test_snapshot_version_from_file_over_limit() {
-        let over_limit_size = usize::try_from(MAX_SNAPSHOT_VERSION_FILE_SIZE + 1).unwrap();
-        let file_content = vec![7u8; over_limit_size];
-        let mut file = NamedTempFile::new().unwrap();
-        file.write_all(&file_content).unwrap();
-        assert_matches!(
-            snapshot_version_from_file(file.path()),
-            Err(SnapshotError::Io(ref message)) if message.to_string().starts_with("snapshot version file too large")
-        );
+    fn test_bank_from_snapshot_archives_verification_thread_budget() {
+        solana_logger::setup();
+        for thread_budget in [0, 1, 3] {
+            assert_eq!(
+                new_verification_thread_pool(thread_budget).current_num_threads(),
+                thread_budget.max(1),
+            );
+        }
+
+        let collector = Pubkey::new_unique();
+        let (genesis_config, mint_keypair) = create_genesis_config(sol_to_lamports(1_000_000.));
+        let mut banks = vec![Arc::new(Bank::new_for_tests(&genesis_config))];
+        while !banks[0].is_complete() {
+            banks[0].register_tick(&Hash::new_unique());
+        }
+        for slot in 1..=2 {
+            let bank = Arc::new(Bank::new_from_parent(
+                banks.last().unwrap(),
+                &collector,
+                slot,
+            ));
+            bank.transfer(sol_to_lamports(1.), &mint_keypair, &Pubkey::new_unique())
+                .unwrap();
+            while !bank.is_complete() {
+                bank.register_tick(&Hash::new_unique());
+            }
+            banks.push(bank);
+        }
+
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let full_snapshot_archive_info = bank_to_full_snapshot_archive(
+            &bank_snapshots_dir,
+            &banks[1],
+            None,
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            ArchiveFormat::Tar,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+        )
+        .unwrap();
+        let incremental_snapshot_archive_info = bank_to_incremental_snapshot_archive(
+            &bank_snapshots_dir,
+            &banks[2],
+            banks[1].slot(),
+            None,
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            ArchiveFormat::Tar,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+        )
+        .unwrap();
+
+        for thread_budget in [1, 2] {
+            let accounts_dir = tempfile::TempDir::new().unwrap();
+            let (roundtrip_bank, _) = bank_from_snapshot_archives(
+                &[PathBuf::from(accounts_dir.path())],
+                bank_snapshots_dir.path(),
+                &full_snapshot_archive_info,
+                Some(&incremental_snapshot_archive_info),
+                &genesis_config,
+                &RuntimeConfig::default(),
+                None,
+                None,
+                AccountSecondaryIndexes::default(),
+                false,
+                None,
+                AccountShrinkThreshold::default(),
+                false,
+                false,
+                false,
+                Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
+                None,
+                &Arc::default(),
+                SnapshotLoadOptions {
+                    verification_thread_budget: Some(thread_budget),
+                    ..SnapshotLoadOptions::default()
+                },
+            )
+            .unwrap();
+            // the accounts hash is verified in the background, on the verification thread pool
+            roundtrip_bank
+                .rc
+                .accounts
+                .accounts_db
+                .verify_accounts_hash_in_bg
+                .wait_for_complete();
+            assert_eq!(roundtrip_bank, *banks[2]);
+        }
     }
 
     #[test]
-    fn test_parse_full_snapshot_archive_filename() {
+    fn test_bank_from_snapshot_archives_untar_across_account_paths() {
+        solana_logger::setup();
+        // the untar threads are a multiple of the account paths, and stay within the budget
+        // along with the thread that reads the archive
         assert_eq!(
-            parse_full_snapshot_archive_filename(&format!(
-                "snapshot-42-{}.tar.bz2",
-                Hash::default()
-            ))
-            .unwrap(),
-            (42, Hash::default(), ArchiveFormat::TarBzip2)
+            unarchive_thread_counts_across_account_paths(Some(4), 2),
+            Some((2, 1))
         );
         assert_eq!(
-            parse_full_snapshot_archive_filename(&format!(
-                "snapshot-43-{}.tar.zst",
-                Hash::default()
-            ))
-            .unwrap(),
-            (43, Hash::default(), ArchiveFormat::TarZstd)
+            unarchive_thread_counts_across_account_paths(Some(16), 2),
+            Some((2, 13))
         );
         assert_eq!(
-            parse_full_snapshot_archive_filename(&format!("snapshot-44-{}.tar", Hash::default()))
-                .unwrap(),
-            (44, Hash::default(), ArchiveFormat::Tar)
+            unarchive_thread_counts_across_account_paths(Some(16), 4),
+            Some((4, 11))
         );
         assert_eq!(
-            parse_full_snapshot_archive_filename(&format!(
-                "snapshot-45-{}.tar.lz4",
-                Hash::default()
-            ))
-            .unwrap(),
-            (45, Hash::default(), ArchiveFormat::TarLz4)
+            unarchive_thread_counts_across_account_paths(Some(32), 3),
+            Some((3, 28))
         );
-
-        assert!(parse_full_snapshot_archive_filename("invalid").is_err());
-        assert!(
-            parse_full_snapshot_archive_filename("snapshot-bad!slot-bad!hash.bad!ext").is_err()
+        // too small a budget for a thread per account path, and one to rebuild the storages
+        assert_eq!(
+            unarchive_thread_counts_across_account_paths(Some(3), 2),
+            None
+        );
+        assert_eq!(
+            unarchive_thread_counts_across_account_paths(Some(3), 4),
+            None
         );
+        let (num_untar_threads, _) = unarchive_thread_counts_across_account_paths(None, 3).unwrap();
+        assert_eq!(num_untar_threads % 3, 0);
 
-        assert!(
-            parse_full_snapshot_archive_filename("snapshot-12345678-bad!hash.bad!ext").is_err()
+        let genesis_config = GenesisConfig::default();
+        let original_bank = Bank::new_for_tests(&genesis_config);
+        while !original_bank.is_complete() {
+            original_bank.register_tick(&Hash::new_unique());
+        }
+
+        let accounts_dirs = [
+            tempfile::TempDir::new().unwrap(),
+            tempfile::TempDir::new().unwrap(),
+        ];
+        let account_paths: Vec<_> = accounts_dirs
+            .iter()
+            .map(|accounts_dir| accounts_dir.path().to_path_buf())
+            .collect();
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let snapshot_archive_info = bank_to_full_snapshot_archive(
+            &bank_snapshots_dir,
+            &original_bank,
+            None,
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            ArchiveFormat::Tar,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+        )
+        .unwrap();
+
+        let load = |full_snapshot_archive_info: &FullSnapshotArchiveInfo| {
+            bank_from_snapshot_archives(
+                &account_paths,
+                bank_snapshots_dir.path(),
+                full_snapshot_archive_info,
+                None,
+                &genesis_config,
+                &RuntimeConfig::default(),
+                None,
+                None,
+                AccountSecondaryIndexes::default(),
+                false,
+                None,
+                AccountShrinkThreshold::default(),
+                false,
+                false,
+                false,
+                Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
+                None,
+                &Arc::default(),
+                SnapshotLoadOptions {
+                    untar_across_account_paths: true,
+                    ..SnapshotLoadOptions::default()
+                },
+            )
+            .map(|(bank, _timings)| bank)
+        };
+        assert_eq!(load(&snapshot_archive_info).unwrap(), original_bank);
+
+        // the archive must have a manifest for its slot, which is checked before untarring
+        let mismatched_archive = full_snapshot_archives_dir.path().join(format!(
+            "snapshot-{}-{}.tar",
+            original_bank.slot() + 1,
+            snapshot_archive_info.hash(),
+        ));
+        fs::copy(snapshot_archive_info.path(), &mismatched_archive).unwrap();
+        let mismatched_archive_info =
+            FullSnapshotArchiveInfo::new_from_path(mismatched_archive).unwrap();
+        assert_matches!(
+            load(&mismatched_archive_info),
+            Err(SnapshotError::ArchiveStructure(
+                ArchiveStructureError::MissingEntry(_)
+            ))
         );
-        assert!(parse_full_snapshot_archive_filename(&format!(
-            "snapshot-12345678-{}.bad!ext",
-            Hash::new_unique()
-        ))
-        .is_err());
-        assert!(parse_full_snapshot_archive_filename("snapshot-12345678-bad!hash.tar").is_err());
+    }
 
-        assert!(parse_full_snapshot_archive_filename(&format!(
-            "snapshot-bad!slot-{}.bad!ext",
-            Hash::new_unique()
-        ))
-        .is_err());
-        assert!(parse_full_snapshot_archive_filename(&format!(
-            "snapshot-12345678-{}.bad!ext",
-            Hash::new_unique()
-        ))
-        .is_err());
-        assert!(parse_full_snapshot_archive_filename(&format!(
-            "snapshot-bad!slot-{}.tar",
-            Hash::new_unique()
-        ))
-        .is_err());
+    /// Test that extracting a snapshot archive with a tiny extraction buffer rebuilds the same bank
+    /// as with the default buffer
+    #[test]
+    fn test_bank_from_snapshot_archives_extraction_buffer_bytes() {
+        solana_logger::setup();
+        let genesis_config = GenesisConfig::default();
+        let original_bank = Bank::new_for_tests(&genesis_config);
+        for i in 0..10 {
+            let account = AccountSharedData::new(1_000_000 + i, 10_000, &Pubkey::default());
+            original_bank.store_account(&solana_sdk::pubkey::new_rand(), &account);
+        }
+        while !original_bank.is_complete() {
+            original_bank.register_tick(&Hash::new_unique());
+        }
+
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let snapshot_archive_info = bank_to_full_snapshot_archive(
+            &bank_snapshots_dir,
+            &original_bank,
+            None,
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            ArchiveFormat::TarZstd,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+        )
+        .unwrap();
 
-        assert!(parse_full_snapshot_archive_filename("snapshot-bad!slot-bad!hash.tar").is_err());
-        assert!(parse_full_snapshot_archive_filename("snapshot-12345678-bad!hash.tar").is_err());
-        assert!(parse_full_snapshot_archive_filename(&format!(
-            "snapshot-bad!slot-{}.tar",
-            Hash::new_unique()
-        ))
-        .is_err());
+        for extraction_buffer_bytes in [None, Some(1_024)] {
+            let accounts_dir = tempfile::TempDir::new().unwrap();
+            let (roundtrip_bank, _) = bank_from_snapshot_archives(
+                &[PathBuf::from(accounts_dir.path())],
+                bank_snapshots_dir.path(),
+                &snapshot_archive_info,
+                None,
+                &genesis_config,
+                &RuntimeConfig::default(),
+                None,
+                None,
+                AccountSecondaryIndexes::default(),
+                false,
+                None,
+                AccountShrinkThreshold::default(),
+                false,
+                false,
+                false,
+                Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
+                None,
+                &Arc::default(),
+                SnapshotLoadOptions {
+                    extraction_buffer_bytes,
+                    ..SnapshotLoadOptions::default()
+                },
+            )
+            .unwrap();
+            assert_eq!(original_bank, roundtrip_bank);
+        }
     }
 
     #[test]
-    fn test_parse_incremental_snapshot_archive_filename() {
+    fn test_last_full_snapshot_accounts_hash() {
         solana_logger::setup();
+        let genesis_config = GenesisConfig::default();
+        let bank0 = Arc::new(Bank::new_for_tests(&genesis_config));
+        let original_bank = Bank::new_from_parent(&bank0, &Pubkey::default(), 1);
+        while !original_bank.is_complete() {
+            original_bank.register_tick(&Hash::new_unique());
+        }
         assert_eq!(
-            parse_incremental_snapshot_archive_filename(&format!(
-                "incremental-snapshot-42-123-{}.tar.bz2",
-                Hash::default()
-            ))
-            .unwrap(),
-            (42, 123, Hash::default(), ArchiveFormat::TarBzip2)
-        );
-        assert_eq!(
-            parse_incremental_snapshot_archive_filename(&format!(
-                "incremental-snapshot-43-234-{}.tar.zst",
-                Hash::default()
-            ))
-            .unwrap(),
-            (43, 234, Hash::default(), ArchiveFormat::TarZstd)
+            original_bank
+                .rc
+                .accounts
+                .accounts_db
+                .last_full_snapshot_accounts_hash(),
+            None
         );
+
+        let accounts_dir = tempfile::TempDir::new().unwrap();
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let snapshot_archive_info = bank_to_full_snapshot_archive(
+            &bank_snapshots_dir,
+            &original_bank,
+            None,
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            ArchiveFormat::Tar,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+        )
+        .unwrap();
+        let expected = Some((original_bank.slot(), original_bank.get_accounts_hash()));
         assert_eq!(
-            parse_incremental_snapshot_archive_filename(&format!(
-                "incremental-snapshot-44-345-{}.tar",
-                Hash::default()
-            ))
-            .unwrap(),
-            (44, 345, Hash::default(), ArchiveFormat::Tar)
+            original_bank
+                .rc
+                .accounts
+                .accounts_db
+                .last_full_snapshot_accounts_hash(),
+            expected
         );
+
+        let (roundtrip_bank, _) = bank_from_snapshot_archives(
+            &[PathBuf::from(accounts_dir.path())],
+            bank_snapshots_dir.path(),
+            &snapshot_archive_info,
+            None,
+            &genesis_config,
+            &RuntimeConfig::default(),
+            None,
+            None,
+            AccountSecondaryIndexes::default(),
+            false,
+            None,
+            AccountShrinkThreshold::default(),
+            false,
+            false,
+            false,
+            Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
+            None,
+            &Arc::default(),
+            SnapshotLoadOptions::default(),
+        )
+        .unwrap();
         assert_eq!(
-            parse_incremental_snapshot_archive_filename(&format!(
-                "incremental-snapshot-45-456-{}.tar.lz4",
-                Hash::default()
-            ))
-            .unwrap(),
-            (45, 456, Hash::default(), ArchiveFormat::TarLz4)
+            roundtrip_bank
+                .rc
+                .accounts
+                .accounts_db
+                .last_full_snapshot_accounts_hash(),
+            expected
         );
 
-        assert!(parse_incremental_snapshot_archive_filename("invalid").is_err());
-        assert!(parse_incremental_snapshot_archive_filename(&format!(
-            "snapshot-42-{}.tar",
-            Hash::new_unique()
-        ))
-        .is_err());
-        assert!(parse_incremental_snapshot_archive_filename(
-            "incremental-snapshot-bad!slot-bad!slot-bad!hash.bad!ext"
-        )
-        .is_err());
+        // older full snapshots are ignored
+        let accounts_db = &roundtrip_bank.rc.accounts.accounts_db;
+        accounts_db.set_last_full_snapshot_accounts_hash(0, Hash::new_unique());
+        assert_eq!(accounts_db.last_full_snapshot_accounts_hash(), expected);
+    }
 
-        assert!(parse_incremental_snapshot_archive_filename(&format!(
-            "incremental-snapshot-bad!slot-56785678-{}.tar",
-            Hash::new_unique()
-        ))
-        .is_err());
+    #[test]
+    fn test_bank_from_snapshot_archives_with_temp_dir_factory() {
+        /// Creates temp dirs in a scratch dir, and records them
+        struct RecordingTempDirFactory {
+            scratch_dir: TempDir,
+            temp_dirs: Mutex<Vec<PathBuf>>,
+        }
+        impl TempDirFactory for RecordingTempDirFactory {
+            fn create_temp_dir(&self, prefix: &str) -> IoResult<TempDir> {
+                let temp_dir =
+                    TempDirInFactory::new(self.scratch_dir.path()).create_temp_dir(prefix)?;
+                self.temp_dirs
+                    .lock()
+                    .unwrap()
+                    .push(temp_dir.path().to_path_buf());
+                Ok(temp_dir)
+            }
+        }
 
-        assert!(parse_incremental_snapshot_archive_filename(&format!(
-            "incremental-snapshot-12345678-bad!slot-{}.tar",
-            Hash::new_unique()
-        ))
-        .is_err());
+        solana_logger::setup();
+        let genesis_config = GenesisConfig::default();
+        let original_bank = Bank::new_for_tests(&genesis_config);
+        while !original_bank.is_complete() {
+            original_bank.register_tick(&Hash::new_unique());
+        }
 
-        assert!(parse_incremental_snapshot_archive_filename(
-            "incremental-snapshot-12341234-56785678-bad!HASH.tar"
+        let accounts_dir = tempfile::TempDir::new().unwrap();
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let snapshot_archive_info = bank_to_full_snapshot_archive(
+            &bank_snapshots_dir,
+            &original_bank,
+            None,
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            ArchiveFormat::Tar,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
         )
-        .is_err());
+        .unwrap();
 
-        assert!(parse_incremental_snapshot_archive_filename(&format!(
-            "incremental-snapshot-12341234-56785678-{}.bad!ext",
-            Hash::new_unique()
-        ))
-        .is_err());
+        let temp_dir_factory = RecordingTempDirFactory {
+            scratch_dir: tempfile::TempDir::new().unwrap(),
+            temp_dirs: Mutex::default(),
+        };
+        let (roundtrip_bank, _) = bank_from_snapshot_archives(
+            &[PathBuf::from(accounts_dir.path())],
+            bank_snapshots_dir.path(),
+            &snapshot_archive_info,
+            None,
+            &genesis_config,
+            &RuntimeConfig::default(),
+            None,
+            None,
+            AccountSecondaryIndexes::default(),
+            false,
+            None,
+            AccountShrinkThreshold::default(),
+            false,
+            false,
+            false,
+            Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
+            None,
+            &Arc::default(),
+            SnapshotLoadOptions {
+                temp_dir_factory: Some(&temp_dir_factory),
+                ..SnapshotLoadOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(original_bank, roundtrip_bank);
+
+        // the full snapshot archive was extracted into the scratch dir
+        let temp_dirs = temp_dir_factory.temp_dirs.into_inner().unwrap();
+        assert_eq!(temp_dirs.len(), 1);
+        assert!(temp_dirs[0].starts_with(temp_dir_factory.scratch_dir.path()));
+        assert!(temp_dirs[0]
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with(TMP_SNAPSHOT_ARCHIVE_PREFIX));
     }
 
     #[test]
-    fn test_check_are_snapshots_compatible() {
+    fn test_bank_from_snapshot_archives_with_progress() {
         solana_logger::setup();
-        let slot1: Slot = 1234;
-        let slot2: Slot = 5678;
-        let slot3: Slot = 999_999;
+        let genesis_config = GenesisConfig::default();
+        let original_bank = Bank::new_for_tests(&genesis_config);
+        while !original_bank.is_complete() {
+            original_bank.register_tick(&Hash::new_unique());
+        }
 
-        let full_snapshot_archive_info = FullSnapshotArchiveInfo::new_from_path(PathBuf::from(
-            format!("/dir/snapshot-{}-{}.tar", slot1, Hash::new_unique()),
-        ))
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let snapshot_archive_info = bank_to_full_snapshot_archive(
+            &bank_snapshots_dir,
+            &original_bank,
+            None,
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            ArchiveFormat::Tar,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+        )
         .unwrap();
 
-        assert!(check_are_snapshots_compatible(&full_snapshot_archive_info, None,).is_ok());
+        let load_with_progress = |accounts_dir: &Path, progress_sender: &Sender<LoadProgress>| {
+            bank_from_snapshot_archives(
+                &[PathBuf::from(accounts_dir)],
+                bank_snapshots_dir.path(),
+                &snapshot_archive_info,
+                None,
+                &genesis_config,
+                &RuntimeConfig::default(),
+                None,
+                None,
+                AccountSecondaryIndexes::default(),
+                false,
+                None,
+                AccountShrinkThreshold::default(),
+                false,
+                false,
+                false,
+                Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
+                None,
+                &Arc::default(),
+                SnapshotLoadOptions {
+                    progress_sender: Some(progress_sender),
+                    ..SnapshotLoadOptions::default()
+                },
+            )
+            .unwrap()
+            .0
+        };
 
-        let incremental_snapshot_archive_info =
-            IncrementalSnapshotArchiveInfo::new_from_path(PathBuf::from(format!(
-                "/dir/incremental-snapshot-{}-{}-{}.tar",
-                slot1,
-                slot2,
-                Hash::new_unique()
-            )))
+        let accounts_dir = tempfile::TempDir::new().unwrap();
+        let (progress_sender, progress_receiver) = crossbeam_channel::unbounded();
+        let roundtrip_bank = load_with_progress(accounts_dir.path(), &progress_sender);
+        assert_eq!(original_bank, roundtrip_bank);
+        // the progress of the accounts hash verification is sent until it completes
+        roundtrip_bank
+            .rc
+            .accounts
+            .accounts_db
+            .verify_accounts_hash_in_bg
+            .wait_for_complete();
+        drop(progress_sender);
+        let progress: Vec<_> = progress_receiver.iter().collect();
+        assert_eq!(progress.first(), Some(&LoadProgress::UnpackingArchives));
+        assert_eq!(progress.last(), Some(&LoadProgress::Complete));
+        assert!(progress
+            .windows(2)
+            .all(|progress| progress[0] < progress[1]));
+
+        // the final counts are always sent
+        let num_storages = progress
+            .iter()
+            .find_map(|progress| match progress {
+                LoadProgress::ArchivesUnpacked { num_storages } => Some(*num_storages),
+                _ => None,
+            })
             .unwrap();
+        assert!(num_storages > 0);
+        assert!(progress.contains(&LoadProgress::Unpacking {
+            untar_percent: 100,
+            num_storages_rebuilt: num_storages,
+        }));
+        assert!(progress.iter().any(|progress| matches!(
+            progress,
+            LoadProgress::GeneratingIndex { num_index_entries } if *num_index_entries > 0,
+        )));
+        assert!(progress.contains(&LoadProgress::BankRebuilt {
+            slot: original_bank.slot()
+        }));
+        assert!(progress.contains(&LoadProgress::HashingAccounts { percent: 100 }));
+
+        // progress is dropped, instead of blocking, when the channel is full
+        let accounts_dir = tempfile::TempDir::new().unwrap();
+        let (progress_sender, progress_receiver) = crossbeam_channel::bounded(1);
+        let roundtrip_bank = load_with_progress(accounts_dir.path(), &progress_sender);
+        assert_eq!(original_bank, roundtrip_bank);
+        let progress: Vec<_> = progress_receiver.try_iter().collect();
+        assert_eq!(progress, vec![LoadProgress::UnpackingArchives]);
+    }
 
-        assert!(check_are_snapshots_compatible(
-            &full_snapshot_archive_info,
-            Some(&incremental_snapshot_archive_info)
+    /// Test that loading an archive whose epoch accounts hash does not match, in non-fatal mode,
+    /// returns the bank flagged as unverified
+    #[test]
+    fn test_bank_from_snapshot_archives_epoch_accounts_hash_mismatch_non_fatal() {
+        solana_logger::setup();
+        let genesis_config_info = crate::genesis_utils::create_genesis_config(sol_to_lamports(1.));
+        let genesis_config = genesis_config_info.genesis_config;
+        let bank0 = Bank::new_for_tests(&genesis_config);
+        bank0
+            .rc
+            .accounts
+            .accounts_db
+            .epoch_accounts_hash_manager
+            .set_valid(EpochAccountsHash::new(Hash::new_unique()), 0);
+
+        // the EAH is hashed into the bank at the calculation stop slot
+        let slot = epoch_accounts_hash::calculation_stop(&bank0);
+        let bank = Bank::new_from_parent(&Arc::new(bank0), &Pubkey::default(), slot);
+        bank.fill_bank_with_ticks_for_tests();
+        bank.squash();
+        bank.force_flush_accounts_cache();
+        bank.update_accounts_hash();
+        assert!(bank.verify_epoch_accounts_hash());
+
+        // now change the EAH, so the one in the archive does not match the bank hash
+        let accounts_db = &bank.rc.accounts.accounts_db;
+        accounts_db.epoch_accounts_hash_manager.set_in_flight(slot);
+        accounts_db
+            .epoch_accounts_hash_manager
+            .set_valid(EpochAccountsHash::new(Hash::new_unique()), slot);
+        assert!(!bank.verify_epoch_accounts_hash());
+
+        let accounts_dir = tempfile::TempDir::new().unwrap();
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let snapshot_storages = bank.get_snapshot_storages(None);
+        let bank_snapshot_info = add_bank_snapshot(
+            &bank_snapshots_dir,
+            &bank,
+            &snapshot_storages,
+            SnapshotVersion::default(),
+        )
+        .unwrap();
+        let snapshot_archive_info = package_and_archive_full_snapshot(
+            &bank,
+            &bank_snapshot_info,
+            &bank_snapshots_dir,
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            snapshot_storages,
+            ArchiveFormat::Tar,
+            SnapshotVersion::default(),
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+        )
+        .unwrap();
+
+        let (deserialized_bank, _) = bank_from_snapshot_archives(
+            &[PathBuf::from(accounts_dir.path())],
+            bank_snapshots_dir.path(),
+            &snapshot_archive_info,
+            None,
+            &genesis_config,
+            &RuntimeConfig::default(),
+            None,
+            None,
+            AccountSecondaryIndexes::default(),
+            false,
+            None,
+            AccountShrinkThreshold::default(),
+            false,
+            false,
+            false,
+            Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
+            None,
+            &Arc::default(),
+            SnapshotLoadOptions {
+                epoch_accounts_hash_mismatch_is_fatal: false,
+                ..SnapshotLoadOptions::default()
+            },
         )
-        .is_ok());
+        .unwrap();
+        assert_eq!(deserialized_bank.slot(), slot);
+        assert!(deserialized_bank.is_startup_verification_failed());
+        assert!(!deserialized_bank.is_startup_verification_complete());
+    }
 
-        let incremental_snapshot_archive_info =
-            IncrementalSnapshotArchiveInfo::new_from_path(PathBuf::from(format!(
-                "/dir/incremental-snapshot-{}-{}-{}.tar",
-                slot2,
-                slot3,
-                Hash::new_unique()
-            )))
-            .unwrap();
+    /// Test that loading an archive with `verify_epoch_accounts_hash` recalculates the EAH, and
+    /// fails if it does not match the one in the archive
+    #[test]
+    fn test_bank_from_snapshot_archives_verify_epoch_accounts_hash() {
+        solana_logger::setup();
+        let genesis_config_info = crate::genesis_utils::create_genesis_config(sol_to_lamports(1.));
+        let genesis_config = genesis_config_info.genesis_config;
+        let bank0 = Arc::new(Bank::new_for_tests(&genesis_config));
+        let start_slot = epoch_accounts_hash::calculation_start(&bank0);
+        let stop_slot = epoch_accounts_hash::calculation_stop(&bank0);
 
-        assert!(check_are_snapshots_compatible(
-            &full_snapshot_archive_info,
-            Some(&incremental_snapshot_archive_info)
-        )
-        .is_err());
-    }
+        let start_bank = Arc::new(Bank::new_from_parent(
+            &bank0,
+            &Pubkey::default(),
+            start_slot,
+        ));
+        let pubkey = Pubkey::new_unique();
+        start_bank.deposit(&pubkey, sol_to_lamports(1.)).unwrap();
+        start_bank
+            .deposit(&Pubkey::new_unique(), sol_to_lamports(1.))
+            .unwrap();
+        start_bank.fill_bank_with_ticks_for_tests();
+        start_bank.freeze();
+        let accounts_hash = start_bank.update_accounts_hash();
+        start_bank.squash();
+        start_bank.force_flush_accounts_cache();
+        let epoch_accounts_hash_manager =
+            &bank0.rc.accounts.accounts_db.epoch_accounts_hash_manager;
+        epoch_accounts_hash_manager.set_valid(EpochAccountsHash::new(accounts_hash), start_slot);
+
+        // archive after cleaning, but without shrinking, so the accounts state at the start slot
+        // is still in the storages
+        let archive_bank = |bank: &Bank| {
+            bank.squash();
+            bank.force_flush_accounts_cache();
+            bank.rc.accounts.accounts_db.clean_accounts_for_tests();
+            bank.update_accounts_hash();
+            let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
+            let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+            let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+            let snapshot_storages = bank.get_snapshot_storages(None);
+            let bank_snapshot_info = add_bank_snapshot(
+                &bank_snapshots_dir,
+                bank,
+                &snapshot_storages,
+                SnapshotVersion::default(),
+            )
+            .unwrap();
+            let snapshot_archive_info = package_and_archive_full_snapshot(
+                bank,
+                &bank_snapshot_info,
+                &bank_snapshots_dir,
+                full_snapshot_archives_dir.path(),
+                incremental_snapshot_archives_dir.path(),
+                snapshot_storages,
+                ArchiveFormat::Tar,
+                SnapshotVersion::default(),
+                DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+                DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+                None,
+            )
+            .unwrap();
+            (snapshot_archive_info, full_snapshot_archives_dir)
+        };
+        let load_bank = |snapshot_archive_info: &FullSnapshotArchiveInfo| {
+            let accounts_dir = tempfile::TempDir::new().unwrap();
+            let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
+            bank_from_snapshot_archives(
+                &[PathBuf::from(accounts_dir.path())],
+                bank_snapshots_dir.path(),
+                snapshot_archive_info,
+                None,
+                &genesis_config,
+                &RuntimeConfig::default(),
+                None,
+                None,
+                AccountSecondaryIndexes::default(),
+                false,
+                None,
+                AccountShrinkThreshold::default(),
+                false,
+                false,
+                false,
+                Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
+                None,
+                &Arc::default(),
+                SnapshotLoadOptions {
+                    epoch_accounts_hash_mismatch_is_fatal: false,
+                    verify_epoch_accounts_hash: true,
+                    ..SnapshotLoadOptions::default()
+                },
+            )
+            .map(|(bank, _timings)| bank)
+        };
 
-    /// A test heler function that creates bank snapshot files
-    fn common_create_bank_snapshot_files(
-        bank_snapshots_dir: &Path,
-        min_slot: Slot,
-        max_slot: Slot,
-    ) {
-        for slot in min_slot..max_slot {
-            let snapshot_dir = get_bank_snapshots_dir(bank_snapshots_dir, slot);
-            fs::create_dir_all(&snapshot_dir).unwrap();
+        // before the stop slot, there is nothing to verify
+        let (snapshot_archive_info, _archives_dir) = archive_bank(&start_bank);
+        assert_eq!(
+            load_bank(&snapshot_archive_info).unwrap().slot(),
+            start_slot
+        );
 
-            let snapshot_filename = get_snapshot_file_name(slot);
-            let snapshot_path = snapshot_dir.join(snapshot_filename);
-            File::create(snapshot_path).unwrap();
-        }
+        // the account updated after the start slot is cleaned from the index
+        let stop_bank = Bank::new_from_parent(&start_bank, &Pubkey::default(), stop_slot);
+        stop_bank.deposit(&pubkey, 1).unwrap();
+        stop_bank.fill_bank_with_ticks_for_tests();
+        let (snapshot_archive_info, _archives_dir) = archive_bank(&stop_bank);
+        assert_eq!(load_bank(&snapshot_archive_info).unwrap().slot(), stop_slot);
+
+        // tamper with the EAH, so the one in the archive does not match the accounts state
+        let tampered_hash = Hash::new_unique();
+        epoch_accounts_hash_manager.set_in_flight(stop_slot);
+        epoch_accounts_hash_manager.set_valid(EpochAccountsHash::new(tampered_hash), stop_slot);
+        let (snapshot_archive_info, _archives_dir) = archive_bank(&stop_bank);
+        assert_matches!(
+            load_bank(&snapshot_archive_info),
+            Err(SnapshotError::EpochAccountsHash(EpochAccountsHashError::Mismatch {
+                calculated,
+                expected,
+            })) if calculated == accounts_hash && expected == tampered_hash
+        );
     }
 
     #[test]
-    fn test_get_bank_snapshots() {
+    fn test_self_verify_eah() {
         solana_logger::setup();
-        let temp_snapshots_dir = tempfile::TempDir::new().unwrap();
-        let min_slot = 10;
-        let max_slot = 20;
-        common_create_bank_snapshot_files(temp_snapshots_dir.path(), min_slot, max_slot);
+        let genesis_config_info = crate::genesis_utils::create_genesis_config(sol_to_lamports(1.));
+        let genesis_config = genesis_config_info.genesis_config;
+        let bank0 = Arc::new(Bank::new_for_tests(&genesis_config));
+        let start_slot = epoch_accounts_hash::calculation_start(&bank0);
+        let stop_slot = epoch_accounts_hash::calculation_stop(&bank0);
 
-        let bank_snapshots = get_bank_snapshots(temp_snapshots_dir.path());
-        assert_eq!(bank_snapshots.len() as Slot, max_slot - min_slot);
-    }
+        let start_bank = Arc::new(Bank::new_from_parent(
+            &bank0,
+            &Pubkey::default(),
+            start_slot,
+        ));
+        start_bank
+            .deposit(&Pubkey::new_unique(), sol_to_lamports(1.))
+            .unwrap();
+        start_bank.fill_bank_with_ticks_for_tests();
+        start_bank.freeze();
+        let accounts_hash = start_bank.update_accounts_hash();
+        start_bank.squash();
+        start_bank.force_flush_accounts_cache();
+        let epoch_accounts_hash_manager =
+            &bank0.rc.accounts.accounts_db.epoch_accounts_hash_manager;
+        epoch_accounts_hash_manager.set_valid(EpochAccountsHash::new(accounts_hash), start_slot);
+
+        let stop_bank = Bank::new_from_parent(&start_bank, &Pubkey::default(), stop_slot);
+        stop_bank.deposit(&Pubkey::new_unique(), 1).unwrap();
+        stop_bank.fill_bank_with_ticks_for_tests();
+        // archive without shrinking, so the accounts state at the start slot is kept
+        let archive_bank = || {
+            stop_bank.squash();
+            stop_bank.force_flush_accounts_cache();
+            stop_bank.update_accounts_hash();
+            let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
+            let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+            let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+            let snapshot_storages = stop_bank.get_snapshot_storages(None);
+            let bank_snapshot_info = add_bank_snapshot(
+                &bank_snapshots_dir,
+                &stop_bank,
+                &snapshot_storages,
+                SnapshotVersion::default(),
+            )
+            .unwrap();
+            let snapshot_archive_info = package_and_archive_full_snapshot(
+                &stop_bank,
+                &bank_snapshot_info,
+                &bank_snapshots_dir,
+                full_snapshot_archives_dir.path(),
+                incremental_snapshot_archives_dir.path(),
+                snapshot_storages,
+                ArchiveFormat::Tar,
+                SnapshotVersion::default(),
+                DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+                DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+                None,
+            )
+            .unwrap();
+            (snapshot_archive_info, full_snapshot_archives_dir)
+        };
 
-    #[test]
-    fn test_get_highest_bank_snapshot_post() {
-        solana_logger::setup();
-        let temp_snapshots_dir = tempfile::TempDir::new().unwrap();
-        let min_slot = 99;
-        let max_slot = 123;
-        common_create_bank_snapshot_files(temp_snapshots_dir.path(), min_slot, max_slot);
+        let scratch_dir = tempfile::TempDir::new().unwrap();
+        let temp_dir_factory = TempDirInFactory::new(scratch_dir.path());
+        let (snapshot_archive_info, _archives_dir) = archive_bank();
+        self_verify_eah(
+            snapshot_archive_info.path(),
+            &genesis_config,
+            &temp_dir_factory,
+        )
+        .unwrap();
 
-        let highest_bank_snapshot = get_highest_bank_snapshot_post(temp_snapshots_dir.path());
-        assert!(highest_bank_snapshot.is_some());
-        assert_eq!(highest_bank_snapshot.unwrap().slot, max_slot - 1);
+        // tamper with the EAH, so the one in the archive does not match the accounts state
+        let tampered_hash = Hash::new_unique();
+        epoch_accounts_hash_manager.set_in_flight(stop_slot);
+        epoch_accounts_hash_manager.set_valid(EpochAccountsHash::new(tampered_hash), stop_slot);
+        let (snapshot_archive_info, _archives_dir) = archive_bank();
+        assert_matches!(
+            self_verify_eah(
+                snapshot_archive_info.path(),
+                &genesis_config,
+                &temp_dir_factory,
+            ),
+            Err(SnapshotError::EpochAccountsHash(EpochAccountsHashError::Mismatch {
+                calculated,
+                expected,
+            })) if calculated == accounts_hash && expected == tampered_hash
+        );
     }
 
-    /// A test helper function that creates full and incremental snapshot archive files.  Creates
-    /// full snapshot files in the range (`min_full_snapshot_slot`, `max_full_snapshot_slot`], and
-    /// incremental snapshot files in the range (`min_incremental_snapshot_slot`,
-    /// `max_incremental_snapshot_slot`].  Additionally, "bad" files are created for both full and
-    /// incremental snapshots to ensure the tests properly filter them out.
-    fn common_create_snapshot_archive_files(
-        full_snapshot_archives_dir: &Path,
-        incremental_snapshot_archives_dir: &Path,
-        min_full_snapshot_slot: Slot,
-        max_full_snapshot_slot: Slot,
-        min_incremental_snapshot_slot: Slot,
-        max_incremental_snapshot_slot: Slot,
-    ) {
-        fs::create_dir_all(full_snapshot_archives_dir).unwrap();
-        fs::create_dir_all(incremental_snapshot_archives_dir).unwrap();
-        for full_snapshot_slot in min_full_snapshot_slot..max_full_snapshot_slot {
-            for incremental_snapshot_slot in
-                min_incremental_snapshot_slot..max_incremental_snapshot_slot
-            {
-                let snapshot_filename = format!(
-                    "incremental-snapshot-{}-{}-{}.tar",
-                    full_snapshot_slot,
-                    incremental_snapshot_slot,
-                    Hash::default()
-                );
-                let snapshot_filepath = incremental_snapshot_archives_dir.join(snapshot_filename);
-                File::create(snapshot_filepath).unwrap();
-            }
+    #[test]
+    fn test_bank_from_snapshot_archives_non_consensus_feature_set() {
+        solana_logger::setup();
+        let genesis_config_info = crate::genesis_utils::create_genesis_config(sol_to_lamports(1.));
+        let genesis_config = genesis_config_info.genesis_config;
+        let bank0 = Bank::new_for_tests(&genesis_config);
+        bank0
+            .rc
+            .accounts
+            .accounts_db
+            .epoch_accounts_hash_manager
+            .set_valid(EpochAccountsHash::new(Hash::new_unique()), 0);
+
+        // the EAH feature only affects the bank hash at the calculation stop slot
+        let slot = epoch_accounts_hash::calculation_stop(&bank0);
+        let bank = Bank::new_from_parent(&Arc::new(bank0), &Pubkey::default(), slot);
+        bank.fill_bank_with_ticks_for_tests();
+        bank.squash();
+        bank.force_flush_accounts_cache();
+        bank.update_accounts_hash();
 
-            let snapshot_filename =
-                format!("snapshot-{}-{}.tar", full_snapshot_slot, Hash::default());
-            let snapshot_filepath = full_snapshot_archives_dir.join(snapshot_filename);
-            File::create(snapshot_filepath).unwrap();
+        let accounts_dir = tempfile::TempDir::new().unwrap();
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let snapshot_storages = bank.get_snapshot_storages(None);
+        let bank_snapshot_info = add_bank_snapshot(
+            &bank_snapshots_dir,
+            &bank,
+            &snapshot_storages,
+            SnapshotVersion::default(),
+        )
+        .unwrap();
+        let snapshot_archive_info = package_and_archive_full_snapshot(
+            &bank,
+            &bank_snapshot_info,
+            &bank_snapshots_dir,
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            snapshot_storages,
+            ArchiveFormat::Tar,
+            SnapshotVersion::default(),
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+        )
+        .unwrap();
 
-            // Add in an incremental snapshot with a bad filename and high slot to ensure filename are filtered and sorted correctly
-            let bad_filename = format!(
-                "incremental-snapshot-{}-{}-bad!hash.tar",
-                full_snapshot_slot,
-                max_incremental_snapshot_slot + 1,
-            );
-            let bad_filepath = incremental_snapshot_archives_dir.join(bad_filename);
-            File::create(bad_filepath).unwrap();
-        }
+        let mut feature_set_without_epoch_accounts_hash = FeatureSet::clone(&bank.feature_set);
+        feature_set_without_epoch_accounts_hash
+            .deactivate(&solana_sdk::feature_set::epoch_accounts_hash::id());
+        let feature_set_without_epoch_accounts_hash =
+            Arc::new(feature_set_without_epoch_accounts_hash);
 
-        // Add in a snapshot with a bad filename and high slot to ensure filename are filtered and
-        // sorted correctly
-        let bad_filename = format!("snapshot-{}-bad!hash.tar", max_full_snapshot_slot + 1);
-        let bad_filepath = full_snapshot_archives_dir.join(bad_filename);
-        File::create(bad_filepath).unwrap();
+        let (mut deserialized_bank, _) = bank_from_snapshot_archives(
+            &[PathBuf::from(accounts_dir.path())],
+            bank_snapshots_dir.path(),
+            &snapshot_archive_info,
+            None,
+            &genesis_config,
+            &RuntimeConfig::default(),
+            None,
+            None,
+            AccountSecondaryIndexes::default(),
+            false,
+            None,
+            AccountShrinkThreshold::default(),
+            false,
+            false,
+            false,
+            Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
+            None,
+            &Arc::default(),
+            SnapshotLoadOptions {
+                non_consensus_feature_set: Some(feature_set_without_epoch_accounts_hash.clone()),
+                ..SnapshotLoadOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(deserialized_bank.slot(), slot);
+        assert_eq!(deserialized_bank.hash(), bank.hash());
+        assert!(deserialized_bank.is_startup_verification_failed());
+        assert!(!deserialized_bank.is_startup_verification_complete());
+        // the override is not kept
+        assert_eq!(deserialized_bank.feature_set, bank.feature_set);
+
+        // without the EAH, the calculated hash no longer matches the bank hash
+        assert_ne!(
+            deserialized_bank
+                .calculate_hash_with_feature_set(feature_set_without_epoch_accounts_hash),
+            Some(bank.hash())
+        );
+        let feature_set = deserialized_bank.feature_set.clone();
+        assert_eq!(
+            deserialized_bank.calculate_hash_with_feature_set(feature_set),
+            Some(bank.hash())
+        );
     }
 
     #[test]
-    fn test_get_full_snapshot_archives() {
+    fn test_verify_snapshot_archive_structure() {
         solana_logger::setup();
+        let genesis_config = GenesisConfig::default();
+        let bank = Bank::new_for_tests(&genesis_config);
+        while !bank.is_complete() {
+            bank.register_tick(&Hash::new_unique());
+        }
+
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
         let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
         let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
-        let min_slot = 123;
-        let max_slot = 456;
-        common_create_snapshot_archive_files(
+        let snapshot_archive_info = bank_to_full_snapshot_archive(
+            &bank_snapshots_dir,
+            &bank,
+            None,
             full_snapshot_archives_dir.path(),
             incremental_snapshot_archives_dir.path(),
-            min_slot,
-            max_slot,
-            0,
-            0,
+            ArchiveFormat::TarZstd,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+        )
+        .unwrap();
+        let num_files_in_archives_dir = || {
+            fs::read_dir(full_snapshot_archives_dir.path())
+                .unwrap()
+                .count()
+        };
+
+        assert_eq!(
+            verify_snapshot_archive_structure(snapshot_archive_info.path()).unwrap(),
+            SnapshotArchiveVerification {
+                slot: bank.slot(),
+                hash: bank.get_accounts_hash(),
+                snapshot_type: SnapshotType::FullSnapshot,
+                archive_format: ArchiveFormat::TarZstd,
+            },
         );
+        // nothing is unpacked next to the archive
+        assert_eq!(num_files_in_archives_dir(), 1);
 
-        let snapshot_archives = get_full_snapshot_archives(full_snapshot_archives_dir);
-        assert_eq!(snapshot_archives.len() as Slot, max_slot - min_slot);
+        // the hash in the file name must match the manifest's
+        let mismatched_archive = full_snapshot_archives_dir.path().join(format!(
+            "snapshot-{}-{}.tar.zst",
+            bank.slot(),
+            Hash::new_unique()
+        ));
+        fs::copy(snapshot_archive_info.path(), &mismatched_archive).unwrap();
+        assert!(matches!(
+            verify_snapshot_archive_structure(&mismatched_archive),
+            Err(SnapshotError::MismatchedSlotHash(..))
+        ));
+        assert_eq!(num_files_in_archives_dir(), 2);
     }
 
     #[test]
-    fn test_get_full_snapshot_archives_remote() {
+    fn test_export_archive_metadata_json() {
         solana_logger::setup();
+        let genesis_config = GenesisConfig::default();
+        let bank = Bank::new_for_tests(&genesis_config);
+        while !bank.is_complete() {
+            bank.register_tick(&Hash::new_unique());
+        }
+
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
         let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
         let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
-        let min_slot = 123;
-        let max_slot = 456;
-        common_create_snapshot_archive_files(
-            &full_snapshot_archives_dir.path().join("remote"),
-            &incremental_snapshot_archives_dir.path().join("remote"),
-            min_slot,
-            max_slot,
-            0,
-            0,
-        );
+        let snapshot_archive_info = bank_to_full_snapshot_archive(
+            &bank_snapshots_dir,
+            &bank,
+            None,
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            ArchiveFormat::TarZstd,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+        )
+        .unwrap();
 
-        let snapshot_archives = get_full_snapshot_archives(full_snapshot_archives_dir);
-        assert_eq!(snapshot_archives.len() as Slot, max_slot - min_slot);
-        assert!(snapshot_archives.iter().all(|info| info.is_remote()));
+        let mut json = Vec::new();
+        export_archive_metadata_json(snapshot_archive_info.path(), &mut json).unwrap();
+        let metadata: SnapshotArchiveMetadata = serde_json::from_slice(&json).unwrap();
+        assert_eq!(metadata.slot, bank.slot());
+        assert_eq!(metadata.epoch, bank.epoch());
+        assert_eq!(metadata.base_slot, None);
+        assert_eq!(metadata.bank_hash, bank.hash().to_string());
+        assert_eq!(metadata.accounts_hash, bank.get_accounts_hash().to_string());
+        assert_eq!(metadata.epoch_accounts_hash, None);
+        assert!(metadata.storage_count > 0);
+        let stored_count: usize = bank
+            .get_snapshot_storages(None)
+            .iter()
+            .flatten()
+            .map(|storage| storage.approx_stored_count())
+            .sum();
+        assert_eq!(metadata.account_count, stored_count as u64);
+
+        // a path that is not a snapshot archive is an error
+        assert!(export_archive_metadata_json(bank_snapshots_dir.path(), &mut Vec::new()).is_err());
     }
 
     #[test]
-    fn test_get_incremental_snapshot_archives() {
+    fn test_verify_against_peer_consensus() {
         solana_logger::setup();
+        let genesis_config = GenesisConfig::default();
+        let bank = Bank::new_for_tests(&genesis_config);
+        while !bank.is_complete() {
+            bank.register_tick(&Hash::new_unique());
+        }
+
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
         let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
         let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
-        let min_full_snapshot_slot = 12;
-        let max_full_snapshot_slot = 23;
-        let min_incremental_snapshot_slot = 34;
-        let max_incremental_snapshot_slot = 45;
-        common_create_snapshot_archive_files(
+        let snapshot_archive_info = bank_to_full_snapshot_archive(
+            &bank_snapshots_dir,
+            &bank,
+            None,
             full_snapshot_archives_dir.path(),
             incremental_snapshot_archives_dir.path(),
-            min_full_snapshot_slot,
-            max_full_snapshot_slot,
-            min_incremental_snapshot_slot,
-            max_incremental_snapshot_slot,
-        );
+            ArchiveFormat::Tar,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+        )
+        .unwrap();
 
-        let incremental_snapshot_archives =
-            get_incremental_snapshot_archives(incremental_snapshot_archives_dir);
-        assert_eq!(
-            incremental_snapshot_archives.len() as Slot,
-            (max_full_snapshot_slot - min_full_snapshot_slot)
-                * (max_incremental_snapshot_slot - min_incremental_snapshot_slot)
+        // three of the five peers agree with the archive
+        let peer_hashes = [
+            bank.hash(),
+            Hash::new_unique(),
+            bank.hash(),
+            Hash::new_unique(),
+            bank.hash(),
+        ];
+        assert!(
+            verify_against_peer_consensus(snapshot_archive_info.path(), &peer_hashes, 3).is_ok()
+        );
+        assert_matches!(
+            verify_against_peer_consensus(snapshot_archive_info.path(), &peer_hashes, 4),
+            Err(SnapshotError::PeerConsensusNotReached(hash, 3, 4)) if hash == bank.hash()
         );
     }
 
     #[test]
-    fn test_get_incremental_snapshot_archives_remote() {
+    fn test_is_compatible_with_version() {
         solana_logger::setup();
+        let genesis_config = GenesisConfig::default();
+        let bank = Bank::new_for_tests(&genesis_config);
+        while !bank.is_complete() {
+            bank.register_tick(&Hash::new_unique());
+        }
+
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
         let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
         let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
-        let min_full_snapshot_slot = 12;
-        let max_full_snapshot_slot = 23;
-        let min_incremental_snapshot_slot = 34;
-        let max_incremental_snapshot_slot = 45;
-        common_create_snapshot_archive_files(
-            &full_snapshot_archives_dir.path().join("remote"),
-            &incremental_snapshot_archives_dir.path().join("remote"),
-            min_full_snapshot_slot,
-            max_full_snapshot_slot,
-            min_incremental_snapshot_slot,
-            max_incremental_snapshot_slot,
+        let snapshot_archive_info = bank_to_full_snapshot_archive(
+            &bank_snapshots_dir,
+            &bank,
+            None,
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            ArchiveFormat::Tar,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+        )
+        .unwrap();
+        assert!(
+            is_compatible_with_version(snapshot_archive_info.path(), SnapshotVersion::V1_2_0)
+                .unwrap()
         );
 
-        let incremental_snapshot_archives =
-            get_incremental_snapshot_archives(incremental_snapshot_archives_dir);
-        assert_eq!(
-            incremental_snapshot_archives.len() as Slot,
-            (max_full_snapshot_slot - min_full_snapshot_slot)
-                * (max_incremental_snapshot_slot - min_incremental_snapshot_slot)
-        );
-        assert!(incremental_snapshot_archives
-            .iter()
-            .all(|info| info.is_remote()));
+        // copy the archive, but as if it was written by a newer version
+        let newer_archives_dir = tempfile::TempDir::new().unwrap();
+        let newer_archive_path = newer_archives_dir
+            .path()
+            .join(snapshot_archive_info.path().file_name().unwrap());
+        let mut builder = tar::Builder::new(File::create(&newer_archive_path).unwrap());
+        let mut archive = Archive::new(File::open(snapshot_archive_info.path()).unwrap());
+        for entry in archive.entries().unwrap() {
+            let entry = entry.unwrap();
+            let path = entry.path().unwrap().into_owned();
+            let mut header = entry.header().clone();
+            if path == Path::new("version") {
+                let newer_version = b"1.3.0";
+                header.set_size(newer_version.len() as u64);
+                header.set_cksum();
+                builder
+                    .append_data(&mut header, path, &newer_version[..])
+                    .unwrap();
+            } else {
+                builder.append(&header, entry).unwrap();
+            }
+        }
+        builder.into_inner().unwrap();
+        assert!(!is_compatible_with_version(&newer_archive_path, SnapshotVersion::V1_2_0).unwrap());
     }
 
     #[test]
-    fn test_get_highest_full_snapshot_archive_slot() {
+    fn test_verify_archive_genesis_config() {
         solana_logger::setup();
+        let genesis_config = GenesisConfig::default();
+        let bank = Bank::new_for_tests(&genesis_config);
+        while !bank.is_complete() {
+            bank.register_tick(&Hash::new_unique());
+        }
+
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
         let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
         let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
-        let min_slot = 123;
-        let max_slot = 456;
-        common_create_snapshot_archive_files(
+        let snapshot_archive_info = bank_to_full_snapshot_archive(
+            &bank_snapshots_dir,
+            &bank,
+            None,
             full_snapshot_archives_dir.path(),
             incremental_snapshot_archives_dir.path(),
-            min_slot,
-            max_slot,
-            0,
-            0,
+            ArchiveFormat::Tar,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+        )
+        .unwrap();
+
+        assert!(
+            verify_archive_genesis_config(snapshot_archive_info.path(), &genesis_config).is_ok()
         );
 
-        assert_eq!(
-            get_highest_full_snapshot_archive_slot(full_snapshot_archives_dir.path()),
-            Some(max_slot - 1)
+        let other_genesis_config = GenesisConfig {
+            creation_time: genesis_config.creation_time + 1,
+            ..genesis_config.clone()
+        };
+        assert_matches!(
+            verify_archive_genesis_config(snapshot_archive_info.path(), &other_genesis_config),
+            Err(SnapshotError::MismatchedGenesisConfig(
+                "genesis creation time"
+            ))
+        );
+
+        let other_genesis_config = GenesisConfig {
+            ticks_per_slot: genesis_config.ticks_per_slot + 1,
+            ..genesis_config
+        };
+        assert_matches!(
+            verify_archive_genesis_config(snapshot_archive_info.path(), &other_genesis_config),
+            Err(SnapshotError::MismatchedGenesisConfig("ticks per slot"))
         );
     }
 
     #[test]
-    fn test_get_highest_incremental_snapshot_slot() {
+    fn test_load_for_validation() {
         solana_logger::setup();
+        let genesis_config = GenesisConfig::default();
+        let bank = Bank::new_for_tests(&genesis_config);
+        while !bank.is_complete() {
+            bank.register_tick(&Hash::new_unique());
+        }
+
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
         let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
         let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
-        let min_full_snapshot_slot = 12;
-        let max_full_snapshot_slot = 23;
-        let min_incremental_snapshot_slot = 34;
-        let max_incremental_snapshot_slot = 45;
-        common_create_snapshot_archive_files(
+        let snapshot_archive_info = bank_to_full_snapshot_archive(
+            &bank_snapshots_dir,
+            &bank,
+            None,
             full_snapshot_archives_dir.path(),
             incremental_snapshot_archives_dir.path(),
-            min_full_snapshot_slot,
-            max_full_snapshot_slot,
-            min_incremental_snapshot_slot,
-            max_incremental_snapshot_slot,
-        );
-
-        for full_snapshot_slot in min_full_snapshot_slot..max_full_snapshot_slot {
-            assert_eq!(
-                get_highest_incremental_snapshot_archive_slot(
-                    incremental_snapshot_archives_dir.path(),
-                    full_snapshot_slot
-                ),
-                Some(max_incremental_snapshot_slot - 1)
-            );
-        }
+            ArchiveFormat::TarZstd,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+        )
+        .unwrap();
 
+        let work_dir = tempfile::TempDir::new().unwrap();
+        let report = load_for_validation_in(
+            snapshot_archive_info.path(),
+            &genesis_config,
+            work_dir.path(),
+        )
+        .unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report.slot, bank.slot());
+        assert_eq!(report.bank_hash, bank.hash());
+        assert_eq!(report.capitalization, bank.capitalization());
+
+        // the bank has been dropped, which removes its account storages
+        let num_account_storage_files = fs::read_dir(work_dir.path().join("accounts"))
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path().is_file())
+            .count();
+        assert_eq!(num_account_storage_files, 0);
+
+        // the work dir is created by the factory, and removed afterwards
+        let temp_dir = tempfile::TempDir::new().unwrap();
         assert_eq!(
-            get_highest_incremental_snapshot_archive_slot(
-                incremental_snapshot_archives_dir.path(),
-                max_full_snapshot_slot
-            ),
-            None
+            load_for_validation(
+                snapshot_archive_info.path(),
+                &genesis_config,
+                &TempDirInFactory::new(temp_dir.path()),
+            )
+            .unwrap(),
+            report
         );
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
     }
 
-    fn common_test_purge_old_snapshot_archives(
-        snapshot_names: &[&String],
-        maximum_full_snapshot_archives_to_retain: usize,
-        maximum_incremental_snapshot_archives_to_retain: usize,
-        expected_snapshots: &[&String],
-    ) {
-        let temp_snap_dir = tempfile::TempDir::new().unwrap();
-
-        for snap_name in snapshot_names {
-            let snap_path = temp_snap_dir.path().join(snap_name);
-            let mut _snap_file = File::create(snap_path);
-        }
-        purge_old_snapshot_archives(
-            temp_snap_dir.path(),
-            temp_snap_dir.path(),
-            maximum_full_snapshot_archives_to_retain,
-            maximum_incremental_snapshot_archives_to_retain,
+    #[test]
+    fn test_bank_to_full_snapshot_archive_progress() {
+        solana_logger::setup();
+        let genesis_config = GenesisConfig::default();
+        let bank = Bank::new_for_tests(&genesis_config);
+        bank.store_account(
+            &Pubkey::new_unique(),
+            &AccountSharedData::new(123, 45, &Pubkey::new_unique()),
         );
-
-        let mut retained_snaps = HashSet::new();
-        for entry in fs::read_dir(temp_snap_dir.path()).unwrap() {
-            let entry_path_buf = entry.unwrap().path();
-            let entry_path = entry_path_buf.as_path();
-            let snapshot_name = entry_path
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
-            retained_snaps.insert(snapshot_name);
-        }
-
-        for snap_name in expected_snapshots {
-            assert!(
-                retained_snaps.contains(snap_name.as_str()),
-                "{} not found",
-                snap_name
-            );
+        bank.set_capitalization();
+        while !bank.is_complete() {
+            bank.register_tick(&Hash::new_unique());
         }
-        assert_eq!(retained_snaps.len(), expected_snapshots.len());
-    }
-
-    #[test]
-    fn test_purge_old_full_snapshot_archives() {
-        let snap1_name = format!("snapshot-1-{}.tar.zst", Hash::default());
-        let snap2_name = format!("snapshot-3-{}.tar.zst", Hash::default());
-        let snap3_name = format!("snapshot-50-{}.tar.zst", Hash::default());
-        let snapshot_names = vec![&snap1_name, &snap2_name, &snap3_name];
 
-        // expecting only the newest to be retained
-        let expected_snapshots = vec![&snap3_name];
-        common_test_purge_old_snapshot_archives(
-            &snapshot_names,
-            1,
+        let archive_progress = Arc::new(Mutex::new(Vec::new()));
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let snapshot_archive_info = bank_to_full_snapshot_archive(
+            &bank_snapshots_dir,
+            &bank,
+            None,
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            ArchiveFormat::TarZstd,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
             DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
-            &expected_snapshots,
-        );
+            Some(Box::new({
+                let archive_progress = Arc::clone(&archive_progress);
+                move |progress| archive_progress.lock().unwrap().push(progress)
+            })),
+        )
+        .unwrap();
 
-        // retaining 0, but minimum to retain is 1
-        common_test_purge_old_snapshot_archives(
-            &snapshot_names,
-            0,
-            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
-            &expected_snapshots,
+        let archive_progress = archive_progress.lock().unwrap();
+        assert_matches!(
+            archive_progress.first(),
+            Some(ArchiveProgress::SerializingBank { bytes }) if *bytes > 0
         );
-
-        // retaining 2, expecting the 2 newest to be retained
-        let expected_snapshots = vec![&snap2_name, &snap3_name];
-        common_test_purge_old_snapshot_archives(
-            &snapshot_names,
-            2,
-            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
-            &expected_snapshots,
+        assert!(archive_progress
+            .iter()
+            .any(|progress| matches!(progress, ArchiveProgress::CopyingStorages { .. })));
+        assert_matches!(
+            archive_progress[archive_progress.len() - 2],
+            ArchiveProgress::Compressing { bytes } if bytes > 0
         );
-
-        // retaining 3, all three should be retained
-        let expected_snapshots = vec![&snap1_name, &snap2_name, &snap3_name];
-        common_test_purge_old_snapshot_archives(
-            &snapshot_names,
-            3,
-            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
-            &expected_snapshots,
+        assert_eq!(
+            archive_progress.last(),
+            Some(&ArchiveProgress::Finalizing {
+                bytes: fs::metadata(snapshot_archive_info.path()).unwrap().len(),
+            })
         );
     }
 
-    /// Mimic a running node's behavior w.r.t. purging old snapshot archives.  Take snapshots in a
-    /// loop, and periodically purge old snapshot archives.  After purging, check to make sure the
-    /// snapshot archives on disk are correct.
     #[test]
-    fn test_purge_old_full_snapshot_archives_in_the_loop() {
+    fn test_export_accounts_columnar() {
+        #[derive(Default)]
+        struct TestWriter {
+            columns: AccountsColumns,
+            num_batches: usize,
+        }
+        impl ColumnarAccountsWriter for TestWriter {
+            fn write_batch(&mut self, columns: &AccountsColumns) -> IoResult<()> {
+                assert!(!columns.is_empty());
+                assert!(columns.len() <= EXPORT_ACCOUNTS_BATCH_SIZE);
+                self.columns.pubkeys.extend(&columns.pubkeys);
+                self.columns.lamports.extend(&columns.lamports);
+                self.columns.owners.extend(&columns.owners);
+                self.columns.data_lens.extend(&columns.data_lens);
+                self.columns.slots.extend(&columns.slots);
+                self.num_batches += 1;
+                Ok(())
+            }
+        }
+
+        solana_logger::setup();
+        let genesis_config = GenesisConfig::default();
+        let bank = Bank::new_for_tests(&genesis_config);
+        let pubkey = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        bank.store_account(&pubkey, &AccountSharedData::new(123, 45, &owner));
+        bank.set_capitalization();
+        while !bank.is_complete() {
+            bank.register_tick(&Hash::new_unique());
+        }
+
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
         let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
         let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
-        let maximum_snapshots_to_retain = 5;
-        let starting_slot: Slot = 42;
+        let snapshot_archive_info = bank_to_full_snapshot_archive(
+            &bank_snapshots_dir,
+            &bank,
+            None,
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            ArchiveFormat::Tar,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+        )
+        .unwrap();
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut writer = TestWriter::default();
+        let num_accounts = export_accounts_columnar(
+            snapshot_archive_info.path(),
+            &mut writer,
+            &TempDirInFactory::new(temp_dir.path()),
+        )
+        .unwrap();
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+        let columns = writer.columns;
+        assert_eq!(num_accounts, bank.accounts_sorted_for_tests().len());
+        assert_eq!(columns.len(), num_accounts);
+        assert_eq!(writer.num_batches, 1);
+        for column_len in [
+            columns.lamports.len(),
+            columns.owners.len(),
+            columns.data_lens.len(),
+            columns.slots.len(),
+        ] {
+            assert_eq!(column_len, num_accounts);
+        }
 
-        for slot in (starting_slot..).take(100) {
-            let full_snapshot_archive_file_name =
-                format!("snapshot-{}-{}.tar", slot, Hash::default());
-            let full_snapshot_archive_path = full_snapshot_archives_dir
-                .as_ref()
-                .join(full_snapshot_archive_file_name);
-            File::create(full_snapshot_archive_path).unwrap();
+        let index = columns.pubkeys.iter().position(|p| *p == pubkey).unwrap();
+        assert_eq!(columns.lamports[index], 123);
+        assert_eq!(columns.owners[index], owner);
+        assert_eq!(columns.data_lens[index], 45);
+        assert_eq!(columns.slots[index], bank.slot());
+    }
 
-            // don't purge-and-check until enough snapshot archives have been created
-            if slot < starting_slot + maximum_snapshots_to_retain as Slot {
-                continue;
-            }
+    #[test]
+    fn test_diff_archives() {
+        solana_logger::setup();
+        let genesis_config = GenesisConfig::default();
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let new_snapshot_archive = |bank: &Bank, full_snapshot_archives_dir: &Path| {
+            bank_to_full_snapshot_archive(
+                &bank_snapshots_dir,
+                bank,
+                None,
+                full_snapshot_archives_dir,
+                incremental_snapshot_archives_dir.path(),
+                ArchiveFormat::Tar,
+                DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+                DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+                None,
+            )
+            .unwrap()
+        };
 
-            // purge infrequently, so there will always be snapshot archives to purge
-            if slot % (maximum_snapshots_to_retain as Slot * 2) != 0 {
-                continue;
+        // Both banks are identical, except bank B has one more account
+        let bank_a = Bank::new_for_tests(&genesis_config);
+        let bank_b = Bank::new_for_tests(&genesis_config);
+        for bank in [&bank_a, &bank_b] {
+            while !bank.is_complete() {
+                bank.register_tick(&Hash::default());
             }
+        }
+        let pubkey = Pubkey::new_unique();
+        bank_b.store_account(
+            &pubkey,
+            &AccountSharedData::new(123, 45, &Pubkey::default()),
+        );
+        bank_b.set_capitalization();
 
-            purge_old_snapshot_archives(
-                &full_snapshot_archives_dir,
-                &incremental_snapshot_archives_dir,
-                maximum_snapshots_to_retain,
-                usize::MAX,
+        let full_snapshot_archives_dir_a = tempfile::TempDir::new().unwrap();
+        let full_snapshot_archives_dir_b = tempfile::TempDir::new().unwrap();
+        let snapshot_archive_info_a =
+            new_snapshot_archive(&bank_a, full_snapshot_archives_dir_a.path());
+        let snapshot_archive_info_b =
+            new_snapshot_archive(&bank_b, full_snapshot_archives_dir_b.path());
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let temp_dir_factory = TempDirInFactory::new(temp_dir.path());
+        let diffs = diff_archives(
+            snapshot_archive_info_a.path(),
+            snapshot_archive_info_b.path(),
+            &temp_dir_factory,
+        )
+        .unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_matches!(diffs[0], AccountDiff::OnlyInB { pubkey: p, .. } if p == pubkey);
+
+        let diffs = diff_archives(
+            snapshot_archive_info_b.path(),
+            snapshot_archive_info_a.path(),
+            &temp_dir_factory,
+        )
+        .unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_matches!(diffs[0], AccountDiff::OnlyInA { pubkey: p, .. } if p == pubkey);
+
+        // an archive does not differ from itself
+        let diffs = diff_archives(
+            snapshot_archive_info_a.path(),
+            snapshot_archive_info_a.path(),
+            &temp_dir_factory,
+        )
+        .unwrap();
+        assert!(diffs.is_empty());
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+
+        // diffing in several passes over ranges of pubkeys gives the same, sorted, diffs
+        let bank_c = Bank::new_for_tests(&genesis_config);
+        while !bank_c.is_complete() {
+            bank_c.register_tick(&Hash::default());
+        }
+        for _ in 0..10 {
+            bank_c.store_account(
+                &Pubkey::new_unique(),
+                &AccountSharedData::new(123, 45, &Pubkey::default()),
             );
-            let mut full_snapshot_archives =
-                get_full_snapshot_archives(&full_snapshot_archives_dir);
-            full_snapshot_archives.sort_unstable();
-            assert_eq!(full_snapshot_archives.len(), maximum_snapshots_to_retain);
-            assert_eq!(full_snapshot_archives.last().unwrap().slot(), slot);
-            for (i, full_snapshot_archive) in full_snapshot_archives.iter().rev().enumerate() {
-                assert_eq!(full_snapshot_archive.slot(), slot - i as Slot);
-            }
         }
+        bank_c.set_capitalization();
+        let full_snapshot_archives_dir_c = tempfile::TempDir::new().unwrap();
+        let snapshot_archive_info_c =
+            new_snapshot_archive(&bank_c, full_snapshot_archives_dir_c.path());
+        let diffs = diff_archives(
+            snapshot_archive_info_b.path(),
+            snapshot_archive_info_c.path(),
+            &temp_dir_factory,
+        )
+        .unwrap();
+        assert_eq!(diffs.len(), 11);
+        assert!(diffs
+            .windows(2)
+            .all(|diffs| diffs[0].pubkey() < diffs[1].pubkey()));
+        assert_eq!(
+            diff_archives_in_passes(
+                snapshot_archive_info_b.path(),
+                snapshot_archive_info_c.path(),
+                &temp_dir_factory,
+                1,
+            )
+            .unwrap(),
+            diffs
+        );
     }
 
     #[test]
-    fn test_purge_old_incremental_snapshot_archives() {
+    fn test_owner_histogram() {
         solana_logger::setup();
+        let (genesis_config, _mint_keypair) = create_genesis_config(sol_to_lamports(100.));
+        let bank = Bank::new_for_tests(&genesis_config);
+        while !bank.is_complete() {
+            bank.register_tick(&Hash::new_unique());
+        }
+        let program_id = Pubkey::new_unique();
+        for i in 0..5 {
+            let account = AccountSharedData::new(i + 1, 0, &system_program::id());
+            bank.store_account(&Pubkey::new_unique(), &account);
+        }
+        for i in 0..3 {
+            let account = AccountSharedData::new(i + 1, 10, &program_id);
+            bank.store_account(&Pubkey::new_unique(), &account);
+        }
+        // zero-lamport accounts are not counted
+        bank.store_account(
+            &Pubkey::new_unique(),
+            &AccountSharedData::new(0, 0, &program_id),
+        );
+        bank.set_capitalization();
+
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
         let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
         let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
-        let starting_slot = 100_000;
-
-        let maximum_incremental_snapshot_archives_to_retain =
-            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN;
-        let maximum_full_snapshot_archives_to_retain = DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN;
-
-        let incremental_snapshot_interval = 100;
-        let num_incremental_snapshots_per_full_snapshot =
-            maximum_incremental_snapshot_archives_to_retain * 2;
-        let full_snapshot_interval =
-            incremental_snapshot_interval * num_incremental_snapshots_per_full_snapshot;
-
-        let mut snapshot_filenames = vec![];
-        (starting_slot..)
-            .step_by(full_snapshot_interval)
-            .take(maximum_full_snapshot_archives_to_retain * 2)
-            .for_each(|full_snapshot_slot| {
-                let snapshot_filename =
-                    format!("snapshot-{}-{}.tar", full_snapshot_slot, Hash::default());
-                let snapshot_path = full_snapshot_archives_dir.path().join(&snapshot_filename);
-                File::create(snapshot_path).unwrap();
-                snapshot_filenames.push(snapshot_filename);
-
-                (full_snapshot_slot..)
-                    .step_by(incremental_snapshot_interval)
-                    .take(num_incremental_snapshots_per_full_snapshot)
-                    .skip(1)
-                    .for_each(|incremental_snapshot_slot| {
-                        let snapshot_filename = format!(
-                            "incremental-snapshot-{}-{}-{}.tar",
-                            full_snapshot_slot,
-                            incremental_snapshot_slot,
-                            Hash::default()
-                        );
-                        let snapshot_path = incremental_snapshot_archives_dir
-                            .path()
-                            .join(&snapshot_filename);
-                        File::create(snapshot_path).unwrap();
-                        snapshot_filenames.push(snapshot_filename);
-                    });
-            });
-
-        purge_old_snapshot_archives(
+        let snapshot_archive_info = bank_to_full_snapshot_archive(
+            &bank_snapshots_dir,
+            &bank,
+            None,
             full_snapshot_archives_dir.path(),
             incremental_snapshot_archives_dir.path(),
-            maximum_full_snapshot_archives_to_retain,
-            maximum_incremental_snapshot_archives_to_retain,
-        );
+            ArchiveFormat::Tar,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+        )
+        .unwrap();
 
-        // Ensure correct number of full snapshot archives are purged/retained
-        // NOTE: One extra full snapshot is always kept (the oldest), hence the `+1`
-        let mut remaining_full_snapshot_archives =
-            get_full_snapshot_archives(full_snapshot_archives_dir.path());
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let temp_dir_factory = TempDirInFactory::new(temp_dir.path());
+        let histogram = owner_histogram(snapshot_archive_info.path(), &temp_dir_factory).unwrap();
+        // counting in several passes over ranges of pubkeys gives the same histogram
         assert_eq!(
-            remaining_full_snapshot_archives.len(),
-            maximum_full_snapshot_archives_to_retain,
+            owner_histogram_in_passes(snapshot_archive_info.path(), &temp_dir_factory, 1).unwrap(),
+            histogram
         );
-        remaining_full_snapshot_archives.sort_unstable();
-        let latest_full_snapshot_archive_slot =
-            remaining_full_snapshot_archives.last().unwrap().slot();
-
-        // Ensure correct number of incremental snapshot archives are purged/retained
-        let mut remaining_incremental_snapshot_archives =
-            get_incremental_snapshot_archives(incremental_snapshot_archives_dir.path());
+        let num_system_accounts = bank
+            .get_all_accounts_with_modified_slots()
+            .unwrap()
+            .iter()
+            .filter(|(_pubkey, account, _slot)| {
+                account.lamports() != 0 && account.owner() == &system_program::id()
+            })
+            .count() as u64;
+        assert!(num_system_accounts > 5);
+        assert_eq!(histogram[&system_program::id()], num_system_accounts);
+        assert_eq!(histogram[&program_id], 3);
         assert_eq!(
-            remaining_incremental_snapshot_archives.len(),
-            maximum_incremental_snapshot_archives_to_retain
-                + maximum_full_snapshot_archives_to_retain.saturating_sub(1)
+            histogram.values().sum::<u64>(),
+            bank.get_all_accounts_with_modified_slots()
+                .unwrap()
+                .iter()
+                .filter(|(_pubkey, account, _slot)| account.lamports() != 0)
+                .count() as u64,
         );
-        remaining_incremental_snapshot_archives.sort_unstable();
-        remaining_incremental_snapshot_archives.reverse();
+    }
 
-        // Ensure there exists one incremental snapshot all but the latest full snapshot
-        for i in (1..maximum_full_snapshot_archives_to_retain).rev() {
-            let incremental_snapshot_archive =
-                remaining_incremental_snapshot_archives.pop().unwrap();
+    #[test]
+    fn test_accounts_changed_between() {
+        solana_logger::setup();
+        let genesis_config = GenesisConfig::default();
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let new_snapshot_archive = |bank: &Bank, full_snapshot_archives_dir: &Path| {
+            bank_to_full_snapshot_archive(
+                &bank_snapshots_dir,
+                bank,
+                None,
+                full_snapshot_archives_dir,
+                incremental_snapshot_archives_dir.path(),
+                ArchiveFormat::Tar,
+                DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+                DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+                None,
+            )
+            .unwrap()
+        };
 
-            let expected_base_slot =
-                latest_full_snapshot_archive_slot - (i * full_snapshot_interval) as u64;
-            assert_eq!(incremental_snapshot_archive.base_slot(), expected_base_slot);
-            let expected_slot = expected_base_slot
-                + (full_snapshot_interval - incremental_snapshot_interval) as u64;
-            assert_eq!(incremental_snapshot_archive.slot(), expected_slot);
+        let older_bank = Bank::new_for_tests(&genesis_config);
+        let newer_bank = Bank::new_for_tests(&genesis_config);
+        for bank in [&older_bank, &newer_bank] {
+            while !bank.is_complete() {
+                bank.register_tick(&Hash::default());
+            }
         }
-
-        // Ensure all remaining incremental snapshots are only for the latest full snapshot
-        for incremental_snapshot_archive in &remaining_incremental_snapshot_archives {
-            assert_eq!(
-                incremental_snapshot_archive.base_slot(),
-                latest_full_snapshot_archive_slot
-            );
+        let unchanged_pubkey = Pubkey::new_unique();
+        let modified_pubkey = Pubkey::new_unique();
+        let removed_pubkey = Pubkey::new_unique();
+        let created_pubkey = Pubkey::new_unique();
+        let never_existed_pubkey = Pubkey::new_unique();
+        let unchanged_account = AccountSharedData::new(1, 2, &Pubkey::default());
+        let modified_account = AccountSharedData::new(3, 4, &Pubkey::default());
+        let created_account = AccountSharedData::new(5, 6, &Pubkey::default());
+        older_bank.store_account(&unchanged_pubkey, &unchanged_account);
+        older_bank.store_account(
+            &modified_pubkey,
+            &AccountSharedData::new(7, 8, &Pubkey::default()),
+        );
+        older_bank.store_account(
+            &removed_pubkey,
+            &AccountSharedData::new(9, 10, &Pubkey::default()),
+        );
+        newer_bank.store_account(&unchanged_pubkey, &unchanged_account);
+        newer_bank.store_account(&modified_pubkey, &modified_account);
+        newer_bank.store_account(&created_pubkey, &created_account);
+        newer_bank.store_account(&never_existed_pubkey, &AccountSharedData::default());
+        for bank in [&older_bank, &newer_bank] {
+            bank.set_capitalization();
         }
 
-        // Ensure the remaining incremental snapshots are at the right slot
-        let expected_remaing_incremental_snapshot_archive_slots =
-            (latest_full_snapshot_archive_slot..)
-                .step_by(incremental_snapshot_interval)
-                .take(num_incremental_snapshots_per_full_snapshot)
-                .skip(
-                    num_incremental_snapshots_per_full_snapshot
-                        - maximum_incremental_snapshot_archives_to_retain,
-                )
-                .collect::<HashSet<_>>();
+        let full_snapshot_archives_dir_older = tempfile::TempDir::new().unwrap();
+        let full_snapshot_archives_dir_newer = tempfile::TempDir::new().unwrap();
+        let older_snapshot_archive_info =
+            new_snapshot_archive(&older_bank, full_snapshot_archives_dir_older.path());
+        let newer_snapshot_archive_info =
+            new_snapshot_archive(&newer_bank, full_snapshot_archives_dir_newer.path());
 
-        let actual_remaining_incremental_snapshot_archive_slots =
-            remaining_incremental_snapshot_archives
-                .iter()
-                .map(|snapshot| snapshot.slot())
-                .collect::<HashSet<_>>();
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let temp_dir_factory = TempDirInFactory::new(temp_dir.path());
+        let changed_accounts = accounts_changed_between(
+            older_snapshot_archive_info.path(),
+            newer_snapshot_archive_info.path(),
+            &temp_dir_factory,
+        )
+        .unwrap();
+        let mut expected = vec![
+            (modified_pubkey, modified_account),
+            (removed_pubkey, AccountSharedData::default()),
+            (created_pubkey, created_account),
+        ];
+        expected.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(changed_accounts, expected);
+
+        // comparing in several passes over ranges of pubkeys gives the same, sorted, accounts
         assert_eq!(
-            actual_remaining_incremental_snapshot_archive_slots,
-            expected_remaing_incremental_snapshot_archive_slots
+            accounts_changed_between_in_passes(
+                older_snapshot_archive_info.path(),
+                newer_snapshot_archive_info.path(),
+                &temp_dir_factory,
+                1,
+            )
+            .unwrap(),
+            expected
         );
+
+        // an archive does not differ from itself
+        let changed_accounts = accounts_changed_between(
+            older_snapshot_archive_info.path(),
+            older_snapshot_archive_info.path(),
+            &temp_dir_factory,
+        )
+        .unwrap();
+        assert!(changed_accounts.is_empty());
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
     }
 
     #[test]
-    fn test_purge_all_incremental_snapshot_archives_when_no_full_snapshot_archives() {
+    fn test_calculate_incremental_accounts_hash() {
+        solana_logger::setup();
+        let collector = Pubkey::new_unique();
+        let (genesis_config, mint_keypair) = create_genesis_config(sol_to_lamports(1_000_000.));
+        let bank0 = Arc::new(Bank::new_for_tests(&genesis_config));
+        bank0
+            .transfer(sol_to_lamports(1.), &mint_keypair, &Pubkey::new_unique())
+            .unwrap();
+        let removed_pubkey = Pubkey::new_unique();
+        bank0.store_account(
+            &removed_pubkey,
+            &AccountSharedData::new(1, 0, &Pubkey::default()),
+        );
+        bank0.fill_bank_with_ticks_for_tests();
+
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
         let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
         let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let full_snapshot_slot = bank0.slot();
+        bank_to_full_snapshot_archive(
+            bank_snapshots_dir.path(),
+            &bank0,
+            None,
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            ArchiveFormat::Tar,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+        )
+        .unwrap();
 
-        for snapshot_filenames in [
-            format!("incremental-snapshot-100-120-{}.tar", Hash::default()),
-            format!("incremental-snapshot-100-140-{}.tar", Hash::default()),
-            format!("incremental-snapshot-100-160-{}.tar", Hash::default()),
-            format!("incremental-snapshot-100-180-{}.tar", Hash::default()),
-            format!("incremental-snapshot-200-220-{}.tar", Hash::default()),
-            format!("incremental-snapshot-200-240-{}.tar", Hash::default()),
-            format!("incremental-snapshot-200-260-{}.tar", Hash::default()),
-            format!("incremental-snapshot-200-280-{}.tar", Hash::default()),
-        ] {
-            let snapshot_path = incremental_snapshot_archives_dir
-                .path()
-                .join(&snapshot_filenames);
-            File::create(snapshot_path).unwrap();
-        }
+        let bank1 = Arc::new(Bank::new_from_parent(&bank0, &collector, 1));
+        bank1
+            .transfer(sol_to_lamports(2.), &mint_keypair, &Pubkey::new_unique())
+            .unwrap();
+        bank1.fill_bank_with_ticks_for_tests();
+        let bank2 = Arc::new(Bank::new_from_parent(&bank1, &collector, 2));
+        bank2
+            .transfer(sol_to_lamports(3.), &mint_keypair, &Pubkey::new_unique())
+            .unwrap();
+        bank2.store_account(&removed_pubkey, &AccountSharedData::default());
+        bank2.fill_bank_with_ticks_for_tests();
 
-        purge_old_snapshot_archives(
+        let incremental_snapshot_archive_info = bank_to_incremental_snapshot_archive(
+            bank_snapshots_dir.path(),
+            &bank2,
+            full_snapshot_slot,
+            None,
             full_snapshot_archives_dir.path(),
             incremental_snapshot_archives_dir.path(),
-            usize::MAX,
-            usize::MAX,
-        );
+            ArchiveFormat::Tar,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+        )
+        .unwrap();
+
+        // hash the latest version of every account in the incremental archive's storages
+        let unpack_dir = tempfile::TempDir::new().unwrap();
+        let storage = unarchive_snapshot_storage(
+            incremental_snapshot_archive_info.path(),
+            ArchiveFormat::Tar,
+            "test untar",
+            &unpack_dir,
+        )
+        .unwrap();
+        let mut latest = HashMap::<Pubkey, (Slot, StoredMetaWriteVersion, Hash)>::new();
+        for entry in storage.iter() {
+            let slot = *entry.key();
+            for store in entry.value().read().unwrap().values() {
+                store.accounts.account_iter().for_each(|stored_account| {
+                    let version = (slot, stored_account.meta.write_version);
+                    let is_latest = latest
+                        .get(&stored_account.meta.pubkey)
+                        .map_or(true, |(slot, write_version, _)| {
+                            (*slot, *write_version) < version
+                        });
+                    if is_latest {
+                        latest.insert(
+                            stored_account.meta.pubkey,
+                            (version.0, version.1, *stored_account.hash),
+                        );
+                    }
+                });
+            }
+        }
+        assert!(latest.contains_key(&removed_pubkey));
+        let mut hashes: Vec<_> = latest
+            .into_iter()
+            .map(|(pubkey, (_slot, _write_version, hash))| (pubkey, hash))
+            .collect();
+        hashes.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        let (expected, _hash_total) =
+            AccountsHash::calculate_hash(vec![hashes.into_iter().map(|(_, hash)| hash).collect()]);
 
-        let remaining_incremental_snapshot_archives =
-            get_incremental_snapshot_archives(incremental_snapshot_archives_dir.path());
-        assert!(remaining_incremental_snapshot_archives.is_empty());
+        let accounts_db = &bank2.rc.accounts.accounts_db;
+        assert_eq!(
+            accounts_db.calculate_incremental_accounts_hash(
+                full_snapshot_slot,
+                bank2.slot(),
+                &bank2.ancestors,
+            ),
+            expected,
+        );
+        assert_ne!(
+            accounts_db.calculate_incremental_accounts_hash(
+                bank1.slot(),
+                bank2.slot(),
+                &bank2.ancestors,
+            ),
+            expected,
+        );
     }
 
-    /// Test roundtrip of bank to a full snapshot, then back again.  This test creates the simplest
-    /// bank possible, so the contents of the snapshot archive will be quite minimal.
     #[test]
-    fn test_roundtrip_bank_to_and_from_full_snapshot_simple() {
+    fn test_validate_archive_structure() {
         solana_logger::setup();
         let genesis_config = GenesisConfig::default();
-        let original_bank = Bank::new_for_tests(&genesis_config);
-
-        while !original_bank.is_complete() {
-            original_bank.register_tick(&Hash::new_unique());
+        let bank = Bank::new_for_tests(&genesis_config);
+        while !bank.is_complete() {
+            bank.register_tick(&Hash::new_unique());
         }
 
-        let accounts_dir = tempfile::TempDir::new().unwrap();
         let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
         let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
         let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
-        let snapshot_archive_format = ArchiveFormat::Tar;
-
         let snapshot_archive_info = bank_to_full_snapshot_archive(
             &bank_snapshots_dir,
-            &original_bank,
+            &bank,
             None,
             full_snapshot_archives_dir.path(),
             incremental_snapshot_archives_dir.path(),
-            snapshot_archive_format,
+            ArchiveFormat::TarZstd,
             DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
             DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
-        )
-        .unwrap();
-
-        let (roundtrip_bank, _) = bank_from_snapshot_archives(
-            &[PathBuf::from(accounts_dir.path())],
-            bank_snapshots_dir.path(),
-            &snapshot_archive_info,
-            None,
-            &genesis_config,
-            &RuntimeConfig::default(),
-            None,
-            None,
-            AccountSecondaryIndexes::default(),
-            false,
-            None,
-            AccountShrinkThreshold::default(),
-            false,
-            false,
-            false,
-            Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
             None,
-            &Arc::default(),
         )
         .unwrap();
 
-        assert_eq!(original_bank, roundtrip_bank);
+        let report = validate_archive_structure(snapshot_archive_info.path()).unwrap();
+        assert_eq!(report.slot, bank.slot());
+        assert_eq!(report.base_slot, None);
+        assert_eq!(report.archive_format, ArchiveFormat::TarZstd);
+        assert!(report.num_storages > 0);
+        assert!(report.num_entries > report.num_storages);
+
+        // build an archive that is missing the manifest
+        let slot = 42;
+        let archive_path = build_full_snapshot_archive_path(
+            full_snapshot_archives_dir.path(),
+            slot,
+            &Hash::new_unique(),
+            ArchiveFormat::Tar,
+        );
+        let mut builder = tar::Builder::new(File::create(&archive_path).unwrap());
+        for (path, contents) in [
+            ("version", SnapshotVersion::default().as_str().as_bytes()),
+            ("snapshots/status_cache", &[0; 8][..]),
+            ("accounts/42.0", &[0; 8][..]),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, contents).unwrap();
+        }
+        builder.into_inner().unwrap();
+
+        assert_matches!(
+            validate_archive_structure(&archive_path),
+            Err(SnapshotError::ArchiveStructure(
+                ArchiveStructureError::MissingEntry(path)
+            )) if path == get_archived_manifest_path(slot)
+        );
     }
 
     /// Test roundtrip of bank to a full snapshot, then back again.  This test is more involved
@@ -3395,6 +8227,7 @@ mod tests {
             snapshot_archive_format,
             DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
             DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
         )
         .unwrap();
 
@@ -3417,6 +8250,7 @@ mod tests {
             Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
             None,
             &Arc::default(),
+            SnapshotLoadOptions::default(),
         )
         .unwrap();
 
@@ -3488,6 +8322,7 @@ mod tests {
             snapshot_archive_format,
             DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
             DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
         )
         .unwrap();
 
@@ -3528,6 +8363,7 @@ mod tests {
             snapshot_archive_format,
             DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
             DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
         )
         .unwrap();
 
@@ -3550,12 +8386,248 @@ mod tests {
             Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
             None,
             &Arc::default(),
+            SnapshotLoadOptions::default(),
         )
         .unwrap();
 
         assert_eq!(*bank4, roundtrip_bank);
     }
 
+    /// Test that loading an incremental snapshot on top of a full snapshot at a different slot
+    /// than its base slot fails with a descriptive error
+    #[test]
+    fn test_bank_from_snapshot_archives_mismatched_base_slot() {
+        solana_logger::setup();
+        let collector = Pubkey::new_unique();
+        let (genesis_config, _mint_keypair) = create_genesis_config(sol_to_lamports(1_000_000.));
+        let mut bank = Arc::new(Bank::new_for_tests(&genesis_config));
+        while !bank.is_complete() {
+            bank.register_tick(&Hash::new_unique());
+        }
+
+        let accounts_dir = tempfile::TempDir::new().unwrap();
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let snapshot_archive_format = ArchiveFormat::Tar;
+
+        // full snapshots at slots 1 and 2, and an incremental snapshot at slot 3 based on slot 2
+        let mut full_snapshot_archive_infos = Vec::new();
+        for slot in 1..=3 {
+            bank = Arc::new(Bank::new_from_parent(&bank, &collector, slot));
+            while !bank.is_complete() {
+                bank.register_tick(&Hash::new_unique());
+            }
+            if slot < 3 {
+                full_snapshot_archive_infos.push(
+                    bank_to_full_snapshot_archive(
+                        bank_snapshots_dir.path(),
+                        &bank,
+                        None,
+                        full_snapshot_archives_dir.path(),
+                        incremental_snapshot_archives_dir.path(),
+                        snapshot_archive_format,
+                        DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+                        DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+                        None,
+                    )
+                    .unwrap(),
+                );
+            }
+        }
+        let incremental_snapshot_archive_info = bank_to_incremental_snapshot_archive(
+            bank_snapshots_dir.path(),
+            &bank,
+            2,
+            None,
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            snapshot_archive_format,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+        )
+        .unwrap();
+
+        let result = bank_from_snapshot_archives(
+            &[PathBuf::from(accounts_dir.path())],
+            bank_snapshots_dir.path(),
+            &full_snapshot_archive_infos[0],
+            Some(&incremental_snapshot_archive_info),
+            &genesis_config,
+            &RuntimeConfig::default(),
+            None,
+            None,
+            AccountSecondaryIndexes::default(),
+            false,
+            None,
+            AccountShrinkThreshold::default(),
+            false,
+            false,
+            false,
+            Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
+            None,
+            &Arc::default(),
+            SnapshotLoadOptions::default(),
+        );
+        assert_matches!(
+            result,
+            Err(SnapshotError::MismatchedBaseSlot {
+                expected: 1,
+                found: 2
+            })
+        );
+    }
+
+    /// Test applying the account deltas from an incremental snapshot onto an accounts db loaded
+    /// from the full snapshot produces the same accounts as loading both snapshots
+    #[test]
+    fn test_apply_incremental_delta() {
+        solana_logger::setup();
+        let collector = Pubkey::new_unique();
+        let key1 = Keypair::new();
+        let key2 = Keypair::new();
+        let key3 = Keypair::new();
+
+        let (genesis_config, mint_keypair) = create_genesis_config(sol_to_lamports(1_000_000.));
+        let bank0 = Arc::new(Bank::new_for_tests(&genesis_config));
+        bank0
+            .transfer(sol_to_lamports(1.), &mint_keypair, &key1.pubkey())
+            .unwrap();
+        while !bank0.is_complete() {
+            bank0.register_tick(&Hash::new_unique());
+        }
+
+        let slot = 1;
+        let bank1 = Arc::new(Bank::new_from_parent(&bank0, &collector, slot));
+        bank1
+            .transfer(sol_to_lamports(2.), &mint_keypair, &key2.pubkey())
+            .unwrap();
+        while !bank1.is_complete() {
+            bank1.register_tick(&Hash::new_unique());
+        }
+
+        let accounts_dir = tempfile::TempDir::new().unwrap();
+        let other_accounts_dir = tempfile::TempDir::new().unwrap();
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let snapshot_archive_format = ArchiveFormat::Tar;
+
+        let full_snapshot_slot = slot;
+        let full_snapshot_archive_info = bank_to_full_snapshot_archive(
+            bank_snapshots_dir.path(),
+            &bank1,
+            None,
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            snapshot_archive_format,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+        )
+        .unwrap();
+
+        let slot = slot + 1;
+        let bank2 = Arc::new(Bank::new_from_parent(&bank1, &collector, slot));
+        bank2
+            .transfer(sol_to_lamports(3.), &mint_keypair, &key1.pubkey())
+            .unwrap();
+        while !bank2.is_complete() {
+            bank2.register_tick(&Hash::new_unique());
+        }
+
+        let slot = slot + 1;
+        let bank3 = Arc::new(Bank::new_from_parent(&bank2, &collector, slot));
+        bank3
+            .transfer(sol_to_lamports(4.), &mint_keypair, &key3.pubkey())
+            .unwrap();
+        while !bank3.is_complete() {
+            bank3.register_tick(&Hash::new_unique());
+        }
+
+        let incremental_snapshot_archive_info = bank_to_incremental_snapshot_archive(
+            bank_snapshots_dir.path(),
+            &bank3,
+            full_snapshot_slot,
+            None,
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            snapshot_archive_format,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+        )
+        .unwrap();
+
+        let load_bank =
+            |accounts_dir: &Path, incremental_info: Option<&IncrementalSnapshotArchiveInfo>| {
+                bank_from_snapshot_archives(
+                    &[PathBuf::from(accounts_dir)],
+                    bank_snapshots_dir.path(),
+                    &full_snapshot_archive_info,
+                    incremental_info,
+                    &genesis_config,
+                    &RuntimeConfig::default(),
+                    None,
+                    None,
+                    AccountSecondaryIndexes::default(),
+                    false,
+                    None,
+                    AccountShrinkThreshold::default(),
+                    false,
+                    false,
+                    false,
+                    Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
+                    None,
+                    &Arc::default(),
+                    SnapshotLoadOptions::default(),
+                )
+                .unwrap()
+                .0
+            };
+        let applied_bank = load_bank(accounts_dir.path(), None);
+        let expected_bank = load_bank(
+            other_accounts_dir.path(),
+            Some(&incremental_snapshot_archive_info),
+        );
+
+        let accounts_db = &applied_bank.rc.accounts.accounts_db;
+        let report = accounts_db
+            .apply_incremental_delta(&incremental_snapshot_archive_info)
+            .unwrap();
+        assert_eq!(report.base_slot, full_snapshot_slot);
+        assert_eq!(report.slot, bank3.slot());
+        assert_eq!(report.slots, vec![bank2.slot(), bank3.slot()]);
+        assert!(report.num_accounts > 0);
+
+        let ancestors = Ancestors::default();
+        for pubkey in [
+            mint_keypair.pubkey(),
+            key1.pubkey(),
+            key2.pubkey(),
+            key3.pubkey(),
+        ] {
+            assert_eq!(
+                accounts_db
+                    .load_with_fixed_root(&ancestors, &pubkey)
+                    .map(|(account, _slot)| account),
+                expected_bank
+                    .rc
+                    .accounts
+                    .accounts_db
+                    .load_with_fixed_root(&ancestors, &pubkey)
+                    .map(|(account, _slot)| account),
+            );
+        }
+
+        // the accounts db is no longer at the incremental snapshot's base slot
+        assert_matches!(
+            accounts_db.apply_incremental_delta(&incremental_snapshot_archive_info),
+            Err(SnapshotError::MismatchedBaseSlot { .. })
+        );
+    }
+
     /// Test rebuilding bank from the latest snapshot archives
     #[test]
     fn test_bank_from_latest_snapshot_archives() {
@@ -3611,6 +8683,7 @@ mod tests {
             snapshot_archive_format,
             DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
             DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
         )
         .unwrap();
 
@@ -3651,6 +8724,7 @@ mod tests {
             snapshot_archive_format,
             DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
             DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
         )
         .unwrap();
 
@@ -3673,12 +8747,130 @@ mod tests {
             Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
             None,
             &Arc::default(),
+            None,
+            DuplicateIncrementalSnapshotArchivePolicy::default(),
+            SnapshotLoadOptions::default(),
         )
         .unwrap();
 
         assert_eq!(deserialized_bank, *bank4);
     }
 
+    /// Test loading the bank as of a slot, from a full snapshot and two incremental snapshots, only
+    /// uses the archives at that slot, and fails rather than loading an earlier slot
+    #[test]
+    fn test_bank_from_latest_snapshot_archives_as_of_slot() {
+        solana_logger::setup();
+        let collector = Pubkey::new_unique();
+        let key = Keypair::new();
+
+        let (genesis_config, mint_keypair) = create_genesis_config(sol_to_lamports(1_000_000.));
+        let mut banks = vec![Arc::new(Bank::new_for_tests(&genesis_config))];
+        while !banks[0].is_complete() {
+            banks[0].register_tick(&Hash::new_unique());
+        }
+        for slot in 1..=3 {
+            let bank = Arc::new(Bank::new_from_parent(
+                banks.last().unwrap(),
+                &collector,
+                slot,
+            ));
+            bank.transfer(sol_to_lamports(1.), &mint_keypair, &key.pubkey())
+                .unwrap();
+            while !bank.is_complete() {
+                bank.register_tick(&Hash::new_unique());
+            }
+            banks.push(bank);
+        }
+
+        let accounts_dir = tempfile::TempDir::new().unwrap();
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let snapshot_archive_format = ArchiveFormat::Tar;
+
+        let full_snapshot_slot = 1;
+        bank_to_full_snapshot_archive(
+            &bank_snapshots_dir,
+            &banks[1],
+            None,
+            &full_snapshot_archives_dir,
+            &incremental_snapshot_archives_dir,
+            snapshot_archive_format,
+            DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
+        )
+        .unwrap();
+        for bank in &banks[2..] {
+            bank_to_incremental_snapshot_archive(
+                &bank_snapshots_dir,
+                bank,
+                full_snapshot_slot,
+                None,
+                &full_snapshot_archives_dir,
+                &incremental_snapshot_archives_dir,
+                snapshot_archive_format,
+                DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+                DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+                None,
+            )
+            .unwrap();
+        }
+
+        let load_as_of_slot = |as_of_slot| {
+            bank_from_latest_snapshot_archives(
+                &bank_snapshots_dir,
+                &full_snapshot_archives_dir,
+                &incremental_snapshot_archives_dir,
+                &[accounts_dir.as_ref().to_path_buf()],
+                &genesis_config,
+                &RuntimeConfig::default(),
+                None,
+                None,
+                AccountSecondaryIndexes::default(),
+                false,
+                None,
+                AccountShrinkThreshold::default(),
+                false,
+                false,
+                false,
+                Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
+                None,
+                &Arc::default(),
+                Some(as_of_slot),
+                DuplicateIncrementalSnapshotArchivePolicy::default(),
+                SnapshotLoadOptions::default(),
+            )
+        };
+
+        // the second incremental snapshot, at slot 3, is ignored
+        let (deserialized_bank, full_snapshot_archive_info, incremental_snapshot_archive_info) =
+            load_as_of_slot(2).unwrap();
+        assert_eq!(full_snapshot_archive_info.slot(), full_snapshot_slot);
+        assert_eq!(incremental_snapshot_archive_info.unwrap().slot(), 2);
+        assert_eq!(deserialized_bank, *banks[2]);
+
+        // only the full snapshot is loaded as of its own slot
+        let (deserialized_bank, full_snapshot_archive_info, incremental_snapshot_archive_info) =
+            load_as_of_slot(full_snapshot_slot).unwrap();
+        assert_eq!(full_snapshot_archive_info.slot(), full_snapshot_slot);
+        assert!(incremental_snapshot_archive_info.is_none());
+        assert_eq!(deserialized_bank, *banks[1]);
+
+        assert_matches!(
+            load_as_of_slot(0),
+            Err(SnapshotError::NoSnapshotArchivesAsOfSlot(0))
+        );
+        assert_matches!(
+            load_as_of_slot(4),
+            Err(SnapshotError::NoSnapshotArchiveAtSlot {
+                requested: 4,
+                highest: 3
+            })
+        );
+    }
+
     /// Test that cleaning works well in the edge cases of zero-lamport accounts and snapshots.
     /// Here's the scenario:
     ///
@@ -3752,6 +8944,7 @@ mod tests {
             snapshot_archive_format,
             DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
             DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
         )
         .unwrap();
 
@@ -3793,6 +8986,7 @@ mod tests {
             snapshot_archive_format,
             DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
             DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
         )
         .unwrap();
         let (deserialized_bank, _) = bank_from_snapshot_archives(
@@ -3814,6 +9008,7 @@ mod tests {
             Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
             None,
             &Arc::default(),
+            SnapshotLoadOptions::default(),
         )
         .unwrap();
         assert_eq!(
@@ -3857,6 +9052,7 @@ mod tests {
             snapshot_archive_format,
             DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
             DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
         )
         .unwrap();
 
@@ -3879,6 +9075,7 @@ mod tests {
             Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
             None,
             &Arc::default(),
+            SnapshotLoadOptions::default(),
         )
         .unwrap();
         assert_eq!(
@@ -3924,6 +9121,7 @@ mod tests {
             snapshot_archive_format,
             DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
             DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
         )
         .unwrap();
 
@@ -3946,6 +9144,7 @@ mod tests {
             snapshot_archive_format,
             DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
             DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            None,
         )
         .unwrap();
 
@@ -1,14 +1,18 @@
 use {
-    std::{fmt, str::FromStr},
+    solana_sdk::hash::hash,
+    std::{borrow::Cow, fmt, str::FromStr},
     strum::Display,
 };
 
-pub const SUPPORTED_ARCHIVE_COMPRESSION: &[&str] = &["bz2", "gzip", "zstd", "lz4", "tar", "none"];
+pub const SUPPORTED_ARCHIVE_COMPRESSION: &[&str] =
+    &["bz2", "gzip", "zstd", "zstd-dict", "lz4", "tar", "none"];
 pub const DEFAULT_ARCHIVE_COMPRESSION: &str = "zstd";
 
 pub const TAR_BZIP2_EXTENSION: &str = "tar.bz2";
 pub const TAR_GZIP_EXTENSION: &str = "tar.gz";
 pub const TAR_ZSTD_EXTENSION: &str = "tar.zst";
+pub const TAR_ZSTD_DICT_EXTENSION_PREFIX: &str = "tar.dict-";
+pub const TAR_ZSTD_DICT_EXTENSION_SUFFIX: &str = ".zst";
 pub const TAR_LZ4_EXTENSION: &str = "tar.lz4";
 pub const TAR_EXTENSION: &str = "tar";
 
@@ -18,27 +22,61 @@ pub enum ArchiveFormat {
     TarBzip2,
     TarGzip,
     TarZstd,
+    /// Zstd compressed with a dictionary.  The dictionary is not stored in the archive, so it
+    /// must be given separately to both create and unpack the archive.  Its ID is part of the
+    /// file extension, so an archive is only unpacked with the dictionary it was created with.
+    TarZstdWithDict(ZstdDictionaryId),
     TarLz4,
     Tar,
 }
 
+/// Identifies the dictionary of an `ArchiveFormat::TarZstdWithDict` archive, see
+/// `zstd_dictionary_id()`
+pub type ZstdDictionaryId = u32;
+
+/// Get the ID of a zstd dictionary, derived from a hash of its contents
+pub fn zstd_dictionary_id(zstd_dictionary: &[u8]) -> ZstdDictionaryId {
+    let hash = hash(zstd_dictionary).to_bytes();
+    ZstdDictionaryId::from_le_bytes(hash[..4].try_into().unwrap())
+}
+
 impl ArchiveFormat {
     /// Get the file extension for the ArchiveFormat
-    pub fn extension(&self) -> &str {
+    pub fn extension(&self) -> Cow<'static, str> {
         match self {
-            ArchiveFormat::TarBzip2 => TAR_BZIP2_EXTENSION,
-            ArchiveFormat::TarGzip => TAR_GZIP_EXTENSION,
-            ArchiveFormat::TarZstd => TAR_ZSTD_EXTENSION,
-            ArchiveFormat::TarLz4 => TAR_LZ4_EXTENSION,
-            ArchiveFormat::Tar => TAR_EXTENSION,
+            ArchiveFormat::TarBzip2 => TAR_BZIP2_EXTENSION.into(),
+            ArchiveFormat::TarGzip => TAR_GZIP_EXTENSION.into(),
+            ArchiveFormat::TarZstd => TAR_ZSTD_EXTENSION.into(),
+            ArchiveFormat::TarZstdWithDict(zstd_dictionary_id) => format!(
+                "{}{:08x}{}",
+                TAR_ZSTD_DICT_EXTENSION_PREFIX, zstd_dictionary_id, TAR_ZSTD_DICT_EXTENSION_SUFFIX
+            )
+            .into(),
+            ArchiveFormat::TarLz4 => TAR_LZ4_EXTENSION.into(),
+            ArchiveFormat::Tar => TAR_EXTENSION.into(),
         }
     }
 
+    /// Set the dictionary of a `TarZstdWithDict` format; other formats are returned as is
+    #[must_use]
+    pub fn with_zstd_dictionary(self, zstd_dictionary: &[u8]) -> Self {
+        match self {
+            ArchiveFormat::TarZstdWithDict(_) => {
+                ArchiveFormat::TarZstdWithDict(zstd_dictionary_id(zstd_dictionary))
+            }
+            archive_format => archive_format,
+        }
+    }
+
+    /// Parse the archive format given on the command line
+    ///
+    /// The dictionary of `zstd-dict` is not known yet, so set it with `with_zstd_dictionary()`.
     pub fn from_cli_arg(archive_format_str: &str) -> Option<ArchiveFormat> {
         match archive_format_str {
             "bz2" => Some(ArchiveFormat::TarBzip2),
             "gzip" => Some(ArchiveFormat::TarGzip),
             "zstd" => Some(ArchiveFormat::TarZstd),
+            "zstd-dict" => Some(ArchiveFormat::TarZstdWithDict(ZstdDictionaryId::default())),
             "lz4" => Some(ArchiveFormat::TarLz4),
             "tar" | "none" => Some(ArchiveFormat::Tar),
             _ => None,
@@ -58,7 +96,18 @@ impl TryFrom<&str> for ArchiveFormat {
             TAR_ZSTD_EXTENSION => Ok(ArchiveFormat::TarZstd),
             TAR_LZ4_EXTENSION => Ok(ArchiveFormat::TarLz4),
             TAR_EXTENSION => Ok(ArchiveFormat::Tar),
-            _ => Err(ParseError::InvalidExtension(extension.to_string())),
+            _ => extension
+                .strip_prefix(TAR_ZSTD_DICT_EXTENSION_PREFIX)
+                .and_then(|extension| extension.strip_suffix(TAR_ZSTD_DICT_EXTENSION_SUFFIX))
+                .filter(|zstd_dictionary_id| {
+                    zstd_dictionary_id.len() == 8
+                        && zstd_dictionary_id.chars().all(|c| c.is_ascii_hexdigit())
+                })
+                .and_then(|zstd_dictionary_id| {
+                    ZstdDictionaryId::from_str_radix(zstd_dictionary_id, 16).ok()
+                })
+                .map(ArchiveFormat::TarZstdWithDict)
+                .ok_or_else(|| ParseError::InvalidExtension(extension.to_string())),
         }
     }
 }
@@ -90,12 +139,18 @@ impl fmt::Display for ParseError {
 mod tests {
     use {super::*, std::iter::zip};
     const INVALID_EXTENSION: &str = "zip";
+    const TAR_ZSTD_DICT_EXTENSION: &str = "tar.dict-0123abcd.zst";
+    const TAR_ZSTD_DICT_ID: ZstdDictionaryId = 0x0123abcd;
 
     #[test]
     fn test_extension() {
         assert_eq!(ArchiveFormat::TarBzip2.extension(), TAR_BZIP2_EXTENSION);
         assert_eq!(ArchiveFormat::TarGzip.extension(), TAR_GZIP_EXTENSION);
         assert_eq!(ArchiveFormat::TarZstd.extension(), TAR_ZSTD_EXTENSION);
+        assert_eq!(
+            ArchiveFormat::TarZstdWithDict(TAR_ZSTD_DICT_ID).extension(),
+            TAR_ZSTD_DICT_EXTENSION
+        );
         assert_eq!(ArchiveFormat::TarLz4.extension(), TAR_LZ4_EXTENSION);
         assert_eq!(ArchiveFormat::Tar.extension(), TAR_EXTENSION);
     }
@@ -114,6 +169,10 @@ mod tests {
             ArchiveFormat::try_from(TAR_ZSTD_EXTENSION),
             Ok(ArchiveFormat::TarZstd)
         );
+        assert_eq!(
+            ArchiveFormat::try_from(TAR_ZSTD_DICT_EXTENSION),
+            Ok(ArchiveFormat::TarZstdWithDict(TAR_ZSTD_DICT_ID))
+        );
         assert_eq!(
             ArchiveFormat::try_from(TAR_LZ4_EXTENSION),
             Ok(ArchiveFormat::TarLz4)
@@ -126,6 +185,17 @@ mod tests {
             ArchiveFormat::try_from(INVALID_EXTENSION),
             Err(ParseError::InvalidExtension(INVALID_EXTENSION.to_string()))
         );
+        for invalid_extension in [
+            "tar.dict.zst",
+            "tar.dict-123abcd.zst",
+            "tar.dict-+123abcd.zst",
+            "tar.dict-0123abcg.zst",
+        ] {
+            assert_eq!(
+                ArchiveFormat::try_from(invalid_extension),
+                Err(ParseError::InvalidExtension(invalid_extension.to_string()))
+            );
+        }
     }
 
     #[test]
@@ -142,6 +212,10 @@ mod tests {
             ArchiveFormat::from_str(TAR_ZSTD_EXTENSION),
             Ok(ArchiveFormat::TarZstd)
         );
+        assert_eq!(
+            ArchiveFormat::from_str(TAR_ZSTD_DICT_EXTENSION),
+            Ok(ArchiveFormat::TarZstdWithDict(TAR_ZSTD_DICT_ID))
+        );
         assert_eq!(
             ArchiveFormat::from_str(TAR_LZ4_EXTENSION),
             Ok(ArchiveFormat::TarLz4)
@@ -162,6 +236,7 @@ mod tests {
             Some(ArchiveFormat::TarBzip2),
             Some(ArchiveFormat::TarGzip),
             Some(ArchiveFormat::TarZstd),
+            Some(ArchiveFormat::TarZstdWithDict(ZstdDictionaryId::default())),
             Some(ArchiveFormat::TarLz4),
             Some(ArchiveFormat::Tar),
             Some(ArchiveFormat::Tar),
@@ -173,4 +248,21 @@ mod tests {
 
         assert_eq!(ArchiveFormat::from_cli_arg("bad"), None);
     }
+
+    #[test]
+    fn test_with_zstd_dictionary() {
+        let zstd_dictionary = b"zstd dictionary";
+        let expected_id = zstd_dictionary_id(zstd_dictionary);
+        assert_ne!(expected_id, zstd_dictionary_id(b"another zstd dictionary"));
+        assert_eq!(
+            ArchiveFormat::from_cli_arg("zstd-dict")
+                .unwrap()
+                .with_zstd_dictionary(zstd_dictionary),
+            ArchiveFormat::TarZstdWithDict(expected_id)
+        );
+        assert_eq!(
+            ArchiveFormat::TarZstd.with_zstd_dictionary(zstd_dictionary),
+            ArchiveFormat::TarZstd
+        );
+    }
 }
@@ -57,14 +57,19 @@ pub(crate) struct SnapshotStorageRebuilder {
     processed_slot_count: AtomicUsize,
     /// Tracks the number of collisions in AppendVecId
     num_collisions: AtomicUsize,
+    /// If set, counts the rebuilt storages, so progress can be reported
+    num_storages_rebuilt: Option<Arc<AtomicUsize>>,
 }
 
 impl SnapshotStorageRebuilder {
     /// Synchronously spawns threads to rebuild snapshot storages
+    ///
+    /// If `num_storages_rebuilt` is set, it is incremented as the storages are rebuilt.
     pub(crate) fn rebuild_storage(
         file_receiver: Receiver<PathBuf>,
         num_threads: usize,
         next_append_vec_id: Arc<AtomicAppendVecId>,
+        num_storages_rebuilt: Option<Arc<AtomicUsize>>,
     ) -> Result<RebuiltSnapshotStorage, SnapshotError> {
         let (snapshot_version_path, snapshot_file_path, append_vec_files) =
             Self::get_version_and_snapshot_files(&file_receiver);
@@ -84,6 +89,7 @@ impl SnapshotStorageRebuilder {
             next_append_vec_id,
             snapshot_storage_lengths,
             append_vec_files,
+            num_storages_rebuilt,
         );
 
         Ok(RebuiltSnapshotStorage {
@@ -99,6 +105,7 @@ impl SnapshotStorageRebuilder {
         num_threads: usize,
         next_append_vec_id: Arc<AtomicAppendVecId>,
         snapshot_storage_lengths: HashMap<Slot, HashMap<usize, usize>>,
+        num_storages_rebuilt: Option<Arc<AtomicUsize>>,
     ) -> Self {
         let storage = DashMap::with_capacity(snapshot_storage_lengths.len());
         let storage_paths: DashMap<_, _> = snapshot_storage_lengths
@@ -116,6 +123,7 @@ impl SnapshotStorageRebuilder {
             next_append_vec_id,
             processed_slot_count: AtomicUsize::new(0),
             num_collisions: AtomicUsize::new(0),
+            num_storages_rebuilt,
         }
     }
 
@@ -188,12 +196,14 @@ impl SnapshotStorageRebuilder {
         next_append_vec_id: Arc<AtomicAppendVecId>,
         snapshot_storage_lengths: HashMap<Slot, HashMap<usize, usize>>,
         append_vec_files: Vec<PathBuf>,
+        num_storages_rebuilt: Option<Arc<AtomicUsize>>,
     ) -> AccountStorageMap {
         let rebuilder = Arc::new(SnapshotStorageRebuilder::new(
             file_receiver,
             num_threads,
             next_append_vec_id,
             snapshot_storage_lengths,
+            num_storages_rebuilt,
         ));
 
         let thread_pool = rebuilder.build_thread_pool();
@@ -245,6 +255,10 @@ impl SnapshotStorageRebuilder {
             if slot_complete {
                 self.process_complete_slot(slot)?;
                 self.processed_slot_count.fetch_add(1, Ordering::AcqRel);
+                if let Some(num_storages_rebuilt) = &self.num_storages_rebuilt {
+                    let num_storages = self.snapshot_storage_lengths.get(&slot).unwrap().len();
+                    num_storages_rebuilt.fetch_add(num_storages, Ordering::Relaxed);
+                }
             }
         }
         Ok(())
@@ -21,6 +21,8 @@ pub(crate) struct VerifyAccountsHashInBackground {
     thread: Mutex<Option<JoinHandle<bool>>>,
     /// set when background thread has completed
     background_completed: Arc<AtomicBool>,
+    /// set when verification failed, but was not fatal; verification will then never complete
+    failed: AtomicBool,
 }
 
 impl Default for VerifyAccountsHashInBackground {
@@ -33,6 +35,7 @@ impl Default for VerifyAccountsHashInBackground {
             // no thread to start with
             thread: Mutex::new(None::<JoinHandle<bool>>),
             background_completed: Arc::new(AtomicBool::new(false)),
+            failed: AtomicBool::new(false),
         }
     }
 }
@@ -58,6 +61,17 @@ impl VerifyAccountsHashInBackground {
         self.verified.store(true, Ordering::Release);
     }
 
+    /// notify that verification failed, without panicking
+    /// Verification will never be complete after this.
+    pub(crate) fn verification_failed(&self) {
+        self.failed.store(true, Ordering::Release);
+    }
+
+    /// return true if verification failed, see `verification_failed()`
+    pub(crate) fn is_failed(&self) -> bool {
+        self.failed.load(Ordering::Acquire)
+    }
+
     /// block until bg process is complete
     pub fn wait_for_complete(&self) {
         // just now completing
@@ -73,10 +87,20 @@ impl VerifyAccountsHashInBackground {
         self.verification_complete();
     }
 
+    /// return true if the bg process has been started and has not finished yet
+    /// unlike `check_complete()`, this never joins the bg thread
+    pub(crate) fn is_running(&self) -> bool {
+        self.thread.lock().unwrap().is_some() && !self.background_completed.load(Ordering::Acquire)
+    }
+
     /// return true if bg hash verification is complete
     /// return false if bg hash verification has not completed yet
     /// if hash verification failed, a panic will occur
     pub(crate) fn check_complete(&self) -> bool {
+        if self.is_failed() {
+            // never complete
+            return false;
+        }
         if self.verified.load(Ordering::Acquire) {
             // already completed
             return true;
@@ -101,11 +125,16 @@ pub(crate) mod tests {
     #[test]
     fn test_default() {
         let def = VerifyAccountsHashInBackground::default();
+        assert!(!def.is_running());
         assert!(!def.check_complete());
         assert!(!def.verified.load(Ordering::Acquire));
         assert!(def.thread.lock().unwrap().is_none());
         def.verification_complete();
         assert!(def.check_complete());
+        assert!(!def.is_failed());
+        def.verification_failed();
+        assert!(!def.check_complete());
+        assert!(def.is_failed());
     }
 
     fn start_thread_and_return(
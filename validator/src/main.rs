@@ -42,7 +42,9 @@ use {
     solana_runtime::{
         accounts_db::{
             AccountShrinkThreshold, AccountsDbConfig, FillerAccountsConfig,
+            DEFAULT_ACCOUNTS_SHRINK_MIN_RECLAIM_BYTES,
             DEFAULT_ACCOUNTS_SHRINK_OPTIMIZE_TOTAL_SPACE, DEFAULT_ACCOUNTS_SHRINK_RATIO,
+            PUBKEY_BINS_FOR_CALCULATING_HASHES,
         },
         accounts_index::{
             AccountIndex, AccountSecondaryIndexes, AccountSecondaryIndexesIncludeExclude,
@@ -51,12 +53,14 @@ use {
         hardened_unpack::MAX_GENESIS_ARCHIVE_UNPACKED_SIZE,
         runtime_config::RuntimeConfig,
         snapshot_config::{SnapshotConfig, SnapshotUsage},
+        snapshot_package::PendingPackagePolicy,
         snapshot_utils::{
-            self, ArchiveFormat, SnapshotVersion, DEFAULT_ARCHIVE_COMPRESSION,
-            DEFAULT_FULL_SNAPSHOT_ARCHIVE_INTERVAL_SLOTS,
+            self, ArchiveFormat, DuplicateIncrementalSnapshotArchivePolicy, SnapshotVersion,
+            DEFAULT_ARCHIVE_COMPRESSION, DEFAULT_FULL_SNAPSHOT_ARCHIVE_INTERVAL_SLOTS,
             DEFAULT_INCREMENTAL_SNAPSHOT_ARCHIVE_INTERVAL_SLOTS,
             DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
-            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN, SUPPORTED_ARCHIVE_COMPRESSION,
+            DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN, MIN_UNARCHIVE_THREAD_BUDGET,
+            SUPPORTED_ARCHIVE_COMPRESSION,
         },
     },
     solana_sdk::{
@@ -503,6 +507,8 @@ pub fn main() {
     let default_accounts_shrink_optimize_total_space =
         &DEFAULT_ACCOUNTS_SHRINK_OPTIMIZE_TOTAL_SPACE.to_string();
     let default_accounts_shrink_ratio = &DEFAULT_ACCOUNTS_SHRINK_RATIO.to_string();
+    let default_accounts_shrink_min_reclaim_bytes =
+        &DEFAULT_ACCOUNTS_SHRINK_MIN_RECLAIM_BYTES.to_string();
     let default_tpu_connection_pool_size = &DEFAULT_TPU_CONNECTION_POOL_SIZE.to_string();
     let default_rpc_max_request_body_size = &MAX_REQUEST_BODY_SIZE.to_string();
 
@@ -906,6 +912,16 @@ pub fn main() {
                 .default_value(default_maximum_incremental_snapshot_archives_to_retain)
                 .help("The maximum number of incremental snapshot archives to hold on to when purging older snapshots.")
         )
+        .arg(
+            Arg::with_name("maximum_snapshots_total_bytes")
+                .long("maximum-snapshots-total-bytes")
+                .value_name("BYTES")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .help("The maximum combined size of the full and incremental snapshot archives. \
+                       The oldest snapshot archives are purged until they fit, but the highest \
+                       full snapshot archive is always kept.")
+        )
         .arg(
             Arg::with_name("snapshot_packager_niceness_adj")
                 .long("snapshot-packager-niceness-adjustment")
@@ -916,6 +932,116 @@ pub fn main() {
                 .help("Add this value to niceness of snapshot packager thread. Negative value \
                       increases priority, positive value decreases priority.")
         )
+        .arg(
+            Arg::with_name("serialize_epoch_accounts_hash_and_archiving")
+                .long("serialize-epoch-accounts-hash-and-archiving")
+                .takes_value(false)
+                .help("Do not create snapshot archives while calculating the epoch accounts hash. \
+                      Reduces I/O contention on constrained nodes.")
+        )
+        .arg(
+            Arg::with_name("snapshot_pending_package_policy")
+                .long("snapshot-pending-package-policy")
+                .value_name("POLICY")
+                .takes_value(true)
+                .possible_values(&["overwrite", "drop-new", "block"])
+                .default_value("overwrite")
+                .help("What to do with a new snapshot package when the previous one has not \
+                       been archived yet. \
+                       Possible values are: \
+                       'overwrite': the newest package wins. \
+                       'drop-new': the pending package wins, unless the new package is a \
+                       full snapshot. \
+                       'block': wait for the pending package to be archived; this holds up \
+                       accounts hash verification meanwhile. \
+                       Full snapshot packages are never dropped.")
+        )
+        .arg(
+            Arg::with_name("max_snapshot_age_slots")
+                .long("max-snapshot-age-slots")
+                .value_name("NUMBER")
+                .takes_value(true)
+                .validator(is_parsable::<Slot>)
+                .help("Report a warning when the last full snapshot archived is more than \
+                       NUMBER slots behind the latest snapshot package, which indicates that \
+                       archiving has stalled.")
+        )
+        .arg(
+            Arg::with_name("snapshot_extraction_buffer_bytes")
+                .long("snapshot-extraction-buffer-bytes")
+                .value_name("BYTES")
+                .takes_value(true)
+                .validator(is_parsable::<std::num::NonZeroUsize>)
+                .help("Bound the decompressed data buffered in memory while extracting the \
+                       snapshot archives at startup to BYTES. \
+                       Smaller values reduce peak memory usage, but slow down extraction. \
+                       [default: about 2 GB]")
+        )
+        .arg(
+            Arg::with_name("snapshot_unarchive_thread_budget")
+                .long("snapshot-unarchive-thread-budget")
+                .value_name("NUMBER")
+                .takes_value(true)
+                .validator(|value| {
+                    value
+                        .parse::<usize>()
+                        .map_err(|err| err.to_string())
+                        .and_then(|threads| {
+                            if threads < MIN_UNARCHIVE_THREAD_BUDGET {
+                                Err(format!(
+                                    "must be at least {}",
+                                    MIN_UNARCHIVE_THREAD_BUDGET
+                                ))
+                            } else {
+                                Ok(())
+                            }
+                        })
+                })
+                .help("Use at most NUMBER threads to unarchive the full and incremental \
+                       snapshot archives at startup. \
+                       [default: about the number of physical cores]")
+        )
+        .arg(
+            Arg::with_name("snapshot_verification_thread_budget")
+                .long("snapshot-verification-thread-budget")
+                .value_name("NUMBER")
+                .takes_value(true)
+                .validator(is_parsable::<std::num::NonZeroUsize>)
+                .help("Use at most NUMBER threads to verify the accounts hashes of the full \
+                       and incremental snapshots at startup, in total. \
+                       [default: the background thread pool]")
+        )
+        .arg(
+            Arg::with_name("snapshot_untar_across_account_paths")
+                .long("snapshot-untar-across-account-paths")
+                .takes_value(false)
+                .help("Untar the snapshot archives at startup with at least one thread per \
+                       accounts path, each writing to one path. \
+                       The snapshot manifests and status caches are verified first. \
+                       Ignored if --snapshot-unarchive-thread-budget is too small for a \
+                       thread per accounts path.")
+        )
+        .arg(
+            Arg::with_name("duplicate_incremental_snapshot_archive_policy")
+                .long("duplicate-incremental-snapshot-archive-policy")
+                .value_name("POLICY")
+                .takes_value(true)
+                .possible_values(&["pick-first-path", "fail"])
+                .default_value("pick-first-path")
+                .help("What to do at startup when there are several incremental snapshot \
+                       archives with the same base slot and slot. \
+                       Possible values are: \
+                       'pick-first-path': load the archive with the lowest path. \
+                       'fail': refuse to load.")
+        )
+        .arg(
+            Arg::with_name("snapshot_zstd_dictionary")
+                .long("snapshot-zstd-dictionary")
+                .value_name("PATH")
+                .takes_value(true)
+                .help("Path to the zstd dictionary used to create and load snapshot archives \
+                       with the zstd-dict archive format")
+        )
         .arg(
             Arg::with_name("minimal_snapshot_download_speed")
                 .long("minimal-snapshot-download-speed")
@@ -1634,6 +1760,12 @@ pub fn main() {
                 .value_name("KEY")
                 .help("When account indexes are enabled, only include specific keys in the index. This overrides --account-index-exclude-key."),
         )
+        .arg(
+            Arg::with_name("account_indexes_lazy")
+                .long("account-index-lazy")
+                .requires("account_indexes")
+                .help("Build the accounts indexes on their first use, instead of at startup"),
+        )
         .arg(
             Arg::with_name("no_accounts_db_caching")
                 .long("no-accounts-db-caching")
@@ -1710,6 +1842,28 @@ pub fn main() {
                 .takes_value(true)
                 .help("Number of passes to calculate the hash of all accounts"),
         )
+        .arg(
+            Arg::with_name("epoch_accounts_hash_verification_shards")
+                .long("epoch-accounts-hash-verification-shards")
+                .value_name("SHARDS")
+                .validator(|s| {
+                    is_pow2(&s)?;
+                    is_within_range(s, 1, PUBKEY_BINS_FOR_CALCULATING_HASHES)
+                })
+                .takes_value(true)
+                .help("Verify the epoch accounts hash incrementally, one of this many shards \
+                       of the keyspace per accounts hash calculation within the epoch accounts \
+                       hash calculation window [maximum: 65536]"),
+        )
+        .arg(
+            Arg::with_name("accounts_db_generate_index_threads")
+                .long("accounts-db-generate-index-threads")
+                .value_name("NUMBER")
+                .validator(is_parsable::<usize>)
+                .takes_value(true)
+                .help("Number of threads used to generate the accounts index when loading \
+                       from a snapshot [default: all cores with the disk index, otherwise 8]"),
+        )
         .arg(
             Arg::with_name("accounts_index_path")
                 .long("accounts-index-path")
@@ -1741,6 +1895,16 @@ pub fn main() {
                 .help("Enables testing of hash calculation using stores in \
                       AccountsHashVerifier. This has a computational cost."),
         )
+        .arg(
+            Arg::with_name("accounts_hash_checkpoint_interval_slots")
+                .long("accounts-hash-checkpoint-interval-slots")
+                .value_name("NUMBER")
+                .takes_value(true)
+                .validator(is_parsable::<Slot>)
+                .help("Calculate an accounts hash checkpoint every NUMBER rooted slots, \
+                       independent of the snapshot intervals.  Checkpoints are logged and \
+                       reported to metrics to help detect divergence."),
+        )
         .arg(
             Arg::with_name("accounts_shrink_optimize_total_space")
                 .long("accounts-shrink-optimize-total-space")
@@ -1764,6 +1928,16 @@ pub fn main() {
                        it becomes a candidate for shrinking. The value must between 0. and 1.0 \
                        inclusive."),
         )
+        .arg(
+            Arg::with_name("accounts_shrink_min_reclaim_bytes")
+                .long("accounts-shrink-min-reclaim-bytes")
+                .takes_value(true)
+                .value_name("BYTES")
+                .validator(is_parsable::<u64>)
+                .default_value(default_accounts_shrink_min_reclaim_bytes)
+                .help("When accounts-shrink-optimize-total-space is true, do not shrink \
+                       accounts storages that would reclaim fewer than this many bytes."),
+        )
         .arg(
             Arg::with_name("no_duplicate_instance_check")
                 .long("no-duplicate-instance-check")
@@ -2314,6 +2488,8 @@ pub fn main() {
         value_t!(matches, "rocksdb_max_compaction_jitter", u64).ok();
     let tpu_coalesce_ms =
         value_t!(matches, "tpu_coalesce_ms", u64).unwrap_or(DEFAULT_TPU_COALESCE_MS);
+    let accounts_hash_checkpoint_interval_slots =
+        value_t!(matches, "accounts_hash_checkpoint_interval_slots", Slot).ok();
     let wal_recovery_mode = matches
         .value_of("wal_recovery_mode")
         .map(BlockstoreRecoveryMode::from);
@@ -2391,7 +2567,10 @@ pub fn main() {
     }
 
     let accounts_shrink_ratio = if accounts_shrink_optimize_total_space {
-        AccountShrinkThreshold::TotalSpace { shrink_ratio }
+        AccountShrinkThreshold::TotalSpace {
+            shrink_ratio,
+            min_reclaim_bytes: value_t_or_exit!(matches, "accounts_shrink_min_reclaim_bytes", u64),
+        }
     } else {
         AccountShrinkThreshold::IndividualStore { shrink_ratio }
     };
@@ -2521,6 +2700,8 @@ pub fn main() {
     if let Some(passes) = value_t!(matches, "accounts_hash_num_passes", usize).ok() {
         accounts_db_config.hash_calc_num_passes = Some(passes);
     }
+    accounts_db_config.generate_index_threads =
+        value_t!(matches, "accounts_db_generate_index_threads", usize).ok();
     let accounts_db_config = Some(accounts_db_config);
 
     let geyser_plugin_config_files = if matches.is_present("geyser_plugin_config") {
@@ -2704,6 +2885,7 @@ pub fn main() {
         accounts_db_test_hash_calculation: matches.is_present("accounts_db_test_hash_calculation"),
         accounts_db_config,
         accounts_db_skip_shrink: matches.is_present("accounts_db_skip_shrink"),
+        accounts_hash_checkpoint_interval_slots,
         tpu_coalesce_ms,
         no_wait_for_vote_to_start_leader: matches.is_present("no_wait_for_vote_to_start_leader"),
         accounts_shrink_ratio,
@@ -2833,6 +3015,24 @@ pub fn main() {
             .unwrap_or_else(|| panic!("Archive format not recognized: {}", archive_format_str))
     };
 
+    let zstd_dictionary = matches.value_of("snapshot_zstd_dictionary").map(|path| {
+        let zstd_dictionary = fs::read(path).unwrap_or_else(|err| {
+            eprintln!("Failed to read zstd dictionary {}: {}", path, err);
+            exit(1);
+        });
+        Arc::<[u8]>::from(zstd_dictionary)
+    });
+    let archive_format = match (archive_format, zstd_dictionary.as_deref()) {
+        (ArchiveFormat::TarZstdWithDict(_), None) => {
+            eprintln!("The zstd-dict snapshot archive format requires --snapshot-zstd-dictionary");
+            exit(1);
+        }
+        (archive_format, Some(zstd_dictionary)) => {
+            archive_format.with_zstd_dictionary(zstd_dictionary)
+        }
+        (archive_format, None) => archive_format,
+    };
+
     let snapshot_version =
         matches
             .value_of("snapshot_version")
@@ -2876,6 +3076,29 @@ pub fn main() {
         maximum_incremental_snapshot_archives_to_retain,
         accounts_hash_debug_verify: validator_config.accounts_db_test_hash_calculation,
         packager_thread_niceness_adj: snapshot_packager_niceness_adj,
+        epoch_accounts_hash_archive_coordinator: matches
+            .is_present("serialize_epoch_accounts_hash_and_archiving")
+            .then(Arc::default),
+        pending_package_policy: match matches.value_of("snapshot_pending_package_policy") {
+            Some("overwrite") => PendingPackagePolicy::Overwrite,
+            Some("drop-new") => PendingPackagePolicy::DropNew,
+            Some("block") => PendingPackagePolicy::Block,
+            _ => unreachable!(),
+        },
+        max_snapshot_age_slots: value_t!(matches, "max_snapshot_age_slots", Slot).ok(),
+        extraction_buffer_bytes: value_t!(matches, "snapshot_extraction_buffer_bytes", usize).ok(),
+        maximum_snapshots_total_bytes: value_t!(matches, "maximum_snapshots_total_bytes", u64).ok(),
+        zstd_dictionary,
+        unarchive_thread_budget: value_t!(matches, "snapshot_unarchive_thread_budget", usize).ok(),
+        verification_thread_budget: value_t!(matches, "snapshot_verification_thread_budget", usize)
+            .ok(),
+        untar_across_account_paths: matches.is_present("snapshot_untar_across_account_paths"),
+        duplicate_incremental_snapshot_archive_policy: match matches
+            .value_of("duplicate_incremental_snapshot_archive_policy")
+        {
+            Some("fail") => DuplicateIncrementalSnapshotArchivePolicy::Fail,
+            _ => DuplicateIncrementalSnapshotArchivePolicy::PickFirstPath,
+        },
     });
 
     validator_config.accounts_hash_interval_slots =
@@ -2960,6 +3183,8 @@ pub fn main() {
     if matches.is_present("halt_on_known_validators_accounts_hash_mismatch") {
         validator_config.halt_on_known_validators_accounts_hash_mismatch = true;
     }
+    validator_config.epoch_accounts_hash_verification_shards =
+        value_t!(matches, "epoch_accounts_hash_verification_shards", usize).ok();
 
     let public_rpc_addr = matches.value_of("public_rpc_addr").map(|addr| {
         solana_net_utils::parse_host_port(addr).unwrap_or_else(|e| {
@@ -3217,6 +3442,7 @@ fn process_account_indexes(matches: &ArgMatches) -> AccountSecondaryIndexes {
     AccountSecondaryIndexes {
         keys,
         indexes: account_indexes,
+        lazy: matches.is_present("account_indexes_lazy"),
     }
 }
 